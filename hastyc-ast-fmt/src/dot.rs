@@ -0,0 +1,430 @@
+use hastyc_common::{identifiers::{ASTNodeID, Ident}, path::Path};
+use hastyc_parser::parser::{BinOpKind, Block, DataVariant, Expr, ExprKind, FnRetTy, Function, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBindingKind, Lit, Package, Pat, PatKind, RestExpr, Stmt, StmtKind, Ty, TyKind, UnOpKind};
+
+/// Renders parts of the syntax tree as graphs in the DOT language of
+/// Graphviz, like:
+///
+/// ```text
+/// digraph items {
+///     n0 [label="package"];
+///     n0 -> n3;
+///     n3 [label="fn main"];
+/// }
+/// ```
+///
+/// Nodes are named after their ids, so they can be matched with other
+/// dumps of the same run. Nodes without ids, like fields of struct
+/// literals, get names of their own.
+pub struct DotPrinter<'pkg> {
+    result: String,
+    pkg: &'pkg Package,
+    /// Counter for names of nodes without ids.
+    anonymous: usize
+}
+
+impl<'pkg> DotPrinter<'pkg> {
+    fn new(pkg: &'pkg Package, name: &str) -> Self {
+        let mut printer = Self {
+            result: String::new(),
+            pkg,
+            anonymous: 0
+        };
+        printer.result.push_str(&format!("digraph \"{}\" {{\n", escape(name)));
+        printer.result.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        printer
+    }
+
+    fn finish(mut self) -> String {
+        self.result.push_str("}\n");
+        self.result
+    }
+
+    /// Graph of the items of the package, with modules pointing to their
+    /// items, and structs and enums to their fields and variants.
+    pub fn item_graph(package: &'pkg Package) -> String {
+        let mut printer = Self::new(package, "items");
+        let root = printer.node(package.id, "package");
+        printer.item_stream(&root, &package.items);
+        printer.finish()
+    }
+
+    /// Graph of the function with the expression tree of its body. Edges
+    /// are labeled with what the child is to its parent, like `lhs` of
+    /// a binary expression.
+    pub fn expr_graph(package: &'pkg Package, item: &Item, function: &Function) -> String {
+        let name = item.ident.text(&package.symbol_storage);
+        let mut printer = Self::new(package, name);
+        let root = printer.node(item.id, &format!("fn {}", name));
+        for (idx, input) in function.signature.inputs.iter().enumerate() {
+            let param = printer.pat(&input.pat);
+            printer.edge(&root, &param, &format!("param {}", idx));
+            if !matches!(input.ty.kind, TyKind::SelfTy) {
+                let ty = printer.ty(&input.ty);
+                printer.edge(&param, &ty, "ty");
+            }
+        }
+        if let FnRetTy::Ty(ref ty) = function.signature.output {
+            let ty = printer.ty(ty);
+            printer.edge(&root, &ty, "output");
+        }
+        if let Some(ref body) = function.body {
+            let body = printer.block(body);
+            printer.edge(&root, &body, "body");
+        }
+        printer.finish()
+    }
+
+    fn ident(&self, ident: &Ident) -> &'pkg str {
+        ident.text(&self.pkg.symbol_storage)
+    }
+
+    fn path(&self, path: &Path) -> String {
+        path.segments.iter()
+            .map(|segment| self.ident(&segment.ident))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    /// Add the node and return its name.
+    fn node(&mut self, id: ASTNodeID, label: &str) -> String {
+        let name = format!("n{}", id.0);
+        self.result.push_str(&format!("    {} [label=\"{}\"];\n", name, escape(label)));
+        name
+    }
+
+    fn anonymous_node(&mut self, label: &str) -> String {
+        self.anonymous += 1;
+        let name = format!("a{}", self.anonymous);
+        self.result.push_str(&format!("    {} [label=\"{}\"];\n", name, escape(label)));
+        name
+    }
+
+    fn edge(&mut self, from: &str, to: &str, label: &str) {
+        if label.is_empty() {
+            self.result.push_str(&format!("    {} -> {};\n", from, to));
+        } else {
+            self.result.push_str(&format!("    {} -> {} [label=\"{}\"];\n", from, to, escape(label)));
+        }
+    }
+
+    fn item_stream(&mut self, parent: &str, items: &ItemStream) {
+        for item in items.items.iter() {
+            let node = self.item(item);
+            self.edge(parent, &node, "");
+        }
+    }
+
+    fn item(&mut self, item: &Item) -> String {
+        let name = self.ident(&item.ident);
+        match item.kind {
+            ItemKind::Module(ref items) => {
+                let node = self.node(item.id, &format!("module {}", name));
+                self.item_stream(&node, items);
+                node
+            },
+            ItemKind::Import(_, ref tree) => {
+                let tree = self.import_tree(tree);
+                self.node(item.id, &format!("import {}", tree))
+            },
+            ItemKind::Fn(_) => self.node(item.id, &format!("fn {}", name)),
+            ItemKind::Struct(ref data) => {
+                let node = self.node(item.id, &format!("struct {}", name));
+                self.data_variant(&node, data);
+                node
+            },
+            ItemKind::Enum(ref def) => {
+                let node = self.node(item.id, &format!("enum {}", name));
+                for variant in def.variants.iter() {
+                    let child = self.node(variant.id, &format!("variant {}", self.ident(&variant.ident)));
+                    self.edge(&node, &child, "");
+                    self.data_variant(&child, &variant.data);
+                }
+                node
+            }
+        }
+    }
+
+    fn import_tree(&self, tree: &ImportTree) -> String {
+        let mut text = self.path(&tree.prefix);
+        if !text.is_empty() {
+            text.push_str("::");
+        }
+        match tree.kind {
+            ImportTreeKind::Simple(ref name, ref rename) => {
+                text.push_str(self.ident(name));
+                if let Some(rename) = rename {
+                    text.push_str(&format!(" as {}", self.ident(rename)));
+                }
+            },
+            ImportTreeKind::SelfImport(ref rename) => {
+                text.push_str("self");
+                if let Some(rename) = rename {
+                    text.push_str(&format!(" as {}", self.ident(rename)));
+                }
+            },
+            ImportTreeKind::Glob => text.push('*'),
+            ImportTreeKind::Nested(ref trees) => {
+                let trees = trees.iter().map(|(tree, _)| self.import_tree(tree)).collect::<Vec<_>>();
+                text.push_str(&format!("{{{}}}", trees.join(", ")));
+            }
+        }
+        text
+    }
+
+    fn data_variant(&mut self, parent: &str, data: &DataVariant) {
+        let (DataVariant::Struct { ref fields } | DataVariant::Tuple { ref fields }) = data else { return };
+        for (idx, field) in fields.iter().enumerate() {
+            let name = match field.ident {
+                Some(ref ident) => self.ident(ident).to_string(),
+                None => idx.to_string()
+            };
+            let ty = self.ty_text(&field.ty);
+            let node = self.node(field.id, &format!("field {}: {}", name, ty));
+            self.edge(parent, &node, "");
+        }
+    }
+
+    fn ty_text(&self, ty: &Ty) -> String {
+        match ty.kind {
+            TyKind::SelfTy => "self".to_string(),
+            TyKind::Path(ref path) => self.path(path),
+            TyKind::Void => "()".to_string(),
+            TyKind::Never => "!".to_string(),
+            TyKind::Infer => "_".to_string()
+        }
+    }
+
+    fn ty(&mut self, ty: &Ty) -> String {
+        let text = self.ty_text(ty);
+        self.node(ty.id, &format!("ty {}", text))
+    }
+
+    fn pat(&mut self, pat: &Pat) -> String {
+        let label = match pat.kind {
+            PatKind::SelfPat => "pat self".to_string(),
+            PatKind::Ident(ref ident) => format!("pat {}", self.ident(ident))
+        };
+        self.node(pat.id, &label)
+    }
+
+    fn block(&mut self, block: &Block) -> String {
+        let node = self.node(block.id, "block");
+        for (idx, stmt) in block.stmts.stmts.iter().enumerate() {
+            let child = self.stmt(stmt);
+            self.edge(&node, &child, &idx.to_string());
+        }
+        node
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> String {
+        match stmt.kind {
+            StmtKind::LetBinding(ref binding) => {
+                let node = self.node(stmt.id, "let");
+                let pat = self.pat(&binding.pat);
+                self.edge(&node, &pat, "pat");
+                if let Some(ref ty) = binding.ty {
+                    if !matches!(ty.kind, TyKind::Infer) {
+                        let ty = self.ty(ty);
+                        self.edge(&node, &ty, "ty");
+                    }
+                }
+                if let LetBindingKind::Init(ref init) = binding.kind {
+                    let init = self.expr(init);
+                    self.edge(&node, &init, "init");
+                }
+                node
+            },
+            // Nested items are drawn by the item graph
+            StmtKind::Item(ref item) => {
+                let kind = item.kind.name_of_type().to_lowercase();
+                self.node(stmt.id, &format!("{} {}", kind, self.ident(&item.ident)))
+            },
+            StmtKind::Expr(ref expr) => {
+                let node = self.node(stmt.id, "expr;");
+                let expr = self.expr(expr);
+                self.edge(&node, &expr, "");
+                node
+            },
+            StmtKind::ExprNS(ref expr) => self.expr(expr)
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr.kind {
+            ExprKind::Path(ref path) => {
+                let path = self.path(path);
+                self.node(expr.id, &format!("path {}", path))
+            },
+            ExprKind::Literal(ref lit) => {
+                let lit = self.lit(lit);
+                self.node(expr.id, &format!("lit {}", lit))
+            },
+            ExprKind::Field(ref receiver, ref field, _) => {
+                let node = self.node(expr.id, &format!("field .{}", self.ident(field)));
+                let receiver = self.expr(receiver);
+                self.edge(&node, &receiver, "receiver");
+                node
+            },
+            ExprKind::Assign(ref target, ref value, _) => {
+                let node = self.node(expr.id, "assign =");
+                let target = self.expr(target);
+                self.edge(&node, &target, "target");
+                let value = self.expr(value);
+                self.edge(&node, &value, "value");
+                node
+            },
+            ExprKind::Unary(ref op, ref operand) => {
+                let op = match op.kind {
+                    UnOpKind::Neg => "-",
+                    UnOpKind::Not => "!"
+                };
+                let node = self.node(expr.id, &format!("unary {}", op));
+                let operand = self.expr(operand);
+                self.edge(&node, &operand, "");
+                node
+            },
+            ExprKind::Binary(ref op, ref lhs, ref rhs) => {
+                let node = self.node(expr.id, &format!("binary {}", binary_operator(&op.kind)));
+                let lhs = self.expr(lhs);
+                self.edge(&node, &lhs, "lhs");
+                let rhs = self.expr(rhs);
+                self.edge(&node, &rhs, "rhs");
+                node
+            },
+            ExprKind::Call(ref target, ref args, _) => {
+                let node = self.node(expr.id, "call");
+                let target = self.expr(target);
+                self.edge(&node, &target, "callee");
+                for (idx, arg) in args.iter().enumerate() {
+                    let arg = self.expr(arg);
+                    self.edge(&node, &arg, &format!("arg {}", idx));
+                }
+                node
+            },
+            ExprKind::If(ref condition, ref block, ref else_expr) => {
+                let node = self.node(expr.id, "if");
+                let condition = self.expr(condition);
+                self.edge(&node, &condition, "cond");
+                let block = self.block(block);
+                self.edge(&node, &block, "then");
+                if let Some(else_expr) = else_expr {
+                    let else_expr = self.expr(else_expr);
+                    self.edge(&node, &else_expr, "else");
+                }
+                node
+            },
+            ExprKind::Block(ref block) => self.block(block),
+            ExprKind::Loop(ref block) => {
+                let node = self.node(expr.id, "loop");
+                let block = self.block(block);
+                self.edge(&node, &block, "body");
+                node
+            },
+            ExprKind::While(ref condition, ref block) => {
+                let node = self.node(expr.id, "while");
+                let condition = self.expr(condition);
+                self.edge(&node, &condition, "cond");
+                let block = self.block(block);
+                self.edge(&node, &block, "body");
+                node
+            },
+            ExprKind::For(ref pat, ref iter, ref block) => {
+                let node = self.node(expr.id, "for");
+                let pat = self.pat(pat);
+                self.edge(&node, &pat, "pat");
+                let iter = self.expr(iter);
+                self.edge(&node, &iter, "iter");
+                let block = self.block(block);
+                self.edge(&node, &block, "body");
+                node
+            },
+            ExprKind::Break(ref value) => {
+                let node = self.node(expr.id, "break");
+                if let Some(value) = value {
+                    let value = self.expr(value);
+                    self.edge(&node, &value, "value");
+                }
+                node
+            },
+            ExprKind::Continue => self.node(expr.id, "continue"),
+            ExprKind::Err => self.node(expr.id, "<error>"),
+            ExprKind::StructLit(ref lit) => {
+                let path = self.path(&lit.path);
+                let node = self.node(expr.id, &format!("struct {}", path));
+                for field in lit.fields.iter() {
+                    let value = self.expr(&field.expr);
+                    self.edge(&node, &value, self.ident(&field.ident));
+                }
+                match lit.rest {
+                    RestExpr::None => {},
+                    RestExpr::Rest(_) => {
+                        let rest = self.anonymous_node("..");
+                        self.edge(&node, &rest, "rest");
+                    },
+                    RestExpr::Valued(ref rest) => {
+                        let rest = self.expr(rest);
+                        self.edge(&node, &rest, "rest");
+                    }
+                }
+                node
+            }
+        }
+    }
+
+    fn lit(&self, lit: &Lit) -> String {
+        let symbols = &self.pkg.symbol_storage;
+        let mut text = symbols.text_of(lit.symbol).unwrap().to_string();
+        if let Some(suffix) = lit.suffix {
+            text.push_str(symbols.text_of(suffix).unwrap());
+        }
+        text
+    }
+}
+
+/// Function item at the path like `math::add`, looked up through
+/// modules starting from the items.
+pub fn find_fn<'a>(items: &'a ItemStream, path: &str, pkg: &Package) -> Option<(&'a Item, &'a Function)> {
+    let (module, name) = match path.rsplit_once("::") {
+        Some((module, name)) => (Some(module), name),
+        None => (None, path)
+    };
+    let mut items = items;
+    for segment in module.into_iter().flat_map(|module| module.split("::")) {
+        items = items.items.iter().find_map(|item| match item.kind {
+            ItemKind::Module(ref module) if item.ident.text(&pkg.symbol_storage) == segment => Some(module),
+            _ => None
+        })?;
+    }
+    items.items.iter().find_map(|item| match item.kind {
+        ItemKind::Fn(ref function) if item.ident.text(&pkg.symbol_storage) == name => Some((item, function)),
+        _ => None
+    })
+}
+
+fn binary_operator(op: &BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "+",
+        BinOpKind::Sub => "-",
+        BinOpKind::Mul => "*",
+        BinOpKind::Div => "/",
+        BinOpKind::Rem => "%",
+        BinOpKind::And => "&&",
+        BinOpKind::Or => "||",
+        BinOpKind::BitAnd => "&",
+        BinOpKind::BitXor => "^",
+        BinOpKind::BitOr => "|",
+        BinOpKind::Shl => "<<",
+        BinOpKind::Shr => ">>",
+        BinOpKind::Eq => "==",
+        BinOpKind::Lt => "<",
+        BinOpKind::Le => "<=",
+        BinOpKind::Ne => "!=",
+        BinOpKind::Ge => ">=",
+        BinOpKind::Gt => ">"
+    }
+}
+
+/// Escape text for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,5 +1,11 @@
+mod dot;
+mod source;
+
 use hastyc_common::{identifiers::{Ident, Symbol}, path::Path};
-use hastyc_parser::parser::{Package, Item, ItemKind, ItemStream, ImportTree, ImportTreeKind, Attributes, AttributeKind, FnSignature, Pat, PatKind, Ty, TyKind, FnRetTy, Block, Stmt, StmtKind, LetBindingKind, Expr, ExprKind, Lit, LitKind, DataVariant, FieldDef, Visibility, EnumDef};
+use hastyc_parser::parser::{Package, Item, ItemKind, ItemStream, ImportTree, ImportTreeKind, Attributes, AttributeKind, FnSignature, Extern, Pat, PatKind, Ty, TyKind, FnRetTy, Block, Stmt, StmtKind, LetBindingKind, Expr, ExprKind, Lit, LitKind, DataVariant, FieldDef, Visibility, EnumDef};
+
+pub use dot::{find_fn, DotPrinter};
+pub use source::{format_file, FormatError, SourceFormatter};
 
 pub struct PackageASTPrettyPrinter<'pkg> {
     result: String,
@@ -36,10 +42,10 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
     }
 
     fn ident(&self, ident: &Ident) -> &str {
-        self.pkg.symbol_storage.text_of(ident.symbol).unwrap()
+        ident.text(&self.pkg.symbol_storage)
     }
     fn symbol(&self, symbol: &Symbol) -> &str {
-        self.pkg.symbol_storage.text_of(symbol.clone()).unwrap()
+        self.pkg.symbol_storage.text_of(*symbol).unwrap()
     }
 
     pub fn pretty_print(package: &'pkg Package) -> String {
@@ -66,7 +72,11 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
         for attr in attributes.attributes.iter() {
             match attr.kind {
                 AttributeKind::FlagAttribute => 
-                    self.push_line(&format!("#[{}]", self.ident(&attr.ident)))
+                    self.push_line(&format!("#[{}]", self.ident(&attr.ident))),
+                AttributeKind::ListAttribute(ref args) => {
+                    let args = args.iter().map(|arg| self.ident(arg)).collect::<Vec<_>>();
+                    self.push_line(&format!("#[{}({})]", self.ident(&attr.ident), args.join(", ")))
+                }
             }
         }
     }
@@ -90,7 +100,10 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
                 self.push_line(&format!("Function {}:", self.ident(&item.ident)));
                 self.pushi();
                 self.function_signature(&function.signature);
-                self.block(function.body.as_ref().unwrap());
+                match function.body {
+                    Some(ref body) => self.block(body),
+                    None => self.push_line("<no body>")
+                }
                 self.popi();
             },
             ItemKind::Struct(ref data) => {
@@ -166,8 +179,8 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
         let mut string = String::new();
 
         string.push_str(&self.vis(&field.vis));
-        if field.ident.is_some() {
-            string.push_str(self.ident(field.ident.as_ref().unwrap()));
+        if let Some(ref ident) = field.ident {
+            string.push_str(self.ident(ident));
             string.push_str(": ");
             string.push_str(&self.ty(&field.ty))
         } else {
@@ -181,8 +194,12 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
         self.push_line(&format!("prefix: {}", self.path(&tree.prefix)));
         match tree.kind {
             ImportTreeKind::Glob => self.push_line("Import: glob"),
-            ImportTreeKind::SelfImport => self.push_line("Import: self"),
-            ImportTreeKind::Simple(ref i) => self.push_line(&format!("Import: {}", self.ident(i))),
+            ImportTreeKind::SelfImport(None) => self.push_line("Import: self"),
+            ImportTreeKind::SelfImport(Some(ref rename)) =>
+                self.push_line(&format!("Import: self as {}", self.ident(rename))),
+            ImportTreeKind::Simple(ref i, None) => self.push_line(&format!("Import: {}", self.ident(i))),
+            ImportTreeKind::Simple(ref i, Some(ref rename)) =>
+                self.push_line(&format!("Import: {} as {}", self.ident(i), self.ident(rename))),
             ImportTreeKind::Nested(ref subtries) => {
                 self.push_line("Nested: [");
                 self.pushi();
@@ -211,6 +228,11 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
 
         if sig.is_const { string.push_str("const ")}
         if sig.is_async { string.push_str("async ")}
+        match sig.ext {
+            Extern::None => {},
+            Extern::Implicit(_) => string.push_str("extern "),
+            Extern::Explicit(ref abi, _) => string.push_str(&format!("extern \"{}\" ", self.symbol(abi)))
+        }
         
         string.push_str("fn(");
 
@@ -221,7 +243,7 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
 
             string.push_str(", ");
         }
-        if sig.inputs.len() > 0 {
+        if !sig.inputs.is_empty() {
             string.pop();
             string.pop();
         }
@@ -259,7 +281,7 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
         self.push_line("{");
         self.pushi();
 
-        for ref stmt in block.stmts.stmts.iter() {
+        for stmt in block.stmts.stmts.iter() {
             self.stmt(stmt);
         }
 
@@ -285,7 +307,7 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
                         "let {}: {} = {};",
                         self.pat(&let_binding.pat),
                         self.ty(let_binding.ty.as_ref().unwrap()),
-                        self.expr(&init)
+                        self.expr(init)
                     ))
                 }
             },
@@ -301,11 +323,11 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
         match expr.kind {
             ExprKind::Path(ref path) => format!("Path({})", self.path(path)),
             ExprKind::Literal(ref lit) => self.lit(lit),
-            ExprKind::Field(ref expr, ref field) => format!("{}.{}", self.expr(expr), self.ident(field)),
-            ExprKind::Unary(ref unop, ref expr) => format!("Unary<{:?}>({})", unop, self.expr(expr)),
+            ExprKind::Field(ref expr, ref field, _) => format!("{}.{}", self.expr(expr), self.ident(field)),
+            ExprKind::Unary(ref unop, ref expr) => format!("Unary<{:?}>({})", unop.kind, self.expr(expr)),
             ExprKind::Binary(ref binop, ref expr1, ref expr2) =>
                 format!("Binary<{:?}>({}; {})", binop.kind, self.expr(expr1), self.expr(expr2)),
-            ExprKind::Call(ref target, ref args) =>
+            ExprKind::Call(ref target, ref args, _) =>
                 format!("Call<{}>({})", self.expr(target), args.iter().map(|a| self.expr(a)).collect::<Vec<String>>().join(", ")),
             ExprKind::Block(ref block) => { let mut sf = self.subformatter(); sf.block(block); format!("\n{}\n", sf.into_text()) },
             ExprKind::If(ref condition, ref block, ref else_expr) =>
@@ -326,12 +348,13 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
             ExprKind::Loop(ref block) => format!("loop \n{}", self.block_str(block)),
             ExprKind::While(ref condition, ref block) => 
                 format!("while ({})\n{}\n", self.expr(condition), self.block_str(block)),
-            ExprKind::Assign(ref target, ref value) =>
+            ExprKind::Assign(ref target, ref value, _) =>
                 format!("Assign({} = {})", self.expr(target), self.expr(value)),
             ExprKind::For(ref pat, ref expr, ref block) =>
                 format!("For ({} in {})\n{}\n", self.pat(pat), self.expr(expr), self.block_str(block)),
             ExprKind::Continue => "Continue".to_string(),
-            ExprKind::Break(ref bvalue) => format!("Break({:?})", bvalue.as_ref().map(|v| self.expr(&v))),
+            ExprKind::Err => "Err".to_string(),
+            ExprKind::Break(ref bvalue) => format!("Break({:?})", bvalue.as_ref().map(|v| self.expr(v))),
             ExprKind::StructLit(ref lit) => format!(
                 "StructLit({}\n{{{}\n}})",
                 self.path(&lit.path),
@@ -352,6 +375,9 @@ impl<'pkg> PackageASTPrettyPrinter<'pkg> {
         });
         string.push_str(">(");
         string.push_str(self.symbol(&lit.symbol));
+        if let Some(ref suffix) = lit.suffix {
+            string.push_str(self.symbol(suffix));
+        }
         string.push(')');
         string
     }
@@ -0,0 +1,835 @@
+use std::fmt;
+
+use hastyc_common::{identifiers::Ident, path::Path, session::{Options, Session}, source::SourceFile, span::Span};
+use hastyc_parser::{lexer::{Lexer, Token, TokenKind, TokenStream}, parser::{Attribute, Attributes, AttributeKind, BinOpKind, Block, DataVariant, EnumDef, Expr, ExprKind, Extern, FieldDef, FnRetTy, Function, ImportKind, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, Lit, LitKind, Package, Pat, PatKind, RestExpr, Stmt, StmtKind, StructLiteral, Ty, TyKind, UnOpKind, Visibility, Parser}};
+
+use crate::PackageASTPrettyPrinter;
+
+/// Lines longer than this are split where the formatter knows how to,
+/// which is only struct literals for now.
+const MAX_WIDTH: usize = 100;
+
+/// Precedence of expressions which can't be an operand without
+/// parentheses, like assignments or `if`.
+const PREC_OPEN: u8 = 0;
+const PREC_UNARY: u8 = 7;
+const PREC_CALL: u8 = 8;
+/// Paths, literals, field accesses and struct literals.
+const PREC_POSTFIX: u8 = 9;
+
+/// Why the file couldn't be formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    /// File has syntax errors, which were reported to the session.
+    Syntax,
+    /// Formatted text doesn't parse into the same code, which is a bug
+    /// of the formatter. The file is left as it was then.
+    ChangedCode,
+    /// Formatting the formatted text changes it again.
+    Unstable
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Syntax => "file has syntax errors",
+            Self::ChangedCode => "formatted code doesn't parse into the same syntax tree",
+            Self::Unstable => "formatting the formatted code changes it again"
+        })
+    }
+}
+
+/// Parse the file on its own and format it. Formatted text is parsed
+/// again and checked to have the same tokens, apart from parentheses and
+/// trailing commas, and the same syntax tree, so that formatting never
+/// changes what the code means.
+pub fn format_file(sess: &Session, file: &SourceFile) -> Result<String, FormatError> {
+    let (tokens, comments) = Lexer::lex_with_comments(sess, file).ok_or(FormatError::Syntax)?;
+    let package = Parser::parse_from_root(sess, file, &tokens).ok_or(FormatError::Syntax)?;
+    let text = SourceFormatter::format(&package, file, &comments);
+
+    // Errors of the formatted text would point to text which isn't anywhere
+    let scratch = Session::new(Options::default());
+    let formatted = SourceFile::new_virtual(file.name.to_string(), text.clone(), file.pkg, file.id);
+    let (new_tokens, new_comments) = Lexer::lex_with_comments(&scratch, &formatted).ok_or(FormatError::ChangedCode)?;
+    let new_package = Parser::parse_from_root(&scratch, &formatted, &new_tokens).ok_or(FormatError::ChangedCode)?;
+    let same_tokens = significant_tokens(&tokens, file).eq(significant_tokens(&new_tokens, &formatted));
+    let same_tree = PackageASTPrettyPrinter::pretty_print(&package) == PackageASTPrettyPrinter::pretty_print(&new_package);
+    if !same_tokens || !same_tree || comments.len() != new_comments.len() {
+        return Err(FormatError::ChangedCode)
+    }
+    if SourceFormatter::format(&new_package, &formatted, &new_comments) != text {
+        return Err(FormatError::Unstable)
+    }
+    Ok(text)
+}
+
+/// Text of tokens which the formatter doesn't add or remove, parentheses
+/// are left out, as the syntax tree has no nodes for them, and so are
+/// commas before closing delimiters.
+fn significant_tokens<'a>(tokens: &'a TokenStream, file: &'a SourceFile) -> impl Iterator<Item = &'a str> + 'a {
+    let tokens = tokens.tokens.as_slice();
+    tokens.iter()
+        .enumerate()
+        .filter(|(idx, token)| match token.kind {
+            TokenKind::LeftParen | TokenKind::RightParen => false,
+            TokenKind::Comma => !tokens.get(idx + 1).is_some_and(is_closing),
+            _ => true
+        })
+        .map(|(_, token)| file.get_span(&token.span))
+}
+
+fn is_closing(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket)
+}
+
+/// Prints the package parsed from a single file back as source code,
+/// in the canonical format. Comments of the file are kept, these go on
+/// their own lines before the item, statement or field that follows them,
+/// unless they were at the end of the line, and at most one blank line
+/// from the source is kept between them.
+pub struct SourceFormatter<'a> {
+    pkg: &'a Package,
+    source: &'a SourceFile,
+    comments: &'a [Span],
+    /// First comment which wasn't printed yet.
+    next_comment: usize,
+    /// End of the last node or comment that was printed, in the source.
+    cursor: u32,
+    result: String,
+    indent: usize
+}
+
+impl<'a> SourceFormatter<'a> {
+    /// Format the file that the package was parsed from, with spans
+    /// of its comments as returned by `Lexer::lex_with_comments`.
+    pub fn format(package: &'a Package, source: &'a SourceFile, comments: &'a [Span]) -> String {
+        let mut formatter = Self {
+            pkg: package,
+            source,
+            comments,
+            next_comment: 0,
+            cursor: 0,
+            result: String::new(),
+            indent: 0
+        };
+
+        for attr in package.attrs.attributes.iter() {
+            formatter.begin_line();
+            formatter.push("#!");
+            formatter.attribute(attr);
+            formatter.end_line();
+            formatter.cursor = attr.ident.span.end;
+        }
+        formatter.items(&package.items, !package.attrs.attributes.is_empty());
+        formatter.leading_comments(source.len as u32, !formatter.result.is_empty());
+        formatter.result
+    }
+
+    fn push(&mut self, text: &str) {
+        self.result.push_str(text);
+    }
+
+    fn begin_line(&mut self) {
+        self.result.push_str(&"    ".repeat(self.indent));
+    }
+
+    fn end_line(&mut self) {
+        self.result.push('\n');
+    }
+
+    /// Length of the line that is being printed.
+    fn column(&self) -> usize {
+        self.result.len() - self.result.rfind('\n').map_or(0, |idx| idx + 1)
+    }
+
+    fn ident(&self, ident: &Ident) -> &'a str {
+        ident.text(&self.pkg.symbol_storage)
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        self.source.get_span(&span)
+    }
+
+    /// Whether there is an empty line in the source between the offsets.
+    fn has_blank_line(&self, start: u32, end: u32) -> bool {
+        if start >= end {
+            return false
+        }
+        let text = self.text(Span::new(self.source.id, start, end));
+        let lines = text.split('\n').collect::<Vec<_>>();
+        lines.len() > 2 && lines[1..lines.len() - 1].iter().any(|line| line.trim().is_empty())
+    }
+
+    /// Print comments before the offset on their own lines, with blank
+    /// lines before them and before the offset kept from the source.
+    /// Blank line never goes before the first line of a block.
+    fn leading_comments(&mut self, offset: u32, allow_blank: bool) {
+        self.separated_comments(offset, allow_blank, false)
+    }
+
+    /// Same as `leading_comments`, but with blank line before the first
+    /// printed line if `force_blank` is set, even if there was none.
+    fn separated_comments(&mut self, offset: u32, allow_blank: bool, mut force_blank: bool) {
+        while let Some(&comment) = self.comments.get(self.next_comment) {
+            if comment.start >= offset {
+                break
+            }
+            if allow_blank && (force_blank || self.has_blank_line(self.cursor, comment.start)) {
+                self.end_line();
+            }
+            self.begin_line();
+            self.push(self.text(comment).trim_end());
+            self.end_line();
+            self.next_comment += 1;
+            self.cursor = comment.end;
+            force_blank = false;
+        }
+        if allow_blank && (force_blank || self.has_blank_line(self.cursor, offset)) {
+            self.end_line();
+        }
+    }
+
+    /// Print comment right after the node ending at the offset, if it
+    /// is on the same line.
+    fn trailing_comment(&mut self, end: u32) {
+        self.cursor = self.cursor.max(end);
+        let Some(&comment) = self.comments.get(self.next_comment) else { return };
+        if comment.start < end || self.text(Span::new(self.source.id, end, comment.start)).contains('\n') {
+            return
+        }
+        self.push(" ");
+        self.push(self.text(comment).trim_end());
+        self.next_comment += 1;
+        self.cursor = comment.end;
+    }
+
+    /// Whether the item fits on a single line, blank lines are kept
+    /// between these only if they were in the source.
+    fn is_one_line(&self, item: &Item) -> bool {
+        match item.kind {
+            ItemKind::Import(..) => true,
+            ItemKind::Module(..) if self.is_module_decl(item) => true,
+            ItemKind::Fn(ref function) => function.body.is_none(),
+            ItemKind::Struct(ref data) => !matches!(data, DataVariant::Struct { .. }),
+            ItemKind::Module(..) | ItemKind::Enum(..) => false
+        }
+    }
+
+    /// Print the items, `after_attrs` is set for the items of the root
+    /// file with package attributes, which are separated from them.
+    fn items(&mut self, items: &ItemStream, after_attrs: bool) {
+        let mut previous: Option<&Item> = None;
+        for item in items.items.iter() {
+            let force_blank = match previous {
+                Some(previous) => !self.is_one_line(previous) || !self.is_one_line(item),
+                None => after_attrs
+            };
+            self.separated_comments(item.span.start, previous.is_some() || after_attrs, force_blank);
+            self.begin_line();
+            self.item(item);
+            self.trailing_comment(item.span.end);
+            self.end_line();
+            previous = Some(item);
+        }
+    }
+
+    fn attributes(&mut self, attrs: &Attributes, inline: bool) {
+        for attr in attrs.attributes.iter() {
+            self.push("#");
+            self.attribute(attr);
+            if inline {
+                self.push(" ");
+            } else {
+                self.end_line();
+                self.begin_line();
+            }
+        }
+    }
+
+    fn attribute(&mut self, attr: &Attribute) {
+        self.push("[");
+        self.push(self.ident(&attr.ident));
+        if let AttributeKind::ListAttribute(ref args) = attr.kind {
+            let args = args.iter().map(|arg| self.ident(arg)).collect::<Vec<_>>();
+            self.push(&format!("({})", args.join(", ")));
+        }
+        self.push("]");
+    }
+
+    fn vis(&mut self, vis: Visibility) {
+        if vis == Visibility::Public {
+            self.push("pub ");
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        self.attributes(&item.attrs, false);
+        self.vis(item.visibility);
+        let name = self.ident(&item.ident);
+        match item.kind {
+            ItemKind::Module(ref items) => {
+                self.push("module ");
+                self.push(name);
+                if self.is_module_decl(item) {
+                    self.push(";");
+                } else if items.items.is_empty() && !self.has_comments_before(item.span.end) {
+                    self.push(" {}");
+                } else {
+                    self.push(" {");
+                    self.end_line();
+                    self.indent += 1;
+                    self.items(items, false);
+                    self.leading_comments(item.span.end, !items.items.is_empty());
+                    self.indent -= 1;
+                    self.begin_line();
+                    self.push("}");
+                }
+            },
+            ItemKind::Import(kind, ref tree) => {
+                self.push("import ");
+                match kind {
+                    ImportKind::Relative => {},
+                    ImportKind::Super => self.push("super::"),
+                    ImportKind::Package => self.push("pkg::")
+                }
+                self.import_tree(tree);
+                self.push(";");
+            },
+            ItemKind::Fn(ref function) => self.function(name, function),
+            ItemKind::Struct(ref data) => {
+                self.push("struct ");
+                self.push(name);
+                match data {
+                    DataVariant::Unit => self.push(";"),
+                    DataVariant::Tuple { .. } => {
+                        self.data_variant(data, item.span.end);
+                        self.push(";");
+                    },
+                    DataVariant::Struct { .. } => {
+                        self.push(" ");
+                        self.data_variant(data, item.span.end);
+                    }
+                }
+            },
+            ItemKind::Enum(ref def) => {
+                self.push("enum ");
+                self.push(name);
+                self.enum_def(def, item.span.end);
+            }
+        }
+    }
+
+    /// Whether the module was declared like `module foo;`.
+    fn is_module_decl(&self, item: &Item) -> bool {
+        self.pkg.module_decls.iter().any(|decl| decl.id == item.id)
+    }
+
+    fn has_comments_before(&self, offset: u32) -> bool {
+        self.comments.get(self.next_comment).is_some_and(|comment| comment.start < offset)
+    }
+
+    fn path(&self, path: &Path) -> String {
+        path.segments.iter()
+            .map(|segment| self.ident(&segment.ident))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    fn import_tree(&mut self, tree: &ImportTree) {
+        let mut text = self.path(&tree.prefix);
+        let separator = if text.is_empty() { "" } else { "::" };
+        match tree.kind {
+            ImportTreeKind::Simple(ref name, ref rename) => {
+                text.push_str(separator);
+                text.push_str(self.ident(name));
+                if let Some(rename) = rename {
+                    text.push_str(&format!(" as {}", self.ident(rename)));
+                }
+                self.push(&text);
+            },
+            ImportTreeKind::SelfImport(ref rename) => {
+                text.push_str(separator);
+                text.push_str("self");
+                if let Some(rename) = rename {
+                    text.push_str(&format!(" as {}", self.ident(rename)));
+                }
+                self.push(&text);
+            },
+            ImportTreeKind::Glob => {
+                text.push_str(separator);
+                text.push('*');
+                self.push(&text);
+            },
+            ImportTreeKind::Nested(ref trees) => {
+                text.push_str(separator);
+                text.push('{');
+                self.push(&text);
+                for (idx, (subtree, _)) in trees.iter().enumerate() {
+                    if idx > 0 {
+                        self.push(", ");
+                    }
+                    self.import_tree(subtree);
+                }
+                self.push("}");
+            }
+        }
+    }
+
+    fn function(&mut self, name: &str, function: &Function) {
+        let signature = &function.signature;
+        match signature.ext {
+            Extern::None => {},
+            Extern::Implicit(_) => self.push("extern "),
+            Extern::Explicit(abi, _) => {
+                let abi = self.pkg.symbol_storage.text_of(abi).unwrap();
+                self.push(&format!("extern \"{}\" ", abi));
+            }
+        }
+        self.push("fn ");
+        self.push(name);
+        self.push("(");
+        for (idx, input) in signature.inputs.iter().enumerate() {
+            if idx > 0 {
+                self.push(", ");
+            }
+            self.attributes(&input.attributes, true);
+            self.pat(&input.pat);
+            if !matches!(input.ty.kind, TyKind::SelfTy) {
+                self.push(": ");
+                self.ty(&input.ty);
+            }
+        }
+        self.push(")");
+        if let FnRetTy::Ty(ref ty) = signature.output {
+            self.push(" -> ");
+            self.ty(ty);
+        }
+        match function.body {
+            Some(ref body) => {
+                self.push(" ");
+                self.block(body);
+            },
+            None => self.push(";")
+        }
+    }
+
+    fn data_variant(&mut self, data: &DataVariant, end: u32) {
+        match data {
+            DataVariant::Unit => {},
+            DataVariant::Tuple { ref fields } => {
+                self.push("(");
+                for (idx, field) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        self.push(", ");
+                    }
+                    self.field_def(field);
+                }
+                self.push(")");
+            },
+            DataVariant::Struct { ref fields } => {
+                if fields.is_empty() && !self.has_comments_before(end) {
+                    self.push("{}");
+                    return
+                }
+                self.push("{");
+                self.end_line();
+                self.indent += 1;
+                for (idx, field) in fields.iter().enumerate() {
+                    self.leading_comments(field.span.start, idx > 0);
+                    self.begin_line();
+                    self.field_def(field);
+                    if idx + 1 < fields.len() {
+                        self.push(",");
+                    }
+                    self.trailing_comment(field.span.end);
+                    self.end_line();
+                }
+                self.leading_comments(end, !fields.is_empty());
+                self.indent -= 1;
+                self.begin_line();
+                self.push("}");
+            }
+        }
+    }
+
+    fn field_def(&mut self, field: &FieldDef) {
+        self.attributes(&field.attrs, true);
+        self.vis(field.vis);
+        if let Some(ref ident) = field.ident {
+            self.push(self.ident(ident));
+            self.push(": ");
+        }
+        self.ty(&field.ty);
+    }
+
+    fn enum_def(&mut self, def: &EnumDef, end: u32) {
+        if def.variants.is_empty() && !self.has_comments_before(end) {
+            self.push(" {}");
+            return
+        }
+        self.push(" {");
+        self.end_line();
+        self.indent += 1;
+        for (idx, variant) in def.variants.iter().enumerate() {
+            // Span of the variant starts after its name
+            let start = variant.ident.span.start;
+            self.leading_comments(start, idx > 0);
+            self.begin_line();
+            self.attributes(&variant.attrs, true);
+            self.vis(variant.vis);
+            self.push(self.ident(&variant.ident));
+            if let DataVariant::Struct { .. } = variant.data {
+                self.push(" ");
+            }
+            self.data_variant(&variant.data, variant.span.end);
+            if idx + 1 < def.variants.len() {
+                self.push(",");
+            }
+            self.trailing_comment(variant.span.end.max(variant.ident.span.end));
+            self.end_line();
+        }
+        self.leading_comments(end, !def.variants.is_empty());
+        self.indent -= 1;
+        self.begin_line();
+        self.push("}");
+    }
+
+    fn pat(&mut self, pat: &Pat) {
+        match pat.kind {
+            PatKind::SelfPat => self.push("self"),
+            PatKind::Ident(ref ident) => self.push(self.ident(ident))
+        }
+    }
+
+    fn ty(&mut self, ty: &Ty) {
+        match ty.kind {
+            TyKind::SelfTy => self.push("self"),
+            TyKind::Void => self.push("()"),
+            TyKind::Never => self.push("!"),
+            TyKind::Path(ref path) => {
+                let path = self.path(path);
+                self.push(&path);
+            },
+            // Inferred types are not written
+            TyKind::Infer => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        let stmts = &block.stmts.stmts;
+        if stmts.is_empty() && !self.has_comments_before(block.span.end) {
+            self.push("{}");
+            return
+        }
+        self.push("{");
+        self.end_line();
+        self.indent += 1;
+        for (idx, stmt) in stmts.iter().enumerate() {
+            self.leading_comments(stmt.span.start, idx > 0);
+            self.begin_line();
+            self.stmt(stmt);
+            self.trailing_comment(stmt.span.end);
+            self.end_line();
+        }
+        self.leading_comments(block.span.end, !stmts.is_empty());
+        self.indent -= 1;
+        self.begin_line();
+        self.push("}");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::LetBinding(ref binding) => self.let_binding(binding),
+            StmtKind::Item(ref item) => self.item(item),
+            StmtKind::Expr(ref expr) => {
+                self.expr(expr);
+                self.push(";");
+            },
+            StmtKind::ExprNS(ref expr) => self.expr(expr)
+        }
+    }
+
+    fn let_binding(&mut self, binding: &LetBinding) {
+        self.attributes(&binding.attribs, false);
+        self.push("let ");
+        self.pat(&binding.pat);
+        if let Some(ref ty) = binding.ty {
+            if !matches!(ty.kind, TyKind::Infer) {
+                self.push(": ");
+                self.ty(ty);
+            }
+        }
+        if let LetBindingKind::Init(ref init) = binding.kind {
+            self.push(" = ");
+            self.expr(init);
+        }
+        self.push(";");
+    }
+
+    /// Precedence of the expression, operands with lower precedence
+    /// than the operator expects are put in parentheses.
+    fn precedence(&self, expr: &Expr) -> u8 {
+        match expr.kind {
+            ExprKind::Path(..) | ExprKind::Field(..) | ExprKind::StructLit(..) => PREC_POSTFIX,
+            // Negative numbers would be parsed as negation of the whole postfix expression
+            ExprKind::Literal(ref lit) => match self.lit_text(lit).starts_with('-') {
+                true => PREC_UNARY,
+                false => PREC_POSTFIX
+            },
+            ExprKind::Call(..) => PREC_CALL,
+            ExprKind::Unary(..) => PREC_UNARY,
+            ExprKind::Binary(ref op, ..) => binary_precedence(&op.kind),
+            ExprKind::Assign(..) | ExprKind::If(..) | ExprKind::Block(..) | ExprKind::Loop(..) |
+            ExprKind::While(..) | ExprKind::For(..) | ExprKind::Break(..) | ExprKind::Continue => PREC_OPEN,
+            ExprKind::Err => PREC_POSTFIX
+        }
+    }
+
+    /// Print the expression, in parentheses if its precedence is lower
+    /// than the given one.
+    fn operand(&mut self, expr: &Expr, precedence: u8) {
+        if self.precedence(expr) < precedence {
+            self.push("(");
+            self.expr(expr);
+            self.push(")");
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::Path(ref path) => {
+                let path = self.path(path);
+                self.push(&path);
+            },
+            ExprKind::Literal(ref lit) => {
+                let text = self.lit_text(lit);
+                self.push(&text);
+            },
+            ExprKind::Field(ref receiver, ref field, _) => {
+                self.operand(receiver, PREC_POSTFIX);
+                self.push(".");
+                self.push(self.ident(field));
+            },
+            ExprKind::Assign(ref target, ref value, _) => {
+                self.operand(target, PREC_POSTFIX);
+                self.push(" = ");
+                self.expr(value);
+            },
+            ExprKind::Unary(ref op, ref operand) => {
+                self.push(match op.kind {
+                    UnOpKind::Neg => "-",
+                    UnOpKind::Not => "!"
+                });
+                // `--` is a single token, and minus right before a number
+                // is parsed as a part of its literal
+                let negative = matches!(operand.kind, ExprKind::Unary(ref inner, _) if matches!(inner.kind, UnOpKind::Neg));
+                let numeric = matches!(operand.kind, ExprKind::Literal(ref lit) if matches!(lit.kind, LitKind::Integer | LitKind::Float));
+                if (negative || numeric) && matches!(op.kind, UnOpKind::Neg) {
+                    self.push("(");
+                    self.expr(operand);
+                    self.push(")");
+                } else {
+                    self.operand(operand, PREC_UNARY);
+                }
+            },
+            ExprKind::Binary(ref op, ref lhs, ref rhs) => {
+                let precedence = binary_precedence(&op.kind);
+                // Operators are left associative
+                self.operand(lhs, precedence);
+                self.push(&format!(" {} ", binary_operator(&op.kind)));
+                self.operand(rhs, precedence + 1);
+            },
+            ExprKind::Call(ref target, ref args, _) => {
+                self.operand(target, PREC_POSTFIX);
+                self.push("(");
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        self.push(", ");
+                    }
+                    self.expr(arg);
+                }
+                self.push(")");
+            },
+            ExprKind::If(ref condition, ref block, ref else_expr) => {
+                self.push("if ");
+                self.expr(condition);
+                self.push(" ");
+                self.block(block);
+                if let Some(else_expr) = else_expr {
+                    self.push(" else ");
+                    self.expr(else_expr);
+                }
+            },
+            ExprKind::Block(ref block) => self.block(block),
+            ExprKind::Loop(ref block) => {
+                self.push("loop ");
+                self.block(block);
+            },
+            ExprKind::While(ref condition, ref block) => {
+                self.push("while ");
+                self.expr(condition);
+                self.push(" ");
+                self.block(block);
+            },
+            ExprKind::For(ref pat, ref iter, ref block) => {
+                self.push("for ");
+                self.pat(pat);
+                self.push(" in ");
+                self.expr(iter);
+                self.push(" ");
+                self.block(block);
+            },
+            ExprKind::Break(ref value) => {
+                self.push("break");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.expr(value);
+                }
+            },
+            ExprKind::Continue => self.push("continue"),
+            ExprKind::StructLit(ref lit) => self.struct_lit(lit),
+            // Parsed code has no errors, these only come from passes
+            ExprKind::Err => self.push("/* error */")
+        }
+    }
+
+    fn lit_text(&self, lit: &Lit) -> String {
+        let symbols = &self.pkg.symbol_storage;
+        let mut text = symbols.text_of(lit.symbol).unwrap().to_string();
+        if let (LitKind::Integer | LitKind::Float, Some(suffix)) = (&lit.kind, lit.suffix) {
+            text.push_str(symbols.text_of(suffix).unwrap());
+        }
+        text
+    }
+
+    /// Struct literal on a single line, or with a field on every line
+    /// if it doesn't fit or has blocks in it.
+    fn struct_lit(&mut self, lit: &StructLiteral) {
+        let path = self.path(&lit.path);
+        self.push(&path);
+        if lit.fields.is_empty() && matches!(lit.rest, RestExpr::None) {
+            self.push(" {}");
+            return
+        }
+        let has_blocks = lit.fields.iter().any(|field| contains_block(&field.expr))
+            || matches!(lit.rest, RestExpr::Valued(ref expr) if contains_block(expr));
+        if !has_blocks {
+            // Nothing with comments is printed without blocks
+            let mut line = Self {
+                pkg: self.pkg,
+                source: self.source,
+                comments: &[],
+                next_comment: 0,
+                cursor: 0,
+                result: String::new(),
+                indent: 0
+            };
+            line.struct_lit_fields(lit, false);
+            if self.column() + line.result.len() <= MAX_WIDTH {
+                self.push(&line.result);
+                return
+            }
+        }
+        self.struct_lit_fields(lit, true);
+    }
+
+    fn struct_lit_fields(&mut self, lit: &StructLiteral, multiline: bool) {
+        let mut parts = 0;
+        let mut separator = |this: &mut Self| {
+            if multiline {
+                if parts > 0 {
+                    this.push(",");
+                }
+                this.end_line();
+                this.begin_line();
+            } else {
+                this.push(if parts > 0 { ", " } else { " " });
+            }
+            parts += 1;
+        };
+        self.push(" {");
+        if multiline {
+            self.indent += 1;
+        }
+        for field in lit.fields.iter() {
+            separator(self);
+            self.attributes(&field.attrs, true);
+            self.push(self.ident(&field.ident));
+            self.push(": ");
+            self.expr(&field.expr);
+        }
+        match lit.rest {
+            RestExpr::None => {},
+            RestExpr::Rest(_) => {
+                separator(self);
+                self.push("..");
+            },
+            RestExpr::Valued(ref expr) => {
+                separator(self);
+                self.push("..");
+                self.expr(expr);
+            }
+        }
+        if multiline {
+            self.indent -= 1;
+            self.end_line();
+            self.begin_line();
+            self.push("}");
+        } else {
+            self.push(" }");
+        }
+    }
+}
+
+fn binary_precedence(op: &BinOpKind) -> u8 {
+    match op {
+        BinOpKind::Or => 1,
+        BinOpKind::And => 2,
+        BinOpKind::Eq | BinOpKind::Ne => 3,
+        BinOpKind::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge => 4,
+        BinOpKind::Add | BinOpKind::Sub | BinOpKind::BitOr | BinOpKind::BitXor |
+        BinOpKind::Shl | BinOpKind::Shr => 5,
+        BinOpKind::Mul | BinOpKind::Div | BinOpKind::Rem | BinOpKind::BitAnd => 6
+    }
+}
+
+fn binary_operator(op: &BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "+",
+        BinOpKind::Sub => "-",
+        BinOpKind::Mul => "*",
+        BinOpKind::Div => "/",
+        BinOpKind::Rem => "%",
+        BinOpKind::And => "&&",
+        BinOpKind::Or => "||",
+        BinOpKind::BitAnd => "&",
+        BinOpKind::BitXor => "^",
+        BinOpKind::BitOr => "|",
+        BinOpKind::Shl => "<<",
+        BinOpKind::Shr => ">>",
+        BinOpKind::Eq => "==",
+        BinOpKind::Lt => "<",
+        BinOpKind::Le => "<=",
+        BinOpKind::Ne => "!=",
+        BinOpKind::Ge => ">=",
+        BinOpKind::Gt => ">"
+    }
+}
+
+/// Whether there is a block anywhere in the expression.
+fn contains_block(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Path(..) | ExprKind::Literal(..) | ExprKind::Continue | ExprKind::Err => false,
+        ExprKind::Field(ref expr, ..) | ExprKind::Unary(_, ref expr) => contains_block(expr),
+        ExprKind::Assign(ref lhs, ref rhs, _) | ExprKind::Binary(_, ref lhs, ref rhs) =>
+            contains_block(lhs) || contains_block(rhs),
+        ExprKind::Call(ref target, ref args, _) => contains_block(target) || args.iter().any(|arg| contains_block(arg)),
+        ExprKind::Break(ref value) => value.as_ref().is_some_and(|value| contains_block(value)),
+        ExprKind::StructLit(ref lit) => lit.fields.iter().any(|field| contains_block(&field.expr))
+            || matches!(lit.rest, RestExpr::Valued(ref expr) if contains_block(expr)),
+        ExprKind::If(..) | ExprKind::Block(..) | ExprKind::Loop(..) | ExprKind::While(..) | ExprKind::For(..) => true
+    }
+}
@@ -0,0 +1,67 @@
+//! Throughput of lexing, parsing and name resolution of the generated
+//! packages, in bytes of their source.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use hastyc_bench::programs;
+use hastyc_common::{identifiers::{PkgID, SourceFileID}, session::Session, source::SourceFile};
+use hastyc_parser::{lexer::Lexer, parser::Parser};
+use hastyc_passes::passes::{name_resolve::NameResolvePass, ASTPass, QueryContext};
+
+fn sources() -> Vec<(&'static str, SourceFile)> {
+    programs().into_iter()
+        .map(|program| (program.name, SourceFile::new_raw(program.code, PkgID::new_unique(), SourceFileID::new_unique())))
+        .collect()
+}
+
+fn lex(c: &mut Criterion) {
+    let sess = Session::default();
+    let mut group = c.benchmark_group("lex");
+    group.sample_size(20);
+    for (name, source) in sources() {
+        group.throughput(Throughput::Bytes(source.len as u64));
+        group.bench_function(name, |b| b.iter(|| {
+            Lexer::lex(&sess, black_box(&source)).expect("generated package should lex")
+        }));
+    }
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let sess = Session::default();
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(20);
+    for (name, source) in sources() {
+        let tokens = Lexer::lex(&sess, &source).expect("generated package should lex");
+        group.throughput(Throughput::Bytes(source.len as u64));
+        group.bench_function(name, |b| b.iter(|| {
+            Parser::parse_from_root(&sess, &source, black_box(&tokens)).expect("generated package should parse")
+        }));
+    }
+    group.finish();
+}
+
+fn resolve(c: &mut Criterion) {
+    let sess = Session::default();
+    let mut group = c.benchmark_group("resolve");
+    group.sample_size(20);
+    for (name, source) in sources() {
+        let tokens = Lexer::lex(&sess, &source).expect("generated package should lex");
+        let package = Parser::parse_from_root(&sess, &source, &tokens).expect("generated package should parse");
+        group.throughput(Throughput::Bytes(source.len as u64));
+        group.bench_function(name, |b| b.iter_batched(
+            || QueryContext::for_package(&sess, &package),
+            |mut ctx| {
+                let Ok(()) = NameResolvePass::new().traverse(&mut ctx);
+                ctx
+            },
+            BatchSize::LargeInput
+        ));
+        assert!(!sess.has_errors(), "generated package should resolve");
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lex, parse, resolve);
+criterion_main!(benches);
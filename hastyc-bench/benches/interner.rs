@@ -0,0 +1,66 @@
+//! Interning names like identifiers of a large package, which are
+//! mostly looked up again, and reading their text back.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use hastyc_common::identifiers::Symbol;
+
+/// Names looked up in every iteration.
+const NAMES: usize = 50_000;
+/// Names never seen before which are interned in every iteration, the
+/// interner keeps them all so there are fewer of them.
+const FRESH_NAMES: usize = 1_000;
+
+fn names(prefix: &str, count: usize) -> Vec<String> {
+    (0..count).map(|idx| format!("{}_{}", prefix, idx)).collect()
+}
+
+fn intern(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intern");
+    group.sample_size(20);
+
+    let existing = names("existing", NAMES);
+    for name in existing.iter() {
+        Symbol::intern(name);
+    }
+    group.throughput(Throughput::Elements(NAMES as u64));
+    group.bench_function("existing", |b| b.iter(|| {
+        for name in existing.iter() {
+            black_box(Symbol::intern(black_box(name)));
+        }
+    }));
+
+    let mut batch = 0;
+    group.throughput(Throughput::Elements(FRESH_NAMES as u64));
+    group.bench_function("fresh", |b| b.iter_batched(
+        || {
+            batch += 1;
+            names(&format!("fresh{}", batch), FRESH_NAMES)
+        },
+        |fresh| {
+            for name in fresh.iter() {
+                black_box(Symbol::intern(name));
+            }
+            fresh
+        },
+        BatchSize::SmallInput
+    ));
+    group.finish();
+}
+
+fn as_str(c: &mut Criterion) {
+    let mut group = c.benchmark_group("as_str");
+    group.sample_size(20);
+    let symbols: Vec<Symbol> = names("text", NAMES).iter().map(|name| Symbol::intern(name)).collect();
+    group.throughput(Throughput::Elements(NAMES as u64));
+    group.bench_function("interned", |b| b.iter(|| {
+        for symbol in symbols.iter() {
+            black_box(black_box(symbol).as_str());
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, intern, as_str);
+criterion_main!(benches);
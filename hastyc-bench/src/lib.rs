@@ -0,0 +1,84 @@
+//! Generators of large synthetic packages for measuring the front of the
+//! compiler, benchmarked with `cargo bench -p hastyc-bench`, and once more
+//! with `--features parallel` to compare the parallel passes. Every package
+//! lexes, parses and resolves without errors.
+
+use std::fmt::Write;
+
+/// Package with its name in benchmark ids.
+pub struct Program {
+    pub name: &'static str,
+    pub code: String
+}
+
+/// Packages which the benchmarks run on.
+pub fn programs() -> Vec<Program> {
+    vec![
+        Program { name: "functions", code: many_functions(10_000) },
+        Program { name: "expressions", code: deep_expressions(1_000, 100) },
+        Program { name: "modules", code: many_modules(1_000, 10) }
+    ]
+}
+
+/// Functions in the root module, each calling the one before it.
+pub fn many_functions(count: usize) -> String {
+    let mut code = String::new();
+    for function in 0..count {
+        writeln!(code, "fn f{}(a: i32, b: i32) -> i32 {{", function).unwrap();
+        writeln!(code, "    let c = a * b + 1;").unwrap();
+        if function > 0 {
+            writeln!(code, "    let d = f{}(c, a - b);", function - 1).unwrap();
+        } else {
+            writeln!(code, "    let d = c;").unwrap();
+        }
+        writeln!(code, "    if c < d + 1 {{ c }} else {{ d / 2 }}").unwrap();
+        writeln!(code, "}}").unwrap();
+    }
+    code
+}
+
+/// Functions returning a binary expression with operands nested in
+/// parentheses `depth` times, which has to stay below the nesting
+/// limit of the parser.
+pub fn deep_expressions(count: usize, depth: usize) -> String {
+    const OPS: [&str; 4] = ["+", "*", "-", "/"];
+    let mut expr = "a".to_string();
+    for level in 0..depth {
+        let operand = if level % 2 == 0 { "b" } else { "a" };
+        expr = format!("({} {} {})", expr, OPS[level % OPS.len()], operand);
+    }
+    let mut code = String::new();
+    for function in 0..count {
+        writeln!(code, "fn e{}(a: i32, b: i32) -> i32 {{", function).unwrap();
+        writeln!(code, "    {}", expr).unwrap();
+        writeln!(code, "}}").unwrap();
+    }
+    code
+}
+
+/// Modules with functions that call each other and a function imported
+/// from a nested module, and `main` in the root calling into all of them.
+pub fn many_modules(modules: usize, fns_per_module: usize) -> String {
+    let mut code = String::new();
+    for module in 0..modules {
+        writeln!(code, "pub module m{} {{", module).unwrap();
+        writeln!(code, "    module inner {{ pub fn g(a: i32) -> i32 {{ a }} }}").unwrap();
+        writeln!(code, "    import inner::g;").unwrap();
+        for function in 0..fns_per_module {
+            writeln!(code, "    pub fn f{}(a: i32, b: i32) -> i32 {{", function).unwrap();
+            writeln!(code, "        let c = g(a + b);").unwrap();
+            if function > 0 {
+                writeln!(code, "        f{}(c, b);", function - 1).unwrap();
+            }
+            writeln!(code, "        c").unwrap();
+            writeln!(code, "    }}").unwrap();
+        }
+        writeln!(code, "}}").unwrap();
+    }
+    writeln!(code, "pub fn main() {{").unwrap();
+    for module in 0..modules {
+        writeln!(code, "    m{}::f{}(1, 2);", module, fns_per_module.saturating_sub(1)).unwrap();
+    }
+    writeln!(code, "}}").unwrap();
+    code
+}
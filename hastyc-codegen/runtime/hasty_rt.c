@@ -0,0 +1,117 @@
+#include "hasty_rt.h"
+
+#include <inttypes.h>
+#include <math.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+void *hasty_rt_alloc(size_t size) {
+    void *ptr = malloc(size == 0 ? 1 : size);
+    if (ptr == NULL) {
+        fputs("out of memory\n", stderr);
+        abort();
+    }
+    return ptr;
+}
+
+void hasty_rt_print(const char *ptr, size_t len) {
+    fwrite(ptr, 1, len, stdout);
+}
+
+void hasty_rt_println(const char *ptr, size_t len) {
+    fwrite(ptr, 1, len, stdout);
+    fputc('\n', stdout);
+}
+
+/* Same message as the VM gives, with the same exit code as a failed `main`. */
+void hasty_rt_panic(const char *ptr, size_t len, const char *location, size_t location_len) {
+    fflush(stdout);
+    fputs("panicked at ", stderr);
+    fwrite(location, 1, location_len, stderr);
+    fputs(": ", stderr);
+    fwrite(ptr, 1, len, stderr);
+    fputc('\n', stderr);
+    exit(101);
+}
+
+static hasty_str copy_str(const char *text, size_t len) {
+    char *ptr = hasty_rt_alloc(len);
+    memcpy(ptr, text, len);
+    return (hasty_str){ ptr, len };
+}
+
+hasty_str hasty_rt_concat(const char *a, size_t a_len, const char *b, size_t b_len) {
+    char *ptr = hasty_rt_alloc(a_len + b_len);
+    memcpy(ptr, a, a_len);
+    memcpy(ptr + a_len, b, b_len);
+    return (hasty_str){ ptr, a_len + b_len };
+}
+
+hasty_str hasty_rt_format_i32(int32_t value) {
+    return hasty_rt_format_i64(value);
+}
+
+hasty_str hasty_rt_format_i64(int64_t value) {
+    char buf[32];
+    int len = snprintf(buf, sizeof buf, "%" PRId64, value);
+    return copy_str(buf, (size_t)len);
+}
+
+hasty_str hasty_rt_format_u64(uint64_t value) {
+    char buf[32];
+    int len = snprintf(buf, sizeof buf, "%" PRIu64, value);
+    return copy_str(buf, (size_t)len);
+}
+
+/* Shortest digits which read back as the same value, written without
+ * an exponent, which is how the VM formats floats. */
+hasty_str hasty_rt_format_f64(double value) {
+    if (isnan(value)) return (hasty_str){ "NaN", 3 };
+    if (isinf(value)) return value < 0 ? (hasty_str){ "-inf", 4 } : (hasty_str){ "inf", 3 };
+
+    char sci[40];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(sci, sizeof sci, "%.*e", precision, value);
+        if (strtod(sci, NULL) == value) break;
+    }
+    /* `sci` is like "-1.2345e+02", take its digits and exponent apart */
+    const char *at = sci;
+    bool negative = *at == '-';
+    if (negative) at++;
+    char digits[20];
+    size_t count = 0;
+    for (; *at != 'e'; at++) {
+        if (*at != '.') digits[count++] = *at;
+    }
+    int exponent = atoi(at + 1);
+    while (count > 1 && digits[count - 1] == '0') count--;
+
+    /* Digits before the point, the rest goes after it */
+    int point = exponent + 1;
+    char buf[400];
+    size_t len = 0;
+    if (negative) buf[len++] = '-';
+    if (point <= 0) {
+        buf[len++] = '0';
+        buf[len++] = '.';
+        for (int i = point; i < 0; i++) buf[len++] = '0';
+        memcpy(buf + len, digits, count);
+        len += count;
+    } else if ((size_t)point >= count) {
+        memcpy(buf + len, digits, count);
+        len += count;
+        for (size_t i = count; i < (size_t)point; i++) buf[len++] = '0';
+    } else {
+        memcpy(buf + len, digits, (size_t)point);
+        len += (size_t)point;
+        buf[len++] = '.';
+        memcpy(buf + len, digits + point, count - (size_t)point);
+        len += count - (size_t)point;
+    }
+    return copy_str(buf, len);
+}
+
+hasty_str hasty_rt_format_bool(bool value) {
+    return value ? (hasty_str){ "true", 4 } : (hasty_str){ "false", 5 };
+}
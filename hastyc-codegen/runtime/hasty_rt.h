@@ -0,0 +1,27 @@
+/* Runtime library of programs compiled by hastyc, see `hastyc_passes::passes::intrinsic`. */
+#ifndef HASTY_RT_H
+#define HASTY_RT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/* UTF-8 bytes of a string, which are not terminated by a zero. */
+typedef struct { const char *ptr; size_t len; } hasty_str;
+
+/* Memory for data made at runtime, programs stop if there is none left. */
+void *hasty_rt_alloc(size_t size);
+
+void hasty_rt_print(const char *ptr, size_t len);
+void hasty_rt_println(const char *ptr, size_t len);
+/* Location is the place of the call, like `src/main.hty:3:5`. */
+void hasty_rt_panic(const char *ptr, size_t len, const char *location, size_t location_len);
+
+hasty_str hasty_rt_concat(const char *a, size_t a_len, const char *b, size_t b_len);
+hasty_str hasty_rt_format_i32(int32_t value);
+hasty_str hasty_rt_format_i64(int64_t value);
+hasty_str hasty_rt_format_u64(uint64_t value);
+hasty_str hasty_rt_format_f64(double value);
+hasty_str hasty_rt_format_bool(bool value);
+
+#endif
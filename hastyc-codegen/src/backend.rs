@@ -0,0 +1,79 @@
+use hastyc_common::{identifiers::SymbolStorage, session::BackendKind, source::SourceMap};
+use hastyc_passes::hir::{mono::MonoItem, DefId, DefKind, Hir};
+use hastyc_passes::mir::{Mir, MirBody};
+use hastyc_vm::bytecode::Program;
+
+use crate::{c::CBackend, vm::VmBackend, CodegenError};
+
+/// What a backend produced.
+#[derive(Debug)]
+pub enum Artifact {
+    Bytecode(Program),
+    /// Source code in another language.
+    Source(String),
+    /// Object file, which has to be linked to get an executable.
+    Object(Vec<u8>)
+}
+
+/// Generator of code for some target. Backends are given functions
+/// of the program one by one, all of them are declared first, so that
+/// a body can call functions which are defined after it. Only then are
+/// the bodies defined, and after that the artifact is finished.
+pub trait CodegenBackend {
+    fn name(&self) -> &'static str;
+
+    /// Declare the function, body is None for functions without a body,
+    /// which have to come from outside of the package.
+    fn declare_function(&mut self, def: DefId, body: Option<&MirBody>) -> Result<(), CodegenError>;
+
+    fn define_body(&mut self, body: &MirBody) -> Result<(), CodegenError>;
+
+    /// Artifact with everything which was defined.
+    fn finish(&mut self) -> Result<Artifact, CodegenError>;
+}
+
+/// Everything backends get from the rest of the compiler.
+#[derive(Debug, Clone, Copy)]
+pub struct CodegenInput<'a> {
+    pub mir: &'a Mir,
+    pub hir: &'a Hir,
+    pub symbols: &'a SymbolStorage,
+    /// Sources of the package, for locations passed to intrinsics.
+    pub sources: &'a SourceMap,
+    /// Items which have to be emitted, see `hir::mono::MonoCollectPass`.
+    pub mono_items: &'a [MonoItem]
+}
+
+/// Generate code for all mono items with the backend of the kind.
+pub fn codegen(kind: BackendKind, input: CodegenInput) -> Result<Artifact, CodegenError> {
+    match kind {
+        BackendKind::Vm => run_backend(&mut VmBackend::new(input.hir, input.sources), input),
+        BackendKind::C => run_backend(&mut CBackend::new(input.hir, input.symbols, input.sources), input),
+        #[cfg(feature = "llvm")]
+        BackendKind::Llvm => {
+            let context = inkwell::context::Context::create();
+            let mut backend = crate::llvm::LlvmBackend::new(&context, input.hir, input.symbols, input.sources)?;
+            run_backend(&mut backend, input)
+        },
+        #[cfg(not(feature = "llvm"))]
+        BackendKind::Llvm => Err(CodegenError::Disabled(kind.name()))
+    }
+}
+
+/// Give functions of all mono items to the backend, types are left to
+/// backends, which emit them once they are used.
+pub fn run_backend(backend: &mut dyn CodegenBackend, input: CodegenInput) -> Result<Artifact, CodegenError> {
+    let functions: Vec<DefId> = input.mono_items.iter()
+        .map(|item| item.def)
+        .filter(|def| matches!(input.hir.def(*def).kind, DefKind::Fn { .. }))
+        .collect();
+    for def in functions.iter() {
+        backend.declare_function(*def, input.mir.body(*def))?;
+    }
+    for def in functions.iter() {
+        if let Some(body) = input.mir.body(*def) {
+            backend.define_body(body)?;
+        }
+    }
+    backend.finish()
+}
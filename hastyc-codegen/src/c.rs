@@ -0,0 +1,653 @@
+use std::{collections::{BTreeSet, HashMap, HashSet}, fmt::Write};
+
+use hastyc_common::{identifiers::{Symbol, SymbolStorage}, source::SourceMap, span::Span};
+use hastyc_parser::parser::{BinOpKind, UnOpKind};
+use hastyc_passes::{hir::{mono::main_fn, ty::Ty, typeck::declared_ty, DefId, DefKind, Hir}, mir::{BasicBlock, ConstKind, Constant, MirBody, Operand, Place, PlaceElem, Rvalue, StatementKind, Terminator, TerminatorKind}, passes::{builtin::{FloatTy, IntTy, PrimTy, UintTy}, intrinsic::{location, ArithmeticError, Intrinsic}}};
+
+use crate::{backend::{Artifact, CodegenBackend}, mangle::symbol_names, pool::ConstPool, runtime, util::{field_ty, operand_ty}, CodegenError};
+
+/// Definitions every program needs, which come after the runtime
+/// header. C99 has no empty structs, so `()` is a struct with a byte
+/// that is never read.
+const PRELUDE: &str = "\
+#include <math.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef struct { char unused; } hasty_unit;
+
+static inline int hasty_str_cmp(hasty_str a, hasty_str b) {
+    int result = memcmp(a.ptr, b.ptr, a.len < b.len ? a.len : b.len);
+    if (result != 0 || a.len == b.len) return result;
+    return a.len < b.len ? -1 : 1;
+}
+";
+
+/// Backend translating MIR of the package to a single C99 source file.
+/// Structs become C structs and enums become a tag together with a union
+/// of their variants, fields are named by their index. Locals are named
+/// like in MIR, and every basic block is a label which terminators jump
+/// to with `goto`. Functions without a body are expected to be defined
+/// by whatever the output is linked with, their `str` parameters are
+/// passed as a pointer and a length, same as the LLVM backend does it.
+/// Intrinsics are calls to the runtime, which is part of the output.
+///
+/// Integer arithmetic that overflows, division by zero and shifts by
+/// the size of the type or more panic through the runtime with the
+/// message the VM fails with, the checks use builtins of GCC and Clang.
+pub struct CBackend<'a> {
+    cx: CCx<'a>,
+    externs: BTreeSet<DefId>,
+    /// Prototypes of functions with a body.
+    decls: String,
+    defs: String
+}
+
+impl<'a> CBackend<'a> {
+    pub fn new(hir: &'a Hir, symbols: &'a SymbolStorage, sources: &'a SourceMap) -> Self {
+        let cx = CCx {
+            hir,
+            symbols,
+            sources,
+            names: symbol_names(hir, symbols),
+            main: main_fn(hir),
+            types: String::new(),
+            type_names: HashMap::new(),
+            defined: HashSet::new(),
+            pool: ConstPool::new(symbols)
+        };
+        Self { cx, externs: BTreeSet::new(), decls: String::new(), defs: String::new() }
+    }
+}
+
+impl CodegenBackend for CBackend<'_> {
+    fn name(&self) -> &'static str {
+        "c"
+    }
+
+    fn declare_function(&mut self, def: DefId, body: Option<&MirBody>) -> Result<(), CodegenError> {
+        match body {
+            Some(body) => {
+                let signature = self.cx.signature(body);
+                writeln!(self.decls, "{};", signature).unwrap();
+            },
+            None => {
+                self.externs.insert(def);
+            }
+        }
+        Ok(())
+    }
+
+    fn define_body(&mut self, body: &MirBody) -> Result<(), CodegenError> {
+        self.cx.body(&mut self.defs, body, &self.externs);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Artifact, CodegenError> {
+        let mut decls = String::new();
+        // Intrinsics are declared by the runtime header
+        for def in self.externs.iter().filter(|def| Intrinsic::of_def(self.cx.hir, **def).is_none()) {
+            let decl = self.cx.extern_decl(*def);
+            writeln!(decls, "{};", decl).unwrap();
+        }
+        decls.push_str(&self.decls);
+
+        let mut out = String::from("/* Generated by hastyc, do not edit. */\n\n");
+        out.push_str(runtime::HEADER);
+        out.push('\n');
+        out.push_str(PRELUDE);
+        let mut data = String::new();
+        for (_, constant) in self.cx.pool.data() {
+            writeln!(data, "static const char {}[] = {};", constant.name, constant.c_literal()).unwrap();
+        }
+        for section in [&self.cx.types, &data, &decls] {
+            if !section.is_empty() {
+                out.push('\n');
+                out.push_str(section);
+            }
+        }
+        out.push_str(&self.defs);
+        out.push('\n');
+        // Header is already there
+        let source = runtime::SOURCE.lines().filter(|line| *line != "#include \"hasty_rt.h\"");
+        for line in source {
+            out.push_str(line);
+            out.push('\n');
+        }
+        Ok(Artifact::Source(out))
+    }
+}
+
+fn params_or_void(params: Vec<String>) -> String {
+    if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    }
+}
+
+struct CCx<'a> {
+    hir: &'a Hir,
+    symbols: &'a SymbolStorage,
+    sources: &'a SourceMap,
+    names: HashMap<DefId, String>,
+    main: Option<DefId>,
+    /// Definitions of structs and enums, each type is defined after
+    /// types of its fields.
+    types: String,
+    type_names: HashMap<DefId, String>,
+    defined: HashSet<DefId>,
+    /// Bytes of strings, defined after the types.
+    pool: ConstPool<'a>
+}
+
+impl CCx<'_> {
+    fn ty(&mut self, ty: Ty) -> String {
+        match ty {
+            Ty::Prim(prim) => prim_ty(prim).to_string(),
+            Ty::Adt(def) => self.adt(def),
+            Ty::FnDef(_) | Ty::Void | Ty::Never => "hasty_unit".to_string(),
+            Ty::Var(_) | Ty::Err => unreachable!("MIR of a package with errors")
+        }
+    }
+
+    /// Return type, `()` and `!` are returned as `void`.
+    fn ret_ty(&mut self, ty: Ty) -> String {
+        match ty {
+            Ty::Void | Ty::Never => "void".to_string(),
+            _ => self.ty(ty)
+        }
+    }
+
+    fn type_name(&mut self, def: DefId) -> String {
+        if let Some(name) = self.type_names.get(&def) {
+            return name.clone()
+        }
+        let item = self.hir.def(def);
+        let name = match item.kind {
+            DefKind::Variant { .. } => {
+                let parent = self.hir.def(item.parent.expect("variant without an enum"));
+                format!("{}_{}_{}", parent.ident.text(self.symbols), item.ident.text(self.symbols), def.index.0)
+            },
+            _ => format!("{}_{}", item.ident.text(self.symbols), def.index.0)
+        };
+        self.type_names.insert(def, name.clone());
+        name
+    }
+
+    /// Name of the struct or enum, defining it the first time it is used.
+    fn adt(&mut self, def: DefId) -> String {
+        let name = self.type_name(def);
+        if !self.defined.insert(def) {
+            return name
+        }
+        let hir = self.hir;
+        let mut members = String::new();
+        match hir.def(def).kind {
+            DefKind::Struct { ref fields, .. } | DefKind::Variant { ref fields, .. } => {
+                for (idx, field) in fields.iter().enumerate() {
+                    let ty = field_ty(hir, *field);
+                    let ty = self.ty(ty);
+                    writeln!(members, "    {} _{}; /* {} */", ty, idx, hir.def(*field).ident.text(self.symbols)).unwrap();
+                }
+                if fields.is_empty() {
+                    members.push_str("    char unused;\n");
+                }
+            },
+            DefKind::Enum { ref variants } => {
+                members.push_str("    uint32_t tag;\n");
+                let mut union = String::new();
+                for (idx, variant) in variants.iter().enumerate() {
+                    let ty = self.adt(*variant);
+                    writeln!(union, "        {} v{};", ty, idx).unwrap();
+                }
+                if !variants.is_empty() {
+                    writeln!(members, "    union {{\n{}    }} data;", union).unwrap();
+                }
+            },
+            _ => unreachable!("type of a definition which is not a struct or enum")
+        }
+        writeln!(self.types, "typedef struct {} {{\n{}}} {};", name, members, name).unwrap();
+        name
+    }
+
+    /// Prototype of a function without a body.
+    fn extern_decl(&mut self, def: DefId) -> String {
+        let hir = self.hir;
+        let DefKind::Fn { ref sig, .. } = hir.def(def).kind else { unreachable!("declared definition is not a function") };
+        let mut params = Vec::new();
+        for ty in sig.inputs.iter() {
+            match declared_ty(hir, ty).unwrap_or(Ty::Err) {
+                Ty::Prim(PrimTy::Str) => {
+                    params.push("const char *".to_string());
+                    params.push("size_t".to_string());
+                },
+                ty => params.push(self.ty(ty))
+            }
+        }
+        let ret = sig.output.as_ref().and_then(|ty| declared_ty(hir, ty)).unwrap_or(Ty::Void);
+        let ret = self.ret_ty(ret);
+        format!("{} {}({})", ret, self.names[&def], params_or_void(params))
+    }
+
+    /// Root `main` returns `int` as C wants it to, whatever it returns.
+    fn signature(&mut self, body: &MirBody) -> String {
+        let params: Vec<String> = body.args()
+            .map(|arg| format!("{} _{}", self.ty(body.local(arg).ty), arg.index()))
+            .collect();
+        let ret = if Some(body.owner) == self.main {
+            "int".to_string()
+        } else {
+            self.ret_ty(body.return_ty())
+        };
+        format!("{} {}({})", ret, self.names[&body.owner], params_or_void(params))
+    }
+
+    fn body(&mut self, out: &mut String, body: &MirBody, externs: &BTreeSet<DefId>) {
+        let mut fx = FnCx { body, code: String::new(), labels: HashSet::new(), externs };
+        let signature = self.signature(body);
+        writeln!(out, "\n{} {{", signature).unwrap();
+        for (idx, local) in body.locals.iter().enumerate() {
+            if (1..=body.arg_count).contains(&idx) {
+                continue
+            }
+            let ty = self.ty(local.ty);
+            match local.name {
+                Some(ref ident) => writeln!(out, "    {} _{}; /* {} */", ty, idx, ident.text(self.symbols)).unwrap(),
+                None => writeln!(out, "    {} _{};", ty, idx).unwrap()
+            }
+        }
+        let mut blocks = Vec::with_capacity(body.blocks.len());
+        for (id, block) in body.basic_blocks() {
+            let start = fx.code.len();
+            for stmt in block.stmts.iter() {
+                if let StatementKind::Assign(ref place, ref rvalue) = stmt.kind {
+                    self.assign(&mut fx, place, rvalue, stmt.span);
+                }
+            }
+            self.terminator(&mut fx, &block.terminator, BasicBlock(id.0 + 1));
+            blocks.push(fx.code[start..].to_string());
+        }
+        for (idx, code) in blocks.iter().enumerate() {
+            // Labels nothing jumps to would be warned about
+            if fx.labels.contains(&idx) {
+                writeln!(out, "bb{}:;", idx).unwrap();
+            }
+            out.push_str(code);
+        }
+        out.push_str("}\n");
+    }
+
+    fn assign(&mut self, fx: &mut FnCx, place: &Place, rvalue: &Rvalue, span: Span) {
+        let dest = place_expr(place);
+        if let Rvalue::Aggregate(def, operands) = rvalue {
+            let hir = self.hir;
+            let ty = self.adt(*def);
+            let fields = self.fields(fx, operands);
+            if let DefKind::Variant { .. } = hir.def(*def).kind {
+                let enum_def = hir.def(*def).parent.expect("variant without an enum");
+                let DefKind::Enum { ref variants } = hir.def(enum_def).kind else { unreachable!("parent of a variant is not an enum") };
+                let tag = variants.iter().position(|variant| variant == def).unwrap();
+                writeln!(fx.code, "    {}.tag = {};", dest, tag).unwrap();
+                writeln!(fx.code, "    {}.data.v{} = ({}){{ {} }};", dest, tag, ty, fields).unwrap();
+            } else {
+                writeln!(fx.code, "    {} = ({}){{ {} }};", dest, ty, fields).unwrap();
+            }
+            return
+        }
+        if self.checked_op(fx, &dest, rvalue, span) {
+            return
+        }
+        let value = self.rvalue(fx, rvalue);
+        writeln!(fx.code, "    {} = {};", dest, value).unwrap();
+    }
+
+    /// Assign integer operation which can fail, together with checks that
+    /// panic if it does. Returns false if the operation can't fail.
+    fn checked_op(&mut self, fx: &mut FnCx, dest: &str, rvalue: &Rvalue, span: Span) -> bool {
+        let (op, lhs, rhs) = match rvalue {
+            Rvalue::BinaryOp(op, lhs, rhs) => (op, lhs, Some(rhs)),
+            Rvalue::UnaryOp(UnOpKind::Neg, operand) => (&BinOpKind::Sub, operand, None),
+            _ => return false
+        };
+        let Ty::Prim(prim) = operand_ty(self.hir, fx.body, lhs) else { return false };
+        if !prim.is_integer() || !matches!(op, BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul | BinOpKind::Div | BinOpKind::Rem | BinOpKind::Shl | BinOpKind::Shr) {
+            return false
+        }
+        // Negation is subtraction from zero
+        let (lhs, rhs) = match rhs {
+            Some(rhs) => (self.operand(fx, lhs), self.operand(fx, rhs)),
+            None => ("0".to_string(), self.operand(fx, lhs))
+        };
+        let overflow = self.panic_call(ArithmeticError::Overflow, span);
+        match op {
+            BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul => {
+                writeln!(fx.code, "    if ({}) {};", overflows(op, prim, &lhs, &rhs), overflow).unwrap();
+            },
+            BinOpKind::Div | BinOpKind::Rem => {
+                let zero = self.panic_call(ArithmeticError::DivisionByZero, span);
+                writeln!(fx.code, "    if ({} == 0) {};", rhs, zero).unwrap();
+                if let PrimTy::Int(_) = prim {
+                    let bits = prim.int_bits().unwrap();
+                    let min = int_literal(prim, (-1i128 << (bits - 1)) as u128);
+                    writeln!(fx.code, "    if ({} == {} && {} == -1) {};", lhs, min, rhs, overflow).unwrap();
+                }
+            },
+            _ => {
+                // Negative amounts become too large ones when converted
+                let bits = prim.int_bits().unwrap();
+                writeln!(fx.code, "    if ((uint64_t){} >= {}) {};", rhs, bits, overflow).unwrap();
+            }
+        }
+        writeln!(fx.code, "    {} = {};", dest, binary(op, Ty::Prim(prim), &lhs, &rhs)).unwrap();
+        true
+    }
+
+    /// Call of the runtime panic with the message of the error, at the
+    /// location of the span.
+    fn panic_call(&mut self, err: ArithmeticError, span: Span) -> String {
+        let message = self.string_data(Symbol::intern(err.message()));
+        let location = self.string_data(Symbol::intern(&location(self.sources, span)));
+        format!("hasty_rt_panic({}, {})", message, location)
+    }
+
+    /// Initializer of all fields, structs without fields have one that
+    /// is never used.
+    fn fields(&mut self, fx: &FnCx, operands: &[Operand]) -> String {
+        if operands.is_empty() {
+            return "0".to_string()
+        }
+        let fields: Vec<String> = operands.iter().map(|operand| self.operand(fx, operand)).collect();
+        fields.join(", ")
+    }
+
+    fn terminator(&mut self, fx: &mut FnCx, terminator: &Terminator, next: BasicBlock) {
+        match terminator.kind {
+            TerminatorKind::Goto(target) => fx.goto(target, next),
+            TerminatorKind::If { ref cond, then, otherwise } => {
+                let cond = self.operand(fx, cond);
+                fx.labels.insert(then.index());
+                fx.labels.insert(otherwise.index());
+                writeln!(fx.code, "    if ({}) goto bb{}; else goto bb{};", cond, then.0, otherwise.0).unwrap();
+            },
+            TerminatorKind::Call { ref func, ref args, ref dest, target } => {
+                let hir = self.hir;
+                let Ty::FnDef(def) = operand_ty(hir, fx.body, func) else {
+                    unreachable!("call of a value which is not a function")
+                };
+                let is_extern = fx.externs.contains(&def);
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args.iter() {
+                    match arg {
+                        // Literals are passed as they are, which is how
+                        // strings are usually passed to externs
+                        Operand::Constant(Constant { kind: ConstKind::Str(symbol), .. }) if is_extern => {
+                            values.push(self.string_data(*symbol));
+                        },
+                        Operand::Copy(place) if is_extern && operand_ty(hir, fx.body, arg) == Ty::Prim(PrimTy::Str) => {
+                            let place = place_expr(place);
+                            values.push(format!("{}.ptr, {}.len", place, place));
+                        },
+                        _ => values.push(self.operand(fx, arg))
+                    }
+                }
+                if Intrinsic::of_def(hir, def).is_some_and(Intrinsic::takes_location) {
+                    let location = location(self.sources, terminator.span);
+                    values.push(self.string_data(Symbol::intern(&location)));
+                }
+                let call = format!("{}({})", self.names[&def], values.join(", "));
+                let returns_value = match hir.def(def).kind {
+                    DefKind::Fn { ref sig, .. } => {
+                        let ret = sig.output.as_ref().and_then(|ty| declared_ty(hir, ty)).unwrap_or(Ty::Void);
+                        !matches!(ret, Ty::Void | Ty::Never)
+                    },
+                    _ => unreachable!("called definition is not a function")
+                };
+                if returns_value {
+                    writeln!(fx.code, "    {} = {};", place_expr(dest), call).unwrap();
+                } else {
+                    writeln!(fx.code, "    {};", call).unwrap();
+                }
+                match target {
+                    Some(target) => fx.goto(target, next),
+                    None => fx.code.push_str("    abort();\n")
+                }
+            },
+            TerminatorKind::Return => {
+                let returns_value = !matches!(fx.body.return_ty(), Ty::Void | Ty::Never);
+                let value = match (Some(fx.body.owner) == self.main, returns_value) {
+                    (true, true) => "(int)_0",
+                    (true, false) => "0",
+                    (false, true) => "_0",
+                    (false, false) => return fx.code.push_str("    return;\n")
+                };
+                writeln!(fx.code, "    return {};", value).unwrap();
+            },
+            TerminatorKind::Unreachable => fx.code.push_str("    abort();\n")
+        }
+    }
+
+    fn operand(&mut self, fx: &FnCx, operand: &Operand) -> String {
+        match operand {
+            Operand::Copy(place) => {
+                // Values of these types carry nothing, and locals holding
+                // them aren't always written to, like results of calls
+                match operand_ty(self.hir, fx.body, operand) {
+                    Ty::Void | Ty::Never | Ty::FnDef(_) => "(hasty_unit){ 0 }".to_string(),
+                    _ => place_expr(place)
+                }
+            },
+            Operand::Constant(constant) => self.constant(constant)
+        }
+    }
+
+    /// Pointer to the pooled bytes of the string and their length.
+    fn string_data(&mut self, symbol: Symbol) -> String {
+        let id = self.pool.string(symbol);
+        let data = self.pool.get(id);
+        format!("{}, {}", data.name, data.bytes.len())
+    }
+
+    fn constant(&mut self, constant: &Constant) -> String {
+        match constant.kind {
+            ConstKind::Int(value) => {
+                let Ty::Prim(prim) = constant.ty else { unreachable!("integer constant of a type which is not primitive") };
+                int_literal(prim, value)
+            },
+            ConstKind::Float(value) => {
+                let suffix = if constant.ty == Ty::Prim(PrimTy::Float(FloatTy::F32)) { "f" } else { "" };
+                if value.is_nan() {
+                    format!("(0.0{} / 0.0{})", suffix, suffix)
+                } else if value.is_infinite() {
+                    format!("({}1.0{} / 0.0{})", if value < 0.0 { "-" } else { "" }, suffix, suffix)
+                } else if suffix.is_empty() {
+                    format!("{:?}", value)
+                } else {
+                    format!("{:?}f", value as f32)
+                }
+            },
+            ConstKind::Bool(value) => value.to_string(),
+            ConstKind::Char(value) => format!("{}u", value as u32),
+            ConstKind::Str(symbol) => format!("(hasty_str){{ {} }}", self.string_data(symbol)),
+            ConstKind::Fn(_) | ConstKind::Void => "(hasty_unit){ 0 }".to_string()
+        }
+    }
+
+    fn rvalue(&mut self, fx: &FnCx, rvalue: &Rvalue) -> String {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(fx, operand),
+            Rvalue::BinaryOp(op, lhs, rhs) => {
+                let ty = operand_ty(self.hir, fx.body, lhs);
+                let (lhs, rhs) = (self.operand(fx, lhs), self.operand(fx, rhs));
+                binary(op, ty, &lhs, &rhs)
+            },
+            Rvalue::UnaryOp(op, operand) => {
+                let ty = operand_ty(self.hir, fx.body, operand);
+                let value = self.operand(fx, operand);
+                match (op, ty) {
+                    (UnOpKind::Neg, _) => format!("-{}", value),
+                    (UnOpKind::Not, Ty::Prim(PrimTy::Bool)) => format!("!{}", value),
+                    (UnOpKind::Not, Ty::Prim(prim)) => format!("({})~{}", prim_ty(prim), value),
+                    (UnOpKind::Not, _) => unreachable!("`!` applied to a value which is not primitive")
+                }
+            },
+            Rvalue::Aggregate(..) => unreachable!("aggregates are assigned field by field")
+        }
+    }
+}
+
+/// Function which is being translated.
+struct FnCx<'a> {
+    body: &'a MirBody,
+    code: String,
+    /// Blocks which are jumped to.
+    labels: HashSet<usize>,
+    externs: &'a BTreeSet<DefId>
+}
+
+impl FnCx<'_> {
+    /// Jump to the block, blocks are laid out in order, so jumps to
+    /// the next block fall through.
+    fn goto(&mut self, target: BasicBlock, next: BasicBlock) {
+        if target != next {
+            self.labels.insert(target.index());
+            writeln!(self.code, "    goto bb{};", target.0).unwrap();
+        }
+    }
+}
+
+fn place_expr(place: &Place) -> String {
+    let mut expr = format!("_{}", place.local.0);
+    for elem in place.projection.iter() {
+        let PlaceElem::Field(idx) = *elem;
+        write!(expr, "._{}", idx).unwrap();
+    }
+    expr
+}
+
+/// Integers are 64 bits at most, pointer sized ones are 64 bits.
+fn prim_ty(prim: PrimTy) -> &'static str {
+    match prim {
+        PrimTy::Int(IntTy::I8) => "int8_t",
+        PrimTy::Int(IntTy::I16) => "int16_t",
+        PrimTy::Int(IntTy::I32) => "int32_t",
+        PrimTy::Int(IntTy::I64 | IntTy::Isize) => "int64_t",
+        PrimTy::Uint(UintTy::U8) => "uint8_t",
+        PrimTy::Uint(UintTy::U16) => "uint16_t",
+        PrimTy::Uint(UintTy::U32) => "uint32_t",
+        PrimTy::Uint(UintTy::U64 | UintTy::Usize) => "uint64_t",
+        PrimTy::Float(FloatTy::F32) => "float",
+        PrimTy::Float(FloatTy::F64) => "double",
+        PrimTy::Bool => "bool",
+        PrimTy::Char => "uint32_t",
+        PrimTy::Str => "hasty_str"
+    }
+}
+
+/// Unsigned type that left shifts of the integer type are done in. Small
+/// types would be promoted to `int` otherwise, which can overflow, and
+/// overflow of signed types is undefined, while unsigned ones wrap.
+fn wide_ty(prim: PrimTy) -> &'static str {
+    match prim.int_bits() {
+        Some(64) => "uint64_t",
+        _ => "uint32_t"
+    }
+}
+
+fn int_literal(prim: PrimTy, value: u128) -> String {
+    let bits = prim.int_bits().expect("integer constant of a type which is not an integer");
+    match prim {
+        PrimTy::Int(_) => {
+            let value = value as i128 as i64;
+            // Smallest values can't be written as a negated literal,
+            // as the literal itself would be too large
+            match bits {
+                64 if value == i64::MIN => "INT64_MIN".to_string(),
+                64 => format!("INT64_C({})", value),
+                32 if value == i32::MIN as i64 => "INT32_MIN".to_string(),
+                _ => value.to_string()
+            }
+        },
+        _ => {
+            let value = value & (u128::MAX >> (128 - bits));
+            match bits {
+                64 => format!("UINT64_C({})", value),
+                _ => format!("{}u", value)
+            }
+        }
+    }
+}
+
+/// Condition of addition, subtraction or multiplication overflowing its
+/// type. Overflow of signed integers is undefined in C, so it is checked
+/// before the operation, with comparisons which can't overflow themselves.
+fn overflows(op: &BinOpKind, prim: PrimTy, lhs: &str, rhs: &str) -> String {
+    let bits = prim.int_bits().expect("checked operation on a type which is not an integer");
+    let (l, r) = (lhs, rhs);
+    if let PrimTy::Int(_) = prim {
+        let (min, max) = (format!("INT{}_MIN", bits), format!("INT{}_MAX", bits));
+        match op {
+            BinOpKind::Add => format!("({r} > 0 && {l} > {max} - {r}) || ({r} < 0 && {l} < {min} - {r})"),
+            BinOpKind::Sub => format!("({r} < 0 && {l} > {max} + {r}) || ({r} > 0 && {l} < {min} + {r})"),
+            _ => format!(
+                "{l} > 0 ? ({r} > 0 ? {l} > {max} / {r} : {r} < {min} / {l}) \
+                 : ({r} > 0 ? {l} < {min} / {r} : {l} != 0 && {r} < {max} / {l})"
+            )
+        }
+    } else {
+        let max = format!("UINT{}_MAX", bits);
+        match op {
+            BinOpKind::Add => format!("{l} > {max} - {r}"),
+            BinOpKind::Sub => format!("{l} < {r}"),
+            _ => format!("{r} != 0 && {l} > {max} / {r}")
+        }
+    }
+}
+
+fn binary(op: &BinOpKind, ty: Ty, lhs: &str, rhs: &str) -> String {
+    let Ty::Prim(prim) = ty else { unreachable!("operator applied to a value which is not primitive") };
+    let symbol = match op {
+        BinOpKind::Add => "+",
+        BinOpKind::Sub => "-",
+        BinOpKind::Mul => "*",
+        BinOpKind::Div => "/",
+        BinOpKind::Rem => "%",
+        BinOpKind::And | BinOpKind::BitAnd => "&",
+        BinOpKind::Or | BinOpKind::BitOr => "|",
+        BinOpKind::BitXor => "^",
+        BinOpKind::Shl => "<<",
+        BinOpKind::Shr => ">>",
+        BinOpKind::Eq => "==",
+        BinOpKind::Ne => "!=",
+        BinOpKind::Lt => "<",
+        BinOpKind::Le => "<=",
+        BinOpKind::Gt => ">",
+        BinOpKind::Ge => ">="
+    };
+    let is_comparison = matches!(op, BinOpKind::Eq | BinOpKind::Ne | BinOpKind::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge);
+    match prim {
+        PrimTy::Str => format!("(hasty_str_cmp({}, {}) {} 0)", lhs, rhs, symbol),
+        PrimTy::Float(float) if matches!(op, BinOpKind::Rem) => {
+            let function = if float == FloatTy::F32 { "fmodf" } else { "fmod" };
+            format!("{}({}, {})", function, lhs, rhs)
+        },
+        _ if is_comparison || !prim.is_integer() => format!("({} {} {})", lhs, symbol, rhs),
+        _ => {
+            let (ty, wide) = (prim_ty(prim), wide_ty(prim));
+            match op {
+                // Shifting by the size of the type or more is undefined, such
+                // shifts panic before, but the amount is still taken modulo
+                // the size, so the compiler doesn't warn about constant ones
+                BinOpKind::Shl => {
+                    let bits = prim.int_bits().unwrap();
+                    format!("({})(({}){} << ({} & {}))", ty, wide, lhs, rhs, bits - 1)
+                },
+                BinOpKind::Shr => {
+                    let bits = prim.int_bits().unwrap();
+                    format!("({})({} >> ({} & {}))", ty, lhs, rhs, bits - 1)
+                },
+                _ => format!("({})({} {} {})", ty, lhs, symbol, rhs)
+            }
+        }
+    }
+}
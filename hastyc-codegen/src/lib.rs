@@ -0,0 +1,34 @@
+use std::fmt::Display;
+
+pub mod backend;
+pub mod c;
+pub mod link;
+pub mod mangle;
+pub mod pool;
+pub mod runtime;
+#[cfg(feature = "llvm")]
+pub mod llvm;
+pub mod vm;
+mod util;
+
+#[derive(Debug, Clone)]
+pub enum CodegenError {
+    /// LLVM failed, or rejected the module it was given.
+    Llvm(String),
+    /// Code uses something that the backend can't compile yet.
+    Unsupported(&'static str),
+    /// Backend with the name was left out when the compiler was built.
+    Disabled(&'static str)
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Llvm(err) => write!(f, "LLVM error: {}", err),
+            Self::Unsupported(what) => write!(f, "{} is not supported by this backend yet", what),
+            Self::Disabled(name) => write!(f, "compiler was built without the `{}` backend", name)
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
@@ -0,0 +1,145 @@
+use std::{fmt::Display, path::{Path, PathBuf}, process::Command};
+
+use crate::runtime;
+
+/// Tools which link object files from native backends into executables.
+/// C compiler drives the system linker, and builds the runtime library,
+/// which is archived into a static library with `ar`.
+#[derive(Debug, Clone)]
+pub struct Linker {
+    cc: Tool,
+    ar: Tool
+}
+
+/// Program together with arguments it is always run with, eg. `ccache cc`.
+#[derive(Debug, Clone)]
+struct Tool {
+    program: PathBuf,
+    args: Vec<String>
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    /// None of the tools of the kind was found, with names that were tried.
+    NotFound { kind: &'static str, tried: Vec<String>, env: &'static str },
+    Io { path: PathBuf, err: std::io::Error },
+    /// Tool couldn't be started.
+    Spawn { program: PathBuf, err: std::io::Error },
+    /// Tool ran, but didn't succeed.
+    Failed { program: PathBuf, status: std::process::ExitStatus, stderr: String }
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound { kind, tried, env } => write!(
+                f, "no {} found to link with, tried `{}`; install one or point `{}` to it",
+                kind, tried.join("`, `"), env
+            ),
+            Self::Io { path, err } => write!(f, "can't write `{}`: {}", path.display(), err),
+            Self::Spawn { program, err } => write!(f, "can't run `{}`: {}", program.display(), err),
+            Self::Failed { program, status, stderr } => {
+                write!(f, "`{}` failed with {}", program.display(), status)?;
+                if !stderr.trim().is_empty() {
+                    write!(f, "\n{}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl Linker {
+    /// Find tools in `PATH`, unless `CC` or `AR` say which to use.
+    pub fn find() -> Result<Self, LinkError> {
+        let cc = Tool::find("C compiler", "CC", &["cc", "gcc", "clang"])?;
+        let ar = Tool::find("archiver", "AR", &["ar", "llvm-ar"])?;
+        Ok(Self { cc, ar })
+    }
+
+    /// Link the object file with the runtime into an executable at the path.
+    /// Intermediate files are put in a directory of their own, which is
+    /// removed afterwards, whether linking succeeded or not.
+    pub fn link(&self, object: &[u8], out: &Path) -> Result<(), LinkError> {
+        let dir = std::env::temp_dir().join(format!("hastyc-link-{}", std::process::id()));
+        let result = self.link_in(&dir, object, out);
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    /// Compile output of the C backend into an executable at the path, it
+    /// has the runtime in it, so nothing else has to be linked.
+    pub fn compile_source(&self, source: &str, out: &Path) -> Result<(), LinkError> {
+        let dir = std::env::temp_dir().join(format!("hastyc-cc-{}", std::process::id()));
+        let path = write(&dir, "main.c", source.as_bytes());
+        let result = path.and_then(|path| self.cc.run(|cmd| cmd.args(["-std=c99", "-O2"]).arg(&path).arg("-lm").arg("-o").arg(out)));
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    fn link_in(&self, dir: &Path, object: &[u8], out: &Path) -> Result<(), LinkError> {
+        write(dir, "hasty_rt.h", runtime::HEADER.as_bytes())?;
+        let source = write(dir, "hasty_rt.c", runtime::SOURCE.as_bytes())?;
+        let object_path = write(dir, "main.o", object)?;
+        let runtime_object = dir.join("hasty_rt.o");
+        self.cc.run(|cmd| cmd.args(["-std=c99", "-O2", "-c"]).arg(&source).arg("-o").arg(&runtime_object))?;
+        let library = dir.join("libhasty_rt.a");
+        self.ar.run(|cmd| cmd.arg("rcs").arg(&library).arg(&runtime_object))?;
+        self.cc.run(|cmd| cmd.arg(&object_path).arg(&library).arg("-lm").arg("-o").arg(out))
+    }
+}
+
+impl Tool {
+    fn find(kind: &'static str, env: &'static str, names: &[&str]) -> Result<Self, LinkError> {
+        if let Ok(value) = std::env::var(env) {
+            let mut words = value.split_whitespace().map(str::to_string);
+            if let Some(program) = words.next() {
+                return match search_path(&program) {
+                    Some(path) => Ok(Self { program: path, args: words.collect() }),
+                    None => Err(LinkError::NotFound { kind, tried: vec![program], env })
+                }
+            }
+        }
+        names.iter()
+            .find_map(|name| search_path(name))
+            .map(|program| Self { program, args: Vec::new() })
+            .ok_or_else(|| LinkError::NotFound { kind, tried: names.iter().map(|name| name.to_string()).collect(), env })
+    }
+
+    fn run(&self, args: impl FnOnce(&mut Command) -> &mut Command) -> Result<(), LinkError> {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        args(&mut cmd);
+        let output = cmd.output().map_err(|err| LinkError::Spawn { program: self.program.clone(), err })?;
+        if !output.status.success() {
+            return Err(LinkError::Failed {
+                program: self.program.clone(),
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned()
+            })
+        }
+        Ok(())
+    }
+}
+
+/// Path of the program, names with a directory in them are used as they are.
+fn search_path(name: &str) -> Option<PathBuf> {
+    let name = Path::new(name);
+    if name.components().count() > 1 {
+        return name.is_file().then(|| name.to_path_buf())
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+fn write(dir: &Path, name: &str, bytes: &[u8]) -> Result<PathBuf, LinkError> {
+    let path = dir.join(name);
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&path, bytes))
+        .map_err(|err| LinkError::Io { path: path.clone(), err })?;
+    Ok(path)
+}
@@ -0,0 +1,743 @@
+use std::collections::{HashMap, HashSet};
+
+use hastyc_common::{identifiers::{SourceFileID, Symbol, SymbolStorage}, source::{FileName, SourceMap}, span::Span};
+use hastyc_parser::parser::{BinOpKind, UnOpKind};
+use hastyc_passes::{hir::{ty::Ty, typeck::declared_ty, DefId, DefKind, Hir}, mir::{BasicBlock, ConstKind, Constant, MirBody, Operand, Place, PlaceElem, Rvalue, StatementKind, Terminator, TerminatorKind}, passes::{builtin::{FloatTy, PrimTy}, intrinsic::{location, ArithmeticError, Intrinsic}}};
+use inkwell::{
+    basic_block::BasicBlock as LlvmBlock,
+    builder::{Builder, BuilderError},
+    context::Context,
+    intrinsics::Intrinsic as LlvmIntrinsic,
+    debug_info::{AsDIScope, DICompileUnit, DIFile, DIFlags, DIFlagsConstants, DIScope, DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder},
+    module::{FlagBehavior, Linkage, Module},
+    targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine},
+    types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, StructType},
+    values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue, InstructionValue, IntValue, PointerValue},
+    AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel
+};
+
+use crate::{backend::{Artifact, CodegenBackend}, mangle::symbol_names, pool::{ConstPool, DataId}, util::{field_ty, operand_ty}, CodegenError};
+
+impl From<BuilderError> for CodegenError {
+    fn from(err: BuilderError) -> Self {
+        Self::Llvm(err.to_string())
+    }
+}
+
+/// Backend compiling MIR of the package to an object file for the host
+/// target, which has to be linked with the runtime, see `crate::runtime`.
+/// The module lives in the given context, which has to outlive the backend.
+/// Objects have DWARF line tables, so debuggers show the source lines.
+/// Integer arithmetic that overflows, division by zero and shifts by
+/// the size of the type or more call the runtime panic with the message
+/// the VM fails with.
+pub struct LlvmBackend<'a, 'ctx> {
+    cx: CodegenCx<'a, 'ctx>,
+    machine: TargetMachine
+}
+
+impl<'a, 'ctx> LlvmBackend<'a, 'ctx> {
+    pub fn new(context: &'ctx Context, hir: &'a Hir, symbols: &'a SymbolStorage, sources: &'a SourceMap) -> Result<Self, CodegenError> {
+        let machine = host_machine()?;
+        let module = context.create_module("package");
+        module.set_triple(&machine.get_triple());
+        module.set_data_layout(&machine.get_target_data().get_data_layout());
+        let cx = CodegenCx {
+            context,
+            module,
+            builder: context.create_builder(),
+            target_data: machine.get_target_data(),
+            hir,
+            symbols,
+            sources,
+            names: symbol_names(hir, symbols),
+            functions: HashMap::new(),
+            externs: HashSet::new(),
+            adts: HashMap::new(),
+            pool: ConstPool::new(symbols),
+            data: HashMap::new(),
+            debug: None
+        };
+        Ok(Self { cx, machine })
+    }
+
+    /// Textual IR of the module, for debugging.
+    pub fn ir(&self) -> String {
+        self.cx.module.print_to_string().to_string()
+    }
+}
+
+impl CodegenBackend for LlvmBackend<'_, '_> {
+    fn name(&self) -> &'static str {
+        "llvm"
+    }
+
+    fn declare_function(&mut self, def: DefId, body: Option<&MirBody>) -> Result<(), CodegenError> {
+        let cx = &mut self.cx;
+        let Some(body) = body else {
+            cx.function(def);
+            return Ok(())
+        };
+        let params: Vec<Ty> = body.args().map(|arg| body.local(arg).ty).collect();
+        let fn_ty = cx.fn_ty(&params, body.return_ty(), false);
+        let function = cx.module.add_function(&cx.names[&def], fn_ty, None);
+        cx.functions.insert(def, function);
+        Ok(())
+    }
+
+    fn define_body(&mut self, body: &MirBody) -> Result<(), CodegenError> {
+        self.cx.body(body)
+    }
+
+    fn finish(&mut self) -> Result<Artifact, CodegenError> {
+        if let Some(ref debug) = self.cx.debug {
+            debug.builder.finalize();
+        }
+        self.cx.module.verify().map_err(|err| CodegenError::Llvm(err.to_string()))?;
+        let buffer = self.machine
+            .write_to_memory_buffer(&self.cx.module, FileType::Object)
+            .map_err(|err| CodegenError::Llvm(err.to_string()))?;
+        Ok(Artifact::Object(buffer.as_slice().to_vec()))
+    }
+}
+
+fn host_machine() -> Result<TargetMachine, CodegenError> {
+    Target::initialize_native(&InitializationConfig::default()).map_err(CodegenError::Llvm)?;
+    let triple = TargetMachine::get_default_triple();
+    let target = Target::from_triple(&triple).map_err(|err| CodegenError::Llvm(err.to_string()))?;
+    target
+        .create_target_machine(
+            &triple,
+            &TargetMachine::get_host_cpu_name().to_string(),
+            &TargetMachine::get_host_cpu_features().to_string(),
+            OptimizationLevel::Default,
+            RelocMode::PIC,
+            CodeModel::Default
+        )
+        .ok_or_else(|| CodegenError::Llvm(format!("no target machine for `{}`", triple)))
+}
+
+struct CodegenCx<'a, 'ctx> {
+    context: &'ctx Context,
+    module: Module<'ctx>,
+    builder: Builder<'ctx>,
+    target_data: TargetData,
+    hir: &'a Hir,
+    symbols: &'a SymbolStorage,
+    sources: &'a SourceMap,
+    names: HashMap<DefId, String>,
+    functions: HashMap<DefId, FunctionValue<'ctx>>,
+    /// Functions without a body, their `str` parameters are passed as
+    /// a pointer and a length, like C would pass them.
+    externs: HashSet<DefId>,
+    /// Types of structs, enums and variants.
+    adts: HashMap<DefId, StructType<'ctx>>,
+    pool: ConstPool<'a>,
+    /// Globals of data in the pool.
+    data: HashMap<DataId, PointerValue<'ctx>>,
+    /// Created with the first body, as the compile unit needs a file.
+    debug: Option<DebugCx<'ctx>>
+}
+
+/// Debug info of the module, only line tables are emitted.
+struct DebugCx<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    unit: DICompileUnit<'ctx>,
+    files: HashMap<SourceFileID, DIFile<'ctx>>
+}
+
+/// Function which is being compiled.
+struct FnCx<'a, 'ctx> {
+    body: &'a MirBody,
+    /// Subprogram of the function, None if the body has no source.
+    scope: Option<DIScope<'ctx>>,
+    /// Stack slot of every local, values live in memory and LLVM promotes
+    /// them to registers, so no phi nodes have to be built.
+    locals: Vec<PointerValue<'ctx>>,
+    blocks: Vec<LlvmBlock<'ctx>>,
+    /// Branch at the end of the entry block, temporaries are allocated
+    /// before it, so that they are allocated once per call.
+    entry_branch: InstructionValue<'ctx>
+}
+
+impl<'a, 'ctx> CodegenCx<'a, 'ctx> {
+    /// Values of `()`, `!` and functions take no space.
+    fn unit_ty(&self) -> StructType<'ctx> {
+        self.context.struct_type(&[], false)
+    }
+
+    fn i8_ptr_ty(&self) -> BasicTypeEnum<'ctx> {
+        self.context.i8_type().ptr_type(AddressSpace::default()).into()
+    }
+
+    /// Strings are a pointer to their UTF-8 bytes and their length.
+    fn str_ty(&self) -> StructType<'ctx> {
+        self.context.struct_type(&[self.i8_ptr_ty(), self.context.i64_type().into()], false)
+    }
+
+    fn ty(&mut self, ty: Ty) -> BasicTypeEnum<'ctx> {
+        match ty {
+            Ty::Prim(PrimTy::Float(FloatTy::F32)) => self.context.f32_type().into(),
+            Ty::Prim(PrimTy::Float(FloatTy::F64)) => self.context.f64_type().into(),
+            Ty::Prim(PrimTy::Bool) => self.context.bool_type().into(),
+            Ty::Prim(PrimTy::Char) => self.context.i32_type().into(),
+            Ty::Prim(PrimTy::Str) => self.str_ty().into(),
+            Ty::Prim(prim) => match prim.int_bits().expect("primitive is not an integer") {
+                8 => self.context.i8_type().into(),
+                16 => self.context.i16_type().into(),
+                32 => self.context.i32_type().into(),
+                _ => self.context.i64_type().into()
+            },
+            Ty::Adt(def) => self.adt_ty(def).into(),
+            Ty::FnDef(_) | Ty::Void | Ty::Never => self.unit_ty().into(),
+            Ty::Var(_) | Ty::Err => unreachable!("MIR of a package with errors")
+        }
+    }
+
+    /// Structs and variants are LLVM structs of their fields. Enums are
+    /// a tag, which is the index of the variant, followed by space for
+    /// the largest variant, of `i64`s so that every variant is aligned.
+    fn adt_ty(&mut self, def: DefId) -> StructType<'ctx> {
+        if let Some(ty) = self.adts.get(&def) {
+            return *ty
+        }
+        let hir = self.hir;
+        let ty = self.context.opaque_struct_type(&format!("{}.{}", hir.def(def).ident.text(self.symbols), def.index.0));
+        self.adts.insert(def, ty);
+        let body: Vec<BasicTypeEnum> = match hir.def(def).kind {
+            DefKind::Struct { ref fields, .. } | DefKind::Variant { ref fields, .. } => {
+                fields.iter().map(|field| self.field_ty(*field)).collect()
+            },
+            DefKind::Enum { ref variants } => {
+                let mut size = 0;
+                for variant in variants.iter() {
+                    let variant = self.adt_ty(*variant);
+                    size = size.max(self.target_data.get_abi_size(&variant));
+                }
+                let payload = self.context.i64_type().array_type(size.div_ceil(8) as u32);
+                vec![self.context.i32_type().into(), payload.into()]
+            },
+            _ => unreachable!("type of a definition which is not a struct or enum")
+        };
+        ty.set_body(&body, false);
+        ty
+    }
+
+    fn field_ty(&mut self, field: DefId) -> BasicTypeEnum<'ctx> {
+        let ty = field_ty(self.hir, field);
+        self.ty(ty)
+    }
+
+    /// Type of a function, `()` and `!` are returned as LLVM `void`.
+    fn fn_ty(&mut self, params: &[Ty], ret: Ty, is_extern: bool) -> FunctionType<'ctx> {
+        let mut param_tys: Vec<BasicMetadataTypeEnum> = Vec::new();
+        for param in params.iter() {
+            match param {
+                Ty::Prim(PrimTy::Str) if is_extern => {
+                    param_tys.push(self.i8_ptr_ty().into());
+                    param_tys.push(self.context.i64_type().into());
+                },
+                _ => param_tys.push(self.ty(*param).into())
+            }
+        }
+        match ret {
+            Ty::Void | Ty::Never => self.context.void_type().fn_type(&param_tys, false),
+            _ => self.ty(ret).fn_type(&param_tys, false)
+        }
+    }
+
+    /// Function of the definition, functions without a body are
+    /// declared the first time they are called. Location passed to
+    /// intrinsics is a `str` after the other parameters.
+    fn function(&mut self, def: DefId) -> FunctionValue<'ctx> {
+        if let Some(function) = self.functions.get(&def) {
+            return *function
+        }
+        let hir = self.hir;
+        let DefKind::Fn { ref sig, .. } = hir.def(def).kind else { unreachable!("called definition is not a function") };
+        let mut params: Vec<Ty> = sig.inputs.iter().map(|ty| declared_ty(hir, ty).unwrap_or(Ty::Err)).collect();
+        if Intrinsic::of_def(hir, def).is_some_and(Intrinsic::takes_location) {
+            params.push(Ty::Prim(PrimTy::Str));
+        }
+        let ret = sig.output.as_ref().and_then(|ty| declared_ty(hir, ty)).unwrap_or(Ty::Void);
+        let fn_ty = self.fn_ty(&params, ret, true);
+        // Runtime panic may already be declared for failed arithmetic
+        let name = &self.names[&def];
+        let function = self.module.get_function(name)
+            .unwrap_or_else(|| self.module.add_function(name, fn_ty, Some(Linkage::External)));
+        self.functions.insert(def, function);
+        self.externs.insert(def);
+        function
+    }
+
+    fn body(&mut self, body: &MirBody) -> Result<(), CodegenError> {
+        let function = self.functions[&body.owner];
+        let entry = self.context.append_basic_block(function, "start");
+        let blocks: Vec<LlvmBlock> = body.basic_blocks()
+            .map(|(id, _)| self.context.append_basic_block(function, &format!("bb{}", id.0)))
+            .collect();
+        self.builder.position_at_end(entry);
+        let mut locals = Vec::with_capacity(body.locals.len());
+        for (idx, local) in body.locals.iter().enumerate() {
+            let name = match local.name {
+                Some(ref ident) => ident.text(self.symbols).to_string(),
+                None => format!("_{}", idx)
+            };
+            let ty = self.ty(local.ty);
+            locals.push(self.builder.build_alloca(ty, &name)?);
+        }
+        for (param, arg) in function.get_param_iter().zip(body.args()) {
+            self.builder.build_store(locals[arg.index()], param)?;
+        }
+        let entry_branch = self.builder.build_unconditional_branch(blocks[0])?;
+        let scope = self.subprogram(function, body);
+        let mut fx = FnCx { body, scope, locals, blocks, entry_branch };
+        for (id, block) in body.basic_blocks() {
+            self.builder.position_at_end(fx.blocks[id.index()]);
+            for stmt in block.stmts.iter() {
+                self.locate(&fx, stmt.span);
+                match stmt.kind {
+                    StatementKind::Assign(ref place, ref rvalue) => {
+                        let value = self.rvalue(&mut fx, rvalue, stmt.span)?;
+                        let ptr = self.place(&fx, place)?;
+                        self.builder.build_store(ptr, value)?;
+                    },
+                    StatementKind::Nop => {}
+                }
+            }
+            self.locate(&fx, block.terminator.span);
+            self.terminator(&mut fx, &block.terminator)?;
+        }
+        self.builder.unset_current_debug_location();
+        Ok(())
+    }
+
+    /// Attach debug info of the body to the function, returning its scope.
+    fn subprogram(&mut self, function: FunctionValue<'ctx>, body: &MirBody) -> Option<DIScope<'ctx>> {
+        let file = self.debug_file(body.span.source)?;
+        let (line, _) = self.sources.get(body.span.source)?.lookup_line(body.span.start);
+        let debug = self.debug.as_ref()?;
+        let fn_ty = debug.builder.create_subroutine_type(file, None, &[], DIFlags::ZERO);
+        let name = self.hir.def(body.owner).ident.text(self.symbols);
+        let subprogram = debug.builder.create_function(
+            debug.unit.as_debug_info_scope(),
+            name,
+            Some(&self.names[&body.owner]),
+            file,
+            line,
+            fn_ty,
+            false,
+            true,
+            line,
+            DIFlags::ZERO,
+            false
+        );
+        function.set_subprogram(subprogram);
+        Some(subprogram.as_debug_info_scope())
+    }
+
+    /// Debug info of the file, creating the compile unit for the first one.
+    fn debug_file(&mut self, source: SourceFileID) -> Option<DIFile<'ctx>> {
+        if let Some(file) = self.debug.as_ref().and_then(|debug| debug.files.get(&source)) {
+            return Some(*file)
+        }
+        let file = self.sources.get(source)?;
+        let (name, directory) = match file.name {
+            FileName::LocalPath(ref path) => {
+                let path = self.sources.full_path(&file.name).unwrap_or_else(|| path.clone());
+                let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                let directory = path.parent().map(|dir| dir.to_string_lossy().into_owned()).unwrap_or_default();
+                (name, directory)
+            },
+            ref name => (name.to_string(), String::new())
+        };
+        let debug = match self.debug {
+            Some(ref mut debug) => debug,
+            None => {
+                let version = self.context.i32_type().const_int(3, false);
+                self.module.add_basic_value_flag("Debug Info Version", FlagBehavior::Warning, version);
+                let (builder, unit) = self.module.create_debug_info_builder(
+                    true,
+                    DWARFSourceLanguage::C,
+                    &name,
+                    &directory,
+                    "hastyc",
+                    false,
+                    "",
+                    0,
+                    "",
+                    DWARFEmissionKind::LineTablesOnly,
+                    0,
+                    false,
+                    false,
+                    "",
+                    ""
+                );
+                self.debug.insert(DebugCx { builder, unit, files: HashMap::new() })
+            }
+        };
+        let di_file = debug.builder.create_file(&name, &directory);
+        debug.files.insert(source, di_file);
+        Some(di_file)
+    }
+
+    /// Mark instructions which follow as compiled from the span.
+    fn locate(&self, fx: &FnCx<'_, 'ctx>, span: Span) {
+        let (Some(scope), Some(debug), Some(file)) = (fx.scope, self.debug.as_ref(), self.sources.get(span.source)) else { return };
+        let (line, col) = file.lookup_line(span.start);
+        let location = debug.builder.create_debug_location(self.context, line, col, scope, None);
+        self.builder.set_current_debug_location(location);
+    }
+
+    fn terminator(&mut self, fx: &mut FnCx<'_, 'ctx>, terminator: &Terminator) -> Result<(), CodegenError> {
+        match terminator.kind {
+            TerminatorKind::Goto(target) => {
+                self.builder.build_unconditional_branch(fx.block(target))?;
+            },
+            TerminatorKind::If { ref cond, then, otherwise } => {
+                let cond = self.operand(fx, cond)?.into_int_value();
+                self.builder.build_conditional_branch(cond, fx.block(then), fx.block(otherwise))?;
+            },
+            TerminatorKind::Call { ref func, ref args, ref dest, target } => {
+                // Functions are known from their types, as there are no
+                // function pointers yet
+                let def = match operand_ty(self.hir, fx.body, func) {
+                    Ty::FnDef(def) => def,
+                    _ => return Err(CodegenError::Unsupported("call of a value which is not a function"))
+                };
+                let function = self.function(def);
+                let is_extern = self.externs.contains(&def);
+                let mut values: Vec<BasicMetadataValueEnum> = Vec::new();
+                for arg in args.iter() {
+                    let value = self.operand(fx, arg)?;
+                    match operand_ty(self.hir, fx.body, arg) {
+                        Ty::Prim(PrimTy::Str) if is_extern => {
+                            let string = value.into_struct_value();
+                            values.push(self.builder.build_extract_value(string, 0, "ptr")?.into());
+                            values.push(self.builder.build_extract_value(string, 1, "len")?.into());
+                        },
+                        _ => values.push(value.into())
+                    }
+                }
+                if Intrinsic::of_def(self.hir, def).is_some_and(Intrinsic::takes_location) {
+                    let (ptr, len) = self.string_data(Symbol::intern(&location(self.sources, terminator.span)));
+                    values.push(ptr.into());
+                    values.push(len.into());
+                }
+                let call = self.builder.build_call(function, &values, "")?;
+                if let Some(result) = call.try_as_basic_value().basic() {
+                    let ptr = self.place(fx, dest)?;
+                    self.builder.build_store(ptr, result)?;
+                }
+                match target {
+                    Some(target) => self.builder.build_unconditional_branch(fx.block(target))?,
+                    None => self.builder.build_unreachable()?
+                };
+            },
+            TerminatorKind::Return => match fx.body.return_ty() {
+                Ty::Void | Ty::Never => {
+                    self.builder.build_return(None)?;
+                },
+                ty => {
+                    let ty = self.ty(ty);
+                    let value = self.builder.build_load(fx.locals[0], "ret")?;
+                    debug_assert_eq!(value.get_type(), ty);
+                    self.builder.build_return(Some(&value))?;
+                }
+            },
+            TerminatorKind::Unreachable => {
+                self.builder.build_unreachable()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn place(&mut self, fx: &FnCx<'_, 'ctx>, place: &Place) -> Result<PointerValue<'ctx>, CodegenError> {
+        let mut ptr = fx.locals[place.local.index()];
+        for elem in place.projection.iter() {
+            let PlaceElem::Field(idx) = *elem;
+            ptr = self.builder.build_struct_gep(ptr, idx, "field")?;
+        }
+        Ok(ptr)
+    }
+
+    fn operand(&mut self, fx: &FnCx<'_, 'ctx>, operand: &Operand) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        match operand {
+            Operand::Copy(place) => {
+                let ptr = self.place(fx, place)?;
+                Ok(self.builder.build_load(ptr, "")?)
+            },
+            Operand::Constant(constant) => Ok(self.constant(constant))
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) -> BasicValueEnum<'ctx> {
+        match constant.kind {
+            // Values are stored sign extended, LLVM truncates them to the type
+            ConstKind::Int(value) => self.ty(constant.ty).into_int_type().const_int(value as u64, false).into(),
+            ConstKind::Float(value) => self.ty(constant.ty).into_float_type().const_float(value).into(),
+            ConstKind::Bool(value) => self.context.bool_type().const_int(value as u64, false).into(),
+            ConstKind::Char(value) => self.context.i32_type().const_int(value as u64, false).into(),
+            ConstKind::Str(symbol) => {
+                let (ptr, len) = self.string_data(symbol);
+                self.context.const_struct(&[ptr.into(), len.into()], false).into()
+            },
+            ConstKind::Fn(_) | ConstKind::Void => self.unit_ty().const_zero().into()
+        }
+    }
+
+    /// Pointer to the pooled bytes of the string and their length, the
+    /// bytes are put in read-only data the first time they are used.
+    fn string_data(&mut self, symbol: Symbol) -> (PointerValue<'ctx>, IntValue<'ctx>) {
+        let id = self.pool.string(symbol);
+        let len = self.context.i64_type().const_int(self.pool.get(id).bytes.len() as u64, false);
+        if let Some(ptr) = self.data.get(&id) {
+            return (*ptr, len)
+        }
+        let constant = self.pool.get(id);
+        let data = self.context.const_string(&constant.bytes, false);
+        let global = self.module.add_global(data.get_type(), None, &constant.name);
+        global.set_initializer(&data);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.set_unnamed_addr(true);
+        let ptr = global.as_pointer_value().const_cast(self.i8_ptr_ty().into_pointer_type());
+        self.data.insert(id, ptr);
+        (ptr, len)
+    }
+
+    /// Value of the rvalue, operations which fail panic at the span.
+    fn rvalue(&mut self, fx: &mut FnCx<'_, 'ctx>, rvalue: &Rvalue, span: Span) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(fx, operand),
+            Rvalue::BinaryOp(op, lhs, rhs) => {
+                let (ty, rhs_ty) = (operand_ty(self.hir, fx.body, lhs), operand_ty(self.hir, fx.body, rhs));
+                let lhs = self.operand(fx, lhs)?;
+                let rhs = self.operand(fx, rhs)?;
+                self.binary(op, ty, lhs, rhs, rhs_ty, span)
+            },
+            Rvalue::UnaryOp(op, operand) => {
+                let ty = operand_ty(self.hir, fx.body, operand);
+                let value = self.operand(fx, operand)?;
+                Ok(match (op, ty) {
+                    (UnOpKind::Neg, Ty::Prim(PrimTy::Float(_))) => self.builder.build_float_neg(value.into_float_value(), "neg")?.into(),
+                    // Only signed integers can be negated, which is subtraction from zero
+                    (UnOpKind::Neg, _) => {
+                        let value = value.into_int_value();
+                        self.overflowing("llvm.ssub.with.overflow", value.get_type().const_zero(), value, span)?.into()
+                    },
+                    (UnOpKind::Not, _) => self.builder.build_not(value.into_int_value(), "not")?.into()
+                })
+            },
+            Rvalue::Aggregate(def, operands) => {
+                let mut values = Vec::with_capacity(operands.len());
+                for operand in operands.iter() {
+                    values.push(self.operand(fx, operand)?);
+                }
+                self.aggregate(fx, *def, &values)
+            }
+        }
+    }
+
+    fn aggregate(&mut self, fx: &mut FnCx<'_, 'ctx>, def: DefId, values: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let ty = self.adt_ty(def);
+        let mut value = ty.get_undef();
+        for (idx, field) in values.iter().enumerate() {
+            value = self.builder.build_insert_value(value, *field, idx as u32, "")?.into_struct_value();
+        }
+        let hir = self.hir;
+        let DefKind::Variant { .. } = hir.def(def).kind else { return Ok(value.into()) };
+        // Variant is written into the payload of its enum through memory,
+        // as LLVM can't reinterpret struct values
+        let enum_def = hir.def(def).parent.expect("variant without an enum");
+        let DefKind::Enum { ref variants } = hir.def(enum_def).kind else { unreachable!("parent of a variant is not an enum") };
+        let tag = variants.iter().position(|variant| *variant == def).unwrap();
+        let enum_ty = self.adt_ty(enum_def);
+        let temp = self.entry_alloca(fx, enum_ty.into())?;
+        let tag_ptr = self.builder.build_struct_gep(temp, 0, "tag")?;
+        self.builder.build_store(tag_ptr, self.context.i32_type().const_int(tag as u64, false))?;
+        let payload = self.builder.build_struct_gep(temp, 1, "payload")?;
+        let payload = self.builder.build_pointer_cast(payload, ty.ptr_type(AddressSpace::default()), "variant")?;
+        self.builder.build_store(payload, value)?;
+        Ok(self.builder.build_load(temp, "")?)
+    }
+
+    /// Stack slot in the entry block of the function.
+    fn entry_alloca(&self, fx: &FnCx<'_, 'ctx>, ty: BasicTypeEnum<'ctx>) -> Result<PointerValue<'ctx>, CodegenError> {
+        let builder = self.context.create_builder();
+        builder.position_before(&fx.entry_branch);
+        Ok(builder.build_alloca(ty, "tmp")?)
+    }
+
+    /// Call of the runtime panic, declared the first time it is used.
+    fn runtime_panic(&mut self) -> FunctionValue<'ctx> {
+        let name = Intrinsic::Panic.runtime_symbol();
+        if let Some(function) = self.module.get_function(name) {
+            return function
+        }
+        let (ptr, len) = (self.i8_ptr_ty().into(), self.context.i64_type().into());
+        let fn_ty = self.context.void_type().fn_type(&[ptr, len, ptr, len], false);
+        self.module.add_function(name, fn_ty, Some(Linkage::External))
+    }
+
+    /// Branch to a call of the runtime panic with the message of the
+    /// error at the span if `failed` is true, code built after this
+    /// runs if it is false.
+    fn panic_if(&mut self, failed: IntValue<'ctx>, err: ArithmeticError, span: Span) -> Result<(), CodegenError> {
+        let current = self.builder.get_insert_block().expect("builder is not in a block");
+        let function = current.get_parent().expect("block is not in a function");
+        let panic_block = self.context.append_basic_block(function, "panic");
+        let ok = self.context.append_basic_block(function, "ok");
+        self.builder.build_conditional_branch(failed, panic_block, ok)?;
+        self.builder.position_at_end(panic_block);
+        let (message, message_len) = self.string_data(Symbol::intern(err.message()));
+        let (location, location_len) = self.string_data(Symbol::intern(&location(self.sources, span)));
+        let panic = self.runtime_panic();
+        self.builder.build_call(panic, &[message.into(), message_len.into(), location.into(), location_len.into()], "")?;
+        self.builder.build_unreachable()?;
+        self.builder.position_at_end(ok);
+        Ok(())
+    }
+
+    /// Result of the `llvm.*.with.overflow` intrinsic, panicking if it overflowed.
+    fn overflowing(&mut self, name: &str, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, span: Span) -> Result<IntValue<'ctx>, CodegenError> {
+        let intrinsic = LlvmIntrinsic::find(name).ok_or_else(|| CodegenError::Llvm(format!("no intrinsic `{}`", name)))?;
+        let function = intrinsic.get_declaration(&self.module, &[lhs.get_type().into()])
+            .ok_or_else(|| CodegenError::Llvm(format!("can't declare `{}`", name)))?;
+        let result = self.builder.build_call(function, &[lhs.into(), rhs.into()], "")?
+            .try_as_basic_value()
+            .basic()
+            .expect("overflow intrinsic without a result")
+            .into_struct_value();
+        let value = self.builder.build_extract_value(result, 0, "")?.into_int_value();
+        let overflowed = self.builder.build_extract_value(result, 1, "overflowed")?.into_int_value();
+        self.panic_if(overflowed, ArithmeticError::Overflow, span)?;
+        Ok(value)
+    }
+
+    /// Panic if the integer operation would fail, after this it can't overflow.
+    fn check_int_op(&mut self, op: &BinOpKind, prim: PrimTy, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, rhs_ty: Ty, span: Span) -> Result<(), CodegenError> {
+        let bits = prim.int_bits().expect("checked operation on a value which is not an integer");
+        let b = &self.builder;
+        match op {
+            BinOpKind::Div | BinOpKind::Rem => {
+                let is_zero = b.build_int_compare(IntPredicate::EQ, rhs, rhs.get_type().const_zero(), "is_zero")?;
+                self.panic_if(is_zero, ArithmeticError::DivisionByZero, span)?;
+                if let PrimTy::Int(_) = prim {
+                    let b = &self.builder;
+                    let ty = lhs.get_type();
+                    let is_min = b.build_int_compare(IntPredicate::EQ, lhs, ty.const_int(1 << (bits - 1), false), "is_min")?;
+                    let is_minus_one = b.build_int_compare(IntPredicate::EQ, rhs, ty.const_all_ones(), "is_minus_one")?;
+                    let overflows = b.build_and(is_min, is_minus_one, "")?;
+                    self.panic_if(overflows, ArithmeticError::Overflow, span)?;
+                }
+            },
+            BinOpKind::Shl | BinOpKind::Shr => {
+                // Negative amounts become too large ones when sign extended
+                let i64_ty = self.context.i64_type();
+                let signed = matches!(rhs_ty, Ty::Prim(PrimTy::Int(_)));
+                let amount = b.build_int_cast_sign_flag(rhs, i64_ty, signed, "")?;
+                let too_large = b.build_int_compare(IntPredicate::UGE, amount, i64_ty.const_int(bits as u64, false), "too_large")?;
+                self.panic_if(too_large, ArithmeticError::Overflow, span)?;
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        op: &BinOpKind,
+        ty: Ty,
+        lhs: BasicValueEnum<'ctx>,
+        rhs: BasicValueEnum<'ctx>,
+        rhs_ty: Ty,
+        span: Span
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let prim = match ty {
+            Ty::Prim(PrimTy::Str) => return Err(CodegenError::Unsupported("comparison of strings")),
+            Ty::Prim(prim) => prim,
+            _ => return Err(CodegenError::Unsupported("operator on a value which is not primitive"))
+        };
+        if let PrimTy::Float(_) = prim {
+            let b = &self.builder;
+            let (lhs, rhs) = (lhs.into_float_value(), rhs.into_float_value());
+            let predicate = match op {
+                BinOpKind::Add => return Ok(b.build_float_add(lhs, rhs, "")?.into()),
+                BinOpKind::Sub => return Ok(b.build_float_sub(lhs, rhs, "")?.into()),
+                BinOpKind::Mul => return Ok(b.build_float_mul(lhs, rhs, "")?.into()),
+                BinOpKind::Div => return Ok(b.build_float_div(lhs, rhs, "")?.into()),
+                BinOpKind::Rem => return Ok(b.build_float_rem(lhs, rhs, "")?.into()),
+                BinOpKind::Eq => FloatPredicate::OEQ,
+                BinOpKind::Ne => FloatPredicate::UNE,
+                BinOpKind::Lt => FloatPredicate::OLT,
+                BinOpKind::Le => FloatPredicate::OLE,
+                BinOpKind::Gt => FloatPredicate::OGT,
+                BinOpKind::Ge => FloatPredicate::OGE,
+                _ => return Err(CodegenError::Unsupported("bitwise operator on floats"))
+            };
+            return Ok(b.build_float_compare(predicate, lhs, rhs, "")?.into())
+        }
+        // Integers, bools and chars, only integers can be signed
+        let signed = matches!(prim, PrimTy::Int(_));
+        let (lhs, rhs): (IntValue, IntValue) = (lhs.into_int_value(), rhs.into_int_value());
+        if prim.is_integer() {
+            let arithmetic = match op {
+                BinOpKind::Add => Some("add"),
+                BinOpKind::Sub => Some("sub"),
+                BinOpKind::Mul => Some("mul"),
+                _ => None
+            };
+            if let Some(arithmetic) = arithmetic {
+                let name = format!("llvm.{}{}.with.overflow", if signed { "s" } else { "u" }, arithmetic);
+                return Ok(self.overflowing(&name, lhs, rhs, span)?.into())
+            }
+            self.check_int_op(op, prim, lhs, rhs, rhs_ty, span)?;
+        }
+        let b = &self.builder;
+        let value: BasicValueEnum = match op {
+            BinOpKind::Add => b.build_int_add(lhs, rhs, "")?.into(),
+            BinOpKind::Sub => b.build_int_sub(lhs, rhs, "")?.into(),
+            BinOpKind::Mul => b.build_int_mul(lhs, rhs, "")?.into(),
+            BinOpKind::Div if signed => b.build_int_signed_div(lhs, rhs, "")?.into(),
+            BinOpKind::Div => b.build_int_unsigned_div(lhs, rhs, "")?.into(),
+            BinOpKind::Rem if signed => b.build_int_signed_rem(lhs, rhs, "")?.into(),
+            BinOpKind::Rem => b.build_int_unsigned_rem(lhs, rhs, "")?.into(),
+            // Operands of `&&` and `||` are already evaluated
+            BinOpKind::And | BinOpKind::BitAnd => b.build_and(lhs, rhs, "")?.into(),
+            BinOpKind::Or | BinOpKind::BitOr => b.build_or(lhs, rhs, "")?.into(),
+            BinOpKind::BitXor => b.build_xor(lhs, rhs, "")?.into(),
+            BinOpKind::Shl | BinOpKind::Shr => {
+                // Amount has type of its own
+                let amount = b.build_int_cast_sign_flag(rhs, lhs.get_type(), false, "")?;
+                match op {
+                    BinOpKind::Shl => b.build_left_shift(lhs, amount, "")?.into(),
+                    _ => b.build_right_shift(lhs, amount, signed, "")?.into()
+                }
+            },
+            BinOpKind::Eq | BinOpKind::Ne | BinOpKind::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge => {
+                let predicate = match (op, signed) {
+                    (BinOpKind::Eq, _) => IntPredicate::EQ,
+                    (BinOpKind::Ne, _) => IntPredicate::NE,
+                    (BinOpKind::Lt, true) => IntPredicate::SLT,
+                    (BinOpKind::Lt, false) => IntPredicate::ULT,
+                    (BinOpKind::Le, true) => IntPredicate::SLE,
+                    (BinOpKind::Le, false) => IntPredicate::ULE,
+                    (BinOpKind::Gt, true) => IntPredicate::SGT,
+                    (BinOpKind::Gt, false) => IntPredicate::UGT,
+                    (_, true) => IntPredicate::SGE,
+                    (_, false) => IntPredicate::UGE
+                };
+                b.build_int_compare(predicate, lhs, rhs, "")?.into()
+            }
+        };
+        Ok(value)
+    }
+}
+
+impl<'ctx> FnCx<'_, 'ctx> {
+    fn block(&self, block: BasicBlock) -> LlvmBlock<'ctx> {
+        self.blocks[block.index()]
+    }
+}
+
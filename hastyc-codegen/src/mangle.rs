@@ -0,0 +1,53 @@
+use std::collections::{HashMap, HashSet};
+
+use hastyc_common::identifiers::SymbolStorage;
+use hastyc_passes::{hir::{mono::main_fn, DefId, DefKind, Hir}, passes::intrinsic::Intrinsic};
+
+/// Names of all functions as they appear in the output, which are valid
+/// C identifiers, so every backend can use them. `main` of the root module
+/// keeps its name, as it is the entry point of the program, and so do
+/// functions without a body, which are defined outside of the package.
+/// Intrinsics are named by the runtime functions implementing them.
+/// Other functions are mangled from their path, with length of every
+/// segment in front of it, so `hello::add` is `_HN5hello3addE`. Functions
+/// with the same path, like ones declared in different blocks, get
+/// their definition index appended.
+pub fn symbol_names(hir: &Hir, symbols: &SymbolStorage) -> HashMap<DefId, String> {
+    let main = main_fn(hir);
+    let mut names = HashMap::new();
+    let mut used = HashSet::new();
+    for (&id, def) in hir.defs.iter() {
+        let DefKind::Fn { body, .. } = def.kind else { continue };
+        let mut name = if let Some(intrinsic) = Intrinsic::of_def(hir, id) {
+            intrinsic.runtime_symbol().to_string()
+        } else if Some(id) == main || body.is_none() {
+            def.ident.text(symbols).to_string()
+        } else {
+            mangle_path(hir, id, symbols)
+        };
+        if !used.insert(name.clone()) {
+            name = format!("{}_{}", name, id.index.0);
+            used.insert(name.clone());
+        }
+        names.insert(id, name);
+    }
+    names
+}
+
+fn mangle_path(hir: &Hir, def: DefId, symbols: &SymbolStorage) -> String {
+    let mut segments = Vec::new();
+    let mut current = Some(def);
+    // Root module has no name of its own
+    while let Some(id) = current.filter(|id| *id != hir.root) {
+        let def = hir.def(id);
+        segments.push(def.ident.text(symbols));
+        current = def.parent;
+    }
+    let mut name = String::from("_HN");
+    for segment in segments.iter().rev() {
+        name.push_str(&segment.len().to_string());
+        name.push_str(segment);
+    }
+    name.push('E');
+    name
+}
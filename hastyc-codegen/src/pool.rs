@@ -0,0 +1,85 @@
+use std::{collections::HashMap, fmt::Write};
+
+use hastyc_common::identifiers::{Symbol, SymbolStorage};
+
+/// Index of data in `ConstPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataId(pub u32);
+
+/// Read-only data that a constant is stored in.
+#[derive(Debug)]
+pub struct ConstData {
+    /// Symbol of the data, made of the kind of data and its index, so
+    /// it is the same as long as constants are used in the same order.
+    pub name: String,
+    /// Bytes of the data, strings are UTF-8 without a terminator.
+    pub bytes: Vec<u8>
+}
+
+impl ConstData {
+    /// Data as a C string literal, only printable ASCII is kept as is,
+    /// so the literal means the same whatever encoding the compiler uses.
+    pub fn c_literal(&self) -> String {
+        let mut literal = String::from("\"");
+        for byte in self.bytes.iter().copied() {
+            match byte {
+                b'"' => literal.push_str("\\\""),
+                b'\\' => literal.push_str("\\\\"),
+                // `??` could start a trigraph
+                b'?' => literal.push_str("\\?"),
+                0x20..=0x7e => literal.push(byte as char),
+                // Unlike hex escapes, octal ones never take following digits
+                _ => write!(literal, "\\{:03o}", byte).unwrap()
+            }
+        }
+        literal.push('"');
+        literal
+    }
+}
+
+/// Constants of the package which native backends put in memory, rather
+/// than in the code. Data with the same bytes is kept once, however many
+/// constants use it, and keeps the name it got when it was first added.
+pub struct ConstPool<'a> {
+    symbols: &'a SymbolStorage,
+    data: Vec<ConstData>,
+    by_bytes: HashMap<Vec<u8>, DataId>,
+    by_symbol: HashMap<Symbol, DataId>
+}
+
+impl<'a> ConstPool<'a> {
+    pub fn new(symbols: &'a SymbolStorage) -> Self {
+        Self { symbols, data: Vec::new(), by_bytes: HashMap::new(), by_symbol: HashMap::new() }
+    }
+
+    /// Data of the string with the symbol.
+    pub fn string(&mut self, symbol: Symbol) -> DataId {
+        if let Some(id) = self.by_symbol.get(&symbol) {
+            return *id
+        }
+        let text = self.symbols.text_of(symbol).unwrap_or("");
+        let id = self.bytes(text.as_bytes(), "str");
+        self.by_symbol.insert(symbol, id);
+        id
+    }
+
+    /// Data with the bytes, named after the kind if it wasn't added yet.
+    pub fn bytes(&mut self, bytes: &[u8], kind: &str) -> DataId {
+        if let Some(id) = self.by_bytes.get(bytes) {
+            return *id
+        }
+        let id = DataId(self.data.len() as u32);
+        self.data.push(ConstData { name: format!("hasty_{}_{}", kind, id.0), bytes: bytes.to_vec() });
+        self.by_bytes.insert(bytes.to_vec(), id);
+        id
+    }
+
+    pub fn get(&self, id: DataId) -> &ConstData {
+        &self.data[id.0 as usize]
+    }
+
+    /// All data, in the order it was added.
+    pub fn data(&self) -> impl Iterator<Item = (DataId, &ConstData)> {
+        self.data.iter().enumerate().map(|(idx, data)| (DataId(idx as u32), data))
+    }
+}
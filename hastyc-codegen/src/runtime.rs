@@ -0,0 +1,10 @@
+//! Runtime library which native backends call intrinsics from. Object
+//! files made by the LLVM backend have to be linked with it, which
+//! `link::Linker` does, the C backend puts it into its output, so that
+//! the source compiles on its own.
+
+/// Declarations of all runtime functions.
+pub const HEADER: &str = include_str!("../runtime/hasty_rt.h");
+
+/// Definitions of runtime functions, it includes `hasty_rt.h`.
+pub const SOURCE: &str = include_str!("../runtime/hasty_rt.c");
@@ -0,0 +1,26 @@
+use hastyc_passes::{hir::{ty::Ty, typeck::declared_ty, DefId, DefKind, Hir}, mir::{MirBody, Operand, Place, PlaceElem}};
+
+/// Declared type of the field.
+pub(crate) fn field_ty(hir: &Hir, field: DefId) -> Ty {
+    let DefKind::Field { ref ty } = hir.def(field).kind else { unreachable!("field is not a field") };
+    declared_ty(hir, ty).unwrap_or(Ty::Err)
+}
+
+/// Type of the place, walking fields of structs.
+pub(crate) fn place_ty(hir: &Hir, body: &MirBody, place: &Place) -> Ty {
+    let mut ty = body.local(place.local).ty;
+    for elem in place.projection.iter() {
+        let PlaceElem::Field(idx) = *elem;
+        let Ty::Adt(def) = ty else { unreachable!("field of a type which is not a struct") };
+        let DefKind::Struct { ref fields, .. } = hir.def(def).kind else { unreachable!("field of a type which is not a struct") };
+        ty = field_ty(hir, fields[idx as usize]);
+    }
+    ty
+}
+
+pub(crate) fn operand_ty(hir: &Hir, body: &MirBody, operand: &Operand) -> Ty {
+    match operand {
+        Operand::Copy(place) => place_ty(hir, body, place),
+        Operand::Constant(constant) => constant.ty
+    }
+}
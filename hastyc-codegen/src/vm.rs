@@ -0,0 +1,44 @@
+use hastyc_common::source::SourceMap;
+use hastyc_passes::{hir::{DefId, Hir}, mir::MirBody};
+use hastyc_vm::compile::Compiler;
+
+use crate::{backend::{Artifact, CodegenBackend}, CodegenError};
+
+/// Backend compiling to bytecode of `hastyc_vm`.
+pub struct VmBackend<'a> {
+    compiler: Option<Compiler<'a>>
+}
+
+impl<'a> VmBackend<'a> {
+    pub fn new(hir: &'a Hir, sources: &'a SourceMap) -> Self {
+        Self { compiler: Some(Compiler::new(hir, sources)) }
+    }
+
+    fn compiler(&mut self) -> &mut Compiler<'a> {
+        self.compiler.as_mut().expect("backend was already finished")
+    }
+}
+
+impl CodegenBackend for VmBackend<'_> {
+    fn name(&self) -> &'static str {
+        "vm"
+    }
+
+    fn declare_function(&mut self, def: DefId, body: Option<&MirBody>) -> Result<(), CodegenError> {
+        match body {
+            Some(body) => self.compiler().declare_body(body),
+            None => self.compiler().declare_extern(def)
+        }
+        Ok(())
+    }
+
+    fn define_body(&mut self, body: &MirBody) -> Result<(), CodegenError> {
+        self.compiler().define_body(body);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Artifact, CodegenError> {
+        let compiler = self.compiler.take().expect("backend was already finished");
+        Ok(Artifact::Bytecode(compiler.finish()))
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+
+/// Size of the first chunk in bytes, every next chunk is twice as big,
+/// up to `MAX_CHUNK_SIZE`.
+const MIN_CHUNK_SIZE: usize = 4 * 1024;
+const MAX_CHUNK_SIZE: usize = 2 * 1024 * 1024;
+
+fn next_chunk_size(previous: usize, needed: usize) -> usize {
+    (previous * 2).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE).max(needed)
+}
+
+/// Bump allocator for values of a single type. Values live as long as the
+/// arena and are dropped together with it, which lets IR nodes reference
+/// each other with plain `&'arena T` instead of `Box` or `Arc`.
+pub struct TypedArena<T> {
+    /// Chunks are never grown past their capacity, so their elements
+    /// never move and references to them stay valid.
+    chunks: RefCell<Vec<Vec<T>>>
+}
+
+impl<T> Default for TypedArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> TypedArena<T> {
+    pub fn new() -> Self {
+        Self {
+            chunks: RefCell::new(Vec::new())
+        }
+    }
+
+    /// Make sure that the last chunk has space for `additional` values.
+    fn reserve(chunks: &mut Vec<Vec<T>>, additional: usize) {
+        let (free, previous) = match chunks.last() {
+            Some(chunk) => (chunk.capacity() - chunk.len(), chunk.capacity()),
+            None => (0, 0)
+        };
+        if free < additional {
+            let elem_size = std::mem::size_of::<T>().max(1);
+            let capacity = next_chunk_size(previous * elem_size, additional * elem_size) / elem_size;
+            chunks.push(Vec::with_capacity(capacity.max(additional)));
+        }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, value: T) -> &mut T {
+        &mut self.alloc_extend(std::iter::once(value))[0]
+    }
+
+    /// Allocate all values from the iterator next to each other.
+    #[allow(clippy::mut_from_ref)] // Every value is handed out only once
+    pub fn alloc_extend(&self, values: impl IntoIterator<Item = T>) -> &mut [T] {
+        // Iterator could allocate into this arena too, so it is collected
+        // before the chunks are borrowed.
+        let values = values.into_iter().collect::<Vec<_>>();
+        let mut chunks = self.chunks.borrow_mut();
+        Self::reserve(&mut chunks, values.len());
+
+        let chunk = chunks.last_mut().unwrap();
+        let start = chunk.len();
+        chunk.extend(values);
+        let len = chunk.len() - start;
+        // SAFETY: There was enough capacity reserved, so the chunk did not
+        // reallocate and values already given out were not moved. Values
+        // are only removed when the arena is dropped, which can't happen
+        // while the returned reference borrows it, and every value is
+        // given out only once, so the mutable reference is unique.
+        unsafe { std::slice::from_raw_parts_mut(chunk.as_mut_ptr().add(start), len) }
+    }
+
+    /// Number of allocated values.
+    pub fn len(&self) -> usize {
+        self.chunks.borrow().iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> std::fmt::Debug for TypedArena<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("TypedArena")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Bump allocator for strings and byte slices. Nothing in it has to be
+/// dropped, so a single arena can hold text of any number of IRs.
+#[derive(Default)]
+pub struct DroplessArena {
+    chunks: RefCell<Vec<Vec<u8>>>
+}
+
+impl DroplessArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(clippy::mut_from_ref)] // Every range is handed out only once
+    pub fn alloc_bytes(&self, bytes: &[u8]) -> &mut [u8] {
+        let mut chunks = self.chunks.borrow_mut();
+        let (free, previous) = match chunks.last() {
+            Some(chunk) => (chunk.capacity() - chunk.len(), chunk.capacity()),
+            None => (0, 0)
+        };
+        if free < bytes.len() {
+            chunks.push(Vec::with_capacity(next_chunk_size(previous, bytes.len())));
+        }
+
+        let chunk = chunks.last_mut().unwrap();
+        let start = chunk.len();
+        chunk.extend_from_slice(bytes);
+        // SAFETY: Same as in `TypedArena::alloc_extend`, the chunk had enough
+        // capacity, so it didn't move, and this range is never given out again.
+        unsafe { std::slice::from_raw_parts_mut(chunk.as_mut_ptr().add(start), bytes.len()) }
+    }
+
+    pub fn alloc_str(&self, text: &str) -> &str {
+        let bytes = self.alloc_bytes(text.as_bytes());
+        // Bytes were copied from valid str, so this never fails
+        std::str::from_utf8(bytes).unwrap()
+    }
+
+    /// Number of allocated bytes.
+    pub fn len(&self) -> usize {
+        self.chunks.borrow().iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl std::fmt::Debug for DroplessArena {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("DroplessArena")
+            .field("len", &self.len())
+            .finish()
+    }
+}
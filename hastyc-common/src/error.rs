@@ -1,16 +1,45 @@
 use colored::*;
+use unicode_width::UnicodeWidthChar;
 
-use crate::{source::SourceFile, span::Span};
+use crate::{highlight::{self, Highlighter}, json::JsonEmitter, source::{FileName, SourceFile, SourceMap}, span::{HasSpan, Span}};
 
 /// Error formatter for hasty errors.
 pub struct ErrorFmt<'a> {
-    segments: Vec<Box<dyn ErrorFmtSegment + 'a>>
+    segments: Vec<Box<dyn ErrorFmtSegment + 'a>>,
+    // Structured copy of the built-in segments, used by non-text emitters
+    pub(crate) message: Option<String>,
+    /// Shown spans, with the note right before them which says what they are.
+    pub(crate) labels: Vec<(&'a SourceFile, Span, Option<String>)>,
+    pub(crate) children: Vec<(&'static str, String)>,
+    /// Number of children when the last span was shown.
+    children_at_label: usize,
+    pub(crate) suggestions: Vec<Suggestion>,
+    pub(crate) code: Option<&'static str>,
+    pub(crate) severity: Severity,
+    /// Files that spans of other files than the given one are shown from.
+    sources: Option<&'a SourceMap>,
+    config: RenderConfig
+}
+
+impl<'a> Default for ErrorFmt<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> ErrorFmt<'a> {
     pub fn new() -> Self {
         Self {
-            segments: Vec::new()
+            segments: Vec::new(),
+            message: None,
+            labels: Vec::new(),
+            children: Vec::new(),
+            children_at_label: 0,
+            suggestions: Vec::new(),
+            code: None,
+            severity: Severity::Error,
+            sources: None,
+            config: RenderConfig::default()
         }
     }
 
@@ -19,22 +48,84 @@ impl<'a> ErrorFmt<'a> {
         self
     }
 
+    /// Set error code, which is shown in titles added after this call.
+    pub fn code(&mut self, code: &'static str) -> &mut Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Set severity, which changes how titles and sources added
+    /// after this call are rendered. Default severity is `Error`.
+    pub fn severity(&mut self, severity: Severity) -> &mut Self {
+        self.severity = severity;
+        self
+    }
+
     pub fn title(&mut self, title: &str) -> &mut Self {
+        self.message.get_or_insert_with(|| title.to_string());
         self.seg(ErrorTitleSegment {
-            text: title.to_string()
+            text: title.to_string(),
+            code: self.code,
+            severity: self.severity
         });
         self
     }
 
+    /// Look up files of spans in the map, so that spans pointing into
+    /// other files than the one given to `source` show their own code.
+    pub fn sources(&mut self, sources: &'a SourceMap) -> &mut Self {
+        self.sources = Some(sources);
+        self
+    }
+
+    /// File to show the span from, spans of other files are only shown
+    /// if their file can be found in the source map.
+    fn file_of(&self, source: &'a SourceFile, span: Span) -> Option<&'a SourceFile> {
+        if span.source == source.id {
+            return Some(source)
+        }
+        self.sources.and_then(|sources| sources.source_of(&span))
+    }
+
+    /// Show the span in source. If the span comes from an expansion,
+    /// this is followed by notes with the call sites of all expansions.
+    /// Spans in files which aren't known aren't shown.
     pub fn source(&mut self, source: &'a SourceFile, span: Span) -> &mut Self {
-        self.seg(ErrorSourceSegment {
-            source,
-            span
-        });
+        // Notes like "Expected because of this:" introduce the span
+        let message = self.children[self.children_at_label..].iter()
+            .rev()
+            .find(|(kind, _)| *kind == "note")
+            .map(|(_, message)| message.clone());
+        let file = self.file_of(source, span);
+        self.labels.push((file.unwrap_or(source), span, message));
+        if let Some(file) = file {
+            self.seg(ErrorSourceSegment {
+                source: file,
+                span,
+                severity: self.severity
+            });
+        }
+        for expn in span.ctxt.backtrace() {
+            self.note(&format!("in {}", expn.kind));
+            if let Some(file) = self.file_of(source, expn.call_site) {
+                self.seg(ErrorSourceSegment {
+                    source: file,
+                    span: expn.call_site,
+                    severity: Severity::Note
+                });
+            }
+        }
+        self.children_at_label = self.children.len();
         self
     }
 
+    /// Show the span of the node in source, see `source`.
+    pub fn source_node(&mut self, source: &'a SourceFile, node: &impl HasSpan) -> &mut Self {
+        self.source(source, node.span())
+    }
+
     pub fn cause(&mut self, message: &'a str) -> &mut Self {
+        self.children.push(("cause", message.to_string()));
         self.seg(ErrorCauseSegment {
             message
         });
@@ -42,50 +133,393 @@ impl<'a> ErrorFmt<'a> {
     }
 
     pub fn help(&mut self, message: &'a str) -> &mut Self {
+        self.children.push(("help", message.to_string()));
         self.seg(HelpMessageSegment {
             message
         });
         self
     }
 
+    pub fn note(&mut self, message: &str) -> &mut Self {
+        self.children.push(("note", message.to_string()));
+        self.seg(NoteMessageSegment {
+            message: message.to_string()
+        });
+        self
+    }
+
+    /// Suggest replacing code at the given span with the replacement.
+    /// This is rendered as help message, but is also kept as structured
+    /// data, so that tools can apply the fix automatically.
+    pub fn suggestion(&mut self, span: Span, replacement: &str, message: &str) -> &mut Self {
+        let suggestion = Suggestion {
+            span,
+            replacement: replacement.to_string(),
+            message: message.to_string()
+        };
+        self.seg(SuggestionSegment {
+            suggestion: suggestion.clone()
+        });
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// All suggestions added to this error.
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    /// Structured copy of the error, for tools that need more
+    /// than just rendered text.
+    pub fn data(&self) -> DiagnosticData {
+        let mut spans = self.labels.iter().map(|(_, span, message)| (*span, message.clone()));
+        DiagnosticData {
+            severity: self.severity,
+            code: self.code,
+            message: self.message.clone().unwrap_or_default(),
+            primary_span: spans.next().map(|(span, _)| span),
+            related_spans: spans.collect(),
+            children: self.children.clone(),
+            suggestions: self.suggestions.clone()
+        }
+    }
+
+    /// Set rendering config used by `build`.
+    pub fn config(&mut self, config: RenderConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
     pub fn build(&mut self) -> String {
+        // Whether to use colors is decided once, not for every colored part
+        let color = if self.config.use_color() { ColorChoice::Always } else { ColorChoice::Never };
+        let config = RenderConfig { color, ..self.config.clone() };
         let mut result = String::new();
         for seg in self.segments.iter() {
-            result.push_str(&seg.stringify());
+            result.push_str(&seg.stringify(&config));
             result.push('\n');
         }
         result
     }
+
+    /// Render without colors and with normalized paths, see `RenderConfig::plain`.
+    pub fn build_plain(&mut self) -> String {
+        self.config(RenderConfig::plain()).build()
+    }
 }
 
-pub trait ErrorDisplay<'ctx, Context> {
+/// Context that diagnostics are rendered in.
+pub trait ErrorContext<'ctx> {
+    /// Files which spans of the diagnostic can point into.
+    fn sources(&self) -> Option<&'ctx SourceMap>;
+}
+
+pub trait ErrorDisplay<'ctx, Context: ErrorContext<'ctx>> {
     fn fmt(&self, fmt: &mut ErrorFmt<'ctx>, ctx: &'ctx Context);
+    /// Stable error code, explanation for it can be found
+    /// with `error_codes::explain`.
+    fn code(&self) -> Option<&'static str> { None }
+    fn severity(&self) -> Severity { Severity::Error }
     fn fmt_error(&self, ctx: &'ctx Context) -> String {
+        self.to_fmt(ctx).build()
+    }
+    fn fmt_error_with(&self, ctx: &'ctx Context, config: &RenderConfig) -> String {
+        self.to_fmt(ctx).config(config.clone()).build()
+    }
+    fn fmt_plain(&self, ctx: &'ctx Context) -> String {
+        self.to_fmt(ctx).build_plain()
+    }
+    fn suggestions(&self, ctx: &'ctx Context) -> Vec<Suggestion> {
+        self.to_fmt(ctx).suggestions
+    }
+    fn data(&self, ctx: &'ctx Context) -> DiagnosticData {
+        self.to_fmt(ctx).data()
+    }
+    /// Error serialized as a single line of JSON, see `JsonEmitter`.
+    fn fmt_json(&self, ctx: &'ctx Context) -> String {
+        JsonEmitter::emit(&self.to_fmt(ctx))
+    }
+    fn to_fmt(&self, ctx: &'ctx Context) -> ErrorFmt<'ctx> {
         let mut fmt = ErrorFmt::new();
+        if let Some(sources) = ctx.sources() {
+            fmt.sources(sources);
+        }
+        fmt.severity(self.severity());
+        if let Some(code) = self.code() {
+            fmt.code(code);
+        }
         self.fmt(&mut fmt, ctx);
-        fmt.build()
+        fmt
+    }
+}
+
+/// When to use colors in rendered diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Use colors if output is a terminal, respecting `NO_COLOR`,
+    /// `CLICOLOR` and `CLICOLOR_FORCE` environment variables.
+    #[default]
+    Auto,
+    Always,
+    Never
+}
+
+impl ColorChoice {
+    /// Parse choice from command line value: "auto", "always" or "never".
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None
+        }
     }
 }
 
+/// Configuration of how diagnostics are rendered as text.
+#[derive(Debug, Clone)]
+pub struct RenderConfig {
+    pub color: ColorChoice,
+    /// Width to which long messages are wrapped, None disables wrapping.
+    pub width: Option<usize>,
+    /// Number of columns between tab stops, used when showing source lines.
+    pub tab_width: usize,
+    /// Show file paths with '/' separators on every platform.
+    pub normalize_paths: bool,
+    /// Colors source lines when colors are used, None shows them plain.
+    pub highlighter: Option<Highlighter>
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            color: ColorChoice::default(),
+            width: None,
+            tab_width: 4,
+            normalize_paths: false,
+            highlighter: None
+        }
+    }
+}
+
+impl RenderConfig {
+    /// Config for the current environment, width is taken from
+    /// `COLUMNS` variable if it is set.
+    pub fn from_env(color: ColorChoice) -> Self {
+        Self {
+            color,
+            width: std::env::var("COLUMNS").ok().and_then(|cols| cols.parse().ok()),
+            ..Self::default()
+        }
+    }
+
+    /// Number of terminal columns the text takes, if it starts at the
+    /// given column. Tabs go to the next tab stop, and wide characters
+    /// (like most CJK characters) take two columns.
+    fn display_width(&self, text: &str, start_column: usize) -> usize {
+        let mut column = start_column;
+        for c in text.chars() {
+            column += match c {
+                '\t' => self.tab_width - column % self.tab_width.max(1),
+                c => c.width().unwrap_or(0)
+            };
+        }
+        column - start_column
+    }
+
+    /// Replace tabs with spaces, so that the line is shown in the
+    /// same way as `display_width` measures it.
+    fn expand_tabs(&self, line: &str) -> String {
+        let mut result = String::with_capacity(line.len());
+        let mut column = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let spaces = self.display_width("\t", column);
+                result.push_str(&" ".repeat(spaces));
+                column += spaces;
+            } else {
+                result.push(c);
+                column += c.width().unwrap_or(0);
+            }
+        }
+        result
+    }
+
+    /// Deterministic config for golden-file tests. Output doesn't depend
+    /// on the terminal, environment or platform that renders it.
+    pub fn plain() -> Self {
+        Self {
+            color: ColorChoice::Never,
+            normalize_paths: true,
+            ..Self::default()
+        }
+    }
+
+    fn file_name(&self, name: &FileName) -> String {
+        let name = name.to_string();
+        if self.normalize_paths { name.replace('\\', "/") } else { name }
+    }
+
+    pub fn use_color(&self) -> bool {
+        match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => colored::control::ShouldColorize::from_env().should_colorize()
+        }
+    }
+
+    /// Text with its colors if colors are used, otherwise plain. This
+    /// doesn't depend on the global switch of `colored`.
+    fn paint(&self, text: ColoredString) -> String {
+        if self.use_color() { highlight::ansi(&text) } else { text.input }
+    }
+
+    /// Wrap message, which is displayed after a label of given width,
+    /// so that continuation lines are aligned with its start.
+    fn wrap(&self, label_width: usize, message: &str) -> String {
+        let Some(width) = self.width else { return message.to_string() };
+        let available = width.saturating_sub(label_width).max(20);
+
+        let mut result = String::new();
+        let mut line_len = 0;
+        for word in message.split(' ') {
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > available {
+                result.push('\n');
+                result.push_str(&" ".repeat(label_width));
+                line_len = 0;
+            } else if line_len > 0 {
+                result.push(' ');
+                line_len += 1;
+            }
+            result.push_str(word);
+            line_len += word_len;
+        }
+        result
+    }
+}
+
+/// Fix for the error, which replaces code at the span with the replacement.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String
+}
+
+impl Suggestion {
+    /// Returns source code with this suggestion applied, or None if
+    /// suggestion is for other source or points outside of it.
+    pub fn apply(&self, source: &SourceFile) -> Option<String> {
+        if self.span.source != source.id { return None }
+        let src = source.src.as_ref()?;
+        let before = src.get(..self.span.start as usize)?;
+        let after = src.get(self.span.end as usize..)?;
+        Some(format!("{}{}{}", before, self.replacement, after))
+    }
+}
+
+/// Everything that diagnostic says, without any formatting.
+#[derive(Debug, Clone)]
+pub struct DiagnosticData {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// Span that the diagnostic is about, None if it has no source.
+    pub primary_span: Option<Span>,
+    /// Other spans shown by the diagnostic, with the note that
+    /// introduces them, like "Shadowed variable is bound here:".
+    pub related_spans: Vec<(Span, Option<String>)>,
+    /// Cause, help and note messages, with their kind.
+    pub children: Vec<(&'static str, String)>,
+    pub suggestions: Vec<Suggestion>
+}
+
 pub struct CommonErrorContext<'a> {
-    pub source: &'a SourceFile
+    pub source: &'a SourceFile,
+    /// All files, spans pointing into other files than `source` are
+    /// shown from them.
+    pub sources: Option<&'a SourceMap>
+}
+
+impl<'a> CommonErrorContext<'a> {
+    /// Context with the file that the diagnostic is about, which is the file
+    /// of its primary span, or `fallback` if it has none or it isn't loaded.
+    pub fn for_diagnostic(diag: &dyn Diagnostic, sources: &'a SourceMap, fallback: &'a SourceFile) -> Self {
+        let source = diag.data(&CommonErrorContext { source: fallback, sources: Some(sources) }).primary_span
+            .and_then(|span| sources.source_of(&span))
+            .unwrap_or(fallback);
+        Self { source, sources: Some(sources) }
+    }
+}
+
+impl<'a> ErrorContext<'a> for CommonErrorContext<'a> {
+    fn sources(&self) -> Option<&'a SourceMap> {
+        self.sources
+    }
+}
+
+/// Diagnostic that can be stored and displayed later, no matter
+/// what stage of the compiler it came from.
+pub trait Diagnostic: for<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> + std::fmt::Debug {}
+
+impl<T> Diagnostic for T
+    where T: for<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> + std::fmt::Debug {}
+
+/// How serious the diagnostic is, only errors stop the compilation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Help,
+    Note,
+    Warning,
+    Error
+}
+
+impl Severity {
+    pub fn is_fatal(&self) -> bool {
+        *self == Self::Error
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Note => "note",
+            Self::Warning => "warning",
+            Self::Error => "error"
+        }
+    }
+
+    fn color(&self, text: &str) -> ColoredString {
+        match self {
+            Self::Help => text.yellow(),
+            Self::Note => text.cyan(),
+            Self::Warning => text.bright_yellow(),
+            Self::Error => text.red()
+        }
+    }
 }
 
 pub trait ErrorFmtSegment {
-    fn stringify(&self) -> String;
+    fn stringify(&self, config: &RenderConfig) -> String;
 }
 
 pub struct ErrorTitleSegment {
-    text: String
+    text: String,
+    code: Option<&'static str>,
+    severity: Severity
 }
 
 impl ErrorFmtSegment for ErrorTitleSegment {
-    fn stringify(&self) -> String {
+    fn stringify(&self, config: &RenderConfig) -> String {
+        let code = match self.code {
+            Some(code) => format!("[{}]", code),
+            None => String::new()
+        };
         format!(
             "{}{} {}",
-            "error".red().bold(),
-            ":".bold(),
-            self.text.bold()
+            config.paint(self.severity.color(&format!("{}{}", self.severity.label(), code)).bold()),
+            config.paint(":".bold()),
+            config.paint(self.text.bold())
         )
     }
 }
@@ -93,38 +527,54 @@ impl ErrorFmtSegment for ErrorTitleSegment {
 
 pub struct ErrorSourceSegment<'a> {
     source: &'a SourceFile,
-    span: Span
+    span: Span,
+    severity: Severity
 }
 
 impl<'a> ErrorFmtSegment for ErrorSourceSegment<'a> {
-    fn stringify(&self) -> String {
-        let src_relative_span = self.span.to_relative(&self.source);
+    fn stringify(&self, config: &RenderConfig) -> String {
+        let src_relative_span = self.span.to_relative(self.source);
         let source = format!(
             "{} {}:{}.{}",
-            "-->".blue(),
-            self.source.name,
+            config.paint("-->".blue()),
+            config.file_name(&self.source.name),
             src_relative_span.0,
             src_relative_span.1
         );
 
-        let src_line = self.span.get_line(&self.source);
+        let (src_line, _) = self.span.get_line(self.source);
         let num_width = src_relative_span.0.to_string().len();
+        let mut src_line = config.expand_tabs(src_line);
+        if let Some(highlighter) = config.highlighter.filter(|_| config.use_color()) {
+            src_line = highlight::render_ansi(&src_line, &highlighter(&src_line));
+        }
         let line = format!(
             "{} {} {}",
-            src_relative_span.0.to_string().blue(),
-            "|".blue(),
-            src_line.0
+            config.paint(src_relative_span.0.to_string().blue()),
+            config.paint("|".blue()),
+            src_line
         );
 
+        // Markers are counted in display columns, and only go to the end of the first line
+        let (line_start, line_end) = self.source.line_bounds(src_relative_span.0);
+        let before = self.source.get_span(
+            &Span::new(self.span.source, line_start, self.span.start)
+        );
+        let marked = self.source.get_span(
+            &Span::new(self.span.source, self.span.start, self.span.end.min(line_end))
+        );
+        let offset = config.display_width(before, 0);
+        let marked_width = config.display_width(marked, offset);
         let highlight_underline = format!(
             "{}{}",
-            " ".repeat(src_line.1 as usize),
-            "^".repeat(self.span.len() as usize).red()
+            " ".repeat(offset),
+            // Empty spans (like "insert here" positions) still get one marker
+            config.paint(self.severity.color(&"^".repeat(marked_width.max(1))))
         );
         let highlight = format!(
             "{} {} {}",
             " ".repeat(num_width),
-            "|".blue(),
+            config.paint("|".blue()),
             highlight_underline
         );
 
@@ -142,11 +592,11 @@ pub struct ErrorCauseSegment<'a> {
 }
 
 impl<'a> ErrorFmtSegment for ErrorCauseSegment<'a> {
-    fn stringify(&self) -> String {
+    fn stringify(&self, config: &RenderConfig) -> String {
         format!(
             "{} {}",
-            "cause:".purple().bold(),
-            self.message.red().bold()
+            config.paint("cause:".purple().bold()),
+            config.paint(config.wrap(7, self.message).red().bold())
         )
     }
 }
@@ -156,11 +606,40 @@ pub struct HelpMessageSegment<'a> {
 }
 
 impl<'a> ErrorFmtSegment for HelpMessageSegment<'a> {
-    fn stringify(&self) -> String {
+    fn stringify(&self, config: &RenderConfig) -> String {
+        format!(
+            "{} {}",
+            config.paint("help:".yellow().bold()),
+            config.paint(config.wrap(6, self.message).bold())
+        )
+    }
+}
+
+pub struct NoteMessageSegment {
+    message: String
+}
+
+impl ErrorFmtSegment for NoteMessageSegment {
+    fn stringify(&self, config: &RenderConfig) -> String {
         format!(
             "{} {}",
-            "help:".yellow().bold(),
-            self.message.bold()
+            config.paint("note:".cyan().bold()),
+            config.paint(config.wrap(6, &self.message).bold())
+        )
+    }
+}
+
+pub struct SuggestionSegment {
+    suggestion: Suggestion
+}
+
+impl ErrorFmtSegment for SuggestionSegment {
+    fn stringify(&self, config: &RenderConfig) -> String {
+        format!(
+            "{} {}: try `{}`",
+            config.paint("help:".yellow().bold()),
+            config.paint(config.wrap(6, &self.suggestion.message).bold()),
+            config.paint(self.suggestion.replacement.green())
         )
     }
 }
\ No newline at end of file
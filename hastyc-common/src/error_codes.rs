@@ -0,0 +1,497 @@
+/// Registry of all error codes with their long-form explanations.
+/// Codes are grouped by the compiler stage that reports them:
+/// `H00xx` for lexer, `H01xx` for parser, `H02xx` for name resolution,
+/// `H03xx` for type checking and `H04xx` for warnings about unused code.
+/// Codes are stable, so once assigned they must never be reused.
+pub static ERROR_CODES: &[ErrorCodeInfo] = &[
+    // Lexer
+    ErrorCodeInfo {
+        code: "H0001",
+        explanation: "\
+Source file was not loaded, so there is nothing to lex.
+
+This is usually an internal problem, where source file was registered
+without its content being read from disk."
+    },
+    ErrorCodeInfo {
+        code: "H0002",
+        explanation: "\
+String literal was never closed.
+
+Every string must end with '\"' before the end of the file:
+
+    let a = \"hello;    // error
+    let a = \"hello\";   // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0003",
+        explanation: "\
+Source contains a character that cannot start any token.
+
+Identifiers must start with an ASCII letter, '_' or '$', and characters
+outside of strings and comments must be a part of some token."
+    },
+
+    // Parser
+    ErrorCodeInfo {
+        code: "H0100",
+        explanation: "\
+Parser found a token that doesn't fit in the current place.
+
+This is a generic syntax error, check the code around the token for
+missing or extra punctuation."
+    },
+    ErrorCodeInfo {
+        code: "H0101",
+        explanation: "\
+Only items can be placed at the top level of a module.
+
+Items are functions, structs, enums, modules and imports. Statements
+and expressions must be put inside of a function:
+
+    let a = 1;              // error
+    fn main() { let a = 1; } // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0102",
+        explanation: "\
+Expected a name, but found something different.
+
+Names of items, fields, arguments and attributes must be identifiers:
+
+    fn 1() {}     // error
+    fn one() {}   // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0103",
+        explanation: "\
+Struct or enum variant has invalid shape.
+
+Variants can be unit (`A`), tuple-like (`A(i32)`) or struct-like
+(`A { a: i32 }`)."
+    },
+    ErrorCodeInfo {
+        code: "H0104",
+        explanation: "\
+Import tree is malformed.
+
+Glob (`*`), `self` and groups (`{ ... }`) must be the last segment of
+an import, glob imports cannot be renamed and import path cannot end
+with '::':
+
+    import a::*::b;    // error
+    import a::*;       // ok
+    import a::{b, c};  // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0105",
+        explanation: "\
+Expected an expression, but found a token that cannot start one.
+
+    let a = ;   // error
+    let a = 1;  // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0106",
+        explanation: "\
+Expected a pattern, but found something different.
+
+Currently only identifiers can be used as patterns:
+
+    let 1 = a;  // error
+    let b = a;  // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0107",
+        explanation: "\
+This syntax is recognized, but not yet supported by the compiler.
+
+The code may be valid in the future, for now it has to be written
+in a different way."
+    },
+    ErrorCodeInfo {
+        code: "H0108",
+        explanation: "\
+Code is nested deeper than parser allows.
+
+Parser limits nesting of blocks, expressions and modules to avoid
+running out of stack. Split deeply nested code into smaller functions
+or intermediate variables."
+    },
+    ErrorCodeInfo {
+        code: "H0109",
+        explanation: "\
+Delimiter was opened, but end of file was reached before it was closed.
+
+Every '(', '[' and '{' must have matching ')', ']' or '}':
+
+    fn main() {     // error, missing '}'"
+    },
+    ErrorCodeInfo {
+        code: "H0110",
+        explanation: "\
+Statement is missing a terminating semicolon.
+
+Statements must end with ';', unless they are the last expression of
+a block or they end with a block themselves:
+
+    let a = 1      // error
+    let a = 1;     // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0111",
+        explanation: "\
+Module declared in a block has its items in another file.
+
+Files of modules are found by the path of modules they are in, which
+blocks don't have. Modules in blocks must have their items in braces:
+
+    fn main() {
+        module a;         // error
+        module a { }      // ok
+    }"
+    },
+    ErrorCodeInfo {
+        code: "H0112",
+        explanation: "\
+File of a module declared like `module foo;` doesn't exist.
+
+Items of the module are loaded from `foo.hty` or `foo/mod.hty` in the
+directory of the module it is declared in, which for the root file is
+the directory of that file. Modules declared in `foo` are then looked up
+in the `foo` directory:
+
+    src/main.hty        // module foo;
+    src/foo.hty         // module bar;
+    src/foo/bar.hty"
+    },
+    ErrorCodeInfo {
+        code: "H0113",
+        explanation: "\
+Module declared like `module foo;` could be loaded from more than one file.
+
+Only one of `foo.hty` and `foo/mod.hty` can exist, remove or rename
+the other one."
+    },
+    ErrorCodeInfo {
+        code: "H0114",
+        explanation: "\
+File of a module was found, but it couldn't be read.
+
+This usually means that it isn't readable by the compiler, or that it is
+not a text file."
+    },
+    ErrorCodeInfo {
+        code: "H0115",
+        explanation: "\
+Extern function was declared with a body.
+
+Extern functions are defined outside of the program and only declared
+here, so their declaration ends with ';':
+
+    extern fn puts(s: str) -> i32 { 0 }   // error
+    extern fn puts(s: str) -> i32;        // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0116",
+        explanation: "\
+Extern function uses an ABI that the compiler doesn't know.
+
+The only supported ABI is \"C\", which is also used when it isn't written:
+
+    extern \"system\" fn puts(s: str) -> i32;   // error
+    extern \"C\" fn puts(s: str) -> i32;        // ok"
+    },
+
+    // Name resolution
+    ErrorCodeInfo {
+        code: "H0200",
+        explanation: "\
+Path could not be resolved to any item or variable.
+
+Check that every segment of the path is spelled correctly, and that
+the item is declared or imported in the current scope."
+    },
+    ErrorCodeInfo {
+        code: "H0201",
+        explanation: "\
+Name is imported by multiple glob imports, which refer to different items.
+
+Glob imports are only ambiguous when the name is actually used, so
+this can be fixed by importing the name explicitly, which takes
+precedence over any glob import."
+    },
+    ErrorCodeInfo {
+        code: "H0202",
+        explanation: "\
+Path that should point to a module points to some other item.
+
+Glob imports and all segments of a path in front of the last one
+must refer to modules."
+    },
+    ErrorCodeInfo {
+        code: "H0203",
+        explanation: "\
+Variable is bound with the same name as another variable in scope.
+
+This is only reported if the compilation asks for it, as shadowing is
+allowed by default. It is a warning or an error, depending on the
+shadowing policy of the compilation:
+
+    fn main() {
+        let a = 1;
+        let a = a + 1;   // shadows `a` from the previous line
+    }
+
+When shadowing only warns, add `#[allow(shadowed_binding)]` to the
+function to silence it there."
+    },
+    ErrorCodeInfo {
+        code: "H0204",
+        explanation: "\
+Module declares or imports multiple items with the same name.
+
+Every item and explicitly imported name must be unique in its module:
+
+    fn parse() {}
+    import lexer::parse;   // error, `parse` is already defined
+
+Rename one of the items, or import it with `as` under another name."
+    },
+    ErrorCodeInfo {
+        code: "H0205",
+        explanation: "\
+Name is explicitly imported multiple times, from different items.
+
+Importing the same item twice is allowed, but two imports binding the
+same name to different items would make the name ambiguous:
+
+    import a::item;
+    import b::item;        // error
+    import b::item as b_item;   // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0206",
+        explanation: "\
+Path refers to an item which its module doesn't export.
+
+Only public items, and names imported with `pub import`, can be reached
+through a path from outside of their module:
+
+    module a {
+        fn hidden() {}
+        pub fn visible() {}
+    }
+    a::hidden();   // error
+    a::visible();  // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0207",
+        explanation: "\
+Module imports itself with `import self;`.
+
+`self` in an import refers to the module named by the path before it,
+as in `import a::{self};`, so it can't be imported on its own. Items
+of the current module are always in scope, so the import can be removed."
+    },
+    ErrorCodeInfo {
+        code: "H0208",
+        explanation: "\
+Path of an import could not be resolved.
+
+Every segment of an imported path must name a module or an item that
+its module exports. If there is a name similar to the one that could
+not be resolved, it is suggested."
+    },
+    ErrorCodeInfo {
+        code: "H0209",
+        explanation: "\
+Expression is nested too deeply.
+
+Passes of the compiler walk expressions recursively, so they limit how
+deep expressions can be nested, to not run out of stack. Usually this
+happens with very long chains of operators in generated code:
+
+    let sum = a + a + a + a + ...;   // thousands of `+`
+
+Split such expressions with variables instead:
+
+    let part = a + a + ...;
+    let sum = part + part + ...;"
+    },
+    ErrorCodeInfo {
+        code: "H0211",
+        explanation: "\
+`for` loop is used, but they are not supported yet.
+
+There are no iterators yet, so `for` loops can't be compiled. Use
+`while` with a counter instead:
+
+    for item in items { }        // error
+
+    let i = 0;
+    while i < 10 { i = i + 1; }  // ok"
+    },
+
+    // Type checking
+    ErrorCodeInfo {
+        code: "H0300",
+        explanation: "\
+Function or tuple struct is called with a wrong number of arguments.
+
+Every parameter of a function, and every field of a tuple struct or
+tuple variant, must be given exactly one argument:
+
+    fn add(a: i32, b: i32) -> i32 { a + b }
+    add(1)       // error
+    add(1, 2)    // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0301",
+        explanation: "\
+Something that is not a function is called.
+
+Only functions, tuple structs and tuple variants can be called. Structs
+with named fields are constructed with a struct literal, and unit
+structs and variants are used by their name alone:
+
+    struct Point { x: i32, y: i32 }
+    Point(1, 2)             // error
+    Point { x: 1, y: 2 }    // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0302",
+        explanation: "\
+Expression has a different type than the one that is required.
+
+Types are required by annotations of variables, by parameters of called
+functions, by struct fields and by the other branch of an `if`:
+
+    let a: i32 = \"text\";    // error
+    let a: i32 = 1;         // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0303",
+        explanation: "\
+Binary operator is used with operands of types it doesn't support.
+
+Arithmetic operators require both operands to be numbers of the same
+type, `&&` and `||` require booleans, bitwise operators require integers
+or booleans, and comparisons require operands of the same type:
+
+    let a = \"a\" + 1;    // error
+    let a = 1 + 2;      // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0304",
+        explanation: "\
+Unary operator is used with an operand of type it doesn't support.
+
+`-` requires a signed integer or a float, and `!` requires an integer
+or a boolean:
+
+    let a: u8 = 1;
+    let b = -a;    // error"
+    },
+    ErrorCodeInfo {
+        code: "H0305",
+        explanation: "\
+Value of a function body has a different type than its return type.
+
+Value of the body is its last expression without a semicolon, a body
+without one has type `()`:
+
+    fn one() -> i32 { 1; }    // error
+    fn one() -> i32 { 1 }     // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0306",
+        explanation: "\
+Function without a return type has a body with a value.
+
+Functions without `->` return `()`. Either declare the return type,
+or end the body with a semicolon to discard the value:
+
+    fn one() { 1 }           // error
+    fn one() -> i32 { 1 }    // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0307",
+        explanation: "\
+Function which returns `!` can finish.
+
+Type `!` means that the function never returns, so its body must never
+reach its end, for example by looping forever:
+
+    fn forever() -> ! { }            // error
+    fn forever() -> ! { loop {} }    // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0308",
+        explanation: "\
+Condition of `if` or `while` is not a `bool`.
+
+Numbers are not treated as true or false, and have to be compared
+explicitly instead:
+
+    let a = 1;
+    if a { }         // error
+    if a != 0 { }    // ok"
+    },
+    ErrorCodeInfo {
+        code: "H0309",
+        explanation: "\
+Number literal has a suffix that is not a type of such numbers.
+
+Integer literals can end with any integer type, and float literals
+with `f32` or `f64`:
+
+    let a = 300u16;     // ok
+    let b = 1.5f32;     // ok
+    let c = 300u17;     // error
+    let d = 1.5u8;      // error
+    let e = 1f32;       // error, write `1.0f32` instead"
+    },
+    ErrorCodeInfo {
+        code: "H0310",
+        explanation: "\
+Number literal doesn't fit into its type.
+
+The type comes from the suffix of the literal, or from how it is used,
+and literals which are not constrained are `i32` or `f64`:
+
+    let a: u8 = 300;        // error, `u8` has values from 0 to 255
+    let b = 3000000000;     // error, this is `i32`
+    let c = 3000000000i64;  // ok"
+    },
+    // Lints
+    ErrorCodeInfo {
+        code: "H0400",
+        explanation: "\
+Function or module is never used.
+
+Definitions are used if they can be reached from `main`, or from any
+public function if the package has no `main`. Code that is only used
+by other dead code is dead too:
+
+    fn unused() { }     // warning
+    fn main() { }
+
+Add `#[allow(dead_code)]` to a function or module to silence this
+warning for it and everything inside of it."
+    },
+];
+
+/// Error code with its explanation.
+#[derive(Debug)]
+pub struct ErrorCodeInfo {
+    pub code: &'static str,
+    pub explanation: &'static str
+}
+
+/// Get long-form explanation of the given error code, eg. "H0110".
+pub fn explain(code: &str) -> Option<&'static str> {
+    ERROR_CODES.iter()
+        .find(|info| info.code == code)
+        .map(|info| info.explanation)
+}
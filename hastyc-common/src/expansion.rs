@@ -0,0 +1,81 @@
+use std::sync::{OnceLock, RwLock};
+
+use crate::{identifiers::Symbol, span::{Span, SyntaxContext}};
+
+/// What created code with the given syntax context.
+#[derive(Debug, Clone)]
+pub enum ExpnKind {
+    /// Code created by compiler pass from simpler constructs,
+    /// the name is shown to the user, eg. "`for` loop".
+    Desugaring(&'static str),
+    /// Code created by expanding macro with the given name.
+    Macro(Symbol)
+}
+
+impl std::fmt::Display for ExpnKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Desugaring(name) => write!(f, "desugaring of {}", name),
+            Self::Macro(name) => write!(f, "expansion of macro `{}!`", name)
+        }
+    }
+}
+
+/// Single expansion, code created by it gets spans with its context.
+#[derive(Debug, Clone)]
+pub struct ExpnData {
+    pub kind: ExpnKind,
+    /// Span of the code which caused the expansion. This may
+    /// itself come from another expansion, which is how
+    /// nested expansions form a backtrace.
+    pub call_site: Span
+}
+
+/// Side table with data of all syntax contexts. Spans only keep the
+/// context id, so that they stay small and `Copy`. Index 0 is the root
+/// context and has no data.
+#[derive(Debug)]
+pub struct ExpansionTable {
+    expansions: RwLock<Vec<ExpnData>>
+}
+
+impl ExpansionTable {
+    pub fn global() -> &'static ExpansionTable {
+        static TABLE: OnceLock<ExpansionTable> = OnceLock::new();
+        TABLE.get_or_init(|| ExpansionTable { expansions: RwLock::default() })
+    }
+
+    fn register(&self, data: ExpnData) -> SyntaxContext {
+        let mut expansions = self.expansions.write().unwrap();
+        expansions.push(data);
+        SyntaxContext(expansions.len() as u32)
+    }
+
+    fn get(&self, ctxt: SyntaxContext) -> Option<ExpnData> {
+        if ctxt.is_root() { return None }
+        self.expansions.read().unwrap().get(ctxt.0 as usize - 1).cloned()
+    }
+}
+
+impl SyntaxContext {
+    /// Create new context for code produced by expansion at the call site.
+    pub fn fresh(kind: ExpnKind, call_site: Span) -> Self {
+        ExpansionTable::global().register(ExpnData { kind, call_site })
+    }
+
+    /// Data of the expansion that created this context, None for root.
+    pub fn expn_data(self) -> Option<ExpnData> {
+        ExpansionTable::global().get(self)
+    }
+
+    /// All expansions leading to this context, innermost first.
+    pub fn backtrace(self) -> Vec<ExpnData> {
+        let mut result = Vec::new();
+        let mut ctxt = self;
+        while let Some(data) = ctxt.expn_data() {
+            ctxt = data.call_site.ctxt;
+            result.push(data);
+        }
+        result
+    }
+}
@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use colored::*;
+
+/// Class of highlighted source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightKind {
+    Keyword,
+    /// Numbers, strings and `true`, `false` or `nil`.
+    Literal,
+    Comment,
+    Identifier,
+    /// Operators and punctuation, like `+`, `::` or `{`.
+    Operator
+}
+
+impl HighlightKind {
+    /// Class of the `<span>` which `render_html` puts the text in.
+    pub fn css_class(self) -> &'static str {
+        match self {
+            Self::Keyword => "hl-keyword",
+            Self::Literal => "hl-literal",
+            Self::Comment => "hl-comment",
+            Self::Identifier => "hl-ident",
+            Self::Operator => "hl-operator"
+        }
+    }
+
+    fn color(self, text: &str) -> ColoredString {
+        match self {
+            Self::Keyword => text.magenta(),
+            Self::Literal => text.green(),
+            Self::Comment => text.bright_black(),
+            Self::Identifier | Self::Operator => text.normal()
+        }
+    }
+}
+
+/// Byte range of text with its class, ranges of one text are sorted and
+/// don't overlap. Text which isn't in any range, like whitespace, is
+/// left as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub kind: HighlightKind,
+    pub range: Range<usize>
+}
+
+/// Finds highlights in a piece of source. Diagnostics only render single
+/// lines, so it needs to handle text which stops in the middle of code.
+pub type Highlighter = fn(&str) -> Vec<Highlight>;
+
+/// Render the text with ANSI colors. They are always rendered, callers
+/// decide whether colors are used, like diagnostics do with `RenderConfig`.
+pub fn render_ansi(text: &str, highlights: &[Highlight]) -> String {
+    render(text, highlights, |result, kind, part| match kind {
+        Some(kind) => result.push_str(&ansi(&kind.color(part))),
+        None => result.push_str(part)
+    })
+}
+
+/// ANSI escapes of the text's colors and styles around it. `colored`
+/// renders them only when its global switch allows, this always does.
+pub(crate) fn ansi(text: &ColoredString) -> String {
+    const STYLES: [(Styles, &str); 4] = [
+        (Styles::Bold, "1"), (Styles::Dimmed, "2"), (Styles::Italic, "3"), (Styles::Underline, "4")
+    ];
+    if text.is_plain() {
+        return text.input.clone()
+    }
+    let codes: Vec<_> = STYLES.iter()
+        .filter(|(styles, _)| text.style.contains(*styles))
+        .map(|(_, code)| std::borrow::Cow::Borrowed(*code))
+        .chain(text.fgcolor.map(|color| color.to_fg_str()))
+        .chain(text.bgcolor.map(|color| color.to_bg_str()))
+        .collect();
+    format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text.input)
+}
+
+/// Render the text as HTML, with highlighted parts in `<span>`s with
+/// classes from `HighlightKind::css_class`. Text is escaped, so the
+/// result can go right into a `<pre>`.
+pub fn render_html(text: &str, highlights: &[Highlight]) -> String {
+    render(text, highlights, |result, kind, part| {
+        if let Some(kind) = kind {
+            result.push_str(&format!("<span class=\"{}\">", kind.css_class()));
+        }
+        for c in part.chars() {
+            match c {
+                '<' => result.push_str("&lt;"),
+                '>' => result.push_str("&gt;"),
+                '&' => result.push_str("&amp;"),
+                '"' => result.push_str("&quot;"),
+                c => result.push(c)
+            }
+        }
+        if kind.is_some() {
+            result.push_str("</span>");
+        }
+    })
+}
+
+/// Split the text into parts by highlights, passing each one to `push`.
+fn render(text: &str, highlights: &[Highlight], mut push: impl FnMut(&mut String, Option<HighlightKind>, &str)) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for highlight in highlights.iter() {
+        // Ranges which don't fit the text are ignored instead of panicking
+        let Range { start, end } = highlight.range;
+        if start < cursor || end > text.len() || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            continue
+        }
+        push(&mut result, None, &text[cursor..start]);
+        push(&mut result, Some(highlight.kind), &text[start..end]);
+        cursor = end;
+    }
+    push(&mut result, None, &text[cursor..]);
+    result
+}
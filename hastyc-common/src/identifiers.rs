@@ -1,48 +1,259 @@
-use std::{sync::atomic::AtomicU32, collections::{BTreeMap, HashMap}, cmp::Ordering};
+use std::{sync::{atomic::AtomicU32, OnceLock, RwLock}, collections::HashMap, cmp::Ordering, fmt::Debug, hash::{BuildHasherDefault, Hasher}};
 
 use crate::span::Span;
 
-/// Storage that matches symbol id to string.
-#[derive(Debug)]
+/// Per-package view of the global interner. Symbols themselves are global,
+/// so they can be compared between packages, this only keeps track of which
+/// symbols were used by the package.
+#[derive(Default)]
 pub struct SymbolStorage {
-    counter: IDCounter,
-    id_map: BTreeMap<u32, String>,
-    inverse_map: HashMap<String, u32>
+    /// Bit set of used symbol ids, symbol ids are dense so this is
+    /// much cheaper to update than a tree or hash set.
+    used: Vec<u64>
+}
+
+impl Debug for SymbolStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.used().map(|symbol| (symbol.0, symbol.as_str())))
+            .finish()
+    }
 }
 
 impl SymbolStorage {
     pub fn new() -> Self {
-        let mut s = Self {
-            counter: IDCounter::create(),
-            id_map: BTreeMap::new(),
-            inverse_map: HashMap::new()
+        Self::default()
+    }
+
+    pub fn get_or_register(&mut self, text: &str) -> Symbol {
+        let symbol = Interner::global().intern(text);
+        let (word, bit) = (symbol.0 as usize / 64, symbol.0 % 64);
+        if word >= self.used.len() {
+            self.used.resize(word + 1, 0);
+        }
+        self.used[word] |= 1 << bit;
+        symbol
+    }
+
+    pub fn text_of(&self, symbol: Symbol) -> Option<&str> {
+        Interner::global().get(symbol)
+    }
+
+    /// Symbols that were registered through this storage.
+    pub fn used(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.used.iter().enumerate().flat_map(|(word_idx, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| Symbol((word_idx * 64) as u32 + bit))
+        })
+    }
+}
+
+const INTERNER_SHARDS: u32 = 16;
+/// Size of arena chunks, longer strings get their own chunk.
+const ARENA_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Global, thread-safe string interner. To reduce lock contention, strings
+/// are split between shards by their hash, and every shard has its own lock.
+/// Each string is stored only once, in the arena of its shard, and both the
+/// lookup map and the id table point into it.
+///
+/// Symbol ids below `PREDEFINED.len()` are the well-known symbols from `kw` and
+/// `sym` modules, the rest encode shard in the lowest bits and index in that
+/// shard in the remaining ones.
+pub struct Interner {
+    shards: Vec<RwLock<InternerShard>>
+}
+
+#[derive(Default)]
+struct InternerShard {
+    map: HashMap<&'static str, Symbol, BuildHasherDefault<FxHasher>>,
+    strings: Vec<&'static str>,
+    arena: StringArena
+}
+
+impl Interner {
+    pub fn global() -> &'static Interner {
+        static INTERNER: OnceLock<Interner> = OnceLock::new();
+        INTERNER.get_or_init(Interner::new)
+    }
+
+    fn new() -> Self {
+        let interner = Self {
+            shards: (0..INTERNER_SHARDS).map(|_| RwLock::default()).collect()
         };
-        s.register("<dummy>");
-        s
+        for (id, text) in PREDEFINED.iter().enumerate() {
+            interner.shards[Self::shard_idx(text)]
+                .write().unwrap()
+                .map.insert(text, Symbol(id as u32));
+        }
+        interner
+    }
+
+    fn shard_idx(text: &str) -> usize {
+        let mut hasher = FxHasher::default();
+        hasher.write(text.as_bytes());
+        // Low bits are used by the hash map, so shard is picked with the high ones
+        (hasher.finish() >> 60) as usize % INTERNER_SHARDS as usize
     }
 
-    fn register(&mut self, text: &str) -> Symbol {
-        let id = self.counter.next();
-        self.id_map.insert(id, text.to_string());
-        self.inverse_map.insert(text.to_string(), id);
+    pub fn intern(&self, text: &str) -> Symbol {
+        let shard_idx = Self::shard_idx(text);
+        let shard = &self.shards[shard_idx];
+        if let Some(symbol) = shard.read().unwrap().map.get(text) {
+            return *symbol
+        }
+
+        let mut shard_data = shard.write().unwrap();
+        // Other thread could have registered it between the locks
+        if let Some(symbol) = shard_data.map.get(text) {
+            return *symbol
+        }
+
+        let id = PREDEFINED.len() as u32
+            + shard_data.strings.len() as u32 * INTERNER_SHARDS
+            + shard_idx as u32;
+
+        let text = shard_data.arena.alloc(text);
+        shard_data.strings.push(text);
+        shard_data.map.insert(text, Symbol(id));
         Symbol(id)
     }
 
-    pub fn get_or_register(&mut self, text: &str) -> Symbol {
-        if let Some(id) = self.inverse_map.get(text) {
-            Symbol(*id)
-        } else {
-            self.register(text)
+    pub fn get(&self, symbol: Symbol) -> Option<&'static str> {
+        let Some(dynamic) = symbol.0.checked_sub(PREDEFINED.len() as u32) else {
+            return Some(PREDEFINED[symbol.0 as usize])
+        };
+        let shard = self.shards[(dynamic % INTERNER_SHARDS) as usize].read().unwrap();
+        shard.strings.get((dynamic / INTERNER_SHARDS) as usize).copied()
+    }
+}
+
+/// Bump allocator for interned strings. Chunks are leaked, as interner
+/// lives for the whole compilation anyway.
+#[derive(Default)]
+struct StringArena {
+    free: &'static mut [u8]
+}
+
+impl StringArena {
+    fn alloc(&mut self, text: &str) -> &'static str {
+        let len = text.len();
+        if len > self.free.len() {
+            let chunk = vec![0u8; len.max(ARENA_CHUNK_SIZE)].into_boxed_slice();
+            self.free = Box::leak(chunk);
+        }
+        let (slot, rest) = std::mem::take(&mut self.free).split_at_mut(len);
+        self.free = rest;
+        slot.copy_from_slice(text.as_bytes());
+        // Bytes were copied from valid str, so this never fails
+        std::str::from_utf8(slot).unwrap()
+    }
+}
+
+/// Fast, non-cryptographic hasher (same algorithm as rustc's FxHasher),
+/// interned strings are short so SipHash would dominate lookup time.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct FxHasher {
+    hash: u64
+}
+
+impl FxHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in chunks.by_ref() {
+            self.add_to_hash(u64::from_le_bytes(chunk.try_into().unwrap()));
         }
+        for byte in chunks.remainder() {
+            self.add_to_hash(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
     }
 
-    pub fn text_of(&self, symbol: Symbol) -> Option<&String> {
-        self.id_map.get(&symbol.0)
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+macro_rules! predefined_symbols {
+    (
+        kw { $($kw:ident: $kw_text:literal),* $(,)? }
+        sym { $($sym:ident),* $(,)? }
+    ) => {
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[repr(u32)]
+        enum PredefinedIdx {
+            $($kw,)*
+            $($sym,)*
+        }
+
+        static PREDEFINED: &[&str] = &[
+            $($kw_text,)*
+            $(stringify!($sym),)*
+        ];
+
+        /// Pre-interned keywords.
+        #[allow(non_upper_case_globals)]
+        pub mod kw {
+            use super::{PredefinedIdx, Symbol};
+            $(pub const $kw: Symbol = Symbol(PredefinedIdx::$kw as u32);)*
+        }
+
+        /// Pre-interned well-known identifiers, like primitive type names.
+        #[allow(non_upper_case_globals)]
+        pub mod sym {
+            use super::{PredefinedIdx, Symbol};
+            $(pub const $sym: Symbol = Symbol(PredefinedIdx::$sym as u32);)*
+        }
+    };
+}
+
+predefined_symbols! {
+    kw {
+        Dummy: "<dummy>",
+        // Module with builtins injected into every package, which can't be named either
+        Prelude: "<prelude>",
+        Fn: "fn", If: "if", Else: "else", True: "true", False: "false",
+        While: "while", For: "for", In: "in", Loop: "loop", Break: "break",
+        Continue: "continue", Return: "return", SelfLower: "self", SelfUpper: "Self",
+        Let: "let", Nil: "nil", Guard: "guard", Pub: "pub", Const: "const",
+        Static: "static", Import: "import", As: "as", Module: "module",
+        Super: "super", Pkg: "pkg", Match: "match", Struct: "struct",
+        Trait: "trait", Impl: "impl", Enum: "enum", Getter: "getter",
+        Setter: "setter", Override: "override", Where: "where", Async: "async",
+        Await: "await", Extern: "extern"
+    }
+    sym {
+        i8, i16, i32, i64, isize,
+        u8, u16, u32, u64, usize,
+        f32, f64, bool, char, str, String,
+        main,
+        // Lint attributes, like `#[allow(dead_code)]`
+        allow, dead_code, shadowed_binding,
+        // Builtin functions of the prelude, and the attribute disabling it
+        print, println, panic, no_prelude,
+        concat, format_i32, format_i64, format_u64, format_f64, format_bool
     }
 }
 
 /// Single identifier like "Hello", "function_name" or sth like that.
+///
+/// Identifiers are compared, ordered and hashed by their symbol only, so
+/// the same name written in two places is the same identifier no matter
+/// where it comes from. Use `span` directly if location matters.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ident {
     pub symbol: Symbol,
     pub span: Span
@@ -56,12 +267,24 @@ impl Ident {
         }
     }
 
+    /// Placeholder identifier for nodes that don't have a name, like imports.
+    /// It uses `kw::Dummy` symbol, which can never come from the source, so
+    /// it is never equal to any real identifier. Its span points nowhere.
     pub fn dummy() -> Self {
         Self {
-            symbol: Symbol(0),
+            symbol: kw::Dummy,
             span: Span::dummy()
         }
     }
+
+    pub fn is_dummy(&self) -> bool {
+        self.symbol == kw::Dummy
+    }
+
+    /// Text of this identifier.
+    pub fn text<'s>(&self, storage: &'s SymbolStorage) -> &'s str {
+        storage.text_of(self.symbol).expect("Ident symbol is not registered in the interner")
+    }
 }
 
 impl PartialEq for Ident {
@@ -71,24 +294,48 @@ impl PartialEq for Ident {
 }
 impl Eq for Ident {}
 
+impl std::hash::Hash for Ident {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.symbol.hash(state);
+    }
+}
+
 impl PartialOrd for Ident {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.symbol.cmp(&other.symbol))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Ident {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Unwrapping here is safe as partial_cmp uses cmp under the hood
-        self.partial_cmp(other).unwrap()
+        self.symbol.cmp(&other.symbol)
     }
 }
 
 /// Symbol used for string interning, this holds only id of internal ident
 /// for memory optimization purposes.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Symbol(pub(crate) u32);
 
+impl Symbol {
+    /// Intern text in the global interner.
+    pub fn intern(text: &str) -> Self {
+        Interner::global().intern(text)
+    }
+
+    /// Text of this symbol. Every symbol comes from the global
+    /// interner, so this never fails for symbols created by it.
+    pub fn as_str(&self) -> &'static str {
+        Interner::global().get(*self).expect("Symbol is not registered in the interner")
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Counter that uses atomic u32 internally. Used for
 /// generation of unique identifiers.
 #[derive(Debug)]
@@ -100,6 +347,14 @@ impl IDCounter {
     pub fn next(&self) -> u32 {
         self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
     }
+
+    /// Make sure that the given id is never returned from `next`, this is
+    /// used for ids that were created elsewhere, eg. loaded from cache.
+    /// Returns false if the id may have already been given out.
+    pub fn reserve(&self, id: u32) -> bool {
+        let previous = self.0.fetch_max(id.saturating_add(1), std::sync::atomic::Ordering::SeqCst);
+        id >= previous
+    }
 }
 impl Clone for IDCounter {
     fn clone(&self) -> Self {
@@ -107,17 +362,29 @@ impl Clone for IDCounter {
     }
 }
 
+/// Ids that are unique in the whole compilation are all issued by one
+/// global counter per type, so that they can be created from any thread.
 macro_rules! impl_basic_id {
     ($name:ident) => {
         impl $name {
-            const COUNTER: IDCounter = IDCounter::create();
-
             pub fn new(id: u32) -> Self {
                 Self(id)
             }
 
+            fn counter() -> &'static IDCounter {
+                static COUNTER: IDCounter = IDCounter::create();
+                &COUNTER
+            }
+
             pub fn new_unique() -> Self {
-                Self(Self::COUNTER.next())
+                Self(Self::counter().next())
+            }
+
+            /// Take an id that was created outside of this compilation, eg.
+            /// by deserializing cached artifact, so that `new_unique` never
+            /// returns it. None if the id could have already been issued.
+            pub fn reserve(id: u32) -> Option<Self> {
+                Self::counter().reserve(id).then_some(Self(id))
             }
         }
     };
@@ -135,23 +402,103 @@ macro_rules! impl_from_counter {
 /// ID of package, this is unique for every crate during compilation,
 /// but may change between compilations, so It shouldn't be used
 /// between them.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PkgID(pub u32);
 impl_basic_id!(PkgID);
 
 /// ID of source file, this is generated as unique for every
 /// source file in the current compilation.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SourceFileID(pub u32);
 impl_basic_id!(SourceFileID);
 
 /// ID of node in AST tree. This is unique **ONLY** in package context,
 /// and it may occur that this repeats between multiple packages.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ASTNodeID(pub u32);
 impl ASTNodeID {
     pub fn new(id: u32) -> Self {
         Self(id)
     }
 }
-impl_from_counter!(ASTNodeID);
\ No newline at end of file
+impl_from_counter!(ASTNodeID);
+
+/// Symbol ids depend on the order of interning, so symbols are serialized
+/// as their text and interned again when deserialized. Thanks to this they
+/// survive round-trips between different compiler runs.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{IDCounter, PkgID, SourceFileID, Symbol, SymbolStorage};
+
+    impl Serialize for Symbol {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Symbol {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let text = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+            Ok(Symbol::intern(&text))
+        }
+    }
+
+    /// Storage is serialized as a list of used symbols.
+    impl Serialize for SymbolStorage {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.used())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SymbolStorage {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let symbols = Vec::<std::borrow::Cow<str>>::deserialize(deserializer)?;
+            let mut storage = SymbolStorage::new();
+            for text in symbols.iter() {
+                storage.get_or_register(text);
+            }
+            Ok(storage)
+        }
+    }
+
+    /// Package and file ids are reserved when deserialized, so that
+    /// ids loaded from cache never clash with newly created ones.
+    macro_rules! impl_reserved_id {
+        ($name:ident) => {
+            impl Serialize for $name {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_u32(self.0)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $name {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let id = u32::deserialize(deserializer)?;
+                    // The same id is usually deserialized many times, eg. in every span,
+                    // so it being already taken is expected here.
+                    $name::counter().reserve(id);
+                    Ok($name(id))
+                }
+            }
+        };
+    }
+    impl_reserved_id!(PkgID);
+    impl_reserved_id!(SourceFileID);
+
+    /// Counter is serialized as the next id it will return.
+    impl Serialize for IDCounter {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u32(self.0.load(std::sync::atomic::Ordering::SeqCst))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for IDCounter {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let next = u32::deserialize(deserializer)?;
+            Ok(IDCounter(std::sync::atomic::AtomicU32::new(next)))
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt::Write;
+
+use crate::{error::ErrorFmt, source::SourceFile, span::Span};
+
+/// Emits diagnostics as JSON lines, so that editors and other tools can
+/// read compiler output without parsing colored text. Every diagnostic
+/// is a single line with the following shape:
+///
+/// ```json
+/// {
+///   "severity": "error",
+///   "code": "H0110",
+///   "message": "Expected ';' but found 'let'.",
+///   "spans": [{ "file": "main.hy", "byte_start": 10, "byte_end": 10,
+///               "file_byte_start": 12, "file_byte_end": 12,
+///               "line_start": 1, "col_start": 11, "line_end": 1, "col_end": 11 }],
+///   "children": [{ "kind": "cause", "message": "..." }],
+///   "suggestions": [{ "message": "...", "replacement": ";", "span": { ... } }]
+/// }
+/// ```
+///
+/// `severity` is one of "error", "warning", "note" or "help". `code` is
+/// null for errors without code, and suggestion `span` is null if its
+/// source file was not attached to the diagnostic.
+///
+/// `byte_start` and `byte_end` are offsets in the normalized text of the
+/// file, without byte order mark and with "\n" line endings, which lines
+/// and columns are counted in too. `file_byte_start` and `file_byte_end`
+/// are offsets in the file as it is on disk, where suggestions apply.
+pub struct JsonEmitter;
+
+impl JsonEmitter {
+    pub fn emit(fmt: &ErrorFmt) -> String {
+        let mut out = String::new();
+        out.push_str("{\"severity\":");
+        push_str(&mut out, fmt.severity.label());
+        out.push_str(",\"code\":");
+        match fmt.code {
+            Some(code) => push_str(&mut out, code),
+            None => out.push_str("null")
+        }
+        out.push_str(",\"message\":");
+        push_str(&mut out, fmt.message.as_deref().unwrap_or_default());
+
+        out.push_str(",\"spans\":[");
+        for (i, (source, span, _)) in fmt.labels.iter().enumerate() {
+            if i > 0 { out.push(',') }
+            push_span(&mut out, source, span);
+        }
+
+        out.push_str("],\"children\":[");
+        for (i, (kind, message)) in fmt.children.iter().enumerate() {
+            if i > 0 { out.push(',') }
+            out.push_str("{\"kind\":");
+            push_str(&mut out, kind);
+            out.push_str(",\"message\":");
+            push_str(&mut out, message);
+            out.push('}');
+        }
+
+        out.push_str("],\"suggestions\":[");
+        for (i, suggestion) in fmt.suggestions.iter().enumerate() {
+            if i > 0 { out.push(',') }
+            out.push_str("{\"message\":");
+            push_str(&mut out, &suggestion.message);
+            out.push_str(",\"replacement\":");
+            push_str(&mut out, &suggestion.replacement);
+            out.push_str(",\"span\":");
+            let source = fmt.labels.iter()
+                .map(|(source, _, _)| source)
+                .find(|source| source.id == suggestion.span.source);
+            match source {
+                Some(source) => push_span(&mut out, source, &suggestion.span),
+                None => out.push_str("null")
+            }
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn push_span(out: &mut String, source: &SourceFile, span: &Span) {
+    let (line_start, col_start) = source.lookup_line(span.start);
+    let (line_end, col_end) = source.lookup_line(span.end);
+    out.push_str("{\"file\":");
+    push_str(out, &source.name.to_string());
+    write!(
+        out,
+        ",\"byte_start\":{},\"byte_end\":{},\"file_byte_start\":{},\"file_byte_end\":{},\"line_start\":{},\"col_start\":{},\"line_end\":{},\"col_end\":{}}}",
+        span.start,
+        span.end,
+        source.removed_bytes.original_offset(span.start),
+        source.removed_bytes.original_offset(span.end),
+        line_start,
+        col_start,
+        line_end,
+        col_end
+    ).unwrap();
+}
+
+/// Push quoted and escaped JSON string.
+fn push_str(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
@@ -1,5 +1,14 @@
 pub mod source;
 pub mod span;
+pub mod expansion;
 pub mod identifiers;
 pub mod path;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod error_codes;
+pub mod highlight;
+pub mod json;
+pub mod lsp;
+pub mod sink;
+pub mod session;
+pub mod stable_hash;
+pub mod arena;
\ No newline at end of file
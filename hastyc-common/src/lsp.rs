@@ -0,0 +1,154 @@
+use crate::{error::{DiagnosticData, Severity}, source::{FileName, SourceFile, SourceMap}, span::Span};
+
+// Data shaped like the Language Server Protocol types with the same names,
+// so that language server can send it without converting it again. With
+// `serde` feature these serialize to exactly the JSON that LSP expects.
+
+/// Position in a document, both are counted from 0 and `character`
+/// is counted in UTF-16 code units, as LSP requires by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LspLocation {
+    pub uri: String,
+    pub range: LspRange
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LspRelatedInformation {
+    pub location: LspLocation,
+    pub message: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct LspTextEdit {
+    pub range: LspRange,
+    pub new_text: String
+}
+
+/// Suggestion that editor can offer as a quick fix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LspFix {
+    pub title: String,
+    pub uri: String,
+    pub edit: LspTextEdit
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct LspDiagnostic {
+    pub range: LspRange,
+    /// 1 = error, 2 = warning, 3 = information, 4 = hint.
+    pub severity: u8,
+    pub code: Option<String>,
+    pub source: String,
+    pub message: String,
+    pub related_information: Vec<LspRelatedInformation>,
+    /// Not a part of LSP diagnostic, language server should turn
+    /// these into code actions.
+    pub fixes: Vec<LspFix>
+}
+
+impl LspDiagnostic {
+    /// Convert diagnostic into LSP shape, returning it together with uri
+    /// of the document it belongs to. None if diagnostic has no span or
+    /// its source is not in the source map.
+    pub fn from_data(data: &DiagnosticData, sources: &SourceMap) -> Option<(String, LspDiagnostic)> {
+        let primary = data.primary_span?;
+        let location = Self::location(&primary, sources)?;
+
+        // LSP has no children, so they are appended to the message
+        let mut message = data.message.clone();
+        for (kind, child) in data.children.iter() {
+            message.push_str(&format!("\n{}: {}", kind, child));
+        }
+
+        // Spans shown without a note are described by the diagnostic itself
+        let related_information = data.related_spans.iter()
+            .filter_map(|(span, note)| {
+                let message = note.as_deref().map_or(data.message.as_str(), |note| note.trim_end_matches(':'));
+                Some(LspRelatedInformation { location: Self::location(span, sources)?, message: message.to_string() })
+            })
+            .collect();
+        let fixes = data.suggestions.iter()
+            .filter_map(|suggestion| {
+                let location = Self::location(&suggestion.span, sources)?;
+                Some(LspFix {
+                    title: suggestion.message.clone(),
+                    uri: location.uri,
+                    edit: LspTextEdit { range: location.range, new_text: suggestion.replacement.clone() }
+                })
+            })
+            .collect();
+
+        let diagnostic = LspDiagnostic {
+            range: location.range,
+            severity: match data.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+                Severity::Note => 3,
+                Severity::Help => 4
+            },
+            code: data.code.map(str::to_string),
+            source: "hastyc".to_string(),
+            message,
+            related_information,
+            fixes
+        };
+        Some((location.uri, diagnostic))
+    }
+
+    fn location(span: &Span, sources: &SourceMap) -> Option<LspLocation> {
+        let source = sources.source_of(span)?;
+        Some(LspLocation {
+            uri: uri(source, sources),
+            range: LspRange {
+                start: position(source, span.start),
+                end: position(source, span.end)
+            }
+        })
+    }
+}
+
+/// Uri of the document, files on disk get `file://` uris, other
+/// sources get made up ones, which editors can't open.
+pub fn uri(source: &SourceFile, sources: &SourceMap) -> String {
+    match source.name {
+        FileName::LocalPath(_) => {
+            let path = sources.full_path(&source.name).unwrap_or_default();
+            let path = path.canonicalize().unwrap_or(path);
+            format!("file://{}", path.display().to_string().replace('\\', "/"))
+        },
+        FileName::Virtual(ref name) => format!("hastyc-virtual:{}", name),
+        FileName::RawText => format!("hastyc-raw:{}", source.id.0)
+    }
+}
+
+/// Convert byte offset into LSP position.
+pub fn position(source: &SourceFile, offset: u32) -> LspPosition {
+    let (line, _) = source.lookup_line(offset);
+    let (line_start, _) = source.line_bounds(line);
+    let before = source.get_span(&Span::new(source.id, line_start, offset.min(source.len as u32)));
+    LspPosition {
+        line: line - 1,
+        character: before.encode_utf16().count() as u32
+    }
+}
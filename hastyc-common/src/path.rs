@@ -1,7 +1,10 @@
-use crate::{identifiers::Ident, span::Span};
+use std::{collections::HashMap, hash::BuildHasherDefault, sync::{OnceLock, RwLock}};
+
+use crate::{identifiers::{FxHasher, Ident, Symbol}, span::Span};
 
 /// Path to an item. For example this could be `hello::world::MyStruct`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Path {
     pub segments: Vec<PathSegment>,
     pub span: Span
@@ -23,6 +26,48 @@ impl Path {
         self.segments.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Interned id of this path, which only depends on names of its segments.
+    pub fn id(&self) -> PathId {
+        let symbols = self.segments.iter()
+            .map(|seg| seg.ident.symbol)
+            .collect::<Vec<_>>();
+        PathId::intern(&symbols)
+    }
+
+    /// Path with segments of the other path added at the end,
+    /// eg. `a::b` joined with `c::d` is `a::b::c::d`.
+    pub fn join(&self, other: &Path) -> Path {
+        let mut segments = self.segments.clone();
+        segments.extend(other.segments.iter().cloned());
+        let span = match (self.is_empty(), other.is_empty()) {
+            (true, _) => other.span,
+            (_, true) => self.span,
+            _ => Span::from_begin_end(self.span, other.span)
+        };
+        Path { segments, span }
+    }
+
+    /// Path with the ident added as the last segment.
+    pub fn with_segment(&self, ident: Ident) -> Path {
+        let span = if self.is_empty() { ident.span } else { Span::from_begin_end(self.span, ident.span) };
+        let mut segments = self.segments.clone();
+        segments.push(PathSegment::new(ident));
+        Path { segments, span }
+    }
+
+    /// Span of segments starting from the given index.
+    pub fn span_from(&self, start_idx: u32) -> Span {
+        match self.segments.get(start_idx as usize..) {
+            Some([first, .., last]) => Span::from_begin_end(first.ident.span, last.ident.span),
+            Some([only]) => only.ident.span,
+            _ => self.span
+        }
+    }
+
     pub fn shifted_clone(&self, count: u32) -> Path {
         let mut new_segments = Vec::new();
         for seg in self.segments.clone().into_iter().skip(count as usize) {
@@ -39,6 +84,7 @@ impl Path {
 
 /// Single path segment representing just one path ident.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathSegment {
     pub ident: Ident
 }
@@ -51,8 +97,91 @@ impl PathSegment {
     }
 }
 
-impl Into<Ident> for PathSegment {
-    fn into(self) -> Ident {
-        self.ident
+impl From<PathSegment> for Ident {
+    fn from(val: PathSegment) -> Self {
+        val.ident
+    }
+}
+/// Interned path, two ids are equal if paths have the same segment names,
+/// no matter where they were written. Unlike `Path` this is `Copy` and
+/// cheap to compare, so it is meant for keys of import/export tables
+/// and for errors that only need to know which path it was.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathId(u32);
+
+/// Global interner of paths, this works the same way as the symbol
+/// interner, but for whole sequences of symbols.
+#[derive(Default)]
+struct PathInterner {
+    map: HashMap<&'static [Symbol], PathId, BuildHasherDefault<FxHasher>>,
+    paths: Vec<&'static [Symbol]>
+}
+
+impl PathInterner {
+    fn global() -> &'static RwLock<PathInterner> {
+        static INTERNER: OnceLock<RwLock<PathInterner>> = OnceLock::new();
+        INTERNER.get_or_init(RwLock::default)
     }
-}
\ No newline at end of file
+}
+
+impl PathId {
+    pub fn intern(segments: &[Symbol]) -> Self {
+        let interner = PathInterner::global();
+        if let Some(id) = interner.read().unwrap().map.get(segments) {
+            return *id
+        }
+
+        let mut interner = interner.write().unwrap();
+        // Other thread could have registered it between the locks
+        if let Some(id) = interner.map.get(segments) {
+            return *id
+        }
+        // Paths live for the whole compilation, so they are just leaked
+        let segments: &'static [Symbol] = Box::leak(segments.into());
+        let id = PathId(interner.paths.len() as u32);
+        interner.paths.push(segments);
+        interner.map.insert(segments, id);
+        id
+    }
+
+    pub fn segments(self) -> &'static [Symbol] {
+        PathInterner::global().read().unwrap().paths[self.0 as usize]
+    }
+
+    pub fn len(self) -> usize {
+        self.segments().len()
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.segments().is_empty()
+    }
+
+    /// Path without its last segment, None for empty path.
+    pub fn parent(self) -> Option<PathId> {
+        let (_, parent) = self.segments().split_last()?;
+        Some(Self::intern(parent))
+    }
+
+    /// Path with the segment added at the end.
+    pub fn join(self, segment: Symbol) -> PathId {
+        let mut segments = self.segments().to_vec();
+        segments.push(segment);
+        Self::intern(&segments)
+    }
+}
+
+impl std::fmt::Display for PathId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.segments().iter().enumerate() {
+            if i > 0 { f.write_str("::")? }
+            f.write_str(segment.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for PathId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PathId({}, `{}`)", self.0, self)
+    }
+}
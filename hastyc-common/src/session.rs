@@ -0,0 +1,296 @@
+use std::{cell::{Ref, RefCell}, collections::HashSet};
+
+use crate::{error::{Diagnostic, RenderConfig}, identifiers::Symbol, sink::{DiagnosticCollector, DiagnosticSink}, source::SourceMap};
+
+/// Version of the language that the code is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Edition {
+    #[default]
+    Edition2024
+}
+
+/// What compiler should output after the compilation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmitKind {
+    /// Tokens of the root file with their kind, text and position.
+    Tokens,
+    Ast,
+    /// Syntax tree of the package as JSON, with node ids, spans and
+    /// the text of symbols, for tools which don't link the compiler.
+    AstJson,
+    Executable,
+    /// Object file of native backends as it is, without linking it
+    /// into an executable.
+    Object,
+    /// Bytecode of the package with its definitions, which can be run
+    /// or depended on without compiling it again.
+    Package,
+    /// What every resolved path refers to.
+    ResolvedNames,
+    /// Source of the package with every name followed by what it
+    /// resolved to, or flagged if it didn't resolve.
+    ResolvedSource,
+    /// Ribs of every function with the names bound in them, name
+    /// resolution only records them when this is requested.
+    Scopes,
+    /// Types of all nodes found by type checking.
+    Types,
+    /// Modules, items, fields and variants as a DOT graph.
+    ItemGraph,
+    /// Expression tree of one function as a DOT graph, the driver
+    /// says which function.
+    ExprGraph,
+    /// Submodules and imports between modules as a DOT graph, after
+    /// name resolution.
+    ModuleGraph
+}
+
+impl EmitKind {
+    /// Parse kind from command line value, eg. "resolved-names".
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tokens" => Some(Self::Tokens),
+            "ast" => Some(Self::Ast),
+            "ast-json" => Some(Self::AstJson),
+            "executable" => Some(Self::Executable),
+            "object" => Some(Self::Object),
+            "package" => Some(Self::Package),
+            "resolved-names" => Some(Self::ResolvedNames),
+            "resolved" => Some(Self::ResolvedSource),
+            "scopes" => Some(Self::Scopes),
+            "types" => Some(Self::Types),
+            "item-graph" => Some(Self::ItemGraph),
+            "expr-graph" => Some(Self::ExprGraph),
+            "module-graph" => Some(Self::ModuleGraph),
+            _ => None
+        }
+    }
+}
+
+/// Backend which generates code from MIR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BackendKind {
+    /// Bytecode, which is run right away.
+    #[default]
+    Vm,
+    /// C99 source.
+    C,
+    /// Object file for the host, only available if the compiler
+    /// was built with LLVM.
+    Llvm
+}
+
+impl BackendKind {
+    /// Parse backend from command line value, eg. "llvm".
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "vm" => Some(Self::Vm),
+            "c" => Some(Self::C),
+            "llvm" => Some(Self::Llvm),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Vm => "vm",
+            Self::C => "c",
+            Self::Llvm => "llvm"
+        }
+    }
+}
+
+/// How much MIR is optimized before code is generated, each level
+/// runs the passes of the one below it and more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum OptLevel {
+    /// MIR is left as it was built.
+    #[default]
+    O0,
+    /// Transforms which only simplify single bodies.
+    O1,
+    /// Small functions are inlined as well.
+    O2
+}
+
+impl OptLevel {
+    /// Parse level from command line value, eg. "2" of `-O2`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "0" => Some(Self::O0),
+            "1" => Some(Self::O1),
+            "2" => Some(Self::O2),
+            _ => None
+        }
+    }
+}
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosticFormat {
+    #[default]
+    Human,
+    /// One JSON object per line, see `json::JsonEmitter`.
+    Json,
+    /// Deterministic text without colors, for golden-file tests.
+    Plain
+}
+
+impl DiagnosticFormat {
+    /// Parse format from command line value: "human", "json" or "plain".
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "human" => Some(Self::Human),
+            "json" => Some(Self::Json),
+            "plain" => Some(Self::Plain),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticOptions {
+    pub format: DiagnosticFormat,
+    pub render: RenderConfig,
+    /// Number of errors after which compilation is aborted.
+    pub error_limit: Option<usize>
+}
+
+/// What name resolution does when a variable is bound with the same
+/// name as another variable that is still in scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadowingPolicy {
+    /// New binding hides the old one, like in `let a = a + 1;`.
+    #[default]
+    Allow,
+    /// Same as `Allow`, but every shadowing binding is reported
+    /// with the `shadowed_binding` warning.
+    Warn,
+    /// Shadowing bindings are errors.
+    Deny
+}
+
+impl ShadowingPolicy {
+    /// Parse policy from command line value, eg. "warn" of `--shadowing=warn`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "allow" => Some(Self::Allow),
+            "warn" => Some(Self::Warn),
+            "deny" => Some(Self::Deny),
+            _ => None
+        }
+    }
+}
+
+/// Options of the current compilation, usually built from command line.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub edition: Edition,
+    /// Unstable language features enabled by the user.
+    pub features: HashSet<String>,
+    pub diagnostics: DiagnosticOptions,
+    pub shadowing: ShadowingPolicy,
+    /// Target triple, eg. "x86_64-linux".
+    pub target: String,
+    pub emit: Vec<EmitKind>,
+    pub backend: BackendKind,
+    pub opt_level: OptLevel,
+    /// How deep expressions can be nested for the passes, see
+    /// `DEFAULT_MAX_EXPR_DEPTH`.
+    pub max_expr_depth: u32
+}
+
+/// Maximum nesting depth of expressions that passes walk through. Chains
+/// of binary operators, which are common in machine generated code, are
+/// parsed in a loop and can nest much deeper than recursive passes can go
+/// without overflowing the stack. Debug builds go through all passes with
+/// about 1700 levels on a 8 MiB stack, so this leaves some room.
+pub const DEFAULT_MAX_EXPR_DEPTH: u32 = 1024;
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            edition: Edition::default(),
+            features: HashSet::new(),
+            diagnostics: DiagnosticOptions::default(),
+            shadowing: ShadowingPolicy::default(),
+            target: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+            emit: vec![EmitKind::Executable],
+            backend: BackendKind::default(),
+            opt_level: OptLevel::default(),
+            max_expr_depth: DEFAULT_MAX_EXPR_DEPTH
+        }
+    }
+}
+
+/// State shared by all stages of a single compilation: options, loaded
+/// sources and reported diagnostics. Lexer, parser and passes all take
+/// `&Session`, so diagnostics are reported through interior mutability.
+#[derive(Debug)]
+pub struct Session {
+    pub options: Options,
+    pub source_map: SourceMap,
+    diagnostics: RefCell<DiagnosticCollector>
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
+}
+
+impl Session {
+    pub fn new(options: Options) -> Self {
+        let diagnostics = match options.diagnostics.error_limit {
+            Some(limit) => DiagnosticCollector::with_error_limit(limit),
+            None => DiagnosticCollector::new()
+        };
+        Self {
+            options,
+            source_map: SourceMap::new(),
+            diagnostics: RefCell::new(diagnostics)
+        }
+    }
+
+    pub fn with_source_map(options: Options, source_map: SourceMap) -> Self {
+        Self {
+            source_map,
+            ..Self::new(options)
+        }
+    }
+
+    pub fn feature_enabled(&self, feature: &str) -> bool {
+        self.options.features.contains(feature)
+    }
+
+    /// Symbols are interned globally, this is just a shortcut.
+    pub fn intern(&self, text: &str) -> Symbol {
+        Symbol::intern(text)
+    }
+
+    pub fn emit(&self, diagnostic: impl Diagnostic + 'static) {
+        self.emit_boxed(Box::new(diagnostic));
+    }
+
+    pub fn emit_boxed(&self, diagnostic: Box<dyn Diagnostic>) {
+        self.diagnostics.borrow_mut().report(diagnostic);
+    }
+
+    pub fn diagnostics(&self) -> Ref<'_, DiagnosticCollector> {
+        self.diagnostics.borrow()
+    }
+
+    /// Take all reported diagnostics, eg. to print them.
+    pub fn take_diagnostics(&self) -> Vec<Box<dyn Diagnostic>> {
+        self.diagnostics.borrow_mut().take()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.borrow().has_errors()
+    }
+
+    /// Whether so many errors were reported, that compilation should stop.
+    pub fn should_abort(&self) -> bool {
+        self.diagnostics.borrow().should_abort()
+    }
+}
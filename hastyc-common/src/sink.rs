@@ -0,0 +1,93 @@
+use crate::error::{CommonErrorContext, Diagnostic, Severity};
+
+/// Receiver of diagnostics from all stages of the compiler. Lexer, parser
+/// and passes report everything they find here, and the driver decides
+/// how and when it is displayed.
+pub trait DiagnosticSink {
+    fn report(&mut self, diagnostic: Box<dyn Diagnostic>);
+
+    /// Number of reported diagnostics with the given severity.
+    fn count(&self, severity: Severity) -> usize;
+
+    /// Whether so many errors were reported, that compilation should
+    /// stop as soon as possible instead of trying to continue.
+    fn should_abort(&self) -> bool;
+
+    fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+/// Sink that keeps all diagnostics in a list, in the order in which
+/// they were reported.
+#[derive(Debug, Default)]
+pub struct DiagnosticCollector {
+    diagnostics: Vec<Box<dyn Diagnostic>>,
+    /// Counts indexed by severity.
+    counts: [usize; 4],
+    /// Number of errors after which compilation should be aborted.
+    error_limit: Option<usize>
+}
+
+impl DiagnosticCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_error_limit(limit: usize) -> Self {
+        Self {
+            error_limit: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[Box<dyn Diagnostic>] {
+        &self.diagnostics
+    }
+
+    /// Take all diagnostics out of the collector, resetting the counts.
+    pub fn take(&mut self) -> Vec<Box<dyn Diagnostic>> {
+        self.counts = [0; 4];
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Render all diagnostics as plain text, for golden-file tests. They are
+    /// ordered by position of their first span, so the output doesn't depend
+    /// on the order in which passes reported them.
+    pub fn render_plain(&self, ctx: &CommonErrorContext) -> String {
+        let mut rendered = self.diagnostics.iter()
+            .map(|diagnostic| {
+                let mut fmt = diagnostic.to_fmt(ctx);
+                let position = fmt.labels.first().map(|(_, span, _)| (span.source.0, span.start, span.end));
+                (position, fmt.build_plain())
+            })
+            .collect::<Vec<_>>();
+        rendered.sort_by_key(|(position, _)| *position);
+        rendered.into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Move all diagnostics into another sink.
+    pub fn drain_into(&mut self, sink: &mut dyn DiagnosticSink) {
+        for diagnostic in self.take() {
+            sink.report(diagnostic);
+        }
+    }
+}
+
+impl DiagnosticSink for DiagnosticCollector {
+    fn report(&mut self, diagnostic: Box<dyn Diagnostic>) {
+        self.counts[diagnostic.severity() as usize] += 1;
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.counts[severity as usize]
+    }
+
+    fn should_abort(&self) -> bool {
+        self.error_limit.is_some_and(|limit| self.count(Severity::Error) >= limit)
+    }
+}
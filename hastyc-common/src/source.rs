@@ -1,6 +1,6 @@
-use std::{fmt::Debug, path::PathBuf};
+use std::{fmt::Debug, ops::Deref, path::{Path, PathBuf}, sync::Arc, time::SystemTime};
 
-use crate::{identifiers::{PkgID, SourceFileID}, span::Span};
+use crate::{identifiers::{PkgID, SourceFileID}, span::Span, stable_hash::{Fingerprint, StableHash}};
 
 /// Source file mapping. This is used for keeping track of
 /// where does specified part of the source code come from.
@@ -9,13 +9,23 @@ pub struct SourceFile {
     /// Path/name of the file from where this is.
     pub name: FileName,
     /// Full source code of the given file.
-    pub src: Option<String>,
-    /// Length of the source code in characters.
-    pub clen: usize,
+    pub src: Option<SourceText>,
+    /// Length of the source code in bytes.
+    pub len: usize,
     /// Package associated with this source file.
     pub pkg: PkgID,
     /// ID associated with this source.
-    pub id: SourceFileID
+    pub id: SourceFileID,
+    /// Hash of the source text, used to tell whether the file changed.
+    pub checksum: Fingerprint,
+    /// Modification time of the file on disk, None for sources
+    /// that don't come from disk.
+    pub modified: Option<SystemTime>,
+    /// What normalizing the text removed from it, see `normalize_text`.
+    pub removed_bytes: RemovedBytes,
+    /// Offset of the first character of every line, computed
+    /// once so that diagnostics don't have to re-scan the source.
+    line_starts: Vec<u32>
 }
 
 impl Debug for SourceFile {
@@ -26,6 +36,7 @@ impl Debug for SourceFile {
             .field("src", &self.src)
             .field("pkg", &self.pkg)
             .field("id", &self.id)
+            .field("checksum", &self.checksum)
             .finish()
     }
 }
@@ -35,7 +46,11 @@ impl Debug for SourceFile {
 /// need enum to keep track of this.
 #[derive(Debug, Clone)]
 pub enum FileName {
-    LocalPath(String),
+    /// File on disk. Path is relative to the project root if the file
+    /// is inside of it, see [`SourceMap::file_name`].
+    LocalPath(PathBuf),
+    /// Source that doesn't come from any file, eg. generated code.
+    Virtual(String),
     RawText
 }
 
@@ -43,10 +58,8 @@ impl std::fmt::Display for FileName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::RawText => f.write_str("<raw source>"),
-            Self::LocalPath(path) => f.write_str(
-                // TODO: make this relative to project root instead of canonical
-                PathBuf::from(path).canonicalize().unwrap().to_str().unwrap()
-            )
+            Self::Virtual(name) => write!(f, "<{}>", name),
+            Self::LocalPath(path) => write!(f, "{}", path.display())
         }
     }
 }
@@ -55,25 +68,384 @@ impl SourceFile {
     /// Creates new source file from raw text, this is
     /// useful for testing.
     pub fn new_raw(text: String, pkg: PkgID, id: SourceFileID) -> Self {
+        Self::new(FileName::RawText, text, pkg, id)
+    }
+
+    /// Creates source file that doesn't exist on disk, like expanded
+    /// prelude, generated code or lines typed into REPL.
+    pub fn new_virtual(name: impl Into<String>, text: String, pkg: PkgID, id: SourceFileID) -> Self {
+        Self::new(FileName::Virtual(name.into()), text, pkg, id)
+    }
+
+    /// Creates new source file with already loaded text, which
+    /// is normalized first, see `normalize_text`.
+    pub fn new(name: FileName, text: String, pkg: PkgID, id: SourceFileID) -> Self {
+        let removed_bytes = RemovedBytes::of(&text);
+        Self {
+            removed_bytes,
+            ..Self::from_text(name, SourceText::from(normalize_text(text)), pkg, id)
+        }
+    }
+
+    /// Creates new source file from text, which is expected to already
+    /// be normalized. This doesn't copy the text, so it is used for
+    /// memory-mapped and shared sources.
+    pub fn from_text(name: FileName, text: SourceText, pkg: PkgID, id: SourceFileID) -> Self {
         let len = text.len();
+        let line_starts = Self::compute_line_starts(&text);
         Self {
-            name: FileName::RawText,
+            name,
+            checksum: text.fingerprint(),
             src: Some(text),
-            clen: len,
+            len,
             pkg,
-            id
+            id,
+            modified: None,
+            removed_bytes: RemovedBytes::default(),
+            line_starts
+        }
+    }
+
+    /// Checksum and modification time, which caches can store to
+    /// later check whether the file needs to be processed again.
+    pub fn stamp(&self) -> SourceStamp {
+        SourceStamp {
+            checksum: self.checksum,
+            modified: self.modified
+        }
+    }
+
+    fn compute_line_starts(text: &str) -> Vec<u32> {
+        let mut starts = vec![0];
+        for (i, byte) in text.bytes().enumerate() {
+            if byte == b'\n' {
+                starts.push(i as u32 + 1);
+            }
         }
+        starts
+    }
+
+    /// Converts byte offset into (line, col), both starting from 1.
+    /// Column is counted in characters, offsets past the end of
+    /// the file are placed on the last line.
+    pub fn lookup_line(&self, offset: u32) -> (u32, u32) {
+        let offset = offset.min(self.len as u32);
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let col = self.get_span(&Span::new(self.id, line_start, offset)).chars().count();
+        (line as u32 + 1, col as u32 + 1)
     }
 
-    /// Get span from the file
-    pub fn get_span(&self, span: &Span) -> String {
+    /// Returns (start, end) of the given line (starting from 1), where
+    /// end is exclusive and doesn't include the '\n'.
+    pub fn line_bounds(&self, line: u32) -> (u32, u32) {
+        let idx = (line.max(1) as usize - 1).min(self.line_starts.len() - 1);
+        let start = self.line_starts[idx];
+        let end = match self.line_starts.get(idx + 1) {
+            Some(next) => next - 1,
+            None => self.len as u32
+        };
+        (start, end)
+    }
+
+    /// Get span from the file. Spans that are out of bounds or
+    /// don't lie on character boundaries give an empty string.
+    pub fn get_span(&self, span: &Span) -> &str {
         if let Some(ref src) = self.src {
-            src.chars()
-                .skip(span.start as usize)
-                .take((span.end - span.start) as usize)
-                .collect()
+            src.get(span.start as usize..span.end as usize).unwrap_or_default()
         } else {
             unimplemented!("Getting span of sources without loaded source is unimplemented")
         }
     }
-}
\ No newline at end of file
+}
+
+/// Text of source file. This is cheap to clone, and can be either
+/// owned or memory-mapped from disk, so that big files don't have
+/// to be copied. Either way it is accessed as `&str`.
+#[derive(Clone)]
+pub struct SourceText(TextRepr);
+
+#[derive(Clone)]
+enum TextRepr {
+    Shared(Arc<str>),
+    #[cfg(feature = "mmap")]
+    Mapped {
+        map: Arc<memmap2::Mmap>,
+        /// Start of the text, after byte order mark.
+        start: usize
+    }
+}
+
+impl SourceText {
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            TextRepr::Shared(ref text) => text,
+            #[cfg(feature = "mmap")]
+            TextRepr::Mapped { ref map, start } => {
+                // SAFETY: Mapped text was checked to be valid UTF-8 when it was created
+                unsafe { std::str::from_utf8_unchecked(&map[start..]) }
+            }
+        }
+    }
+
+    /// Memory-map the file, returning its text together with what
+    /// normalizing it removed. Its content is checked to be UTF-8 without
+    /// NUL bytes, and if it has "\r\n" line endings, it is read and
+    /// normalized instead, as mapped text cannot be changed.
+    ///
+    /// The file must not be modified while it is mapped, tools which
+    /// generate code should write new files instead of changing them.
+    #[cfg(feature = "mmap")]
+    pub fn map_file(path: &Path) -> std::io::Result<(Self, RemovedBytes)> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: Mapped files are expected to not change during the compilation,
+        // which is documented above. Content is validated before it is used as str.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let text = std::str::from_utf8(&map)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        if text.contains('\0') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} contains NUL byte, it is probably not a source file", path.display())
+            ))
+        }
+        let removed = RemovedBytes::of(text);
+        if !removed.crlf.is_empty() {
+            return Ok((Self::from(normalize_text(text.to_string())), removed))
+        }
+        let start = removed.bom as usize;
+        Ok((Self(TextRepr::Mapped { map: Arc::new(map), start }), removed))
+    }
+}
+
+impl Deref for SourceText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for SourceText {
+    fn from(text: String) -> Self {
+        Self(TextRepr::Shared(text.into()))
+    }
+}
+
+impl From<Arc<str>> for SourceText {
+    fn from(text: Arc<str>) -> Self {
+        Self(TextRepr::Shared(text))
+    }
+}
+
+impl Debug for SourceText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Strip byte order mark and convert "\r\n" line endings into "\n", so that
+/// files written on Windows have the same spans, lines and columns as
+/// everywhere else. Lone '\r' characters are kept as they are.
+/// `RemovedBytes` maps offsets in the normalized text back to the original.
+pub fn normalize_text(mut text: String) -> String {
+    if text.starts_with('\u{feff}') {
+        text.drain(..'\u{feff}'.len_utf8());
+    }
+    if text.contains("\r\n") {
+        text = text.replace("\r\n", "\n");
+    }
+    text
+}
+
+/// Bytes which `normalize_text` removes from a text, so that offsets in
+/// the normalized text can be mapped back to the file on disk, eg. for
+/// tools applying suggestions to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemovedBytes {
+    /// Length of the byte order mark, 0 if there is none.
+    bom: u32,
+    /// Offsets of line breaks in the normalized text which were "\r\n".
+    crlf: Vec<u32>
+}
+
+impl RemovedBytes {
+    pub fn of(text: &str) -> Self {
+        let bom = if text.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+        // Every "\r\n" before moves the following ones one byte back
+        let crlf = text[bom..].match_indices("\r\n")
+            .enumerate()
+            .map(|(idx, (offset, _))| (offset - idx) as u32)
+            .collect();
+        Self { bom: bom as u32, crlf }
+    }
+
+    /// Offset in the original text of the offset in the normalized one.
+    /// Offsets of "\r\n" line breaks point before the '\r'.
+    pub fn original_offset(&self, offset: u32) -> u32 {
+        offset + self.bom + self.crlf.partition_point(|&crlf| crlf < offset) as u32
+    }
+}
+
+/// Size from which `SourceMap::load_file` memory-maps files, smaller
+/// ones are cheaper to read.
+#[cfg(feature = "mmap")]
+pub const MAP_THRESHOLD: u64 = 1 << 20;
+
+/// All source files of the current compilation, together with the
+/// project root. Paths of loaded files are stored relative to the root,
+/// so that diagnostics don't print long absolute paths.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    root: Option<PathBuf>,
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    /// Creates source map without project root, paths
+    /// are displayed as they were given.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            root: Some(root.canonicalize().unwrap_or(root)),
+            files: Vec::new()
+        }
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Name of the file at given path, relative to the project root
+    /// if possible. Without a root paths are kept as they are, paths
+    /// outside of it are made absolute, so `full_path` finds them
+    /// wherever the compiler runs from.
+    pub fn file_name(&self, path: &Path) -> FileName {
+        let Some(ref root) = self.root else {
+            return FileName::LocalPath(path.to_path_buf());
+        };
+        // Files which don't exist yet can't be canonicalized
+        let path = path.canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .unwrap_or_else(|_| path.to_path_buf());
+        let name = path.strip_prefix(root).map(Path::to_path_buf).unwrap_or(path);
+        FileName::LocalPath(name)
+    }
+
+    /// Path which can be used to open the file, this is the
+    /// root joined with the relative path, absolute paths are
+    /// returned as they are. None for sources which don't come
+    /// from disk.
+    pub fn full_path(&self, name: &FileName) -> Option<PathBuf> {
+        match name {
+            FileName::LocalPath(path) => Some(match self.root {
+                Some(ref root) => root.join(path),
+                None => path.clone()
+            }),
+            FileName::Virtual(_) | FileName::RawText => None
+        }
+    }
+
+    /// Memory-maps file and adds it to the map, which avoids copying
+    /// big generated files. Falls back to reading the file if it needs
+    /// to be normalized. See `SourceText::map_file` for details.
+    #[cfg(feature = "mmap")]
+    pub fn load_file_mapped(&mut self, path: impl AsRef<Path>, pkg: PkgID) -> std::io::Result<SourceFileID> {
+        let path = path.as_ref();
+        let (text, removed_bytes) = SourceText::map_file(path)?;
+        let name = self.file_name(path);
+        let mut file = SourceFile::from_text(name, text, pkg, SourceFileID::new_unique());
+        file.modified = std::fs::metadata(path)?.modified().ok();
+        file.removed_bytes = removed_bytes;
+        Ok(self.add_file(file))
+    }
+
+    /// Reads file from disk and adds it to the map. With the `mmap`
+    /// feature, files of at least `MAP_THRESHOLD` bytes are memory-mapped
+    /// with `load_file_mapped` instead.
+    pub fn load_file(&mut self, path: impl AsRef<Path>, pkg: PkgID) -> std::io::Result<SourceFileID> {
+        let path = path.as_ref();
+        #[cfg(feature = "mmap")]
+        if std::fs::metadata(path)?.len() >= MAP_THRESHOLD {
+            return self.load_file_mapped(path, pkg)
+        }
+        let text = std::fs::read_to_string(path)?;
+        if let Some(offset) = text.find('\0') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} contains NUL byte at offset {}, it is probably not a source file", path.display(), offset)
+            ))
+        }
+        let name = self.file_name(path);
+        let mut file = SourceFile::new(name, text, pkg, SourceFileID::new_unique());
+        file.modified = std::fs::metadata(path)?.modified().ok();
+        Ok(self.add_file(file))
+    }
+
+    /// Adds source with the given text and name that doesn't come from disk.
+    pub fn add_virtual(&mut self, name: impl Into<String>, text: String, pkg: PkgID) -> SourceFileID {
+        self.add_file(SourceFile::new_virtual(name, text, pkg, SourceFileID::new_unique()))
+    }
+
+    pub fn add_file(&mut self, file: SourceFile) -> SourceFileID {
+        let id = file.id;
+        self.files.push(file);
+        id
+    }
+
+    pub fn get(&self, id: SourceFileID) -> Option<&SourceFile> {
+        self.files.iter().find(|file| file.id == id)
+    }
+
+    /// File that the span points into.
+    pub fn source_of(&self, span: &Span) -> Option<&SourceFile> {
+        self.get(span.source)
+    }
+
+    /// Text of the span, no matter if it points into
+    /// a file on disk or a virtual one.
+    pub fn span_text(&self, span: &Span) -> Option<&str> {
+        self.source_of(span).map(|source| source.get_span(span))
+    }
+
+    /// (file, line, col) where the span starts, line and col start from 1.
+    pub fn lookup_position(&self, span: &Span) -> Option<(&FileName, u32, u32)> {
+        let source = self.source_of(span)?;
+        let (line, col) = source.lookup_line(span.start);
+        Some((&source.name, line, col))
+    }
+
+    pub fn stamp(&self, id: SourceFileID) -> Option<SourceStamp> {
+        self.get(id).map(SourceFile::stamp)
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+}
+
+/// State of the source file at the time it was loaded. This is stored
+/// next to cached results of lexing or parsing, so that they can be reused
+/// when the file didn't change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceStamp {
+    pub checksum: Fingerprint,
+    pub modified: Option<SystemTime>
+}
+
+impl SourceStamp {
+    /// Whether the file at the path still has the same content. If it was
+    /// not modified since, this only reads metadata, otherwise the content
+    /// is hashed, as touched files often don't actually change.
+    pub fn is_fresh(&self, path: &Path) -> std::io::Result<bool> {
+        let modified = std::fs::metadata(path)?.modified().ok();
+        if modified.is_some() && modified == self.modified {
+            return Ok(true)
+        }
+        Ok(normalize_text(std::fs::read_to_string(path)?).fingerprint() == self.checksum)
+    }
+}
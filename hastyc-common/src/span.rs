@@ -1,14 +1,32 @@
 use std::fmt::Debug;
 
-use crate::{identifiers::SourceFileID, source::SourceFile};
+use crate::{identifiers::{Ident, SourceFileID}, path::Path, source::SourceFile};
 
 /// Span represents region in the source code from which
 /// given data come.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: u32,
     pub end: u32,
-    pub source: SourceFileID
+    pub source: SourceFileID,
+    /// Expansion which created the code, root for code written by the user.
+    /// See `expansion::ExpansionTable` for the data of contexts.
+    pub ctxt: SyntaxContext
+}
+
+/// Id of expansion (desugaring or macro) that some span comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyntaxContext(pub u32);
+
+impl SyntaxContext {
+    /// Context of code that was written directly in the source.
+    pub const ROOT: SyntaxContext = SyntaxContext(0);
+
+    pub fn is_root(self) -> bool {
+        self == Self::ROOT
+    }
 }
 
 impl Span {
@@ -20,7 +38,8 @@ impl Span {
         Self {
             start,
             end,
-            source
+            source,
+            ctxt: SyntaxContext::ROOT
         }
     }
 
@@ -28,83 +47,168 @@ impl Span {
         Self {
             start: 0,
             end: 0,
-            source: SourceFileID(0)
+            source: SourceFileID(0),
+            ctxt: SyntaxContext::ROOT
         }
     }
 
+    /// Whether this is `Span::dummy`, which doesn't point at any code,
+    /// even though its source may be a loaded file.
+    pub fn is_dummy(&self) -> bool {
+        self.start == 0 && self.end == 0 && self.source == SourceFileID(0)
+    }
+
     pub fn from_begin_end(begin: Span, end: Span) -> Self {
         Self {
             start: begin.start,
             end: end.end,
-            source: begin.source
+            source: begin.source,
+            ctxt: begin.ctxt
         }
     }
 
+    /// Same span, but marked as created by the given expansion.
+    pub fn with_ctxt(self, ctxt: SyntaxContext) -> Self {
+        Self { ctxt, ..self }
+    }
+
+    /// Whether this span comes from desugaring or macro expansion.
+    pub fn from_expansion(&self) -> bool {
+        !self.ctxt.is_root()
+    }
+
+    /// Length of the span in bytes.
     pub fn len(&self) -> u32 {
         self.end - self.start
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Whether the byte offset is inside of the span.
+    pub fn contains(&self, offset: u32) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    /// Whether the other span is entirely inside of this one.
+    pub fn contains_span(&self, other: &Span) -> bool {
+        self.source == other.source && self.start <= other.start && other.end <= self.end
+    }
+
     /// Get text from source file, this checks whether
     /// source is same as expected source, returning None
-    /// if it isn't.
-    pub fn get_text(&self, source: &SourceFile) -> Option<String> {
+    /// if it isn't or if span doesn't lie on character boundaries.
+    pub fn get_text<'a>(&self, source: &'a SourceFile) -> Option<&'a str> {
         if self.source != source.id { return None }
-        if let Some(ref src) = source.src {
-            Some(
-                src.chars()
-                    .skip(self.start as usize)
-                    .take(self.len() as usize)
-                    .collect()
-                )
-        } else { None }
+        source.src.as_ref()?.get(self.start as usize..self.end as usize)
     }
 
     /// Converts span to relative start, eg. (line, col)
     pub fn to_relative(&self, source: &SourceFile) -> (u32, u32) {
-        let mut line = 0;
-        let mut col = 0;
-
-        for (i, char) in source.src.as_ref().unwrap().chars().enumerate() {
-            if char == '\n' {
-                line += 1;
-                col = 0;
-            } else {
-                col += 1;
-            }
+        source.lookup_line(self.start)
+    }
 
-            if i == self.start as usize {
-                return (line + 1, col)
-            }
+    /// This returns (line_text, span_offset_in_line), where
+    /// offset is counted in characters.
+    pub fn get_line<'a>(&self, source: &'a SourceFile) -> (&'a str, u32) {
+        let (line, col) = self.to_relative(source);
+        let (line_start, line_end) = source.line_bounds(line);
+
+        let line = source.get_span(
+            &Span::new(source.id, line_start, line_end)
+        );
+
+        (line, col - 1)
+    }
+}
+
+/// Value together with the span where it comes from, used for
+/// small nodes that don't need their own struct, like operators.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spanned<Kind> {
+    pub kind: Kind,
+    pub span: Span
+}
+
+impl<Kind> Spanned<Kind> {
+    pub fn new(kind: Kind, span: Span) -> Self {
+        Self {
+            kind,
+            span
         }
-        return (0, 0)
     }
+}
+
+pub trait MakeSpanned where Self: Sized {
+    fn spanned(self, span: Span) -> Spanned<Self> {
+        Spanned { kind: self, span }
+    }
+}
+
+impl<Kind> MakeSpanned for Kind {}
+
+/// Anything that comes from some place in the source, like AST nodes.
+pub trait HasSpan {
+    fn span(&self) -> Span;
+
+    fn span_text<'a>(&self, source: &'a SourceFile) -> Option<&'a str> {
+        self.span().get_text(source)
+    }
+}
 
-    fn get_line_start_end(source: &SourceFile, line: u32) -> (u32, u32) {
-        let mut start = 0;
-        let mut cline = 0;
-        
-        for (i, char) in source.src.as_ref().unwrap().chars().enumerate() {
-            if char == '\n' {
-                if cline + 1 == line {
-                    return (start, i as u32)
+/// Implement `HasSpan` for structs with a `span` field.
+#[macro_export]
+macro_rules! impl_has_span {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $crate::span::HasSpan for $ty {
+                fn span(&self) -> $crate::span::Span {
+                    self.span
                 }
-                start = i as u32;
-                cline += 1;
             }
-        }
+        )*
+    };
+}
 
-        return (0, 0)
+impl_has_span!(Ident, Path);
+
+impl HasSpan for Span {
+    fn span(&self) -> Span {
+        *self
     }
+}
 
-    /// This returns (line_text, line_start_span)
-    pub fn get_line(&self, source: &SourceFile) -> (String, u32) {
-        let relative = self.to_relative(source);
-        let (line_start, line_end) = Self::get_line_start_end(source, relative.0);
+impl<Kind> HasSpan for Spanned<Kind> {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
 
-        let line = source.get_span(
-            &Span::new(source.id, line_start + 1, line_end)
-        );
+impl<T: HasSpan + ?Sized> HasSpan for &T {
+    fn span(&self) -> Span {
+        (**self).span()
+    }
+}
 
-        (line, self.start - line_start - 1)
+impl<T: HasSpan + ?Sized> HasSpan for Box<T> {
+    fn span(&self) -> Span {
+        (**self).span()
     }
-}
\ No newline at end of file
+}
+
+/// Find the node with the smallest span that contains the offset. For
+/// nested nodes this is the innermost one, ties go to the first node.
+pub fn innermost<T: HasSpan>(
+    nodes: impl IntoIterator<Item = T>,
+    source: SourceFileID,
+    offset: u32
+) -> Option<T> {
+    nodes.into_iter()
+        .filter(|node| {
+            let span = node.span();
+            span.source == source && span.contains(offset)
+        })
+        .min_by_key(|node| node.span().len())
+}
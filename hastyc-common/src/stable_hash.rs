@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use crate::{identifiers::{ASTNodeID, Ident, Symbol}, path::{Path, PathSegment}, span::{Span, Spanned}};
+
+/// 128-bit fingerprint of a value, which is the same between compiler runs
+/// and platforms as long as the value itself doesn't change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub u128);
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// Hasher with output that doesn't depend on the platform, compiler version
+/// or order of interning. This is 128-bit FNV-1a, which is not cryptographic,
+/// but is more than enough for detecting changes in the code.
+#[derive(Debug, Clone)]
+pub struct StableHasher {
+    state: u128
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StableHasher {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    pub fn new() -> Self {
+        Self { state: Self::OFFSET_BASIS }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u128;
+            self.state = self.state.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Lengths are always written as u64, so that they don't depend on the platform.
+    pub fn write_len(&mut self, len: usize) {
+        self.write_u64(len as u64);
+    }
+
+    pub fn write_str(&mut self, text: &str) {
+        self.write_len(text.len());
+        self.write(text.as_bytes());
+    }
+
+    pub fn finish(&self) -> Fingerprint {
+        Fingerprint(self.state)
+    }
+}
+
+/// Hashing that is stable between compiler runs. Unlike `std::hash::Hash`
+/// implementations must not depend on anything that may change between runs,
+/// so symbols are hashed by their text, while spans and node ids are skipped,
+/// as moving code around shouldn't change its fingerprint.
+pub trait StableHash {
+    fn stable_hash(&self, hasher: &mut StableHasher);
+
+    fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = StableHasher::new();
+        self.stable_hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Implement `StableHash` for struct by hashing the listed fields in order,
+/// fields that are not listed are skipped.
+#[macro_export]
+macro_rules! impl_stable_hash {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::stable_hash::StableHash for $ty {
+            fn stable_hash(&self, hasher: &mut $crate::stable_hash::StableHasher) {
+                $($crate::stable_hash::StableHash::stable_hash(&self.$field, hasher);)*
+                let _ = hasher;
+            }
+        }
+    };
+}
+
+/// Implement `StableHash` for enum without any data, by hashing its discriminant.
+#[macro_export]
+macro_rules! impl_stable_hash_unit_enum {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $crate::stable_hash::StableHash for $ty {
+                fn stable_hash(&self, hasher: &mut $crate::stable_hash::StableHasher) {
+                    hasher.write_u32(self.clone() as u32);
+                }
+            }
+        )*
+    };
+}
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u8(*self as u8);
+    }
+}
+
+impl StableHash for u32 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u32(*self);
+    }
+}
+
+impl StableHash for str {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self);
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Box<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Arc<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            None => hasher.write_u8(0),
+            Some(value) => {
+                hasher.write_u8(1);
+                value.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_len(self.len());
+        for value in self.iter() {
+            value.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+impl<A: StableHash, B: StableHash> StableHash for (A, B) {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.0.stable_hash(hasher);
+        self.1.stable_hash(hasher);
+    }
+}
+
+impl StableHash for Symbol {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self.as_str());
+    }
+}
+
+/// Spans only tell where the code is, not what it is.
+impl StableHash for Span {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+/// Node ids depend on how much code was parsed before the node.
+impl StableHash for ASTNodeID {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl<Kind: StableHash> StableHash for Spanned<Kind> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.kind.stable_hash(hasher);
+    }
+}
+
+impl_stable_hash!(Ident { symbol });
+impl_stable_hash!(PathSegment { ident });
+impl_stable_hash!(Path { segments });
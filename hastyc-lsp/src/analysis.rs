@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use hastyc_common::{error::{CommonErrorContext, Severity}, identifiers::{ASTNodeID, PkgID, SourceFileID}, session::{Options, Session}, source::{FileName, SourceFile}, span::Span};
+use hastyc_parser::{loader, parser::{Package, ParserOptions}};
+use hastyc_passes::{hir::{lower::LowerToHir, typeck::TypeCheckPass}, passes::{defs::{DefId, Definitions}, desugar::DesugarLoops, manager::PassManager, name_resolve::NameResolvePass, prelude::InjectPrelude, transform::ASTMutPipeline, QueryContext}};
+use serde_json::{json, Value};
+
+use crate::{completion, hover, position, uri};
+
+/// What the server knows about a document, computed from scratch every time
+/// it changes. Document is the root of its package, and modules it declares
+/// like `module foo;` are loaded from disk, so changes to other documents
+/// which were not saved yet are not seen.
+pub struct Analysis {
+    uri: String,
+    sess: Session,
+    root: SourceFileID,
+    /// None if parser couldn't recover from errors.
+    package: Option<Package>,
+    defs: Option<Definitions>,
+    resolved_names: HashMap<ASTNodeID, DefId>
+}
+
+impl Analysis {
+    pub fn new(uri: &str, text: String) -> Self {
+        let (sess, root, mut package) = load(uri, text);
+        let mut defs = None;
+        let mut resolved_names = HashMap::new();
+        if let Some(ref mut package) = package {
+            let cx = resolve(&sess, package, false);
+            resolved_names = cx.resolved_names.to_vec().into_iter().collect();
+            defs = Some(cx.defs);
+        }
+        Self { uri: uri.to_string(), sess, root, package, defs, resolved_names }
+    }
+
+    pub fn source(&self) -> &SourceFile {
+        self.sess.source_map.get(self.root).expect("document is not in its source map")
+    }
+
+    /// Diagnostics of the document in the format of the protocol. Those that
+    /// are about files of its modules are left out, as they belong to other
+    /// documents.
+    pub fn diagnostics(&self) -> Vec<Value> {
+        let root = self.source();
+        self.sess.diagnostics().diagnostics().iter()
+            .filter_map(|diag| {
+                let ctx = CommonErrorContext::for_diagnostic(diag.as_ref(), &self.sess.source_map, root);
+                if ctx.source.id != self.root {
+                    return None
+                }
+                let data = diag.data(&ctx);
+                let range = match data.primary_span {
+                    Some(span) => position::range(root, span),
+                    None => json!({ "start": position::position(root, 0), "end": position::position(root, 0) })
+                };
+                // Spans shown without a note are described by the diagnostic itself
+                let related: Vec<Value> = data.related_spans.iter()
+                    .filter_map(|(span, note)| Some(json!({
+                        "location": self.location(*span)?,
+                        "message": note.as_deref().map_or(data.message.as_str(), |note| note.trim_end_matches(':'))
+                    })))
+                    .collect();
+                let mut message = data.message;
+                for (kind, child) in data.children.iter() {
+                    message.push_str(&format!("\n{}: {}", kind, child));
+                }
+                let severity = match data.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                    Severity::Note => 3,
+                    Severity::Help => 4
+                };
+                Some(json!({
+                    "range": range,
+                    "severity": severity,
+                    "code": data.code,
+                    "source": "hastyc",
+                    "message": message,
+                    "relatedInformation": related
+                }))
+            })
+            .collect()
+    }
+
+    /// Location of the definition that the name at the offset refers to,
+    /// or of the definition itself if the offset is in its declaration.
+    pub fn definition(&self, offset: u32) -> Option<Value> {
+        let package = self.package.as_ref()?;
+        let defs = self.defs.as_ref()?;
+        let node = package.span_table.as_ref()?.node_at(self.root, offset)?;
+        let def = match self.resolved_names.get(&node) {
+            Some(def) => *def,
+            None => defs.def_id(node)?
+        };
+        self.location(defs.get(def).ident.span)
+    }
+
+    /// Location of the span in the protocol, in this document or a file
+    /// of one of its modules. Intrinsics of the prelude have no location.
+    fn location(&self, span: Span) -> Option<Value> {
+        if span.is_dummy() {
+            return None
+        }
+        let source = self.sess.source_map.source_of(&span)?;
+        let uri = if source.id == self.root {
+            self.uri.clone()
+        } else {
+            // Code which isn't on disk can't be shown
+            uri::from_path(&self.sess.source_map.full_path(&source.name)?)
+        };
+        Some(json!({ "uri": uri, "range": position::range(source, span) }))
+    }
+
+    /// Completions at the offset, see `completion::complete`.
+    pub fn completion(&self, offset: u32) -> Vec<Value> {
+        let text = self.source().src.as_ref().map_or("", |src| src.as_str());
+        completion::complete(&self.uri, text, offset)
+    }
+
+    /// Hover at the offset, see `hover::hover`.
+    pub fn hover(&self, offset: u32) -> Option<Value> {
+        let text = self.source().src.as_ref().map_or("", |src| src.as_str());
+        hover::hover(&self.uri, text, offset)
+    }
+}
+
+/// Session with the text of the document as the root of its package,
+/// together with the package loaded from it. Package is None if parser
+/// couldn't recover from errors.
+pub fn load(uri: &str, text: String) -> (Session, SourceFileID, Option<Package>) {
+    let options = Options { emit: Vec::new(), ..Options::default() };
+    let mut sess = Session::new(options);
+    let name = match uri::to_path(uri) {
+        Some(path) => FileName::LocalPath(path),
+        None => FileName::Virtual(uri.to_string())
+    };
+    let root = sess.source_map.add_file(SourceFile::new(name, text, PkgID::new_unique(), SourceFileID::new_unique()));
+
+    let parser_options = ParserOptions { build_span_table: true, recover: true, ..ParserOptions::default() };
+    let package = loader::load_package(&mut sess, root, &parser_options);
+    (sess, root, package)
+}
+
+/// Context of the loaded package with names resolved, and with types
+/// checked as well if `check_types` is set.
+pub fn resolve<'cx>(sess: &'cx Session, package: &'cx mut Package, check_types: bool) -> QueryContext<'cx> {
+    // Pipeline stops early when there are errors, which are expected
+    // here, so names are resolved no matter how far it got
+    ASTMutPipeline::new()
+        .with_pass(InjectPrelude)
+        .with_pass(DesugarLoops)
+        .run(package, sess);
+    let mut cx = QueryContext::for_package(sess, package);
+    let mut passes = PassManager::new().with_pass(NameResolvePass::new());
+    if check_types {
+        passes.add_pass(LowerToHir);
+        passes.add_pass(TypeCheckPass);
+    }
+    passes.run(&mut cx);
+    cx
+}
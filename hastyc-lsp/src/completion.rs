@@ -0,0 +1,134 @@
+use hastyc_common::{identifiers::SourceFileID, path::Path};
+use hastyc_parser::parser::{ExprKind, TyKind, Visibility};
+use hastyc_passes::{hir::ty, passes::{defs::{DefId, DefKind}, scope::ScopeAtQuery, QueryContext, ResolveIdQuery, ResolvedId}};
+use serde_json::{json, Value};
+
+use crate::{analysis, signature};
+
+/// Identifier put at the cursor before the document is analysed, so that
+/// unfinished code like `point.` or `math::` parses into a field access
+/// or a path, which tells what to complete.
+const PLACEHOLDER: &str = "__hastyc_complete";
+
+/// Completion items for the offset in the text of the document. After `.`
+/// these are fields of the receiver, after `::` members of the module or
+/// enum before it, and otherwise the names in scope. Only names starting
+/// with the part of the identifier before the cursor are included.
+pub fn complete(uri: &str, text: &str, offset: u32) -> Vec<Value> {
+    let mut offset = (offset as usize).min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let typed = text[..offset].chars()
+        .rev()
+        .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+        .map(char::len_utf8)
+        .sum::<usize>();
+    let start = offset - typed;
+    let typed = &text[start..offset];
+
+    let mut edited = text.to_string();
+    edited.insert_str(offset, PLACEHOLDER);
+    let (sess, root, package) = analysis::load(uri, edited);
+    let Some(mut package) = package else { return Vec::new() };
+    let cx = analysis::resolve(&sess, &mut package, true);
+
+    candidates(&cx, root, start as u32).into_iter()
+        .filter(|(name, _)| name.starts_with(typed))
+        .enumerate()
+        .map(|(idx, (name, def))| json!({
+            "label": name,
+            "kind": item_kind(cx.defs.get(def).kind),
+            "detail": signature::describe(&cx, def),
+            // Names are already in the order of their relevance
+            "sortText": format!("{:04}", idx)
+        }))
+        .collect()
+}
+
+/// Names that could be written at the offset, which is the start of
+/// the identifier with the placeholder.
+fn candidates(cx: &QueryContext, root: SourceFileID, offset: u32) -> Vec<(String, DefId)> {
+    let node = cx.package.span_table.as_ref().and_then(|table| table.node_at(root, offset));
+    let path = match node.map(|node| cx.query(ResolveIdQuery(node))) {
+        Some(ResolvedId::Expr(expr)) => match expr.kind {
+            ExprKind::Field(ref receiver, ref field, _) if field.span.contains(offset) => {
+                return fields(cx, cx.types.get(receiver.id))
+            },
+            ExprKind::Path(ref path) => Some(path),
+            ExprKind::StructLit(ref lit) => Some(&lit.path),
+            _ => None
+        },
+        Some(ResolvedId::Ty(ty)) => match ty.kind {
+            TyKind::Path(ref path) => Some(path),
+            _ => None
+        },
+        _ => None
+    };
+    let segment = path.and_then(|path| path.segments.iter().position(|segment| segment.ident.span.contains(offset)));
+    match (path, segment) {
+        (Some(path), Some(idx)) if idx > 0 => {
+            let Some(target) = resolve_prefix(cx, root, offset, path, idx) else { return Vec::new() };
+            members(cx, target)
+        },
+        _ => cx.query(ScopeAtQuery { source: root, offset }).iter()
+            .map(|name| (name.name.as_str().to_string(), name.def))
+            .collect()
+    }
+}
+
+/// Definition that the segments of the path before `idx` refer to. First
+/// segment is looked up in scope and the others in what comes before them.
+fn resolve_prefix(cx: &QueryContext, root: SourceFileID, offset: u32, path: &Path, idx: usize) -> Option<DefId> {
+    let first = path.segments[0].ident.symbol;
+    let scope = cx.query(ScopeAtQuery { source: root, offset });
+    let mut target = scope.iter().find(|name| name.name == first)?.def;
+    for segment in path.segments[1..idx].iter() {
+        target = cx.defs.iter()
+            .find(|(_, def)| def.parent == Some(target) && def.kind != DefKind::Local && def.ident.symbol == segment.ident.symbol)?
+            .0;
+    }
+    Some(target)
+}
+
+/// Names that can follow `::` after the definition, public items of
+/// modules and variants of enums.
+fn members(cx: &QueryContext, target: DefId) -> Vec<(String, DefId)> {
+    let symbols = &cx.package.symbol_storage;
+    let kind = cx.defs.get(target).kind;
+    cx.defs.iter()
+        .filter(|(_, def)| def.parent == Some(target))
+        .filter(|(_, def)| match kind {
+            DefKind::Module => def.kind != DefKind::Local && def.visibility == Visibility::Public,
+            DefKind::Enum => def.kind == DefKind::Variant,
+            _ => false
+        })
+        .map(|(id, def)| (def.ident.text(symbols).to_string(), id))
+        .collect()
+}
+
+/// Fields of the struct of the type, if it is a struct.
+fn fields(cx: &QueryContext, ty: Option<ty::Ty>) -> Vec<(String, DefId)> {
+    let symbols = &cx.package.symbol_storage;
+    let Some(ty::Ty::Adt(adt)) = ty else { return Vec::new() };
+    if cx.defs.get(adt).kind != DefKind::Struct {
+        return Vec::new()
+    }
+    cx.defs.iter()
+        .filter(|(_, def)| def.parent == Some(adt) && def.kind == DefKind::Field)
+        .map(|(id, def)| (def.ident.text(symbols).to_string(), id))
+        .collect()
+}
+
+/// `CompletionItemKind` of the protocol for the kind of definition.
+fn item_kind(kind: DefKind) -> u32 {
+    match kind {
+        DefKind::Module => 9,
+        DefKind::Fn => 3,
+        DefKind::Struct => 22,
+        DefKind::Enum => 13,
+        DefKind::Variant => 20,
+        DefKind::Field => 5,
+        DefKind::Local => 6
+    }
+}
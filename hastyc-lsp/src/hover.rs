@@ -0,0 +1,84 @@
+use hastyc_common::{identifiers::{ASTNodeID, SourceFileID}, span::Span};
+use hastyc_passes::{hir::ty::{Ty, TypeDisplay}, passes::{builtin::PrimTy, defs::DefId, NodeIndexQuery, QueryContext, ResolveIdQuery, ResolvedId}};
+use serde_json::{json, Value};
+
+use crate::{analysis, position, signature};
+
+/// Hover for the node at the offset in the text of the document. Names
+/// show the signature of the definition they refer to, or which they
+/// declare, and other expressions show their inferred type. None if
+/// there is nothing to show. Comments are dropped by the lexer, so doc
+/// comments can't be shown yet.
+pub fn hover(uri: &str, text: &str, offset: u32) -> Option<Value> {
+    let (sess, root, package) = analysis::load(uri, text.to_string());
+    let mut package = package?;
+    let cx = analysis::resolve(&sess, &mut package, true);
+    let node = cx.package.span_table.as_ref()?.node_at(root, offset)?;
+    let contents = match target(&cx, node, root, offset) {
+        Some(Target::Def(def)) => signature::describe(&cx, def),
+        Some(Target::Prim(prim)) => prim.to_string(),
+        None => {
+            let ty = cx.types.get(node).filter(|ty| *ty != Ty::Err)?;
+            TypeDisplay::new(ty, cx.hir.as_ref()?, &cx.package.symbol_storage).to_string()
+        }
+    };
+    let source = sess.source_map.get(root)?;
+    let mut hover = json!({
+        "contents": { "kind": "markdown", "value": format!("```hasty\n{}\n```", contents) }
+    });
+    if let Some(span) = node_span(&cx, node) {
+        hover["range"] = position::range(source, span);
+    }
+    Some(hover)
+}
+
+/// What the name at the offset refers to.
+enum Target {
+    Def(DefId),
+    Prim(PrimTy)
+}
+
+fn target(cx: &QueryContext, node: ASTNodeID, root: SourceFileID, offset: u32) -> Option<Target> {
+    // Types are resolved under the node that declares them, like
+    // the parameter in `a: i32`, not under the type itself
+    let owner = match cx.query(ResolveIdQuery(node)) {
+        ResolvedId::Ty(ty) => Some(ty_owner(cx, ty.id)),
+        // What these have recorded is what their type refers to
+        ResolvedId::FnInput(_) | ResolvedId::LetBinding(_) | ResolvedId::FieldDef(_) => None,
+        _ => Some(node)
+    };
+    if let Some(owner) = owner {
+        if let Some(def) = cx.resolved_names.get(&owner) {
+            return Some(Target::Def(def))
+        }
+        if let Some(prim) = cx.prim_tys.get(&owner) {
+            return Some(Target::Prim(prim))
+        }
+    }
+    // Names in declarations are not nodes of their own
+    cx.defs.iter()
+        .find(|(_, def)| def.ident.span.source == root && def.ident.span.contains(offset))
+        .map(|(def, _)| Target::Def(def))
+}
+
+/// Node that the type is resolved under.
+fn ty_owner(cx: &QueryContext, ty: ASTNodeID) -> ASTNodeID {
+    let nodes = cx.query(NodeIndexQuery);
+    nodes.values()
+        .find_map(|node| match *node {
+            ResolvedId::FnInput(input) if input.ty.id == ty => Some(input.id),
+            ResolvedId::LetBinding(binding) if binding.ty.as_ref().is_some_and(|binding_ty| binding_ty.id == ty) => Some(binding.id),
+            ResolvedId::FieldDef(field) if field.ty.id == ty => Some(field.id),
+            _ => None
+        })
+        .unwrap_or(ty)
+}
+
+/// Span of expressions and types, which are what hovers usually point at.
+fn node_span(cx: &QueryContext, node: ASTNodeID) -> Option<Span> {
+    match cx.query(ResolveIdQuery(node)) {
+        ResolvedId::Expr(expr) => Some(expr.span),
+        ResolvedId::Ty(ty) => Some(ty.span),
+        _ => None
+    }
+}
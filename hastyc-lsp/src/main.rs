@@ -0,0 +1,26 @@
+use std::process::ExitCode;
+
+use crate::server::Server;
+
+mod analysis;
+mod completion;
+mod hover;
+mod position;
+mod server;
+mod signature;
+mod transport;
+mod uri;
+
+fn main() -> ExitCode {
+    // Logs go to stderr, as stdout is used for messages
+    env_logger::init();
+    let mut server = Server::new();
+    match server.run(&mut std::io::stdin().lock(), &mut std::io::stdout().lock()) {
+        Ok(()) if server.was_shut_down() => ExitCode::SUCCESS,
+        Ok(()) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use hastyc_common::{source::SourceFile, span::Span};
+use serde_json::{json, Value};
+
+/// Position of the byte offset in the protocol, where lines start from 0
+/// and characters are counted in UTF-16 code units.
+pub fn position(source: &SourceFile, offset: u32) -> Value {
+    let (line, _) = source.lookup_line(offset);
+    let (start, _) = source.line_bounds(line);
+    let before = source.get_span(&Span::new(source.id, start, offset.min(source.len as u32)));
+    json!({ "line": line - 1, "character": before.encode_utf16().count() })
+}
+
+pub fn range(source: &SourceFile, span: Span) -> Value {
+    json!({ "start": position(source, span.start), "end": position(source, span.end) })
+}
+
+/// Byte offset of the position, positions past the end of a line are
+/// placed at its end. None if the position is malformed.
+pub fn offset(source: &SourceFile, position: &Value) -> Option<u32> {
+    let line = u32::try_from(position.get("line")?.as_u64()?).ok()?;
+    let character = position.get("character")?.as_u64()? as usize;
+    let (start, end) = source.line_bounds(line.saturating_add(1));
+    let text = source.get_span(&Span::new(source.id, start, end));
+    let mut units = 0;
+    for (idx, ch) in text.char_indices() {
+        if units >= character {
+            return Some(start + idx as u32)
+        }
+        units += ch.len_utf16();
+    }
+    Some(end)
+}
@@ -0,0 +1,221 @@
+use std::{any::Any, collections::HashMap, io::{self, BufRead, Write}, panic::AssertUnwindSafe};
+
+use log::{debug, error};
+use serde_json::{json, Value};
+
+use crate::{analysis::Analysis, position, transport};
+
+// Error codes of JSON-RPC and the protocol
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// Error response to a request.
+struct ResponseError {
+    code: i64,
+    message: String
+}
+
+impl ResponseError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn invalid_params() -> Self {
+        Self::new(INVALID_PARAMS, "invalid params")
+    }
+}
+
+/// Language server, which talks to the client over JSON-RPC. Documents are
+/// synced in full on every change, which is enough for files of the size
+/// that the compiler handles.
+pub struct Server {
+    documents: HashMap<String, Analysis>,
+    initialized: bool,
+    shutdown: bool,
+    /// Notifications to send after the message is handled.
+    outgoing: Vec<Value>
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            initialized: false,
+            shutdown: false,
+            outgoing: Vec::new()
+        }
+    }
+
+    /// Whether the client asked the server to shut down before exiting,
+    /// which is when the server exits successfully.
+    pub fn was_shut_down(&self) -> bool {
+        self.shutdown
+    }
+
+    /// Handle messages until the client sends `exit`, or input ends.
+    pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        while let Some(body) = transport::read_message(input)? {
+            let message = match serde_json::from_slice::<Value>(&body) {
+                Ok(message) => message,
+                Err(err) => {
+                    transport::write_message(output, &error_response(Value::Null, ResponseError::new(PARSE_ERROR, err.to_string())))?;
+                    continue
+                }
+            };
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+            match (message.get("id").cloned(), message.get("method").and_then(Value::as_str)) {
+                (Some(id), Some(method)) => {
+                    debug!(target: "lsp", "Request {}: {}.", id, method);
+                    let response = match self.request(method, &params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err(err) => error_response(id, err)
+                    };
+                    transport::write_message(output, &response)?;
+                },
+                (None, Some("exit")) => return Ok(()),
+                (None, Some(method)) => {
+                    debug!(target: "lsp", "Notification: {}.", method);
+                    self.notification(method, &params);
+                },
+                // Responses, server doesn't send any requests to the client
+                (_, None) => {}
+            }
+            for notification in std::mem::take(&mut self.outgoing) {
+                transport::write_message(output, &notification)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, ResponseError> {
+        if self.shutdown {
+            return Err(ResponseError::new(INVALID_REQUEST, "server is shutting down"))
+        }
+        if method == "initialize" {
+            self.initialized = true;
+            return Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 1 },
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": [".", ":"] }
+                },
+                "serverInfo": { "name": "hastyc-lsp", "version": env!("CARGO_PKG_VERSION") }
+            }))
+        }
+        if !self.initialized {
+            return Err(ResponseError::new(SERVER_NOT_INITIALIZED, "server is not initialized"))
+        }
+        match method {
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/definition" => {
+                let uri = document_uri(params).ok_or_else(ResponseError::invalid_params)?;
+                let Some(analysis) = self.documents.get(uri) else { return Ok(Value::Null) };
+                let offset = position::offset(analysis.source(), &params["position"]).ok_or_else(ResponseError::invalid_params)?;
+                Ok(analysis.definition(offset).unwrap_or(Value::Null))
+            },
+            // Hover and completion analyse the document again, with
+            // the same chance of hitting a bug of the compiler
+            "textDocument/hover" => {
+                let uri = document_uri(params).ok_or_else(ResponseError::invalid_params)?;
+                let Some(analysis) = self.documents.get(uri) else { return Ok(Value::Null) };
+                let offset = position::offset(analysis.source(), &params["position"]).ok_or_else(ResponseError::invalid_params)?;
+                let hover = std::panic::catch_unwind(AssertUnwindSafe(|| analysis.hover(offset)))
+                    .map_err(|panic| compiler_panicked("hovering in", uri, panic))?;
+                Ok(hover.unwrap_or(Value::Null))
+            },
+            "textDocument/completion" => {
+                let uri = document_uri(params).ok_or_else(ResponseError::invalid_params)?;
+                let Some(analysis) = self.documents.get(uri) else { return Ok(Value::Null) };
+                let offset = position::offset(analysis.source(), &params["position"]).ok_or_else(ResponseError::invalid_params)?;
+                let items = std::panic::catch_unwind(AssertUnwindSafe(|| analysis.completion(offset)))
+                    .map_err(|panic| compiler_panicked("completing in", uri, panic))?;
+                Ok(Value::Array(items))
+            },
+            _ => Err(ResponseError::new(METHOD_NOT_FOUND, format!("unknown method `{}`", method)))
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) {
+        let Some(uri) = document_uri(params) else { return };
+        match method {
+            "textDocument/didOpen" => {
+                if let Some(text) = params["textDocument"]["text"].as_str() {
+                    self.analyze(uri, text.to_string());
+                }
+            },
+            "textDocument/didChange" => {
+                // Sync is full, so the last change has the whole text
+                let text = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let Some(text) = text {
+                    self.analyze(uri, text.to_string());
+                }
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.publish_diagnostics(uri, Vec::new());
+            },
+            _ => {}
+        }
+    }
+
+    fn analyze(&mut self, uri: &str, text: String) {
+        // Bugs of the compiler shouldn't take down the whole server
+        let analysis = std::panic::catch_unwind(AssertUnwindSafe(|| Analysis::new(uri, text)));
+        match analysis {
+            Ok(analysis) => {
+                let diagnostics = analysis.diagnostics();
+                self.documents.insert(uri.to_string(), analysis);
+                self.publish_diagnostics(uri, diagnostics);
+            },
+            Err(panic) => {
+                let err = compiler_panicked("analysing", uri, panic);
+                self.documents.remove(uri);
+                // Diagnostics of the last version would look like those of
+                // this one, so they are replaced with the failure itself
+                let start = json!({ "line": 0, "character": 0 });
+                self.publish_diagnostics(uri, vec![json!({
+                    "range": { "start": start, "end": start },
+                    "severity": 1,
+                    "source": "hastyc",
+                    "message": format!("{}, so this file can't be checked until it changes", err.message)
+                })]);
+            }
+        }
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) {
+        self.outgoing.push(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics }
+        }));
+    }
+}
+
+fn document_uri(params: &Value) -> Option<&str> {
+    params.get("textDocument")?.get("uri")?.as_str()
+}
+
+/// Log the panic of the compiler while doing something with the document,
+/// giving the error to respond with.
+fn compiler_panicked(doing: &str, uri: &str, panic: Box<dyn Any + Send>) -> ResponseError {
+    let message = panic.downcast_ref::<&str>().copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    error!(target: "lsp", "Compiler panicked while {} {}: {}.", doing, uri, message);
+    ResponseError::new(INTERNAL_ERROR, format!("compiler panicked: {}", message))
+}
+
+fn error_response(id: Value, err: ResponseError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": err.code, "message": err.message } })
+}
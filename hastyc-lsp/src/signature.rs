@@ -0,0 +1,79 @@
+use hastyc_common::identifiers::SymbolStorage;
+use hastyc_parser::parser::{DataVariant, FnRetTy, ItemKind, Pat, PatKind, Ty, TyKind};
+use hastyc_passes::{hir::ty::{self, TypeDisplay}, passes::{defs::{DefId, DefKind}, QueryContext, ResolveIdQuery, ResolvedId}};
+
+/// Short description of the definition, like `fn add(a: i32, b: i32) -> i32`
+/// for items or `x: i32` for fields and locals.
+pub fn describe(cx: &QueryContext, def: DefId) -> String {
+    let symbols = &cx.package.symbol_storage;
+    let data = cx.defs.get(def);
+    let name = data.ident.text(symbols);
+    match (data.kind, cx.query(ResolveIdQuery(data.ast_id))) {
+        (DefKind::Fn, ResolvedId::Item(item)) => {
+            let ItemKind::Fn(ref function) = item.kind else { return format!("fn {}", name) };
+            let signature = &function.signature;
+            let inputs = signature.inputs.iter()
+                .map(|input| format!("{}: {}", pat_text(&input.pat, symbols), ty_text(&input.ty, symbols)))
+                .collect::<Vec<_>>();
+            let output = match signature.output {
+                FnRetTy::Default => String::new(),
+                FnRetTy::Ty(ref ty) => format!(" -> {}", ty_text(ty, symbols))
+            };
+            format!("fn {}({}){}", name, inputs.join(", "), output)
+        },
+        (DefKind::Variant, ResolvedId::EnumVariant(variant)) => {
+            let parent = data.parent.map_or("", |parent| cx.defs.get(parent).ident.text(symbols));
+            let fields = match variant.data {
+                DataVariant::Tuple { ref fields } => {
+                    let fields = fields.iter().map(|field| ty_text(&field.ty, symbols)).collect::<Vec<_>>();
+                    format!("({})", fields.join(", "))
+                },
+                DataVariant::Struct { .. } => " { .. }".to_string(),
+                DataVariant::Unit => String::new()
+            };
+            format!("{}::{}{}", parent, name, fields)
+        },
+        (DefKind::Field, ResolvedId::FieldDef(field)) => format!("{}: {}", name, ty_text(&field.ty, symbols)),
+        (DefKind::Local, node) => {
+            // Inferred type is the most precise, annotation is used
+            // when type checking didn't get to the local
+            let inferred = cx.hir.as_ref()
+                .zip(cx.types.get(data.ast_id).filter(|ty| *ty != ty::Ty::Err))
+                .map(|(hir, ty)| TypeDisplay::new(ty, hir, symbols).to_string());
+            let annotated = match node {
+                ResolvedId::FnInput(input) => Some(ty_text(&input.ty, symbols)),
+                ResolvedId::LetBinding(binding) => binding.ty.as_ref().map(|ty| ty_text(ty, symbols)),
+                _ => None
+            };
+            match inferred.or(annotated) {
+                Some(ty) => format!("{}: {}", name, ty),
+                None => name.to_string()
+            }
+        },
+        (DefKind::Module, _) => format!("module {}", name),
+        (DefKind::Struct, _) => format!("struct {}", name),
+        (DefKind::Enum, _) => format!("enum {}", name),
+        _ => name.to_string()
+    }
+}
+
+fn pat_text<'a>(pat: &Pat, symbols: &'a SymbolStorage) -> &'a str {
+    match pat.kind {
+        PatKind::Ident(ref ident) => ident.text(symbols),
+        PatKind::SelfPat => "self"
+    }
+}
+
+/// Type the way it is written in the source.
+pub fn ty_text(ty: &Ty, symbols: &SymbolStorage) -> String {
+    match ty.kind {
+        TyKind::Path(ref path) => path.segments.iter()
+            .map(|segment| segment.ident.text(symbols))
+            .collect::<Vec<_>>()
+            .join("::"),
+        TyKind::SelfTy => "Self".to_string(),
+        TyKind::Void => "()".to_string(),
+        TyKind::Never => "!".to_string(),
+        TyKind::Infer => "_".to_string()
+    }
+}
@@ -0,0 +1,42 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// Read body of the next message, which is framed by headers like in HTTP.
+/// Only `Content-Length` is used, other headers are skipped. None at the
+/// end of input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None)
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(invalid(format!("malformed header `{}`", header)))
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            let value = value.trim();
+            length = Some(value.parse::<usize>().map_err(|_| invalid(format!("invalid content length `{}`", value)))?);
+        }
+    }
+    let length = length.ok_or_else(|| invalid("message without content length".to_string()))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
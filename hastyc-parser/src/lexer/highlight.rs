@@ -0,0 +1,47 @@
+use hastyc_common::{highlight::{self, Highlight, HighlightKind}, identifiers::{PkgID, SourceFileID}, source::SourceFile};
+
+use super::{Lexer, TokenKind};
+
+/// Classify tokens and comments of the text for highlighting, this fits
+/// `RenderConfig::highlighter`. Text the lexer can't handle, like a line
+/// in the middle of a string, gets no highlights.
+pub fn highlight(text: &str) -> Vec<Highlight> {
+    let source = SourceFile::new_virtual("<highlight>", text.to_string(), PkgID(0), SourceFileID(0));
+    let Ok((tokens, comments)) = Lexer::lex_tokens(&source) else { return Vec::new() };
+    let mut highlights: Vec<Highlight> = tokens.iter()
+        .filter_map(|token| Some(Highlight {
+            kind: token_highlight(token.kind)?,
+            range: token.span.start as usize..token.span.end as usize
+        }))
+        .chain(comments.iter().map(|span| Highlight {
+            kind: HighlightKind::Comment,
+            range: span.start as usize..span.end as usize
+        }))
+        .collect();
+    highlights.sort_by_key(|highlight| highlight.range.start);
+    highlights
+}
+
+/// Text with ANSI colors, for printing to a terminal.
+pub fn highlight_ansi(text: &str) -> String {
+    highlight::render_ansi(text, &highlight(text))
+}
+
+/// Text as HTML, see `render_html` for the markup.
+pub fn highlight_html(text: &str) -> String {
+    highlight::render_html(text, &highlight(text))
+}
+
+fn token_highlight(kind: TokenKind) -> Option<HighlightKind> {
+    use TokenKind::*;
+    Some(match kind {
+        True | False | Nil | Literal { .. } => HighlightKind::Literal,
+        Fn | If | Else | While | For | In | Loop | Break | Continue | Return
+        | LSelf | USelf | Let | Guard | Pub | Const | Static | Import | As
+        | Module | Super | Pkg | Match | Struct | Trait | Impl | Enum
+        | Getter | Setter | Override | Where | Async | Await | Extern => HighlightKind::Keyword,
+        Ident => HighlightKind::Identifier,
+        EOF => return None,
+        _ => HighlightKind::Operator
+    })
+}
@@ -1,7 +1,9 @@
+mod highlight;
 mod token;
 use std::sync::Arc;
 
-use hastyc_common::{source::SourceFile, span::Span};
+use hastyc_common::{error::{CommonErrorContext, ErrorDisplay, ErrorFmt}, session::Session, source::SourceFile, span::Span};
+pub use highlight::{highlight, highlight_ansi, highlight_html};
 pub use token::*;
 
 #[derive(Debug)]
@@ -12,6 +14,63 @@ pub enum LexerError {
     },
     UnexpectedCharacter {
         position: u32
+    },
+    /// Character literal doesn't end right after its character.
+    UnterminatedChar {
+        span: Span
+    },
+    EmptyChar {
+        span: Span
+    }
+}
+
+impl<'a> ErrorDisplay<'a, CommonErrorContext<'a>> for LexerError {
+    fn code(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::EmptySource => "H0001",
+            Self::UnterminatedString { .. } => "H0002",
+            Self::UnexpectedCharacter { .. } => "H0003",
+            Self::UnterminatedChar { .. } => "H0004",
+            Self::EmptyChar { .. } => "H0005"
+        })
+    }
+
+    fn fmt(&self, fmt: &mut ErrorFmt<'a>, ctx: &'a CommonErrorContext<'a>) {
+        match self {
+            Self::EmptySource => {
+                fmt
+                    .title(&format!("Source of {} is not loaded.", ctx.source.name));
+            }
+            Self::UnterminatedString { ref span } => {
+                fmt
+                    .title("Unterminated string literal.")
+                    .source(ctx.source, *span)
+                    .cause("This string is never closed.")
+                    .help("Add '\"' at the end of the string.");
+            }
+            Self::UnexpectedCharacter { ref position } => {
+                let char_len = ctx.source.src.as_ref()
+                    .and_then(|src| src.get(*position as usize..)?.chars().next())
+                    .map_or(1, |c| c.len_utf8() as u32);
+                fmt
+                    .title("Unexpected character.")
+                    .source(ctx.source, Span::new(ctx.source.id, *position, position + char_len))
+                    .cause("This character cannot start any token.");
+            }
+            Self::UnterminatedChar { ref span } => {
+                fmt
+                    .title("Unterminated character literal.")
+                    .source(ctx.source, *span)
+                    .cause("Character literal holds a single character.")
+                    .help("Close it with ' right after the character, strings are written in \".");
+            }
+            Self::EmptyChar { ref span } => {
+                fmt
+                    .title("Empty character literal.")
+                    .source(ctx.source, *span)
+                    .cause("Character literal must hold a single character.");
+            }
+        }
     }
 }
 
@@ -19,21 +78,42 @@ pub struct Lexer<'a> {
     source: &'a SourceFile,
     src: &'a str,
     tokens: Vec<Token>,
+    /// Spans of `//` comments, which are not tokens.
+    comments: Vec<Span>,
     current: u32,
     start: u32
 }
 
 impl<'a> Lexer<'a> {
-    /// Create TokenStream from the given source file.
-    pub fn lex(source: &'a SourceFile) -> Result<TokenStream, LexerError> {
+    /// Create TokenStream from the given source file. Lexer stops on the
+    /// first error, which is reported to the session.
+    pub fn lex(sess: &Session, source: &'a SourceFile) -> Option<TokenStream> {
+        Self::lex_with_comments(sess, source).map(|(ts, _)| ts)
+    }
+
+    /// Same as `lex`, but also returns spans of all comments in the file,
+    /// in the order they appear. These are needed by tools which print
+    /// the source back, like the formatter.
+    pub fn lex_with_comments(sess: &Session, source: &'a SourceFile) -> Option<(TokenStream, Vec<Span>)> {
+        match Self::lex_tokens(source) {
+            Ok(lexed) => Some(lexed),
+            Err(err) => {
+                sess.emit(err);
+                None
+            }
+        }
+    }
+
+    fn lex_tokens(source: &'a SourceFile) -> Result<(TokenStream, Vec<Span>), LexerError> {
         if source.src.is_none() {
             return Err(LexerError::EmptySource)
         }
 
         let mut lexer = Lexer {
             source,
-            src: source.src.as_ref().unwrap().as_str(),
+            src: source.src.as_ref().unwrap(),
             tokens: Vec::new(),
+            comments: Vec::new(),
             current: 0,
             start: 0
         };
@@ -44,38 +124,40 @@ impl<'a> Lexer<'a> {
             lexer.scan_token()?;
         }
 
-        Ok(TokenStream {
+        let tokens = TokenStream {
             source: source.id,
             tokens: Arc::new(lexer.tokens)
-        })
+        };
+        Ok((tokens, lexer.comments))
     }
 
     /// Check whether reader has reached the and of source file.
     fn is_at_end(&self) -> bool {
-        self.current as usize >= self.source.clen
+        self.current as usize >= self.src.len()
     }
 
-    fn nth_src_char(&self, n: u32) -> char {
-        self.src.chars().nth(n as usize).unwrap()
+    /// Get n-th character after the cursor, or '\0' if there is none.
+    fn char_after_cursor(&self, n: usize) -> char {
+        self.src[self.current as usize..].chars().nth(n).unwrap_or('\0')
     }
 
     /// Get char and move cursor to the next one.
     fn advance(&mut self) -> char {
-        let current_char = self.nth_src_char(self.current);
-        self.current += 1;
+        let current_char = self.char_after_cursor(0);
+        self.current += current_char.len_utf8() as u32;
         current_char
     }
 
     /// Check character without consuming it.
     fn peek(&self) -> char {
         if self.is_at_end() { return '\0' }
-        self.nth_src_char(self.current)
+        self.char_after_cursor(0)
     }
 
     /// Peek next character.
     fn peek_next(&self) -> char {
-        if self.current as usize + 1 >= self.source.clen { return '\0' }
-        self.nth_src_char(self.current + 1)
+        if self.is_at_end() { return '\0' }
+        self.char_after_cursor(1)
     }
 
     /// Add token to the currently built token stream.
@@ -91,8 +173,8 @@ impl<'a> Lexer<'a> {
         if self.is_at_end() { return false; }
         if self.peek() != expected { return false; }
 
-        self.current += 1;
-        return true;
+        self.current += expected.len_utf8() as u32;
+        true
     }
 
     /// Create span from current data
@@ -155,6 +237,7 @@ impl<'a> Lexer<'a> {
                 if self.try_match('/') {
                     while self.peek() != '\n' && !self.is_at_end()
                         { self.advance(); }
+                    self.comments.push(self.cspan());
                 } else {
                     self.add_token(TokenKind::Slash)
                 }
@@ -179,7 +262,7 @@ impl<'a> Lexer<'a> {
             ' ' | '\r' | '\t' | '\n' => { /* ignore */ },
             _ => {
                 Err(
-                    LexerError::UnexpectedCharacter { position: self.current - 1 }
+                    LexerError::UnexpectedCharacter { position: self.start }
                 )?
             }
         }
@@ -206,17 +289,28 @@ impl<'a> Lexer<'a> {
     }
 
     fn character(&mut self) -> Result<(), LexerError> {
-        unimplemented!()
+        if self.try_match('\'') {
+            return Err(LexerError::EmptyChar { span: self.cspan() })
+        }
+        // TODO: Support escape characters, like in strings
+        if !self.is_at_end() && self.peek() != '\n' {
+            self.advance();
+        }
+        if !self.try_match('\'') {
+            return Err(LexerError::UnterminatedChar { span: self.cspan() })
+        }
+        self.add_token(TokenKind::Literal { kind: LiteralKind::Char });
+        Ok(())
     }
 
     fn number(&mut self) -> Result<(), LexerError> {
         // TODO: Support for other radix numbers
-        while self.peek().is_digit(10) { self.advance(); }
+        while self.peek().is_ascii_digit() { self.advance(); }
         let mut kind = TokenKind::Literal { 
             kind: LiteralKind::Int { base: Base::Decimal }
         };
 
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             // TODO: Support exponent notation
             kind = TokenKind::Literal { 
                 kind: LiteralKind::Float { has_exponent: false }
@@ -224,7 +318,17 @@ impl<'a> Lexer<'a> {
 
             // Consume '.'
             self.advance();
-            while self.peek().is_digit(10) { self.advance(); }
+            while self.peek().is_ascii_digit() { self.advance(); }
+        }
+
+        // Suffix like in `300u8`, which is checked by the type checker
+        if self.peek().is_alphabetic() || self.peek() == '_' {
+            while {
+                let c = self.peek();
+                c.is_alphanumeric() || c == '_'
+            } {
+                self.advance();
+            }
         }
 
         self.add_token(kind);
@@ -239,11 +343,7 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
 
-        let text: String = self.src.chars()
-            .skip(self.start as usize)
-            .take(self.current as usize - self.start as usize)
-            .collect();
-        let text = text.as_str();
+        let text = &self.src[self.start as usize..self.current as usize];
 
         self.add_token(
             match text {
@@ -284,6 +384,7 @@ impl<'a> Lexer<'a> {
                 "where" => TokenKind::Where,
                 "async" => TokenKind::Async,
                 "await" => TokenKind::Await,
+                "extern" => TokenKind::Extern,
 
                 // Ident
                 _ => TokenKind::Ident
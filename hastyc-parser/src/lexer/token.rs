@@ -3,6 +3,7 @@ use std::{sync::Arc, fmt::Debug};
 use hastyc_common::{span::Span, identifiers::SourceFileID};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TokenStream {
     pub source: SourceFileID,
     pub tokens: Arc<Vec<Token>>,
@@ -23,9 +24,14 @@ impl TokenStream {
     pub fn len(&self) -> usize {
         self.tokens.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
@@ -53,6 +59,7 @@ impl Token {
 /// Kind of token, this does not contain any information about its
 /// contents nor any additional data.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     // Single-character tokens
     LeftParen, RightParen, LeftBrace, RightBrace,
@@ -71,6 +78,7 @@ pub enum TokenKind {
     Guard, Pub, Const, Static, Import, As, Module,
     Super, Pkg, Match, Struct, Trait, Impl, Enum,
     Getter, Setter, Override, Where, Async, Await,
+    Extern,
 
     // Special and other
     Ident,
@@ -84,6 +92,7 @@ pub enum TokenKind {
 
 /// Kind of literal token.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LiteralKind {
     Int {
         base: Base
@@ -98,6 +107,7 @@ pub enum LiteralKind {
 
 /// Numeric base of integer literal.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Base {
     Binary,
     Octal,
@@ -1,2 +1,3 @@
 pub mod lexer;
-pub mod parser;
\ No newline at end of file
+pub mod loader;
+pub mod parser;
@@ -0,0 +1,202 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Arc};
+
+use hastyc_common::{error::{CommonErrorContext, ErrorDisplay, ErrorFmt}, identifiers::{ASTNodeID, SourceFileID}, session::Session, source::FileName, span::Span};
+use log::debug;
+
+use crate::{lexer::Lexer, parser::{ItemKind, ItemStream, NodeSpanTable, Package, Parser, ParserOptions}};
+
+/// Extension of source files.
+pub const EXTENSION: &str = "hty";
+
+/// Root files of a package directory, which are tried in this order.
+const ROOT_FILES: &[&str] = &["src/main.hty", "src/lib.hty"];
+
+/// Root file of the package in the directory, `src/main.hty` for
+/// executables or `src/lib.hty` for libraries.
+pub fn find_root(dir: &Path) -> Option<PathBuf> {
+    ROOT_FILES.iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Parse the package from its root file, which has to be in the source map
+/// already, together with files of all modules declared like `module foo;`.
+/// Items of `module foo;` are in `foo.hty` or `foo/mod.hty` next to the root,
+/// and modules inside of `foo` are looked up in the `foo` directory.
+/// Every file is added to the source map, so spans of the package point to
+/// the file they come from. Errors of all files are reported to the session,
+/// and None is returned if there were any, unless options allow to recover
+/// from them. Modules whose files couldn't be parsed are left empty then.
+pub fn load_package(sess: &mut Session, root: SourceFileID, options: &ParserOptions) -> Option<Package> {
+    let root_file = sess.source_map.get(root)?;
+    let pkg = root_file.pkg;
+    // Files of modules can't be found next to sources which aren't on disk
+    let dir = sess.source_map.full_path(&root_file.name)
+        .map(|path| path.parent().map(Path::to_path_buf).unwrap_or_default());
+    let tokens = Lexer::lex(sess, root_file)?;
+    let root_options = ParserOptions { build_span_table: false, ..options.clone() };
+    let mut package = Parser::parse_from_root_with_options(sess, root_file, &tokens, &root_options)?;
+
+    let mut loaded = HashMap::new();
+    let mut failed = false;
+    let mut next = 0;
+    while next < package.module_decls.len() && !sess.should_abort() {
+        let decl = package.module_decls[next].clone();
+        let name = decl.ident.text(&package.symbol_storage).to_string();
+        let mut path = decl.parents.clone();
+        path.push(decl.ident.clone());
+
+        let found = match dir {
+            Some(ref dir) => {
+                let mut module_dir = dir.clone();
+                for parent in decl.parents.iter() {
+                    module_dir.push(parent.text(&package.symbol_storage));
+                }
+                let candidates = [
+                    module_dir.join(format!("{}.{}", name, EXTENSION)),
+                    module_dir.join(&name).join(format!("mod.{}", EXTENSION))
+                ];
+                match candidates.iter().filter(|path| path.is_file()).collect::<Vec<_>>()[..] {
+                    [file] => Ok(file.clone()),
+                    [] => Err(LoaderError::ModuleNotFound {
+                        name,
+                        span: decl.ident.span,
+                        candidates: candidates.iter().map(|path| sess.source_map.file_name(path)).collect()
+                    }),
+                    ref files => Err(LoaderError::AmbiguousModule {
+                        name,
+                        span: decl.ident.span,
+                        files: files.iter().map(|path| sess.source_map.file_name(path)).collect()
+                    })
+                }
+            },
+            None => Err(LoaderError::ModuleNotFound { name, span: decl.ident.span, candidates: Vec::new() })
+        };
+        let file_path = match found {
+            Ok(path) => path,
+            Err(err) => {
+                sess.emit(err);
+                failed = true;
+                next += 1;
+                continue
+            }
+        };
+
+        debug!(target: "parser", "Loading module file {:?}.", file_path);
+        let id = match sess.source_map.load_file(&file_path, pkg) {
+            Ok(id) => id,
+            Err(err) => {
+                let file = sess.source_map.file_name(&file_path);
+                sess.emit(LoaderError::Io { file, span: decl.ident.span, err });
+                failed = true;
+                next += 1;
+                continue
+            }
+        };
+        let file = sess.source_map.get(id).unwrap();
+        let items = Lexer::lex(sess, file)
+            .and_then(|tokens| Parser::parse_module_stream(sess, file, &tokens, &mut package, path, options));
+        match items {
+            Some(items) => {
+                package.module_decls[next].file = Some(id);
+                loaded.insert(decl.id, items);
+            },
+            None => failed = true
+        }
+        next += 1;
+    }
+    if (failed && !options.recover) || sess.should_abort() {
+        return None
+    }
+
+    fill_modules(&mut package.items, &mut loaded);
+    if options.build_span_table {
+        package.span_table = Some(NodeSpanTable::build(&package.items));
+    }
+    Some(package)
+}
+
+/// Put loaded items into modules they belong to, including modules
+/// declared in the loaded files.
+fn fill_modules(items: &mut ItemStream, loaded: &mut HashMap<ASTNodeID, ItemStream>) {
+    let has_modules = items.items.iter().any(|item| matches!(item.kind, ItemKind::Module(_)));
+    if !has_modules || loaded.is_empty() {
+        return
+    }
+    for item in Arc::make_mut(&mut items.items).iter_mut() {
+        if let ItemKind::Module(ref mut module) = item.kind {
+            if let Some(file_items) = loaded.remove(&item.id) {
+                *module = file_items;
+            }
+            fill_modules(module, loaded);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoaderError {
+    /// None of the files that the module could be in exists.
+    ModuleNotFound {
+        name: String,
+        span: Span,
+        candidates: Vec<FileName>
+    },
+    /// Module could be in more than one of the files.
+    AmbiguousModule {
+        name: String,
+        span: Span,
+        files: Vec<FileName>
+    },
+    /// File of the module was found, but couldn't be read.
+    Io {
+        file: FileName,
+        span: Span,
+        err: std::io::Error
+    }
+}
+
+impl<'a> ErrorDisplay<'a, CommonErrorContext<'a>> for LoaderError {
+    fn code(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::ModuleNotFound { .. } => "H0112",
+            Self::AmbiguousModule { .. } => "H0113",
+            Self::Io { .. } => "H0114"
+        })
+    }
+
+    fn fmt(&self, fmt: &mut ErrorFmt<'a>, ctx: &'a CommonErrorContext<'a>) {
+        match self {
+            Self::ModuleNotFound { ref name, ref span, ref candidates } => {
+                fmt
+                    .title(&format!("Can't find file of module '{}'.", name))
+                    .source(ctx.source, *span)
+                    .cause("Module is declared here.");
+                if candidates.is_empty() {
+                    fmt.note("Source of the package is not on disk, so only modules in braces can be used.");
+                } else {
+                    fmt
+                        .note(&format!("Items of the module should be in `{}`.", join(candidates, "` or `")))
+                        .help("Create one of the files, or write items of the module in braces.");
+                }
+            }
+            Self::AmbiguousModule { ref name, ref span, ref files } => {
+                fmt
+                    .title(&format!("Module '{}' is in more than one file.", name))
+                    .source(ctx.source, *span)
+                    .cause("Module is declared here.")
+                    .note(&format!("It could be in `{}`.", join(files, "` and `")))
+                    .help("Remove or rename all of them but one.");
+            }
+            Self::Io { ref file, ref span, ref err } => {
+                fmt
+                    .title(&format!("Can't read file of module `{}`: {}.", file, err))
+                    .source(ctx.source, *span)
+                    .cause("Module is declared here.");
+            }
+        }
+    }
+}
+
+fn join(files: &[FileName], separator: &str) -> String {
+    files.iter().map(ToString::to_string).collect::<Vec<_>>().join(separator)
+}
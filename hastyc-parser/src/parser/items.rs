@@ -1,11 +1,12 @@
 use std::sync::Arc;
 
-use hastyc_common::{identifiers::{ASTNodeID, IDCounter, Ident, SymbolStorage}, span::Span, path::Path};
+use hastyc_common::{identifiers::{ASTNodeID, IDCounter, PkgID, SourceFileID, Ident, Symbol, SymbolStorage}, impl_has_span, span::Span, path::Path};
 
-use super::StmtStream;
+use super::{NodeSpanTable, StmtStream};
 
 /// Currently unimplemented, basically there for future implementation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Attributes {
     pub attributes: Vec<Attribute>
 }
@@ -16,34 +17,79 @@ impl Attributes {
             attributes: Vec::new()
         }
     }
+
+    /// Whether there is flag attribute with the name, eg. `#[inline]`.
+    pub fn has_flag(&self, name: Symbol) -> bool {
+        self.attributes.iter().any(|attr| attr.ident.symbol == name && matches!(attr.kind, AttributeKind::FlagAttribute))
+    }
+
+    /// Whether there is list attribute with the name, which has the
+    /// argument, eg. `#[allow(dead_code)]`.
+    pub fn has_list_arg(&self, name: Symbol, arg: Symbol) -> bool {
+        self.attributes.iter().any(|attr| match attr.kind {
+            AttributeKind::ListAttribute(ref args) => {
+                attr.ident.symbol == name && args.iter().any(|ident| ident.symbol == arg)
+            },
+            AttributeKind::FlagAttribute => false
+        })
+    }
 }
 
 /// One single attribute
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Attribute {
     pub ident: Ident,
     pub kind: AttributeKind
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AttributeKind {
     /// Attribute without any additional data like `#[hello]`
     FlagAttribute,
+    /// Attribute with list of names like `#[allow(dead_code)]`
+    ListAttribute(Vec<Ident>),
     // TODO: Add more attribute kinds when necessary
 }
 
 /// Source package, this is basically a root node for the whole AST.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Package {
     pub attrs: Attributes,
     pub items: ItemStream,
     pub id: ASTNodeID,
+    /// Package of the root source file.
+    pub pkg: PkgID,
     pub idgen: IDCounter,
-    pub symbol_storage: SymbolStorage
+    pub symbol_storage: SymbolStorage,
+    /// Lookup table from source positions to nodes, this is
+    /// built only if requested in parser options.
+    pub span_table: Option<NodeSpanTable>,
+    /// Modules declared like `module foo;`, in the order they were parsed.
+    pub module_decls: Vec<ModuleDecl>
+}
+
+/// Module with items in a file of its own, declared like `module foo;`.
+/// Parser leaves such modules empty, `loader` fills them with items
+/// parsed from their files.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModuleDecl {
+    /// Id of the module item.
+    pub id: ASTNodeID,
+    pub ident: Ident,
+    /// Modules that the declaration is in, starting from the root.
+    pub parents: Vec<Ident>,
+    /// File that items of the module were parsed from, None until
+    /// the loader finds it.
+    pub file: Option<SourceFileID>
 }
 
 /// Stream of language items.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ItemStream {
     pub items: Arc<Vec<Item>>
 }
@@ -63,6 +109,7 @@ impl ItemStream {
 
 /// Single language item, it hold its kind, attributes, id and more useful information.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Item {
     pub attrs: Attributes,
     pub id: ASTNodeID,
@@ -73,6 +120,7 @@ pub struct Item {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Visibility {
     Public,
     Inherited
@@ -81,6 +129,7 @@ pub enum Visibility {
 /// Kind of language item. These are things like imports, function declarations,
 /// struct definitions, constants, etc...
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ItemKind {
     Module(ItemStream),
     Import(ImportKind, ImportTree),
@@ -104,6 +153,7 @@ impl ItemKind {
 /// Imports can be either relative (eg. `import hello::world`),
 /// super (eg. `import super::hello`), or package based (eg. `import pkg::hello`).
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImportKind {
     Relative,
     Super,
@@ -111,8 +161,9 @@ pub enum ImportKind {
 }
 
 /// As Hasty uses import system inspired by Rust, imports are not paths,
-/// but trees. For example `import a::{b, c::{self, d}}` will produce a tree.
+/// but trees. For example `import a::{b, c::{self as e, d}, f::*}` will produce a tree.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportTree {
     pub prefix: Path,
     pub kind: ImportTreeKind,
@@ -120,12 +171,12 @@ pub struct ImportTree {
 }
 
 impl ImportTree {
-    /// Import tree with only prefix, name and span
-    pub fn simple(mut name: Path, span: Span) -> Self {
+    /// Import tree with only prefix, name, optional alias and span
+    pub fn simple(mut name: Path, rename: Option<Ident>, span: Span) -> Self {
         let import_name = name.pop();
         Self {
             prefix: name,
-            kind: ImportTreeKind::Simple(import_name.unwrap().into()),
+            kind: ImportTreeKind::Simple(import_name.unwrap().into(), rename),
             span
         }
     }
@@ -148,30 +199,33 @@ impl ImportTree {
         }
     }
 
-    /// Self import
-    pub fn self_import(prefix: Path, span: Span) -> Self {
+    /// Self import with optional alias
+    pub fn self_import(prefix: Path, rename: Option<Ident>, span: Span) -> Self {
         Self {
             prefix,
-            kind: ImportTreeKind::SelfImport,
+            kind: ImportTreeKind::SelfImport(rename),
             span
         }
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImportTreeKind {
-    /// Import prefix
-    Simple(Ident),
-    /// Self import
-    SelfImport,
+    /// import prefix::name or import prefix::name as alias
+    Simple(Ident, Option<Ident>),
+    /// import prefix::self or import prefix::self as alias
+    SelfImport(Option<Ident>),
     /// import prefix::{ ... }
     Nested(Vec<(ImportTree, ASTNodeID)>),
     /// import prefix::*
     Glob
 }
 
-/// Function definition.
+/// Function definition. Body is `None` only for extern
+/// declarations like `extern fn hello();`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     pub generics: Generics,
     pub signature: FnSignature,
@@ -180,6 +234,7 @@ pub struct Function {
 
 /// Block of code like `{ ... }` in `fn hello() { ... }`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub stmts: StmtStream,
     pub id: ASTNodeID,
@@ -198,6 +253,7 @@ impl Block {
 
 /// Generics. These are those `<T>` thingies.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Generics {
     // TODO: Implement generics in some reasonable way.
 }
@@ -205,17 +261,39 @@ pub struct Generics {
 /// Function signature containing information about its types
 /// and things like this.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FnSignature {
     pub is_const: bool,
     pub is_async: bool,
+    pub ext: Extern,
     pub inputs: Vec<FnInput>,
     pub output: FnRetTy, 
     pub span: Span   
 }
 
 
+/// Whether function is declared as `extern`, this holds ABI
+/// of the function if it was provided.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Extern {
+    /// Normal function.
+    None,
+    /// `extern fn`, uses default ABI.
+    Implicit(Span),
+    /// `extern "C" fn` with explicit ABI string and its span.
+    Explicit(Symbol, Span)
+}
+
+impl Extern {
+    pub fn is_extern(&self) -> bool {
+        !matches!(self, Self::None)
+    }
+}
+
 /// Function input param.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FnInput {
     pub attributes: Attributes,
     pub id: ASTNodeID,
@@ -225,6 +303,7 @@ pub struct FnInput {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FnRetTy {
     Default, // This is () for normal functions.
     Ty(Ty)
@@ -233,6 +312,7 @@ pub enum FnRetTy {
 /// Simple type like `i32`, `()` or more complex one like
 /// `hello::world::MyType`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ty {
     pub id: ASTNodeID,
     pub kind: TyKind,
@@ -241,6 +321,7 @@ pub struct Ty {
 
 /// Kind of type.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TyKind {
     /// This is used for passing "self" to the function as an argument.
     SelfTy,
@@ -256,6 +337,7 @@ pub enum TyKind {
 
 /// A pattern.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pat {
     pub id: ASTNodeID,
     pub kind: PatKind,
@@ -272,12 +354,14 @@ impl Pat {
 
 /// Kind of pattern.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PatKind {
     SelfPat,
     Ident(Ident)
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataVariant {
     Struct {
         fields: Vec<FieldDef>,
@@ -289,6 +373,7 @@ pub enum DataVariant {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldDef {
     pub attrs: Attributes,
     pub id: ASTNodeID,
@@ -299,11 +384,13 @@ pub struct FieldDef {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumDef {
     pub variants: Vec<EnumVariant>
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumVariant {
     pub attrs: Attributes,
     pub id: ASTNodeID,
@@ -311,4 +398,6 @@ pub struct EnumVariant {
     pub vis: Visibility,
     pub ident: Ident,
     pub data: DataVariant
-}
\ No newline at end of file
+}
+
+impl_has_span!(Item, ImportTree, Block, FnSignature, FnInput, Ty, Pat, FieldDef, EnumVariant);
@@ -1,9 +1,12 @@
 mod items;
 mod stmt;
+mod node_map;
+mod stable_hash;
 
 pub use items::*;
 pub use stmt::*;
-use hastyc_common::{source::SourceFile, identifiers::{IDCounter, SymbolStorage, Ident, ASTNodeID}, span::Span, path::{Path, PathSegment}, error::{ErrorDisplay, CommonErrorContext}};
+pub use node_map::*;
+use hastyc_common::{source::SourceFile, identifiers::{IDCounter, SymbolStorage, Ident, ASTNodeID}, span::Span, path::{Path, PathSegment}, error::{ErrorDisplay, CommonErrorContext}, session::Session};
 
 use crate::lexer::{TokenStream, Token, TokenKind, LiteralKind};
 
@@ -13,34 +16,29 @@ macro_rules! basic_binary_expression_impl {
     ($(for $name:ident use $fun:ident where $($kind:ident => $ty:ident),+);+;) => {
         $(
             fn $name(&mut self) -> Result<Expr, ParserError> {
-                let span_start = self.previous().span;
-                let lhs = self.$fun()?;
-                let mut kind = lhs.kind;
+                let mut expr = self.$fun()?;
                 
                 while $(self.try_match(TokenKind::$kind))||* {
                     let op_kind = self.previous().kind;
+                    let op_span = self.previous().span;
                     let rhs = self.$fun()?;
-                    kind = ExprKind::Binary(
-                        match op_kind {
-                            $(TokenKind::$kind => BinOpKind::$ty),+,
-                            _ => { unreachable!() }
-                        }.spanned(self.previous().span),
-                        Box::new(Expr {
-                            id: self.node_id(),
-                            kind,
-                            span: lhs.span,
-                            attrs: Attributes::empty()
-                        }),
-                        Box::new(rhs)
-                    )    
+                    let span = Span::from_begin_end(expr.span, rhs.span);
+                    expr = Expr {
+                        id: self.node_id(),
+                        kind: ExprKind::Binary(
+                            match op_kind {
+                                $(TokenKind::$kind => BinOpKind::$ty),+,
+                                _ => { unreachable!() }
+                            }.spanned(op_span),
+                            Box::new(expr),
+                            Box::new(rhs)
+                        ),
+                        span,
+                        attrs: Attributes::empty()
+                    };
                 }
 
-                Ok(Expr {
-                    id: self.node_id(),
-                    kind,
-                    span: Span::from_begin_end(span_start, self.previous().span),
-                    attrs: Attributes::empty()
-                })
+                Ok(expr)
             }
         )+
     };
@@ -51,7 +49,49 @@ pub struct Parser<'pkg, 'a> {
     tokens: &'a TokenStream,
     current: usize,
     symbol_storage: SymbolStorage,
-    source_file: &'a SourceFile
+    source_file: &'a SourceFile,
+    /// Errors that parser has recovered from, these are reported
+    /// together after the whole package is parsed.
+    errors: Vec<ParserError>,
+    /// Current nesting depth of recursive constructs.
+    depth: u32,
+    /// Maximum nesting depth before parser gives up with an error.
+    max_depth: u32,
+    /// Modules that the parser is in, starting from the root of the package.
+    module_path: Vec<Ident>,
+    /// Modules declared like `module foo;` in the parsed file.
+    module_decls: Vec<ModuleDecl>,
+    /// Number of blocks that items are being parsed in.
+    block_items: u32
+}
+
+/// Default maximum nesting depth of blocks, expressions and modules.
+/// This is low enough to stay within default 8MB main thread stack
+/// even in debug builds, where parser stack frames are much bigger.
+pub const DEFAULT_MAX_NESTING_DEPTH: u32 = 128;
+
+/// Options that change how the package is parsed.
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Maximum nesting depth before parser gives up with an error.
+    pub max_depth: u32,
+    /// Whether to build `NodeSpanTable` for the package, this is
+    /// useful for IDE features, but not needed for compilation.
+    pub build_span_table: bool,
+    /// Keep items that were parsed, when parser recovered from all errors.
+    /// Errors are still reported, so this is for tools which work with
+    /// code that is being edited, like the language server.
+    pub recover: bool
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
+            build_span_table: false,
+            recover: false
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -69,17 +109,79 @@ pub enum ParserError {
     },
     ExpectedVariant {
         found: Token
+    },
+    InvalidImportTree {
+        kind: InvalidImportKind,
+        span: Span
+    },
+    ExpectedExpression {
+        found: Token
+    },
+    ExpectedPattern {
+        found: Token
+    },
+    /// Syntax that is recognized, but not yet supported by the compiler.
+    UnsupportedSyntax {
+        feature: &'static str,
+        span: Span
+    },
+    NestingTooDeep {
+        limit: u32,
+        span: Span
+    },
+    /// End of file was reached while some delimiter was still open.
+    UnclosedDelimiter {
+        open: Token,
+        expected: TokenKind,
+        eof: Span
+    },
+    MissingSemicolon {
+        found: Token,
+        /// Position where the semicolon should be inserted.
+        suggestion: Span
+    },
+    /// `module foo;` inside of a block, where there is no file
+    /// that the module could be loaded from.
+    ModuleFileInBlock {
+        span: Span
+    },
+    /// Extern function declared with a body, like `extern fn foo() {}`.
+    ExternFnBody {
+        span: Span
+    },
+    /// ABI string of an extern function other than `"C"`.
+    UnknownAbi {
+        span: Span
     }
 }
 
 impl<'a> ErrorDisplay<'a, CommonErrorContext<'a>> for ParserError {
+    fn code(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::ExpectedToken { .. } => "H0100",
+            Self::ExpectedItem { .. } => "H0101",
+            Self::ExpectedName { .. } => "H0102",
+            Self::ExpectedVariant { .. } => "H0103",
+            Self::InvalidImportTree { .. } => "H0104",
+            Self::ExpectedExpression { .. } => "H0105",
+            Self::ExpectedPattern { .. } => "H0106",
+            Self::UnsupportedSyntax { .. } => "H0107",
+            Self::NestingTooDeep { .. } => "H0108",
+            Self::UnclosedDelimiter { .. } => "H0109",
+            Self::MissingSemicolon { .. } => "H0110",
+            Self::ModuleFileInBlock { .. } => "H0111",
+            Self::ExternFnBody { .. } => "H0115",
+            Self::UnknownAbi { .. } => "H0116"
+        })
+    }
+
     fn fmt(&self, fmt: &mut hastyc_common::error::ErrorFmt<'a>, ctx: &'a CommonErrorContext<'a>) {
         match self {
             Self::ExpectedToken { expected: _, ref found } => {
                 fmt
                     .title(&format!(
                         "No rules expected the token '{}'.",
-                        found.span.get_text(ctx.source).unwrap()
+                        token_text(found, ctx.source)
                     ))
                     .source(ctx.source, found.span)
                     .cause("No rules expected this token here.");
@@ -88,7 +190,7 @@ impl<'a> ErrorDisplay<'a, CommonErrorContext<'a>> for ParserError {
                 fmt
                     .title(&format!(
                         "Expected item but found '{}'.",
-                        found.span.get_text(ctx.source).unwrap()
+                        token_text(found, ctx.source)
                     ))
                     .source(ctx.source, found.span)
                     .cause("Only items are expected in modules.");
@@ -98,7 +200,7 @@ impl<'a> ErrorDisplay<'a, CommonErrorContext<'a>> for ParserError {
                     .title(&format!(
                         "Expected name for {} but found '{}'.",
                         target,
-                        found.span.get_text(ctx.source).unwrap()
+                        token_text(found, ctx.source)
                     ))
                     .source(ctx.source, found.span)
                     .cause("Names must be identifiers.");
@@ -107,15 +209,125 @@ impl<'a> ErrorDisplay<'a, CommonErrorContext<'a>> for ParserError {
                 fmt 
                     .title(&format!(
                         "Expected struct or enum variant but found '{}'.",
-                        found.span.get_text(ctx.source).unwrap()
+                        token_text(found, ctx.source)
                     ))
                     .source(ctx.source, found.span)
                     .cause("Struct/enum variant must be unit, tuple or struct-like. What you provided is none of those.");
             }
+            Self::InvalidImportTree { ref kind, ref span } => {
+                fmt
+                    .title(&format!("Invalid import: {}.", kind))
+                    .source(ctx.source, *span)
+                    .cause(kind.cause());
+            }
+            Self::ExpectedExpression { ref found } => {
+                fmt
+                    .title(&format!(
+                        "Expected expression but found '{}'.",
+                        token_text(found, ctx.source)
+                    ))
+                    .source(ctx.source, found.span)
+                    .cause("This token cannot start an expression.");
+            }
+            Self::ExpectedPattern { ref found } => {
+                fmt
+                    .title(&format!(
+                        "Expected pattern but found '{}'.",
+                        token_text(found, ctx.source)
+                    ))
+                    .source(ctx.source, found.span)
+                    .cause("Only identifier patterns are currently allowed here.");
+            }
+            Self::UnsupportedSyntax { ref feature, ref span } => {
+                fmt
+                    .title(&format!("Unsupported syntax: {}.", feature))
+                    .source(ctx.source, *span)
+                    .cause("This syntax is not yet supported by the compiler.");
+            }
+            Self::NestingTooDeep { ref limit, ref span } => {
+                fmt
+                    .title(&format!("Code is nested too deeply (limit is {}).", limit))
+                    .source(ctx.source, *span)
+                    .cause("Parser cannot go any deeper from here.")
+                    .help("Try to split deeply nested code into smaller functions or variables.");
+            }
+            Self::UnclosedDelimiter { ref open, ref expected, eof: _ } => {
+                fmt
+                    .title(&format!(
+                        "Unclosed delimiter '{}'.",
+                        token_text(open, ctx.source)
+                    ))
+                    .source(ctx.source, open.span)
+                    .cause("This delimiter is never closed.")
+                    .note(&format!(
+                        "Expected '{}' to close it, but reached the end of file.",
+                        closing_delimiter_text(*expected)
+                    ));
+            }
+            Self::MissingSemicolon { ref found, ref suggestion } => {
+                fmt
+                    .title(&format!(
+                        "Expected ';' but found '{}'.",
+                        token_text(found, ctx.source)
+                    ))
+                    .source(ctx.source, *suggestion)
+                    .cause("Statements must be terminated with a semicolon.")
+                    .suggestion(*suggestion, ";", "add semicolon here");
+            }
+            Self::ModuleFileInBlock { ref span } => {
+                fmt
+                    .title("Module in a block cannot have items in another file.")
+                    .source(ctx.source, *span)
+                    .cause("Modules declared like this are only allowed in other modules.")
+                    .help("Write items of the module in braces, like `module name { ... }`.");
+            }
+            Self::ExternFnBody { ref span } => {
+                fmt
+                    .title("Extern function cannot have a body.")
+                    .source(ctx.source, *span)
+                    .cause("Extern functions are defined outside of the program.")
+                    .help("Replace the body with `;`, or remove `extern` to define the function here.");
+            }
+            Self::UnknownAbi { ref span } => {
+                fmt
+                    .title(&format!(
+                        "Unknown ABI {}.",
+                        span.get_text(ctx.source).unwrap_or_default()
+                    ))
+                    .source(ctx.source, *span)
+                    .cause("Only the \"C\" ABI is supported.");
+            }
         }
     }
 }
 
+/// Text of the token for error messages.
+fn token_text(token: &Token, source: &SourceFile) -> String {
+    if token.kind == TokenKind::EOF {
+        return "<end of file>".to_string()
+    }
+    token.span.get_text(source).unwrap_or_default().to_string()
+}
+
+/// Returns closing delimiter for the given opening one.
+fn closing_delimiter(open: TokenKind) -> Option<TokenKind> {
+    match open {
+        TokenKind::LeftBrace => Some(TokenKind::RightBrace),
+        TokenKind::LeftParen => Some(TokenKind::RightParen),
+        TokenKind::LeftBracket => Some(TokenKind::RightBracket),
+        _ => None
+    }
+}
+
+fn closing_delimiter_text(close: TokenKind) -> &'static str {
+    match close {
+        TokenKind::RightBrace => "}",
+        TokenKind::RightParen => ")",
+        TokenKind::RightBracket => "]",
+        _ => unreachable!()
+    }
+}
+
 #[derive(Debug)]
 pub enum NameTarget {
     Module, Import, Attribute,
@@ -139,24 +351,84 @@ impl std::fmt::Display for NameTarget {
     }
 }
 
+/// Kind of invalid import tree, like `a::*::b`.
+#[derive(Debug)]
+pub enum InvalidImportKind {
+    GlobNotLast,
+    SelfNotLast,
+    NestedNotLast,
+    AliasedGlob,
+    TrailingSeparator
+}
+
+impl InvalidImportKind {
+    fn cause(&self) -> &'static str {
+        match self {
+            Self::GlobNotLast => "Nothing can follow '*' in an import.",
+            Self::SelfNotLast => "Nothing can follow 'self' in an import.",
+            Self::NestedNotLast => "Nothing can follow '{ ... }' group in an import.",
+            Self::AliasedGlob => "Glob imports bring many names into scope, so they cannot be renamed.",
+            Self::TrailingSeparator => "Expected name, '*', 'self' or '{ ... }' after '::'."
+        }
+    }
+}
+
+impl std::fmt::Display for InvalidImportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GlobNotLast => f.write_str("glob must be the last segment"),
+            Self::SelfNotLast => f.write_str("self must be the last segment"),
+            Self::NestedNotLast => f.write_str("import group must be the last segment"),
+            Self::AliasedGlob => f.write_str("glob cannot be aliased"),
+            Self::TrailingSeparator => f.write_str("path ends with '::'")
+        }
+    }
+}
+
 impl<'pkg, 'a> Parser<'pkg, 'a> {
-    pub fn parse_from_root(root_file: &'a SourceFile, root_ts: &'a TokenStream) -> Result<Package, ParserError> {
+    /// Parse the whole package. As parser tries to recover from some errors,
+    /// all errors that were found are reported to the session, and None is
+    /// returned if there were any.
+    pub fn parse_from_root(
+        sess: &Session,
+        root_file: &'a SourceFile,
+        root_ts: &'a TokenStream
+    ) -> Option<Package> {
+        Self::parse_from_root_with_options(sess, root_file, root_ts, &ParserOptions::default())
+    }
+
+    /// Same as `parse_from_root`, but with custom parser options.
+    pub fn parse_from_root_with_options(
+        sess: &Session,
+        root_file: &'a SourceFile,
+        root_ts: &'a TokenStream,
+        options: &ParserOptions
+    ) -> Option<Package> {
         let counter = IDCounter::create();
         let mut package = Package {
-            attrs: Attributes::empty(), // TODO: Parse global attributes
+            // Global attributes are parsed together with the root stream
+            attrs: Attributes::empty(),
             items: ItemStream::empty(),
             id: (&counter).into(),
+            pkg: root_file.pkg,
             idgen: counter,
-            symbol_storage: SymbolStorage::new()
+            symbol_storage: SymbolStorage::new(),
+            span_table: None,
+            module_decls: Vec::new()
         };
 
         debug!(target: "parser", "Starting parse of package from root: {:?}.", root_file.name);
-        let items = Self::parse_root_stream(root_file, root_ts, &mut package)?;
+        let parsed = Self::parse_stream(root_file, root_ts, &mut package, Vec::new(), options.max_depth, true);
+        let (attrs, items) = Self::report_errors(sess, parsed, options)?;
+        package.attrs = Attributes { attributes: attrs };
 
+        if options.build_span_table {
+            package.span_table = Some(NodeSpanTable::build(&items));
+        }
         package.items = items;
 
         trace!(target: "parser", "Package symbol storage dump: {:?}.", package.symbol_storage);
-        Ok(package)
+        Some(package)
     }
 
     // Utility functions
@@ -167,8 +439,8 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     fn ident(&mut self, token: &Token) -> Ident {
         let token_text = self.source_file.get_span(&token.span);
         Ident::new(
-            self.symbol_storage.get_or_register(&token_text),
-            token.span.clone()
+            self.symbol_storage.get_or_register(token_text),
+            token.span
         )
     }
 
@@ -189,14 +461,36 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
     /// Can return EOF, but clones the value, so peek is preferable.
     fn safe_peek(&self) -> Token {
-        if self.is_at_end() { Token {
-            kind: TokenKind::EOF,
-            span: Span::dummy()
-        }} else {
+        if self.is_at_end() {
+            self.eof_token()
+        } else {
             self.peek().clone()
         }
     }
 
+    /// EOF token, its span is empty and points right after the last token.
+    fn eof_token(&self) -> Token {
+        let span = match self.tokens.tokens.last() {
+            Some(last) => Span::new(last.span.source, last.span.end, last.span.end),
+            None => Span::new(self.tokens.source, 0, 0)
+        };
+        Token { kind: TokenKind::EOF, span }
+    }
+
+    /// Innermost delimiter that has been opened, but not closed yet.
+    /// This is computed from the tokens consumed so far.
+    fn unclosed_delimiter(&self) -> Option<(Token, TokenKind)> {
+        let mut stack: Vec<(&Token, TokenKind)> = Vec::new();
+        for token in self.tokens.iter().take(self.current) {
+            if let Some(close) = closing_delimiter(token.kind) {
+                stack.push((token, close));
+            } else if let Some(idx) = stack.iter().rposition(|(_, close)| *close == token.kind) {
+                stack.truncate(idx);
+            }
+        }
+        stack.pop().map(|(token, close)| (token.clone(), close))
+    }
+
     fn previous(&self) -> &Token {
         self.tokens.iter().nth(self.current - 1).unwrap()
     }
@@ -233,50 +527,173 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         if self.check(tk) {
             Ok(self.advance())
         } else {
-            if self.is_at_end() {
-                Err(
-                    ParserError::ExpectedToken {
-                        expected: tk,
-                        found: Token { kind: TokenKind::EOF, span: Span::dummy() }
-                    }
-                )?
-            }
-
             Err(
                 ParserError::ExpectedToken {
                     expected: tk,
-                    found: self.peek().clone()
+                    found: self.safe_peek()
                 }
             )
         }
     }
 
+    /// Expect semicolon, if it is missing, error is recorded and parsing
+    /// continues as if it was there.
+    fn expect_semi(&mut self) {
+        if self.try_match(TokenKind::Semi) { return }
+
+        let prev = self.previous().span;
+        self.errors.push(ParserError::MissingSemicolon {
+            found: self.safe_peek(),
+            suggestion: Span::new(prev.source, prev.end, prev.end)
+        });
+    }
+
+    /// Run parsing function one nesting level deeper, returning error
+    /// if maximum nesting depth is exceeded.
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParserError>
+    ) -> Result<T, ParserError> {
+        if self.depth >= self.max_depth {
+            return Err(ParserError::NestingTooDeep {
+                limit: self.max_depth,
+                span: self.safe_peek().span
+            })
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
     // Parsing functions
-    pub fn parse_root_stream(root_file: &'a SourceFile, token_stream: &'a TokenStream, pkg: &mut Package) -> Result<ItemStream, ParserError> {
+    pub fn parse_root_stream(
+        root_file: &'a SourceFile,
+        token_stream: &'a TokenStream,
+        pkg: &mut Package,
+        max_depth: u32
+    ) -> Result<ItemStream, Vec<ParserError>> {
+        match Self::parse_stream(root_file, token_stream, pkg, Vec::new(), max_depth, true) {
+            (Some((attrs, items)), errors) if errors.is_empty() => {
+                pkg.attrs = Attributes { attributes: attrs };
+                Ok(items)
+            },
+            (_, errors) => Err(errors)
+        }
+    }
+
+    /// Parse items of module declared like `module foo;` from its own file.
+    /// Nodes get ids of the package, and modules declared in the file are
+    /// added to its `module_decls`, with `parents` in front of their path.
+    /// Errors are reported to the session like in `parse_from_root`.
+    pub fn parse_module_stream(
+        sess: &Session,
+        file: &'a SourceFile,
+        token_stream: &'a TokenStream,
+        pkg: &mut Package,
+        parents: Vec<Ident>,
+        options: &ParserOptions
+    ) -> Option<ItemStream> {
+        let parsed = Self::parse_stream(file, token_stream, pkg, parents, options.max_depth, false);
+        Self::report_errors(sess, parsed, options).map(|(_, items)| items)
+    }
+
+    /// Report errors of the parsed file, returning what was parsed unless
+    /// there were errors that options don't allow to recover from.
+    fn report_errors<T>(sess: &Session, (parsed, errors): (Option<T>, Vec<ParserError>), options: &ParserOptions) -> Option<T> {
+        let recovered = errors.is_empty() || options.recover;
+        for err in errors {
+            sess.emit(err);
+        }
+        parsed.filter(|_| recovered)
+    }
+
+    fn parse_stream(
+        file: &'a SourceFile,
+        token_stream: &'a TokenStream,
+        pkg: &mut Package,
+        module_path: Vec<Ident>,
+        max_depth: u32,
+        global_attrs: bool
+    ) -> (Option<(Vec<Attribute>, ItemStream)>, Vec<ParserError>) {
+        // Symbols used by all files are kept in the same storage
+        let symbol_storage = std::mem::take(&mut pkg.symbol_storage);
         let mut parser = Parser {
             tokens: token_stream,
             current: 0,
-            symbol_storage: SymbolStorage::new(),
-            source_file: root_file,
+            symbol_storage,
+            source_file: file,
             package: pkg,
+            errors: Vec::new(),
+            depth: 0,
+            max_depth,
+            module_path,
+            module_decls: Vec::new(),
+            block_items: 0
         };
-        let mut items = Vec::new();
-
-        while !parser.is_at_end() {
-            let item = parser.parse_item()?;
+        let parsed = parser.parse_file(global_attrs);
+
+        let Parser { symbol_storage, module_decls, errors, .. } = parser;
+        pkg.symbol_storage = symbol_storage;
+        let parsed = parsed.map(|(attrs, items)| {
+            pkg.module_decls.extend(module_decls);
+            (attrs, ItemStream::from_items(items))
+        });
+        (parsed, errors)
+    }
 
-            items.push(item);
+    /// Parse all items of the file, None if parser couldn't recover from
+    /// an error, which is added to the errors.
+    fn parse_file(&mut self, global_attrs: bool) -> Option<(Vec<Attribute>, Vec<Item>)> {
+        let mut attrs = Vec::new();
+        while global_attrs && !self.is_at_end() {
+            match self.try_parse_attribute(true) {
+                Ok(Some(attr)) => attrs.push(attr),
+                Ok(None) => break,
+                Err(err) => {
+                    self.errors.push(err);
+                    return None
+                }
+            }
         }
 
-        pkg.symbol_storage = parser.symbol_storage;
-        Ok(ItemStream::from_items(items))
+        let mut items = Vec::new();
+        while !self.is_at_end() {
+            match self.parse_item() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    // Running out of tokens with open delimiter is most likely
+                    // caused by that delimiter, so we report it instead.
+                    let err = match self.unclosed_delimiter() {
+                        Some((open, expected)) if self.is_at_end() => ParserError::UnclosedDelimiter {
+                            open,
+                            expected,
+                            eof: self.eof_token().span
+                        },
+                        _ => err
+                    };
+                    self.errors.push(err);
+                    return None
+                }
+            }
+        }
+        Some((attrs, items))
     }
 
-    /// Parse attribute like `#[attribute]`.
-    pub fn try_parse_attribute(&mut self, _can_be_global: bool) -> Result<Option<Attribute>, ParserError> {
-        //? can_be_global is a placeholder for later use
-        // Try to parse hashtag
+    /// Parse attribute like `#[attribute]`, or if `global` is set, attribute
+    /// of the whole package like `#![attribute]`. These can only be at the
+    /// start of the root file, attributes of the other kind are left alone.
+    pub fn try_parse_attribute(&mut self, global: bool) -> Result<Option<Attribute>, ParserError> {
+        let is_global = self.check(TokenKind::Hash)
+            && self.tokens.iter().nth(self.current + 1).is_some_and(|token| token.kind == TokenKind::Bang);
+        if is_global != global {
+            return Ok(None)
+        }
         if self.try_match(TokenKind::Hash) {
+            if global {
+                self.consume(TokenKind::Bang)?;
+            }
             self.consume(TokenKind::LeftBracket)?;
             
             let ident = self.expect_ident(
@@ -286,9 +703,24 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                 }
             )?;
 
-            // Currently only option is unnamed argument, so just expect that
+            let kind = if self.try_match(TokenKind::LeftParen) {
+                let mut args = Vec::new();
+                while !self.check(TokenKind::RightParen) {
+                    args.push(self.expect_ident(ParserError::ExpectedName {
+                        target: NameTarget::Attribute,
+                        found: self.safe_peek().clone()
+                    })?);
+
+                    if !self.try_match(TokenKind::Comma) { break; }
+                }
+                self.consume(TokenKind::RightParen)?;
+                AttributeKind::ListAttribute(args)
+            } else {
+                AttributeKind::FlagAttribute
+            };
+
             self.consume(TokenKind::RightBracket)?;
-            Ok(Some(Attribute { ident, kind: AttributeKind::FlagAttribute }))
+            Ok(Some(Attribute { ident, kind }))
         } else {
             Ok(None)
         }
@@ -297,11 +729,8 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     /// Parse attributes. This can return empty vector if none are found.
     pub fn parse_attributes(&mut self) -> Result<Attributes, ParserError> {
         let mut attribs = Vec::new();
-        loop {
-            match self.try_parse_attribute(false)? {
-                Some(attr) => attribs.push(attr),
-                None => break
-            }
+        while let Some(attr) = self.try_parse_attribute(false)? {
+            attribs.push(attr);
         }
         Ok(Attributes {
             attributes: attribs
@@ -325,11 +754,16 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             Visibility::Public
         } else { Visibility::Inherited };
 
+        if self.is_at_end() {
+            Err(ParserError::ExpectedItem { found: self.eof_token() })?
+        }
+
         // Every item has its own keyword, which makes the work a lot easier :D
         let mut item = match self.advance().kind {
             TokenKind::Module => self.parse_module()?,
             TokenKind::Import => self.parse_import()?,
             TokenKind::Fn => self.parse_fn()?,
+            TokenKind::Extern => self.parse_extern_fn()?,
             TokenKind::Struct => self.parse_struct_def()?,
             TokenKind::Enum => self.parse_enum_def()?,
             _ => {
@@ -486,7 +920,8 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         })
     }
 
-    /// Module definition like `module hello { ... }`.
+    /// Module definition like `module hello { ... }`, or `module hello;`
+    /// with items in a file of its own.
     pub fn parse_module(&mut self) -> Result<Item, ParserError> {
         let span_keyword = self.previous().span;
         let name = self.expect_ident(
@@ -496,13 +931,25 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             }
         )?;
 
+        if self.try_match(TokenKind::Semi) {
+            return self.module_decl(name, span_keyword)
+        }
         self.consume(TokenKind::LeftBrace)?;
 
+        self.module_path.push(name.clone());
         let mut items = Vec::new();
+        let mut result = Ok(());
         while !self.check(TokenKind::RightBrace) {
-            let i = self.parse_item()?;
-            items.push(i);
+            match self.nested(Self::parse_item) {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    result = Err(err);
+                    break
+                }
+            }
         }
+        self.module_path.pop();
+        result?;
 
         self.consume(TokenKind::RightBrace)?;
 
@@ -517,6 +964,31 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         })
     }
 
+    /// Module with items in another file, which is left empty
+    /// and recorded for the loader to find the file.
+    fn module_decl(&mut self, name: Ident, span_keyword: Span) -> Result<Item, ParserError> {
+        let span = Span::from_begin_end(span_keyword, self.previous().span);
+        if self.block_items > 0 {
+            return Err(ParserError::ModuleFileInBlock { span })
+        }
+
+        let id = self.node_id();
+        self.module_decls.push(ModuleDecl {
+            id,
+            ident: name.clone(),
+            parents: self.module_path.clone(),
+            file: None
+        });
+        Ok(Item {
+            attrs: Attributes::empty(),
+            id,
+            visibility: Visibility::Inherited,
+            kind: ItemKind::Module(ItemStream::empty()),
+            ident: name,
+            span
+        })
+    }
+
     /// Import like `import hello::world` or `import hello::{world, lorem::{ipsum, self}}`
     pub fn parse_import(&mut self) -> Result<Item, ParserError> {
         let span_keyword = self.previous().span;
@@ -547,21 +1019,31 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
 
     pub fn parse_import_tree(&mut self) -> Result<ImportTree, ParserError> {
-        let span_start = self.previous().span;
+        let span_start = self.safe_peek().span;
         let prefix = self.parse_import_prefix_path()?;
         trace!(target: "parser", "Found path with prefix '{:?}'.", prefix);
 
         // First: check for glob
         if self.try_match(TokenKind::Star) {
             let span = Span::from_begin_end(span_start, self.previous().span);
+            if self.check(TokenKind::As) {
+                Err(ParserError::InvalidImportTree {
+                    kind: InvalidImportKind::AliasedGlob,
+                    span: self.peek().span
+                })?
+            }
+            self.ensure_import_tree_end(InvalidImportKind::GlobNotLast)?;
             return Ok(ImportTree::glob(prefix, span))
         }
         
         // Second: Self import
         let has_dcolon = self.previous().kind == TokenKind::DColon;
-        if (has_dcolon || prefix.len() == 0) && self.try_match(TokenKind::LSelf) {
+        if (has_dcolon || prefix.is_empty()) && self.try_match(TokenKind::LSelf) {
+            self.ensure_import_tree_end(InvalidImportKind::SelfNotLast)?;
+            let rename = self.parse_import_rename()?;
             return Ok(ImportTree::self_import(
                 prefix,
+                rename,
                 Span::from_begin_end(span_start, self.previous().span)
             ))
         }
@@ -571,11 +1053,12 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             let mut subtrees = Vec::new();
             trace!(target: "parser", "Parsing nested import tree.");
             loop {
-                let subtree = self.parse_import_tree()?;
+                let subtree = self.nested(Self::parse_import_tree)?;
                 subtrees.push(subtree);
                 if !self.try_match(TokenKind::Comma) { break; }
             }
             self.consume(TokenKind::RightBrace)?;
+            self.ensure_import_tree_end(InvalidImportKind::NestedNotLast)?;
             return Ok(ImportTree::nested(
                 prefix,
                 subtrees.into_iter().map(|i| (i, self.node_id())).collect(),
@@ -584,7 +1067,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         }
 
         // Forth: Simple import
-        if prefix.len() == 0 {
+        if prefix.is_empty() {
             Err(
                 ParserError::ExpectedName {
                     target: NameTarget::Import,
@@ -592,16 +1075,51 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                 }
             )?
         }
+        if has_dcolon {
+            Err(ParserError::InvalidImportTree {
+                kind: InvalidImportKind::TrailingSeparator,
+                span: self.previous().span
+            })?
+        }
 
+        let rename = self.parse_import_rename()?;
         Ok(ImportTree::simple(
             prefix, 
+            rename,
             Span::from_begin_end(span_start, self.previous().span)
         ))
     }
 
+    /// Alias of the import like `as alias` in `import hello as alias`.
+    fn parse_import_rename(&mut self) -> Result<Option<Ident>, ParserError> {
+        if !self.try_match(TokenKind::As) { return Ok(None) }
+        let ident = self.expect_ident(
+            ParserError::ExpectedName {
+                target: NameTarget::Import,
+                found: self.safe_peek()
+            }
+        )?;
+        Ok(Some(ident))
+    }
+
+    /// Returns error if import tree continues with `::` after segment
+    /// that must be the last one (eg. `*` or `self`).
+    fn ensure_import_tree_end(&mut self, kind: InvalidImportKind) -> Result<(), ParserError> {
+        if self.check(TokenKind::DColon) {
+            let span_start = self.peek().span;
+            self.advance();
+            let span_end = if self.is_at_end() { span_start } else { self.peek().span };
+            Err(ParserError::InvalidImportTree {
+                kind,
+                span: Span::from_begin_end(span_start, span_end)
+            })?
+        }
+        Ok(())
+    }
+
     /// For import like `hello::world::{lorem, ipsum}` prefix path would be the hello::world part.
     pub fn parse_import_prefix_path(&mut self) -> Result<Path, ParserError> {
-        let span_start = self.previous().span;
+        let span_start = self.safe_peek().span;
         let mut path_segments = Vec::new();
 
         while self.check(TokenKind::Ident) {
@@ -617,8 +1135,9 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             // Check for double colon
             if !self.try_match(TokenKind::DColon) { break; }
         }
-        let span_end = self.previous().span;
-        let span = Span::from_begin_end(span_start, span_end);
+        let span = if path_segments.is_empty() {
+            Span::new(span_start.source, span_start.start, span_start.start)
+        } else { Span::from_begin_end(span_start, self.previous().span) };
 
         Ok(Path {
             segments: path_segments, 
@@ -628,6 +1147,31 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
 
     pub fn parse_fn(&mut self) -> Result<Item, ParserError> {
         let span_start = self.previous().span;
+        self.parse_fn_with_extern(Extern::None, span_start)
+    }
+
+    /// Extern function declaration like `extern fn hello();` or `extern "C" fn hello();`
+    pub fn parse_extern_fn(&mut self) -> Result<Item, ParserError> {
+        let span_start = self.previous().span;
+
+        // Optional ABI string, only "C" is supported for now
+        let ext = if self.check(TokenKind::Literal { kind: LiteralKind::Str }) {
+            let lit = self.parse_lit()?;
+            let abi_span = self.previous().span;
+            // Symbol of the literal keeps its quotes
+            let quoted = self.symbol_storage.text_of(lit.symbol).unwrap_or_default();
+            let abi = quoted[1..quoted.len() - 1].to_string();
+            if abi != "C" {
+                self.errors.push(ParserError::UnknownAbi { span: abi_span });
+            }
+            Extern::Explicit(self.symbol_storage.get_or_register(&abi), abi_span)
+        } else { Extern::Implicit(span_start) };
+
+        self.consume(TokenKind::Fn)?;
+        self.parse_fn_with_extern(ext, span_start)
+    }
+
+    fn parse_fn_with_extern(&mut self, ext: Extern, span_start: Span) -> Result<Item, ParserError> {
         // get function name
         let ident = self.expect_ident(
             ParserError::ExpectedName {
@@ -656,9 +1200,14 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
 
         let sig_span_end = self.previous().span;
 
-        // Body
-        // Temporary
-        let block = self.parse_block()?;
+        // Body, extern functions are declared without one
+        let body = if ext.is_extern() {
+            if !self.try_match(TokenKind::Semi) {
+                let body = self.parse_block()?;
+                self.errors.push(ParserError::ExternFnBody { span: body.span });
+            }
+            None
+        } else { Some(Box::new(self.parse_block()?)) };
 
         // Return
         Ok(Item {
@@ -671,11 +1220,12 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                     signature: FnSignature {
                         is_const: false,
                         is_async: false,
+                        ext,
                         inputs: args,
                         output: ret_ty,
                         span: Span::from_begin_end(span_start, sig_span_end)
                     },
-                    body: Some(Box::new(block))
+                    body
                 }
             ),
             ident,
@@ -684,8 +1234,8 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
 
     pub fn parse_fn_arg(&mut self) -> Result<FnInput, ParserError> {
+        let span_start = self.safe_peek().span;
         let attributes = self.parse_attributes()?;
-        let span_start = self.previous().span;
 
         // Special case: self
         if self.try_match(TokenKind::LSelf) {
@@ -723,8 +1273,8 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
 
     pub fn parse_pattern(&mut self) -> Result<Pat, ParserError> {
         // TODO: Add more patterns
-        if let Ok(token) = self.consume(TokenKind::Ident) {
-            let token = token.clone();
+        if self.try_match(TokenKind::Ident) {
+            let token = self.previous().clone();
             return Ok(
                 Pat {
                     id: self.node_id(),
@@ -733,11 +1283,11 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                 }
             )
         }
-        unimplemented!("Only ident patterns are available")
+        Err(ParserError::ExpectedPattern { found: self.safe_peek() })
     }
 
     pub fn parse_ty(&mut self) -> Result<Ty, ParserError> {
-        let span_start = self.previous().span;
+        let span_start = self.safe_peek().span;
         // Void / Tuple
         if self.try_match(TokenKind::LeftParen) {
             if self.try_match(TokenKind::RightParen) {
@@ -747,7 +1297,10 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                     span: Span::from_begin_end(span_start, self.previous().span)
                 });
             }
-            unimplemented!("Tuple type is not yet supported")
+            Err(ParserError::UnsupportedSyntax {
+                feature: "tuple types",
+                span: self.previous().span
+            })?
         }
 
         // Never type
@@ -799,6 +1352,10 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
     
     pub fn parse_block(&mut self) -> Result<Block, ParserError> {
+        self.nested(Self::parse_block_inner)
+    }
+
+    fn parse_block_inner(&mut self) -> Result<Block, ParserError> {
         let span_start = self.safe_peek().span;
 
         self.consume(TokenKind::LeftBrace)?;
@@ -808,7 +1365,12 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             if self.try_match(TokenKind::RightBrace) {
                 break;
             }
-            // TODO: Check is at end
+            if self.is_at_end() {
+                Err(ParserError::ExpectedToken {
+                    expected: TokenKind::RightBrace,
+                    found: self.eof_token()
+                })?
+            }
 
             let stmt = self.parse_stmt()?;
             stmts.push(stmt);
@@ -822,7 +1384,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
 
     pub fn parse_stmt(&mut self) -> Result<Stmt, ParserError> {
-        let span_start = self.previous().span;
+        let span_start = self.safe_peek().span;
         // First: Let binding
         let attrib = self.parse_attributes();
         if self.try_match(TokenKind::Let) {
@@ -832,15 +1394,18 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                 lb.attribs = attrib;
             }
             
-            self.consume(TokenKind::Semi)?;
+            self.expect_semi();
 
-            return Ok(Stmt {
+            Ok(Stmt {
                 id: self.node_id(),
                 kind,
                 span: Span::from_begin_end(span_start, self.previous().span)
             })   
         } else {
-            match self.parse_item() {
+            self.block_items += 1;
+            let item = self.parse_item();
+            self.block_items -= 1;
+            match item {
                 Ok(item) => {
                     let kind = StmtKind::Item(Box::new(item));
                     return Ok(Stmt {
@@ -857,8 +1422,12 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             let expr = self.parse_expr()?;
             let kind = if self.try_match(TokenKind::Semi) {
                 StmtKind::Expr(Box::new(expr))
-            } else {
+            } else if self.check(TokenKind::RightBrace) || expr.kind.is_block_like() {
                 StmtKind::ExprNS(Box::new(expr))
+            } else {
+                // Expression without semicolon can only be at the end of the block
+                self.expect_semi();
+                StmtKind::Expr(Box::new(expr))
             };
 
             Ok(Stmt {
@@ -874,11 +1443,15 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         let pat = self.parse_pattern()?;
         let ty = if self.try_match(TokenKind::Colon) {
             self.parse_ty()?
-        } else { Ty {
-            id: self.node_id(),
-            kind: TyKind::Infer,
-            span: self.previous().span
-        } };
+        } else {
+            // Type is not written anywhere, so span is empty
+            let prev = self.previous().span;
+            Ty {
+                id: self.node_id(),
+                kind: TyKind::Infer,
+                span: Span::new(prev.source, prev.end, prev.end)
+            }
+        };
 
         let kind = if self.try_match(TokenKind::Equal) {
             LetBindingKind::Init(Box::new(self.parse_expr()?))
@@ -897,7 +1470,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
 
     pub fn parse_expr(&mut self) -> Result<Expr, ParserError> {
-        self.expr_block()
+        self.nested(Self::expr_block)
     }
 
     fn expr_block(&mut self) -> Result<Expr, ParserError> {
@@ -999,7 +1572,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             };
             return Ok(Expr {
                 id: self.node_id(),
-                kind: ExprKind::Break(expr.map(|e| Box::new(e))),
+                kind: ExprKind::Break(expr.map(Box::new)),
                 span: Span::from_begin_end(span_start, self.previous().span),
                 attrs: Attributes::empty()
             })
@@ -1026,8 +1599,20 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         if self.try_match(TokenKind::Bang) || self.try_match(TokenKind::Minus) {
             let token_span = self.previous().span;
             let op_kind = self.previous().kind;
-            let right = self.expr_unary()?;
+            // Only a literal right after the minus is folded, not `-(-4)` or `- -4`
+            let literal_operand = matches!(self.safe_peek().kind, TokenKind::Literal { .. });
+            let right = self.nested(Self::expr_unary)?;
             let right_span = right.span;
+
+            // Negative numeric literals are folded into a single literal
+            if op_kind == TokenKind::Minus && literal_operand {
+                if let ExprKind::Literal(ref lit) = right.kind {
+                    if let LitKind::Integer | LitKind::Float = lit.kind {
+                        return Ok(self.negate_lit(lit, Span::from_begin_end(token_span, right_span)));
+                    }
+                }
+            }
+
             return Ok(Expr {
                 id: self.node_id(),
                 kind: ExprKind::Unary(
@@ -1035,7 +1620,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                         TokenKind::Bang => UnOpKind::Not,
                         TokenKind::Minus => UnOpKind::Neg,
                         _ => unreachable!()
-                    },
+                    }.spanned(token_span),
                     Box::new(right)
                 ),
                 span: Span::from_begin_end(token_span, right_span),
@@ -1046,6 +1631,24 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         self.expr_call()
     }
 
+    /// Creates negated numeric literal expression, so `-4` becomes `Lit(-4)`
+    /// instead of `Unary(Neg, Lit(4))`.
+    fn negate_lit(&mut self, lit: &Lit, span: Span) -> Expr {
+        let negated = format!("-{}", self.symbol_storage.text_of(lit.symbol).unwrap());
+
+        Expr {
+            id: self.node_id(),
+            kind: ExprKind::Literal(Lit {
+                id: self.node_id(),
+                kind: lit.kind.clone(),
+                symbol: self.symbol_storage.get_or_register(&negated),
+                suffix: lit.suffix
+            }),
+            span,
+            attrs: Attributes::empty()
+        }
+    }
+
     fn expr_call(&mut self) -> Result<Expr, ParserError> {
         let expr = self.expr_assignment()?;
 
@@ -1061,11 +1664,13 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                     break;
                 }
             }
+            let args_end = self.previous().span;
+            let span = Span::from_begin_end(expr.span, args_end);
 
             return Ok(Expr {
                 id: self.node_id(),
-                kind: ExprKind::Call(Box::new(expr), args),
-                span: Span::from_begin_end(args_start, self.previous().span),
+                kind: ExprKind::Call(Box::new(expr), args, DelimSpan::new(args_start, args_end)),
+                span,
                 attrs: Attributes::empty()
             })
         }
@@ -1077,12 +1682,13 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         let lvalue = self.expr_field_access()?;
 
         if self.try_match(TokenKind::Equal) {
+            let eq_span = self.previous().span;
             let rvalue = self.parse_expr()?;
             let span = Span::from_begin_end(lvalue.span, rvalue.span);
 
            return Ok(Expr {
                 id: self.node_id(),
-                kind: ExprKind::Assign(Box::new(lvalue), Box::new(rvalue)),
+                kind: ExprKind::Assign(Box::new(lvalue), Box::new(rvalue), eq_span),
                 span,
                 attrs: Attributes::empty()
             })
@@ -1095,6 +1701,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
         let mut expr = self.expr_struct_lit()?;
 
         while self.try_match(TokenKind::Dot) {
+            let dot_span = self.previous().span;
             let ident = self.expect_ident(
                 ParserError::ExpectedName {
                     target: NameTarget::Field,
@@ -1102,11 +1709,11 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                 }
             )?;
 
-            let ident_span = ident.span;
+            let span = Span::from_begin_end(expr.span, ident.span);
             expr = Expr {
                 id: self.node_id(),
-                kind: ExprKind::Field(Box::new(expr), ident),
-                span: ident_span,
+                kind: ExprKind::Field(Box::new(expr), ident, dot_span),
+                span,
                 attrs: Attributes::empty()
             }
         }
@@ -1116,7 +1723,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
 
     fn expr_struct_lit(&mut self) -> Result<Expr, ParserError> {
         let span_start = self.safe_peek().span;
-        let path = self.expr_primary()?;
+        let mut path = self.expr_primary()?;
 
         if let ExprKind::Path(..) = path.kind {
             if self.try_match(TokenKind::LeftBrace) {
@@ -1158,7 +1765,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
                 return Ok(Expr {
                     id: self.node_id(),
                     kind: ExprKind::StructLit(Box::new(StructLiteral {
-                        path: match path.kind {
+                        path: match path.take_kind() {
                             ExprKind::Path(path) => path,
                             _ => panic!() // will this ever happen? 
                         },
@@ -1174,7 +1781,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
     }
 
     fn expr_primary(&mut self) -> Result<Expr, ParserError> {
-        let span_start = self.previous().span;
+        let span_start = self.safe_peek().span;
 
         // Grouping
         if self.try_match(TokenKind::LeftParen) {
@@ -1188,7 +1795,9 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             ExprKind::Path(path)
         } else if let Ok(lit) = self.parse_lit() {
             ExprKind::Literal(lit)
-        } else { unimplemented!("Only path and literal expressions are implemented, found token: {:?}", self.safe_peek()) };
+        } else {
+            Err(ParserError::ExpectedExpression { found: self.safe_peek() })?
+        };
 
         Ok(Expr {
             id: self.node_id(),
@@ -1200,7 +1809,7 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
 
     /// Try to parse literal
     pub fn parse_lit(&mut self) -> Result<Lit, ParserError> {
-        if let TokenKind::Literal { .. } = self.peek().kind {
+        if let TokenKind::Literal { .. } = self.safe_peek().kind {
             let token = self.advance();
             let TokenKind::Literal { kind } = token.kind else { unreachable!() };
             
@@ -1213,12 +1822,21 @@ impl<'pkg, 'a> Parser<'pkg, 'a> {
             };
 
             let t_span = token.span; // For borrow checker satisfaction
+            let id = self.node_id();
+            let text = self.source_file.get_span(&t_span);
+            // Only numbers have suffixes, which start with the first letter
+            let (text, suffix) = match lit_kind {
+                LitKind::Integer | LitKind::Float => {
+                    let end = text.find(|c: char| c.is_alphabetic() || c == '_').unwrap_or(text.len());
+                    text.split_at(end)
+                },
+                _ => (text, "")
+            };
             Ok(Lit {
-                id: self.node_id(),
+                id,
                 kind: lit_kind,
-                symbol: self.symbol_storage.get_or_register(
-                    &self.source_file.get_span(&t_span)
-                )
+                symbol: self.symbol_storage.get_or_register(text),
+                suffix: (!suffix.is_empty()).then(|| self.symbol_storage.get_or_register(suffix))
             })
         } else {
             Err(ParserError::ExpectedToken { 
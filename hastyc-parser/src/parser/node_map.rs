@@ -0,0 +1,234 @@
+use hastyc_common::{identifiers::{ASTNodeID, SourceFileID}, span::Span};
+
+use super::{Block, DataVariant, Expr, ExprKind, FnRetTy, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBindingKind, RestExpr, Stmt, StmtKind, Ty};
+
+/// Sorted table of non-overlapping source segments, where each segment
+/// is mapped to the innermost AST node that contains it. This allows
+/// finding node at the given position in O(log n) without walking the AST.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeSpanTable {
+    segments: Vec<(Span, ASTNodeID)>
+}
+
+impl NodeSpanTable {
+    /// Build table from all nodes in the item stream.
+    pub fn build(items: &ItemStream) -> Self {
+        let mut collector = SpanCollector { entries: Vec::new() };
+        collector.item_stream(items);
+
+        // Parents come before their children, as they start earlier or
+        // at the same position, but end later.
+        let mut entries = collector.entries;
+        entries.sort_by_key(|(span, _)| (span.source.0, span.start, std::cmp::Reverse(span.end)));
+
+        let mut segments = Vec::new();
+        let mut stack: Vec<(Span, ASTNodeID)> = Vec::new();
+        let mut cursor = 0;
+
+        for (span, id) in entries {
+            // Close nodes that end before this one starts, or that are in another file
+            while let Some(&(top, top_id)) = stack.last() {
+                if top.source == span.source && top.end > span.start { break }
+                Self::emit(&mut segments, top, cursor, top.end, top_id);
+                cursor = cursor.max(top.end);
+                stack.pop();
+            }
+            if stack.is_empty() { cursor = span.start }
+
+            // Part of the parent before this node
+            if let Some(&(top, top_id)) = stack.last() {
+                Self::emit(&mut segments, top, cursor, span.start, top_id);
+            }
+            cursor = cursor.max(span.start);
+
+            // Children are clamped to their parent, in case spans are not perfectly nested
+            let end = match stack.last() {
+                Some((top, _)) => span.end.min(top.end),
+                None => span.end
+            };
+            stack.push((Span::new(span.source, span.start, end), id));
+        }
+        while let Some((top, top_id)) = stack.pop() {
+            Self::emit(&mut segments, top, cursor, top.end, top_id);
+            cursor = cursor.max(top.end);
+        }
+
+        Self { segments }
+    }
+
+    fn emit(segments: &mut Vec<(Span, ASTNodeID)>, of: Span, start: u32, end: u32, id: ASTNodeID) {
+        if start < end {
+            segments.push((Span::new(of.source, start, end), id));
+        }
+    }
+
+    /// Find innermost node that contains the given position.
+    pub fn node_at(&self, source: SourceFileID, offset: u32) -> Option<ASTNodeID> {
+        let idx = self.segments.partition_point(|(span, _)| {
+            (span.source.0, span.start) <= (source.0, offset)
+        });
+        let (span, id) = self.segments.get(idx.checked_sub(1)?)?;
+        if span.source == source && offset < span.end {
+            Some(*id)
+        } else { None }
+    }
+
+    /// Number of segments in the table.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+/// Collects spans with node ids from the whole AST.
+struct SpanCollector {
+    entries: Vec<(Span, ASTNodeID)>
+}
+
+impl SpanCollector {
+    fn add(&mut self, span: Span, id: ASTNodeID) {
+        self.entries.push((span, id));
+    }
+
+    fn item_stream(&mut self, items: &ItemStream) {
+        for item in items.items.iter() {
+            self.item(item);
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        self.add(item.span, item.id);
+        match item.kind {
+            ItemKind::Module(ref items) => self.item_stream(items),
+            ItemKind::Import(_, ref tree) => self.import_tree(tree),
+            ItemKind::Fn(ref function) => {
+                for input in function.signature.inputs.iter() {
+                    self.add(input.span, input.id);
+                    self.add(input.pat.span, input.pat.id);
+                    self.ty(&input.ty);
+                }
+                if let FnRetTy::Ty(ref ty) = function.signature.output {
+                    self.ty(ty);
+                }
+                if let Some(ref body) = function.body {
+                    self.block(body);
+                }
+            },
+            ItemKind::Struct(ref data) => self.data_variant(data),
+            ItemKind::Enum(ref def) => {
+                for variant in def.variants.iter() {
+                    self.add(variant.span, variant.id);
+                    self.data_variant(&variant.data);
+                }
+            }
+        }
+    }
+
+    fn import_tree(&mut self, tree: &ImportTree) {
+        if let ImportTreeKind::Nested(ref subtrees) = tree.kind {
+            for (subtree, id) in subtrees.iter() {
+                self.add(subtree.span, *id);
+                self.import_tree(subtree);
+            }
+        }
+    }
+
+    fn data_variant(&mut self, data: &DataVariant) {
+        match data {
+            DataVariant::Unit => {},
+            DataVariant::Struct { ref fields } | DataVariant::Tuple { ref fields } => {
+                for field in fields.iter() {
+                    self.add(field.span, field.id);
+                    self.ty(&field.ty);
+                }
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &Ty) {
+        self.add(ty.span, ty.id);
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.add(block.span, block.id);
+        for stmt in block.stmts.stmts.iter() {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.add(stmt.span, stmt.id);
+        match stmt.kind {
+            StmtKind::LetBinding(ref binding) => {
+                self.add(binding.span, binding.id);
+                self.add(binding.pat.span, binding.pat.id);
+                if let Some(ref ty) = binding.ty {
+                    self.ty(ty);
+                }
+                if let LetBindingKind::Init(ref expr) = binding.kind {
+                    self.expr(expr);
+                }
+            },
+            StmtKind::Item(ref item) => self.item(item),
+            StmtKind::Expr(ref expr) | StmtKind::ExprNS(ref expr) => self.expr(expr)
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.add(expr.span, expr.id);
+        match expr.kind {
+            ExprKind::Path(_) | ExprKind::Literal(_) | ExprKind::Continue | ExprKind::Err => {},
+            ExprKind::Field(ref target, _, _) => self.expr(target),
+            ExprKind::Assign(ref target, ref value, _) => {
+                self.expr(target);
+                self.expr(value);
+            },
+            ExprKind::Unary(_, ref expr) => self.expr(expr),
+            ExprKind::Binary(_, ref lhs, ref rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            },
+            ExprKind::Call(ref target, ref args, _) => {
+                self.expr(target);
+                for arg in args.iter() {
+                    self.expr(arg);
+                }
+            },
+            ExprKind::If(ref condition, ref block, ref else_expr) => {
+                self.expr(condition);
+                self.block(block);
+                if let Some(ref else_expr) = else_expr {
+                    self.expr(else_expr);
+                }
+            },
+            ExprKind::Block(ref block) | ExprKind::Loop(ref block) => self.block(block),
+            ExprKind::While(ref condition, ref block) => {
+                self.expr(condition);
+                self.block(block);
+            },
+            ExprKind::For(ref pat, ref iter, ref block) => {
+                self.add(pat.span, pat.id);
+                self.expr(iter);
+                self.block(block);
+            },
+            ExprKind::Break(ref value) => {
+                if let Some(ref value) = value {
+                    self.expr(value);
+                }
+            },
+            ExprKind::StructLit(ref lit) => {
+                for field in lit.fields.iter() {
+                    self.add(field.span, field.id);
+                    self.expr(&field.expr);
+                }
+                if let RestExpr::Valued(ref rest) = lit.rest {
+                    self.expr(rest);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use hastyc_common::{identifiers::ASTNodeID, impl_stable_hash, impl_stable_hash_unit_enum, stable_hash::{Fingerprint, StableHash, StableHasher}};
+
+use super::{Attribute, AttributeKind, Attributes, BinOpKind, Block, DataVariant, EnumDef, EnumVariant, Expr, ExprKind, Extern, FieldDef, FieldLitExpr, FnInput, FnRetTy, FnSignature, Function, Generics, ImportKind, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, Lit, LitKind, Package, Pat, PatKind, RestExpr, Stmt, StmtKind, StmtStream, StructLiteral, Ty, TyKind, UnOpKind, Visibility};
+
+/// Hash variant index followed by its fields.
+macro_rules! variant {
+    ($hasher:ident, $idx:literal $(, $field:expr)*) => {{
+        $hasher.write_u32($idx);
+        $($field.stable_hash($hasher);)*
+    }};
+}
+
+impl Package {
+    /// Fingerprints of all items in the package, including items nested in
+    /// modules. Each fingerprint covers the whole item, so it changes if
+    /// anything inside of it changes, but not when the item is only moved.
+    pub fn item_fingerprints(&self) -> Vec<(ASTNodeID, Fingerprint)> {
+        fn collect(stream: &ItemStream, result: &mut Vec<(ASTNodeID, Fingerprint)>) {
+            for item in stream.items.iter() {
+                result.push((item.id, item.fingerprint()));
+                if let ItemKind::Module(ref items) = item.kind {
+                    collect(items, result);
+                }
+            }
+        }
+
+        let mut result = Vec::new();
+        collect(&self.items, &mut result);
+        result
+    }
+}
+
+impl_stable_hash!(Package { attrs, items });
+impl_stable_hash!(Attributes { attributes });
+impl_stable_hash!(Attribute { ident, kind });
+impl_stable_hash!(ItemStream { items });
+impl_stable_hash!(Item { attrs, visibility, kind, ident });
+impl_stable_hash!(ImportTree { prefix, kind });
+impl_stable_hash!(Function { generics, signature, body });
+impl_stable_hash!(Block { stmts });
+impl_stable_hash!(Generics {});
+impl_stable_hash!(FnSignature { is_const, is_async, ext, inputs, output });
+impl_stable_hash!(FnInput { attributes, pat, ty });
+impl_stable_hash!(Ty { kind });
+impl_stable_hash!(Pat { kind });
+impl_stable_hash!(FieldDef { attrs, vis, ident, ty });
+impl_stable_hash!(EnumDef { variants });
+impl_stable_hash!(EnumVariant { attrs, vis, ident, data });
+impl_stable_hash!(StmtStream { stmts });
+impl_stable_hash!(Stmt { kind });
+impl_stable_hash!(Expr { kind, attrs });
+impl_stable_hash!(LetBinding { pat, ty, kind, attribs });
+impl_stable_hash!(Lit { kind, symbol, suffix });
+impl_stable_hash!(StructLiteral { path, fields, rest });
+impl_stable_hash!(FieldLitExpr { attrs, ident, expr });
+
+impl_stable_hash_unit_enum!(Visibility, ImportKind, UnOpKind, BinOpKind, LitKind);
+
+impl StableHash for AttributeKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::FlagAttribute => variant!(hasher, 0),
+            Self::ListAttribute(args) => variant!(hasher, 1, args)
+        }
+    }
+}
+
+impl StableHash for ItemKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Module(items) => variant!(hasher, 0, items),
+            Self::Import(kind, tree) => variant!(hasher, 1, kind, tree),
+            Self::Fn(function) => variant!(hasher, 2, function),
+            Self::Struct(data) => variant!(hasher, 3, data),
+            Self::Enum(def) => variant!(hasher, 4, def)
+        }
+    }
+}
+
+impl StableHash for ImportTreeKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Simple(name, rename) => variant!(hasher, 0, name, rename),
+            Self::SelfImport(rename) => variant!(hasher, 1, rename),
+            Self::Nested(trees) => variant!(hasher, 2, trees),
+            Self::Glob => variant!(hasher, 3)
+        }
+    }
+}
+
+impl StableHash for Extern {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::None => variant!(hasher, 0),
+            Self::Implicit(_) => variant!(hasher, 1),
+            Self::Explicit(abi, _) => variant!(hasher, 2, abi)
+        }
+    }
+}
+
+impl StableHash for FnRetTy {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Default => variant!(hasher, 0),
+            Self::Ty(ty) => variant!(hasher, 1, ty)
+        }
+    }
+}
+
+impl StableHash for TyKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::SelfTy => variant!(hasher, 0),
+            Self::Path(path) => variant!(hasher, 1, path),
+            Self::Void => variant!(hasher, 2),
+            Self::Never => variant!(hasher, 3),
+            Self::Infer => variant!(hasher, 4)
+        }
+    }
+}
+
+impl StableHash for PatKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::SelfPat => variant!(hasher, 0),
+            Self::Ident(ident) => variant!(hasher, 1, ident)
+        }
+    }
+}
+
+impl StableHash for DataVariant {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Struct { fields } => variant!(hasher, 0, fields),
+            Self::Tuple { fields } => variant!(hasher, 1, fields),
+            Self::Unit => variant!(hasher, 2)
+        }
+    }
+}
+
+impl StableHash for StmtKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::LetBinding(binding) => variant!(hasher, 0, binding),
+            Self::Item(item) => variant!(hasher, 1, item),
+            Self::Expr(expr) => variant!(hasher, 2, expr),
+            Self::ExprNS(expr) => variant!(hasher, 3, expr)
+        }
+    }
+}
+
+impl StableHash for ExprKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Path(path) => variant!(hasher, 0, path),
+            Self::Literal(lit) => variant!(hasher, 1, lit),
+            Self::Field(target, field, _) => variant!(hasher, 2, target, field),
+            Self::Assign(target, value, _) => variant!(hasher, 3, target, value),
+            Self::Unary(op, expr) => variant!(hasher, 4, op, expr),
+            Self::Binary(op, lhs, rhs) => variant!(hasher, 5, op, lhs, rhs),
+            Self::Call(target, args, _) => variant!(hasher, 6, target, args),
+            Self::If(condition, block, else_expr) => variant!(hasher, 7, condition, block, else_expr),
+            Self::Block(block) => variant!(hasher, 8, block),
+            Self::Loop(block) => variant!(hasher, 9, block),
+            Self::While(condition, block) => variant!(hasher, 10, condition, block),
+            Self::For(pat, iter, block) => variant!(hasher, 11, pat, iter, block),
+            Self::Break(value) => variant!(hasher, 12, value),
+            Self::Continue => variant!(hasher, 13),
+            Self::StructLit(lit) => variant!(hasher, 14, lit),
+            Self::Err => variant!(hasher, 15)
+        }
+    }
+}
+
+impl StableHash for LetBindingKind {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Decl => variant!(hasher, 0),
+            Self::Init(expr) => variant!(hasher, 1, expr)
+        }
+    }
+}
+
+impl StableHash for RestExpr {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Self::Valued(expr) => variant!(hasher, 0, expr),
+            Self::Rest(_) => variant!(hasher, 1),
+            Self::None => variant!(hasher, 2)
+        }
+    }
+}
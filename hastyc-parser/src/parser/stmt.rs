@@ -1,9 +1,12 @@
-use hastyc_common::{identifiers::{ASTNodeID, Symbol, Ident}, span::Span, path::Path};
+use hastyc_common::{identifiers::{ASTNodeID, Symbol, Ident}, impl_has_span, span::{HasSpan, Span}, path::Path};
+
+pub use hastyc_common::span::{MakeSpanned, Spanned};
 
 use super::{Attributes, Item, Pat, Ty, Block};
 
 /// Stream of statements. This is like a part of code.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StmtStream {
     pub stmts: Vec<Stmt>
 }
@@ -25,6 +28,7 @@ impl StmtStream {
 /// One single statement, this can be variable declaration,
 /// function call, some conditional flow or things like that.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stmt {
     pub id: ASTNodeID,
     pub kind: StmtKind,
@@ -33,6 +37,7 @@ pub struct Stmt {
 
 /// Expression is like a statement with return value.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expr {
     pub id: ASTNodeID,
     pub kind: ExprKind,
@@ -40,8 +45,17 @@ pub struct Expr {
     pub attrs: Attributes,
 }
 
+impl Expr {
+    /// Move the kind out, leaving `ExprKind::Err` in its place. Expressions
+    /// implement `Drop`, so this is how their kind is matched by value.
+    pub fn take_kind(&mut self) -> ExprKind {
+        std::mem::replace(&mut self.kind, ExprKind::Err)
+    }
+}
+
 /// Kind of statement
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StmtKind {
     /// Let statement like `let _: _ = _;`.
     LetBinding(Box<LetBinding>),
@@ -54,15 +68,18 @@ pub enum StmtKind {
 
 /// Kind of expression
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExprKind {
     Path(Path),
     Literal(Lit),
-    /// Field access like `value.field`
-    Field(Box<Expr>, Ident),
-    Assign(Box<Expr>, Box<Expr>),
-    Unary(UnOpKind, Box<Expr>),
+    /// Field access like `value.field`, span is the span of `.`
+    Field(Box<Expr>, Ident, Span),
+    /// Assignment like `a = b`, span is the span of `=`
+    Assign(Box<Expr>, Box<Expr>, Span),
+    Unary(UnOp, Box<Expr>),
     Binary(BinOp, Box<Expr>, Box<Expr>),
-    Call(Box<Expr>, Vec<Box<Expr>>),
+    /// Call like `target(args)`
+    Call(Box<Expr>, Vec<Box<Expr>>, DelimSpan),
     /// if expr {block} else {block}
     If(Box<Expr>, Box<Block>, Option<Box<Expr>>),
     Block(Box<Block>),
@@ -72,10 +89,62 @@ pub enum ExprKind {
     For(Pat, Box<Expr>, Box<Block>),
     Break(Option<Box<Expr>>),
     Continue,
-    StructLit(Box<StructLiteral>)
+    StructLit(Box<StructLiteral>),
+    /// Expression which was already reported as an error, or was moved
+    /// out with `Expr::take_kind`.
+    Err
+}
+
+impl ExprKind {
+    /// Whether expression ends with a block, these can be used
+    /// as statements without a semicolon.
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Self::If(..) | Self::Block(..) | Self::Loop(..) |
+            Self::While(..) | Self::For(..)
+        )
+    }
+
+    /// Move out expressions which are direct operands of this one,
+    /// leaving everything else to be dropped with it.
+    fn take_operands(self, operands: &mut Vec<Expr>) {
+        match self {
+            Self::Field(target, ..) | Self::Unary(_, target) => operands.push(*target),
+            Self::Assign(lhs, rhs, _) | Self::Binary(_, lhs, rhs) => operands.extend([*lhs, *rhs]),
+            Self::Call(target, args, _) => {
+                operands.push(*target);
+                operands.extend(args.into_iter().map(|arg| *arg));
+            },
+            Self::If(condition, _, else_expr) => {
+                operands.push(*condition);
+                operands.extend(else_expr.map(|expr| *expr));
+            },
+            Self::While(condition, _) | Self::For(_, condition, _) => operands.push(*condition),
+            Self::Break(value) => operands.extend(value.map(|value| *value)),
+            Self::Path(_) | Self::Literal(_) | Self::Block(_) | Self::Loop(_) |
+            Self::Continue | Self::StructLit(_) | Self::Err => {}
+        }
+    }
 }
 
+/// Chains of operators like `a + a + ... + a` are parsed in a loop, so
+/// unlike blocks they aren't limited in depth and the derived recursive
+/// drop could overflow the stack. Operands are dropped from a worklist
+/// instead, blocks are still dropped recursively.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut operands = Vec::new();
+        self.take_kind().take_operands(&mut operands);
+        while let Some(mut operand) = operands.pop() {
+            operand.take_kind().take_operands(&mut operands);
+        }
+    }
+}
+
+pub type UnOp = Spanned<UnOpKind>;
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOpKind {
     Neg,
     Not
@@ -83,6 +152,7 @@ pub enum UnOpKind {
 
 pub type BinOp = Spanned<BinOpKind>;
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinOpKind {
     Add, Sub, Mul,
     Div, Rem, And,
@@ -92,6 +162,7 @@ pub enum BinOpKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LetBinding {
     pub id: ASTNodeID,
     pub pat: Pat,
@@ -102,6 +173,7 @@ pub struct LetBinding {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LetBindingKind {
     /// Just variable declaration `let variable;`
     Decl,
@@ -110,13 +182,18 @@ pub enum LetBindingKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lit {
     pub id: ASTNodeID,
     pub kind: LitKind,
-    pub symbol: Symbol
+    /// Text of the literal, without the suffix.
+    pub symbol: Symbol,
+    /// Type written after a number, like `u8` in `300u8`.
+    pub suffix: Option<Symbol>
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LitKind {
     Bool,
     Char,
@@ -126,6 +203,7 @@ pub enum LitKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructLiteral {
     pub path: Path,
     pub fields: Vec<FieldLitExpr>,
@@ -133,6 +211,7 @@ pub struct StructLiteral {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldLitExpr {
     pub attrs: Attributes,
     pub id: ASTNodeID,
@@ -142,31 +221,40 @@ pub struct FieldLitExpr {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RestExpr {
     Valued(Box<Expr>),
     Rest(Span),
     None
 }
 
-#[derive(Debug, Clone)]
-pub struct Spanned<Kind> {
-    pub kind: Kind,
-    pub span: Span
+/// Spans of opening and closing delimiters, for example
+/// `(` and `)` of a function call.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DelimSpan {
+    pub open: Span,
+    pub close: Span
 }
 
-impl<Kind> Spanned<Kind> {
-    pub fn new(kind: Kind, span: Span) -> Self {
+impl DelimSpan {
+    pub fn new(open: Span, close: Span) -> Self {
         Self {
-            kind,
-            span
+            open,
+            close
         }
     }
-}
 
-pub trait MakeSpanned where Self: Sized {
-    fn spanned(self, span: Span) -> Spanned<Self> {
-        Spanned { kind: self, span: span }
+    /// Span covering both delimiters and everything in between.
+    pub fn entire(&self) -> Span {
+        Span::from_begin_end(self.open, self.close)
     }
 }
 
-impl<Kind> MakeSpanned for Kind {}
\ No newline at end of file
+impl_has_span!(Stmt, Expr, LetBinding, FieldLitExpr);
+
+impl HasSpan for DelimSpan {
+    fn span(&self) -> Span {
+        self.entire()
+    }
+}
\ No newline at end of file
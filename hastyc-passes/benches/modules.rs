@@ -0,0 +1,59 @@
+//! Name resolution of a synthetic package with many modules, run with
+//! `cargo bench -p hastyc-passes --bench modules`, and once more with
+//! `--features parallel` to compare.
+
+use std::{fmt::Write, time::Instant};
+
+use hastyc_common::{identifiers::{PkgID, SourceFileID}, session::Session, source::SourceFile};
+use hastyc_parser::{lexer::Lexer, parser::Parser};
+use hastyc_passes::passes::{name_resolve::NameResolvePass, ASTPass, QueryContext};
+
+const MODULES: usize = 500;
+const FNS_PER_MODULE: usize = 20;
+const RUNS: usize = 10;
+
+/// Modules with functions that call each other, and a function
+/// imported from a nested module.
+fn synthetic_package() -> String {
+    let mut code = String::new();
+    for module in 0..MODULES {
+        writeln!(code, "pub module m{} {{", module).unwrap();
+        writeln!(code, "    module inner {{ pub fn g(a: i32) -> i32 {{ a }} }}").unwrap();
+        writeln!(code, "    import inner::g;").unwrap();
+        for function in 0..FNS_PER_MODULE {
+            writeln!(code, "    pub fn f{}(a: i32, b: i32) -> i32 {{", function).unwrap();
+            writeln!(code, "        let c = a + b;").unwrap();
+            writeln!(code, "        let d = g(c) * a - b;").unwrap();
+            if function > 0 {
+                writeln!(code, "        f{}(c, d);", function - 1).unwrap();
+            }
+            writeln!(code, "        d").unwrap();
+            writeln!(code, "    }}").unwrap();
+        }
+        writeln!(code, "}}").unwrap();
+    }
+    code
+}
+
+fn main() {
+    let code = synthetic_package();
+    let source = SourceFile::new_raw(code, PkgID::new_unique(), SourceFileID::new_unique());
+    let sess = Session::default();
+    let tokens = Lexer::lex(&sess, &source).expect("synthetic package should lex");
+    let package = Parser::parse_from_root(&sess, &source, &tokens).expect("synthetic package should parse");
+
+    let mut best = None;
+    for _ in 0..RUNS {
+        let mut ctx = QueryContext::for_package(&sess, &package);
+        let start = Instant::now();
+        let Ok(()) = NameResolvePass::new().traverse(&mut ctx);
+        assert!(!sess.has_errors(), "synthetic package should resolve");
+        let elapsed = start.elapsed();
+        best = Some(best.map_or(elapsed, |best: std::time::Duration| best.min(elapsed)));
+    }
+    let mode = if cfg!(feature = "parallel") { "parallel" } else { "sequential" };
+    println!(
+        "name resolution, {} modules with {} functions ({}): {:?}",
+        MODULES, FNS_PER_MODULE, mode, best.unwrap()
+    );
+}
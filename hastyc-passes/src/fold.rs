@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use hastyc_common::path::Path;
+use hastyc_parser::parser::{Block, DataVariant, EnumDef, EnumVariant, Expr, ExprKind, FieldDef, FieldLitExpr, FnInput, FnRetTy, Function, ImportTree, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, Lit, Pat, RestExpr, Stmt, StmtKind, StmtStream, StructLiteral, Ty, TyKind};
+
+/// Rewriter of the AST, which takes nodes by value and returns their
+/// replacements. Like `visit::Visitor`, every method by default rebuilds
+/// the node from its folded children with the matching `walk_*` function,
+/// so a transform only overrides nodes it wants to change.
+///
+/// Folders which create new nodes must give them fresh ids from the
+/// package `IDCounter`, see `passes::transform::MutPassContext`.
+pub trait Folder: Sized {
+    fn fold_item_stream(&mut self, stream: ItemStream) -> ItemStream {
+        walk_item_stream(self, stream)
+    }
+
+    fn fold_item(&mut self, item: Item) -> Item {
+        walk_item(self, item)
+    }
+
+    fn fold_import_tree(&mut self, tree: ImportTree) -> ImportTree {
+        tree
+    }
+
+    fn fold_fn(&mut self, function: Function) -> Function {
+        walk_fn(self, function)
+    }
+
+    fn fold_fn_input(&mut self, input: FnInput) -> FnInput {
+        walk_fn_input(self, input)
+    }
+
+    fn fold_data_variant(&mut self, data: DataVariant) -> DataVariant {
+        walk_data_variant(self, data)
+    }
+
+    fn fold_field_def(&mut self, field: FieldDef) -> FieldDef {
+        walk_field_def(self, field)
+    }
+
+    fn fold_enum_variant(&mut self, variant: EnumVariant) -> EnumVariant {
+        walk_enum_variant(self, variant)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        walk_block(self, block)
+    }
+
+    fn fold_stmt_stream(&mut self, stream: StmtStream) -> StmtStream {
+        walk_stmt_stream(self, stream)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        walk_stmt(self, stmt)
+    }
+
+    fn fold_let_binding(&mut self, binding: LetBinding) -> LetBinding {
+        walk_let_binding(self, binding)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+
+    fn fold_struct_lit(&mut self, lit: StructLiteral) -> StructLiteral {
+        walk_struct_lit(self, lit)
+    }
+
+    fn fold_field_lit(&mut self, field: FieldLitExpr) -> FieldLitExpr {
+        walk_field_lit(self, field)
+    }
+
+    fn fold_ty(&mut self, ty: Ty) -> Ty {
+        walk_ty(self, ty)
+    }
+
+    // Leaf nodes, these are returned unchanged
+
+    fn fold_pat(&mut self, pat: Pat) -> Pat { pat }
+    fn fold_lit(&mut self, lit: Lit) -> Lit { lit }
+    fn fold_path(&mut self, path: Path) -> Path { path }
+}
+
+#[allow(clippy::boxed_local)] // Children are boxed in the AST, so they come boxed
+fn fold_box<T>(value: Box<T>, fold: impl FnOnce(T) -> T) -> Box<T> {
+    Box::new(fold(*value))
+}
+
+fn fold_expr_box<F: Folder>(folder: &mut F, expr: Box<Expr>) -> Box<Expr> {
+    fold_box(expr, |expr| folder.fold_expr(expr))
+}
+
+fn fold_block_box<F: Folder>(folder: &mut F, block: Box<Block>) -> Box<Block> {
+    fold_box(block, |block| folder.fold_block(block))
+}
+
+pub fn walk_item_stream<F: Folder>(folder: &mut F, stream: ItemStream) -> ItemStream {
+    // Item streams may be shared, in which case they are copied
+    let items = Arc::unwrap_or_clone(stream.items);
+    ItemStream::from_items(items.into_iter().map(|item| folder.fold_item(item)).collect())
+}
+
+pub fn walk_item<F: Folder>(folder: &mut F, item: Item) -> Item {
+    let kind = match item.kind {
+        ItemKind::Module(items) => ItemKind::Module(folder.fold_item_stream(items)),
+        ItemKind::Import(kind, tree) => ItemKind::Import(kind, folder.fold_import_tree(tree)),
+        ItemKind::Fn(function) => ItemKind::Fn(folder.fold_fn(function)),
+        ItemKind::Struct(data) => ItemKind::Struct(folder.fold_data_variant(data)),
+        ItemKind::Enum(def) => ItemKind::Enum(EnumDef {
+            variants: def.variants.into_iter().map(|variant| folder.fold_enum_variant(variant)).collect()
+        })
+    };
+    Item { kind, ..item }
+}
+
+pub fn walk_fn<F: Folder>(folder: &mut F, mut function: Function) -> Function {
+    function.signature.inputs = function.signature.inputs.into_iter()
+        .map(|input| folder.fold_fn_input(input))
+        .collect();
+    function.signature.output = match function.signature.output {
+        FnRetTy::Ty(ty) => FnRetTy::Ty(folder.fold_ty(ty)),
+        FnRetTy::Default => FnRetTy::Default
+    };
+    function.body = function.body.map(|body| fold_block_box(folder, body));
+    function
+}
+
+pub fn walk_fn_input<F: Folder>(folder: &mut F, input: FnInput) -> FnInput {
+    FnInput {
+        pat: folder.fold_pat(input.pat),
+        ty: folder.fold_ty(input.ty),
+        ..input
+    }
+}
+
+pub fn walk_data_variant<F: Folder>(folder: &mut F, data: DataVariant) -> DataVariant {
+    match data {
+        DataVariant::Struct { fields } => DataVariant::Struct {
+            fields: fields.into_iter().map(|field| folder.fold_field_def(field)).collect()
+        },
+        DataVariant::Tuple { fields } => DataVariant::Tuple {
+            fields: fields.into_iter().map(|field| folder.fold_field_def(field)).collect()
+        },
+        DataVariant::Unit => DataVariant::Unit
+    }
+}
+
+pub fn walk_field_def<F: Folder>(folder: &mut F, field: FieldDef) -> FieldDef {
+    FieldDef {
+        ty: folder.fold_ty(field.ty),
+        ..field
+    }
+}
+
+pub fn walk_enum_variant<F: Folder>(folder: &mut F, variant: EnumVariant) -> EnumVariant {
+    EnumVariant {
+        data: folder.fold_data_variant(variant.data),
+        ..variant
+    }
+}
+
+pub fn walk_block<F: Folder>(folder: &mut F, block: Block) -> Block {
+    Block {
+        stmts: folder.fold_stmt_stream(block.stmts),
+        ..block
+    }
+}
+
+pub fn walk_stmt_stream<F: Folder>(folder: &mut F, stream: StmtStream) -> StmtStream {
+    StmtStream::from_vec(stream.stmts.into_iter().map(|stmt| folder.fold_stmt(stmt)).collect())
+}
+
+pub fn walk_stmt<F: Folder>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let kind = match stmt.kind {
+        StmtKind::LetBinding(binding) => StmtKind::LetBinding(fold_box(binding, |binding| folder.fold_let_binding(binding))),
+        StmtKind::Item(item) => StmtKind::Item(fold_box(item, |item| folder.fold_item(item))),
+        StmtKind::Expr(expr) => StmtKind::Expr(fold_expr_box(folder, expr)),
+        StmtKind::ExprNS(expr) => StmtKind::ExprNS(fold_expr_box(folder, expr))
+    };
+    Stmt { kind, ..stmt }
+}
+
+pub fn walk_let_binding<F: Folder>(folder: &mut F, binding: LetBinding) -> LetBinding {
+    LetBinding {
+        pat: folder.fold_pat(binding.pat),
+        ty: binding.ty.map(|ty| folder.fold_ty(ty)),
+        kind: match binding.kind {
+            LetBindingKind::Init(expr) => LetBindingKind::Init(fold_expr_box(folder, expr)),
+            LetBindingKind::Decl => LetBindingKind::Decl
+        },
+        ..binding
+    }
+}
+
+pub fn walk_expr<F: Folder>(folder: &mut F, mut expr: Expr) -> Expr {
+    expr.kind = match expr.take_kind() {
+        ExprKind::Path(path) => ExprKind::Path(folder.fold_path(path)),
+        ExprKind::Literal(lit) => ExprKind::Literal(folder.fold_lit(lit)),
+        ExprKind::Field(target, field, span) => ExprKind::Field(fold_expr_box(folder, target), field, span),
+        ExprKind::Assign(target, value, span) => {
+            let target = fold_expr_box(folder, target);
+            ExprKind::Assign(target, fold_expr_box(folder, value), span)
+        },
+        ExprKind::Unary(op, operand) => ExprKind::Unary(op, fold_expr_box(folder, operand)),
+        ExprKind::Binary(op, lhs, rhs) => {
+            let lhs = fold_expr_box(folder, lhs);
+            ExprKind::Binary(op, lhs, fold_expr_box(folder, rhs))
+        },
+        ExprKind::Call(target, args, span) => {
+            let target = fold_expr_box(folder, target);
+            let args = args.into_iter().map(|arg| fold_expr_box(folder, arg)).collect();
+            ExprKind::Call(target, args, span)
+        },
+        ExprKind::If(condition, block, else_expr) => {
+            let condition = fold_expr_box(folder, condition);
+            let block = fold_block_box(folder, block);
+            ExprKind::If(condition, block, else_expr.map(|expr| fold_expr_box(folder, expr)))
+        },
+        ExprKind::Block(block) => ExprKind::Block(fold_block_box(folder, block)),
+        ExprKind::Loop(block) => ExprKind::Loop(fold_block_box(folder, block)),
+        ExprKind::While(condition, block) => {
+            let condition = fold_expr_box(folder, condition);
+            ExprKind::While(condition, fold_block_box(folder, block))
+        },
+        ExprKind::For(pat, iter, block) => {
+            let pat = folder.fold_pat(pat);
+            let iter = fold_expr_box(folder, iter);
+            ExprKind::For(pat, iter, fold_block_box(folder, block))
+        },
+        ExprKind::Break(value) => ExprKind::Break(value.map(|value| fold_expr_box(folder, value))),
+        ExprKind::Continue => ExprKind::Continue,
+        ExprKind::Err => ExprKind::Err,
+        ExprKind::StructLit(lit) => ExprKind::StructLit(fold_box(lit, |lit| folder.fold_struct_lit(lit)))
+    };
+    expr
+}
+
+pub fn walk_struct_lit<F: Folder>(folder: &mut F, lit: StructLiteral) -> StructLiteral {
+    StructLiteral {
+        path: folder.fold_path(lit.path),
+        fields: lit.fields.into_iter().map(|field| folder.fold_field_lit(field)).collect(),
+        rest: match lit.rest {
+            RestExpr::Valued(expr) => RestExpr::Valued(fold_expr_box(folder, expr)),
+            rest => rest
+        }
+    }
+}
+
+pub fn walk_field_lit<F: Folder>(folder: &mut F, field: FieldLitExpr) -> FieldLitExpr {
+    FieldLitExpr {
+        expr: fold_expr_box(folder, field.expr),
+        ..field
+    }
+}
+
+pub fn walk_ty<F: Folder>(folder: &mut F, ty: Ty) -> Ty {
+    let kind = match ty.kind {
+        TyKind::Path(path) => TyKind::Path(folder.fold_path(path)),
+        kind => kind
+    };
+    Ty { kind, ..ty }
+}
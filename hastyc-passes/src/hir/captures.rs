@@ -0,0 +1,110 @@
+use std::{collections::{HashMap, HashSet}, convert::Infallible};
+
+use hastyc_common::{identifiers::ASTNodeID, span::Span};
+
+use crate::passes::{ASTPass, QueryContext};
+
+use super::{ty::Ty, Body, DefId, HirExprKind, Res};
+
+/// How a closure holds a captured variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureMode {
+    /// Copy of the value, taken when the closure is created.
+    ByValue,
+    /// Reference to the variable, so that assignments to it are seen by
+    /// both the closure and the function which declares it.
+    ByRef
+}
+
+/// Variable captured by a closure, together with how it is stored.
+#[derive(Debug, Clone, Copy)]
+pub struct Capture {
+    pub target: DefId,
+    pub mode: CaptureMode,
+    /// Type of the variable, the field holds a reference to it for `ByRef`.
+    pub ty: Ty,
+    /// Span of the first use inside of the closure.
+    pub span: Span
+}
+
+/// Environment of a closure. Backends lay it out like a struct with
+/// one field for every capture, in order of the captures, so that all
+/// of them agree on where each variable is.
+#[derive(Debug, Clone, Default)]
+pub struct ClosureEnv {
+    pub captures: Vec<Capture>
+}
+
+impl ClosureEnv {
+    /// Index of the field which holds the captured variable.
+    pub fn field_of(&self, target: DefId) -> Option<usize> {
+        self.captures.iter().position(|capture| capture.target == target)
+    }
+}
+
+/// Pass computing environments of closures from upvars found by name
+/// resolution, it needs their types, so it has to run after
+/// `hir::typeck::TypeCheckPass`.
+#[derive(Debug, Default)]
+pub struct CapturePass;
+
+impl<'ctx> ASTPass<'ctx> for CapturePass {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "closure-captures"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["typeck"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let envs = closure_envs(ctx);
+        ctx.closure_envs = envs;
+        Ok(())
+    }
+}
+
+/// Environments of all closures, keyed by their AST nodes. Variables
+/// which are assigned anywhere are captured by reference, as the closure
+/// has to see the same variable as the rest of the body, others are
+/// captured by value.
+pub fn closure_envs(cx: &QueryContext) -> HashMap<ASTNodeID, ClosureEnv> {
+    let hir = cx.hir.as_ref().expect("package must be lowered before computing captures");
+    let assigned: HashSet<ASTNodeID> = hir.bodies()
+        .flat_map(|(_, body)| assigned_locals(body))
+        .collect();
+    cx.upvars.iter()
+        .map(|(&closure, upvars)| {
+            let captures = upvars.iter()
+                .map(|upvar| {
+                    let ast_id = cx.defs.get(upvar.target).ast_id;
+                    let mode = if assigned.contains(&ast_id) { CaptureMode::ByRef } else { CaptureMode::ByValue };
+                    Capture {
+                        target: upvar.target,
+                        mode,
+                        ty: cx.types.get(ast_id).unwrap_or(Ty::Err),
+                        span: upvar.span
+                    }
+                })
+                .collect();
+            (closure, ClosureEnv { captures })
+        })
+        .collect()
+}
+
+/// Locals which the body assigns to, either directly or through
+/// one of their fields.
+fn assigned_locals(body: &Body) -> impl Iterator<Item = ASTNodeID> + '_ {
+    body.exprs.iter().filter_map(move |expr| {
+        let HirExprKind::Assign(mut target, _) = expr.kind else { return None };
+        loop {
+            match body.expr(target).kind {
+                HirExprKind::Field(base, _) => target = base,
+                HirExprKind::Path(Res::Local(local)) => return Some(body.local(local).ast_id),
+                _ => return None
+            }
+        }
+    })
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashSet, convert::Infallible};
+
+use hastyc_common::{error::{CommonErrorContext, ErrorDisplay, ErrorFmt, Severity}, identifiers::{sym, Symbol}, span::Span};
+
+use crate::passes::{ASTPass, QueryContext};
+
+use super::{mono::{collect_mono_items, main_fn}, DefId, DefKind, Hir};
+
+/// Pass warning about functions and modules that can't be reached from
+/// the roots used by `hir::mono::MonoCollectPass`. Definitions inside of
+/// something marked with `#[allow(dead_code)]` are never reported.
+#[derive(Debug, Default)]
+pub struct DeadCodePass;
+
+impl<'ctx> ASTPass<'ctx> for DeadCodePass {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "dead-code"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["lower-to-hir"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let cx: &QueryContext = ctx;
+        let hir = cx.hir.as_ref().expect("package must be lowered before looking for dead code");
+        let entry = match main_fn(hir) {
+            Some(_) => "`main`",
+            None => "any public function"
+        };
+
+        // Definitions which are used or contain something used
+        let mut live = HashSet::from([hir.root]);
+        for item in collect_mono_items(cx) {
+            let mut def = Some(item.def);
+            while let Some(id) = def {
+                if !live.insert(id) { break }
+                def = hir.def(id).parent;
+            }
+        }
+
+        for (&id, def) in hir.defs.iter() {
+            let Some(parent) = def.parent else { continue };
+            if !matches!(def.kind, DefKind::Fn { .. } | DefKind::Module { .. }) || live.contains(&id) {
+                continue
+            }
+            // Only the outermost dead definition is reported
+            if !live.contains(&parent) || is_allowed(hir, id) {
+                continue
+            }
+            cx.emit(DeadCodeWarning {
+                descr: def.kind.descr(),
+                name: def.ident.symbol,
+                entry,
+                span: def.ident.span
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether the definition or anything containing it allows dead code.
+fn is_allowed(hir: &Hir, id: DefId) -> bool {
+    let mut def = Some(id);
+    while let Some(id) = def {
+        if hir.def(id).attrs.has_list_arg(sym::allow, sym::dead_code) {
+            return true
+        }
+        def = hir.def(id).parent;
+    }
+    false
+}
+
+#[derive(Debug)]
+pub struct DeadCodeWarning {
+    /// Kind of the definition, eg. "function".
+    pub descr: &'static str,
+    pub name: Symbol,
+    /// What the definition is not reachable from.
+    pub entry: &'static str,
+    /// Span of the name of the definition.
+    pub span: Span
+}
+
+impl<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> for DeadCodeWarning {
+    fn code(&self) -> Option<&'static str> {
+        Some("H0400")
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn fmt(&self, fmt: &mut ErrorFmt<'ctx>, ctx: &'ctx CommonErrorContext) {
+        let descr = self.descr[..1].to_uppercase() + &self.descr[1..];
+        fmt
+            .title(&format!("{} `{}` is never used.", descr, self.name))
+            .source(ctx.source, self.span)
+            .note(&format!("It can't be reached from {}.", self.entry))
+            .help("Add `#[allow(dead_code)]` to the definition if this is intended.");
+    }
+}
@@ -0,0 +1,393 @@
+use std::{collections::{BTreeMap, HashMap}, convert::Infallible};
+
+use hastyc_common::{identifiers::{ASTNodeID, Ident, Symbol}, span::Span};
+use hastyc_parser::parser::{Attributes, Block, DataVariant, Expr, ExprKind, FieldDef, FnInput, FnRetTy, Function, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, RestExpr, Stmt, StmtKind, Ty, TyKind, Visibility};
+
+use crate::{passes::{defs::{self, pat_ident}, ASTPass, QueryContext}, visit::{walk_expr, DepthLimit, Visitor}};
+
+use super::{Body, BodyId, CtorKind, Def, DefId, DefKind, ExprId, FnSig, Hir, HirBlock, HirExpr, HirExprKind, HirRestExpr, HirStmt, HirStmtKind, HirTy, HirTyKind, BlockId, Local, LocalId, Res, StmtId};
+
+/// Pass that lowers the package into HIR using results of name resolution,
+/// so it has to run after `NameResolvePass`. Lowered package is stored
+/// in `QueryContext::hir`.
+///
+/// Loops must be desugared with `passes::desugar::DesugarLoops` before
+/// name resolution, as names used by the desugared loops are resolved
+/// on the AST. Lowering `while` or `for` is a bug in the driver.
+#[derive(Debug, Default)]
+pub struct LowerToHir;
+
+impl<'ctx> ASTPass<'ctx> for LowerToHir {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "lower-to-hir"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["name-resolve"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let hir = lower_package(ctx);
+        ctx.set_hir(hir);
+        Ok(())
+    }
+}
+
+/// Lower the package of the context into HIR.
+pub fn lower_package(cx: &QueryContext) -> Hir {
+    let mut lowerer = Lowerer {
+        cx,
+        defs: BTreeMap::new(),
+        def_ids: HashMap::new(),
+        bodies: Vec::new(),
+        fns: Vec::new(),
+        fields: Vec::new()
+    };
+
+    // All definitions are created first, so that bodies can refer
+    // to items defined after them
+    let root = lowerer.alloc_def(cx.package.id, None, Ident::dummy(), Visibility::Public, &cx.package.attrs, Span::dummy());
+    let items = lowerer.lower_item_stream(&cx.package.items, root);
+    lowerer.set_kind(root, DefKind::Module { items });
+
+    for (id, field) in std::mem::take(&mut lowerer.fields) {
+        let ty = lower_ty(lowerer.cx, &field.ty, field.id);
+        lowerer.set_kind(id, DefKind::Field { ty });
+    }
+    for (id, function) in std::mem::take(&mut lowerer.fns) {
+        let sig = lowerer.lower_sig(function);
+        let body = lowerer.lower_body(id, function);
+        lowerer.set_kind(id, DefKind::Fn { sig, body });
+    }
+
+    Hir {
+        defs: lowerer.defs,
+        bodies: lowerer.bodies,
+        root,
+        def_ids: lowerer.def_ids
+    }
+}
+
+struct Lowerer<'ast, 'ctx> {
+    cx: &'ast QueryContext<'ctx>,
+    defs: BTreeMap<DefId, Def>,
+    def_ids: HashMap<ASTNodeID, DefId>,
+    bodies: Vec<Body>,
+    /// Functions and fields, which refer to other definitions by their
+    /// types, so they are finished once all definitions exist.
+    fns: Vec<(DefId, &'ast Function)>,
+    fields: Vec<(DefId, &'ast FieldDef)>
+}
+
+impl<'ast> Lowerer<'ast, '_> {
+    /// Create definition, its kind is filled in once its children
+    /// or, for functions and fields, all definitions are lowered.
+    fn alloc_def(&mut self, ast_id: ASTNodeID, parent: Option<DefId>, ident: Ident, visibility: Visibility, attrs: &Attributes, span: Span) -> DefId {
+        let id = self.cx.defs.def_id(ast_id).expect("lowered definition was not collected");
+        self.defs.insert(id, Def {
+            ast_id,
+            parent,
+            ident,
+            visibility,
+            attrs: attrs.clone(),
+            kind: DefKind::Module { items: Vec::new() },
+            span
+        });
+        self.def_ids.insert(ast_id, id);
+        id
+    }
+
+    fn set_kind(&mut self, id: DefId, kind: DefKind) {
+        self.defs.get_mut(&id).expect("kind set before definition was created").kind = kind;
+    }
+
+    fn lower_item_stream(&mut self, stream: &'ast ItemStream, parent: DefId) -> Vec<DefId> {
+        stream.items.iter()
+            .filter_map(|item| self.lower_item(item, parent))
+            .collect()
+    }
+
+    /// Lower item into definition, imports have none as
+    /// paths using them are already resolved to their targets.
+    fn lower_item(&mut self, item: &'ast Item, parent: DefId) -> Option<DefId> {
+        if let ItemKind::Import(..) = item.kind {
+            return None;
+        }
+        let id = self.alloc_def(item.id, Some(parent), item.ident.clone(), item.visibility, &item.attrs, item.span);
+        let kind = match item.kind {
+            ItemKind::Module(ref items) => DefKind::Module { items: self.lower_item_stream(items, id) },
+            ItemKind::Fn(ref function) => {
+                if let Some(ref body) = function.body {
+                    // Items declared in the body belong to the function
+                    let mut nested = NestedItems { items: Vec::new(), depth: DepthLimit::new(self.cx.sess.options.max_expr_depth) };
+                    let Ok(()) = nested.visit_block(body);
+                    for item in nested.items {
+                        self.lower_item(item, id);
+                    }
+                }
+                self.fns.push((id, function));
+                return Some(id)
+            },
+            ItemKind::Struct(ref data) => {
+                let (fields, ctor) = self.lower_data_variant(data, id);
+                DefKind::Struct { fields, ctor }
+            },
+            ItemKind::Enum(ref def) => {
+                let variants = def.variants.iter()
+                    .map(|variant| {
+                        let vid = self.alloc_def(variant.id, Some(id), variant.ident.clone(), variant.vis, &variant.attrs, variant.span);
+                        let (fields, ctor) = self.lower_data_variant(&variant.data, vid);
+                        self.set_kind(vid, DefKind::Variant { fields, ctor });
+                        vid
+                    })
+                    .collect();
+                DefKind::Enum { variants }
+            },
+            ItemKind::Import(..) => unreachable!()
+        };
+        self.set_kind(id, kind);
+        Some(id)
+    }
+
+    fn lower_data_variant(&mut self, data: &'ast DataVariant, parent: DefId) -> (Vec<DefId>, CtorKind) {
+        let (fields, ctor) = match data {
+            DataVariant::Struct { ref fields } => (fields, CtorKind::Struct),
+            DataVariant::Tuple { ref fields } => (fields, CtorKind::Tuple),
+            DataVariant::Unit => return (Vec::new(), CtorKind::Unit)
+        };
+        let fields = fields.iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                // Tuple fields are named by their index, like in name resolution
+                let ident = match field.ident {
+                    Some(ref ident) => ident.clone(),
+                    None => Ident::new(Symbol::intern(&idx.to_string()), field.span)
+                };
+                let id = self.alloc_def(field.id, Some(parent), ident, field.vis, &field.attrs, field.span);
+                self.fields.push((id, field));
+                id
+            })
+            .collect();
+        (fields, ctor)
+    }
+
+    fn lower_sig(&self, function: &Function) -> FnSig {
+        let sig = &function.signature;
+        FnSig {
+            inputs: sig.inputs.iter()
+                .map(|input| lower_ty(self.cx, &input.ty, input.id))
+                .collect(),
+            output: match sig.output {
+                FnRetTy::Ty(ref ty) => Some(lower_ty(self.cx, ty, ty.id)),
+                FnRetTy::Default => None
+            },
+            span: sig.span
+        }
+    }
+
+    fn lower_body(&mut self, owner: DefId, function: &'ast Function) -> Option<BodyId> {
+        let block = function.body.as_ref()?;
+        let mut lowerer = BodyLowerer {
+            cx: self.cx,
+            locals: HashMap::new(),
+            body: Body {
+                owner,
+                params: Vec::new(),
+                locals: Vec::new(),
+                exprs: Vec::new(),
+                stmts: Vec::new(),
+                blocks: Vec::new(),
+                value: BlockId(0)
+            },
+            depth: DepthLimit::new(self.cx.sess.options.max_expr_depth)
+        };
+        for input in function.signature.inputs.iter() {
+            let param = lowerer.lower_param(input);
+            lowerer.body.params.push(param);
+        }
+        lowerer.body.value = lowerer.lower_block(block);
+
+        let id = BodyId(self.bodies.len() as u32);
+        self.bodies.push(lowerer.body);
+        Some(id)
+    }
+}
+
+/// Lower type, `owner` is the node under which name resolution
+/// recorded what the type refers to.
+fn lower_ty(cx: &QueryContext, ty: &Ty, owner: ASTNodeID) -> HirTy {
+    let kind = match ty.kind {
+        TyKind::Path(_) => HirTyKind::Path(match cx.prim_tys.get(&owner) {
+            Some(prim) => Res::PrimTy(prim),
+            None => cx.resolved_names.get(&owner)
+                .filter(|def| cx.defs.get(*def).kind != defs::DefKind::Local)
+                .map_or(Res::Err, Res::Def)
+        }),
+        TyKind::SelfTy => HirTyKind::SelfTy,
+        TyKind::Void => HirTyKind::Void,
+        TyKind::Never => HirTyKind::Never,
+        TyKind::Infer => HirTyKind::Infer
+    };
+    HirTy { kind, span: ty.span }
+}
+
+/// Visitor collecting items declared in a function body,
+/// without going into the items themselves.
+struct NestedItems<'ast> {
+    items: Vec<&'ast Item>,
+    depth: DepthLimit
+}
+
+impl<'ast> Visitor<'ast> for NestedItems<'ast> {
+    type Break = Infallible;
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Self::Break> {
+        // Items in too deeply nested expressions are not lowered, like
+        // the expressions themselves
+        if !self.depth.enter() {
+            return Ok(())
+        }
+        let result = walk_expr(self, expr);
+        self.depth.exit();
+        result
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Self::Break> {
+        self.items.push(item);
+        Ok(())
+    }
+}
+
+struct BodyLowerer<'a, 'ctx> {
+    cx: &'a QueryContext<'ctx>,
+    /// Locals of the body by the AST node that declares them.
+    locals: HashMap<ASTNodeID, LocalId>,
+    body: Body,
+    depth: DepthLimit
+}
+
+impl BodyLowerer<'_, '_> {
+    /// What the node refers to according to name resolution.
+    fn res(&self, id: ASTNodeID) -> Res {
+        let Some(target) = self.cx.resolved_names.get(&id) else {
+            return Res::Err;
+        };
+        let data = self.cx.defs.get(target);
+        match data.kind {
+            defs::DefKind::Local => self.locals.get(&data.ast_id).map_or(Res::Err, |local| Res::Local(*local)),
+            _ => Res::Def(target)
+        }
+    }
+
+    fn alloc_local(&mut self, ast_id: ASTNodeID, ident: Ident, ty: Option<HirTy>, span: Span) -> LocalId {
+        let id = LocalId(self.body.locals.len() as u32);
+        self.body.locals.push(Local { ast_id, ident, ty, span });
+        self.locals.insert(ast_id, id);
+        id
+    }
+
+    fn lower_param(&mut self, input: &FnInput) -> LocalId {
+        let ty = lower_ty(self.cx, &input.ty, input.id);
+        self.alloc_local(input.id, pat_ident(&input.pat), Some(ty), input.span)
+    }
+
+    fn lower_block(&mut self, block: &Block) -> BlockId {
+        let mut stmts = Vec::new();
+        let mut tail = None;
+        let count = block.stmts.stmts.len();
+        for (idx, stmt) in block.stmts.stmts.iter().enumerate() {
+            match stmt.kind {
+                // Last expression without semicolon is the value of the block
+                StmtKind::ExprNS(ref expr) if idx + 1 == count => tail = Some(self.lower_expr(expr)),
+                _ => stmts.push(self.lower_stmt(stmt))
+            }
+        }
+        let id = BlockId(self.body.blocks.len() as u32);
+        self.body.blocks.push(HirBlock { ast_id: block.id, stmts, tail, span: block.span });
+        id
+    }
+
+    fn lower_stmt(&mut self, stmt: &Stmt) -> StmtId {
+        let kind = match stmt.kind {
+            StmtKind::LetBinding(ref binding) => self.lower_let(binding),
+            StmtKind::Item(ref item) => HirStmtKind::Item(self.cx.defs.def_id(item.id).expect("item was not collected")),
+            StmtKind::Expr(ref expr) | StmtKind::ExprNS(ref expr) => HirStmtKind::Expr(self.lower_expr(expr))
+        };
+        let id = StmtId(self.body.stmts.len() as u32);
+        self.body.stmts.push(HirStmt { ast_id: stmt.id, kind, span: stmt.span });
+        id
+    }
+
+    fn lower_let(&mut self, binding: &LetBinding) -> HirStmtKind {
+        // Initializer can't see the new local, like in name resolution
+        let init = match binding.kind {
+            LetBindingKind::Init(ref expr) => Some(self.lower_expr(expr)),
+            LetBindingKind::Decl => None
+        };
+        let ty = binding.ty.as_ref().map(|ty| lower_ty(self.cx, ty, binding.id));
+        let local = self.alloc_local(binding.id, pat_ident(&binding.pat), ty, binding.span);
+        HirStmtKind::Let { local, init }
+    }
+
+    fn lower_expr(&mut self, expr: &Expr) -> ExprId {
+        // Expressions nested too deeply were reported by name resolution,
+        // so they are lowered as erroneous paths. HIR is never nested more
+        // than `Options::max_expr_depth`, so passes over it can always go through it.
+        if !self.depth.enter() {
+            return self.push_expr(expr, HirExprKind::Path(Res::Err))
+        }
+        let kind = match expr.kind {
+            ExprKind::Path(_) => HirExprKind::Path(self.res(expr.id)),
+            ExprKind::Literal(ref lit) => HirExprKind::Literal(lit.clone()),
+            ExprKind::Field(ref target, ref field, _) => HirExprKind::Field(self.lower_expr(target), field.clone()),
+            ExprKind::Assign(ref target, ref value, _) => {
+                let target = self.lower_expr(target);
+                HirExprKind::Assign(target, self.lower_expr(value))
+            },
+            ExprKind::Unary(ref op, ref operand) => HirExprKind::Unary(op.clone(), self.lower_expr(operand)),
+            ExprKind::Binary(ref op, ref lhs, ref rhs) => {
+                let lhs = self.lower_expr(lhs);
+                HirExprKind::Binary(op.clone(), lhs, self.lower_expr(rhs))
+            },
+            ExprKind::Call(ref target, ref args, _) => {
+                let target = self.lower_expr(target);
+                let args = args.iter().map(|arg| self.lower_expr(arg)).collect();
+                HirExprKind::Call(target, args)
+            },
+            ExprKind::If(ref condition, ref block, ref else_expr) => {
+                let condition = self.lower_expr(condition);
+                let block = self.lower_block(block);
+                HirExprKind::If(condition, block, else_expr.as_ref().map(|expr| self.lower_expr(expr)))
+            },
+            ExprKind::Block(ref block) => HirExprKind::Block(self.lower_block(block)),
+            ExprKind::Loop(ref block) => HirExprKind::Loop(self.lower_block(block)),
+            ExprKind::While(..) | ExprKind::For(..) => {
+                unreachable!("loops must be desugared with `DesugarLoops` before name resolution")
+            },
+            ExprKind::Break(ref value) => HirExprKind::Break(value.as_ref().map(|value| self.lower_expr(value))),
+            ExprKind::Continue => HirExprKind::Continue,
+            ExprKind::Err => HirExprKind::Path(Res::Err),
+            ExprKind::StructLit(ref lit) => {
+                let fields = lit.fields.iter()
+                    .map(|field| (field.ident.clone(), self.lower_expr(&field.expr)))
+                    .collect();
+                let rest = match lit.rest {
+                    RestExpr::Valued(ref expr) => HirRestExpr::Valued(self.lower_expr(expr)),
+                    RestExpr::Rest(span) => HirRestExpr::Rest(span),
+                    RestExpr::None => HirRestExpr::None
+                };
+                HirExprKind::StructLit { res: self.res(expr.id), fields, rest }
+            }
+        };
+        self.depth.exit();
+        self.push_expr(expr, kind)
+    }
+
+    fn push_expr(&mut self, expr: &Expr, kind: HirExprKind) -> ExprId {
+        let id = ExprId(self.body.exprs.len() as u32);
+        self.body.exprs.push(HirExpr { ast_id: expr.id, kind, span: expr.span });
+        id
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, HashMap};
+
+use hastyc_common::{identifiers::{ASTNodeID, Ident}, span::Span};
+use hastyc_parser::parser::{Attributes, BinOp, Lit, UnOp, Visibility};
+
+use crate::passes::builtin::PrimTy;
+
+pub use crate::passes::defs::DefId;
+
+pub mod lower;
+pub mod typeck;
+pub mod ty;
+pub mod mono;
+pub mod dead_code;
+pub mod captures;
+pub mod passing;
+
+/// Ids of IR nodes, these are indices into tables of the IR, like
+/// tables of `Hir` or `Body`.
+macro_rules! index_id {
+    ($($(#[$attr:meta])* $name:ident),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $name(pub u32);
+
+            impl $name {
+                pub fn index(self) -> usize {
+                    self.0 as usize
+                }
+            }
+        )*
+    };
+}
+pub(crate) use index_id;
+
+index_id! {
+    /// Body of a function.
+    BodyId,
+    /// Local variable or parameter, unique in its body.
+    LocalId,
+    ExprId,
+    StmtId,
+    BlockId
+}
+
+/// What a name refers to, as found by name resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Res {
+    Def(DefId),
+    Local(LocalId),
+    PrimTy(PrimTy),
+    /// Name was not resolved, either because resolution failed
+    /// or because it doesn't cover this kind of name yet.
+    Err
+}
+
+/// Lowered package. Unlike the AST, names are already resolved, loops
+/// are desugared and bodies are flat tables of nodes, so analyses can
+/// refer to nodes by index.
+#[derive(Debug)]
+pub struct Hir {
+    /// Items, variants and fields, locals are in bodies instead.
+    pub defs: BTreeMap<DefId, Def>,
+    pub bodies: Vec<Body>,
+    /// Module of the whole package.
+    pub root: DefId,
+    def_ids: HashMap<ASTNodeID, DefId>
+}
+
+impl Hir {
+    pub fn def(&self, id: DefId) -> &Def {
+        &self.defs[&id]
+    }
+
+    pub fn body(&self, id: BodyId) -> &Body {
+        &self.bodies[id.index()]
+    }
+
+    /// Definition lowered from the AST node.
+    pub fn def_id(&self, ast_id: ASTNodeID) -> Option<DefId> {
+        self.def_ids.get(&ast_id).copied()
+    }
+
+    /// All function bodies together with their ids.
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyId, &Body)> {
+        self.bodies.iter().enumerate().map(|(idx, body)| (BodyId(idx as u32), body))
+    }
+}
+
+#[derive(Debug)]
+pub struct Def {
+    pub ast_id: ASTNodeID,
+    /// Definition that contains this one, None only for the root module.
+    pub parent: Option<DefId>,
+    pub ident: Ident,
+    pub visibility: Visibility,
+    pub attrs: Attributes,
+    pub kind: DefKind,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub enum DefKind {
+    Module { items: Vec<DefId> },
+    Fn { sig: FnSig, body: Option<BodyId> },
+    Struct { fields: Vec<DefId>, ctor: CtorKind },
+    Enum { variants: Vec<DefId> },
+    Variant { fields: Vec<DefId>, ctor: CtorKind },
+    Field { ty: HirTy }
+}
+
+impl DefKind {
+    /// Name of the kind of definition, used in diagnostics.
+    pub fn descr(&self) -> &'static str {
+        match self {
+            Self::Module { .. } => "module",
+            Self::Fn { .. } => "function",
+            Self::Struct { .. } => "struct",
+            Self::Enum { .. } => "enum",
+            Self::Variant { .. } => "variant",
+            Self::Field { .. } => "field"
+        }
+    }
+}
+
+/// How struct or variant is constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtorKind {
+    /// `Name { field: value }`
+    Struct,
+    /// `Name(value)`
+    Tuple,
+    /// `Name`
+    Unit
+}
+
+#[derive(Debug)]
+pub struct FnSig {
+    pub inputs: Vec<HirTy>,
+    /// None if the function doesn't declare return type.
+    pub output: Option<HirTy>,
+    pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct HirTy {
+    pub kind: HirTyKind,
+    pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum HirTyKind {
+    Path(Res),
+    SelfTy,
+    Void,
+    Never,
+    Infer
+}
+
+/// Function body with all of its nodes.
+#[derive(Debug)]
+pub struct Body {
+    pub owner: DefId,
+    /// Locals of the parameters, in order.
+    pub params: Vec<LocalId>,
+    pub locals: Vec<Local>,
+    pub exprs: Vec<HirExpr>,
+    pub stmts: Vec<HirStmt>,
+    pub blocks: Vec<HirBlock>,
+    pub value: BlockId
+}
+
+impl Body {
+    pub fn local(&self, id: LocalId) -> &Local {
+        &self.locals[id.index()]
+    }
+
+    pub fn expr(&self, id: ExprId) -> &HirExpr {
+        &self.exprs[id.index()]
+    }
+
+    pub fn stmt(&self, id: StmtId) -> &HirStmt {
+        &self.stmts[id.index()]
+    }
+
+    pub fn block(&self, id: BlockId) -> &HirBlock {
+        &self.blocks[id.index()]
+    }
+}
+
+#[derive(Debug)]
+pub struct Local {
+    pub ast_id: ASTNodeID,
+    pub ident: Ident,
+    /// Declared type, None for parameters without one and for `let`
+    /// without type annotation.
+    pub ty: Option<HirTy>,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub struct HirBlock {
+    pub ast_id: ASTNodeID,
+    pub stmts: Vec<StmtId>,
+    /// Last expression without semicolon, which is the value of the block.
+    pub tail: Option<ExprId>,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub struct HirStmt {
+    pub ast_id: ASTNodeID,
+    pub kind: HirStmtKind,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub enum HirStmtKind {
+    Let { local: LocalId, init: Option<ExprId> },
+    Item(DefId),
+    /// Expression followed by semicolon, or block-like expression
+    /// which is not the last one in the block.
+    Expr(ExprId)
+}
+
+#[derive(Debug)]
+pub struct HirExpr {
+    pub ast_id: ASTNodeID,
+    pub kind: HirExprKind,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub enum HirExprKind {
+    Path(Res),
+    Literal(Lit),
+    Field(ExprId, Ident),
+    Assign(ExprId, ExprId),
+    Unary(UnOp, ExprId),
+    Binary(BinOp, ExprId, ExprId),
+    Call(ExprId, Vec<ExprId>),
+    If(ExprId, BlockId, Option<ExprId>),
+    Block(BlockId),
+    /// The only loop in HIR, others are lowered into it.
+    Loop(BlockId),
+    Break(Option<ExprId>),
+    Continue,
+    StructLit {
+        res: Res,
+        fields: Vec<(Ident, ExprId)>,
+        rest: HirRestExpr
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HirRestExpr {
+    /// `..base`
+    Valued(ExprId),
+    /// `..` alone
+    Rest(Span),
+    None
+}
@@ -0,0 +1,160 @@
+use std::{collections::HashSet, convert::Infallible};
+
+use hastyc_common::identifiers::sym;
+use hastyc_parser::parser::Visibility;
+
+use crate::passes::{ASTPass, QueryContext};
+
+use super::{ty::Ty, DefId, DefKind, Hir, HirTy, HirTyKind, Res};
+
+/// Types substituted for generic parameters of a definition, in order
+/// of the parameters. Empty for definitions without generics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Substs(pub Vec<Ty>);
+
+/// Concrete instance of a function or type which backends have to emit.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MonoItem {
+    pub def: DefId,
+    pub substs: Substs
+}
+
+/// Pass collecting mono items reachable from the roots, so it has to run
+/// after `hir::typeck::TypeCheckPass`. Root of a binary is its `main`
+/// function, libraries have all public functions as roots.
+///
+/// There are no generics yet, so every item has empty substitutions.
+#[derive(Debug, Default)]
+pub struct MonoCollectPass;
+
+impl<'ctx> ASTPass<'ctx> for MonoCollectPass {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "mono-collect"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["typeck"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let items = collect_mono_items(ctx);
+        ctx.mono_items = items;
+        Ok(())
+    }
+}
+
+/// Mono items reachable from the roots, in order they were found.
+pub fn collect_mono_items(cx: &QueryContext) -> Vec<MonoItem> {
+    let hir = cx.hir.as_ref().expect("package must be lowered before collecting mono items");
+    let mut collector = MonoCollector {
+        hir,
+        cx,
+        items: Vec::new(),
+        seen: HashSet::new(),
+        queue: Vec::new()
+    };
+    for root in roots(hir) {
+        collector.push(root);
+    }
+    while let Some(item) = collector.queue.pop() {
+        collector.walk(&item);
+    }
+    collector.items
+}
+
+/// `main` function of the root module, packages without it are libraries.
+pub fn main_fn(hir: &Hir) -> Option<DefId> {
+    let DefKind::Module { ref items } = hir.def(hir.root).kind else { unreachable!("root is not a module") };
+    items.iter().copied().find(|item| {
+        let def = hir.def(*item);
+        def.ident.symbol == sym::main && matches!(def.kind, DefKind::Fn { .. })
+    })
+}
+
+fn roots(hir: &Hir) -> Vec<DefId> {
+    if let Some(main) = main_fn(hir) {
+        return vec![main]
+    }
+    hir.defs.iter()
+        .filter(|(_, def)| matches!(def.kind, DefKind::Fn { .. }) && matches!(def.visibility, Visibility::Public))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+struct MonoCollector<'a, 'ctx> {
+    hir: &'a Hir,
+    cx: &'a QueryContext<'ctx>,
+    items: Vec<MonoItem>,
+    seen: HashSet<MonoItem>,
+    /// Items found but not walked yet.
+    queue: Vec<MonoItem>
+}
+
+impl MonoCollector<'_, '_> {
+    fn push(&mut self, def: DefId) {
+        let item = MonoItem { def, substs: Substs::default() };
+        if self.seen.insert(item.clone()) {
+            self.items.push(item.clone());
+            self.queue.push(item);
+        }
+    }
+
+    /// Collect items used by the item.
+    fn walk(&mut self, item: &MonoItem) {
+        let hir = self.hir;
+        match hir.def(item.def).kind {
+            DefKind::Fn { ref sig, body } => {
+                for ty in sig.inputs.iter().chain(sig.output.iter()) {
+                    self.walk_hir_ty(ty);
+                }
+                let Some(body) = body else { return };
+                let body = hir.body(body);
+                let ast_ids = body.locals.iter().map(|local| local.ast_id)
+                    .chain(body.exprs.iter().map(|expr| expr.ast_id));
+                for ast_id in ast_ids {
+                    if let Some(ty) = self.cx.types.get(ast_id) {
+                        self.walk_ty(ty);
+                    }
+                }
+            },
+            DefKind::Struct { ref fields, .. } => self.walk_fields(fields),
+            DefKind::Enum { ref variants } => {
+                for variant in variants.iter() {
+                    let DefKind::Variant { ref fields, .. } = hir.def(*variant).kind else { continue };
+                    self.walk_fields(fields);
+                }
+            },
+            DefKind::Module { .. } | DefKind::Variant { .. } | DefKind::Field { .. } => {}
+        }
+    }
+
+    fn walk_fields(&mut self, fields: &[DefId]) {
+        for field in fields.iter() {
+            if let DefKind::Field { ref ty } = self.hir.def(*field).kind {
+                self.walk_hir_ty(ty);
+            }
+        }
+    }
+
+    fn walk_hir_ty(&mut self, ty: &HirTy) {
+        if let HirTyKind::Path(Res::Def(def)) = ty.kind {
+            if matches!(self.hir.def(def).kind, DefKind::Struct { .. } | DefKind::Enum { .. }) {
+                self.push(def);
+            }
+        }
+    }
+
+    fn walk_ty(&mut self, ty: Ty) {
+        match ty {
+            Ty::Adt(def) => self.push(def),
+            // Constructors are emitted together with their types
+            Ty::FnDef(def) => match self.hir.def(def).kind {
+                DefKind::Variant { .. } => self.push(self.hir.def(def).parent.expect("variant without enum")),
+                _ => self.push(def)
+            },
+            Ty::Prim(_) | Ty::Void | Ty::Never | Ty::Var(_) | Ty::Err => {}
+        }
+    }
+}
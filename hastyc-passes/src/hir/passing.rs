@@ -0,0 +1,231 @@
+use std::{collections::{HashMap, HashSet}, convert::Infallible};
+
+use crate::passes::{builtin::{FloatTy, IntTy, PrimTy, UintTy}, ASTPass, QueryContext};
+
+use super::{ty::Ty, typeck::declared_ty, BlockId, Body, DefId, DefKind, ExprId, Hir, HirExprKind, HirRestExpr, HirStmtKind, LocalId, Res};
+
+/// Values up to this size in bytes are copied, this is two registers
+/// on 64 bit targets, which are the only ones supported.
+pub const MAX_COPY_SIZE: u64 = 16;
+
+/// How the value of a parameter or local is passed around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PassingMode {
+    /// Value is small, so it is copied, which is as cheap as passing
+    /// a reference to it.
+    Copy,
+    /// Value is large, but it is read only once, so its storage can
+    /// be handed over without copying it.
+    Move,
+    /// Value is large and read more than once, so it is passed by
+    /// reference instead of being copied every time.
+    ByRef
+}
+
+/// Pass classifying parameters and locals of all bodies by how their
+/// values should be passed, so that backends can choose calling
+/// conventions and don't copy values when they don't need to. Modes
+/// depend on types, so this has to run after `hir::typeck::TypeCheckPass`.
+#[derive(Debug, Default)]
+pub struct PassingModePass;
+
+impl<'ctx> ASTPass<'ctx> for PassingModePass {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "passing-modes"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["typeck"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let modes = passing_modes(ctx);
+        ctx.passing_modes = modes;
+        Ok(())
+    }
+}
+
+/// Passing modes of parameters and locals of all bodies, by their definitions.
+pub fn passing_modes(cx: &QueryContext) -> HashMap<DefId, PassingMode> {
+    let hir = cx.hir.as_ref().expect("package must be lowered before computing passing modes");
+    let mut modes = HashMap::new();
+    for (_, body) in hir.bodies() {
+        let reads = count_reads(body);
+        for (idx, local) in body.locals.iter().enumerate() {
+            let Some(def) = cx.defs.def_id(local.ast_id) else { continue };
+            let ty = cx.types.get(local.ast_id).unwrap_or(Ty::Err);
+            let mode = match ty_size(hir, ty, &mut HashSet::new()) {
+                Some(size) if size <= MAX_COPY_SIZE => PassingMode::Copy,
+                // Recursive types are never small
+                _ if reads[idx] <= 1 => PassingMode::Move,
+                _ => PassingMode::ByRef
+            };
+            modes.insert(def, mode);
+        }
+    }
+    modes
+}
+
+/// Size of the type in bytes, as backends lay it out. Fields are not
+/// padded and enums have a tag of their own size, so this is only an
+/// estimate. None for types which contain themselves, which have no
+/// size until there are pointers.
+pub fn ty_size(hir: &Hir, ty: Ty, seen: &mut HashSet<DefId>) -> Option<u64> {
+    Some(match ty {
+        Ty::Prim(prim) => prim_size(prim),
+        Ty::Adt(def) => {
+            if !seen.insert(def) {
+                return None
+            }
+            let size = match hir.def(def).kind {
+                DefKind::Struct { ref fields, .. } => fields_size(hir, fields, seen)?,
+                DefKind::Enum { ref variants } => {
+                    let mut largest = 0;
+                    for variant in variants.iter() {
+                        let DefKind::Variant { ref fields, .. } = hir.def(*variant).kind else { continue };
+                        largest = largest.max(fields_size(hir, fields, seen)?);
+                    }
+                    largest + tag_size(variants.len())
+                },
+                _ => 0
+            };
+            seen.remove(&def);
+            size
+        },
+        // Functions are known statically, erroneous values never exist
+        Ty::FnDef(_) | Ty::Void | Ty::Never | Ty::Var(_) | Ty::Err => 0
+    })
+}
+
+fn fields_size(hir: &Hir, fields: &[DefId], seen: &mut HashSet<DefId>) -> Option<u64> {
+    let mut size = 0;
+    for field in fields.iter() {
+        let DefKind::Field { ref ty } = hir.def(*field).kind else { continue };
+        size += ty_size(hir, declared_ty(hir, ty).unwrap_or(Ty::Err), seen)?;
+    }
+    Some(size)
+}
+
+fn prim_size(prim: PrimTy) -> u64 {
+    match prim {
+        PrimTy::Int(IntTy::I8) | PrimTy::Uint(UintTy::U8) | PrimTy::Bool => 1,
+        PrimTy::Int(IntTy::I16) | PrimTy::Uint(UintTy::U16) => 2,
+        PrimTy::Int(IntTy::I32) | PrimTy::Uint(UintTy::U32) | PrimTy::Float(FloatTy::F32) | PrimTy::Char => 4,
+        PrimTy::Int(IntTy::I64 | IntTy::Isize) | PrimTy::Uint(UintTy::U64 | UintTy::Usize) | PrimTy::Float(FloatTy::F64) => 8,
+        // Pointer and length
+        PrimTy::Str => 16
+    }
+}
+
+/// Smallest tag that can tell the variants apart.
+fn tag_size(variants: usize) -> u64 {
+    match variants {
+        0 | 1 => 0,
+        2..=256 => 1,
+        257..=65536 => 2,
+        _ => 4
+    }
+}
+
+/// How many times each local of the body is read, assigning to a local
+/// doesn't read it. Reads inside of a loop which doesn't declare the local
+/// can happen many times, so they count twice.
+fn count_reads(body: &Body) -> Vec<u32> {
+    let mut counter = ReadCounter {
+        body,
+        reads: vec![0; body.locals.len()],
+        decl_loops: vec![0; body.locals.len()],
+        loops: 0
+    };
+    counter.block(body.value);
+    counter.reads
+}
+
+struct ReadCounter<'a> {
+    body: &'a Body,
+    reads: Vec<u32>,
+    /// Number of loops around the declaration of each local.
+    decl_loops: Vec<u32>,
+    /// Number of loops around the current expression.
+    loops: u32
+}
+
+impl ReadCounter<'_> {
+    fn block(&mut self, id: BlockId) {
+        let block = self.body.block(id);
+        for stmt in block.stmts.iter() {
+            match self.body.stmt(*stmt).kind {
+                HirStmtKind::Let { local, init } => {
+                    if let Some(init) = init {
+                        self.expr(init);
+                    }
+                    self.decl_loops[local.index()] = self.loops;
+                },
+                HirStmtKind::Item(_) => {},
+                HirStmtKind::Expr(expr) => self.expr(expr)
+            }
+        }
+        if let Some(tail) = block.tail {
+            self.expr(tail);
+        }
+    }
+
+    fn read(&mut self, local: LocalId) {
+        let count = if self.loops > self.decl_loops[local.index()] { 2 } else { 1 };
+        self.reads[local.index()] += count;
+    }
+
+    fn expr(&mut self, id: ExprId) {
+        match self.body.expr(id).kind {
+            HirExprKind::Path(Res::Local(local)) => self.read(local),
+            HirExprKind::Path(_) | HirExprKind::Literal(_) | HirExprKind::Continue => {},
+            HirExprKind::Field(target, _) | HirExprKind::Unary(_, target) => self.expr(target),
+            HirExprKind::Assign(target, value) => {
+                // Assigned local is written, not read, but fields are
+                // assigned through their local
+                if !matches!(self.body.expr(target).kind, HirExprKind::Path(Res::Local(_))) {
+                    self.expr(target);
+                }
+                self.expr(value);
+            },
+            HirExprKind::Binary(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            },
+            HirExprKind::Call(target, ref args) => {
+                self.expr(target);
+                for arg in args.iter() {
+                    self.expr(*arg);
+                }
+            },
+            HirExprKind::If(condition, block, else_expr) => {
+                self.expr(condition);
+                self.block(block);
+                if let Some(else_expr) = else_expr {
+                    self.expr(else_expr);
+                }
+            },
+            HirExprKind::Block(block) => self.block(block),
+            HirExprKind::Loop(block) => {
+                self.loops += 1;
+                self.block(block);
+                self.loops -= 1;
+            },
+            HirExprKind::Break(value) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            },
+            HirExprKind::StructLit { ref fields, rest, .. } => {
+                for (_, field) in fields.iter() {
+                    self.expr(*field);
+                }
+                if let HirRestExpr::Valued(rest) = rest {
+                    self.expr(rest);
+                }
+            }
+        }
+    }
+}
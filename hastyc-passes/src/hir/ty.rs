@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use hastyc_common::identifiers::{ASTNodeID, SymbolStorage};
+
+use crate::passes::builtin::PrimTy;
+
+use super::{DefId, Hir};
+
+/// Type of a value in HIR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Prim(PrimTy),
+    /// Struct or enum.
+    Adt(DefId),
+    /// Function or tuple constructor, which is a value of its own type.
+    FnDef(DefId),
+    /// `()`
+    Void,
+    /// `!`, type of expressions that never finish, like `break`.
+    Never,
+    /// Type which is not known yet.
+    Var(TyVid),
+    /// Type of something erroneous, the error was already reported.
+    Err
+}
+
+/// Id of type inference variable, unique in its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TyVid(pub u32);
+
+impl TyVid {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Types of AST nodes, as found by `hir::typeck::TypeCheckPass`. This has
+/// inferred types of expressions, blocks and locals and declared types of
+/// definitions. Types in the table never contain inference variables.
+#[derive(Debug, Default)]
+pub struct TypeTable {
+    node_types: HashMap<ASTNodeID, Ty>
+}
+
+impl TypeTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: ASTNodeID, ty: Ty) {
+        self.node_types.insert(id, ty);
+    }
+
+    pub fn get(&self, id: ASTNodeID) -> Option<Ty> {
+        self.node_types.get(&id).copied()
+    }
+
+    /// All nodes with their types, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (ASTNodeID, Ty)> + '_ {
+        self.node_types.iter().map(|(id, ty)| (*id, *ty))
+    }
+}
+
+/// Renders type the way it would be written in the source.
+pub struct TypeDisplay<'a> {
+    ty: Ty,
+    hir: &'a Hir,
+    symbols: &'a SymbolStorage
+}
+
+impl<'a> TypeDisplay<'a> {
+    pub fn new(ty: Ty, hir: &'a Hir, symbols: &'a SymbolStorage) -> Self {
+        Self { ty, hir, symbols }
+    }
+}
+
+impl std::fmt::Display for TypeDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.ty {
+            Ty::Prim(prim) => write!(f, "{}", prim),
+            Ty::Adt(def) => f.write_str(self.hir.def(def).ident.text(self.symbols)),
+            Ty::FnDef(def) => write!(f, "fn {}", self.hir.def(def).ident.text(self.symbols)),
+            Ty::Void => f.write_str("()"),
+            Ty::Never => f.write_str("!"),
+            // Variables are only meaningful inside their body
+            Ty::Var(_) => f.write_str("_"),
+            Ty::Err => f.write_str("{error}")
+        }
+    }
+}
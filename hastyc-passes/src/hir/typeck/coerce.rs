@@ -0,0 +1,45 @@
+use crate::{hir::ty::Ty, passes::builtin::{FloatTy, IntTy, PrimTy}};
+
+use super::infer::{InferCtxt, VarKind};
+
+/// Whether value of type `found` can be used where `expected` is needed,
+/// unifying the types if it can. This is the only place where the types
+/// don't have to be equal:
+///
+/// - `!` coerces to any type, as there is never a value of it, so
+///   `let x: i32 = return;` is fine.
+/// - Nothing coerces to `!`, so `fn f() -> ! { 1 }` is an error.
+/// - Erroneous types coerce both ways, their error was already reported.
+///
+/// Otherwise the types must be equal, see `InferCtxt::unify`.
+pub fn coerce(infer: &mut InferCtxt, found: Ty, expected: Ty) -> bool {
+    // TODO: `T` to `T?`, once there are optional types
+    match (infer.resolve(found), infer.resolve(expected)) {
+        (Ty::Never, _) => true,
+        (Ty::Err, _) | (_, Ty::Err) => true,
+        (_, Ty::Never) => false,
+        (found, expected) => infer.unify(expected, found)
+    }
+}
+
+/// Type of an expression whose value comes from one of two branches,
+/// like arms of `if` or values of `break` out of the same loop. Branch
+/// that never finishes coerces to the other one, so it doesn't decide
+/// the type. None if the branches have different types.
+pub fn join(infer: &mut InferCtxt, a: Ty, b: Ty) -> Option<Ty> {
+    match (infer.resolve(a), infer.resolve(b)) {
+        (Ty::Never, _) => Some(b),
+        (_, Ty::Never) => Some(a),
+        _ => infer.unify(a, b).then_some(a)
+    }
+}
+
+/// Type of a literal without suffix, if nothing in the body decides it.
+/// Integers are `i32` and floats are `f64`.
+pub fn literal_default(kind: VarKind) -> Option<Ty> {
+    match kind {
+        VarKind::Int => Some(Ty::Prim(PrimTy::Int(IntTy::I32))),
+        VarKind::Float => Some(Ty::Prim(PrimTy::Float(FloatTy::F64))),
+        VarKind::General => None
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{hir::ty::{Ty, TyVid}, passes::builtin::PrimTy};
+
+/// What an inference variable can become.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarKind {
+    General,
+    /// Type of integer literal, any integer type.
+    Int,
+    /// Type of float literal, any float type.
+    Float
+}
+
+#[derive(Debug, Clone, Copy)]
+enum VarValue {
+    Unknown(VarKind),
+    Known(Ty)
+}
+
+/// Table of inference variables of a body.
+#[derive(Debug, Default)]
+pub struct InferCtxt {
+    vars: Vec<VarValue>
+}
+
+impl InferCtxt {
+    pub fn new_var(&mut self, kind: VarKind) -> Ty {
+        let vid = TyVid(self.vars.len() as u32);
+        self.vars.push(VarValue::Unknown(kind));
+        Ty::Var(vid)
+    }
+
+    /// Replace variables which are already known with their types.
+    pub fn resolve(&self, ty: Ty) -> Ty {
+        let mut ty = ty;
+        while let Ty::Var(vid) = ty {
+            match self.vars[vid.index()] {
+                VarValue::Known(known) => ty = known,
+                VarValue::Unknown(_) => break
+            }
+        }
+        ty
+    }
+
+    /// Kind of the variable, None if the type is not an unknown variable.
+    pub fn var_kind(&self, ty: Ty) -> Option<VarKind> {
+        match self.resolve(ty) {
+            Ty::Var(vid) => match self.vars[vid.index()] {
+                VarValue::Unknown(kind) => Some(kind),
+                VarValue::Known(_) => unreachable!()
+            },
+            _ => None
+        }
+    }
+
+    /// Bind the unknown variable to the erroneous type, once it was
+    /// reported that its type can't be known, so that it isn't again.
+    pub fn bind_err(&mut self, ty: Ty) {
+        if let Ty::Var(vid) = self.resolve(ty) {
+            self.vars[vid.index()] = VarValue::Known(Ty::Err);
+        }
+    }
+
+    /// Make types equal, binding variables if needed. Returns false if they
+    /// can't be equal. Erroneous types are equal to anything, `!` is only
+    /// equal to itself, as it is handled by `coerce::coerce`.
+    pub fn unify(&mut self, a: Ty, b: Ty) -> bool {
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        match (a, b) {
+            _ if a == b => true,
+            (Ty::Err, _) | (_, Ty::Err) => true,
+            (Ty::Var(a), Ty::Var(b)) => {
+                let (VarValue::Unknown(a_kind), VarValue::Unknown(b_kind)) = (self.vars[a.index()], self.vars[b.index()]) else {
+                    unreachable!()
+                };
+                let kind = match (a_kind, b_kind) {
+                    (VarKind::General, kind) | (kind, VarKind::General) => kind,
+                    _ if a_kind == b_kind => a_kind,
+                    _ => return false
+                };
+                self.vars[a.index()] = VarValue::Known(Ty::Var(b));
+                self.vars[b.index()] = VarValue::Unknown(kind);
+                true
+            },
+            (Ty::Var(vid), ty) | (ty, Ty::Var(vid)) => {
+                let VarValue::Unknown(kind) = self.vars[vid.index()] else { unreachable!() };
+                if !kind_accepts(kind, ty) {
+                    return false
+                }
+                self.vars[vid.index()] = VarValue::Known(ty);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+fn kind_accepts(kind: VarKind, ty: Ty) -> bool {
+    match kind {
+        VarKind::General => true,
+        VarKind::Int => matches!(ty, Ty::Prim(prim) if prim.is_integer()),
+        VarKind::Float => matches!(ty, Ty::Prim(PrimTy::Float(_)))
+    }
+}
@@ -0,0 +1,1206 @@
+use std::convert::Infallible;
+
+use hastyc_common::{error::{CommonErrorContext, ErrorDisplay, ErrorFmt}, expansion::ExpnKind, identifiers::{ASTNodeID, Ident, Symbol}, span::Span};
+use hastyc_parser::parser::{BinOp, BinOpKind, Lit, LitKind, UnOp, UnOpKind};
+
+use crate::{passes::{builtin::{FloatTy, PrimTy}, ASTPass, QueryContext}, util::find_similar_name};
+
+use self::{coerce::{coerce, join, literal_default}, infer::{InferCtxt, VarKind}};
+
+use super::{ty::{Ty, TypeDisplay, TypeTable}, BlockId, Body, CtorKind, DefId, DefKind, ExprId, Hir, HirExprKind, HirRestExpr, HirStmtKind, HirTy, HirTyKind, LocalId, Res, StmtId};
+
+pub mod infer;
+pub mod coerce;
+
+/// Pass checking the lowered bodies, so it has to run after
+/// `hir::lower::LowerToHir`. Errors are reported to the session
+/// without stopping, so that all of them are reported at once.
+#[derive(Debug, Default)]
+pub struct TypeCheckPass;
+
+impl<'ctx> ASTPass<'ctx> for TypeCheckPass {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "typeck"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["lower-to-hir"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let cx: &QueryContext = ctx;
+        let hir = cx.hir.as_ref().expect("package must be lowered before type checking");
+        let mut types = TypeTable::new();
+        for (&id, def) in hir.defs.iter() {
+            if let Some(ty) = def_ty(hir, id) {
+                types.insert(def.ast_id, ty);
+            }
+            let declared: Vec<&HirTy> = match def.kind {
+                DefKind::Fn { ref sig, .. } => sig.inputs.iter().chain(sig.output.iter()).collect(),
+                DefKind::Field { ref ty } => vec![ty],
+                _ => Vec::new()
+            };
+            for ty in declared {
+                check_declared_ty(cx, hir, ty);
+            }
+        }
+        // Nodes left with erroneous types, which is fine only if
+        // their errors were reported already
+        let mut unchecked = None;
+        for (_, body) in hir.bodies() {
+            let mut checker = BodyChecker {
+                hir,
+                body,
+                cx,
+                infer: InferCtxt::default(),
+                locals: vec![Ty::Err; body.locals.len()],
+                loops: Vec::new(),
+                node_types: Vec::new(),
+                literals: Vec::new()
+            };
+            checker.check();
+            checker.check_literals();
+            checker.check_unresolved();
+            unchecked = unchecked.or(checker.first_unchecked());
+            checker.write_types(&mut types);
+        }
+        if let Some(span) = unchecked.filter(|_| !cx.has_errors()) {
+            cx.emit(TypeCheckError::TypeAnnotationsNeeded { name: None, span });
+        }
+        ctx.set_types(types);
+        Ok(())
+    }
+}
+
+/// Declared type of a definition, None for modules. Tuple structs and
+/// variants have type of their constructor.
+fn def_ty(hir: &Hir, id: DefId) -> Option<Ty> {
+    let def = hir.def(id);
+    Some(match def.kind {
+        DefKind::Module { .. } => return None,
+        DefKind::Fn { .. }
+        | DefKind::Struct { ctor: CtorKind::Tuple, .. }
+        | DefKind::Variant { ctor: CtorKind::Tuple, .. } => Ty::FnDef(id),
+        DefKind::Struct { .. } | DefKind::Enum { .. } => Ty::Adt(id),
+        DefKind::Variant { .. } => Ty::Adt(def.parent.expect("variant without enum")),
+        DefKind::Field { ref ty } => declared_ty(hir, ty).unwrap_or(Ty::Err)
+    })
+}
+
+/// Report type written in the source which names something that is not
+/// a type, like a function.
+fn check_declared_ty(cx: &QueryContext, hir: &Hir, ty: &HirTy) {
+    let HirTyKind::Path(Res::Def(id)) = ty.kind else { return };
+    let def = hir.def(id);
+    if !matches!(def.kind, DefKind::Struct { .. } | DefKind::Enum { .. }) {
+        cx.emit(TypeCheckError::NotAType { name: def.ident.symbol, descr: def.kind.descr(), span: ty.span, decl_span: declared_at(def.span) });
+    }
+}
+
+/// Type written in the source, None if it has to be inferred.
+pub fn declared_ty(hir: &Hir, ty: &HirTy) -> Option<Ty> {
+    Some(match ty.kind {
+        HirTyKind::Path(Res::PrimTy(prim)) => Ty::Prim(prim),
+        HirTyKind::Path(Res::Def(def)) => match hir.def(def).kind {
+            DefKind::Struct { .. } | DefKind::Enum { .. } => Ty::Adt(def),
+            _ => Ty::Err
+        },
+        HirTyKind::Path(_) => Ty::Err,
+        // TODO: Self type, once there are impls
+        HirTyKind::SelfTy => Ty::Err,
+        HirTyKind::Void => Ty::Void,
+        HirTyKind::Never => Ty::Never,
+        HirTyKind::Infer => return None
+    })
+}
+
+/// Infers types of a body bottom-up, checking them along the way.
+struct BodyChecker<'a, 'ctx> {
+    hir: &'a Hir,
+    body: &'a Body,
+    cx: &'a QueryContext<'ctx>,
+    infer: InferCtxt,
+    /// Types of locals, set once their declaration is checked.
+    locals: Vec<Ty>,
+    /// Type of values the enclosing loops break with, innermost
+    /// last. None if the loop has no `break` yet.
+    loops: Vec<Option<Ty>>,
+    /// Types of checked expressions and blocks, by their AST nodes,
+    /// with their spans for diagnostics.
+    node_types: Vec<(ASTNodeID, Span, Ty)>,
+    /// Number literals with their types, which are checked to fit
+    /// into the type once the whole body is inferred.
+    literals: Vec<(ExprId, Ty)>
+}
+
+impl BodyChecker<'_, '_> {
+    fn check(&mut self) {
+        let body = self.body;
+        for param in body.params.iter() {
+            self.locals[param.index()] = match body.local(*param).ty {
+                Some(ref ty) => self.ty_of_hir(ty),
+                None => Ty::Err
+            };
+        }
+        let found = self.check_block(body.value);
+        self.check_return(found);
+    }
+
+    /// Check value of the body against return type of its function.
+    fn check_return(&mut self, found: Ty) {
+        let body = self.body;
+        let DefKind::Fn { ref sig, .. } = self.hir.def(body.owner).kind else { return };
+        let block = body.block(body.value);
+        // Blocks without tail are pointed at as a whole
+        let span = block.tail.map_or(block.span, |tail| body.expr(tail).span);
+        let Some(ref output) = sig.output else {
+            if !coerce(&mut self.infer, found, Ty::Void) {
+                self.cx.emit(TypeCheckError::UnexpectedReturnValue {
+                    found: self.display(found),
+                    suggested: self.suggestable(found),
+                    span,
+                    sig_span: sig.span
+                });
+            }
+            return
+        };
+        match self.ty_of_hir(output) {
+            Ty::Never => {
+                if !matches!(self.infer.resolve(found), Ty::Never | Ty::Err) {
+                    self.cx.emit(TypeCheckError::NeverReturns { span, ret_span: output.span });
+                }
+            },
+            expected => {
+                if !coerce(&mut self.infer, found, expected) {
+                    self.cx.emit(TypeCheckError::ReturnTypeMismatch {
+                        expected: self.display(expected),
+                        found: self.display(found),
+                        span,
+                        ret_span: output.span
+                    });
+                }
+            }
+        }
+    }
+
+    /// Type of a number literal. Its suffix decides the type, otherwise
+    /// it is inferred from how the literal is used. Literals with invalid
+    /// suffixes are errors, so that they don't cause mismatches.
+    fn check_number(&mut self, lit: &Lit, span: Span) -> Ty {
+        let is_float = matches!(lit.kind, LitKind::Float);
+        let kind = if is_float { VarKind::Float } else { VarKind::Int };
+        let Some(suffix) = lit.suffix else {
+            return self.infer.new_var(kind)
+        };
+        match PrimTy::from_symbol(suffix) {
+            Some(prim @ PrimTy::Float(_)) if is_float => Ty::Prim(prim),
+            Some(prim) if !is_float && prim.is_integer() => Ty::Prim(prim),
+            prim => {
+                // Integers with float suffix are suggested to be written as floats
+                let as_float = (!is_float && matches!(prim, Some(PrimTy::Float(_))))
+                    .then(|| format!("{}.0{}", lit.symbol, suffix));
+                self.cx.emit(TypeCheckError::InvalidLiteralSuffix { suffix, is_float, as_float, span });
+                Ty::Err
+            }
+        }
+    }
+
+    /// Check that number literals fit into their final types, so that
+    /// `let x: u8 = 300;` is an error. Floats only have to be finite.
+    fn check_literals(&self) {
+        for &(id, ty) in self.literals.iter() {
+            let Ty::Prim(prim) = self.finalize(ty) else { continue };
+            let expr = self.body.expr(id);
+            let HirExprKind::Literal(ref lit) = expr.kind else { unreachable!() };
+            let text = lit.symbol.as_str();
+            let (fits, min, max) = match prim {
+                PrimTy::Float(FloatTy::F32) => (
+                    text.parse::<f32>().is_ok_and(f32::is_finite),
+                    format!("{:e}", f32::MIN),
+                    format!("{:e}", f32::MAX)
+                ),
+                PrimTy::Float(FloatTy::F64) => (
+                    text.parse::<f64>().is_ok_and(f64::is_finite),
+                    format!("{:e}", f64::MIN),
+                    format!("{:e}", f64::MAX)
+                ),
+                prim => {
+                    let Some((min, max)) = prim.int_range() else { continue };
+                    let fits = text.parse::<i128>().is_ok_and(|value| (min..=max).contains(&value));
+                    (fits, min.to_string(), max.to_string())
+                }
+            };
+            if !fits {
+                self.cx.emit(TypeCheckError::LiteralOutOfRange { ty: prim, min, max, span: expr.span });
+            }
+        }
+    }
+
+    /// Report variables whose types nothing in the body decided, like
+    /// `let a;` which is never assigned. Each one is reported once, at
+    /// the first local of its type, or the first expression if no local
+    /// has it.
+    fn check_unresolved(&self) {
+        let mut reported = Vec::new();
+        let locals = self.body.locals.iter().zip(self.locals.iter())
+            .map(|(local, ty)| (local.span, Some(local.ident.symbol), *ty));
+        let exprs = self.node_types.iter().map(|(_, span, ty)| (*span, None, *ty));
+        for (span, name, ty) in locals.chain(exprs) {
+            let var = self.infer.resolve(ty);
+            if self.infer.var_kind(var) != Some(VarKind::General) || reported.contains(&var) {
+                continue
+            }
+            reported.push(var);
+            self.cx.emit(TypeCheckError::TypeAnnotationsNeeded { name, span });
+        }
+    }
+
+    /// First node whose type ended up erroneous, in the order they were
+    /// checked.
+    fn first_unchecked(&self) -> Option<Span> {
+        let locals = self.body.locals.iter().zip(self.locals.iter()).map(|(local, ty)| (local.span, *ty));
+        let exprs = self.node_types.iter().map(|(_, span, ty)| (*span, *ty));
+        locals.chain(exprs)
+            .find(|(_, ty)| self.finalize(*ty) == Ty::Err)
+            .map(|(span, _)| span)
+    }
+
+    /// Store types of the body in the table, once it is checked.
+    fn write_types(&self, types: &mut TypeTable) {
+        for (ast_id, _, ty) in self.node_types.iter() {
+            types.insert(*ast_id, self.finalize(*ty));
+        }
+        for (local, ty) in self.body.locals.iter().zip(self.locals.iter()) {
+            types.insert(local.ast_id, self.finalize(*ty));
+        }
+    }
+
+    /// Type with inference variables replaced. Literals which were not
+    /// constrained get their default types, other unknown types are errors.
+    fn finalize(&self, ty: Ty) -> Ty {
+        match self.infer.resolve(ty) {
+            var @ Ty::Var(_) => self.infer.var_kind(var)
+                .and_then(literal_default)
+                .unwrap_or(Ty::Err),
+            ty => ty
+        }
+    }
+
+    fn ty_of_hir(&mut self, ty: &HirTy) -> Ty {
+        match declared_ty(self.hir, ty) {
+            Some(ty) => ty,
+            None => self.infer.new_var(VarKind::General)
+        }
+    }
+
+    /// Type of a path expression, reporting paths of definitions which
+    /// are not values, like modules.
+    fn ty_of_res(&self, res: Res, span: Span) -> Ty {
+        match res {
+            Res::Local(local) => self.locals[local.index()],
+            Res::Def(id) => self.def_value_ty(id).unwrap_or_else(|| {
+                let def = self.hir.def(id);
+                self.cx.emit(TypeCheckError::NotAValue { name: def.ident.symbol, descr: def.kind.descr(), span, decl_span: declared_at(def.span) });
+                Ty::Err
+            }),
+            Res::PrimTy(prim) => {
+                self.cx.emit(TypeCheckError::NotAValue { name: Symbol::intern(&prim.to_string()), descr: "builtin type", span, decl_span: None });
+                Ty::Err
+            },
+            Res::Err => Ty::Err
+        }
+    }
+
+    /// Type of the definition used as a value, None if it is not one.
+    fn def_value_ty(&self, id: DefId) -> Option<Ty> {
+        let def = self.hir.def(id);
+        Some(match def.kind {
+            DefKind::Fn { .. }
+            | DefKind::Struct { ctor: CtorKind::Tuple, .. }
+            | DefKind::Variant { ctor: CtorKind::Tuple, .. } => Ty::FnDef(id),
+            DefKind::Struct { ctor: CtorKind::Unit, .. } => Ty::Adt(id),
+            DefKind::Variant { ctor: CtorKind::Unit, .. } => Ty::Adt(def.parent.expect("variant without enum")),
+            _ => return None
+        })
+    }
+
+    fn field_def_ty(&mut self, field: DefId) -> Ty {
+        match self.hir.def(field).kind {
+            DefKind::Field { ref ty } => self.ty_of_hir(ty),
+            _ => Ty::Err
+        }
+    }
+
+    fn find_field(&self, fields: &[DefId], name: Symbol) -> Option<DefId> {
+        fields.iter().copied().find(|field| self.hir.def(*field).ident.symbol == name)
+    }
+
+    /// Report mismatch if `found` can't be coerced to `expected`,
+    /// `expected_span` is the node that requires the type, eg. type
+    /// annotation.
+    fn expect(&mut self, expected: Ty, found: Ty, span: Span, expected_span: Option<Span>) {
+        if !coerce(&mut self.infer, found, expected) {
+            self.report_mismatch(expected, found, span, expected_span);
+        }
+    }
+
+    /// Type of two branches, reporting mismatch of `found` against the
+    /// first one if they have different types.
+    fn join(&mut self, first: Ty, found: Ty, span: Span, expected_span: Option<Span>) -> Ty {
+        join(&mut self.infer, first, found).unwrap_or_else(|| {
+            self.report_mismatch(first, found, span, expected_span);
+            first
+        })
+    }
+
+    fn report_mismatch(&self, expected: Ty, found: Ty, span: Span, expected_span: Option<Span>) {
+        self.cx.emit(TypeCheckError::TypeMismatch {
+            expected: self.display(expected),
+            found: self.display(found),
+            span,
+            expected_span
+        });
+    }
+
+    fn check_block(&mut self, id: BlockId) -> Ty {
+        let block = self.body.block(id);
+        let mut diverges = false;
+        for stmt in block.stmts.iter() {
+            diverges |= self.check_stmt(*stmt);
+        }
+        let ty = match block.tail {
+            Some(tail) => self.check_expr(tail),
+            None if diverges => Ty::Never,
+            None => Ty::Void
+        };
+        self.node_types.push((block.ast_id, block.span, ty));
+        ty
+    }
+
+    /// Check statement, returning whether it never finishes.
+    fn check_stmt(&mut self, id: StmtId) -> bool {
+        match self.body.stmt(id).kind {
+            HirStmtKind::Let { local, init } => {
+                self.check_let(local, init);
+                false
+            },
+            HirStmtKind::Item(_) => false,
+            HirStmtKind::Expr(expr) => {
+                let ty = self.check_expr(expr);
+                self.infer.resolve(ty) == Ty::Never
+            }
+        }
+    }
+
+    fn check_let(&mut self, local: LocalId, init: Option<ExprId>) {
+        let body = self.body;
+        // Without annotation the local has the type of its value, even if
+        // that is `!`, which would coerce to a new variable without deciding it
+        let declared = body.local(local).ty.as_ref()
+            .filter(|ty| !matches!(ty.kind, HirTyKind::Infer))
+            .map(|ty| {
+                check_declared_ty(self.cx, self.hir, ty);
+                (self.ty_of_hir(ty), ty.span)
+            });
+        let ty = match (declared, init) {
+            (Some((ty, span)), Some(init)) => {
+                let found = self.check_expr(init);
+                self.expect(ty, found, body.expr(init).span, Some(span));
+                ty
+            },
+            (Some((ty, _)), None) => ty,
+            (None, Some(init)) => self.check_expr(init),
+            (None, None) => self.infer.new_var(VarKind::General)
+        };
+        self.locals[local.index()] = ty;
+    }
+
+    fn check_expr(&mut self, id: ExprId) -> Ty {
+        let ty = self.check_expr_kind(id);
+        let expr = self.body.expr(id);
+        self.node_types.push((expr.ast_id, expr.span, ty));
+        ty
+    }
+
+    fn check_expr_kind(&mut self, id: ExprId) -> Ty {
+        let body = self.body;
+        let expr = body.expr(id);
+        match expr.kind {
+            HirExprKind::Path(res) => self.ty_of_res(res, expr.span),
+            HirExprKind::Literal(ref lit) => match lit.kind {
+                LitKind::Integer | LitKind::Float => {
+                    let ty = self.check_number(lit, expr.span);
+                    self.literals.push((id, ty));
+                    ty
+                },
+                LitKind::Bool => Ty::Prim(PrimTy::Bool),
+                LitKind::Char => Ty::Prim(PrimTy::Char),
+                LitKind::String => Ty::Prim(PrimTy::Str)
+            },
+            HirExprKind::Field(target, ref field) => {
+                let target_ty = self.check_expr(target);
+                self.check_field(target, target_ty, field)
+            },
+            HirExprKind::Assign(target, value) => {
+                let target = self.check_expr(target);
+                let found = self.check_expr(value);
+                self.expect(target, found, body.expr(value).span, None);
+                Ty::Void
+            },
+            HirExprKind::Unary(ref op, operand) => self.check_unary(op, operand),
+            HirExprKind::Binary(ref op, lhs, rhs) => self.check_binary(op, lhs, rhs),
+            HirExprKind::Call(callee, ref args) => self.check_call(callee, args, expr.span),
+            HirExprKind::If(condition, block, else_expr) => {
+                let condition_ty = self.check_expr(condition);
+                self.check_condition(condition_ty, condition, expr.span);
+                let then_ty = self.check_block(block);
+                let Some(else_expr) = else_expr else { return Ty::Void };
+                let else_ty = self.check_expr(else_expr);
+                self.join(then_ty, else_ty, body.expr(else_expr).span, Some(body.block(block).span))
+            },
+            HirExprKind::Block(block) => self.check_block(block),
+            HirExprKind::Loop(block) => {
+                self.loops.push(None);
+                self.check_block(block);
+                // Loop without `break` never finishes
+                self.loops.pop().flatten().unwrap_or(Ty::Never)
+            },
+            HirExprKind::Break(value) => {
+                let (ty, span) = match value {
+                    Some(value) => (self.check_expr(value), body.expr(value).span),
+                    None => (Ty::Void, expr.span)
+                };
+                match self.loops.last().copied() {
+                    Some(Some(first)) => {
+                        let ty = self.join(first, ty, span, None);
+                        *self.loops.last_mut().unwrap() = Some(ty);
+                    },
+                    Some(None) => *self.loops.last_mut().unwrap() = Some(ty),
+                    None => {}
+                }
+                Ty::Never
+            },
+            HirExprKind::Continue => Ty::Never,
+            HirExprKind::StructLit { res, ref fields, rest } => self.check_struct_lit(res, fields, rest, expr.span)
+        }
+    }
+
+    /// Check fields of a struct literal against the struct or variant.
+    /// Every field has to be given, unless the rest are copied from
+    /// `..base` or left without value with `..` alone.
+    fn check_struct_lit(&mut self, res: Res, fields: &[(Ident, ExprId)], rest: HirRestExpr, span: Span) -> Ty {
+        let body = self.body;
+        let Res::Def(id) = res else {
+            for (_, value) in fields.iter() {
+                self.check_expr(*value);
+            }
+            return Ty::Err
+        };
+        let def = self.hir.def(id);
+        let (ty, def_fields): (Ty, &[DefId]) = match def.kind {
+            DefKind::Struct { ref fields, .. } => (Ty::Adt(id), fields),
+            DefKind::Variant { ref fields, .. } => (Ty::Adt(def.parent.expect("variant without enum")), fields),
+            _ => (Ty::Err, &[])
+        };
+        let mut given = Vec::new();
+        for (ident, value) in fields.iter() {
+            let found = self.check_expr(*value);
+            if ty == Ty::Err {
+                continue
+            }
+            match self.find_field(def_fields, ident.symbol) {
+                Some(field) => {
+                    given.push(field);
+                    let expected = self.field_def_ty(field);
+                    self.expect(expected, found, body.expr(*value).span, Some(self.hir.def(field).span));
+                },
+                None => {
+                    let names = def_fields.iter().map(|field| self.hir.def(*field).ident.symbol);
+                    self.cx.emit(TypeCheckError::UnknownField {
+                        name: def.ident.symbol,
+                        descr: def.kind.descr(),
+                        field: ident.symbol,
+                        suggestion: find_similar_name(ident.symbol, names),
+                        span: ident.span,
+                        decl_span: def.span
+                    });
+                }
+            }
+        }
+        match rest {
+            HirRestExpr::Valued(base) => {
+                let found = self.check_expr(base);
+                self.expect(ty, found, body.expr(base).span, None);
+            },
+            HirRestExpr::Rest(_) => {},
+            HirRestExpr::None => {
+                let missing: Vec<Symbol> = def_fields.iter()
+                    .filter(|field| !given.contains(field))
+                    .map(|field| self.hir.def(*field).ident.symbol)
+                    .collect();
+                if !missing.is_empty() {
+                    self.cx.emit(TypeCheckError::MissingFields { name: def.ident.symbol, fields: missing, span, decl_span: def.span });
+                }
+            }
+        }
+        ty
+    }
+
+    /// Check that condition of `if` is bool, `if_span` tells whether
+    /// the `if` was written as such or comes from a desugared loop.
+    fn check_condition(&mut self, ty: Ty, condition: ExprId, if_span: Span) {
+        if coerce(&mut self.infer, ty, Ty::Prim(PrimTy::Bool)) {
+            return
+        }
+        let construct = match if_span.ctxt.expn_data().map(|data| data.kind) {
+            Some(ExpnKind::Desugaring("while loop")) => "while",
+            Some(ExpnKind::Desugaring("for loop")) => "for",
+            _ => "if"
+        };
+        let span = self.body.expr(condition).span;
+        self.cx.emit(TypeCheckError::NonBoolCondition {
+            construct,
+            found: self.display(ty),
+            // Condition of `for` is created by desugaring, so it can't be rewritten
+            compare_to_zero: self.is_integer(self.infer.resolve(ty)) && span.ctxt.is_root(),
+            span
+        });
+    }
+
+    /// Type of the field of `target`, reporting fields which it doesn't
+    /// have. Only structs have fields.
+    fn check_field(&mut self, target: ExprId, target_ty: Ty, field: &Ident) -> Ty {
+        let ty = self.infer.resolve(target_ty);
+        let fields: &[DefId] = match ty {
+            _ if Self::is_unchecked(ty) => return Ty::Err,
+            Ty::Var(_) if self.infer.var_kind(ty) == Some(VarKind::General) => {
+                // Nothing decided the type yet, so its fields are not known
+                self.cx.emit(TypeCheckError::TypeAnnotationsNeeded { name: None, span: self.body.expr(target).span });
+                self.infer.bind_err(ty);
+                return Ty::Err
+            },
+            Ty::Adt(id) => match self.hir.def(id).kind {
+                DefKind::Struct { ref fields, .. } => fields,
+                _ => &[]
+            },
+            _ => &[]
+        };
+        if let Some(field) = self.find_field(fields, field.symbol) {
+            return self.field_def_ty(field)
+        }
+        let names = fields.iter().map(|field| self.hir.def(*field).ident.symbol);
+        self.cx.emit(TypeCheckError::NoField {
+            field: field.symbol,
+            ty: self.display(ty),
+            has_fields: !fields.is_empty(),
+            suggestion: find_similar_name(field.symbol, names),
+            span: field.span
+        });
+        Ty::Err
+    }
+
+    /// Check that the callee can be called with the arguments. Callees which
+    /// are definitions are checked even if they are not callable,
+    /// other values only if their type is a function.
+    fn check_call(&mut self, callee: ExprId, args: &[ExprId], span: Span) -> Ty {
+        let body = self.body;
+        let callee_ty = match body.expr(callee).kind {
+            // Definitions which are not values are reported as not callable
+            HirExprKind::Path(Res::Def(def)) if self.def_value_ty(def).is_none() => {
+                let expr = body.expr(callee);
+                self.node_types.push((expr.ast_id, expr.span, Ty::Err));
+                Ty::Err
+            },
+            _ => self.check_expr(callee)
+        };
+        let arg_tys = args.iter().map(|arg| self.check_expr(*arg)).collect::<Vec<_>>();
+
+        let callee = body.expr(callee);
+        let def_id = match (&callee.kind, self.infer.resolve(callee_ty)) {
+            (&HirExprKind::Path(Res::Def(def)), _) | (_, Ty::FnDef(def)) => def,
+            (_, ty) if Self::is_unchecked(ty) => return Ty::Err,
+            (_, ty) if self.infer.var_kind(ty) == Some(VarKind::General) => {
+                self.cx.emit(TypeCheckError::TypeAnnotationsNeeded { name: None, span: callee.span });
+                self.infer.bind_err(ty);
+                return Ty::Err
+            },
+            (_, ty) => {
+                self.cx.emit(TypeCheckError::NotCallableValue { ty: self.display(ty), span: callee.span });
+                return Ty::Err
+            }
+        };
+        let def = self.hir.def(def_id);
+        let (params, decl_span, output) = match def.kind {
+            DefKind::Fn { ref sig, .. } => {
+                let output = match sig.output {
+                    Some(ref ty) => self.ty_of_hir(ty),
+                    None => Ty::Void
+                };
+                let params = sig.inputs.iter()
+                    .map(|ty| (self.ty_of_hir(ty), declared_at(ty.span)))
+                    .collect::<Vec<_>>();
+                (params, declared_at(sig.span), output)
+            },
+            // Tuple structs and variants are called to construct them
+            DefKind::Struct { ref fields, ctor: CtorKind::Tuple }
+            | DefKind::Variant { ref fields, ctor: CtorKind::Tuple } => {
+                let output = match def.kind {
+                    DefKind::Variant { .. } => Ty::Adt(def.parent.expect("variant without enum")),
+                    _ => Ty::Adt(def_id)
+                };
+                let params = fields.iter()
+                    .map(|field| (self.field_def_ty(*field), Some(self.hir.def(*field).span)))
+                    .collect::<Vec<_>>();
+                (params, Some(def.span), output)
+            },
+            ref kind => {
+                self.cx.emit(TypeCheckError::NotCallable {
+                    name: def.ident.symbol,
+                    descr: kind.descr(),
+                    span: callee.span,
+                    decl_span: def.span
+                });
+                return Ty::Err
+            }
+        };
+        if args.len() != params.len() {
+            self.cx.emit(TypeCheckError::ArgCountMismatch {
+                name: def.ident.symbol,
+                expected: params.len(),
+                found: args.len(),
+                span,
+                decl_span
+            });
+        }
+        for ((expected, param_span), (arg, found)) in params.into_iter().zip(args.iter().zip(arg_tys)) {
+            self.expect(expected, found, body.expr(*arg).span, param_span);
+        }
+        output
+    }
+
+    fn is_integer(&self, ty: Ty) -> bool {
+        match self.infer.var_kind(ty) {
+            Some(kind) => kind != VarKind::Float,
+            None => matches!(ty, Ty::Prim(prim) if prim.is_integer())
+        }
+    }
+
+    fn is_numeric(&self, ty: Ty) -> bool {
+        self.infer.var_kind(ty).is_some() || matches!(ty, Ty::Prim(prim) if prim.is_numeric())
+    }
+
+    fn is_bool(&self, ty: Ty) -> bool {
+        self.infer.var_kind(ty) == Some(VarKind::General) || ty == Ty::Prim(PrimTy::Bool)
+    }
+
+    /// Types which can't be checked, as they are already erroneous
+    /// or their expression never finishes.
+    fn is_unchecked(ty: Ty) -> bool {
+        matches!(ty, Ty::Err | Ty::Never)
+    }
+
+    fn check_unary(&mut self, op: &UnOp, operand: ExprId) -> Ty {
+        let ty = self.check_expr(operand);
+        let ty = self.infer.resolve(ty);
+        if Self::is_unchecked(ty) {
+            return ty
+        }
+        let valid = match op.kind {
+            UnOpKind::Neg => {
+                self.is_numeric(ty)
+                && !matches!(ty, Ty::Prim(PrimTy::Uint(_)))
+            },
+            UnOpKind::Not => self.is_integer(ty) || self.is_bool(ty)
+        };
+        if !valid {
+            self.cx.emit(TypeCheckError::UnaryOpMismatch {
+                verb: unary_verb(&op.kind),
+                ty: self.display(ty),
+                span: self.body.expr(operand).span,
+                op_span: op.span
+            });
+            return Ty::Err
+        }
+        ty
+    }
+
+    fn check_binary(&mut self, op: &BinOp, lhs: ExprId, rhs: ExprId) -> Ty {
+        let lhs_ty = self.check_expr(lhs);
+        let rhs_ty = self.check_expr(rhs);
+        let (l, r) = (self.infer.resolve(lhs_ty), self.infer.resolve(rhs_ty));
+        let bool_ty = Ty::Prim(PrimTy::Bool);
+
+        let (valid, ty) = match op.kind {
+            BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul | BinOpKind::Div | BinOpKind::Rem => {
+                (self.is_numeric(l) && self.is_numeric(r), l)
+            },
+            BinOpKind::BitAnd | BinOpKind::BitOr | BinOpKind::BitXor => {
+                ((self.is_integer(l) && self.is_integer(r)) || (self.is_bool(l) && self.is_bool(r)), l)
+            },
+            // Shift amount doesn't have to be of the same type
+            BinOpKind::Shl | BinOpKind::Shr => (self.is_integer(l) && self.is_integer(r), l),
+            BinOpKind::And | BinOpKind::Or => (self.is_bool(l) && self.is_bool(r), bool_ty),
+            BinOpKind::Eq | BinOpKind::Ne => (true, bool_ty),
+            BinOpKind::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge => {
+                let ordered = |ty| self.is_numeric(ty) || ty == Ty::Prim(PrimTy::Char);
+                (ordered(l) && ordered(r), bool_ty)
+            }
+        };
+        if Self::is_unchecked(l) || Self::is_unchecked(r) {
+            return if ty == bool_ty { ty } else { Ty::Err }
+        }
+        let valid = valid && match op.kind {
+            BinOpKind::Shl | BinOpKind::Shr => true,
+            BinOpKind::And | BinOpKind::Or => self.infer.unify(l, bool_ty) && self.infer.unify(r, bool_ty),
+            _ => self.infer.unify(l, r)
+        };
+        if !valid {
+            self.cx.emit(TypeCheckError::BinaryOpMismatch {
+                verb: binary_verb(&op.kind),
+                lhs: self.display(l),
+                rhs: self.display(r),
+                lhs_span: self.body.expr(lhs).span,
+                rhs_span: self.body.expr(rhs).span
+            });
+            // Comparisons are still bool, so that errors don't cascade
+            return if ty == bool_ty { ty } else { Ty::Err }
+        }
+        ty
+    }
+
+    /// Type as it could be written in the source, if it can be.
+    /// Literals are suggested with their default types.
+    fn suggestable(&self, ty: Ty) -> Option<String> {
+        match self.finalize(ty) {
+            ty @ (Ty::Prim(_) | Ty::Adt(_)) => Some(self.display(ty)),
+            _ => None
+        }
+    }
+
+    /// Render type for diagnostics.
+    fn display(&self, ty: Ty) -> String {
+        match self.infer.resolve(ty) {
+            var @ Ty::Var(_) => match self.infer.var_kind(var) {
+                Some(VarKind::Int) => "{integer}".to_string(),
+                Some(VarKind::Float) => "{float}".to_string(),
+                _ => "_".to_string()
+            },
+            ty => TypeDisplay::new(ty, self.hir, &self.cx.package.symbol_storage).to_string()
+        }
+    }
+}
+
+fn unary_verb(op: &UnOpKind) -> &'static str {
+    match op {
+        UnOpKind::Neg => "negate",
+        UnOpKind::Not => "apply `!` to"
+    }
+}
+
+fn binary_verb(op: &BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "add",
+        BinOpKind::Sub => "subtract",
+        BinOpKind::Mul => "multiply",
+        BinOpKind::Div => "divide",
+        BinOpKind::Rem => "take remainder of",
+        BinOpKind::And => "apply `&&` to",
+        BinOpKind::Or => "apply `||` to",
+        BinOpKind::BitAnd => "apply `&` to",
+        BinOpKind::BitOr => "apply `|` to",
+        BinOpKind::BitXor => "apply `^` to",
+        BinOpKind::Shl | BinOpKind::Shr => "shift",
+        BinOpKind::Eq | BinOpKind::Ne | BinOpKind::Lt
+        | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge => "compare"
+    }
+}
+
+#[derive(Debug)]
+pub enum TypeCheckError {
+    /// Function or tuple constructor called with a wrong number of arguments.
+    ArgCountMismatch {
+        name: Symbol,
+        expected: usize,
+        found: usize,
+        /// Span of the call.
+        span: Span,
+        /// Span of the signature or of the tuple struct or variant,
+        /// None for intrinsics.
+        decl_span: Option<Span>
+    },
+    /// Called definition is not a function nor a tuple constructor.
+    NotCallable {
+        name: Symbol,
+        /// Kind of the called definition, eg. "module".
+        descr: &'static str,
+        /// Span of the callee.
+        span: Span,
+        decl_span: Span
+    },
+    /// Called value is not a function, like an integer.
+    NotCallableValue {
+        ty: String,
+        span: Span
+    },
+    /// Path used as a value names something which is not one, like a module.
+    NotAValue {
+        name: Symbol,
+        descr: &'static str,
+        span: Span,
+        /// None for builtin types and intrinsics.
+        decl_span: Option<Span>
+    },
+    /// Path used as a type names something which is not one, like a function.
+    NotAType {
+        name: Symbol,
+        descr: &'static str,
+        span: Span,
+        /// None for intrinsics.
+        decl_span: Option<Span>
+    },
+    /// Expression has different type than the one required.
+    TypeMismatch {
+        expected: String,
+        found: String,
+        span: Span,
+        /// Node which requires the expected type, like type annotation.
+        expected_span: Option<Span>
+    },
+    /// Binary operator can't be applied to types of its operands.
+    BinaryOpMismatch {
+        /// What the operator does, eg. "add".
+        verb: &'static str,
+        lhs: String,
+        rhs: String,
+        lhs_span: Span,
+        rhs_span: Span
+    },
+    /// Unary operator can't be applied to type of its operand.
+    UnaryOpMismatch {
+        verb: &'static str,
+        ty: String,
+        span: Span,
+        op_span: Span
+    },
+    /// Value of function body doesn't match the declared return type.
+    ReturnTypeMismatch {
+        expected: String,
+        found: String,
+        /// Span of the tail expression, or the body if it has none.
+        span: Span,
+        ret_span: Span
+    },
+    /// Function without declared return type has body with a value.
+    UnexpectedReturnValue {
+        found: String,
+        /// Return type to suggest, if the type can be written.
+        suggested: Option<String>,
+        span: Span,
+        sig_span: Span
+    },
+    /// Function returning `!` has body that can finish.
+    NeverReturns {
+        span: Span,
+        ret_span: Span
+    },
+    /// Condition of `if` or a loop is not bool.
+    NonBoolCondition {
+        /// Keyword of the construct, eg. "while".
+        construct: &'static str,
+        found: String,
+        /// Whether to suggest comparing the condition with zero.
+        compare_to_zero: bool,
+        span: Span
+    },
+    /// Number literal has suffix which is not a type of such numbers.
+    InvalidLiteralSuffix {
+        suffix: Symbol,
+        is_float: bool,
+        /// The literal written as a float, for integers with float suffix.
+        as_float: Option<String>,
+        span: Span
+    },
+    /// Number literal doesn't fit into its type.
+    LiteralOutOfRange {
+        ty: PrimTy,
+        min: String,
+        max: String,
+        span: Span
+    },
+    /// Field access of a field which the type doesn't have.
+    NoField {
+        field: Symbol,
+        ty: String,
+        /// Whether the type has any fields, only structs have them.
+        has_fields: bool,
+        suggestion: Option<Symbol>,
+        /// Span of the field name.
+        span: Span
+    },
+    /// Struct literal gives a field which the struct doesn't have.
+    UnknownField {
+        /// Name of the struct or variant.
+        name: Symbol,
+        descr: &'static str,
+        field: Symbol,
+        suggestion: Option<Symbol>,
+        span: Span,
+        decl_span: Span
+    },
+    /// Struct literal doesn't give some of the fields.
+    MissingFields {
+        name: Symbol,
+        fields: Vec<Symbol>,
+        /// Span of the whole literal.
+        span: Span,
+        decl_span: Span
+    },
+    /// Type of a local or expression wasn't decided by anything in the body,
+    /// or it was needed before it was decided.
+    TypeAnnotationsNeeded {
+        /// Name of the local, None for expressions.
+        name: Option<Symbol>,
+        span: Span
+    }
+}
+
+/// Span of the declaration to point at in notes, None for intrinsics of
+/// the prelude, which aren't declared in any source.
+fn declared_at(span: Span) -> Option<Span> {
+    Some(span).filter(|span| !span.is_dummy())
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, word)
+    } else {
+        format!("{} {}s", count, word)
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// Names joined like `a`, `b` and `c`.
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last)
+    }
+}
+
+impl<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> for TypeCheckError {
+    fn code(&self) -> Option<&'static str> {
+        Some(match self {
+            TypeCheckError::ArgCountMismatch { .. } => "H0300",
+            TypeCheckError::NotCallable { .. } => "H0301",
+            TypeCheckError::TypeMismatch { .. } => "H0302",
+            TypeCheckError::BinaryOpMismatch { .. } => "H0303",
+            TypeCheckError::UnaryOpMismatch { .. } => "H0304",
+            TypeCheckError::ReturnTypeMismatch { .. } => "H0305",
+            TypeCheckError::UnexpectedReturnValue { .. } => "H0306",
+            TypeCheckError::NeverReturns { .. } => "H0307",
+            TypeCheckError::NonBoolCondition { .. } => "H0308",
+            TypeCheckError::InvalidLiteralSuffix { .. } => "H0309",
+            TypeCheckError::LiteralOutOfRange { .. } => "H0310",
+            TypeCheckError::NoField { .. } => "H0311",
+            TypeCheckError::UnknownField { .. } => "H0312",
+            TypeCheckError::MissingFields { .. } => "H0313",
+            TypeCheckError::TypeAnnotationsNeeded { .. } => "H0314",
+            TypeCheckError::NotCallableValue { .. } => "H0315",
+            TypeCheckError::NotAValue { .. } => "H0316",
+            TypeCheckError::NotAType { .. } => "H0317"
+        })
+    }
+
+    fn fmt(&self, fmt: &mut ErrorFmt<'ctx>, ctx: &'ctx CommonErrorContext) {
+        match self {
+            TypeCheckError::ArgCountMismatch { ref name, expected, found, ref span, ref decl_span } => {
+                let was = if *found == 1 { "was" } else { "were" };
+                fmt
+                    .title(&format!("`{}` takes {}, but {} {} given.", name, plural(*expected, "argument"), found, was))
+                    .source(ctx.source, *span)
+                    .cause("This call has a wrong number of arguments.");
+                if let Some(decl_span) = decl_span {
+                    fmt
+                        .note(&format!("`{}` is declared here:", name))
+                        .source(ctx.source, *decl_span);
+                }
+            }
+            TypeCheckError::NotCallable { ref name, descr, ref span, ref decl_span } => {
+                fmt
+                    .title(&format!("Expected function, found {} `{}`.", descr, name))
+                    .source(ctx.source, *span)
+                    .cause("Only functions, tuple structs and tuple variants can be called.")
+                    .note(&format!("`{}` is declared here:", name))
+                    .source(ctx.source, *decl_span);
+            }
+            TypeCheckError::NotCallableValue { ref ty, ref span } => {
+                fmt
+                    .title(&format!("Expected function, found `{}`.", ty))
+                    .source(ctx.source, *span)
+                    .cause("Only functions, tuple structs and tuple variants can be called.");
+            }
+            TypeCheckError::NotAValue { ref name, descr, ref span, ref decl_span } => {
+                fmt
+                    .title(&format!("Expected value, found {} `{}`.", descr, name))
+                    .source(ctx.source, *span)
+                    .cause("This can't be used as a value.");
+                if let Some(decl_span) = decl_span {
+                    fmt
+                        .note(&format!("`{}` is declared here:", name))
+                        .source(ctx.source, *decl_span);
+                }
+            }
+            TypeCheckError::NotAType { ref name, descr, ref span, ref decl_span } => {
+                fmt
+                    .title(&format!("Expected type, found {} `{}`.", descr, name))
+                    .source(ctx.source, *span)
+                    .cause("Only structs, enums and builtin types can be used as types.");
+                if let Some(decl_span) = decl_span {
+                    fmt
+                        .note(&format!("`{}` is declared here:", name))
+                        .source(ctx.source, *decl_span);
+                }
+            }
+            TypeCheckError::TypeMismatch { ref expected, ref found, ref span, ref expected_span } => {
+                fmt
+                    .title("Mismatched types.")
+                    .source(ctx.source, *span)
+                    .note(&format!("Expected `{}`, found `{}`.", expected, found));
+                if let Some(expected_span) = expected_span {
+                    fmt
+                        .note("Expected because of this:")
+                        .source(ctx.source, *expected_span);
+                }
+            }
+            TypeCheckError::BinaryOpMismatch { verb, ref lhs, ref rhs, ref lhs_span, ref rhs_span } => {
+                fmt
+                    .title(&format!("Cannot {} `{}` and `{}`.", verb, lhs, rhs))
+                    .note(&format!("Left operand has type `{}`:", lhs))
+                    .source(ctx.source, *lhs_span)
+                    .note(&format!("Right operand has type `{}`:", rhs))
+                    .source(ctx.source, *rhs_span);
+            }
+            TypeCheckError::UnaryOpMismatch { verb, ref ty, ref span, ref op_span } => {
+                fmt
+                    .title(&format!("Cannot {} `{}`.", verb, ty))
+                    .source(ctx.source, *op_span)
+                    .note(&format!("Operand has type `{}`:", ty))
+                    .source(ctx.source, *span);
+            }
+            TypeCheckError::ReturnTypeMismatch { ref expected, ref found, ref span, ref ret_span } => {
+                fmt
+                    .title(&format!("Function should return `{}`, but its body has type `{}`.", expected, found))
+                    .source(ctx.source, *span)
+                    .cause("This is the value of the function body.")
+                    .note("Return type is declared here:")
+                    .source(ctx.source, *ret_span);
+            }
+            TypeCheckError::UnexpectedReturnValue { ref found, ref suggested, ref span, ref sig_span } => {
+                fmt
+                    .title("Function without return type returns a value.")
+                    .source(ctx.source, *span)
+                    .note(&format!("This value has type `{}`, but the function returns `()`.", found))
+                    .note("Function is declared here:")
+                    .source(ctx.source, *sig_span);
+                if let Some(suggested) = suggested {
+                    let end = Span::new(sig_span.source, sig_span.end, sig_span.end);
+                    fmt.suggestion(end, &format!(" -> {}", suggested), "add return type");
+                }
+            }
+            TypeCheckError::NeverReturns { ref span, ref ret_span } => {
+                fmt
+                    .title("Function returning `!` can finish.")
+                    .source(ctx.source, *span)
+                    .cause("Execution can reach the end of this body.")
+                    .note("Return type is declared here:")
+                    .source(ctx.source, *ret_span);
+            }
+            TypeCheckError::NonBoolCondition { construct, ref found, compare_to_zero, ref span } => {
+                fmt
+                    .title(&format!("Condition of `{}` must be `bool`, found `{}`.", construct, found))
+                    .source(ctx.source, *span)
+                    .cause("Conditions are never converted to `bool` implicitly.");
+                if *compare_to_zero {
+                    if let Some(text) = span.get_text(ctx.source) {
+                        fmt.suggestion(*span, &format!("{} != 0", text), "compare with zero explicitly");
+                    }
+                }
+            }
+            TypeCheckError::InvalidLiteralSuffix { ref suffix, is_float, ref as_float, ref span } => {
+                let (descr, valid) = if *is_float {
+                    ("float", "`f32` and `f64`")
+                } else {
+                    ("integer", "`i8`, `i16`, `i32`, `i64`, `isize`, `u8`, `u16`, `u32`, `u64` and `usize`")
+                };
+                fmt
+                    .title(&format!("Invalid suffix `{}` for {} literal.", suffix, descr))
+                    .source(ctx.source, *span)
+                    .note(&format!("Valid suffixes are {}.", valid));
+                if let Some(as_float) = as_float {
+                    fmt.suggestion(*span, as_float, "write it as a float literal");
+                }
+            }
+            TypeCheckError::LiteralOutOfRange { ty, ref min, ref max, ref span } => {
+                let text = span.get_text(ctx.source).unwrap_or_default();
+                fmt
+                    .title(&format!("Literal `{}` is out of range for `{}`.", text, ty))
+                    .source(ctx.source, *span)
+                    .note(&format!("`{}` has values from {} to {}.", ty, min, max));
+            }
+            TypeCheckError::NoField { ref field, ref ty, has_fields, ref suggestion, ref span } => {
+                fmt
+                    .title(&format!("No field `{}` on type `{}`.", field, ty))
+                    .source(ctx.source, *span);
+                if !*has_fields {
+                    fmt.note(&format!("`{}` has no fields, only structs have them.", ty));
+                }
+                if let Some(suggestion) = suggestion {
+                    fmt.suggestion(*span, suggestion.as_str(), "a field with a similar name exists");
+                }
+            }
+            TypeCheckError::UnknownField { ref name, descr, ref field, ref suggestion, ref span, ref decl_span } => {
+                fmt
+                    .title(&format!("Unknown field `{}` in initializer of `{}`.", field, name))
+                    .source(ctx.source, *span)
+                    .cause("This field doesn't exist.")
+                    .note(&format!("{} `{}` has no field named `{}`.", capitalize(descr), name, field));
+                if let Some(suggestion) = suggestion {
+                    fmt.suggestion(*span, suggestion.as_str(), "a field with a similar name exists");
+                }
+                fmt
+                    .note(&format!("`{}` is declared here:", name))
+                    .source(ctx.source, *decl_span);
+            }
+            TypeCheckError::MissingFields { ref name, ref fields, ref span, ref decl_span } => {
+                let names = fields.iter().map(|field| format!("`{}`", field)).collect::<Vec<_>>();
+                fmt
+                    .title(&format!("Missing {} {} in initializer of `{}`.", if fields.len() == 1 { "field" } else { "fields" }, join_names(&names), name))
+                    .source(ctx.source, *span)
+                    .note("Every field has to be given a value, or copied from another value with `..base`.")
+                    .note(&format!("`{}` is declared here:", name))
+                    .source(ctx.source, *decl_span);
+            }
+            TypeCheckError::TypeAnnotationsNeeded { ref name, ref span } => {
+                fmt
+                    .title("Type annotations needed.")
+                    .source(ctx.source, *span);
+                match name {
+                    Some(name) => fmt
+                        .cause("Nothing decides the type of this variable.")
+                        .note(&format!("Give `{}` a type, like `let {}: i32`, or a value it can be inferred from.", name, name)),
+                    None => fmt.cause("Type of this value has to be known here.")
+                };
+            }
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod util;
-pub mod passes;
\ No newline at end of file
+pub mod visit;
+pub mod fold;
+pub mod passes;
+pub mod hir;pub mod mir;
@@ -0,0 +1,439 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use hastyc_common::{identifiers::Symbol, span::Span};
+use hastyc_parser::parser::{BinOpKind, Lit, LitKind};
+
+use crate::{hir::{ty::Ty, typeck::declared_ty, BlockId, Body, CtorKind, DefKind, ExprId, Hir, HirExprKind, HirRestExpr, HirStmtKind, LocalId, Res, StmtId}, passes::{ASTPass, QueryContext}};
+
+use super::{BasicBlock, BasicBlockData, ConstKind, Constant, Local, LocalDecl, Mir, MirBody, Operand, Place, Rvalue, Statement, StatementKind, Terminator, TerminatorKind, RETURN_PLACE, START_BLOCK};
+
+/// Pass lowering HIR bodies to MIR, it has to run after
+/// `hir::typeck::TypeCheckPass`, as types of locals are taken from its
+/// table. Packages with errors are not lowered, so the builder can
+/// rely on names being resolved and types being correct.
+#[derive(Debug, Default)]
+pub struct BuildMir;
+
+impl<'ctx> ASTPass<'ctx> for BuildMir {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "build-mir"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["typeck"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        if ctx.has_errors() {
+            return Ok(())
+        }
+        let mir = build_mir(ctx);
+        ctx.mir = Some(mir);
+        Ok(())
+    }
+}
+
+pub fn build_mir(cx: &QueryContext) -> Mir {
+    let hir = cx.hir.as_ref().expect("package must be lowered before building MIR");
+    let mut mir = Mir::default();
+    for (_, body) in hir.bodies() {
+        let builder = Builder {
+            cx,
+            hir,
+            body,
+            locals: Vec::new(),
+            blocks: Vec::new(),
+            current: START_BLOCK,
+            local_map: HashMap::new(),
+            loops: Vec::new()
+        };
+        mir.bodies.insert(body.owner, builder.build());
+    }
+    mir
+}
+
+/// Basic block which gets its terminator once it is finished.
+struct BlockBuilder {
+    stmts: Vec<Statement>,
+    terminator: Option<Terminator>
+}
+
+struct LoopScope {
+    /// Block after the loop.
+    break_block: BasicBlock,
+    /// Start of the loop body.
+    continue_block: BasicBlock,
+    /// Place the loop writes its value to.
+    dest: Place
+}
+
+struct Builder<'a, 'ctx> {
+    cx: &'a QueryContext<'ctx>,
+    hir: &'a Hir,
+    body: &'a Body,
+    locals: Vec<LocalDecl>,
+    blocks: Vec<BlockBuilder>,
+    /// Block new statements are added to.
+    current: BasicBlock,
+    local_map: HashMap<LocalId, Local>,
+    loops: Vec<LoopScope>
+}
+
+impl Builder<'_, '_> {
+    fn build(mut self) -> MirBody {
+        let body = self.body;
+        let owner = self.hir.def(body.owner);
+        let DefKind::Fn { ref sig, .. } = owner.kind else { unreachable!("body is not owned by a function") };
+        let return_ty = match sig.output {
+            Some(ref ty) => declared_ty(self.hir, ty).unwrap_or(Ty::Err),
+            None => Ty::Void
+        };
+        self.locals.push(LocalDecl { ty: return_ty, name: None, span: sig.span });
+        for param in body.params.iter() {
+            self.declare_local(*param);
+        }
+        self.new_block();
+
+        self.block_into(Place::local(RETURN_PLACE), body.value);
+        let span = body.block(body.value).span;
+        self.terminate(TerminatorKind::Return, span);
+
+        // Blocks left unfinished come after code that never finishes
+        let blocks = self.blocks.into_iter()
+            .map(|block| BasicBlockData {
+                stmts: block.stmts,
+                terminator: block.terminator.unwrap_or(Terminator { kind: TerminatorKind::Unreachable, span })
+            })
+            .collect();
+        MirBody {
+            owner: body.owner,
+            locals: self.locals,
+            arg_count: body.params.len(),
+            blocks,
+            span: owner.span
+        }
+    }
+
+    fn declare_local(&mut self, id: LocalId) -> Local {
+        let hir_local = self.body.local(id);
+        let local = Local(self.locals.len() as u32);
+        self.locals.push(LocalDecl {
+            ty: self.cx.types.get(hir_local.ast_id).unwrap_or(Ty::Err),
+            name: Some(hir_local.ident.clone()),
+            span: hir_local.span
+        });
+        self.local_map.insert(id, local);
+        local
+    }
+
+    fn new_temp(&mut self, ty: Ty, span: Span) -> Place {
+        let local = Local(self.locals.len() as u32);
+        self.locals.push(LocalDecl { ty, name: None, span });
+        Place::local(local)
+    }
+
+    fn new_block(&mut self) -> BasicBlock {
+        let block = BasicBlock(self.blocks.len() as u32);
+        self.blocks.push(BlockBuilder { stmts: Vec::new(), terminator: None });
+        block
+    }
+
+    fn ty(&self, expr: ExprId) -> Ty {
+        self.cx.types.get(self.body.expr(expr).ast_id).unwrap_or(Ty::Err)
+    }
+
+    fn push_assign(&mut self, place: Place, rvalue: Rvalue, span: Span) {
+        self.blocks[self.current.index()].stmts.push(Statement {
+            kind: StatementKind::Assign(place, rvalue),
+            span
+        });
+    }
+
+    fn assign_void(&mut self, place: Place, span: Span) {
+        let void = Constant { kind: ConstKind::Void, ty: Ty::Void };
+        self.push_assign(place, Rvalue::Use(Operand::Constant(void)), span);
+    }
+
+    /// Finish the current block, code lowered after this and before
+    /// switching to other block is unreachable.
+    fn terminate(&mut self, kind: TerminatorKind, span: Span) {
+        let block = &mut self.blocks[self.current.index()];
+        debug_assert!(block.terminator.is_none(), "block is already terminated");
+        block.terminator = Some(Terminator { kind, span });
+    }
+
+    fn goto(&mut self, target: BasicBlock, span: Span) {
+        self.terminate(TerminatorKind::Goto(target), span);
+    }
+
+    /// Continue in a new block, which nothing jumps to.
+    fn start_unreachable(&mut self) {
+        self.current = self.new_block();
+    }
+
+    fn block_into(&mut self, dest: Place, id: BlockId) {
+        let block = self.body.block(id);
+        for stmt in block.stmts.iter() {
+            self.stmt(*stmt);
+        }
+        match block.tail {
+            Some(tail) => self.expr_into(dest, tail),
+            None => {
+                // Block that never finishes doesn't have a value
+                if self.cx.types.get(block.ast_id) == Some(Ty::Void) {
+                    self.assign_void(dest, block.span);
+                }
+            }
+        }
+    }
+
+    fn stmt(&mut self, id: StmtId) {
+        let stmt = self.body.stmt(id);
+        match stmt.kind {
+            HirStmtKind::Let { local, init } => {
+                let local = self.declare_local(local);
+                if let Some(init) = init {
+                    self.expr_into(Place::local(local), init);
+                }
+            },
+            HirStmtKind::Item(_) => {},
+            HirStmtKind::Expr(expr) => {
+                let temp = self.new_temp(self.ty(expr), stmt.span);
+                self.expr_into(temp, expr);
+            }
+        }
+    }
+
+    /// Lower expression to an operand, using a temporary
+    /// if the value has to be computed first.
+    fn as_operand(&mut self, id: ExprId) -> Operand {
+        let expr = self.body.expr(id);
+        match expr.kind {
+            HirExprKind::Literal(ref lit) => Operand::Constant(constant(lit, self.ty(id))),
+            HirExprKind::Path(Res::Def(def)) if self.ty(id) == Ty::FnDef(def) => {
+                Operand::Constant(Constant { kind: ConstKind::Fn(def), ty: Ty::FnDef(def) })
+            },
+            HirExprKind::Path(Res::Local(_)) | HirExprKind::Field(..) => Operand::Copy(self.as_place(id)),
+            _ => {
+                let temp = self.new_temp(self.ty(id), expr.span);
+                self.expr_into(temp.clone(), id);
+                Operand::Copy(temp)
+            }
+        }
+    }
+
+    /// Lower expression to a place, using a temporary
+    /// if the expression is not a variable or its field.
+    fn as_place(&mut self, id: ExprId) -> Place {
+        let expr = self.body.expr(id);
+        match expr.kind {
+            HirExprKind::Path(Res::Local(local)) => Place::local(self.local_map[&local]),
+            HirExprKind::Field(target, ref ident) => {
+                let Ty::Adt(def) = self.ty(target) else { unreachable!("field of a value that is not a struct") };
+                let DefKind::Struct { ref fields, .. } = self.hir.def(def).kind else { unreachable!("field of a value that is not a struct") };
+                let index = fields.iter()
+                    .position(|field| self.hir.def(*field).ident.symbol == ident.symbol)
+                    .expect("unknown field");
+                self.as_place(target).field(index as u32)
+            },
+            _ => {
+                let temp = self.new_temp(self.ty(id), expr.span);
+                self.expr_into(temp.clone(), id);
+                temp
+            }
+        }
+    }
+
+    /// Lower expression, writing its value to `dest`.
+    fn expr_into(&mut self, dest: Place, id: ExprId) {
+        let expr = self.body.expr(id);
+        let span = expr.span;
+        match expr.kind {
+            HirExprKind::Path(Res::Def(def)) => match self.hir.def(def).kind {
+                // Unit structs and variants are values on their own
+                DefKind::Struct { ctor: CtorKind::Unit, .. } | DefKind::Variant { ctor: CtorKind::Unit, .. } => {
+                    self.push_assign(dest, Rvalue::Aggregate(def, Vec::new()), span);
+                },
+                _ => {
+                    let operand = self.as_operand(id);
+                    self.push_assign(dest, Rvalue::Use(operand), span);
+                }
+            },
+            HirExprKind::Path(_) | HirExprKind::Literal(_) | HirExprKind::Field(..) => {
+                let operand = self.as_operand(id);
+                self.push_assign(dest, Rvalue::Use(operand), span);
+            },
+            HirExprKind::Assign(target, value) => {
+                let place = self.as_place(target);
+                self.expr_into(place, value);
+                self.assign_void(dest, span);
+            },
+            HirExprKind::Unary(ref op, operand) => {
+                let operand = self.as_operand(operand);
+                self.push_assign(dest, Rvalue::UnaryOp(op.kind.clone(), operand), span);
+            },
+            HirExprKind::Binary(ref op, lhs, rhs) => match op.kind {
+                BinOpKind::And | BinOpKind::Or => {
+                    let is_or = matches!(op.kind, BinOpKind::Or);
+                    self.short_circuit_into(dest, is_or, lhs, rhs, span);
+                },
+                ref kind => {
+                    let lhs = self.as_operand(lhs);
+                    let rhs = self.as_operand(rhs);
+                    self.push_assign(dest, Rvalue::BinaryOp(kind.clone(), lhs, rhs), span);
+                }
+            },
+            HirExprKind::Call(callee, ref args) => self.call_into(dest, callee, args, self.ty(id), span),
+            HirExprKind::If(cond, then, else_expr) => {
+                let cond = self.as_operand(cond);
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                let join = self.new_block();
+                self.terminate(TerminatorKind::If { cond, then: then_block, otherwise: else_block }, span);
+
+                self.current = then_block;
+                self.block_into(dest.clone(), then);
+                self.goto(join, span);
+
+                self.current = else_block;
+                match else_expr {
+                    Some(else_expr) => self.expr_into(dest, else_expr),
+                    None => self.assign_void(dest, span)
+                }
+                self.goto(join, span);
+                self.current = join;
+            },
+            HirExprKind::Block(block) => self.block_into(dest, block),
+            HirExprKind::Loop(block) => {
+                let head = self.new_block();
+                let exit = self.new_block();
+                self.goto(head, span);
+                self.current = head;
+                self.loops.push(LoopScope { break_block: exit, continue_block: head, dest });
+                let temp = self.new_temp(Ty::Void, span);
+                self.block_into(temp, block);
+                self.goto(head, span);
+                self.loops.pop();
+                self.current = exit;
+            },
+            HirExprKind::Break(value) => {
+                let Some(scope) = self.loops.last() else {
+                    self.terminate(TerminatorKind::Unreachable, span);
+                    return self.start_unreachable()
+                };
+                let (loop_dest, exit) = (scope.dest.clone(), scope.break_block);
+                match value {
+                    Some(value) => self.expr_into(loop_dest, value),
+                    None => self.assign_void(loop_dest, span)
+                }
+                self.goto(exit, span);
+                self.start_unreachable();
+            },
+            HirExprKind::Continue => {
+                match self.loops.last() {
+                    Some(scope) => self.goto(scope.continue_block, span),
+                    None => self.terminate(TerminatorKind::Unreachable, span)
+                }
+                self.start_unreachable();
+            },
+            HirExprKind::StructLit { res, ref fields, rest } => {
+                let Res::Def(def) = res else { unreachable!("struct literal of unresolved path") };
+                let (DefKind::Struct { fields: ref def_fields, .. } | DefKind::Variant { fields: ref def_fields, .. }) = self.hir.def(def).kind else {
+                    unreachable!("struct literal of something that is not a struct")
+                };
+                // Fields are evaluated in order they are written in
+                let mut values = vec![None; def_fields.len()];
+                for (ident, value) in fields.iter() {
+                    let index = def_fields.iter()
+                        .position(|field| self.hir.def(*field).ident.symbol == ident.symbol)
+                        .expect("unknown field");
+                    values[index] = Some(self.as_operand(*value));
+                }
+                let base = match rest {
+                    HirRestExpr::Valued(base) => Some(self.as_place(base)),
+                    HirRestExpr::Rest(_) | HirRestExpr::None => None
+                };
+                let operands = values.into_iter().enumerate()
+                    .map(|(index, value)| match (value, &base) {
+                        (Some(value), _) => value,
+                        (None, Some(base)) => Operand::Copy(base.field(index as u32)),
+                        // Missing fields are left uninitialized, like variables without value
+                        (None, None) => {
+                            let field = def_fields[index];
+                            let ty = self.cx.types.get(self.hir.def(field).ast_id).unwrap_or(Ty::Err);
+                            Operand::Copy(self.new_temp(ty, span))
+                        }
+                    })
+                    .collect();
+                self.push_assign(dest, Rvalue::Aggregate(def, operands), span);
+            }
+        }
+    }
+
+    /// Lower `lhs && rhs` or `lhs || rhs`, `rhs` is only evaluated if `lhs`
+    /// doesn't decide the result.
+    fn short_circuit_into(&mut self, dest: Place, is_or: bool, lhs: ExprId, rhs: ExprId, span: Span) {
+        let cond = self.as_operand(lhs);
+        let rhs_block = self.new_block();
+        let short_block = self.new_block();
+        let join = self.new_block();
+        let (then, otherwise) = if is_or { (short_block, rhs_block) } else { (rhs_block, short_block) };
+        self.terminate(TerminatorKind::If { cond, then, otherwise }, span);
+
+        self.current = short_block;
+        let value = Constant { kind: ConstKind::Bool(is_or), ty: self.ty(lhs) };
+        self.push_assign(dest.clone(), Rvalue::Use(Operand::Constant(value)), span);
+        self.goto(join, span);
+
+        self.current = rhs_block;
+        self.expr_into(dest, rhs);
+        self.goto(join, span);
+        self.current = join;
+    }
+
+    fn call_into(&mut self, dest: Place, callee: ExprId, args: &[ExprId], ty: Ty, span: Span) {
+        // Tuple structs and variants are constructed, not called
+        if let Ty::FnDef(def) = self.ty(callee) {
+            if matches!(self.hir.def(def).kind, DefKind::Struct { .. } | DefKind::Variant { .. }) {
+                let operands = args.iter().map(|arg| self.as_operand(*arg)).collect();
+                return self.push_assign(dest, Rvalue::Aggregate(def, operands), span)
+            }
+        }
+        let func = self.as_operand(callee);
+        let args = args.iter().map(|arg| self.as_operand(*arg)).collect();
+        let target = (ty != Ty::Never).then(|| self.new_block());
+        self.terminate(TerminatorKind::Call { func, args, dest, target }, span);
+        match target {
+            Some(target) => self.current = target,
+            None => self.start_unreachable()
+        }
+    }
+}
+
+fn constant(lit: &Lit, ty: Ty) -> Constant {
+    let text = lit.symbol.as_str();
+    let kind = match lit.kind {
+        LitKind::Integer => ConstKind::Int(parse_int(text)),
+        LitKind::Float => ConstKind::Float(text.parse().expect("float literal is not a number")),
+        LitKind::Bool => ConstKind::Bool(text == "true"),
+        LitKind::Char => ConstKind::Char(unquote(text).chars().next().expect("empty char literal")),
+        LitKind::String => ConstKind::Str(Symbol::intern(unquote(text)))
+    };
+    Constant { kind, ty }
+}
+
+/// Integer literal as two's complement, values that don't fit are
+/// saturated, as they were already reported by type checking.
+fn parse_int(text: &str) -> u128 {
+    match text.strip_prefix('-') {
+        Some(positive) => positive.parse::<i128>().map_or(i128::MIN, |value| -value) as u128,
+        None => text.parse::<u128>().unwrap_or(u128::MAX)
+    }
+}
+
+/// Content of string or char literal, without the quotes.
+fn unquote(text: &str) -> &str {
+    &text[1..text.len() - 1]
+}
@@ -0,0 +1,152 @@
+use hastyc_parser::parser::{BinOpKind, UnOpKind};
+
+use crate::{hir::ty::Ty, passes::builtin::{FloatTy, PrimTy}};
+
+use super::{ConstKind, Constant};
+
+/// Evaluate binary operation on constants, the way it would be evaluated
+/// at runtime. Returns None if the result can't be known at compile time,
+/// like when the operation overflows or divides by zero.
+pub fn eval_binary(op: &BinOpKind, lhs: &Constant, rhs: &Constant) -> Option<Constant> {
+    let kind = match (&lhs.kind, &rhs.kind) {
+        (&ConstKind::Int(a), &ConstKind::Int(b)) => return eval_int_binary(op, a, lhs.ty, b, rhs.ty),
+        (&ConstKind::Float(a), &ConstKind::Float(b)) => match op {
+            BinOpKind::Add => ConstKind::Float(round_float(a + b, lhs.ty)),
+            BinOpKind::Sub => ConstKind::Float(round_float(a - b, lhs.ty)),
+            BinOpKind::Mul => ConstKind::Float(round_float(a * b, lhs.ty)),
+            BinOpKind::Div => ConstKind::Float(round_float(a / b, lhs.ty)),
+            BinOpKind::Rem => ConstKind::Float(round_float(a % b, lhs.ty)),
+            _ => ConstKind::Bool(compare(op, a, b)?)
+        },
+        (&ConstKind::Bool(a), &ConstKind::Bool(b)) => ConstKind::Bool(match op {
+            BinOpKind::And | BinOpKind::BitAnd => a & b,
+            BinOpKind::Or | BinOpKind::BitOr => a | b,
+            BinOpKind::BitXor => a ^ b,
+            _ => compare(op, a, b)?
+        }),
+        (&ConstKind::Char(a), &ConstKind::Char(b)) => ConstKind::Bool(compare(op, a, b)?),
+        (&ConstKind::Str(a), &ConstKind::Str(b)) => ConstKind::Bool(match op {
+            BinOpKind::Eq => a == b,
+            BinOpKind::Ne => a != b,
+            _ => return None
+        }),
+        _ => return None
+    };
+    let ty = match kind {
+        ConstKind::Bool(_) => Ty::Prim(PrimTy::Bool),
+        _ => lhs.ty
+    };
+    Some(Constant { kind, ty })
+}
+
+/// Evaluate unary operation on a constant, see `eval_binary`.
+pub fn eval_unary(op: &UnOpKind, operand: &Constant) -> Option<Constant> {
+    let kind = match (op, &operand.kind) {
+        (UnOpKind::Neg, &ConstKind::Float(value)) => ConstKind::Float(-value),
+        (UnOpKind::Neg, &ConstKind::Int(value)) => {
+            let int = IntTy::of(operand.ty)?;
+            if !int.signed {
+                return None
+            }
+            ConstKind::Int(int.check((value as i128).checked_neg()?)?)
+        },
+        (UnOpKind::Not, &ConstKind::Bool(value)) => ConstKind::Bool(!value),
+        // Signed values are sign extended, so they stay that way when negated
+        (UnOpKind::Not, &ConstKind::Int(value)) => ConstKind::Int(IntTy::of(operand.ty)?.truncate(!value)),
+        _ => return None
+    };
+    Some(Constant { kind, ty: operand.ty })
+}
+
+fn compare<T: PartialOrd>(op: &BinOpKind, a: T, b: T) -> Option<bool> {
+    Some(match op {
+        BinOpKind::Eq => a == b,
+        BinOpKind::Ne => a != b,
+        BinOpKind::Lt => a < b,
+        BinOpKind::Le => a <= b,
+        BinOpKind::Gt => a > b,
+        BinOpKind::Ge => a >= b,
+        _ => return None
+    })
+}
+
+/// Round result of `f32` operation, as constants are always kept as `f64`.
+fn round_float(value: f64, ty: Ty) -> f64 {
+    match ty {
+        Ty::Prim(PrimTy::Float(FloatTy::F32)) => value as f32 as f64,
+        _ => value
+    }
+}
+
+/// Integer type of a constant. Values are stored in `u128`, signed ones
+/// sign extended, so `-1_i8` is `u128::MAX`.
+#[derive(Clone, Copy)]
+struct IntTy {
+    bits: u32,
+    signed: bool
+}
+
+impl IntTy {
+    fn of(ty: Ty) -> Option<Self> {
+        let Ty::Prim(prim) = ty else { return None };
+        Some(Self {
+            bits: prim.int_bits()?,
+            signed: matches!(prim, PrimTy::Int(_))
+        })
+    }
+
+    /// Value as stored, if it fits in the type.
+    fn check(self, value: i128) -> Option<u128> {
+        let (min, max) = if self.signed {
+            (-(1i128 << (self.bits - 1)), (1i128 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1i128 << self.bits) - 1)
+        };
+        (min..=max).contains(&value).then_some(value as u128)
+    }
+
+    /// Drop bits that don't fit in the type, wrapping the value around.
+    fn truncate(self, value: u128) -> u128 {
+        let shift = 128 - self.bits;
+        if self.signed {
+            (((value << shift) as i128) >> shift) as u128
+        } else {
+            (value << shift) >> shift
+        }
+    }
+}
+
+fn eval_int_binary(op: &BinOpKind, a: u128, a_ty: Ty, b: u128, b_ty: Ty) -> Option<Constant> {
+    let int = IntTy::of(a_ty)?;
+    let (sa, sb) = (a as i128, b as i128);
+    let value = match op {
+        // Signed values fit in i128 and unsigned ones are at most 64 bits,
+        // so both can be computed as i128 and checked afterwards
+        BinOpKind::Add => int.check(sa.checked_add(sb)?)?,
+        BinOpKind::Sub => int.check(sa.checked_sub(sb)?)?,
+        BinOpKind::Mul => int.check(sa.checked_mul(sb)?)?,
+        BinOpKind::Div => int.check(sa.checked_div(sb)?)?,
+        BinOpKind::Rem => int.check(sa.checked_rem(sb)?)?,
+        BinOpKind::BitAnd => a & b,
+        BinOpKind::BitOr => a | b,
+        BinOpKind::BitXor => a ^ b,
+        BinOpKind::Shl | BinOpKind::Shr => {
+            // Amount has type of its own
+            let amount_ty = IntTy::of(b_ty)?;
+            if (amount_ty.signed && sb < 0) || b >= int.bits as u128 {
+                return None
+            }
+            match op {
+                BinOpKind::Shl => int.truncate(a << b),
+                _ if int.signed => (sa >> b) as u128,
+                _ => a >> b
+            }
+        },
+        BinOpKind::And | BinOpKind::Or => return None,
+        _ => {
+            let result = if int.signed { compare(op, sa, sb)? } else { compare(op, a, b)? };
+            return Some(Constant { kind: ConstKind::Bool(result), ty: Ty::Prim(PrimTy::Bool) })
+        }
+    };
+    Some(Constant { kind: ConstKind::Int(value), ty: a_ty })
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use hastyc_common::{identifiers::{Ident, Symbol}, span::Span};
+use hastyc_parser::parser::{BinOpKind, UnOpKind};
+
+use crate::hir::{index_id, ty::Ty, DefId};
+
+pub mod build;
+pub mod const_eval;
+pub mod pretty;
+pub mod transform;
+
+index_id! {
+    /// Local of a MIR body, see `MirBody::locals`.
+    Local,
+    BasicBlock
+}
+
+/// Local holding the return value of a body.
+pub const RETURN_PLACE: Local = Local(0);
+
+/// First basic block of every body.
+pub const START_BLOCK: BasicBlock = BasicBlock(0);
+
+/// Mid-level IR of the package, lowered from HIR by `build::BuildMir`.
+/// Bodies are control flow graphs of basic blocks, where every value
+/// lives in a local and every operation is a simple statement, so
+/// optimizations and backends don't have to deal with nested expressions.
+#[derive(Debug, Default)]
+pub struct Mir {
+    /// Bodies by functions that own them.
+    pub bodies: BTreeMap<DefId, MirBody>
+}
+
+impl Mir {
+    pub fn body(&self, owner: DefId) -> Option<&MirBody> {
+        self.bodies.get(&owner)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MirBody {
+    pub owner: DefId,
+    /// Return place is first, followed by arguments,
+    /// user variables and temporaries.
+    pub locals: Vec<LocalDecl>,
+    pub arg_count: usize,
+    pub blocks: Vec<BasicBlockData>,
+    pub span: Span
+}
+
+impl MirBody {
+    pub fn local(&self, local: Local) -> &LocalDecl {
+        &self.locals[local.index()]
+    }
+
+    pub fn block(&self, block: BasicBlock) -> &BasicBlockData {
+        &self.blocks[block.index()]
+    }
+
+    pub fn block_mut(&mut self, block: BasicBlock) -> &mut BasicBlockData {
+        &mut self.blocks[block.index()]
+    }
+
+    pub fn return_ty(&self) -> Ty {
+        self.local(RETURN_PLACE).ty
+    }
+
+    /// Locals of the arguments, in order.
+    pub fn args(&self) -> impl Iterator<Item = Local> {
+        (1..=self.arg_count).map(|idx| Local(idx as u32))
+    }
+
+    /// All blocks together with their ids.
+    pub fn basic_blocks(&self) -> impl Iterator<Item = (BasicBlock, &BasicBlockData)> {
+        self.blocks.iter().enumerate().map(|(idx, block)| (BasicBlock(idx as u32), block))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalDecl {
+    pub ty: Ty,
+    /// Name of user variable, None for temporaries and return place.
+    pub name: Option<Ident>,
+    pub span: Span
+}
+
+/// Statements that run one after another, followed by the terminator,
+/// which is the only place where control flow can change.
+#[derive(Debug, Clone)]
+pub struct BasicBlockData {
+    pub stmts: Vec<Statement>,
+    pub terminator: Terminator
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Assign(Place, Rvalue),
+    /// Statement that does nothing, left by optimizations
+    /// so they don't have to shift statements around.
+    Nop
+}
+
+/// Local, or a field of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub local: Local,
+    pub projection: Vec<PlaceElem>
+}
+
+impl Place {
+    pub fn local(local: Local) -> Self {
+        Self { local, projection: Vec::new() }
+    }
+
+    /// Field of the place, by its index in the struct.
+    pub fn field(&self, index: u32) -> Self {
+        let mut projection = self.projection.clone();
+        projection.push(PlaceElem::Field(index));
+        Self { local: self.local, projection }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceElem {
+    /// Field of a struct, by its index.
+    Field(u32)
+}
+
+/// Value used by statements and terminators.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Copy(Place),
+    Constant(Constant)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub kind: ConstKind,
+    pub ty: Ty
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstKind {
+    /// Integer, negative values are stored as their two's complement.
+    Int(u128),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    /// Content of string literal, without quotes.
+    Str(Symbol),
+    /// Function, which is a value of its `Ty::FnDef` type.
+    Fn(DefId),
+    /// `()`
+    Void
+}
+
+/// Computation of a value that is assigned to a place.
+#[derive(Debug, Clone)]
+pub enum Rvalue {
+    Use(Operand),
+    /// Binary operation, `&&` and `||` are lowered to branches instead.
+    BinaryOp(BinOpKind, Operand, Operand),
+    UnaryOp(UnOpKind, Operand),
+    /// Struct or variant with values of all its fields, in order.
+    Aggregate(DefId, Vec<Operand>)
+}
+
+#[derive(Debug, Clone)]
+pub struct Terminator {
+    pub kind: TerminatorKind,
+    pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum TerminatorKind {
+    Goto(BasicBlock),
+    /// Go to `then` if the bool condition is true, else to `otherwise`.
+    If {
+        cond: Operand,
+        then: BasicBlock,
+        otherwise: BasicBlock
+    },
+    /// Call function and store its result in `dest`, after which
+    /// execution continues at `target`. Target is None if the
+    /// function never returns.
+    Call {
+        func: Operand,
+        args: Vec<Operand>,
+        dest: Place,
+        target: Option<BasicBlock>
+    },
+    Return,
+    /// Block which can't be reached, like code after `break`.
+    Unreachable
+}
+
+impl TerminatorKind {
+    /// Blocks execution can continue at.
+    pub fn successors(&self) -> Vec<BasicBlock> {
+        match *self {
+            Self::Goto(target) => vec![target],
+            Self::If { then, otherwise, .. } => vec![then, otherwise],
+            Self::Call { target, .. } => target.into_iter().collect(),
+            Self::Return | Self::Unreachable => Vec::new()
+        }
+    }
+}
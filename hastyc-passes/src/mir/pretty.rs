@@ -0,0 +1,175 @@
+use std::fmt::{Display, Formatter, Result};
+
+use hastyc_common::identifiers::SymbolStorage;
+
+use crate::{hir::{ty::{Ty, TypeDisplay}, DefId, DefKind, Hir}, passes::builtin::{FloatTy, PrimTy}};
+
+use super::{ConstKind, Constant, Mir, MirBody, Operand, Place, PlaceElem, Rvalue, StatementKind, TerminatorKind};
+
+/// Renders MIR of the whole package in a textual form, used for debugging.
+pub struct MirDisplay<'a> {
+    mir: &'a Mir,
+    hir: &'a Hir,
+    symbols: &'a SymbolStorage
+}
+
+impl<'a> MirDisplay<'a> {
+    pub fn new(mir: &'a Mir, hir: &'a Hir, symbols: &'a SymbolStorage) -> Self {
+        Self { mir, hir, symbols }
+    }
+}
+
+impl Display for MirDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for (idx, body) in self.mir.bodies.values().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", BodyDisplay::new(body, self.hir, self.symbols))?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders single MIR body, like:
+///
+/// ```text
+/// fn add_one(_1: i32) -> i32 {
+///     let _0: i32;
+///     let _1: i32; // a
+///
+///     bb0: {
+///         _0 = Add(copy _1, const 1_i32);
+///         return;
+///     }
+/// }
+/// ```
+pub struct BodyDisplay<'a> {
+    body: &'a MirBody,
+    hir: &'a Hir,
+    symbols: &'a SymbolStorage
+}
+
+impl<'a> BodyDisplay<'a> {
+    pub fn new(body: &'a MirBody, hir: &'a Hir, symbols: &'a SymbolStorage) -> Self {
+        Self { body, hir, symbols }
+    }
+
+    fn ty(&self, ty: Ty) -> TypeDisplay<'a> {
+        TypeDisplay::new(ty, self.hir, self.symbols)
+    }
+
+    fn name(&self, def: DefId) -> &'a str {
+        self.hir.def(def).ident.text(self.symbols)
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Copy(place) => format!("copy {}", place),
+            Operand::Constant(constant) => format!("const {}", self.constant(constant))
+        }
+    }
+
+    fn operands(&self, operands: &[Operand]) -> String {
+        operands.iter().map(|operand| self.operand(operand)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn constant(&self, constant: &Constant) -> String {
+        match constant.kind {
+            // Integers are shown with their type, as they are stored without it
+            ConstKind::Int(value) => match constant.ty {
+                Ty::Prim(PrimTy::Int(_)) => format!("{}_{}", value as i128, self.ty(constant.ty)),
+                _ => format!("{}_{}", value, self.ty(constant.ty))
+            },
+            ConstKind::Float(value) => match constant.ty {
+                Ty::Prim(PrimTy::Float(FloatTy::F32)) => format!("{:?}_{}", value as f32, self.ty(constant.ty)),
+                _ => format!("{:?}_{}", value, self.ty(constant.ty))
+            },
+            ConstKind::Bool(value) => value.to_string(),
+            ConstKind::Char(value) => format!("{:?}", value),
+            ConstKind::Str(symbol) => format!("\"{}\"", symbol),
+            ConstKind::Fn(def) => self.name(def).to_string(),
+            ConstKind::Void => "()".to_string()
+        }
+    }
+
+    fn rvalue(&self, rvalue: &Rvalue) -> String {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(operand),
+            Rvalue::BinaryOp(op, lhs, rhs) => format!("{:?}({}, {})", op, self.operand(lhs), self.operand(rhs)),
+            Rvalue::UnaryOp(op, operand) => format!("{:?}({})", op, self.operand(operand)),
+            Rvalue::Aggregate(def, operands) => {
+                let def_ref = self.hir.def(*def);
+                let name = match def_ref.kind {
+                    DefKind::Variant { .. } => {
+                        let parent = def_ref.parent.expect("variant without enum");
+                        format!("{}::{}", self.name(parent), self.name(*def))
+                    },
+                    _ => self.name(*def).to_string()
+                };
+                if operands.is_empty() {
+                    name
+                } else {
+                    format!("{} {{ {} }}", name, self.operands(operands))
+                }
+            }
+        }
+    }
+}
+
+impl Display for BodyDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let body = self.body;
+        let args = body.args()
+            .map(|arg| format!("{}: {}", Place::local(arg), self.ty(body.local(arg).ty)))
+            .collect::<Vec<_>>();
+        writeln!(f, "fn {}({}) -> {} {{", self.name(body.owner), args.join(", "), self.ty(body.return_ty()))?;
+        for (idx, decl) in body.locals.iter().enumerate() {
+            write!(f, "    let _{}: {};", idx, self.ty(decl.ty))?;
+            match decl.name {
+                Some(ref name) => writeln!(f, " // {}", name.text(self.symbols))?,
+                None => writeln!(f)?
+            }
+        }
+        for (id, block) in body.basic_blocks() {
+            writeln!(f)?;
+            writeln!(f, "    bb{}: {{", id.0)?;
+            for stmt in block.stmts.iter() {
+                match stmt.kind {
+                    StatementKind::Assign(ref place, ref rvalue) => writeln!(f, "        {} = {};", place, self.rvalue(rvalue))?,
+                    StatementKind::Nop => writeln!(f, "        nop;")?
+                }
+            }
+            let terminator = match block.terminator.kind {
+                TerminatorKind::Goto(target) => format!("goto -> bb{}", target.0),
+                TerminatorKind::If { ref cond, then, otherwise } => {
+                    format!("if {} -> [true: bb{}, false: bb{}]", self.operand(cond), then.0, otherwise.0)
+                },
+                TerminatorKind::Call { ref func, ref args, ref dest, target } => {
+                    let target = match target {
+                        Some(target) => format!("bb{}", target.0),
+                        None => "!".to_string()
+                    };
+                    format!("{} = call {}({}) -> {}", dest, self.operand(func), self.operands(args), target)
+                },
+                TerminatorKind::Return => "return".to_string(),
+                TerminatorKind::Unreachable => "unreachable".to_string()
+            };
+            writeln!(f, "        {};", terminator)?;
+            writeln!(f, "    }}")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for Place {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "_{}", self.local.0)?;
+        for elem in self.projection.iter() {
+            match elem {
+                PlaceElem::Field(index) => write!(f, ".{}", index)?
+            }
+        }
+        Ok(())
+    }
+}
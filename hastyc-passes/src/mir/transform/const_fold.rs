@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::mir::{const_eval::{eval_binary, eval_unary}, ConstKind, Constant, Local, Mir, MirBody, Operand, Rvalue, StatementKind, TerminatorKind};
+
+use super::{reachable_blocks, MirPass};
+
+/// Pass replacing operations on constants with their results, using
+/// `mir::const_eval`. Locals that are only ever assigned one constant are
+/// replaced with it, so folding carries through variables, and branches on
+/// constant conditions become gotos. This repeats until nothing changes,
+/// which also folds `&&` and `||` with constant operands, as they are
+/// lowered to branches.
+#[derive(Debug, Default)]
+pub struct ConstFold;
+
+impl MirPass for ConstFold {
+    fn name(&self) -> &'static str {
+        "const-fold"
+    }
+
+    fn run(&mut self, mir: &mut Mir) {
+        for body in mir.bodies.values_mut() {
+            while fold_body(body) {}
+        }
+    }
+}
+
+/// Single round of folding, returns whether anything changed.
+fn fold_body(body: &mut MirBody) -> bool {
+    let known = known_locals(body);
+    let mut changed = false;
+    for block in body.blocks.iter_mut() {
+        for stmt in block.stmts.iter_mut() {
+            if let StatementKind::Assign(_, ref mut rvalue) = stmt.kind {
+                changed |= fold_rvalue(rvalue, &known);
+            }
+        }
+        match block.terminator.kind {
+            TerminatorKind::If { ref mut cond, then, otherwise } => {
+                changed |= substitute(cond, &known);
+                if let Operand::Constant(Constant { kind: ConstKind::Bool(value), .. }) = *cond {
+                    block.terminator.kind = TerminatorKind::Goto(if value { then } else { otherwise });
+                    changed = true;
+                }
+            },
+            TerminatorKind::Call { ref mut func, ref mut args, .. } => {
+                changed |= substitute(func, &known);
+                for arg in args.iter_mut() {
+                    changed |= substitute(arg, &known);
+                }
+            },
+            TerminatorKind::Goto(_) | TerminatorKind::Return | TerminatorKind::Unreachable => ()
+        }
+    }
+    changed
+}
+
+fn fold_rvalue(rvalue: &mut Rvalue, known: &HashMap<Local, Constant>) -> bool {
+    let mut changed = false;
+    match rvalue {
+        Rvalue::Use(operand) | Rvalue::UnaryOp(_, operand) => changed |= substitute(operand, known),
+        Rvalue::BinaryOp(_, lhs, rhs) => {
+            changed |= substitute(lhs, known);
+            changed |= substitute(rhs, known);
+        },
+        Rvalue::Aggregate(_, operands) => {
+            for operand in operands.iter_mut() {
+                changed |= substitute(operand, known);
+            }
+        }
+    }
+    let folded = match rvalue {
+        Rvalue::BinaryOp(op, Operand::Constant(lhs), Operand::Constant(rhs)) => eval_binary(op, lhs, rhs),
+        Rvalue::UnaryOp(op, Operand::Constant(operand)) => eval_unary(op, operand),
+        _ => None
+    };
+    if let Some(constant) = folded {
+        *rvalue = Rvalue::Use(Operand::Constant(constant));
+        changed = true;
+    }
+    changed
+}
+
+/// Replace copy of a known local with its value.
+fn substitute(operand: &mut Operand, known: &HashMap<Local, Constant>) -> bool {
+    let Operand::Copy(ref place) = *operand else { return false };
+    if !place.projection.is_empty() {
+        return false
+    }
+    match known.get(&place.local) {
+        Some(constant) => {
+            *operand = Operand::Constant(constant.clone());
+            true
+        },
+        None => false
+    }
+}
+
+/// Locals which are only ever assigned the same constant. Every read of
+/// such local comes after an assignment, so it always sees that constant.
+/// Assignments in blocks that can't be reached are ignored, so branches
+/// cut off by folding don't keep their locals from being folded.
+fn known_locals(body: &MirBody) -> HashMap<Local, Constant> {
+    let reachable = reachable_blocks(body);
+    // None marks local which can hold anything else
+    let mut values: HashMap<Local, Option<Constant>> = body.args().map(|arg| (arg, None)).collect();
+    let mut assign = |local: Local, value: Option<&Constant>| {
+        let entry = values.entry(local).or_insert_with(|| value.cloned());
+        if entry.as_ref() != value {
+            *entry = None;
+        }
+    };
+    for (_, block) in body.basic_blocks().filter(|(id, _)| reachable.contains(id)) {
+        for stmt in block.stmts.iter() {
+            let StatementKind::Assign(ref place, ref rvalue) = stmt.kind else { continue };
+            match rvalue {
+                Rvalue::Use(Operand::Constant(constant)) if place.projection.is_empty() => assign(place.local, Some(constant)),
+                _ => assign(place.local, None)
+            }
+        }
+        if let TerminatorKind::Call { ref dest, .. } = block.terminator.kind {
+            assign(dest.local, None);
+        }
+    }
+    values.into_iter().filter_map(|(local, value)| Some((local, value?))).collect()
+}
@@ -0,0 +1,72 @@
+use crate::mir::{Local, Mir, MirBody, Operand, Place, Rvalue, StatementKind, TerminatorKind, RETURN_PLACE};
+
+use super::{operands_mut, MirPass};
+
+/// Pass removing temporaries which only hold a copy of a place that
+/// never changes, like `_3 = copy _1` of an argument, reading the place
+/// itself wherever the temporary was read. Temporaries are only assigned
+/// once, before they are read, so when the copied local is assigned at
+/// most once as well, both always hold the same value.
+#[derive(Debug, Default)]
+pub struct CopyProp;
+
+impl MirPass for CopyProp {
+    fn name(&self) -> &'static str {
+        "copy-prop"
+    }
+
+    fn run(&mut self, mir: &mut Mir) {
+        for body in mir.bodies.values_mut() {
+            while let Some((temp, source)) = redundant_copy(body) {
+                for block in body.blocks.iter_mut() {
+                    operands_mut(block, |operand| {
+                        let Operand::Copy(ref mut place) = *operand else { return };
+                        if place.local == temp {
+                            let mut projection = source.projection.clone();
+                            projection.append(&mut place.projection);
+                            *place = Place { local: source.local, projection };
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Find copy to a temporary which can be removed, turning it into `Nop`,
+/// returns the temporary and the place it copied.
+fn redundant_copy(body: &mut MirBody) -> Option<(Local, Place)> {
+    // Arguments are assigned by the caller
+    let mut assignments = vec![0usize; body.locals.len()];
+    for arg in body.args() {
+        assignments[arg.index()] += 1;
+    }
+    for block in body.blocks.iter() {
+        for stmt in block.stmts.iter() {
+            if let StatementKind::Assign(ref place, _) = stmt.kind {
+                assignments[place.local.index()] += 1;
+            }
+        }
+        if let TerminatorKind::Call { ref dest, .. } = block.terminator.kind {
+            assignments[dest.local.index()] += 1;
+        }
+    }
+    let (locals, arg_count) = (&body.locals, body.arg_count);
+    let is_temp = |local: Local| local != RETURN_PLACE && local.index() > arg_count && locals[local.index()].name.is_none();
+    for block in body.blocks.iter_mut() {
+        for stmt in block.stmts.iter_mut() {
+            let StatementKind::Assign(ref place, Rvalue::Use(Operand::Copy(ref source))) = stmt.kind else { continue };
+            let temp = place.local;
+            if !place.projection.is_empty() || !is_temp(temp) || source.local == temp {
+                continue
+            }
+            if assignments[temp.index()] != 1 || assignments[source.local.index()] != 1 {
+                continue
+            }
+            let source = source.clone();
+            stmt.kind = StatementKind::Nop;
+            return Some((temp, source))
+        }
+    }
+    None
+}
@@ -0,0 +1,46 @@
+use crate::mir::{BasicBlock, Mir, MirBody, StatementKind};
+
+use super::{reachable_blocks, successors_mut, MirPass};
+
+/// Pass removing blocks which can't be reached from the start of their
+/// body, like branches cut off by `ConstFold`, and `Nop` statements left
+/// by other passes. Blocks which are left keep their order, so the start
+/// block stays first.
+#[derive(Debug, Default)]
+pub struct RemoveDeadBlocks;
+
+impl MirPass for RemoveDeadBlocks {
+    fn name(&self) -> &'static str {
+        "remove-dead-blocks"
+    }
+
+    fn run(&mut self, mir: &mut Mir) {
+        for body in mir.bodies.values_mut() {
+            remove_dead_blocks(body);
+        }
+    }
+}
+
+fn remove_dead_blocks(body: &mut MirBody) {
+    let reachable = reachable_blocks(body);
+    // New index of every block which is kept
+    let mut renumbered = vec![None; body.blocks.len()];
+    let mut next = 0;
+    for (idx, block) in renumbered.iter_mut().enumerate() {
+        if reachable.contains(&BasicBlock(idx as u32)) {
+            *block = Some(BasicBlock(next));
+            next += 1;
+        }
+    }
+    let blocks = std::mem::take(&mut body.blocks);
+    for (idx, mut block) in blocks.into_iter().enumerate() {
+        if renumbered[idx].is_none() {
+            continue
+        }
+        block.stmts.retain(|stmt| !matches!(stmt.kind, StatementKind::Nop));
+        successors_mut(&mut block.terminator.kind, |succ| {
+            *succ = renumbered[succ.index()].expect("successor of a reachable block is not reachable");
+        });
+        body.blocks.push(block);
+    }
+}
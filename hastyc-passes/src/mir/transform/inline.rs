@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use crate::{hir::{ty::Ty, DefId}, mir::{BasicBlock, ConstKind, Constant, Local, Mir, MirBody, Operand, Place, Rvalue, Statement, StatementKind, TerminatorKind, RETURN_PLACE}};
+
+use super::{places_mut, successors_mut, MirPass};
+
+/// Most statements that a function can have to be inlined.
+pub const INLINE_THRESHOLD: usize = 16;
+
+/// Pass replacing calls of small functions with their bodies. Only
+/// functions which call nothing are inlined, so inlining never has to
+/// stop at recursion, and bodies are inlined as they were before
+/// the pass ran. Locals of the callee are appended to the caller and
+/// its blocks are appended after the blocks of the caller.
+#[derive(Debug, Default)]
+pub struct Inline;
+
+impl MirPass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&mut self, mir: &mut Mir) {
+        let callees: HashMap<DefId, MirBody> = mir.bodies.iter()
+            .filter(|(_, body)| is_inlinable(body))
+            .map(|(def, body)| (*def, body.clone()))
+            .collect();
+        for body in mir.bodies.values_mut() {
+            // Inlined blocks are appended, but they have no calls
+            for idx in 0..body.blocks.len() {
+                let block = BasicBlock(idx as u32);
+                let TerminatorKind::Call { ref func, target: Some(_), .. } = body.block(block).terminator.kind else { continue };
+                let callee = match callee_ty(body, func) {
+                    Ty::FnDef(def) => callees.get(&def),
+                    _ => None
+                };
+                if let Some(callee) = callee {
+                    inline_call(body, block, callee);
+                }
+            }
+        }
+    }
+}
+
+fn is_inlinable(body: &MirBody) -> bool {
+    let stmts: usize = body.blocks.iter().map(|block| block.stmts.len()).sum();
+    let calls = body.blocks.iter().any(|block| matches!(block.terminator.kind, TerminatorKind::Call { .. }));
+    stmts <= INLINE_THRESHOLD && !calls
+}
+
+fn callee_ty(body: &MirBody, func: &Operand) -> Ty {
+    match func {
+        Operand::Constant(constant) => constant.ty,
+        Operand::Copy(place) if place.projection.is_empty() => body.local(place.local).ty,
+        Operand::Copy(_) => Ty::Err
+    }
+}
+
+/// Replace the call which terminates the block with a jump to a copy of
+/// the callee, which assigns the arguments first and goes to the target
+/// of the call after it stores its result.
+fn inline_call(body: &mut MirBody, block: BasicBlock, callee: &MirBody) {
+    let local_base = body.locals.len() as u32;
+    let block_base = body.blocks.len() as u32;
+    body.locals.extend(callee.locals.iter().cloned());
+    let terminator = &mut body.block_mut(block).terminator;
+    let span = terminator.span;
+    let kind = std::mem::replace(&mut terminator.kind, TerminatorKind::Goto(BasicBlock(block_base)));
+    let TerminatorKind::Call { args, dest, target: Some(target), .. } = kind else { unreachable!("inlined block doesn't end with a call") };
+    for (arg, operand) in callee.args().zip(args) {
+        let place = Place::local(Local(arg.0 + local_base));
+        body.block_mut(block).stmts.push(Statement { kind: StatementKind::Assign(place, Rvalue::Use(operand)), span });
+    }
+    let result = match callee.return_ty() {
+        Ty::Void => Operand::Constant(Constant { kind: ConstKind::Void, ty: Ty::Void }),
+        _ => Operand::Copy(Place::local(Local(RETURN_PLACE.0 + local_base)))
+    };
+    for callee_block in callee.blocks.iter() {
+        let mut inlined = callee_block.clone();
+        places_mut(&mut inlined, |place| place.local = Local(place.local.0 + local_base));
+        successors_mut(&mut inlined.terminator.kind, |succ| *succ = BasicBlock(succ.0 + block_base));
+        if let TerminatorKind::Return = inlined.terminator.kind {
+            let stmt = Statement { kind: StatementKind::Assign(dest.clone(), Rvalue::Use(result.clone())), span };
+            inlined.stmts.push(stmt);
+            inlined.terminator.kind = TerminatorKind::Goto(target);
+        }
+        body.blocks.push(inlined);
+    }
+}
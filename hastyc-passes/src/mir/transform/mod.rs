@@ -0,0 +1,211 @@
+use std::{collections::HashSet, convert::Infallible};
+
+use hastyc_common::session::OptLevel;
+
+use crate::passes::{ASTPass, QueryContext};
+
+use self::{const_fold::ConstFold, copy_prop::CopyProp, dead_blocks::RemoveDeadBlocks, inline::Inline};
+use super::{BasicBlock, BasicBlockData, Mir, MirBody, Operand, Place, Rvalue, StatementKind, TerminatorKind, START_BLOCK};
+
+pub mod const_fold;
+pub mod copy_prop;
+pub mod dead_blocks;
+pub mod inline;
+
+/// Pass that rewrites MIR, usually to optimize it. Passes have to keep
+/// bodies valid, so they can run in any order.
+pub trait MirPass {
+    /// Name of the pass, eg. for printing the pipeline.
+    fn name(&self) -> &'static str;
+
+    fn run(&mut self, mir: &mut Mir);
+}
+
+/// MIR passes that are run in order on the package.
+#[derive(Default)]
+pub struct MirPipeline {
+    passes: Vec<Box<dyn MirPass>>
+}
+
+impl MirPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Passes which optimize MIR at the level. Inlining goes first, so
+    /// that the other passes simplify inlined bodies together with their
+    /// callers, and dead blocks are removed last, after folding cut them
+    /// off.
+    pub fn for_level(level: OptLevel) -> Self {
+        let mut pipeline = Self::new();
+        if level >= OptLevel::O2 {
+            pipeline.add_pass(Inline);
+        }
+        if level >= OptLevel::O1 {
+            pipeline.add_pass(ConstFold);
+            pipeline.add_pass(CopyProp);
+            pipeline.add_pass(RemoveDeadBlocks);
+        }
+        pipeline
+    }
+
+    pub fn with_pass(mut self, pass: impl MirPass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn add_pass(&mut self, pass: impl MirPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Names of passes in the order in which they run.
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    pub fn run(&mut self, mir: &mut Mir) {
+        self.run_with(mir, |_, _| {});
+    }
+
+    /// Run all passes, calling the hook with name of every pass and
+    /// MIR it left, eg. to print it.
+    pub fn run_with(&mut self, mir: &mut Mir, mut after_pass: impl FnMut(&'static str, &Mir)) {
+        for pass in self.passes.iter_mut() {
+            pass.run(mir);
+            after_pass(pass.name(), mir);
+        }
+    }
+}
+
+/// Hook called with MIR after a pass of the pipeline, together with
+/// the query context, eg. to print the MIR with names from HIR.
+type MirDumpHook = Box<dyn FnMut(&Mir, &QueryContext)>;
+
+/// Pass running `MirPipeline::for_level` with the level from options of
+/// the session, on MIR built by `build::BuildMir`.
+#[derive(Default)]
+pub struct OptimizeMir {
+    /// Hooks together with the name of the MIR pass they run after.
+    dumps: Vec<(&'static str, MirDumpHook)>
+}
+
+impl OptimizeMir {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call the hook every time the MIR pass with the name finishes,
+    /// passes which don't run at the level never call it.
+    pub fn add_dump(&mut self, after: &'static str, hook: impl FnMut(&Mir, &QueryContext) + 'static) {
+        self.dumps.push((after, Box::new(hook)));
+    }
+}
+
+impl<'ctx> ASTPass<'ctx> for OptimizeMir {
+    type Err = Infallible;
+
+    fn name(&self) -> &'static str {
+        "optimize-mir"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["build-mir"]
+    }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
+        let Some(mut mir) = ctx.mir.take() else { return Ok(()) };
+        let mut pipeline = MirPipeline::for_level(ctx.sess.options.opt_level);
+        pipeline.run_with(&mut mir, |name, mir| {
+            for (_, hook) in self.dumps.iter_mut().filter(|(after, _)| *after == name) {
+                hook(mir, ctx);
+            }
+        });
+        ctx.mir = Some(mir);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for OptimizeMir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("OptimizeMir")
+            .field("dumps", &self.dumps.iter().map(|(after, _)| *after).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for MirPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("MirPipeline")
+            .field("passes", &self.pass_names())
+            .finish()
+    }
+}
+
+/// Blocks which can be reached from the start of the body.
+pub(super) fn reachable_blocks(body: &MirBody) -> HashSet<BasicBlock> {
+    let mut reachable = HashSet::from([START_BLOCK]);
+    let mut stack = vec![START_BLOCK];
+    while let Some(block) = stack.pop() {
+        for succ in body.block(block).terminator.kind.successors() {
+            if reachable.insert(succ) {
+                stack.push(succ);
+            }
+        }
+    }
+    reachable
+}
+
+/// Call the function with every operand that the block reads.
+pub(super) fn operands_mut(block: &mut BasicBlockData, mut f: impl FnMut(&mut Operand)) {
+    for stmt in block.stmts.iter_mut() {
+        let StatementKind::Assign(_, ref mut rvalue) = stmt.kind else { continue };
+        match rvalue {
+            Rvalue::Use(operand) | Rvalue::UnaryOp(_, operand) => f(operand),
+            Rvalue::BinaryOp(_, lhs, rhs) => {
+                f(lhs);
+                f(rhs);
+            },
+            Rvalue::Aggregate(_, operands) => operands.iter_mut().for_each(&mut f)
+        }
+    }
+    match block.terminator.kind {
+        TerminatorKind::If { ref mut cond, .. } => f(cond),
+        TerminatorKind::Call { ref mut func, ref mut args, .. } => {
+            f(func);
+            args.iter_mut().for_each(f);
+        },
+        TerminatorKind::Goto(_) | TerminatorKind::Return | TerminatorKind::Unreachable => ()
+    }
+}
+
+/// Call the function with every place that the block reads or assigns.
+pub(super) fn places_mut(block: &mut BasicBlockData, mut f: impl FnMut(&mut Place)) {
+    operands_mut(block, |operand| {
+        if let Operand::Copy(ref mut place) = *operand {
+            f(place);
+        }
+    });
+    for stmt in block.stmts.iter_mut() {
+        if let StatementKind::Assign(ref mut place, _) = stmt.kind {
+            f(place);
+        }
+    }
+    if let TerminatorKind::Call { ref mut dest, .. } = block.terminator.kind {
+        f(dest);
+    }
+}
+
+/// Call the function with every block that the terminator can continue at.
+pub(super) fn successors_mut(terminator: &mut TerminatorKind, mut f: impl FnMut(&mut BasicBlock)) {
+    match terminator {
+        TerminatorKind::Goto(block) => f(block),
+        TerminatorKind::If { then, otherwise, .. } => {
+            f(then);
+            f(otherwise);
+        },
+        TerminatorKind::Call { target: Some(block), .. } => f(block),
+        TerminatorKind::Call { target: None, .. } | TerminatorKind::Return | TerminatorKind::Unreachable => ()
+    }
+}
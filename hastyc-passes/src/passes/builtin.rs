@@ -0,0 +1,102 @@
+use hastyc_common::identifiers::{sym, Symbol};
+
+/// Type defined by the language itself, which can be used without
+/// any declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimTy {
+    Int(IntTy),
+    Uint(UintTy),
+    Float(FloatTy),
+    Bool,
+    Char,
+    Str
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntTy {
+    I8, I16, I32, I64, Isize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UintTy {
+    U8, U16, U32, U64, Usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatTy {
+    F32, F64
+}
+
+/// All primitive types together with their names. Names are only used
+/// if nothing declared by the user has the same name, so they can be
+/// shadowed like any other name from an outer scope.
+pub const PRIMITIVE_TYPES: &[(Symbol, PrimTy)] = &[
+    (sym::i8, PrimTy::Int(IntTy::I8)),
+    (sym::i16, PrimTy::Int(IntTy::I16)),
+    (sym::i32, PrimTy::Int(IntTy::I32)),
+    (sym::i64, PrimTy::Int(IntTy::I64)),
+    (sym::isize, PrimTy::Int(IntTy::Isize)),
+    (sym::u8, PrimTy::Uint(UintTy::U8)),
+    (sym::u16, PrimTy::Uint(UintTy::U16)),
+    (sym::u32, PrimTy::Uint(UintTy::U32)),
+    (sym::u64, PrimTy::Uint(UintTy::U64)),
+    (sym::usize, PrimTy::Uint(UintTy::Usize)),
+    (sym::f32, PrimTy::Float(FloatTy::F32)),
+    (sym::f64, PrimTy::Float(FloatTy::F64)),
+    (sym::bool, PrimTy::Bool),
+    (sym::char, PrimTy::Char),
+    (sym::str, PrimTy::Str)
+];
+
+impl PrimTy {
+    /// Primitive type with the given name.
+    pub fn from_symbol(symbol: Symbol) -> Option<Self> {
+        PRIMITIVE_TYPES.iter()
+            .find(|(name, _)| *name == symbol)
+            .map(|(_, ty)| *ty)
+    }
+
+    pub fn symbol(self) -> Symbol {
+        PRIMITIVE_TYPES.iter()
+            .find(|(_, ty)| *ty == self)
+            .map(|(name, _)| *name)
+            .expect("every primitive type has a name")
+    }
+
+    /// Signed and unsigned integers.
+    pub fn is_integer(self) -> bool {
+        matches!(self, Self::Int(_) | Self::Uint(_))
+    }
+
+    /// Integers and floats.
+    pub fn is_numeric(self) -> bool {
+        self.is_integer() || matches!(self, Self::Float(_))
+    }
+
+    /// Size of integer types in bits, pointer sized integers are 64 bits
+    /// as only 64 bit targets are supported.
+    pub fn int_bits(self) -> Option<u32> {
+        Some(match self {
+            Self::Int(IntTy::I8) | Self::Uint(UintTy::U8) => 8,
+            Self::Int(IntTy::I16) | Self::Uint(UintTy::U16) => 16,
+            Self::Int(IntTy::I32) | Self::Uint(UintTy::U32) => 32,
+            Self::Int(IntTy::I64 | IntTy::Isize) | Self::Uint(UintTy::U64 | UintTy::Usize) => 64,
+            _ => return None
+        })
+    }
+
+    /// Smallest and largest value of integer types.
+    pub fn int_range(self) -> Option<(i128, i128)> {
+        let bits = self.int_bits()?;
+        Some(match self {
+            Self::Int(_) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+            _ => (0, (1 << bits) - 1)
+        })
+    }
+}
+
+impl std::fmt::Display for PrimTy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
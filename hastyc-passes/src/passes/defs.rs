@@ -0,0 +1,222 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use hastyc_common::{identifiers::{kw, ASTNodeID, Ident, PkgID, Symbol}, span::Span};
+use hastyc_parser::parser::{DataVariant, EnumVariant, Expr, ExprKind, FnInput, Item, ItemKind, LetBinding, Package, Pat, PatKind, Visibility};
+
+use crate::visit::{walk_enum_variant, walk_expr, walk_fn_input, walk_item, walk_let_binding, DepthLimit, Visitor};
+
+/// Index of a definition in `Definitions` of its package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DefIndex(pub u32);
+
+impl DefIndex {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Definition of an item, variant, field or local variable. Unlike
+/// `ASTNodeID`, this is unique between packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DefId {
+    pub pkg: PkgID,
+    pub index: DefIndex
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefKind {
+    Module,
+    Fn,
+    Struct,
+    Enum,
+    Variant,
+    Field,
+    /// Function parameter or variable bound in a body.
+    Local
+}
+
+impl DefKind {
+    /// Name of the kind of definition, used in diagnostics.
+    pub fn descr(self) -> &'static str {
+        match self {
+            Self::Module => "module",
+            Self::Fn => "function",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Variant => "variant",
+            Self::Field => "field",
+            Self::Local => "local variable"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DefData {
+    /// Node that declares the definition. For locals this is the
+    /// parameter or `let` binding, and the pattern for `for` loops.
+    pub ast_id: ASTNodeID,
+    pub kind: DefKind,
+    /// Definition that contains this one, None only for the root module.
+    /// Locals and items declared in a body belong to its function.
+    pub parent: Option<DefId>,
+    pub visibility: Visibility,
+    pub ident: Ident,
+    pub span: Span
+}
+
+/// Table of all definitions in a package, which name resolution and
+/// later passes use to refer to them. The root module is always first.
+#[derive(Debug)]
+pub struct Definitions {
+    pkg: PkgID,
+    defs: Vec<DefData>,
+    def_ids: HashMap<ASTNodeID, DefId>
+}
+
+impl Definitions {
+    /// Collect definitions of the package, in the order they appear in it.
+    pub fn collect(package: &Package, max_expr_depth: u32) -> Self {
+        let mut collector = DefCollector {
+            defs: Self { pkg: package.pkg, defs: Vec::new(), def_ids: HashMap::new() },
+            parent: None,
+            depth: DepthLimit::new(max_expr_depth)
+        };
+        let root = collector.alloc(package.id, DefKind::Module, Ident::dummy(), Visibility::Public, Span::dummy());
+        collector.with_parent(root, |collector| {
+            let Ok(()) = collector.visit_item_stream(&package.items);
+        });
+        collector.defs
+    }
+
+    pub fn get(&self, id: DefId) -> &DefData {
+        assert_eq!(id.pkg, self.pkg, "definition {:?} is from another package", id);
+        &self.defs[id.index.index()]
+    }
+
+    /// Definition declared by the AST node, None if it doesn't declare any.
+    pub fn def_id(&self, ast_id: ASTNodeID) -> Option<DefId> {
+        self.def_ids.get(&ast_id).copied()
+    }
+
+    /// Module of the whole package.
+    pub fn root(&self) -> DefId {
+        DefId { pkg: self.pkg, index: DefIndex(0) }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (DefId, &DefData)> {
+        self.defs.iter()
+            .enumerate()
+            .map(|(idx, data)| (DefId { pkg: self.pkg, index: DefIndex(idx as u32) }, data))
+    }
+
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+}
+
+/// Binding name of the pattern.
+pub(crate) fn pat_ident(pat: &Pat) -> Ident {
+    match pat.kind {
+        PatKind::Ident(ref ident) => ident.clone(),
+        PatKind::SelfPat => Ident::new(kw::SelfLower, pat.span)
+    }
+}
+
+struct DefCollector {
+    defs: Definitions,
+    parent: Option<DefId>,
+    depth: DepthLimit
+}
+
+impl DefCollector {
+    fn alloc(&mut self, ast_id: ASTNodeID, kind: DefKind, ident: Ident, visibility: Visibility, span: Span) -> DefId {
+        let id = DefId { pkg: self.defs.pkg, index: DefIndex(self.defs.defs.len() as u32) };
+        self.defs.defs.push(DefData { ast_id, kind, parent: self.parent, visibility, ident, span });
+        self.defs.def_ids.insert(ast_id, id);
+        id
+    }
+
+    fn local(&mut self, ast_id: ASTNodeID, pat: &Pat, span: Span) {
+        self.alloc(ast_id, DefKind::Local, pat_ident(pat), Visibility::Inherited, span);
+    }
+
+    fn with_parent(&mut self, parent: DefId, f: impl FnOnce(&mut Self)) {
+        let outer = self.parent.replace(parent);
+        f(self);
+        self.parent = outer;
+    }
+}
+
+impl<'ast> Visitor<'ast> for DefCollector {
+    type Break = Infallible;
+
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Self::Break> {
+        let kind = match item.kind {
+            // Imports only bring in names of other definitions
+            ItemKind::Import(..) => return Ok(()),
+            ItemKind::Module(_) => DefKind::Module,
+            ItemKind::Fn(_) => DefKind::Fn,
+            ItemKind::Struct(_) => DefKind::Struct,
+            ItemKind::Enum(_) => DefKind::Enum
+        };
+        let id = self.alloc(item.id, kind, item.ident.clone(), item.visibility, item.span);
+        // Depth is counted from the item, like in name resolution
+        let depth = self.depth;
+        self.depth = depth.nested();
+        self.with_parent(id, |collector| {
+            let Ok(()) = walk_item(collector, item);
+        });
+        self.depth = depth;
+        Ok(())
+    }
+
+    fn visit_enum_variant(&mut self, variant: &'ast EnumVariant) -> Result<(), Self::Break> {
+        let id = self.alloc(variant.id, DefKind::Variant, variant.ident.clone(), variant.vis, variant.span);
+        self.with_parent(id, |collector| {
+            let Ok(()) = walk_enum_variant(collector, variant);
+        });
+        Ok(())
+    }
+
+    fn visit_data_variant(&mut self, data: &'ast DataVariant) -> Result<(), Self::Break> {
+        let (DataVariant::Struct { ref fields } | DataVariant::Tuple { ref fields }) = data else {
+            return Ok(())
+        };
+        for (idx, field) in fields.iter().enumerate() {
+            // Tuple fields are named by their index, like in name resolution
+            let ident = match field.ident {
+                Some(ref ident) => ident.clone(),
+                None => Ident::new(Symbol::intern(&idx.to_string()), field.span)
+            };
+            self.alloc(field.id, DefKind::Field, ident, field.vis, field.span);
+        }
+        Ok(())
+    }
+
+    fn visit_fn_input(&mut self, input: &'ast FnInput) -> Result<(), Self::Break> {
+        self.local(input.id, &input.pat, input.span);
+        walk_fn_input(self, input)
+    }
+
+    fn visit_let_binding(&mut self, binding: &'ast LetBinding) -> Result<(), Self::Break> {
+        self.local(binding.id, &binding.pat, binding.span);
+        walk_let_binding(self, binding)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Self::Break> {
+        // Too deeply nested expressions are reported by name resolution
+        if !self.depth.enter() {
+            return Ok(())
+        }
+        if let ExprKind::For(ref pat, ..) = expr.kind {
+            self.local(pat.id, pat, pat.span);
+        }
+        let result = walk_expr(self, expr);
+        self.depth.exit();
+        result
+    }
+}
@@ -0,0 +1,126 @@
+use hastyc_common::{error::{CommonErrorContext, ErrorDisplay, ErrorFmt}, expansion::ExpnKind, span::{Span, SyntaxContext}};
+use hastyc_parser::parser::{Attributes, Block, Expr, ExprKind, Item, ItemStream, Stmt, StmtKind, StmtStream};
+
+use crate::{fold::{walk_expr, walk_item, Folder}, visit::DepthLimit};
+
+use super::transform::{ASTMutPass, MutPassContext};
+
+/// Lowers `while` loops into `loop`, so that later passes only have to
+/// handle a single looping construct.
+///
+/// `while cond { .. }` becomes
+/// ```text
+/// loop { if cond { .. } else { break; } }
+/// ```
+/// Created nodes get spans of the loop marked as coming from desugaring.
+/// There are no iterators yet, so `for` loops are reported and replaced
+/// with `ExprKind::Err`.
+#[derive(Debug, Default)]
+pub struct DesugarLoops;
+
+impl ASTMutPass for DesugarLoops {
+    fn name(&self) -> &'static str {
+        "desugar-loops"
+    }
+
+    fn run(&mut self, items: ItemStream, cx: &MutPassContext) -> ItemStream {
+        LoopDesugarer { cx, depth: DepthLimit::new(cx.sess.options.max_expr_depth) }.fold_item_stream(items)
+    }
+}
+
+struct LoopDesugarer<'a, 'cx> {
+    cx: &'a MutPassContext<'cx>,
+    depth: DepthLimit
+}
+
+impl Folder for LoopDesugarer<'_, '_> {
+    fn fold_item(&mut self, item: Item) -> Item {
+        let depth = self.depth;
+        self.depth = depth.nested();
+        let item = walk_item(self, item);
+        self.depth = depth;
+        item
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        // Too deeply nested expressions are left as they are, name
+        // resolution reports them and they are never lowered
+        if !self.depth.enter() {
+            return expr
+        }
+        // Nested loops are lowered first
+        let mut expr = walk_expr(self, expr);
+        self.depth.exit();
+        expr.kind = match expr.take_kind() {
+            ExprKind::While(condition, body) => {
+                let span = Self::desugared_span(expr.span, "while loop");
+                self.loop_with_condition(*condition, *body, span)
+            },
+            ExprKind::For(_, iter, _) => {
+                self.cx.sess.emit(UnsupportedFor { span: Span::from_begin_end(expr.span, iter.span) });
+                ExprKind::Err
+            },
+            kind => kind
+        };
+        expr
+    }
+}
+
+impl LoopDesugarer<'_, '_> {
+    fn desugared_span(span: Span, name: &'static str) -> Span {
+        span.with_ctxt(SyntaxContext::fresh(ExpnKind::Desugaring(name), span))
+    }
+
+    /// `loop { if condition body else { break; } }`
+    fn loop_with_condition(&self, condition: Expr, body: Block, span: Span) -> ExprKind {
+        let brk = self.stmt(StmtKind::Expr(Box::new(self.expr(ExprKind::Break(None), span))), span);
+        let else_block = self.expr(ExprKind::Block(Box::new(self.block(vec![brk], span))), span);
+        let if_expr = self.expr(ExprKind::If(Box::new(condition), Box::new(body), Some(Box::new(else_block))), span);
+        let loop_body = self.block(vec![self.stmt(StmtKind::ExprNS(Box::new(if_expr)), span)], span);
+        ExprKind::Loop(Box::new(loop_body))
+    }
+
+    fn expr(&self, kind: ExprKind, span: Span) -> Expr {
+        Expr {
+            id: self.cx.next_id(),
+            kind,
+            span,
+            attrs: Attributes::empty()
+        }
+    }
+
+    fn stmt(&self, kind: StmtKind, span: Span) -> Stmt {
+        Stmt {
+            id: self.cx.next_id(),
+            kind,
+            span
+        }
+    }
+
+    fn block(&self, stmts: Vec<Stmt>, span: Span) -> Block {
+        Block {
+            stmts: StmtStream::from_vec(stmts),
+            id: self.cx.next_id(),
+            span
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnsupportedFor {
+    /// Span from `for` to the end of the iterated expression.
+    pub span: Span
+}
+
+impl<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> for UnsupportedFor {
+    fn code(&self) -> Option<&'static str> {
+        Some("H0211")
+    }
+
+    fn fmt(&self, fmt: &mut ErrorFmt<'ctx>, ctx: &'ctx CommonErrorContext) {
+        fmt
+            .title("`for` loops are not supported yet.")
+            .source(ctx.source, self.span)
+            .help("Use `while` with a counter instead.");
+    }
+}
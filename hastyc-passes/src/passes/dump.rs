@@ -0,0 +1,510 @@
+use std::{collections::{BTreeMap, HashMap}, convert::Infallible, fmt::{Display, Formatter, Result}};
+
+use hastyc_common::{identifiers::{kw, ASTNodeID}, path::Path, source::SourceMap, span::Span};
+use hastyc_parser::parser::{Expr, ExprKind, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, TyKind};
+
+use crate::{hir::ty::TypeDisplay, util::{RibKind, RibTree}, visit::{walk_expr, walk_item, DepthLimit, Visitor}};
+
+use super::{builtin::PrimTy, defs::{DefId, DefKind}, QueryContext, ResolveIdQuery, ResolvedId};
+
+/// Longest snippet of source shown next to a node, longer ones are cut.
+const MAX_SNIPPET: usize = 32;
+
+/// Renders what every resolved path refers to, one line per path sorted
+/// by where it is used, like:
+///
+/// ```text
+/// (3:13-3:14, a) -> local variable (2:9-2:10, a)
+/// ```
+///
+/// Output doesn't depend on ids or on the order in which modules were
+/// resolved, so it can be compared between runs.
+pub struct ResolvedNamesDisplay<'a, 'cx> {
+    cx: &'a QueryContext<'cx>
+}
+
+impl<'a, 'cx> ResolvedNamesDisplay<'a, 'cx> {
+    pub fn new(cx: &'a QueryContext<'cx>) -> Self {
+        Self { cx }
+    }
+}
+
+impl Display for ResolvedNamesDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let cx = self.cx;
+        let symbols = &cx.package.symbol_storage;
+        let mut lines: Vec<(Span, String, String)> = cx.resolved_names.to_vec().into_iter()
+            .filter_map(|(node, def)| {
+                let path = used_path(cx.query(ResolveIdQuery(node)))?;
+                let def = cx.defs.get(def);
+                let target = format!(
+                    "{} ({}, {})",
+                    def.kind.descr(),
+                    SpanDisplay::new(def.ident.span, &cx.sess.source_map),
+                    def.ident.text(symbols)
+                );
+                Some((path.span, PathDisplay(path, cx).to_string(), target))
+            })
+            .collect();
+        lines.sort_by(|(a, a_name, _), (b, b_name, _)| span_key(a).cmp(&span_key(b)).then_with(|| a_name.cmp(b_name)));
+        for (span, name, target) in lines {
+            writeln!(f, "({}, {}) -> {}", SpanDisplay::new(span, &cx.sess.source_map), name, target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders source files of the package with what every name resolved to
+/// written after it, and names which didn't resolve flagged, like:
+///
+/// ```text
+/// // main.hty
+/// fn main() {
+///     let a = 1;
+///     a[local variable #4 at 2:9-2:10] + b[unresolved]
+/// }
+/// ```
+///
+/// Names are paths in expressions and types, and imported names, which
+/// are annotated after the whole import tree. Numbers are indices of
+/// definitions, targets in other files have the file name before their
+/// span, and primitive types are written like `i32[primitive i32]`.
+pub struct ResolvedSourceDisplay<'a, 'cx> {
+    cx: &'a QueryContext<'cx>
+}
+
+impl<'a, 'cx> ResolvedSourceDisplay<'a, 'cx> {
+    pub fn new(cx: &'a QueryContext<'cx>) -> Self {
+        Self { cx }
+    }
+
+    fn target(&self, def: DefId, file: &Span) -> String {
+        let def_data = self.cx.defs.get(def);
+        let source_map = &self.cx.sess.source_map;
+        let span = SpanDisplay::new(def_data.ident.span, source_map);
+        match source_map.source_of(&def_data.ident.span) {
+            Some(source) if source.id != file.source => format!("{} #{} at {}:{}", def_data.kind.descr(), def.index.0, source.name, span),
+            _ => format!("{} #{} at {}", def_data.kind.descr(), def.index.0, span)
+        }
+    }
+}
+
+impl Display for ResolvedSourceDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let cx = self.cx;
+        // Paths are recorded under nodes that use them, so they are found
+        // by their spans while walking
+        let mut paths: HashMap<(u32, u32, u32), Resolution> = HashMap::new();
+        for (node, def) in cx.resolved_names.to_vec() {
+            if let Some(path) = used_path(cx.query(ResolveIdQuery(node))) {
+                paths.insert(span_key(&path.span), Resolution::Def(def));
+            }
+        }
+        for (node, prim) in cx.prim_tys.to_vec() {
+            if let Some(path) = used_path(cx.query(ResolveIdQuery(node))) {
+                paths.insert(span_key(&path.span), Resolution::Prim(prim));
+            }
+        }
+        let mut collector = NameCollector { cx, paths: &paths, names: Vec::new(), depth: DepthLimit::new(cx.sess.options.max_expr_depth) };
+        let Ok(()) = collector.visit_item_stream(&cx.package.items);
+        let mut names: Vec<(Span, String)> = collector.names.into_iter()
+            .map(|(span, resolution)| {
+                let text = match resolution {
+                    Some(Resolution::Def(def)) => self.target(def, &span),
+                    Some(Resolution::Prim(prim)) => format!("primitive {}", prim),
+                    None => "unresolved".to_string()
+                };
+                (span, text)
+            })
+            .collect();
+        names.sort_by_key(|(span, _)| (span.source.0, span.end, span.start));
+
+        for source in cx.sess.source_map.files().iter().filter(|source| source.pkg == cx.package.pkg) {
+            let Some(ref text) = source.src else { continue };
+            let text = text.as_str();
+            writeln!(f, "// {}", source.name)?;
+            let mut pos = 0;
+            for (span, name) in names.iter().filter(|(span, _)| span.source == source.id) {
+                let end = (span.end as usize).clamp(pos, text.len());
+                let Some(before) = text.get(pos..end) else { continue };
+                write!(f, "{}[{}]", before, name)?;
+                pos = end;
+            }
+            f.write_str(&text[pos..])?;
+            if !text.ends_with('\n') {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Resolution {
+    Def(DefId),
+    Prim(PrimTy)
+}
+
+/// Finds every name with what it resolved to, if anything.
+struct NameCollector<'a, 'cx> {
+    cx: &'a QueryContext<'cx>,
+    paths: &'a HashMap<(u32, u32, u32), Resolution>,
+    names: Vec<(Span, Option<Resolution>)>,
+    depth: DepthLimit
+}
+
+impl NameCollector<'_, '_> {
+    fn import_tree(&mut self, tree: &ImportTree, node: ASTNodeID) {
+        if let ImportTreeKind::Nested(ref trees) = tree.kind {
+            for (subtree, id) in trees.iter() {
+                self.import_tree(subtree, *id);
+            }
+            return
+        }
+        let def = self.cx.resolved_names.get(&node);
+        self.names.push((tree.span, def.map(Resolution::Def)));
+    }
+}
+
+impl<'ast> Visitor<'ast> for NameCollector<'_, '_> {
+    type Break = Infallible;
+
+    fn visit_item(&mut self, item: &'ast Item) -> std::result::Result<(), Self::Break> {
+        if let ItemKind::Import(_, ref tree) = item.kind {
+            self.import_tree(tree, item.id);
+            return Ok(())
+        }
+        walk_item(self, item)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> std::result::Result<(), Self::Break> {
+        // Name resolution skips these as well
+        if !self.depth.enter() {
+            return Ok(())
+        }
+        walk_expr(self, expr)?;
+        self.depth.exit();
+        Ok(())
+    }
+
+    fn visit_path(&mut self, path: &'ast Path) -> std::result::Result<(), Self::Break> {
+        // Nodes added by passes, like the prelude, have dummy spans
+        if path.span.start < path.span.end {
+            self.names.push((path.span, self.paths.get(&span_key(&path.span)).copied()));
+        }
+        Ok(())
+    }
+}
+
+/// Renders ribs of every function as a tree, with names in the order in
+/// which they were bound, like:
+///
+/// ```text
+/// fn main (1:4-1:8)
+///     fn: a (1:9-1:10)
+///         block: b (2:9-2:10)
+///             loop
+/// ```
+///
+/// Ribs are only recorded if `EmitKind::Scopes` was requested, otherwise
+/// this renders nothing.
+pub struct ScopesDisplay<'a, 'cx> {
+    cx: &'a QueryContext<'cx>
+}
+
+impl<'a, 'cx> ScopesDisplay<'a, 'cx> {
+    pub fn new(cx: &'a QueryContext<'cx>) -> Self {
+        Self { cx }
+    }
+
+    fn rib(&self, f: &mut Formatter<'_>, rib: &RibTree, indent: usize) -> Result {
+        write!(f, "{:indent$}{}", "", rib_name(rib.kind), indent = indent * 4)?;
+        for (idx, (ident, _)) in rib.names.iter().enumerate() {
+            let sep = if idx == 0 { ":" } else { "," };
+            write!(
+                f,
+                "{} {} ({})",
+                sep,
+                ident.text(&self.cx.package.symbol_storage),
+                SpanDisplay::new(ident.span, &self.cx.sess.source_map)
+            )?;
+        }
+        writeln!(f)?;
+        for child in rib.children.iter() {
+            self.rib(f, child, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ScopesDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        // Intrinsics of the prelude aren't declared in any source
+        let mut scopes: Vec<_> = self.cx.scopes.iter().filter(|scope| !scope.ident.span.is_dummy()).collect();
+        scopes.sort_by(|a, b| {
+            span_key(&a.ident.span).cmp(&span_key(&b.ident.span))
+                .then_with(|| a.ident.symbol.as_str().cmp(b.ident.symbol.as_str()))
+        });
+        for scope in scopes {
+            writeln!(
+                f,
+                "fn {} ({})",
+                scope.ident.text(&self.cx.package.symbol_storage),
+                SpanDisplay::new(scope.ident.span, &self.cx.sess.source_map)
+            )?;
+            self.rib(f, &scope.ribs, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders the type table, one line per node sorted by its span,
+/// together with the start of its source, like:
+///
+/// ```text
+/// (2:13-2:18) expr `1 + 2`: i32
+/// ```
+pub struct TypesDisplay<'a, 'cx> {
+    cx: &'a QueryContext<'cx>
+}
+
+impl<'a, 'cx> TypesDisplay<'a, 'cx> {
+    pub fn new(cx: &'a QueryContext<'cx>) -> Self {
+        Self { cx }
+    }
+}
+
+impl Display for TypesDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let cx = self.cx;
+        // Types can only be found for lowered packages
+        let Some(ref hir) = cx.hir else { return Ok(()) };
+        let mut lines: Vec<(Span, &str, String, String)> = cx.types.iter()
+            .filter_map(|(node, ty)| {
+                let (descr, span) = node_descr(cx.query(ResolveIdQuery(node))).filter(|(_, span)| !span.is_dummy())?;
+                let snippet = snippet(cx.sess.source_map.span_text(&span).unwrap_or(""));
+                Some((span, descr, snippet, TypeDisplay::new(ty, hir, &cx.package.symbol_storage).to_string()))
+            })
+            .collect();
+        lines.sort_by(|a, b| span_key(&a.0).cmp(&span_key(&b.0)).then_with(|| (a.1, &a.3).cmp(&(b.1, &b.3))));
+        for (span, descr, snippet, ty) in lines {
+            writeln!(f, "({}) {} `{}`: {}", SpanDisplay::new(span, &cx.sess.source_map), descr, snippet, ty)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders modules and imports between them as a graph in the DOT
+/// language of Graphviz, like:
+///
+/// ```text
+/// digraph modules {
+///     m0 [label="pkg"];
+///     m1 [label="pkg::math"];
+///     m0 -> m1;
+///     m0 -> m1 [style=dashed, label="add, sub"];
+/// }
+/// ```
+///
+/// Solid edges go from modules to their submodules, dashed ones from
+/// modules to the modules of what they import, labeled with imported
+/// names. Imports inside of functions are not included.
+pub struct ModuleGraphDisplay<'a, 'cx> {
+    cx: &'a QueryContext<'cx>
+}
+
+impl<'a, 'cx> ModuleGraphDisplay<'a, 'cx> {
+    pub fn new(cx: &'a QueryContext<'cx>) -> Self {
+        Self { cx }
+    }
+
+    /// Full path of the module, root module is `pkg`.
+    fn module_path(&self, module: DefId) -> String {
+        let symbols = &self.cx.package.symbol_storage;
+        let mut segments = Vec::new();
+        let mut current = module;
+        while let Some(parent) = self.cx.defs.get(current).parent {
+            segments.push(self.cx.defs.get(current).ident.text(symbols));
+            current = parent;
+        }
+        segments.push("pkg");
+        segments.reverse();
+        segments.join("::")
+    }
+
+    /// The definition if it is a module, otherwise the module it is in.
+    fn module_of(&self, mut def: DefId) -> Option<DefId> {
+        while self.cx.defs.get(def).kind != DefKind::Module {
+            def = self.cx.defs.get(def).parent?;
+        }
+        Some(def)
+    }
+
+    fn collect_imports(&self, module: DefId, items: &ItemStream, edges: &mut BTreeMap<(DefId, DefId), Vec<String>>) {
+        for item in items.items.iter() {
+            match item.kind {
+                ItemKind::Module(ref items) => {
+                    if let Some(submodule) = self.cx.defs.def_id(item.id) {
+                        self.collect_imports(submodule, items, edges);
+                    }
+                },
+                ItemKind::Import(_, ref tree) => self.collect_tree(module, tree, item.id, edges),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_tree(&self, module: DefId, tree: &ImportTree, node: ASTNodeID, edges: &mut BTreeMap<(DefId, DefId), Vec<String>>) {
+        let symbols = &self.cx.package.symbol_storage;
+        let name = match tree.kind {
+            ImportTreeKind::Nested(ref trees) => {
+                for (subtree, id) in trees.iter() {
+                    self.collect_tree(module, subtree, *id, edges);
+                }
+                return
+            },
+            ImportTreeKind::Glob => "*".to_string(),
+            ImportTreeKind::Simple(ref name, _) => name.text(symbols).to_string(),
+            ImportTreeKind::SelfImport(_) => "self".to_string()
+        };
+        let Some(target) = self.cx.resolved_names.get(&node) else { return };
+        let Some(target) = self.module_of(target) else { return };
+        if self.cx.defs.get(target).ident.symbol == kw::Prelude {
+            return
+        }
+        let names = edges.entry((module, target)).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+}
+
+impl Display for ModuleGraphDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let cx = self.cx;
+        let modules: Vec<DefId> = cx.defs.iter()
+            .filter(|(_, def)| def.kind == DefKind::Module && def.ident.symbol != kw::Prelude)
+            .map(|(id, _)| id)
+            .collect();
+        writeln!(f, "digraph modules {{")?;
+        writeln!(f, "    node [shape=box, fontname=\"monospace\"];")?;
+        for module in modules.iter() {
+            writeln!(f, "    m{} [label=\"{}\"];", module.index.0, self.module_path(*module))?;
+        }
+        for module in modules.iter() {
+            if let Some(parent) = cx.defs.get(*module).parent {
+                writeln!(f, "    m{} -> m{};", parent.index.0, module.index.0)?;
+            }
+        }
+        let mut edges = BTreeMap::new();
+        self.collect_imports(cx.defs.root(), &cx.package.items, &mut edges);
+        for ((from, to), names) in edges {
+            writeln!(f, "    m{} -> m{} [style=dashed, label=\"{}\"];", from.index.0, to.index.0, names.join(", "))?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Renders span as `line:col-line:col`, `?` if its file is not loaded.
+pub struct SpanDisplay<'a> {
+    span: Span,
+    source_map: &'a SourceMap
+}
+
+impl<'a> SpanDisplay<'a> {
+    pub fn new(span: Span, source_map: &'a SourceMap) -> Self {
+        Self { span, source_map }
+    }
+}
+
+impl Display for SpanDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let source = match self.source_map.source_of(&self.span) {
+            Some(source) if !self.span.is_dummy() => source,
+            _ => return f.write_str("?")
+        };
+        let (start_line, start_col) = source.lookup_line(self.span.start);
+        let (end_line, end_col) = source.lookup_line(self.span.end);
+        write!(f, "{}:{}-{}:{}", start_line, start_col, end_line, end_col)
+    }
+}
+
+struct PathDisplay<'a, 'cx>(&'a Path, &'a QueryContext<'cx>);
+
+impl Display for PathDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for (idx, segment) in self.0.segments.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("::")?;
+            }
+            f.write_str(segment.ident.text(&self.1.package.symbol_storage))?;
+        }
+        Ok(())
+    }
+}
+
+/// Path written in the node which name resolution recorded it under,
+/// types are recorded under the node that declares them.
+fn used_path(node: ResolvedId<'_>) -> Option<&Path> {
+    let ty = match node {
+        ResolvedId::Expr(expr) => return match expr.kind {
+            ExprKind::Path(ref path) => Some(path),
+            ExprKind::StructLit(ref lit) => Some(&lit.path),
+            _ => None
+        },
+        ResolvedId::Ty(ty) => ty,
+        ResolvedId::FnInput(input) => &input.ty,
+        ResolvedId::LetBinding(binding) => binding.ty.as_ref()?,
+        ResolvedId::FieldDef(field) => &field.ty,
+        _ => return None
+    };
+    match ty.kind {
+        TyKind::Path(ref path) => Some(path),
+        _ => None
+    }
+}
+
+/// Kind of the node together with its span, None for nodes without one.
+fn node_descr(node: ResolvedId<'_>) -> Option<(&'static str, Span)> {
+    Some(match node {
+        ResolvedId::Item(item) => ("item", item.span),
+        ResolvedId::Expr(expr) => ("expr", expr.span),
+        ResolvedId::Stmt(stmt) => ("stmt", stmt.span),
+        ResolvedId::FnInput(input) => ("param", input.span),
+        ResolvedId::Block(block) => ("block", block.span),
+        ResolvedId::LetBinding(binding) => ("let", binding.span),
+        ResolvedId::Pat(pat) => ("pat", pat.span),
+        ResolvedId::Ty(ty) => ("ty", ty.span),
+        ResolvedId::FieldDef(field) => ("field", field.span),
+        ResolvedId::EnumVariant(variant) => ("variant", variant.span),
+        ResolvedId::FieldLit(field) => ("field", field.span),
+        ResolvedId::Unknown | ResolvedId::Package(_) | ResolvedId::Lit(_) => return None
+    })
+}
+
+fn rib_name(kind: RibKind) -> &'static str {
+    match kind {
+        RibKind::Module => "module",
+        RibKind::Item => "item",
+        RibKind::FnBoundary => "fn",
+        RibKind::Closure(_) => "closure",
+        RibKind::Block => "block",
+        RibKind::Loop => "loop"
+    }
+}
+
+/// First line of the text, cut to `MAX_SNIPPET` characters.
+fn snippet(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("");
+    if line.len() < text.len() || line.chars().count() > MAX_SNIPPET {
+        let cut: String = line.chars().take(MAX_SNIPPET).collect();
+        format!("{}...", cut.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Spans sort by file first, so that dumps don't mix files.
+fn span_key(span: &Span) -> (u32, u32, u32) {
+    (span.source.0, span.start, span.end)
+}
@@ -0,0 +1,155 @@
+use hastyc_common::{identifiers::{kw, sym, Symbol}, source::SourceMap, span::Span};
+
+use crate::hir::{DefId, DefKind, Hir};
+
+/// Function of the prelude which is implemented by the runtime. The VM
+/// implements them natively, and native backends call functions of their
+/// runtime library, named by `Intrinsic::runtime_symbol`, so programs
+/// behave the same no matter how they are run.
+///
+/// The runtime also allocates memory for strings that it creates, but
+/// allocation can't be used from code, as the language has no pointers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intrinsic {
+    Print,
+    Println,
+    /// Stop the program, printing the message together with the
+    /// location of the call.
+    Panic,
+    /// Join two strings into a new one.
+    Concat,
+    FormatI32,
+    FormatI64,
+    FormatU64,
+    /// Shortest text which reads back as the same float, without
+    /// an exponent, like `1.5` or `100`.
+    FormatF64,
+    /// `true` or `false`.
+    FormatBool
+}
+
+/// What an intrinsic returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntrinsicOutput {
+    Void,
+    Never,
+    /// Primitive type with the name.
+    Ty(Symbol)
+}
+
+pub const INTRINSICS: &[Intrinsic] = &[
+    Intrinsic::Print,
+    Intrinsic::Println,
+    Intrinsic::Panic,
+    Intrinsic::Concat,
+    Intrinsic::FormatI32,
+    Intrinsic::FormatI64,
+    Intrinsic::FormatU64,
+    Intrinsic::FormatF64,
+    Intrinsic::FormatBool
+];
+
+impl Intrinsic {
+    pub fn name(self) -> Symbol {
+        match self {
+            Self::Print => sym::print,
+            Self::Println => sym::println,
+            Self::Panic => sym::panic,
+            Self::Concat => sym::concat,
+            Self::FormatI32 => sym::format_i32,
+            Self::FormatI64 => sym::format_i64,
+            Self::FormatU64 => sym::format_u64,
+            Self::FormatF64 => sym::format_f64,
+            Self::FormatBool => sym::format_bool
+        }
+    }
+
+    /// Names and types of the parameters.
+    pub fn inputs(self) -> &'static [(&'static str, Symbol)] {
+        match self {
+            Self::Print | Self::Println => &[("value", sym::str)],
+            Self::Panic => &[("message", sym::str)],
+            Self::Concat => &[("a", sym::str), ("b", sym::str)],
+            Self::FormatI32 => &[("value", sym::i32)],
+            Self::FormatI64 => &[("value", sym::i64)],
+            Self::FormatU64 => &[("value", sym::u64)],
+            Self::FormatF64 => &[("value", sym::f64)],
+            Self::FormatBool => &[("value", sym::bool)]
+        }
+    }
+
+    pub fn output(self) -> IntrinsicOutput {
+        match self {
+            Self::Print | Self::Println => IntrinsicOutput::Void,
+            Self::Panic => IntrinsicOutput::Never,
+            _ => IntrinsicOutput::Ty(sym::str)
+        }
+    }
+
+    /// Name of the function implementing the intrinsic in the runtime
+    /// library of native backends.
+    pub fn runtime_symbol(self) -> &'static str {
+        match self {
+            Self::Print => "hasty_rt_print",
+            Self::Println => "hasty_rt_println",
+            Self::Panic => "hasty_rt_panic",
+            Self::Concat => "hasty_rt_concat",
+            Self::FormatI32 => "hasty_rt_format_i32",
+            Self::FormatI64 => "hasty_rt_format_i64",
+            Self::FormatU64 => "hasty_rt_format_u64",
+            Self::FormatF64 => "hasty_rt_format_f64",
+            Self::FormatBool => "hasty_rt_format_bool"
+        }
+    }
+
+    /// Whether location of the call is passed after the other arguments,
+    /// as a string made by `location`.
+    pub fn takes_location(self) -> bool {
+        matches!(self, Self::Panic)
+    }
+
+    pub fn from_name(name: Symbol) -> Option<Self> {
+        INTRINSICS.iter().copied().find(|intrinsic| intrinsic.name() == name)
+    }
+
+    /// Intrinsic that the function is, only functions declared in the
+    /// prelude are, not ones the user declared with the same name.
+    pub fn of_def(hir: &Hir, def: DefId) -> Option<Self> {
+        let item = hir.def(def);
+        let DefKind::Fn { body: None, .. } = item.kind else { return None };
+        let parent = hir.def(item.parent?);
+        if parent.ident.symbol != kw::Prelude {
+            return None
+        }
+        Self::from_name(item.ident.symbol)
+    }
+}
+
+/// Location passed to intrinsics, like `src/main.hty:3:5`.
+pub fn location(source_map: &SourceMap, span: Span) -> String {
+    match source_map.get(span.source) {
+        Some(file) => {
+            let (line, col) = file.lookup_line(span.start);
+            format!("{}:{}:{}", file.name, line, col)
+        },
+        None => "<unknown>".to_string()
+    }
+}
+
+/// Failed check of integer arithmetic, which the VM and code of native
+/// backends panic with, with the same message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+    /// Result doesn't fit into its type, or a shift is larger than it.
+    Overflow,
+    DivisionByZero
+}
+
+impl ArithmeticError {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Overflow => "attempt to compute with overflow",
+            Self::DivisionByZero => "attempt to divide by zero"
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, convert::Infallible, time::{Duration, Instant}};
+
+use super::{ASTPass, QueryContext};
+
+/// Pass as it is stored by the manager. Only passes which report their
+/// errors to the session can be managed, so that one failing pass
+/// doesn't keep the others from running.
+type BoxedPass = Box<dyn for<'ctx> ASTPass<'ctx, Err = Infallible>>;
+
+/// Hook called with the query context after a pass, eg. to print HIR.
+type DumpHook<'a> = Box<dyn FnMut(&QueryContext) + 'a>;
+
+/// How long a single pass took.
+#[derive(Debug, Clone, Copy)]
+pub struct PassTiming {
+    pub name: &'static str,
+    pub duration: Duration
+}
+
+/// Analysis passes that are run over the query context. Every pass runs
+/// after the passes it declares in `ASTPass::dependencies`, otherwise
+/// passes run in the order in which they were added, so the driver
+/// doesn't have to sequence them by hand.
+#[derive(Default)]
+pub struct PassManager<'a> {
+    passes: Vec<BoxedPass>,
+    /// Hooks together with the name of the pass they run after.
+    dumps: Vec<(&'static str, DumpHook<'a>)>,
+    timings: Vec<PassTiming>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+    New,
+    InProgress,
+    Done
+}
+
+impl<'a> PassManager<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pass(mut self, pass: impl for<'ctx> ASTPass<'ctx, Err = Infallible> + 'static) -> Self {
+        self.add_pass(pass);
+        self
+    }
+
+    pub fn add_pass(&mut self, pass: impl for<'ctx> ASTPass<'ctx, Err = Infallible> + 'static) {
+        assert!(
+            self.passes.iter().all(|other| other.name() != pass.name()),
+            "pass `{}` is added twice", pass.name()
+        );
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Call the hook with the query context every time the pass with
+    /// the given name finishes.
+    pub fn with_dump(mut self, after: &'static str, hook: impl FnMut(&QueryContext) + 'a) -> Self {
+        self.add_dump(after, hook);
+        self
+    }
+
+    pub fn add_dump(&mut self, after: &'static str, hook: impl FnMut(&QueryContext) + 'a) {
+        self.dumps.push((after, Box::new(hook)));
+    }
+
+    /// Names of passes in the order in which they run.
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.order().into_iter().map(|idx| self.passes[idx].name()).collect()
+    }
+
+    /// Timings of the passes from the last run, in the order they ran.
+    pub fn timings(&self) -> &[PassTiming] {
+        &self.timings
+    }
+
+    /// Run all passes, stopping early only if the session reached its
+    /// error limit. Dump hooks are called right after their pass.
+    pub fn run(&mut self, cx: &mut QueryContext) {
+        let order = self.order();
+        for (after, _) in self.dumps.iter() {
+            assert!(self.passes.iter().any(|pass| pass.name() == *after), "dump after unknown pass `{}`", after);
+        }
+        self.timings.clear();
+        for idx in order {
+            let pass = &mut self.passes[idx];
+            let start = Instant::now();
+            let Ok(()) = pass.traverse(cx);
+            let Ok(()) = pass.finish(cx);
+            let name = pass.name();
+            self.timings.push(PassTiming { name, duration: start.elapsed() });
+
+            for (_, hook) in self.dumps.iter_mut().filter(|(after, _)| *after == name) {
+                hook(cx);
+            }
+            if cx.sess.should_abort() {
+                break
+            }
+        }
+    }
+
+    /// Indices of passes in the order in which they run, found with
+    /// a depth first search over dependencies. Missing dependencies and
+    /// cycles are bugs in the driver, so they panic.
+    fn order(&self) -> Vec<usize> {
+        let by_name: HashMap<&str, usize> = self.passes.iter()
+            .enumerate()
+            .map(|(idx, pass)| (pass.name(), idx))
+            .collect();
+        let mut states = vec![VisitState::New; self.passes.len()];
+        let mut order = Vec::with_capacity(self.passes.len());
+        for idx in 0..self.passes.len() {
+            self.visit(idx, &by_name, &mut states, &mut order);
+        }
+        order
+    }
+
+    fn visit(&self, idx: usize, by_name: &HashMap<&str, usize>, states: &mut [VisitState], order: &mut Vec<usize>) {
+        match states[idx] {
+            VisitState::Done => return,
+            VisitState::InProgress => panic!("pass `{}` depends on itself", self.passes[idx].name()),
+            VisitState::New => ()
+        }
+        states[idx] = VisitState::InProgress;
+        let pass = &self.passes[idx];
+        for dep in pass.dependencies() {
+            let Some(&dep_idx) = by_name.get(dep) else {
+                panic!("pass `{}` depends on `{}`, which was not added", pass.name(), dep);
+            };
+            self.visit(dep_idx, by_name, states, order);
+        }
+        states[idx] = VisitState::Done;
+        order.push(idx);
+    }
+}
+
+impl std::fmt::Debug for PassManager<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("PassManager")
+            .field("passes", &self.pass_names())
+            .field("timings", &self.timings)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,69 +1,158 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, convert::Infallible, rc::Rc};
 
-use hastyc_common::identifiers::ASTNodeID;
-use hastyc_parser::parser::{Block, DataVariant, Expr, FieldDef, FnInput, Function, Item, ItemKind, ItemStream, LetBinding, Package, Pat, Stmt, StmtKind, StmtStream, Ty};
+use hastyc_common::{error::Diagnostic, identifiers::ASTNodeID, session::Session, span::Span};
+use hastyc_parser::parser::{Block, EnumVariant, Expr, FieldDef, FieldLitExpr, FnInput, Item, LetBinding, Lit, Package, Pat, Stmt, Ty};
+
+use crate::{util::ShardedMap, hir::{captures::ClosureEnv, mono::MonoItem, passing::PassingMode, ty::{self, TypeTable}, Hir}, mir::Mir, visit::{walk_block, walk_enum_variant, walk_expr, walk_field_def, walk_field_lit, walk_fn_input, walk_item, walk_let_binding, walk_package, walk_stmt, walk_ty, DepthLimit, Visitor}};
+
+use self::{builtin::PrimTy, defs::{DefId, Definitions}, name_resolve::FnScopes, query::{Input, QueryCaches}};
+
+pub use self::query::Query;
 
 pub mod name_resolve;
+pub mod transform;
+pub mod desugar;
+pub mod builtin;
+pub mod intrinsic;
+pub mod query;
+pub mod parallel;
+pub mod defs;
+pub mod prelude;
+pub mod manager;
+pub mod dump;
+pub mod scope;
 
 /// Context for the current compiler pass. This contains all information about resolved
 /// names, types, and other things.
+#[derive(Debug)]
 pub struct QueryContext<'ctx> {
+    pub sess: &'ctx Session,
     pub package: &'ctx Package,
-    /// Mapping of which AST node refers to which AST node
-    pub resolved_names: HashMap<ASTNodeID, ASTNodeID>
+    /// All definitions of the package, collected when the context is created.
+    pub defs: Definitions,
+    /// Mapping of which AST node refers to which definition, sharded
+    /// so that modules can be resolved in parallel.
+    pub resolved_names: ShardedMap<ASTNodeID, DefId>,
+    /// Nodes with types that resolved to primitive types, keyed
+    /// the same way as types in `resolved_names`.
+    pub prim_tys: ShardedMap<ASTNodeID, PrimTy>,
+    /// Variables from outer scopes used by each closure, in order of
+    /// their first use. These make up the closure environment.
+    pub upvars: HashMap<ASTNodeID, Vec<Upvar>>,
+    /// Ribs of every function, only recorded by name resolution
+    /// if `EmitKind::Scopes` is requested, for debug dumps.
+    pub scopes: Vec<FnScopes>,
+    /// Package lowered by `hir::lower::LowerToHir`, analyses after
+    /// name resolution work on this instead of the AST. Query input,
+    /// so it should only be changed with `set_hir`.
+    pub hir: Option<Hir>,
+    /// Types found by `hir::typeck::TypeCheckPass`, empty until it runs.
+    /// Query input, so it should only be changed with `set_types`.
+    pub types: TypeTable,
+    /// Environments of closures, keyed like `upvars`, which backends use
+    /// to build closure objects. Found by `hir::captures::CapturePass`.
+    pub closure_envs: HashMap<ASTNodeID, ClosureEnv>,
+    /// How values of parameters and locals are passed, by their
+    /// definitions. Found by `hir::passing::PassingModePass`.
+    pub passing_modes: HashMap<DefId, PassingMode>,
+    /// Items for backends to emit, collected by `hir::mono::MonoCollectPass`.
+    pub mono_items: Vec<MonoItem>,
+    /// Bodies lowered by `mir::build::BuildMir`, None if it didn't run
+    /// or the package has errors.
+    pub mir: Option<Mir>,
+    queries: QueryCaches<'ctx>
+}
+
+/// Variable captured by a closure.
+#[derive(Debug, Clone, Copy)]
+pub struct Upvar {
+    /// Definition of the captured variable.
+    pub target: DefId,
+    /// Span of the first use inside of the closure.
+    pub span: Span
 }
 
-/// Pass that modifies AST or query context
+/// Pass that modifies AST or query context. Passes usually walk
+/// the AST with a `visit::Visitor` from their `traverse`.
 pub trait ASTPass<'ctx> {
     type Err;
 
-    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err> {
-        self.traverse_itemstream(&ctx.package.items, ctx)
-    }
-    fn traverse_itemstream(&mut self, stream: &ItemStream, ctx: &mut QueryContext) -> Result<(), Self::Err> {
-        for item in stream.items.iter() {
-            self.visit_item(item, ctx)?;
-        }
-        Ok(())
-    }
-    fn traverse_stmtstream(&mut self, stream: &StmtStream, ctx: &mut QueryContext) -> Result<(), Self::Err> {
-        for stmt in stream.stmts.iter() {
-            self.visit_stmt(stmt, ctx)?;
-        }
-        Ok(())
-    }
+    /// Name of the pass, which `manager::PassManager` uses for
+    /// dependencies and dump hooks.
+    fn name(&self) -> &'static str;
 
-    fn visit_item(&mut self, item: &Item, ctx: &mut QueryContext) -> Result<(), Self::Err>;
-    fn visit_stmt(&mut self, stmt: &Stmt, ctx: &mut QueryContext) -> Result<(), Self::Err>;
-    fn visit_expr(&mut self, expr: &Expr, ctx: &mut QueryContext) -> Result<(), Self::Err>;
+    /// Names of passes which have to run before this one.
+    fn dependencies(&self) -> &'static [&'static str] { &[] }
+
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Self::Err>;
     fn finish(&mut self, _ctx: &mut QueryContext) -> Result<(), Self::Err> { Ok(()) }
 }
 
 impl<'cx> QueryContext<'cx> {
     pub fn for_package(
+        sess: &'cx Session,
         package: &'cx Package
     ) -> Self {
         Self {
-            package: &package,
-            resolved_names: HashMap::new()
+            sess,
+            package,
+            defs: Definitions::collect(package, sess.options.max_expr_depth),
+            resolved_names: ShardedMap::new(),
+            prim_tys: ShardedMap::new(),
+            upvars: HashMap::new(),
+            scopes: Vec::new(),
+            hir: None,
+            types: TypeTable::new(),
+            closure_envs: HashMap::new(),
+            passing_modes: HashMap::new(),
+            mono_items: Vec::new(),
+            mir: None,
+            queries: QueryCaches::new()
         }
     }
 
-    pub fn query<Q>(&'cx self, query: Q) -> Q::Result<'cx> where Q: Query {
-        query.run(self)
+    /// Report diagnostic without stopping the pass, this is meant for
+    /// warnings and notes, but errors can be reported this way too.
+    pub fn emit(&self, diagnostic: impl Diagnostic + 'static) {
+        self.sess.emit(diagnostic);
     }
-}
 
-pub trait Query {
-    type Result<'cx>;
+    /// Whether any of the reported diagnostics is an error.
+    pub fn has_errors(&self) -> bool {
+        self.sess.has_errors()
+    }
+
+    /// Output of the query, reused from earlier calls unless something
+    /// it was computed from changed since then. See `query::Query`.
+    pub fn query<Q>(&self, query: Q) -> Q::Output where Q: Query<'cx> {
+        self.queries.get(self, query)
+    }
 
-    fn run<'cx>(&self, cx: &'cx QueryContext) -> Self::Result<'cx>;
+    /// Record that the query being computed reads the input, queries have
+    /// to call this before they read any of the inputs.
+    pub fn read_input(&self, input: Input) {
+        self.queries.read_input(input);
+    }
+
+    pub fn set_hir(&mut self, hir: Hir) {
+        self.hir = Some(hir);
+        self.queries.input_changed(Input::Hir);
+    }
+
+    pub fn set_types(&mut self, types: TypeTable) {
+        self.types = types;
+        self.queries.input_changed(Input::Types);
+    }
 }
 
-pub struct ResolveIdQuery(ASTNodeID);
+/// AST node with the given id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolveIdQuery(pub ASTNodeID);
 
+#[derive(Debug, Clone, Copy)]
 pub enum ResolvedId<'cx> {
     Unknown,
+    Package(&'cx Package),
     Item(&'cx Item),
     Expr(&'cx Expr),
     Stmt(&'cx Stmt),
@@ -72,114 +161,168 @@ pub enum ResolvedId<'cx> {
     LetBinding(&'cx LetBinding),
     Pat(&'cx Pat),
     Ty(&'cx Ty),
-    FieldDef(&'cx FieldDef)
+    FieldDef(&'cx FieldDef),
+    EnumVariant(&'cx EnumVariant),
+    Lit(&'cx Lit),
+    FieldLit(&'cx FieldLitExpr)
 }
 
-impl Query for ResolveIdQuery {
-    type Result<'cx> = ResolvedId<'cx>;
+/// Nodes are compared by identity, not by their content.
+impl PartialEq for ResolvedId<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        use std::ptr::eq;
+        match (self, other) {
+            (Self::Unknown, Self::Unknown) => true,
+            (Self::Package(a), Self::Package(b)) => eq(*a, *b),
+            (Self::Item(a), Self::Item(b)) => eq(*a, *b),
+            (Self::Expr(a), Self::Expr(b)) => eq(*a, *b),
+            (Self::Stmt(a), Self::Stmt(b)) => eq(*a, *b),
+            (Self::FnInput(a), Self::FnInput(b)) => eq(*a, *b),
+            (Self::Block(a), Self::Block(b)) => eq(*a, *b),
+            (Self::LetBinding(a), Self::LetBinding(b)) => eq(*a, *b),
+            (Self::Pat(a), Self::Pat(b)) => eq(*a, *b),
+            (Self::Ty(a), Self::Ty(b)) => eq(*a, *b),
+            (Self::FieldDef(a), Self::FieldDef(b)) => eq(*a, *b),
+            (Self::EnumVariant(a), Self::EnumVariant(b)) => eq(*a, *b),
+            (Self::Lit(a), Self::Lit(b)) => eq(*a, *b),
+            (Self::FieldLit(a), Self::FieldLit(b)) => eq(*a, *b),
+            _ => false
+        }
+    }
+}
 
-    fn run<'cx>(&self, cx: &'cx QueryContext) -> Self::Result<'cx> {
-        let Some(resolved) = self.item_stream(&cx.package.items)
-            else { return ResolvedId::Unknown };
-        
-        resolved
+impl<'ctx> Query<'ctx> for ResolveIdQuery {
+    type Output = ResolvedId<'ctx>;
+
+    fn storage<'a>(caches: &'a QueryCaches<'ctx>) -> &'a query::QueryStorage<'ctx, Self> {
+        &caches.resolve_id
+    }
+
+    fn compute(&self, cx: &QueryContext<'ctx>) -> Self::Output {
+        cx.query(NodeIndexQuery).get(&self.0).copied().unwrap_or(ResolvedId::Unknown)
     }
 }
 
-impl ResolveIdQuery {
-    fn item_stream<'cx>(&self, is: &'cx ItemStream) -> Option<ResolvedId<'cx>> {
-        for item in is.items.iter() {
-            if let Some(r) = self.item(item) {
-                return Some(r)
-            } 
-        }
-        None
-    }
-
-    fn item<'cx>(&self, i: &'cx Item) -> Option<ResolvedId<'cx>> {
-        if i.id == self.0 { return Some(ResolvedId::Item(i)) }
-        match i.kind {
-            ItemKind::Module(ref is) => self.item_stream(is),
-            ItemKind::Fn(ref fun) => self.fun(fun),
-            ItemKind::Import(ref kind, ref tree) => todo!(),
-            ItemKind::Struct(ref datavar) => self.datavar(datavar),
-            ItemKind::Enum(ref datavar) => todo!(),
-            _ => None
-        }
+/// All nodes of the package by their ids, including the package itself,
+/// built with a single walk of the AST.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeIndexQuery;
+
+impl<'ctx> Query<'ctx> for NodeIndexQuery {
+    type Output = Rc<HashMap<ASTNodeID, ResolvedId<'ctx>>>;
+
+    fn storage<'a>(caches: &'a QueryCaches<'ctx>) -> &'a query::QueryStorage<'ctx, Self> {
+        &caches.node_index
     }
 
-    fn stmt_stream<'cx>(&self, ss: &'cx StmtStream) -> Option<ResolvedId<'cx>> {
-        for stmt in ss.stmts.iter() {
-            if let Some(r) = self.stmt(stmt) {
-                return Some(r)
-            }
-        }
-        None
-    }
-
-    fn stmt<'cx>(&self, s: &'cx Stmt) -> Option<ResolvedId<'cx>> {
-        if s.id == self.0 { return Some(ResolvedId::Stmt(s)) }
-        match s.kind {
-            StmtKind::LetBinding(ref binding) => {
-                if binding.id == self.0 { return Some(ResolvedId::LetBinding(binding)) }
-                if binding.pat.id == self.0 { return Some(ResolvedId::Pat(&binding.pat)) }
-                if let Some(ref ty) = binding.ty {
-                    if ty.id == self.0 { return Some(ResolvedId::Ty(ty)) }
-                }
-                None
-            },
-            StmtKind::Item(ref item) => self.item(item),
-            StmtKind::Expr(ref expr) => self.expr(expr),
-            StmtKind::ExprNS(ref expr) => self.expr(expr),
-            _ => None
-        }
+    fn compute(&self, cx: &QueryContext<'ctx>) -> Self::Output {
+        let mut indexer = NodeIndexer { nodes: HashMap::new(), depth: DepthLimit::new(cx.sess.options.max_expr_depth) };
+        let Ok(()) = indexer.visit_package(cx.package);
+        Rc::new(indexer.nodes)
+    }
+}
+
+/// Visitor that collects every node with an id.
+struct NodeIndexer<'ast> {
+    nodes: HashMap<ASTNodeID, ResolvedId<'ast>>,
+    depth: DepthLimit
+}
+
+impl<'ast> NodeIndexer<'ast> {
+    /// Ids are unique, so a node is never indexed twice.
+    fn insert(&mut self, id: ASTNodeID, node: ResolvedId<'ast>) {
+        let old = self.nodes.insert(id, node);
+        debug_assert!(old.is_none(), "node {:?} was indexed twice", id);
     }
+}
 
-    fn expr<'cx>(&self, e: &'cx Expr) -> Option<ResolvedId<'cx>> {
-        if e.id == self.0 { return Some(ResolvedId::Expr(e)) }
-        None
+/// Add node to the index and walk into it.
+macro_rules! index_node {
+    ($self:ident, $node:ident, $variant:ident, $walk:ident) => {{
+        $self.insert($node.id, ResolvedId::$variant($node));
+        $walk($self, $node)
+    }};
+}
+
+impl<'ast> Visitor<'ast> for NodeIndexer<'ast> {
+    type Break = Infallible;
+
+    fn visit_package(&mut self, package: &'ast Package) -> Result<(), Self::Break> {
+        index_node!(self, package, Package, walk_package)
     }
 
-    fn fun<'cx>(&self, fun: &'cx Function) -> Option<ResolvedId<'cx>> {
-        for input in fun.signature.inputs.iter() {
-            if input.id == self.0 { return Some(ResolvedId::FnInput(input)) }
-        }
-        if let Some(ref body) = fun.body {
-            if body.id == self.0 { return Some(ResolvedId::Block(body)) }
-            return self.stmt_stream(&body.stmts);
-        }
-        None
-    }
-
-    fn datavar<'cx>(&self, dv: &'cx DataVariant) -> Option<ResolvedId<'cx>> {
-        match dv {
-            DataVariant::Unit => None,
-            DataVariant::Struct { ref fields } => {
-                for field in fields.iter() {
-                    if field.id == self.0 { return Some(ResolvedId::FieldDef(field)) }
-                    if field.ty.id == self.0 { return Some(ResolvedId::Ty(&field.ty)) }
-                }
-                None
-            },
-            DataVariant::Tuple { ref fields } => {
-                for field in fields.iter() {
-                    if field.id == self.0 { return Some(ResolvedId::FieldDef(field)) }
-                    if field.ty.id == self.0 { return Some(ResolvedId::Ty(&field.ty)) }
-                }
-                None
-            }
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Self::Break> {
+        index_node!(self, item, Item, walk_item)
+    }
+
+    fn visit_enum_variant(&mut self, variant: &'ast EnumVariant) -> Result<(), Self::Break> {
+        index_node!(self, variant, EnumVariant, walk_enum_variant)
+    }
+
+    fn visit_field_def(&mut self, field: &'ast FieldDef) -> Result<(), Self::Break> {
+        index_node!(self, field, FieldDef, walk_field_def)
+    }
+
+    fn visit_fn_input(&mut self, input: &'ast FnInput) -> Result<(), Self::Break> {
+        index_node!(self, input, FnInput, walk_fn_input)
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) -> Result<(), Self::Break> {
+        index_node!(self, block, Block, walk_block)
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) -> Result<(), Self::Break> {
+        index_node!(self, stmt, Stmt, walk_stmt)
+    }
+
+    fn visit_let_binding(&mut self, binding: &'ast LetBinding) -> Result<(), Self::Break> {
+        index_node!(self, binding, LetBinding, walk_let_binding)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Self::Break> {
+        // Nodes in too deeply nested expressions are left out, as they
+        // are never resolved
+        if !self.depth.enter() {
+            return Ok(())
         }
+        let result = index_node!(self, expr, Expr, walk_expr);
+        self.depth.exit();
+        result
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) -> Result<(), Self::Break> {
+        index_node!(self, ty, Ty, walk_ty)
+    }
+
+    fn visit_field_lit(&mut self, field: &'ast FieldLitExpr) -> Result<(), Self::Break> {
+        index_node!(self, field, FieldLit, walk_field_lit)
+    }
+
+    fn visit_pat(&mut self, pat: &'ast Pat) -> Result<(), Self::Break> {
+        self.insert(pat.id, ResolvedId::Pat(pat));
+        Ok(())
+    }
+
+    fn visit_lit(&mut self, lit: &'ast Lit) -> Result<(), Self::Break> {
+        self.insert(lit.id, ResolvedId::Lit(lit));
+        Ok(())
     }
 }
 
-pub struct GetTyQuery(ASTNodeID);
+/// Type of the node, as found by type checking. Expressions, blocks and
+/// locals have their inferred types, definitions their declared ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GetTyQuery(pub ASTNodeID);
 
-impl Query for GetTyQuery {
-    type Result<'cx> = Option<&'cx Ty>;
+impl<'ctx> Query<'ctx> for GetTyQuery {
+    type Output = Option<ty::Ty>;
 
-    fn run<'cx>(&self, cx: &'cx QueryContext) -> Self::Result<'cx> {
-        // TODO: Implement
+    fn storage<'a>(caches: &'a QueryCaches<'ctx>) -> &'a query::QueryStorage<'ctx, Self> {
+        &caches.get_ty
+    }
 
-        None
+    fn compute(&self, cx: &QueryContext<'ctx>) -> Self::Output {
+        cx.read_input(Input::Types);
+        cx.types.get(self.0)
     }
 }
\ No newline at end of file
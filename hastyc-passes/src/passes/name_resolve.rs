@@ -1,238 +1,1192 @@
-use std::collections::BTreeMap;
+use std::{collections::{BTreeMap, HashMap, HashSet}, convert::Infallible};
 
-use hastyc_common::{identifiers::{ASTNodeID, Ident}, path::Path, error::{ErrorDisplay, CommonErrorContext}};
-use hastyc_parser::parser::{DataVariant, ExprKind, ItemKind, LetBindingKind, StmtKind, TyKind};
+use hastyc_common::{identifiers::{sym, ASTNodeID, Ident, Symbol}, path::{Path, PathId}, session::{EmitKind, ShadowingPolicy}, span::Span, error::{ErrorDisplay, CommonErrorContext, Severity}};
+use hastyc_parser::parser::{DataVariant, Expr, ExprKind, FnRetTy, Function, ImportKind, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, Pat, PatKind, StmtStream, Ty, TyKind, Visibility};
 
-use crate::util::RibStack;
+use crate::{util::{find_similar_name, RibKind, RibStack, RibTree}, visit::{walk_expr, walk_item_stream, walk_stmt_stream, walk_struct_lit, DepthLimit, Visitor}};
 
-use super::{ASTPass, QueryContext};
+use super::{builtin::{PrimTy, PRIMITIVE_TYPES}, parallel::{run_module_pass, Module, ModuleContext, ModulePass}, prelude::Prelude, ASTPass, QueryContext, Upvar};
 
 #[derive(Debug)]
 pub struct NameResolvePass {
     stack: RibStack,
     subpasses: BTreeMap<ASTNodeID, NameResolvePass>,
+    /// Names visible from outside of the module, these are public
+    /// items and names imported with `pub import`.
+    exports: HashMap<Symbol, Export>,
+    /// Names brought in by glob imports. These have lower precedence
+    /// than any explicit name, and a name being imported by
+    /// multiple globs is only an error if it is used.
+    globs: HashMap<Symbol, Vec<GlobBinding>>,
+    /// Names declared by items or explicit imports of the module, used
+    /// to report duplicate names and to point at private items.
+    declared: HashMap<Symbol, Declaration>,
+    /// Names bound by imports that could not be resolved. Paths that
+    /// start with them resolve to nothing without another error, as
+    /// the import was already reported.
+    failed_imports: HashSet<Symbol>,
+    /// Whether a glob import could not be resolved, in which case any
+    /// unknown name could have come from it, so none are reported.
+    failed_glob: bool,
+    /// Names used when nothing else in the module has the same name.
+    prelude: Prelude
+}
+
+/// Name which is visible from outside of its module.
+#[derive(Debug, Clone, Copy)]
+pub struct Export {
+    pub target: ASTNodeID,
+    /// Import item that re-exports the name, None if this is
+    /// the item itself.
+    pub reexported_by: Option<ASTNodeID>
+}
+
+/// Name imported by a glob import.
+#[derive(Debug, Clone, Copy)]
+pub struct GlobBinding {
+    pub target: ASTNodeID,
+    /// Import item which contains the glob.
+    pub import: ASTNodeID,
+    /// Span of the glob import tree, used in diagnostics.
+    pub span: Span
+}
+
+/// Ribs that name resolution pushed for a single function, nested
+/// functions are included in the ribs of the function around them.
+#[derive(Debug, Clone)]
+pub struct FnScopes {
+    /// Item of the function.
+    pub item: ASTNodeID,
+    pub ident: Ident,
+    /// Rib of the signature, which holds the parameters and the body.
+    pub ribs: RibTree
+}
+
+/// Name bound in the module rib by an item or an explicit import.
+#[derive(Debug, Clone, Copy)]
+struct Declaration {
+    target: ASTNodeID,
+    /// Span of the name, or of the rename of an import.
+    span: Span,
+    import: bool
+}
+
+impl Default for NameResolvePass {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NameResolvePass {
     pub fn new() -> Self {
         Self {
             stack: RibStack::new(),
-            subpasses: BTreeMap::new()
+            subpasses: BTreeMap::new(),
+            exports: HashMap::new(),
+            globs: HashMap::new(),
+            declared: HashMap::new(),
+            failed_imports: HashSet::new(),
+            failed_glob: false,
+            prelude: Prelude::default()
+        }
+    }
+
+    /// Resolve name, explicit bindings are tried first, then names
+    /// imported by globs, and then the prelude.
+    pub fn resolve_ident(&self, ident: Ident) -> Result<Option<&ASTNodeID>, NameResolveError> {
+        let symbol = ident.symbol;
+        Ok(self.resolve_in_module(ident)?.or_else(|| self.prelude.get(symbol)))
+    }
+
+    /// Resolve name declared in the module or imported into it, this is
+    /// how later segments of paths are resolved, so they can't use the prelude.
+    fn resolve_in_module(&self, ident: Ident) -> Result<Option<&ASTNodeID>, NameResolveError> {
+        if let Some(target) = self.stack.get_ident(&ident) {
+            return Ok(Some(target))
+        }
+        let Some(bindings) = self.globs.get(&ident.symbol) else { return Ok(None) };
+        // The same item can be imported by many globs, eg. through re-exports
+        let first = &bindings[0];
+        if let Some(other) = bindings.iter().find(|binding| binding.target != first.target) {
+            return Err(NameResolveError::AmbiguousGlob {
+                name: ident.symbol,
+                span: ident.span,
+                candidates: [first.span, other.span]
+            })
         }
+        Ok(Some(&first.target))
     }
 
-    pub fn resolve_ident(&self, ident: Ident) -> Option<&ASTNodeID> {
-        return self.stack.get_ident(&ident);
+    pub fn exports(&self) -> &HashMap<Symbol, Export> {
+        &self.exports
     }
 
-    pub fn resolve_path(&self, path: &Path) -> Result<&ASTNodeID, NameResolveError> {
-        let mut segments = path.segments.iter();
-        let mut c = 0;
+    /// Whether this is the pass of a module, rather than of a struct or an enum.
+    fn is_module(&self) -> bool {
+        self.stack.ribs().first().is_some_and(|rib| rib.kind == RibKind::Module)
+    }
+
+    /// Names that single segment paths can refer to, used to suggest
+    /// a similar name when a path can't be resolved.
+    fn scope_names(&self) -> Vec<Symbol> {
+        let mut names = self.stack.visible_names();
+        names.extend(self.globs.keys().copied());
+        names.extend(self.prelude.names());
+        names.extend(PRIMITIVE_TYPES.iter().map(|(name, _)| *name));
+        names
+    }
+
+    /// Bind name declared by an item or an explicit import in the
+    /// module rib. Importing the same item twice is allowed, any other
+    /// name that is already bound is an error.
+    fn declare(&mut self, ident: &Ident, target: ASTNodeID, import: bool) -> Result<(), NameResolveError> {
+        if let Some(previous) = self.declared.get(&ident.symbol) {
+            if !(import && previous.import) {
+                return Err(NameResolveError::DuplicateDefinition {
+                    name: ident.symbol,
+                    span: ident.span,
+                    previous: previous.span
+                })
+            }
+            if previous.target != target {
+                return Err(NameResolveError::AmbiguousImport {
+                    name: ident.symbol,
+                    span: ident.span,
+                    previous: previous.span
+                })
+            }
+            return Ok(())
+        }
+        self.declared.insert(ident.symbol, Declaration { target, span: ident.span, import });
+        self.stack.add_ident_mapping(ident.clone(), target);
+        Ok(())
+    }
+
+    /// Declare items of the module and of modules nested in it, so that
+    /// imports can refer to items of any module.
+    fn declare_items(&mut self, stream: &ItemStream, errors: &mut Vec<NameResolveError>) {
+        // Module rib stays on the stack after the traversal, so that
+        // other passes can resolve paths through this module
+        self.stack.push(RibKind::Module);
+
+        // Register all item names, duplicates are still resolved, but
+        // their names keep referring to the first item
+        for item in stream.items.iter() {
+            if let ItemKind::Import(_, _) = item.kind { continue }
+            match self.declare(&item.ident, item.id, false) {
+                Ok(()) if item.visibility == Visibility::Public => {
+                    self.exports.insert(item.ident.symbol, Export { target: item.id, reexported_by: None });
+                },
+                Ok(()) => (),
+                Err(err) => errors.push(err)
+            }
+
+            if let ItemKind::Module(ref module) = item.kind {
+                let mut subpass = NameResolvePass::new();
+                subpass.prelude = self.prelude.clone();
+                subpass.declare_items(module, errors);
+                self.subpasses.insert(item.id, subpass);
+            }
+        }
+    }
+
+    /// Pass of the module reached by going through the given modules.
+    fn module_pass(&self, path: &[ASTNodeID]) -> &NameResolvePass {
+        path.iter().fold(self, |pass, id| &pass.subpasses[id])
+    }
+
+    fn module_pass_mut(&mut self, path: &[ASTNodeID]) -> &mut NameResolvePass {
+        path.iter().fold(self, |pass, id| pass.subpasses.get_mut(id).expect("module without a pass"))
+    }
+
+    /// Find pass of the module (or other item with its own names) with
+    /// the given id, which may be nested in any of the submodules.
+    fn find_pass(&self, id: ASTNodeID) -> Option<&NameResolvePass> {
+        if let Some(pass) = self.subpasses.get(&id) {
+            return Some(pass)
+        }
+        self.subpasses.values().find_map(|pass| pass.find_pass(id))
+    }
+
+    /// Remember names that the import tree would bind, once it failed.
+    fn import_failed(&mut self, prefix: &Path, tree: &ImportTree) {
+        match tree.kind {
+            ImportTreeKind::Glob => self.failed_glob = true,
+            ImportTreeKind::Nested(ref trees) => {
+                for (subtree, _) in trees.iter() {
+                    self.import_failed(&prefix.join(&subtree.prefix), subtree);
+                }
+            },
+            ImportTreeKind::Simple(ref name, ref rename) => {
+                self.failed_imports.insert(rename.as_ref().unwrap_or(name).symbol);
+            },
+            ImportTreeKind::SelfImport(ref rename) => {
+                let name = rename.as_ref().or(prefix.segments.last().map(|segment| &segment.ident));
+                self.failed_imports.extend(name.map(|name| name.symbol));
+            }
+        }
+    }
+
+    /// Bind name imported explicitly, these work just like items
+    /// declared in the current module.
+    fn add_import_binding(
+        &mut self,
+        name: &Ident,
+        target: ASTNodeID,
+        vis: Visibility,
+        import: ASTNodeID
+    ) -> Result<(), NameResolveError> {
+        self.declare(name, target, true)?;
+        if vis == Visibility::Public {
+            self.exports.insert(name.symbol, Export { target, reexported_by: Some(import) });
+        }
+        Ok(())
+    }
+
+    /// Resolve path to the node it refers to. None is an error resolution
+    /// for paths that go through a name of a failed import, or a name that
+    /// could have come from a failed glob import. The import was already
+    /// reported, so such paths are just left unresolved.
+    pub fn resolve_path(&self, path: &Path) -> Result<Option<ASTNodeID>, NameResolveError> {
+        self.resolve_segments(path, true)
+    }
+
+    /// Resolve path, the first segment can only name something from the
+    /// prelude if `prelude` is set.
+    fn resolve_segments(&self, path: &Path, prelude: bool) -> Result<Option<ASTNodeID>, NameResolveError> {
         let mut sub = self;
-        while let Some(ref seg) = segments.next() {
-            let seg = sub.resolve_ident(seg.ident.clone());
-            if seg.is_none() { return Err(
-                NameResolveError::UnknownPath {
-                    path: path.clone(),
-                    start_idx: c
+        let mut resolved = None;
+        for (c, seg) in path.segments.iter().enumerate() {
+            let found = match c {
+                0 if prelude => sub.resolve_ident(seg.ident.clone())?,
+                _ => sub.resolve_in_module(seg.ident.clone())?
+            };
+            let Some(id) = found else {
+                if sub.failed_glob || sub.failed_imports.contains(&seg.ident.symbol) {
+                    return Ok(None)
                 }
-            ); }
-            c += 1;
-            if let Some(ref subsub) = sub.subpasses.get(seg.unwrap()) {
-                sub = subsub;
-            } else { return Ok(seg.unwrap()); }
-        }
-        Err(NameResolveError::UnknownPath {
-            path: path.clone(),
-            start_idx: 0
+                // Later segments can only name what the module exports
+                let candidates = match c {
+                    0 if prelude => sub.scope_names(),
+                    0 => sub.stack.visible_names(),
+                    _ if sub.is_module() => sub.exports.keys().copied().collect(),
+                    _ => sub.stack.visible_names()
+                };
+                return Err(NameResolveError::UnknownPath {
+                    path: path.id(),
+                    start_idx: c as u32,
+                    span: path.span_from(c as u32),
+                    suggestion: find_similar_name(seg.ident.symbol, candidates).map(|name| (seg.ident.span, name))
+                });
+            };
+            if c > 0 && sub.is_module() && sub.exports.get(&seg.ident.symbol).map(|export| export.target) != Some(*id) {
+                return Err(NameResolveError::PrivateItem {
+                    name: seg.ident.symbol,
+                    span: seg.ident.span,
+                    defined: sub.declared.get(&seg.ident.symbol).map(|declaration| declaration.span)
+                })
+            }
+            resolved = Some(*id);
+            if c + 1 == path.segments.len() { break }
+            // Items are looked up in any nested pass, as the module could
+            // have been imported under a different name
+            let Some(subsub) = self.find_pass(*id) else {
+                return Err(NameResolveError::NotAModule {
+                    path: path.id(),
+                    span: seg.ident.span
+                })
+            };
+            sub = subsub;
+        }
+        resolved.map(Some).ok_or_else(|| NameResolveError::UnknownPath {
+            path: path.id(),
+            start_idx: 0,
+            span: path.span_from(0),
+            suggestion: None
         })
     }
 
-    fn resolve_ty(
-        &mut self, ty: &hastyc_parser::parser::Ty,
-    ) -> Result<Option<&ASTNodeID>, NameResolveError> {
-        match ty.kind {
-            TyKind::Path(ref path) => Ok(Some(self.resolve_path(path)?)),
-            TyKind::SelfTy => unimplemented!("Name resolution for Self type is not implemented"),
-            _ => { Ok(None) }
+    /// Resolve type, recording what it refers to under `owner`. Single
+    /// segment paths that don't name anything in scope may be primitive types.
+    fn resolve_ty(&self, ty: &Ty, owner: ASTNodeID, cx: &ModuleContext) -> Result<(), NameResolveError> {
+        let TyKind::Path(ref path) = ty.kind else {
+            // TODO: Self type, once there are impls
+            return Ok(())
+        };
+        if let [ref segment] = path.segments[..] {
+            if self.resolve_ident(segment.ident.clone())?.is_none() {
+                if let Some(prim) = PrimTy::from_symbol(segment.ident.symbol) {
+                    cx.prim_tys.insert(owner, prim);
+                    return Ok(())
+                }
+            }
+        }
+        if let Some(target) = self.resolve_path(path)? {
+            cx.record_name(owner, target);
         }
+        Ok(())
     }
 
-    fn visit_datavariant(
-        &mut self,
-        dv: &DataVariant,
-        cx: &mut QueryContext,
-        item_id: ASTNodeID
-    ) -> Result<(), NameResolveError> {
-        match dv {
-            DataVariant::Unit => { },
-            DataVariant::Struct { ref fields } => {
+    /// Pass with names inside of a struct or an enum, None if the item has no names.
+    fn visit_data_item(&self, item: &Item, cx: &ModuleContext, errors: &mut Vec<NameResolveError>) -> Option<NameResolvePass> {
+        match item.kind {
+            ItemKind::Struct(ref datavar) => self.visit_datavariant(datavar, cx, errors),
+            ItemKind::Enum(ref def) => {
+                // Variants are reached by `Enum::Variant`, and their fields
+                // live in a pass of the variant, just like struct fields
                 let mut subpass = NameResolvePass::new();
-                for field in fields.iter() {
-                    if let Some(rty) = self.resolve_ty(&field.ty)? {
-                        let rty = *rty;
-                        cx.resolved_names.insert(field.id, rty);
+                subpass.stack.push(RibKind::Item);
+                for variant in def.variants.iter() {
+                    subpass.stack.add_ident_mapping(variant.ident.clone(), variant.id);
+                    if let Some(fields) = self.visit_datavariant(&variant.data, cx, errors) {
+                        subpass.subpasses.insert(variant.id, fields);
                     }
-                    subpass.stack.add_ident_mapping(field.ident.as_ref().unwrap().clone(), field.id);
                 }
-                self.subpasses.insert(item_id, subpass);
+                Some(subpass)
             },
-            DataVariant::Tuple { ref fields } => {
-                let mut subpass = NameResolvePass::new();
-                unimplemented!("Tuple struct variant is not yet supported")
+            _ => None
+        }
+    }
+
+    /// Resolve types of the fields, returning pass in which the fields
+    /// are registered. Tuple fields are registered by their index.
+    fn visit_datavariant(
+        &self,
+        dv: &DataVariant,
+        cx: &ModuleContext,
+        errors: &mut Vec<NameResolveError>
+    ) -> Option<NameResolvePass> {
+        let fields = match dv {
+            DataVariant::Unit => return None,
+            DataVariant::Struct { ref fields } => fields,
+            DataVariant::Tuple { ref fields } => fields
+        };
+        let mut subpass = NameResolvePass::new();
+        subpass.stack.push(RibKind::Item);
+        for (idx, field) in fields.iter().enumerate() {
+            if let Err(err) = self.resolve_ty(&field.ty, field.id, cx) {
+                errors.push(err);
             }
+            let ident = match field.ident {
+                Some(ref ident) => ident.clone(),
+                None => Ident::new(Symbol::intern(&idx.to_string()), field.span)
+            };
+            subpass.stack.add_ident_mapping(ident, field.id);
         }
-        Ok(())
+        Some(subpass)
     }
 }
 
+#[derive(Debug)]
 pub enum NameResolveError {
     UnknownPath {
-        path: Path,
-        start_idx: u32
+        path: PathId,
+        /// Index of the first segment that could not be resolved.
+        start_idx: u32,
+        /// Span of segments starting from `start_idx`.
+        span: Span,
+        /// Span of the first segment that could not be resolved, and
+        /// a similar name that is visible where it was looked up.
+        suggestion: Option<(Span, Symbol)>
+    },
+    /// Name is imported by multiple glob imports, which point to different items.
+    AmbiguousGlob {
+        name: Symbol,
+        span: Span,
+        /// Spans of two of the glob imports.
+        candidates: [Span; 2]
+    },
+    /// Glob import or path prefix points to something that is not a module.
+    NotAModule {
+        path: PathId,
+        /// Span of the segment that is not a module.
+        span: Span
+    },
+    /// Module declares or imports multiple items with the same name.
+    DuplicateDefinition {
+        name: Symbol,
+        span: Span,
+        /// Span of the name that was bound first.
+        previous: Span
+    },
+    /// Name is explicitly imported multiple times, by imports that
+    /// point to different items.
+    AmbiguousImport {
+        name: Symbol,
+        span: Span,
+        /// Span of the name bound by the first import.
+        previous: Span
+    },
+    /// Path goes through a module to an item which it doesn't export.
+    PrivateItem {
+        name: Symbol,
+        /// Span of the segment naming the private item.
+        span: Span,
+        /// Span of its name in the module, None if the module only
+        /// got it from a glob import.
+        defined: Option<Span>
+    },
+    /// `import self;`, which would import the current module into itself.
+    SelfImport {
+        span: Span
+    },
+    /// Path of an import could not be resolved.
+    UnresolvedImport {
+        path: PathId,
+        /// Index of the first segment that could not be resolved.
+        start_idx: u32,
+        /// Span of segments starting from `start_idx`.
+        span: Span,
+        /// Same as in `UnknownPath`.
+        suggestion: Option<(Span, Symbol)>
+    },
+    /// `import super::...` in the root module, which has no parent.
+    NoParentModule {
+        /// Span of the import item.
+        span: Span
+    },
+    /// Expression is nested deeper than `Options::max_expr_depth`.
+    NestingTooDeep {
+        limit: u32,
+        /// Span of the first expression over the limit.
+        span: Span
+    }
+}
+
+impl NameResolveError {
+    /// Unknown paths of imports are reported as unresolved imports.
+    fn in_import(self) -> Self {
+        match self {
+            Self::UnknownPath { path, start_idx, span, suggestion } => Self::UnresolvedImport { path, start_idx, span, suggestion },
+            err => err
+        }
     }
 }
 
 impl<'ctx> ASTPass<'ctx> for NameResolvePass {
-    type Err = NameResolveError;
+    type Err = Infallible;
 
-    fn traverse_itemstream(
-        &mut self,
-        stream: &hastyc_parser::parser::ItemStream,
-        ctx: &mut super::QueryContext
-    ) -> Result<(), NameResolveError> {
-        // Register all item names
-        for item in stream.items.iter() {
-            self.stack.add_ident_mapping(item.ident.clone(), item.id);
+    fn name(&self) -> &'static str {
+        "name-resolve"
+    }
 
-            if let ItemKind::Module(ref module) = item.kind {
-                let mut subpass = NameResolvePass::new();
-                subpass.traverse_itemstream(module, ctx)?;
-                self.subpasses.insert(item.id, subpass);
+    /// Errors don't stop the pass, every one of them is reported and the
+    /// path that failed is left unresolved, which lowering turns into an
+    /// error resolution. This way one typo doesn't hide other problems.
+    fn traverse(&mut self, ctx: &'ctx mut QueryContext) -> Result<(), Infallible> {
+        let package = ctx.package;
+        let cx = ModuleContext::new(ctx);
+        self.prelude = Prelude::of_package(package);
+        // Scopes are built first, one module after another, as modules can
+        // import from each other. Bodies only read them, so after that,
+        // bodies of every module can be resolved on their own.
+        let mut errors = Vec::new();
+        self.declare_items(&package.items, &mut errors);
+        let Ok(()) = Resolver {
+            root: self,
+            root_id: package.id,
+            module: Vec::new(),
+            outer: None,
+            cx: &cx,
+            errors: &mut errors
+        }.visit_item_stream(&package.items);
+        let Ok(outputs) = run_module_pass(&BodyPass { root: self }, &cx);
+        for err in errors {
+            ctx.emit(err);
+        }
+        for output in outputs {
+            ctx.upvars.extend(output.upvars);
+            ctx.scopes.extend(output.scopes);
+            for err in output.errors {
+                ctx.emit(err);
+            }
+            for binding in output.shadowed {
+                ctx.emit(binding);
             }
         }
-        self.stack.push();
+        Ok(())
+    }
+}
 
-        // Visit all items
-        for item in stream.items.iter() {
-            self.visit_item(item, ctx)?;
+/// Builds scopes of modules for `NameResolvePass`, everything
+/// except for function signatures and bodies. Runs once items of
+/// every module are declared, submodules are done before their parent,
+/// so that the parent can import names that they re-export.
+struct Resolver<'a, 'cx> {
+    root: &'a mut NameResolvePass,
+    /// Module that `root` is the pass of.
+    root_id: ASTNodeID,
+    /// Modules from the root to the current one.
+    module: Vec<ASTNodeID>,
+    /// Scope around the root, if it is a module declared in a function body.
+    outer: Option<OuterScope<'a>>,
+    cx: &'a ModuleContext<'cx>,
+    errors: &'a mut Vec<NameResolveError>
+}
+
+/// Module with a function that declares a module in its body, and the
+/// root of the package, which `super` and `pkg` refer to from there.
+#[derive(Clone, Copy)]
+struct OuterScope<'a> {
+    package: &'a NameResolvePass,
+    module: ASTNodeID,
+    pass: &'a NameResolvePass
+}
+
+/// Module that paths of an import start in.
+#[derive(Clone, Copy)]
+struct ImportBase<'a> {
+    pass: &'a NameResolvePass,
+    /// Module named by `super` or `pkg`. None for relative imports,
+    /// which start in the scope of the current module and can use the prelude.
+    module: Option<ASTNodeID>
+}
+
+impl<'a> ImportBase<'a> {
+    /// Resolve path of the import, empty path is the module itself.
+    fn resolve(&self, path: &Path) -> Result<Option<ASTNodeID>, NameResolveError> {
+        match self.module {
+            Some(module) if path.segments.is_empty() => Ok(Some(module)),
+            module => self.pass.resolve_segments(path, module.is_none())
         }
-        self.stack.pop();
-        Ok(())
     }
 
-    fn traverse_stmtstream(
-        &mut self,
-        stream: &hastyc_parser::parser::StmtStream,
-        ctx: &mut super::QueryContext
-    ) -> Result<(), NameResolveError> {
-        self.stack.push();
-        for stmt in stream.stmts.iter() {
-            self.visit_stmt(stmt, ctx)?;
+    /// Resolve path which must point to a module, returning its id and
+    /// pass. None is an error resolution, like in `resolve_path`.
+    fn resolve_module(&self, path: &Path) -> Result<Option<(ASTNodeID, &'a NameResolvePass)>, NameResolveError> {
+        if let (Some(module), true) = (self.module, path.segments.is_empty()) {
+            return Ok(Some((module, self.pass)))
         }
-        self.stack.pop();
-        Ok(())
+        let Some(id) = self.resolve(path)? else { return Ok(None) };
+        let pass = self.pass.find_pass(id).ok_or_else(|| NameResolveError::NotAModule {
+            path: path.id(),
+            span: path.span_from(path.segments.len() as u32 - 1)
+        })?;
+        Ok(Some((id, pass)))
     }
+}
 
-    fn visit_item(
-        &mut self,
-        item: &hastyc_parser::parser::Item, 
-        ctx: &mut super::QueryContext
-    ) -> Result<(), NameResolveError> {
-        match item.kind {
-            ItemKind::Module(ref module) => {
-                if !self.subpasses.contains_key(&item.id) {
-                    let mut subpass = NameResolvePass::new();
-                    subpass.traverse_itemstream(module, ctx)?;
-                    self.subpasses.insert(item.id, subpass);
+impl Resolver<'_, '_> {
+    fn pass(&self) -> &NameResolvePass {
+        self.root.module_pass(&self.module)
+    }
+
+    fn pass_mut(&mut self) -> &mut NameResolvePass {
+        self.root.module_pass_mut(&self.module)
+    }
+
+    /// Where paths of the import start, `super` in the root of the
+    /// package is reported before imports are resolved.
+    fn import_base(&self, kind: ImportKind) -> ImportBase<'_> {
+        match (kind, self.module.split_last(), self.outer) {
+            (ImportKind::Relative, _, _) => ImportBase { pass: self.pass(), module: None },
+            (ImportKind::Super, Some((_, parent)), _) => ImportBase {
+                pass: self.root.module_pass(parent),
+                module: Some(parent.last().copied().unwrap_or(self.root_id))
+            },
+            (ImportKind::Super, None, Some(outer)) => ImportBase { pass: outer.pass, module: Some(outer.module) },
+            (ImportKind::Package, _, Some(outer)) => ImportBase { pass: outer.package, module: Some(self.cx.package.id) },
+            (ImportKind::Package, _, None) => ImportBase { pass: self.root, module: Some(self.root_id) },
+            (ImportKind::Super, None, None) => unreachable!("`super` import in the root of the package")
+        }
+    }
+
+    /// Resolve import tree of the import item and bind the names it imports.
+    /// `node` is the id of the tree, which is the item itself for the whole
+    /// tree, and what the tree imports is recorded under it. Imports that
+    /// fail are pushed to `errors`, and the names they would bind are
+    /// remembered, so that their uses don't cause more errors.
+    fn resolve_import(&mut self, item: &Item, prefix: &Path, tree: &ImportTree, node: ASTNodeID) {
+        let ItemKind::Import(kind, _) = item.kind else { unreachable!("import tree of an item that is not an import") };
+        let prefix = prefix.join(&tree.prefix);
+        let result = match tree.kind {
+            ImportTreeKind::Nested(ref trees) => {
+                // Prefix is resolved once for the whole group, so that
+                // a wrong prefix is reported only once
+                let resolved = match prefix.segments.is_empty() {
+                    true => Ok(true),
+                    false => self.import_base(kind).resolve_module(&prefix).map(|module| module.is_some())
+                };
+                if let Ok(true) = resolved {
+                    for (subtree, id) in trees.iter() {
+                        self.resolve_import(item, &prefix, subtree, *id);
+                    }
                 }
+                resolved
+            },
+            _ => self.bind_import(kind, &prefix, tree, item, node)
+        };
+        match result {
+            Ok(true) => (),
+            Ok(false) => self.pass_mut().import_failed(&prefix, tree),
+            Err(err) => {
+                self.errors.push(err.in_import());
+                self.pass_mut().import_failed(&prefix, tree);
             }
-            ItemKind::Fn(ref function) => {
-                // TODO: Generics
-                // Go to signature
-                for input in function.signature.inputs.iter() {
-                    // Register input as variable
-                    if let Some(ident) = input.pat.ident() {
-                        self.stack.add_ident_mapping(ident.clone(), input.id);
+        }
+    }
+
+    /// Bind names of a single import tree that is not nested, `prefix`
+    /// already includes its own prefix. The item or module it imports
+    /// is recorded under `node`. Returns false if the path is an
+    /// error resolution.
+    fn bind_import(&mut self, kind: ImportKind, prefix: &Path, tree: &ImportTree, item: &Item, node: ASTNodeID) -> Result<bool, NameResolveError> {
+        let (vis, import, cx) = (item.visibility, item.id, self.cx);
+        match tree.kind {
+            ImportTreeKind::Glob => {
+                let Some((target, module)) = self.import_base(kind).resolve_module(prefix)? else { return Ok(false) };
+                cx.record_name(node, target);
+                let names = module.exports.iter()
+                    .map(|(name, export)| (*name, export.target))
+                    .collect::<Vec<_>>();
+                let pass = self.pass_mut();
+                for (name, target) in names {
+                    pass.globs.entry(name).or_default().push(GlobBinding {
+                        target,
+                        import,
+                        span: tree.span
+                    });
+                    // Re-exported glob names are also lower priority than explicit ones
+                    if vis == Visibility::Public {
+                        pass.exports.entry(name).or_insert(Export { target, reexported_by: Some(import) });
                     }
                 }
-
-                // Go to body
-                self.traverse_stmtstream(&function.body.as_ref().unwrap().stmts, ctx)?;
-            }
-            ItemKind::Import(ref kind, ref tree) => {
-                unimplemented!("Name resolution for imports is not yet implemented");
             },
-            ItemKind::Struct(ref datavar) => {
-                self.visit_datavariant(datavar, ctx, item.id)?;
+            ImportTreeKind::Simple(ref name, ref rename) => {
+                let Some(target) = self.import_base(kind).resolve(&prefix.with_segment(name.clone()))? else { return Ok(false) };
+                cx.record_name(node, target);
+                self.pass_mut().add_import_binding(rename.as_ref().unwrap_or(name), target, vis, import)?;
             },
-            _ => todo!()
+            ImportTreeKind::SelfImport(ref rename) => {
+                // `a::{self}` binds the module `a` itself, `import self;`
+                // would bind the current module, which is already in scope
+                let Some(name) = prefix.segments.last().map(|segment| segment.ident.clone()) else {
+                    return Err(NameResolveError::SelfImport { span: tree.span })
+                };
+                let Some((target, _)) = self.import_base(kind).resolve_module(prefix)? else { return Ok(false) };
+                cx.record_name(node, target);
+                self.pass_mut().add_import_binding(rename.as_ref().unwrap_or(&name), target, vis, import)?;
+            },
+            ImportTreeKind::Nested(_) => unreachable!("nested imports are split by `resolve_import`")
         }
-        Ok(())
+        Ok(true)
     }
+}
 
-    fn visit_stmt(
-        &mut self,
-        stmt: &hastyc_parser::parser::Stmt,
-        cx: &mut super::QueryContext
-    ) -> Result<(), NameResolveError> {
-        match stmt.kind {
-            StmtKind::LetBinding(ref binding) => {
-                if let Some(ident) = binding.pat.ident() {
-                    self.stack.add_ident_mapping(ident.clone(), binding.id);
-                }
-                if let Some(ref ty) = binding.ty {
-                    if let Some(ty_resolved) = self.resolve_ty(ty)? {
-                        let ty_resolved = *ty_resolved;
-                        cx.resolved_names.insert(binding.id, ty_resolved);
-                    }
+impl<'ast> Visitor<'ast> for Resolver<'_, '_> {
+    type Break = Infallible;
+
+    fn visit_item_stream(&mut self, stream: &'ast ItemStream) -> Result<(), Infallible> {
+        for item in stream.items.iter() {
+            if let ItemKind::Module(ref module) = item.kind {
+                self.module.push(item.id);
+                self.visit_item_stream(module)?;
+                self.module.pop();
+            }
+        }
+
+        // Imports are resolved before anything else, so that items
+        // can use imported names no matter where the import is
+        for item in stream.items.iter() {
+            let ItemKind::Import(kind, ref tree) = item.kind else { continue };
+            if matches!(kind, ImportKind::Super) && self.module.is_empty() && self.outer.is_none() {
+                self.errors.push(NameResolveError::NoParentModule { span: item.span });
+                self.pass_mut().import_failed(&Path::empty(), tree);
+                continue
+            }
+            self.resolve_import(item, &Path::empty(), tree, item.id);
+        }
+
+        walk_item_stream(self, stream)
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Infallible> {
+        match item.kind {
+            // Modules in item streams were already resolved before their
+            // imports, and functions are resolved by `BodyPass`
+            ItemKind::Module(_) | ItemKind::Fn(_) => { },
+            // Already resolved when the item stream was entered
+            ItemKind::Import(_, _) => { },
+            ItemKind::Struct(_) | ItemKind::Enum(_) => {
+                if let Some(subpass) = self.root.module_pass(&self.module).visit_data_item(item, self.cx, self.errors) {
+                    self.pass_mut().subpasses.insert(item.id, subpass);
                 }
-                if let LetBindingKind::Init(ref expr) = binding.kind {
-                    self.visit_expr(expr, cx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves functions of every module once all scopes are built.
+/// Diagnostics are reported once all modules are done, so that they
+/// are in the same order no matter how modules run.
+struct BodyPass<'a> {
+    root: &'a NameResolvePass
+}
+
+/// What `BodyPass` found in a single module.
+struct ModuleBodies {
+    /// Variables captured by closures in the module.
+    upvars: HashMap<ASTNodeID, Vec<Upvar>>,
+    /// Ribs of functions, if they were recorded.
+    scopes: Vec<FnScopes>,
+    errors: Vec<NameResolveError>,
+    /// Shadowing bindings to report.
+    shadowed: Vec<ShadowedBinding>
+}
+
+impl ModulePass for BodyPass<'_> {
+    type Output = ModuleBodies;
+    type Err = Infallible;
+
+    fn visit_module(&self, module: Module<'_>, cx: &ModuleContext) -> Result<ModuleBodies, Infallible> {
+        let scope = match module.id {
+            Some(id) => self.root.find_pass(id).expect("module without a pass"),
+            None => self.root
+        };
+        let mut resolver = BodyResolver::new(self.root, module.id.unwrap_or(cx.package.id), scope, cx);
+        resolver.visit_module_fns(module.items);
+        Ok(ModuleBodies {
+            upvars: resolver.upvars,
+            scopes: resolver.scopes,
+            errors: resolver.errors,
+            shadowed: resolver.shadowed
+        })
+    }
+}
+
+/// Walks function signatures and bodies, writing everything it resolves
+/// into the context. Names are looked up in local variables first, and
+/// then in the scope of the module.
+struct BodyResolver<'a, 'cx> {
+    /// Pass of the root of the package.
+    package: &'a NameResolvePass,
+    /// Module of the functions, which `scope` is the pass of.
+    module: ASTNodeID,
+    scope: &'a NameResolvePass,
+    locals: RibStack,
+    cx: &'a ModuleContext<'cx>,
+    upvars: HashMap<ASTNodeID, Vec<Upvar>>,
+    scopes: Vec<FnScopes>,
+    errors: Vec<NameResolveError>,
+    shadowed: Vec<ShadowedBinding>,
+    /// Whether the current function has `#[allow(shadowed_binding)]`.
+    allow_shadowing: bool,
+    depth: DepthLimit
+}
+
+impl<'a, 'cx> BodyResolver<'a, 'cx> {
+    fn new(package: &'a NameResolvePass, module: ASTNodeID, scope: &'a NameResolvePass, cx: &'a ModuleContext<'cx>) -> Self {
+        let mut locals = RibStack::new();
+        if cx.options.emit.contains(&EmitKind::Scopes) {
+            locals.record();
+        }
+        Self {
+            package,
+            module,
+            scope,
+            locals,
+            cx,
+            upvars: HashMap::new(),
+            scopes: Vec::new(),
+            errors: Vec::new(),
+            shadowed: Vec::new(),
+            allow_shadowing: false,
+            depth: DepthLimit::new(cx.options.max_expr_depth)
+        }
+    }
+
+    /// Resolve functions directly in the module, nested modules are separate.
+    fn visit_module_fns(&mut self, items: &ItemStream) {
+        for item in items.items.iter() {
+            if let ItemKind::Fn(ref function) = item.kind {
+                let Ok(()) = self.visit_fn_item(item, function);
+                for ribs in self.locals.take_recorded() {
+                    self.scopes.push(FnScopes { item: item.id, ident: item.ident.clone(), ribs });
                 }
             }
-            StmtKind::Expr(ref expr) => {
-                self.visit_expr(expr, cx)?;
+        }
+    }
+
+    /// Resolve function together with lint attributes of its item.
+    fn visit_fn_item(&mut self, item: &Item, function: &Function) -> Result<(), Infallible> {
+        let outer = self.allow_shadowing;
+        self.allow_shadowing |= item.attrs.has_list_arg(sym::allow, sym::shadowed_binding);
+        // Depth of expressions is counted from their function, as lowering
+        // lowers nested functions separately
+        let depth = self.depth;
+        self.depth = depth.nested();
+        let result = self.visit_fn(function);
+        self.allow_shadowing = outer;
+        self.depth = depth;
+        result
+    }
+
+    /// Introduce variables bound by the pattern into the current rib,
+    /// `target` is the node that declares them.
+    fn bind_pat(&mut self, pat: &Pat, target: ASTNodeID) {
+        match pat.kind {
+            PatKind::Ident(ref ident) => {
+                self.check_shadowing(ident);
+                self.locals.add_ident_mapping(ident.clone(), target)
+            },
+            // `self` is not looked up through ribs
+            PatKind::SelfPat => {}
+        }
+    }
+
+    /// Record binding which shadows a variable that is still in scope,
+    /// if the shadowing policy of the session reports it. Bindings made
+    /// by desugaring are never reported, as they can't be renamed.
+    fn check_shadowing(&mut self, ident: &Ident) {
+        let deny = match self.cx.options.shadowing {
+            ShadowingPolicy::Allow => return,
+            ShadowingPolicy::Warn => false,
+            ShadowingPolicy::Deny => true
+        };
+        if (!deny && self.allow_shadowing) || ident.span.from_expansion() {
+            return
+        }
+        let Some(&shadowed) = self.locals.shadowed_local(ident) else { return };
+        let shadowed = self.cx.defs.get(self.cx.def_id(shadowed)).ident.span;
+        self.shadowed.push(ShadowedBinding { name: ident.symbol, span: ident.span, shadowed, deny });
+    }
+
+    /// Resolve path used in a body. Failed paths are reported and give
+    /// None, so nothing is recorded for them. Only single segment paths
+    /// can refer to local variables, so only these get similar locals
+    /// suggested.
+    fn resolve_path(&mut self, path: &Path) -> Option<ASTNodeID> {
+        if let [ref segment] = path.segments[..] {
+            if let Some(local) = self.locals.get_ident(&segment.ident) {
+                return Some(*local)
             }
-            StmtKind::ExprNS(ref expr) => {
-                self.visit_expr(expr, cx)?;
+        }
+        match self.scope.resolve_path(path) {
+            Ok(target) => target,
+            Err(NameResolveError::UnknownPath { path: id, start_idx, span, suggestion }) => {
+                let suggestion = match path.segments[..] {
+                    [ref segment] => {
+                        let candidates = self.locals.visible_names().into_iter().chain(self.scope.scope_names());
+                        find_similar_name(segment.ident.symbol, candidates).map(|name| (segment.ident.span, name))
+                    },
+                    _ => suggestion
+                };
+                self.errors.push(NameResolveError::UnknownPath { path: id, start_idx, span, suggestion });
+                None
+            },
+            Err(err) => {
+                self.errors.push(err);
+                None
             }
-            StmtKind::Item(ref item) => {
-                self.visit_item(&item, cx)?;
+        }
+    }
+
+    fn resolve_ty(&mut self, ty: &Ty, owner: ASTNodeID) {
+        if let Err(err) = self.scope.resolve_ty(ty, owner, self.cx) {
+            self.errors.push(err);
+        }
+    }
+
+    /// Record variable as captured by every closure between its use and declaration.
+    fn record_upvars(&mut self, ident: &Ident, target: ASTNodeID) {
+        let target = self.cx.def_id(target);
+        for closure in self.locals.capturing_closures(ident) {
+            let upvars = self.upvars.entry(closure).or_default();
+            if !upvars.iter().any(|upvar| upvar.target == target) {
+                upvars.push(Upvar { target, span: ident.span });
             }
         }
+    }
+}
+
+impl<'ast> Visitor<'ast> for BodyResolver<'_, '_> {
+    type Break = Infallible;
+
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Infallible> {
+        // Items in blocks can't be named from outside of them, so their
+        // passes are dropped once they are resolved
+        match item.kind {
+            ItemKind::Fn(ref function) => self.visit_fn_item(item, function)?,
+            ItemKind::Module(ref module) => {
+                let mut pass = NameResolvePass::new();
+                pass.prelude = self.scope.prelude.clone();
+                pass.declare_items(module, &mut self.errors);
+                let Ok(()) = Resolver {
+                    root: &mut pass,
+                    root_id: item.id,
+                    module: Vec::new(),
+                    outer: Some(OuterScope { package: self.package, module: self.module, pass: self.scope }),
+                    cx: self.cx,
+                    errors: &mut self.errors
+                }.visit_item_stream(module);
+                for nested in Module::collect(module) {
+                    let scope = match nested.id {
+                        Some(id) => pass.find_pass(id).expect("module without a pass"),
+                        None => &pass
+                    };
+                    let mut resolver = BodyResolver::new(self.package, nested.id.unwrap_or(item.id), scope, self.cx);
+                    resolver.allow_shadowing = self.allow_shadowing;
+                    resolver.visit_module_fns(nested.items);
+                    self.upvars.extend(resolver.upvars);
+                    self.scopes.extend(resolver.scopes);
+                    self.errors.extend(resolver.errors);
+                    self.shadowed.extend(resolver.shadowed);
+                }
+            },
+            ItemKind::Struct(_) | ItemKind::Enum(_) => {
+                self.scope.visit_data_item(item, self.cx, &mut self.errors);
+            },
+            ItemKind::Import(_, _) => { }
+        }
         Ok(())
     }
 
-    fn visit_expr(
-        &mut self,
-        expr: &hastyc_parser::parser::Expr,
-        cx: &mut super::QueryContext
-    ) -> Result<(), NameResolveError> {
+    fn visit_fn(&mut self, function: &'ast Function) -> Result<(), Infallible> {
+        // TODO: Generics
+        let signature = &function.signature;
+        for input in signature.inputs.iter() {
+            self.resolve_ty(&input.ty, input.id);
+        }
+        if let FnRetTy::Ty(ref ty) = signature.output {
+            self.resolve_ty(ty, ty.id);
+        }
+
+        self.locals.push(RibKind::FnBoundary);
+        for input in signature.inputs.iter() {
+            // Register input as variable
+            self.bind_pat(&input.pat, input.id);
+        }
+
+        // Go to body, extern functions have none
+        if let Some(ref body) = function.body {
+            self.visit_block(body)?;
+        }
+        self.locals.pop(RibKind::FnBoundary);
+        Ok(())
+    }
+
+    fn visit_stmt_stream(&mut self, stream: &'ast StmtStream) -> Result<(), Infallible> {
+        self.locals.push(RibKind::Block);
+        walk_stmt_stream(self, stream)?;
+        self.locals.pop(RibKind::Block);
+        Ok(())
+    }
+
+    fn visit_let_binding(&mut self, binding: &'ast LetBinding) -> Result<(), Infallible> {
+        if let Some(ref ty) = binding.ty {
+            self.resolve_ty(ty, binding.id);
+        }
+        // Initializer is resolved before the name is bound,
+        // so `let a = a;` refers to the previous `a`
+        if let LetBindingKind::Init(ref expr) = binding.kind {
+            self.visit_expr(expr)?;
+        }
+        self.bind_pat(&binding.pat, binding.id);
+        Ok(())
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Infallible> {
+        // Deeper expressions are skipped by all passes, so that they
+        // don't overflow the stack
+        let reported = self.depth.exceeded();
+        if !self.depth.enter() {
+            if !reported {
+                self.errors.push(NameResolveError::NestingTooDeep { limit: self.depth.limit(), span: expr.span });
+            }
+            return Ok(())
+        }
         match expr.kind {
             ExprKind::Path(ref path) => {
-                let target = self.resolve_path(path)?;
-                cx.resolved_names.insert(expr.id, *target);
+                if let Some(target) = self.resolve_path(path) {
+                    self.cx.record_name(expr.id, target);
+                    if let [ref segment] = path.segments[..] {
+                        self.record_upvars(&segment.ident, target);
+                    }
+                }
+            }
+            ExprKind::StructLit(ref lit) => {
+                if let Some(target) = self.resolve_path(&lit.path) {
+                    self.cx.record_name(expr.id, target);
+                }
+                walk_struct_lit(self, lit)?;
             }
-            ExprKind::Field(ref subexpr, ref ident) => {
-                self.visit_expr(&subexpr, cx)?;
+            ExprKind::Loop(ref block) => {
+                self.locals.push(RibKind::Loop);
+                self.visit_block(block)?;
+                self.locals.pop(RibKind::Loop);
             }
-            _ => todo!()
+            ExprKind::While(ref condition, ref block) => {
+                self.visit_expr(condition)?;
+                self.locals.push(RibKind::Loop);
+                self.visit_block(block)?;
+                self.locals.pop(RibKind::Loop);
+            }
+            ExprKind::For(ref pat, ref iter, ref block) => {
+                self.visit_expr(iter)?;
+                self.locals.push(RibKind::Loop);
+                self.bind_pat(pat, pat.id);
+                self.visit_block(block)?;
+                self.locals.pop(RibKind::Loop);
+            }
+            // Tuple structs and variants are constructed with call syntax,
+            // so their path resolves to the struct or variant like any other
+            _ => walk_expr(self, expr)?
         }
+        self.depth.exit();
         Ok(())
     }
 }
 
 impl<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> for NameResolveError {
+    fn code(&self) -> Option<&'static str> {
+        Some(match self {
+            NameResolveError::UnknownPath { .. } => "H0200",
+            NameResolveError::AmbiguousGlob { .. } => "H0201",
+            NameResolveError::NotAModule { .. } => "H0202",
+            NameResolveError::DuplicateDefinition { .. } => "H0204",
+            NameResolveError::AmbiguousImport { .. } => "H0205",
+            NameResolveError::PrivateItem { .. } => "H0206",
+            NameResolveError::SelfImport { .. } => "H0207",
+            NameResolveError::UnresolvedImport { .. } => "H0208",
+            NameResolveError::NestingTooDeep { .. } => "H0209",
+            NameResolveError::NoParentModule { .. } => "H0210"
+        })
+    }
+
     fn fmt(&self, fmt: &mut hastyc_common::error::ErrorFmt<'ctx>, ctx: &'ctx CommonErrorContext) {
         match self {
-            NameResolveError::UnknownPath { ref path, ref start_idx } => {
+            NameResolveError::UnknownPath { ref span, ref suggestion, .. } => {
                 fmt
                     .title("Path could not be resolved.")
-                    .source(ctx.source, path.shifted_clone(*start_idx).span)
-                    .cause("This path could not have been resolved.")
-                    .help("Ensure that this path is spelled correctly and that there are items with these names.");
+                    .source(ctx.source, *span)
+                    .cause("This path could not have been resolved.");
+                suggest_similar(fmt, suggestion, "Ensure that this path is spelled correctly and that there are items with these names.");
+            }
+            NameResolveError::AmbiguousGlob { ref name, ref span, ref candidates } => {
+                fmt
+                    .title(&format!("Name `{}` is ambiguous.", name))
+                    .source(ctx.source, *span)
+                    .cause("This name is imported by multiple glob imports, which refer to different items.")
+                    .note("It could refer to the item imported here:")
+                    .source(ctx.source, candidates[0])
+                    .note("Or to the item imported here:")
+                    .source(ctx.source, candidates[1])
+                    .help("Import the name explicitly to choose which item should be used.");
+            }
+            NameResolveError::NotAModule { ref span, .. } => {
+                fmt
+                    .title("Expected module.")
+                    .source(ctx.source, *span)
+                    .cause("Only modules can contain other items.");
+            }
+            NameResolveError::DuplicateDefinition { ref name, ref span, ref previous } => {
+                fmt
+                    .title(&format!("Name `{}` is defined multiple times.", name))
+                    .source(ctx.source, *span)
+                    .cause("This name is already used in this module.")
+                    .note(&format!("`{}` is first defined here:", name))
+                    .source(ctx.source, *previous)
+                    .help("Rename one of the items, or import it under a different name with `as`.");
+            }
+            NameResolveError::AmbiguousImport { ref name, ref span, ref previous } => {
+                fmt
+                    .title(&format!("Name `{}` is imported multiple times.", name))
+                    .source(ctx.source, *span)
+                    .cause("This import refers to a different item than an earlier import of the same name.")
+                    .note("The name is first imported here:")
+                    .source(ctx.source, *previous)
+                    .help("Import one of the items under a different name with `as`.");
+            }
+            NameResolveError::PrivateItem { ref name, ref span, ref defined } => {
+                fmt
+                    .title(&format!("`{}` is private.", name))
+                    .source(ctx.source, *span)
+                    .cause("This item is not visible outside of its module.");
+                if let Some(defined) = defined {
+                    fmt
+                        .note(&format!("`{}` is defined here:", name))
+                        .source(ctx.source, *defined);
+                }
+                fmt.help("Make the item public with `pub`, or re-export it with `pub import`.");
+            }
+            NameResolveError::SelfImport { ref span } => {
+                fmt
+                    .title("Module can't import itself.")
+                    .source(ctx.source, *span)
+                    .cause("`self` can only be imported from another module, like in `import a::{self};`.")
+                    .help("Remove this import, items of the current module are already in scope.");
+            }
+            NameResolveError::UnresolvedImport { ref span, ref suggestion, .. } => {
+                fmt
+                    .title("Import could not be resolved.")
+                    .source(ctx.source, *span)
+                    .cause("There is no item with this path.");
+                suggest_similar(fmt, suggestion, "Ensure that the path is spelled correctly and that the items are public.");
+            }
+            NameResolveError::NoParentModule { ref span } => {
+                fmt
+                    .title("Root module has no parent.")
+                    .source(ctx.source, *span)
+                    .cause("`super` refers to the parent module, but this import is in the root of the package.")
+                    .help("Import the items from the root with `pkg::` or without a prefix.");
+            }
+            NameResolveError::NestingTooDeep { limit, ref span } => {
+                fmt
+                    .title(&format!("Expression is nested too deeply (limit is {}).", limit))
+                    .source(ctx.source, *span)
+                    .cause("This expression and everything inside of it is not checked.")
+                    .help("Try to split long chains of operators or deeply nested code into variables or smaller functions.");
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Suggest the similar name if there is one, otherwise show the help.
+fn suggest_similar<'ctx>(fmt: &mut hastyc_common::error::ErrorFmt<'ctx>, suggestion: &Option<(Span, Symbol)>, help: &'ctx str) {
+    match suggestion {
+        Some((span, name)) => {
+            fmt.suggestion(*span, name.as_str(), "a similar name exists");
+        },
+        None => {
+            fmt.help(help);
+        }
+    }
+}
+
+/// Variable bound with the same name as another variable which is still
+/// in scope. This is the `shadowed_binding` lint, reported only when
+/// `Options::shadowing` asks for it, as a warning or an error.
+#[derive(Debug)]
+pub struct ShadowedBinding {
+    pub name: Symbol,
+    /// Span of the new binding.
+    pub span: Span,
+    /// Span of the binding that is shadowed.
+    pub shadowed: Span,
+    /// Whether shadowing is denied, which makes this an error.
+    pub deny: bool
+}
+
+impl<'ctx> ErrorDisplay<'ctx, CommonErrorContext<'ctx>> for ShadowedBinding {
+    fn code(&self) -> Option<&'static str> {
+        Some("H0203")
+    }
+
+    fn severity(&self) -> Severity {
+        if self.deny { Severity::Error } else { Severity::Warning }
+    }
+
+    fn fmt(&self, fmt: &mut hastyc_common::error::ErrorFmt<'ctx>, ctx: &'ctx CommonErrorContext) {
+        fmt
+            .title(&format!("Variable `{}` shadows an earlier binding.", self.name))
+            .source(ctx.source, self.span)
+            .note("Shadowed variable is bound here:")
+            .source(ctx.source, self.shadowed);
+        if self.deny {
+            fmt.help("Rename one of the variables, shadowing is not allowed in this compilation.");
+        } else {
+            fmt.help("Rename one of the variables, or add `#[allow(shadowed_binding)]` to the function if this is intended.");
+        }
+    }
+}
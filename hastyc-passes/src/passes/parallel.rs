@@ -0,0 +1,94 @@
+use hastyc_common::{identifiers::ASTNodeID, session::Options};
+use hastyc_parser::parser::{ItemKind, ItemStream, Package};
+
+use crate::util::ShardedMap;
+
+use super::{builtin::PrimTy, defs::{DefId, Definitions}, QueryContext};
+
+/// Parts of the query context that can be shared between threads, used
+/// by `ModulePass`es. Maps are sharded, so that modules can fill them at
+/// the same time.
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleContext<'a> {
+    pub package: &'a Package,
+    pub options: &'a Options,
+    pub defs: &'a Definitions,
+    pub resolved_names: &'a ShardedMap<ASTNodeID, DefId>,
+    pub prim_tys: &'a ShardedMap<ASTNodeID, PrimTy>
+}
+
+impl<'a> ModuleContext<'a> {
+    pub fn new(cx: &'a QueryContext) -> Self {
+        Self {
+            package: cx.package,
+            options: &cx.sess.options,
+            defs: &cx.defs,
+            resolved_names: &cx.resolved_names,
+            prim_tys: &cx.prim_tys
+        }
+    }
+
+    /// Definition declared by the node, which names have to resolve to.
+    pub fn def_id(&self, target: ASTNodeID) -> DefId {
+        self.defs.def_id(target).expect("name resolved to a node that is not a definition")
+    }
+
+    /// Record that the node refers to the definition declared by `target`.
+    pub fn record_name(&self, owner: ASTNodeID, target: ASTNodeID) {
+        self.resolved_names.insert(owner, self.def_id(target));
+    }
+}
+
+/// Single module with only its own items, modules nested in it are
+/// separate modules.
+#[derive(Debug, Clone, Copy)]
+pub struct Module<'ast> {
+    /// Id of the module item, None for the root of the item stream.
+    pub id: Option<ASTNodeID>,
+    pub items: &'ast ItemStream
+}
+
+impl<'ast> Module<'ast> {
+    /// The item stream and all modules nested in it, parents before
+    /// their children. Modules inside of function bodies are skipped.
+    pub fn collect(items: &'ast ItemStream) -> Vec<Self> {
+        let mut modules = vec![Self { id: None, items }];
+        let mut idx = 0;
+        while idx < modules.len() {
+            for item in modules[idx].items.items.iter() {
+                if let ItemKind::Module(ref module) = item.kind {
+                    modules.push(Self { id: Some(item.id), items: module });
+                }
+            }
+            idx += 1;
+        }
+        modules
+    }
+}
+
+/// Pass that handles every module on its own, without seeing results
+/// of other modules, so that modules can be processed in parallel. This
+/// is done with the `parallel` feature, otherwise they run in order.
+pub trait ModulePass: Sync {
+    type Output: Send;
+    type Err: Send;
+
+    fn visit_module(&self, module: Module<'_>, cx: &ModuleContext) -> Result<Self::Output, Self::Err>;
+}
+
+/// Run pass on every module of the package, returning outputs in the
+/// order of `Module::collect`. If any modules fail, error of the first
+/// one is returned, so errors don't depend on how threads are scheduled.
+pub fn run_module_pass<P: ModulePass>(pass: &P, cx: &ModuleContext) -> Result<Vec<P::Output>, P::Err> {
+    let modules = Module::collect(&cx.package.items);
+
+    #[cfg(feature = "parallel")]
+    let results: Vec<_> = {
+        use rayon::prelude::*;
+        modules.into_par_iter().map(|module| pass.visit_module(module, cx)).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results: Vec<_> = modules.into_iter().map(|module| pass.visit_module(module, cx)).collect();
+
+    results.into_iter().collect()
+}
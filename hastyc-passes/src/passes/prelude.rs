@@ -0,0 +1,131 @@
+use std::{collections::HashMap, sync::Arc};
+
+use hastyc_common::{identifiers::{kw, sym, ASTNodeID, Ident, Symbol}, path::Path, span::Span};
+use hastyc_parser::parser::{Attribute, AttributeKind, Attributes, Extern, FnInput, FnRetTy, FnSignature, Function, Generics, Item, ItemKind, ItemStream, Package, Pat, PatKind, Ty, TyKind, Visibility};
+
+use super::{intrinsic::{Intrinsic, IntrinsicOutput, INTRINSICS}, transform::{ASTMutPass, MutPassContext}};
+
+/// Whether the package uses the prelude, which is the case unless
+/// it opts out with `#![no_prelude]`.
+pub fn has_prelude(attrs: &Attributes) -> bool {
+    !attrs.has_flag(sym::no_prelude)
+}
+
+/// Adds the intrinsics to the package, so that `NameResolvePass`
+/// can put them in the prelude. They are declared in a module named
+/// `<prelude>`, which can't be named in the source, and which allows
+/// dead code, as most packages use only a few of them. Nothing is added
+/// to packages with `#![no_prelude]`.
+#[derive(Debug, Default)]
+pub struct InjectPrelude;
+
+impl ASTMutPass for InjectPrelude {
+    fn name(&self) -> &'static str {
+        "inject-prelude"
+    }
+
+    fn run(&mut self, items: ItemStream, cx: &MutPassContext) -> ItemStream {
+        if !has_prelude(cx.attrs) {
+            return items
+        }
+        let span = Span::dummy();
+        let builtins = INTRINSICS.iter().map(|intrinsic| intrinsic_fn(*intrinsic, cx)).collect();
+        let allow_dead_code = Attribute {
+            ident: Ident::new(sym::allow, span),
+            kind: AttributeKind::ListAttribute(vec![Ident::new(sym::dead_code, span)])
+        };
+        let module = Item {
+            attrs: Attributes { attributes: vec![allow_dead_code] },
+            id: cx.next_id(),
+            visibility: Visibility::Inherited,
+            kind: ItemKind::Module(ItemStream::from_items(builtins)),
+            ident: Ident::new(kw::Prelude, span),
+            span
+        };
+        // Prelude goes first, as it is there before any user item
+        let mut items = Arc::unwrap_or_clone(items.items);
+        items.insert(0, module);
+        ItemStream::from_items(items)
+    }
+}
+
+/// Intrinsic declared as `extern fn` without a body, so that backends
+/// provide it.
+fn intrinsic_fn(intrinsic: Intrinsic, cx: &MutPassContext) -> Item {
+    let span = Span::dummy();
+    let ty = |kind| Ty { id: cx.next_id(), kind, span };
+    let named = |name| TyKind::Path(Path::empty().with_segment(Ident::new(name, span)));
+    let inputs = intrinsic.inputs().iter()
+        .map(|&(name, ty_name)| FnInput {
+            attributes: Attributes::empty(),
+            id: cx.next_id(),
+            span,
+            pat: Pat { id: cx.next_id(), kind: PatKind::Ident(Ident::new(Symbol::intern(name), span)), span },
+            ty: ty(named(ty_name))
+        })
+        .collect();
+    let output = match intrinsic.output() {
+        IntrinsicOutput::Void => FnRetTy::Default,
+        IntrinsicOutput::Never => FnRetTy::Ty(ty(TyKind::Never)),
+        IntrinsicOutput::Ty(name) => FnRetTy::Ty(ty(named(name)))
+    };
+    let signature = FnSignature {
+        is_const: false,
+        is_async: false,
+        ext: Extern::Implicit(span),
+        inputs,
+        output,
+        span
+    };
+    Item {
+        attrs: Attributes::empty(),
+        id: cx.next_id(),
+        visibility: Visibility::Inherited,
+        kind: ItemKind::Fn(Function { generics: Generics {}, signature, body: None }),
+        ident: Ident::new(intrinsic.name(), span),
+        span
+    }
+}
+
+/// Names that every module can use without importing them. They have
+/// the lowest precedence, below glob imports, so that any other name
+/// shadows them, and they can't be reached through paths like `a::print`.
+/// Primitive types aren't part of it, they are named even without it,
+/// see `builtin::PRIMITIVE_TYPES`.
+#[derive(Debug, Clone, Default)]
+pub struct Prelude {
+    /// Intrinsics, found in the module added by `InjectPrelude`.
+    names: HashMap<Symbol, ASTNodeID>
+}
+
+impl Prelude {
+    /// Prelude of the package, which is empty with `#![no_prelude]`.
+    pub fn of_package(package: &Package) -> Self {
+        if !has_prelude(&package.attrs) {
+            return Self::default()
+        }
+        let module = package.items.items.iter().find_map(|item| match item.kind {
+            ItemKind::Module(ref items) if item.ident.symbol == kw::Prelude => Some(items),
+            _ => None
+        });
+        let names = module.into_iter()
+            .flat_map(|items| items.items.iter())
+            .map(|item| (item.ident.symbol, item.id))
+            .collect();
+        Self { names }
+    }
+
+    pub fn get(&self, symbol: Symbol) -> Option<&ASTNodeID> {
+        self.names.get(&symbol)
+    }
+
+    /// All names in the prelude.
+    pub fn names(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.names.keys().copied()
+    }
+
+    /// Intrinsics in the prelude together with their items.
+    pub fn items(&self) -> impl Iterator<Item = (Symbol, ASTNodeID)> + '_ {
+        self.names.iter().map(|(name, id)| (*name, *id))
+    }
+}
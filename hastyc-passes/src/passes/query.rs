@@ -0,0 +1,187 @@
+use std::{cell::{Cell, RefCell}, collections::{HashMap, HashSet}, fmt::Debug, hash::Hash, rc::Rc};
+
+use super::{scope::ScopeAtQuery, GetTyQuery, NodeIndexQuery, QueryContext, ResolveIdQuery};
+
+/// Point in time of the query context, bumped whenever an input changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Revision(pub u64);
+
+/// Parts of the query context that are set by passes. Queries record which
+/// of them they read, so only their results are recomputed after a change.
+/// The package is not an input, as it can't change during the lifetime of
+/// the context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    /// `QueryContext::hir`, set with `QueryContext::set_hir`.
+    Hir,
+    /// `QueryContext::types`, set with `QueryContext::set_types`.
+    Types
+}
+
+/// Query is a key, which is computed into an output at most once for
+/// every revision of the inputs. Queries can use other queries and inputs
+/// while they are computed, which is recorded, so that cached output is
+/// reused until something it was computed from changes.
+pub trait Query<'ctx>: Clone + Eq + Hash + Debug + 'ctx {
+    /// Output has to be comparable, so that queries depending on output
+    /// which was recomputed to the same value don't have to be recomputed.
+    type Output: Clone + PartialEq;
+
+    /// Where the outputs of this query are cached.
+    fn storage<'a>(caches: &'a QueryCaches<'ctx>) -> &'a QueryStorage<'ctx, Self>;
+
+    /// Compute the output, this should only be called by `QueryContext::query`.
+    fn compute(&self, cx: &QueryContext<'ctx>) -> Self::Output;
+}
+
+/// Cached output of a query.
+struct Memo<'ctx, O> {
+    value: O,
+    /// Revision in which the value last changed.
+    changed_at: Revision,
+    /// Revision in which the value was last known to be up to date.
+    verified_at: Revision,
+    deps: Vec<Dep<'ctx>>
+}
+
+/// Something that a query read while it was computed.
+#[derive(Clone)]
+enum Dep<'ctx> {
+    Input(Input),
+    Query(Rc<dyn ErasedQuery<'ctx> + 'ctx>)
+}
+
+/// Query of any type, so that dependencies can be stored together.
+trait ErasedQuery<'ctx> {
+    /// Bring the query up to date and return when its output last changed.
+    fn changed_at(&self, cx: &QueryContext<'ctx>) -> Revision;
+}
+
+impl<'ctx, Q: Query<'ctx>> ErasedQuery<'ctx> for Q {
+    fn changed_at(&self, cx: &QueryContext<'ctx>) -> Revision {
+        cx.queries.fetch(cx, self).1
+    }
+}
+
+/// Outputs of one kind of query.
+pub struct QueryStorage<'ctx, Q: Query<'ctx>> {
+    memos: RefCell<HashMap<Q, Memo<'ctx, Q::Output>>>,
+    /// Queries that are being computed, used to detect cycles.
+    in_progress: RefCell<HashSet<Q>>
+}
+
+impl<'ctx, Q: Query<'ctx>> Default for QueryStorage<'ctx, Q> {
+    fn default() -> Self {
+        Self {
+            memos: RefCell::new(HashMap::new()),
+            in_progress: RefCell::new(HashSet::new())
+        }
+    }
+}
+
+/// Cached outputs of all queries together with revisions of the inputs.
+/// New queries need a storage here, returned from their `Query::storage`.
+#[derive(Default)]
+pub struct QueryCaches<'ctx> {
+    revision: Cell<u64>,
+    /// Revisions in which inputs last changed, inputs that are not here
+    /// never changed.
+    inputs: RefCell<HashMap<Input, Revision>>,
+    /// Dependencies of queries that are being computed, innermost last.
+    active: RefCell<Vec<Vec<Dep<'ctx>>>>,
+
+    pub(super) node_index: QueryStorage<'ctx, NodeIndexQuery>,
+    pub(super) resolve_id: QueryStorage<'ctx, ResolveIdQuery>,
+    pub(super) get_ty: QueryStorage<'ctx, GetTyQuery>,
+    pub(super) scope_at: QueryStorage<'ctx, ScopeAtQuery>
+}
+
+impl<'ctx> QueryCaches<'ctx> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn revision(&self) -> Revision {
+        Revision(self.revision.get())
+    }
+
+    /// Start a new revision in which the input changed.
+    pub(super) fn input_changed(&mut self, input: Input) {
+        let revision = self.revision.get() + 1;
+        self.revision.set(revision);
+        self.inputs.get_mut().insert(input, Revision(revision));
+    }
+
+    /// Record that the query being computed reads the input.
+    pub(super) fn read_input(&self, input: Input) {
+        self.record(Dep::Input(input));
+    }
+
+    /// Output of the query, recorded as a dependency of the query being computed.
+    pub(super) fn get<Q: Query<'ctx>>(&self, cx: &QueryContext<'ctx>, query: Q) -> Q::Output {
+        let (value, _) = self.fetch(cx, &query);
+        self.record(Dep::Query(Rc::new(query)));
+        value
+    }
+
+    fn record(&self, dep: Dep<'ctx>) {
+        if let Some(deps) = self.active.borrow_mut().last_mut() {
+            deps.push(dep);
+        }
+    }
+
+    fn dep_changed_at(&self, cx: &QueryContext<'ctx>, dep: &Dep<'ctx>) -> Revision {
+        match dep {
+            Dep::Input(input) => self.inputs.borrow().get(input).copied().unwrap_or(Revision(0)),
+            Dep::Query(query) => query.changed_at(cx)
+        }
+    }
+
+    /// Up to date output of the query, together with the revision in which it last changed.
+    fn fetch<Q: Query<'ctx>>(&self, cx: &QueryContext<'ctx>, query: &Q) -> (Q::Output, Revision) {
+        let storage = Q::storage(self);
+        let current = self.revision();
+        let cached = match storage.memos.borrow().get(query) {
+            Some(memo) if memo.verified_at == current => return (memo.value.clone(), memo.changed_at),
+            Some(memo) => Some((memo.verified_at, memo.deps.clone())),
+            None => None
+        };
+
+        // Memo is still valid if none of its dependencies changed since it was verified
+        if let Some((verified_at, deps)) = cached {
+            if deps.iter().all(|dep| self.dep_changed_at(cx, dep) <= verified_at) {
+                let mut memos = storage.memos.borrow_mut();
+                let memo = memos.get_mut(query).expect("memo removed while it was verified");
+                memo.verified_at = current;
+                return (memo.value.clone(), memo.changed_at)
+            }
+        }
+
+        if !storage.in_progress.borrow_mut().insert(query.clone()) {
+            panic!("query {:?} depends on itself", query);
+        }
+        self.active.borrow_mut().push(Vec::new());
+        let value = query.compute(cx);
+        let deps = self.active.borrow_mut().pop().expect("query dependencies were popped too early");
+        storage.in_progress.borrow_mut().remove(query);
+
+        let mut memos = storage.memos.borrow_mut();
+        // Output that didn't change keeps its revision, so that queries
+        // depending on it can still use their cached outputs
+        let changed_at = match memos.get(query) {
+            Some(old) if old.value == value => old.changed_at,
+            _ => current
+        };
+        memos.insert(query.clone(), Memo { value: value.clone(), changed_at, verified_at: current, deps });
+        (value, changed_at)
+    }
+}
+
+impl Debug for QueryCaches<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("QueryCaches")
+            .field("revision", &self.revision())
+            .finish_non_exhaustive()
+    }
+}
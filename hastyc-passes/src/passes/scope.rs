@@ -0,0 +1,245 @@
+use std::{collections::HashSet, convert::Infallible, rc::Rc};
+
+use hastyc_common::{identifiers::{kw, ASTNodeID, Ident, SourceFileID, Symbol}, span::Span};
+use hastyc_parser::parser::{Block, Expr, ExprKind, Function, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBindingKind, Pat, StmtKind, Visibility};
+
+use crate::visit::{walk_expr, DepthLimit, Visitor};
+
+use super::{defs::{pat_ident, DefId}, prelude::Prelude, query::{QueryCaches, QueryStorage}, Query, QueryContext};
+
+/// Names that a single segment path at the byte offset of the file could
+/// refer to, innermost first, without names that are shadowed. These are
+/// locals bound before the offset, items and imports of the module, names
+/// from its glob imports and the prelude. Imports are looked up in
+/// `QueryContext::resolved_names`, so this is meant to be used after name
+/// resolution. Files that no module was loaded from are taken to be
+/// the root file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScopeAtQuery {
+    pub source: SourceFileID,
+    pub offset: u32
+}
+
+/// Name in scope together with the definition it refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeName {
+    pub name: Symbol,
+    pub def: DefId
+}
+
+impl<'ctx> Query<'ctx> for ScopeAtQuery {
+    type Output = Rc<Vec<ScopeName>>;
+
+    fn storage<'a>(caches: &'a QueryCaches<'ctx>) -> &'a QueryStorage<'ctx, Self> {
+        &caches.scope_at
+    }
+
+    fn compute(&self, cx: &QueryContext<'ctx>) -> Self::Output {
+        let mut collector = ScopeCollector {
+            cx,
+            source: self.source,
+            offset: self.offset,
+            names: Vec::new(),
+            module_names: 0,
+            depth: DepthLimit::new(cx.sess.options.max_expr_depth)
+        };
+        let Ok(()) = collector.visit_item_stream(&cx.package.items);
+        // Names were collected outermost first, so the first of every
+        // name is the one that shadows the others
+        let mut seen = HashSet::new();
+        let names = collector.names.into_iter()
+            .rev()
+            .filter(|name| seen.insert(name.name))
+            .collect();
+        Rc::new(names)
+    }
+}
+
+/// Walks down to the offset, binding names of every scope on the way.
+struct ScopeCollector<'a, 'cx> {
+    cx: &'a QueryContext<'cx>,
+    source: SourceFileID,
+    offset: u32,
+    /// Names in the order they were bound, innermost last.
+    names: Vec<ScopeName>,
+    /// Number of names that come from the module, functions in blocks
+    /// see only these, as locals around them are not in scope.
+    module_names: usize,
+    depth: DepthLimit
+}
+
+impl ScopeCollector<'_, '_> {
+    fn contains(&self, span: Span) -> bool {
+        span.source == self.source && span.contains(self.offset)
+    }
+
+    /// Whether the offset is in the module, which may have its items
+    /// in a file of its own, or in any of its submodules.
+    fn is_in_module(&self, id: ASTNodeID, items: &ItemStream) -> bool {
+        let in_file = self.cx.package.module_decls.iter().any(|decl| decl.id == id && decl.file == Some(self.source));
+        in_file || items.items.iter().any(|item| match item.kind {
+            ItemKind::Module(ref module) => self.contains(item.span) || self.is_in_module(item.id, module),
+            _ => false
+        })
+    }
+
+    fn bind(&mut self, ident: &Ident, target: ASTNodeID) {
+        // Names made by desugaring can't be written in the source
+        if ident.span.from_expansion() {
+            return
+        }
+        if let Some(def) = self.cx.defs.def_id(target) {
+            self.names.push(ScopeName { name: ident.symbol, def });
+        }
+    }
+
+    fn bind_pat(&mut self, pat: &Pat, target: ASTNodeID) {
+        self.bind(&pat_ident(pat), target);
+    }
+
+    /// Bind names of explicit imports, names from glob imports are
+    /// bound separately, as they have lower precedence.
+    fn bind_import(&mut self, tree: &ImportTree, node: ASTNodeID, prefix_last: Option<&Ident>, globs: bool) {
+        let prefix_last = tree.prefix.segments.last().map(|segment| &segment.ident).or(prefix_last);
+        let name = match tree.kind {
+            ImportTreeKind::Nested(ref trees) => {
+                for (subtree, id) in trees.iter() {
+                    self.bind_import(subtree, *id, prefix_last, globs);
+                }
+                return
+            },
+            ImportTreeKind::Glob if globs => {
+                let Some(module) = self.cx.resolved_names.get(&node) else { return };
+                let exported = self.cx.defs.iter()
+                    .filter(|(_, def)| def.parent == Some(module) && def.visibility == Visibility::Public)
+                    .map(|(def, data)| ScopeName { name: data.ident.symbol, def })
+                    .collect::<Vec<_>>();
+                self.names.extend(exported);
+                return
+            },
+            ImportTreeKind::Simple(ref name, ref rename) if !globs => rename.as_ref().unwrap_or(name),
+            ImportTreeKind::SelfImport(ref rename) if !globs => match rename.as_ref().or(prefix_last) {
+                Some(name) => name,
+                None => return
+            },
+            _ => return
+        };
+        if let Some(def) = self.cx.resolved_names.get(&node) {
+            self.names.push(ScopeName { name: name.symbol, def });
+        }
+    }
+
+    /// Enter the module, names from outside of it are not in scope.
+    fn visit_module(&mut self, items: &ItemStream) {
+        self.names.clear();
+        // Prelude is a map, so its names are sorted to always come in the same order
+        let mut prelude = Prelude::of_package(self.cx.package).items().collect::<Vec<_>>();
+        prelude.sort_by_key(|(name, _)| std::cmp::Reverse(name.as_str()));
+        for (name, id) in prelude {
+            if let Some(def) = self.cx.defs.def_id(id) {
+                self.names.push(ScopeName { name, def });
+            }
+        }
+        for item in items.items.iter() {
+            if let ItemKind::Import(_, ref tree) = item.kind {
+                self.bind_import(tree, item.id, None, true);
+            }
+        }
+        for item in items.items.iter() {
+            match item.kind {
+                ItemKind::Import(_, ref tree) => self.bind_import(tree, item.id, None, false),
+                // Prelude module can't be named
+                _ if item.ident.symbol == kw::Prelude => {},
+                _ => self.bind(&item.ident, item.id)
+            }
+        }
+        self.module_names = self.names.len();
+
+        for item in items.items.iter() {
+            let Ok(()) = self.visit_item(item);
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for ScopeCollector<'_, '_> {
+    type Break = Infallible;
+
+    fn visit_item_stream(&mut self, stream: &'ast ItemStream) -> Result<(), Infallible> {
+        self.visit_module(stream);
+        Ok(())
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Infallible> {
+        match item.kind {
+            ItemKind::Module(ref module) if self.contains(item.span) || self.is_in_module(item.id, module) => {
+                self.visit_module(module);
+            },
+            ItemKind::Fn(ref function) if self.contains(item.span) => {
+                let depth = self.depth;
+                self.depth = depth.nested();
+                self.visit_fn(function)?;
+                self.depth = depth;
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn visit_fn(&mut self, function: &'ast Function) -> Result<(), Infallible> {
+        self.names.truncate(self.module_names);
+        for input in function.signature.inputs.iter() {
+            self.bind_pat(&input.pat, input.id);
+        }
+        match function.body {
+            Some(ref body) => self.visit_block(body),
+            None => Ok(())
+        }
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) -> Result<(), Infallible> {
+        if !self.contains(block.span) {
+            return Ok(())
+        }
+        let stmts = &block.stmts.stmts;
+        // Statements made by desugaring can contain the statements after
+        // them, so the last statement around the offset is the one it is in
+        let current = stmts.iter().rposition(|stmt| self.contains(stmt.span));
+        let before = match current {
+            Some(idx) => &stmts[..idx],
+            None => &stmts[..stmts.iter().take_while(|stmt| stmt.span.end <= self.offset).count()]
+        };
+        for stmt in before.iter() {
+            if let StmtKind::LetBinding(ref binding) = stmt.kind {
+                self.bind_pat(&binding.pat, binding.id);
+            }
+        }
+        let Some(idx) = current else { return Ok(()) };
+        match stmts[idx].kind {
+            // Initializer is before the name is bound
+            StmtKind::LetBinding(ref binding) => match binding.kind {
+                LetBindingKind::Init(ref expr) => self.visit_expr(expr),
+                _ => Ok(())
+            },
+            StmtKind::Item(ref item) => self.visit_item(item),
+            StmtKind::Expr(ref expr) | StmtKind::ExprNS(ref expr) => self.visit_expr(expr)
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Infallible> {
+        if !self.contains(expr.span) || !self.depth.enter() {
+            return Ok(())
+        }
+        match expr.kind {
+            ExprKind::For(ref pat, ref iter, ref block) => {
+                self.visit_expr(iter)?;
+                if self.contains(block.span) {
+                    self.bind_pat(pat, pat.id);
+                    self.visit_block(block)?;
+                }
+            },
+            _ => walk_expr(self, expr)?
+        }
+        self.depth.exit();
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use hastyc_common::{identifiers::{ASTNodeID, IDCounter}, session::Session};
+use hastyc_parser::parser::{Attributes, ItemStream, NodeSpanTable, Package};
+
+/// Pass that rewrites the AST, usually with a `fold::Folder`. These run
+/// before any analysis, as everything recorded about the old nodes is
+/// invalid once they are rewritten. Errors are reported to the session.
+pub trait ASTMutPass {
+    /// Name of the pass, eg. for printing the pipeline.
+    fn name(&self) -> &'static str;
+
+    fn run(&mut self, items: ItemStream, cx: &MutPassContext) -> ItemStream;
+}
+
+/// Everything mutable pass can use besides the items it rewrites.
+pub struct MutPassContext<'a> {
+    pub sess: &'a Session,
+    pub attrs: &'a Attributes,
+    ids: &'a IDCounter
+}
+
+impl<'a> MutPassContext<'a> {
+    /// Fresh id for a node created by the pass, taken from the package
+    /// counter, so it never collides with ids given out by the parser.
+    pub fn next_id(&self) -> ASTNodeID {
+        self.ids.into()
+    }
+}
+
+/// Mutable passes that are run in order on the package.
+#[derive(Default)]
+pub struct ASTMutPipeline {
+    passes: Vec<Box<dyn ASTMutPass>>
+}
+
+impl ASTMutPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pass(mut self, pass: impl ASTMutPass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn add_pass(&mut self, pass: impl ASTMutPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Names of passes in the order in which they run.
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Run all passes on the package, stopping after the first pass that
+    /// reported an error. Returns whether all passes ran. Span table is
+    /// rebuilt if the package had one, so that it includes new nodes.
+    pub fn run(&mut self, package: &mut Package, sess: &Session) -> bool {
+        let mut finished = true;
+        for pass in self.passes.iter_mut() {
+            let items = std::mem::replace(&mut package.items, ItemStream::empty());
+            let cx = MutPassContext {
+                sess,
+                attrs: &package.attrs,
+                ids: &package.idgen
+            };
+            package.items = pass.run(items, &cx);
+            if sess.has_errors() {
+                finished = false;
+                break;
+            }
+        }
+        if package.span_table.is_some() {
+            package.span_table = Some(NodeSpanTable::build(&package.items));
+        }
+        finished
+    }
+}
+
+impl std::fmt::Debug for ASTMutPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f
+            .debug_struct("ASTMutPipeline")
+            .field("passes", &self.pass_names())
+            .finish()
+    }
+}
@@ -1,62 +1,306 @@
-use std::collections::BTreeMap;
+use std::{collections::{hash_map::RandomState, HashMap}, fmt::Debug, hash::{BuildHasher, Hash}, sync::RwLock};
 
-use hastyc_common::identifiers::{Ident, ASTNodeID};
+use hastyc_common::identifiers::{Ident, ASTNodeID, Symbol};
 
-/// Stack that holds ribs, which are modifications to the scope. These ribs are data structures
-/// that can add or shadow something in the scope. Addition modifies the latest rib, while shadowing
-/// pushes new one onto the stack.
+/// Stack that holds ribs, which are modifications to the scope. Ribs are pushed and popped
+/// only at syntax boundaries, like blocks or function bodies, and names are always added to
+/// the latest rib. Shadowing a name in the same rib simply replaces it, as names are resolved
+/// in the same order in which they are declared. Whether shadowing is allowed is decided by
+/// name resolution, with the help of `shadowed_local`.
 #[derive(Debug, Clone)]
 pub struct RibStack {
-    stack: Vec<Rib>
+    stack: Vec<Rib>,
+    /// Trees of ribs on the stack, parallel to it, only kept when recording.
+    recording: Option<Vec<RibTree>>,
+    /// Recorded trees of ribs which were already popped.
+    recorded: Vec<RibTree>
+}
+
+/// Syntax boundary at which rib was pushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RibKind {
+    /// Items of a module, this rib is never popped, so that paths
+    /// can be resolved through the module later.
+    Module,
+    /// Names inside of an item, like struct fields or enum variants.
+    Item,
+    /// Function signature, local variables can't be used across this rib.
+    FnBoundary,
+    /// Closure parameters, unlike functions, closures can use outer
+    /// local variables, which are then captured by the closure.
+    Closure(ASTNodeID),
+    Block,
+    /// Loop, which also holds the names bound by `for` pattern.
+    Loop
+}
+
+impl RibKind {
+    /// Whether names in this rib are local variables, rather than items.
+    pub fn is_local(&self) -> bool {
+        !matches!(self, Self::Module | Self::Item)
+    }
 }
 
 /// Rib is a single modification to the scope.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct Rib {
-    /// Identifiers created in this rib
-    pub created_ident: BTreeMap<Ident, ASTNodeID>,
+    pub kind: RibKind,
+    /// Identifiers created in this rib, keyed only by their symbol
+    /// as the same name can be used in many places.
+    pub created_ident: HashMap<Symbol, ASTNodeID>,
+}
+
+/// Rib which was recorded by `RibStack::record`, together with the ribs
+/// pushed while it was on the stack. Unlike `Rib` this keeps every name
+/// bound in it, in the order they were bound, shadowed ones included.
+#[derive(Debug, Clone)]
+pub struct RibTree {
+    pub kind: RibKind,
+    pub names: Vec<(Ident, ASTNodeID)>,
+    pub children: Vec<RibTree>
+}
+
+impl Default for RibStack {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RibStack {
     pub fn new() -> Self {
-        Self { stack: Vec::new() }
+        Self { stack: Vec::new(), recording: None, recorded: Vec::new() }
+    }
+
+    /// Record structure of ribs pushed from now on, for debug dumps.
+    /// Ribs which are already on the stack are not recorded.
+    pub fn record(&mut self) {
+        self.recording = Some(Vec::new());
     }
 
-    pub fn push(&mut self) { self.stack.push(Rib::default()) }
-    pub fn pop(&mut self) -> Option<Rib> { self.stack.pop() }
+    /// Trees of recorded ribs that were popped from the bottom of
+    /// recording since the last call.
+    pub fn take_recorded(&mut self) -> Vec<RibTree> {
+        std::mem::take(&mut self.recorded)
+    }
+
+    pub fn push(&mut self, kind: RibKind) {
+        self.stack.push(Rib::new(kind));
+        if let Some(ref mut open) = self.recording {
+            open.push(RibTree { kind, names: Vec::new(), children: Vec::new() });
+        }
+    }
 
-    /// Get last from the stack creating new if there is none
-    fn get_last(&mut self) -> &mut Rib {
-        if self.stack.len() == 0 {
-            self.stack.push(Rib::default())
+    /// Pop the latest rib, which must be of the given kind, so that
+    /// mismatched push and pop is caught right where it happens.
+    pub fn pop(&mut self, kind: RibKind) -> Option<Rib> {
+        let rib = self.stack.pop();
+        debug_assert_eq!(rib.as_ref().map(|rib| rib.kind), Some(kind), "popped rib of a different kind");
+        if let Some(tree) = self.recording.as_mut().and_then(Vec::pop) {
+            match self.recording.as_mut().and_then(|open| open.last_mut()) {
+                Some(parent) => parent.children.push(tree),
+                None => self.recorded.push(tree)
+            }
         }
-        self.stack.last_mut().unwrap()
+        rib
+    }
+
+    /// All ribs from bottom to top.
+    pub fn ribs(&self) -> &[Rib] {
+        &self.stack
     }
 
     /// Get ident mapping looking at the stack top to bottom
     pub fn get_ident(&self, ident: &Ident) -> Option<&ASTNodeID> {
-        for elem in self.stack.iter() {
+        self.lookup(ident).map(|(_, node)| node)
+    }
+
+    /// Find ident together with index of the rib it was found in. Local
+    /// variables behind a function boundary are skipped, as functions
+    /// can only use items from outer scopes.
+    fn lookup(&self, ident: &Ident) -> Option<(usize, &ASTNodeID)> {
+        let mut crossed_fn = false;
+        for (idx, elem) in self.stack.iter().enumerate().rev() {
             if let Some(node) = elem.try_get_ident_mapping(ident) {
-                return Some(node)
+                if !(crossed_fn && elem.kind.is_local()) {
+                    return Some((idx, node))
+                }
             }
+            crossed_fn |= elem.kind == RibKind::FnBoundary;
         }
         None
     }
 
+    /// Names that can be looked up at the top of the stack, following
+    /// the same rules as `get_ident`. Used to suggest similar names.
+    pub fn visible_names(&self) -> Vec<Symbol> {
+        let mut names = Vec::new();
+        let mut crossed_fn = false;
+        for rib in self.stack.iter().rev() {
+            if !(crossed_fn && rib.kind.is_local()) {
+                names.extend(rib.created_ident.keys().copied());
+            }
+            crossed_fn |= rib.kind == RibKind::FnBoundary;
+        }
+        names
+    }
+
+    /// Local variable which a new binding of the ident would shadow,
+    /// None if the name is not bound or refers to an item.
+    pub fn shadowed_local(&self, ident: &Ident) -> Option<&ASTNodeID> {
+        let (idx, node) = self.lookup(ident)?;
+        self.stack[idx].kind.is_local().then_some(node)
+    }
+
+    /// Closures that capture the ident if it is used at the top of the
+    /// stack, from the innermost one. Empty if the ident is an item or a
+    /// variable declared inside of the innermost closure.
+    pub fn capturing_closures(&self, ident: &Ident) -> Vec<ASTNodeID> {
+        let Some((idx, _)) = self.lookup(ident) else { return Vec::new() };
+        if !self.stack[idx].kind.is_local() {
+            return Vec::new()
+        }
+        self.stack[idx + 1..].iter().rev()
+            .filter_map(|rib| match rib.kind {
+                RibKind::Closure(closure) => Some(closure),
+                _ => None
+            })
+            .collect()
+    }
+
+    /// Add name to the latest rib, there must be at least one.
     pub fn add_ident_mapping(&mut self, ident: Ident, def_node: ASTNodeID) {
-        if let Some(_node) = self.get_ident(&ident) {
-            self.push();
+        if let Some(tree) = self.recording.as_mut().and_then(|open| open.last_mut()) {
+            tree.names.push((ident.clone(), def_node));
         }
-        self.get_last().ident_mapping(ident, def_node)
+        self.stack
+            .last_mut()
+            .expect("name added outside of any rib")
+            .ident_mapping(ident, def_node)
     }
 }
 
 impl Rib {
+    pub fn new(kind: RibKind) -> Self {
+        Self {
+            kind,
+            created_ident: HashMap::new()
+        }
+    }
+
     pub fn ident_mapping(&mut self, ident: Ident, def_node: ASTNodeID) {
-        self.created_ident.insert(ident, def_node);
+        self.created_ident.insert(ident.symbol, def_node);
     }
 
     pub fn try_get_ident_mapping(&self, ident: &Ident) -> Option<&ASTNodeID> {
-        self.created_ident.get(&ident)
+        self.created_ident.get(&ident.symbol)
+    }
+}
+
+/// Candidate closest to the name, if it is close enough to be a typo of
+/// it. Names that can't be written in the source, like `<prelude>`, are
+/// never suggested. Ties are broken alphabetically, so that suggestions
+/// don't depend on the order of hash maps.
+pub fn find_similar_name(name: Symbol, candidates: impl IntoIterator<Item = Symbol>) -> Option<Symbol> {
+    let text = name.as_str();
+    let max_distance = (text.chars().count() / 3).max(1);
+    candidates.into_iter()
+        .filter(|candidate| *candidate != name && !candidate.as_str().starts_with('<'))
+        .map(|candidate| (edit_distance(text, candidate.as_str()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by(|(a, a_name), (b, b_name)| a.cmp(b).then_with(|| a_name.as_str().cmp(b_name.as_str())))
+        .map(|(_, candidate)| candidate)
+}
+
+/// Optimal string alignment distance, number of characters that have to
+/// be inserted, removed or replaced, or pairs of adjacent characters that
+/// have to be swapped, to turn one string into the other. Swaps count as
+/// one edit, so a typo like `itme` is as close to `item` as `iten` is.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Distances from the two previous prefixes of `a` to every prefix of `b`
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 0..a.len() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let replaced = previous[j] + usize::from(a[i] != b[j]);
+            row[j + 1] = replaced.min(row[j] + 1).min(previous[j + 1] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                row[j + 1] = row[j + 1].min(before[j - 1] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
+}
+
+const MAP_SHARDS: usize = 16;
+
+/// Hash map that can be filled from many threads at once. Keys are split
+/// between shards by their hash, and every shard has its own lock, so
+/// threads only wait for each other when they use the same shard.
+pub struct ShardedMap<K, V> {
+    shards: Vec<RwLock<HashMap<K, V>>>,
+    hasher: RandomState
+}
+
+impl<K, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        Self {
+            shards: (0..MAP_SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            hasher: RandomState::new()
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> ShardedMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn shard(&self, key: &K) -> &RwLock<HashMap<K, V>> {
+        // Low bits are used by the hash map, so shard is picked with the high ones
+        let hash = self.hasher.hash_one(key);
+        &self.shards[(hash >> 60) as usize % MAP_SHARDS]
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).write().unwrap().insert(key, value)
+    }
+
+    /// Copy of the value, the map can't lend it out as other threads
+    /// may be writing to it.
+    pub fn get(&self, key: &K) -> Option<V> where V: Clone {
+        self.shard(key).read().unwrap().get(key).cloned()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.shard(key).read().unwrap().contains_key(key)
+    }
+
+    /// Copy of all entries, in no particular order.
+    pub fn to_vec(&self) -> Vec<(K, V)> where K: Clone, V: Clone {
+        self.shards.iter()
+            .flat_map(|shard| shard.read().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>())
+            .collect()
     }
-}
\ No newline at end of file
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for ShardedMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut map = f.debug_map();
+        for shard in self.shards.iter() {
+            map.entries(shard.read().unwrap().iter());
+        }
+        map.finish()
+    }
+}
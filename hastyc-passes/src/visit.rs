@@ -0,0 +1,307 @@
+use hastyc_common::path::Path;
+use hastyc_parser::parser::{Block, DataVariant, EnumVariant, Expr, ExprKind, FieldDef, FieldLitExpr, FnInput, FnRetTy, Function, ImportTree, ImportTreeKind, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, Lit, Package, Pat, RestExpr, Stmt, StmtKind, StmtStream, StructLiteral, Ty, TyKind};
+
+/// Counts how deep a recursive walk is in nested expressions. Every
+/// expression is one level deeper than the one containing it, and passes
+/// have to count the same way, so that they all skip the same expressions.
+/// The limit is `Options::max_expr_depth`, name resolution reports deeper
+/// expressions and other passes skip them.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthLimit {
+    depth: u32,
+    limit: u32,
+    exceeded: bool
+}
+
+impl DepthLimit {
+    pub fn new(limit: u32) -> Self {
+        Self { depth: 0, limit, exceeded: false }
+    }
+
+    /// Limit for a nested function, whose depth is counted from itself.
+    pub fn nested(&self) -> Self {
+        Self::new(self.limit)
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Go one level deeper, false if that would exceed the limit. In
+    /// that case the expression has to be skipped without calling `exit`.
+    pub fn enter(&mut self) -> bool {
+        if self.depth >= self.limit {
+            self.exceeded = true;
+            return false
+        }
+        self.depth += 1;
+        true
+    }
+
+    /// Whether some expression was already skipped, so that only
+    /// the first one is reported.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    pub fn exit(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+/// Walker over the AST. Every method by default visits all children of
+/// the node with the matching `walk_*` function, so a pass only has to
+/// override methods for nodes it cares about, calling `walk_*` from them
+/// if it wants to go deeper. Returning `Err` stops the whole walk.
+pub trait Visitor<'ast>: Sized {
+    /// Value that stops the walk, usually an error.
+    type Break;
+
+    fn visit_package(&mut self, package: &'ast Package) -> Result<(), Self::Break> {
+        walk_package(self, package)
+    }
+
+    fn visit_item_stream(&mut self, stream: &'ast ItemStream) -> Result<(), Self::Break> {
+        walk_item_stream(self, stream)
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) -> Result<(), Self::Break> {
+        walk_item(self, item)
+    }
+
+    fn visit_import_tree(&mut self, tree: &'ast ImportTree) -> Result<(), Self::Break> {
+        walk_import_tree(self, tree)
+    }
+
+    fn visit_fn(&mut self, function: &'ast Function) -> Result<(), Self::Break> {
+        walk_fn(self, function)
+    }
+
+    fn visit_fn_input(&mut self, input: &'ast FnInput) -> Result<(), Self::Break> {
+        walk_fn_input(self, input)
+    }
+
+    fn visit_data_variant(&mut self, data: &'ast DataVariant) -> Result<(), Self::Break> {
+        walk_data_variant(self, data)
+    }
+
+    fn visit_field_def(&mut self, field: &'ast FieldDef) -> Result<(), Self::Break> {
+        walk_field_def(self, field)
+    }
+
+    fn visit_enum_variant(&mut self, variant: &'ast EnumVariant) -> Result<(), Self::Break> {
+        walk_enum_variant(self, variant)
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) -> Result<(), Self::Break> {
+        walk_block(self, block)
+    }
+
+    fn visit_stmt_stream(&mut self, stream: &'ast StmtStream) -> Result<(), Self::Break> {
+        walk_stmt_stream(self, stream)
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) -> Result<(), Self::Break> {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_let_binding(&mut self, binding: &'ast LetBinding) -> Result<(), Self::Break> {
+        walk_let_binding(self, binding)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Self::Break> {
+        walk_expr(self, expr)
+    }
+
+    fn visit_struct_lit(&mut self, lit: &'ast StructLiteral) -> Result<(), Self::Break> {
+        walk_struct_lit(self, lit)
+    }
+
+    fn visit_field_lit(&mut self, field: &'ast FieldLitExpr) -> Result<(), Self::Break> {
+        walk_field_lit(self, field)
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) -> Result<(), Self::Break> {
+        walk_ty(self, ty)
+    }
+
+    // Leaf nodes, these have nothing to walk into
+
+    fn visit_pat(&mut self, _pat: &'ast Pat) -> Result<(), Self::Break> { Ok(()) }
+    fn visit_lit(&mut self, _lit: &'ast Lit) -> Result<(), Self::Break> { Ok(()) }
+    fn visit_path(&mut self, _path: &'ast Path) -> Result<(), Self::Break> { Ok(()) }
+}
+
+pub fn walk_package<'ast, V: Visitor<'ast>>(visitor: &mut V, package: &'ast Package) -> Result<(), V::Break> {
+    visitor.visit_item_stream(&package.items)
+}
+
+pub fn walk_item_stream<'ast, V: Visitor<'ast>>(visitor: &mut V, stream: &'ast ItemStream) -> Result<(), V::Break> {
+    for item in stream.items.iter() {
+        visitor.visit_item(item)?;
+    }
+    Ok(())
+}
+
+pub fn walk_item<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Item) -> Result<(), V::Break> {
+    match item.kind {
+        ItemKind::Module(ref items) => visitor.visit_item_stream(items),
+        ItemKind::Import(_, ref tree) => visitor.visit_import_tree(tree),
+        ItemKind::Fn(ref function) => visitor.visit_fn(function),
+        ItemKind::Struct(ref data) => visitor.visit_data_variant(data),
+        ItemKind::Enum(ref def) => {
+            for variant in def.variants.iter() {
+                visitor.visit_enum_variant(variant)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn walk_import_tree<'ast, V: Visitor<'ast>>(visitor: &mut V, tree: &'ast ImportTree) -> Result<(), V::Break> {
+    visitor.visit_path(&tree.prefix)?;
+    if let ImportTreeKind::Nested(ref trees) = tree.kind {
+        for (subtree, _) in trees.iter() {
+            visitor.visit_import_tree(subtree)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn walk_fn<'ast, V: Visitor<'ast>>(visitor: &mut V, function: &'ast Function) -> Result<(), V::Break> {
+    for input in function.signature.inputs.iter() {
+        visitor.visit_fn_input(input)?;
+    }
+    if let FnRetTy::Ty(ref ty) = function.signature.output {
+        visitor.visit_ty(ty)?;
+    }
+    if let Some(ref body) = function.body {
+        visitor.visit_block(body)?;
+    }
+    Ok(())
+}
+
+pub fn walk_fn_input<'ast, V: Visitor<'ast>>(visitor: &mut V, input: &'ast FnInput) -> Result<(), V::Break> {
+    visitor.visit_pat(&input.pat)?;
+    visitor.visit_ty(&input.ty)
+}
+
+pub fn walk_data_variant<'ast, V: Visitor<'ast>>(visitor: &mut V, data: &'ast DataVariant) -> Result<(), V::Break> {
+    match data {
+        DataVariant::Struct { ref fields } | DataVariant::Tuple { ref fields } => {
+            for field in fields.iter() {
+                visitor.visit_field_def(field)?;
+            }
+            Ok(())
+        },
+        DataVariant::Unit => Ok(())
+    }
+}
+
+pub fn walk_field_def<'ast, V: Visitor<'ast>>(visitor: &mut V, field: &'ast FieldDef) -> Result<(), V::Break> {
+    visitor.visit_ty(&field.ty)
+}
+
+pub fn walk_enum_variant<'ast, V: Visitor<'ast>>(visitor: &mut V, variant: &'ast EnumVariant) -> Result<(), V::Break> {
+    visitor.visit_data_variant(&variant.data)
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast>>(visitor: &mut V, block: &'ast Block) -> Result<(), V::Break> {
+    visitor.visit_stmt_stream(&block.stmts)
+}
+
+pub fn walk_stmt_stream<'ast, V: Visitor<'ast>>(visitor: &mut V, stream: &'ast StmtStream) -> Result<(), V::Break> {
+    for stmt in stream.stmts.iter() {
+        visitor.visit_stmt(stmt)?;
+    }
+    Ok(())
+}
+
+pub fn walk_stmt<'ast, V: Visitor<'ast>>(visitor: &mut V, stmt: &'ast Stmt) -> Result<(), V::Break> {
+    match stmt.kind {
+        StmtKind::LetBinding(ref binding) => visitor.visit_let_binding(binding),
+        StmtKind::Item(ref item) => visitor.visit_item(item),
+        StmtKind::Expr(ref expr) | StmtKind::ExprNS(ref expr) => visitor.visit_expr(expr)
+    }
+}
+
+pub fn walk_let_binding<'ast, V: Visitor<'ast>>(visitor: &mut V, binding: &'ast LetBinding) -> Result<(), V::Break> {
+    visitor.visit_pat(&binding.pat)?;
+    if let Some(ref ty) = binding.ty {
+        visitor.visit_ty(ty)?;
+    }
+    if let LetBindingKind::Init(ref expr) = binding.kind {
+        visitor.visit_expr(expr)?;
+    }
+    Ok(())
+}
+
+pub fn walk_expr<'ast, V: Visitor<'ast>>(visitor: &mut V, expr: &'ast Expr) -> Result<(), V::Break> {
+    match expr.kind {
+        ExprKind::Path(ref path) => visitor.visit_path(path),
+        ExprKind::Literal(ref lit) => visitor.visit_lit(lit),
+        ExprKind::Field(ref target, _, _) => visitor.visit_expr(target),
+        ExprKind::Assign(ref target, ref value, _) => {
+            visitor.visit_expr(target)?;
+            visitor.visit_expr(value)
+        },
+        ExprKind::Unary(_, ref operand) => visitor.visit_expr(operand),
+        ExprKind::Binary(_, ref lhs, ref rhs) => {
+            visitor.visit_expr(lhs)?;
+            visitor.visit_expr(rhs)
+        },
+        ExprKind::Call(ref target, ref args, _) => {
+            visitor.visit_expr(target)?;
+            for arg in args.iter() {
+                visitor.visit_expr(arg)?;
+            }
+            Ok(())
+        },
+        ExprKind::If(ref condition, ref block, ref else_expr) => {
+            visitor.visit_expr(condition)?;
+            visitor.visit_block(block)?;
+            if let Some(ref else_expr) = else_expr {
+                visitor.visit_expr(else_expr)?;
+            }
+            Ok(())
+        },
+        ExprKind::Block(ref block) | ExprKind::Loop(ref block) => visitor.visit_block(block),
+        ExprKind::While(ref condition, ref block) => {
+            visitor.visit_expr(condition)?;
+            visitor.visit_block(block)
+        },
+        ExprKind::For(ref pat, ref iter, ref block) => {
+            visitor.visit_pat(pat)?;
+            visitor.visit_expr(iter)?;
+            visitor.visit_block(block)
+        },
+        ExprKind::Break(ref value) => match value {
+            Some(value) => visitor.visit_expr(value),
+            None => Ok(())
+        },
+        ExprKind::Continue | ExprKind::Err => Ok(()),
+        ExprKind::StructLit(ref lit) => visitor.visit_struct_lit(lit)
+    }
+}
+
+pub fn walk_struct_lit<'ast, V: Visitor<'ast>>(visitor: &mut V, lit: &'ast StructLiteral) -> Result<(), V::Break> {
+    visitor.visit_path(&lit.path)?;
+    for field in lit.fields.iter() {
+        visitor.visit_field_lit(field)?;
+    }
+    if let RestExpr::Valued(ref rest) = lit.rest {
+        visitor.visit_expr(rest)?;
+    }
+    Ok(())
+}
+
+pub fn walk_field_lit<'ast, V: Visitor<'ast>>(visitor: &mut V, field: &'ast FieldLitExpr) -> Result<(), V::Break> {
+    visitor.visit_expr(&field.expr)
+}
+
+pub fn walk_ty<'ast, V: Visitor<'ast>>(visitor: &mut V, ty: &'ast Ty) -> Result<(), V::Break> {
+    match ty.kind {
+        TyKind::Path(ref path) => visitor.visit_path(path),
+        TyKind::SelfTy | TyKind::Void | TyKind::Never | TyKind::Infer => Ok(())
+    }
+}
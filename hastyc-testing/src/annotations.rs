@@ -0,0 +1,85 @@
+use hastyc_common::{error::{CommonErrorContext, Diagnostic, Severity}, identifiers::SourceFileID, source::{SourceFile, SourceMap}};
+
+/// Diagnostic which a fixture expects, written in a comment like
+/// `//~ ERROR could not be resolved` on its line, or `//~^ ERROR ...`
+/// for the line above, with a `^` for every line up. Diagnostic matches
+/// if the text is in its message or is its code, like `H0200`.
+struct Annotation {
+    file: SourceFileID,
+    line: u32,
+    severity: Severity,
+    text: String
+}
+
+/// Check diagnostics of the session against annotations in its files.
+/// Every annotation must be matched by a diagnostic on its line, and
+/// errors can't be left without an annotation. Returns what is wrong,
+/// if anything.
+pub fn check(sources: &SourceMap, diagnostics: &[Box<dyn Diagnostic>]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut annotations = Vec::new();
+    for file in sources.files() {
+        if let Err(problem) = parse(file, &mut annotations) {
+            problems.push(problem);
+        }
+    }
+    let Some(fallback) = sources.files().first() else { return problems };
+
+    let mut matched = vec![false; annotations.len()];
+    for diag in diagnostics.iter() {
+        let data = diag.data(&CommonErrorContext::for_diagnostic(diag.as_ref(), sources, fallback));
+        let position = data.primary_span.and_then(|span| {
+            let file = sources.source_of(&span)?;
+            Some((file, file.lookup_line(span.start).0))
+        });
+        let found = position.and_then(|(file, line)| (0..annotations.len()).find(|&idx| {
+            let annotation = &annotations[idx];
+            !matched[idx] && annotation.file == file.id && annotation.line == line
+                && annotation.severity == data.severity
+                && (data.message.contains(&annotation.text) || data.code == Some(annotation.text.as_str()))
+        }));
+        match (found, position) {
+            (Some(idx), _) => matched[idx] = true,
+            (None, _) if data.severity != Severity::Error => {},
+            (None, Some((file, line))) => problems.push(format!("unexpected error at {}:{}: {}", file.name, line, data.message)),
+            (None, None) => problems.push(format!("unexpected error: {}", data.message))
+        }
+    }
+
+    for (annotation, _) in annotations.iter().zip(matched).filter(|(_, matched)| !matched) {
+        let name = sources.get(annotation.file).map_or(String::new(), |file| file.name.to_string());
+        problems.push(format!(
+            "expected {} `{}` at {}:{}, which wasn't reported",
+            annotation.severity.label(), annotation.text, name, annotation.line
+        ));
+    }
+    problems
+}
+
+/// Find annotations in the file, or the first one which can't be read.
+fn parse(file: &SourceFile, annotations: &mut Vec<Annotation>) -> Result<(), String> {
+    let Some(ref text) = file.src else { return Ok(()) };
+    for (idx, line) in text.as_str().lines().enumerate() {
+        let Some((_, annotation)) = line.split_once("//~") else { continue };
+        let up = annotation.len() - annotation.trim_start_matches('^').len();
+        let annotation = annotation[up..].trim();
+        let (kind, text) = annotation.split_once(char::is_whitespace).unwrap_or((annotation, ""));
+        let severity = match kind {
+            "ERROR" => Severity::Error,
+            "WARNING" => Severity::Warning,
+            "NOTE" => Severity::Note,
+            "HELP" => Severity::Help,
+            _ => return Err(format!("unknown annotation `{}` at {}:{}, expected ERROR, WARNING, NOTE or HELP", kind, file.name, idx + 1))
+        };
+        let Some(line) = (idx + 1).checked_sub(up).filter(|&line| line > 0) else {
+            return Err(format!("annotation at {}:{} points above the first line", file.name, idx + 1))
+        };
+        annotations.push(Annotation {
+            file: file.id,
+            line: line as u32,
+            severity,
+            text: text.trim().to_string()
+        });
+    }
+    Ok(())
+}
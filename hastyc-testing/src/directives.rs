@@ -0,0 +1,82 @@
+use hastyc_common::{session::{OptLevel, ShadowingPolicy}, source::SourceFile};
+
+use crate::stages::{Output, Stage};
+
+/// How a fixture is compiled, written in comments like
+/// `//@ stop-after: typecheck`, `//@ print: types, diagnostics`,
+/// `//@ opt-level: 1` or `//@ shadowing: deny`.
+/// Fixtures without them are resolved and print their syntax tree and
+/// diagnostics. With only a stage, what it made is printed together
+/// with diagnostics.
+pub struct Directives {
+    pub stop_after: Stage,
+    pub outputs: Vec<Output>,
+    /// Level MIR is optimized at, nothing is by default.
+    pub opt_level: OptLevel,
+    /// Policy of name resolution for shadowed variables, allowed by default.
+    pub shadowing: ShadowingPolicy
+}
+
+/// Read directives of the file, or the first one which can't be read.
+pub fn parse(file: &SourceFile) -> Result<Directives, String> {
+    let mut stop_after = None;
+    let mut outputs = Vec::new();
+    let mut opt_level = OptLevel::default();
+    let mut shadowing = ShadowingPolicy::default();
+    let text = file.src.as_ref().map_or("", |src| src.as_str());
+    for (idx, line) in text.lines().enumerate() {
+        let Some(directive) = line.trim_start().strip_prefix("//@") else { continue };
+        let at = || format!("{}:{}", file.name, idx + 1);
+        let Some((name, value)) = directive.split_once(':') else {
+            return Err(format!("directive at {} has no value, expected `//@ <name>: <value>`", at()))
+        };
+        let value = value.trim();
+        match name.trim() {
+            "stop-after" => {
+                let Some(stage) = Stage::parse(value) else {
+                    return Err(format!("unknown stage `{}` at {}", value, at()))
+                };
+                stop_after = Some(stage);
+            },
+            "print" => {
+                for name in value.split(',').map(str::trim) {
+                    let Some(output) = Output::parse(name) else {
+                        return Err(format!("unknown output `{}` at {}", name, at()))
+                    };
+                    outputs.push(output);
+                }
+            },
+            "opt-level" => {
+                let Some(level) = OptLevel::parse(value) else {
+                    return Err(format!("unknown optimization level `{}` at {}", value, at()))
+                };
+                opt_level = level;
+            },
+            "shadowing" => {
+                let Some(policy) = ShadowingPolicy::parse(value) else {
+                    return Err(format!("unknown shadowing policy `{}` at {}", value, at()))
+                };
+                shadowing = policy;
+            },
+            name => return Err(format!("unknown directive `{}` at {}", name, at()))
+        }
+    }
+    let stop_after = match stop_after {
+        Some(stage) => {
+            if outputs.is_empty() {
+                outputs = vec![stage.default_output(), Output::Diagnostics];
+            }
+            stage
+        },
+        None if outputs.is_empty() => {
+            outputs = vec![Output::Ast, Output::Diagnostics];
+            Stage::Resolve
+        },
+        // Printed outputs decide how far it has to go
+        None => outputs.iter().map(|output| output.stage()).fold(Stage::Resolve, Stage::max)
+    };
+    if let Some(output) = outputs.iter().find(|output| output.stage() > stop_after) {
+        return Err(format!("`{}` is only printed with `stop-after: {}` in {}", output.name(), output.stage().name(), file.name))
+    }
+    Ok(Directives { stop_after, outputs, opt_level, shadowing })
+}
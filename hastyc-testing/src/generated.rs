@@ -0,0 +1,56 @@
+//! Fixtures too big to be checked in, their source is generated here
+//! and only snapshots are stored under `tests/fixtures`.
+
+/// Function making the source of a generated fixture.
+type Generator = fn() -> String;
+
+/// Names of generated fixtures, relative to the fixtures directory,
+/// with functions making their source.
+pub const FIXTURES: &[(&str, Generator)] = &[
+    ("resolve/deep_chain.hty", deep_chain),
+    ("resolve/long_chain.hty", long_chain)
+];
+
+/// Source of the generated fixture with the name, if there is one.
+pub fn source(name: &str) -> Option<String> {
+    FIXTURES.iter().find(|(fixture, _)| *fixture == name).map(|(_, source)| source())
+}
+
+/// Chain much deeper than the limit, which is reported without
+/// overflowing the stack in any pass or when the tree is dropped.
+fn deep_chain() -> String {
+    chain(
+        "// Chain of 60000 additions, deeper than the limit of nested\n\
+         // expressions.\n\
+         //@ print: diagnostics\n",
+        60000,
+        " //~ ERROR H0209"
+    )
+}
+
+/// Chain just below the default limit, which has to compile.
+fn long_chain() -> String {
+    chain(
+        "// Chain of 1000 additions, below the limit of nested expressions.\n\
+         //@ stop-after: mir\n\
+         //@ print: diagnostics\n",
+        1000,
+        ""
+    )
+}
+
+/// Function returning a sum of `terms` variables, twenty on a line.
+/// Annotation is written after the first one.
+fn chain(header: &str, terms: usize, annotation: &str) -> String {
+    let mut source = format!("{}\nfn main() -> i32 {{\n    let a = 1;\n    a{}\n", header, annotation);
+    let mut left = terms - 1;
+    while left > 0 {
+        let line = left.min(20);
+        source.push_str("   ");
+        source.push_str(&" + a".repeat(line));
+        source.push('\n');
+        left -= line;
+    }
+    source.push_str("}\n");
+    source
+}
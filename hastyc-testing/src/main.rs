@@ -1,139 +1,342 @@
-use hastyc_ast_fmt::PackageASTPrettyPrinter;
-use hastyc_common::{error::{CommonErrorContext, ErrorDisplay}, identifiers::{ASTNodeID, PkgID, SourceFileID}, source::SourceFile};
-use hastyc_parser::{lexer::Lexer, parser::Parser};
-use hastyc_passes::passes::{name_resolve::NameResolvePass, ASTPass, GetTyQuery, QueryContext};
-
-// const CODE: &str = "
-// #[test_attribute]
-// #[yoooo]
-// import hello::world;
-
-// pub fn hello_world(self, hello_world: hello::world::this_is::MyType) -> () {
-//     import inline::test;
-//     let a;
-//     fn b() {
-//         let x;
-//     }
-//     let typed: i32 = 1;
-//     let c = 5.method;
-//     let hello = a.b.c_d;
-//     let bruh = -4;
-//     let binary_bruh = -bruh + (1 - 2);
-//     let comparison: bool = bruh >= binary_bruh;
-//     let function_call = hello_world(1, 2, bruh, 3 + 1);
-//     let method_call = object.method();
-
-//     {
-//         let a = 1;
-//     }
-
-//     if bruh == -4 {
-//         let conditional = 1;
-//         conditional
-//     } else {
-//         1 + 2
-//     }
-
-//     let a = 1;
-//     loop {
-//         a = a + 1;
-//         if a > 10 {
-//             break;
-//         }
-//     }
-
-//     while bruh > 1 {
-//         bruh = bruh + 1;
-//     }
-
-//     for test in hello.world() {
-//         continue;
-//     }
-// }
-
-// struct Hello;
-// struct World(i32, pub f32);
-// struct HelloWorld {
-//     pub a: i32,
-//     b: usize
-// }
-
-// enum Hello {
-//     VariantA,
-//     VariantB(i32, pub usize),
-//     VariantC {
-//         hello: isize,
-//         pub world: usize
-//     }
-// }
-
-// fn test() {
-//     path::to::my_struct {
-//         a: 1 + 1,
-//         b: function.call()
-//     }
-// }
-
-// // fn a(hello: i32) hello
-// // let a = 1;
-// ";
-
-const CODE: &str = "
-    module hello {
-        module world {
-            struct MyStruct {
-                a: Bruh
+use std::{io::Read, panic::AssertUnwindSafe, path::{Path, PathBuf}, process::ExitCode};
+
+use hastyc_common::{identifiers::PkgID, session::{OptLevel, Options, Session, ShadowingPolicy}, source::SourceMap};
+use stages::{Output, Stage};
+
+mod annotations;
+mod directives;
+mod generated;
+mod roundtrip;
+mod run;
+mod stages;
+
+/// Random packages checked when no fixtures are filtered.
+const ROUNDTRIP_CASES: u64 = 256;
+
+const USAGE: &str = "\
+Usage: hastyc-testing [--update] [--roundtrip=<count>] [--seed=<n>] [filter...]
+       hastyc-testing run <path|-> [--stop-after=<stage>] [--print=<output>,...]
+
+Compiles every `.hty` file under `tests/fixtures` and compares the syntax
+tree and diagnostics with the `.expected` file next to it. Filters only
+run fixtures whose path contains one of them. Source of fixtures too
+big to be checked in is generated by `src/generated.rs`, only their
+snapshots are in `tests/fixtures`.
+
+Fixtures are resolved and print their syntax tree unless comments like
+`//@ stop-after: typecheck` or `//@ print: types, diagnostics` ask for
+something else. `//@ opt-level: <n>` optimizes MIR at the level and
+`//@ shadowing: <allow|warn|deny>` sets the policy for shadowed variables. With `stop-after: run`, the program is compiled with
+every backend and run, printing its exit status, output and error
+once if they all agree.
+
+Every error has to be expected by a comment like `//~ ERROR <message>`
+on its line, or `//~^ ERROR <message>` for the line above. Message is
+a part of the error message, or its code. WARNING, NOTE and HELP are
+checked when they are expected.
+
+Then random syntax trees are printed with the source formatter and parsed
+back, which has to give the same trees. This runs 256 of them unless
+fixtures are filtered, a failing one is reported with its seed.
+
+With `run`, a single file is compiled instead, or text from stdin for
+`-`, and what was asked for is printed like in snapshots.
+
+Options:
+    --update                Write snapshots of failing and new fixtures
+                            instead of failing
+    --roundtrip=<count>     Number of random trees to check
+    --seed=<n>              Seed of the first tree, following ones get
+                            the next seeds
+    -h, --help              Print this message
+
+Options of `run`:
+    --stop-after=<stage>    lex, parse, resolve, typecheck, mir or run,
+                            resolve is the default
+    --print=<output>,...    tokens, ast, formatted, names, scopes, types,
+                            mir, run or diagnostics, by default what the
+                            last stage made and diagnostics
+    --opt-level=<n>         0, 1 or 2, how much MIR is optimized, 0 is
+                            the default
+    --shadowing=<policy>    allow, warn or deny shadowed variables, allow
+                            is the default";
+
+/// Outcome of one fixture.
+enum Outcome {
+    Passed,
+    Updated,
+    /// Snapshot is missing or differs, with what to print about it.
+    Failed(String)
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "run") {
+        return run_file(&args[1..])
+    }
+    let mut update = false;
+    let mut filters = Vec::new();
+    let mut cases = None;
+    let mut seed = 0;
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS
+            },
+            "--update" => update = true,
+            _ if arg.starts_with("--roundtrip=") || arg.starts_with("--seed=") => {
+                let (name, value) = arg.split_once('=').unwrap();
+                let Ok(value) = value.parse::<u64>() else {
+                    return usage_error(&format!("invalid number `{}`", value))
+                };
+                match name {
+                    "--seed" => seed = value,
+                    _ => cases = Some(value)
+                }
+            },
+            _ if arg.starts_with('-') => return usage_error(&format!("unknown option `{}`", arg)),
+            _ => filters.push(arg)
+        }
+    }
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+    let mut fixtures = Vec::new();
+    collect_fixtures(&root, &mut fixtures);
+    fixtures.extend(generated::FIXTURES.iter().map(|(name, _)| root.join(name)));
+    fixtures.sort();
+
+    let (mut passed, mut updated, mut failed) = (0, 0, Vec::new());
+    for fixture in fixtures.iter() {
+        let name = fixture.strip_prefix(&root).unwrap_or(fixture).to_string_lossy().replace('\\', "/");
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue
+        }
+        match check_fixture(&root, fixture, update) {
+            Outcome::Passed => passed += 1,
+            Outcome::Updated => {
+                println!("updated {}", name);
+                updated += 1;
+            },
+            Outcome::Failed(message) => {
+                println!("FAILED {}\n{}", name, message);
+                failed.push(name);
             }
+        }
+    }
+
+    let result = if failed.is_empty() { "ok" } else { "FAILED" };
+    println!("\nfixture result: {}. {} passed; {} failed; {} updated", result, passed, failed.len(), updated);
 
-            struct Bruh;
+    let fixtures_failed = !failed.is_empty();
+
+    let cases = cases.unwrap_or(if filters.is_empty() { ROUNDTRIP_CASES } else { 0 });
+    if cases > 0 {
+        let mut roundtrip_failed = 0;
+        for seed in seed..seed.saturating_add(cases) {
+            if let Err(message) = roundtrip::check_seed(seed) {
+                println!("FAILED roundtrip seed {}\n{}", seed, message);
+                failed.push(format!("roundtrip seed {}", seed));
+                roundtrip_failed += 1;
+            }
         }
+        let result = if roundtrip_failed == 0 { "ok" } else { "FAILED" };
+        println!("roundtrip result: {}. {} passed; {} failed", result, cases - roundtrip_failed, roundtrip_failed);
+    }
+    if failed.is_empty() {
+        return ExitCode::SUCCESS
+    }
+    println!("\nfailures:");
+    for name in failed.iter() {
+        println!("    {}", name);
     }
+    if fixtures_failed {
+        println!("\nrun with `--update` to accept the new output");
+    }
+    ExitCode::FAILURE
+}
 
-    fn bruh() {
-        let test: hello::world::MyStruct;
-        let bruh = test.a;
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    ExitCode::from(2)
+}
+
+/// The `run` command, which compiles a file, or stdin given as `-`, and
+/// prints the outputs. Fails if the compiler reported errors.
+fn run_file(args: &[String]) -> ExitCode {
+    let mut path = None;
+    let mut stop_after = Stage::Resolve;
+    let mut outputs = Vec::new();
+    let mut options = Options::default();
+    for arg in args {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS
+        } else if let Some(value) = arg.strip_prefix("--stop-after=") {
+            let Some(stage) = Stage::parse(value) else {
+                return usage_error(&format!("unknown stage `{}`", value))
+            };
+            stop_after = stage;
+        } else if let Some(value) = arg.strip_prefix("--print=") {
+            for name in value.split(',') {
+                let Some(output) = Output::parse(name) else {
+                    return usage_error(&format!("unknown output `{}`", name))
+                };
+                outputs.push(output);
+            }
+        } else if let Some(value) = arg.strip_prefix("--opt-level=") {
+            let Some(level) = OptLevel::parse(value) else {
+                return usage_error(&format!("unknown optimization level `{}`", value))
+            };
+            options.opt_level = level;
+        } else if let Some(value) = arg.strip_prefix("--shadowing=") {
+            let Some(policy) = ShadowingPolicy::parse(value) else {
+                return usage_error(&format!("unknown shadowing policy `{}`", value))
+            };
+            options.shadowing = policy;
+        } else if arg.starts_with('-') && arg != "-" {
+            return usage_error(&format!("unknown option `{}`", arg))
+        } else if path.is_none() {
+            path = Some(arg.as_str());
+        } else {
+            return usage_error(&format!("unexpected argument `{}`", arg))
+        }
+    }
+    let Some(path) = path else { return usage_error("no file given to `run`") };
+    if outputs.is_empty() {
+        outputs = vec![stop_after.default_output(), Output::Diagnostics];
+    }
+    if let Some(output) = outputs.iter().find(|output| output.stage() > stop_after) {
+        return usage_error(&format!("`{}` is only printed with `--stop-after={}`", output.name(), output.stage().name()))
     }
-";
 
-fn main() {
-    env_logger::init();
+    let mut sess = Session::new(options);
+    let root = if path == "-" {
+        let mut text = String::new();
+        if let Err(err) = std::io::stdin().read_to_string(&mut text) {
+            eprintln!("error: can't read stdin: {}", err);
+            return ExitCode::FAILURE
+        }
+        sess.source_map.add_virtual("stdin", text, PkgID::new_unique())
+    } else {
+        match sess.source_map.load_file(path, PkgID::new_unique()) {
+            Ok(root) => root,
+            Err(err) => {
+                eprintln!("error: can't read `{}`: {}", path, err);
+                return ExitCode::FAILURE
+            }
+        }
+    };
+    print!("{}", stages::compile(&mut sess, root, stop_after, &outputs));
+    if sess.has_errors() { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+/// Find all `.hty` files under the directory. Files named `mod.hty` are
+/// modules which fixtures next to their directory declare, not fixtures.
+fn collect_fixtures(dir: &Path, fixtures: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_fixtures(&path, fixtures);
+        } else if path.extension().is_some_and(|ext| ext == "hty") && !path.ends_with("mod.hty") {
+            fixtures.push(path);
+        }
+    }
+}
+
+/// Compile the fixture and compare the output with its snapshot, or
+/// write the snapshot if it differs and `update` is set.
+fn check_fixture(root: &Path, fixture: &Path, update: bool) -> Outcome {
+    let snapshot = fixture.with_extension("expected");
+    // A panic in the compiler fails the fixture instead of the whole run
+    let (actual, problems) = match std::panic::catch_unwind(AssertUnwindSafe(|| compile(root, fixture))) {
+        Ok(compiled) => compiled,
+        Err(_) => return Outcome::Failed("    compiler panicked".to_string())
+    };
+    // Snapshots can be updated, but annotations have to be fixed by hand
+    if !problems.is_empty() {
+        return Outcome::Failed(problems.iter().map(|problem| format!("    {}\n", problem)).collect())
+    }
+    let expected = std::fs::read_to_string(&snapshot).ok();
+    if expected.as_deref() == Some(actual.as_str()) {
+        return Outcome::Passed
+    }
+    if update {
+        return match std::fs::write(&snapshot, actual) {
+            Ok(()) => Outcome::Updated,
+            Err(err) => Outcome::Failed(format!("    can't write `{}`: {}", snapshot.display(), err))
+        }
+    }
+    match expected {
+        Some(expected) => Outcome::Failed(diff(&expected, &actual)),
+        None => Outcome::Failed(format!("    no snapshot at `{}`", snapshot.display()))
+    }
+}
 
-    let source = SourceFile::new_raw(
-        CODE.to_string(),
-        PkgID::new_unique(),
-        SourceFileID::new_unique()
-    );
-
-    let ts = Lexer::lex(&source).unwrap();
-    let package = Parser::parse_from_root(&source, &ts);
-
-    if let Err(err) = package {
-        println!(
-            "{}",
-            err.fmt_error(&CommonErrorContext {
-                source: &source
-            })
-        );
-        return;
-    }
-
-    println!("AST: {:#?}", 
-        package
-    );
-
-    let pkg = package.as_ref().unwrap();
-    let mut ctx = QueryContext::for_package(pkg);
-    let mut pass = NameResolvePass::new();
-    if let Err(err) = pass.traverse(&mut ctx) {
-        println!(
-            "{}",
-            err.fmt_error(&CommonErrorContext {
-                source: &source
-            })
-        );
-        return;
-    }
-    // println!("Pass: {:?}", pass);
-    println!("Cx: {:?}", ctx);
-    println!("Type of test.a: {:?}", ctx.query(GetTyQuery(ASTNodeID::new(20))));
+/// Snapshot of the fixture: what its directives ask for, by default its
+/// syntax tree followed by diagnostics, together with problems of
+/// annotations of expected diagnostics. Paths in diagnostics are relative
+/// to the fixtures directory, so snapshots don't depend on where it is.
+fn compile(root: &Path, fixture: &Path) -> (String, Vec<String>) {
+    let mut sess = Session::with_source_map(Options::default(), SourceMap::with_root(root));
+    let name = fixture.strip_prefix(root).unwrap_or(fixture).to_string_lossy().replace('\\', "/");
+    let source = match generated::source(&name) {
+        Some(text) => sess.source_map.add_virtual(name, text, PkgID::new_unique()),
+        None => match sess.source_map.load_file(fixture, PkgID::new_unique()) {
+            Ok(source) => source,
+            Err(_) => return (format!("can't read `{}`\n", fixture.display()), Vec::new())
+        }
+    };
+    let directives = match directives::parse(sess.source_map.get(source).unwrap()) {
+        Ok(directives) => directives,
+        Err(problem) => return (String::new(), vec![problem])
+    };
+    sess.options.opt_level = directives.opt_level;
+    sess.options.shadowing = directives.shadowing;
+    let output = stages::compile(&mut sess, source, directives.stop_after, &directives.outputs);
+    let problems = annotations::check(&sess.source_map, sess.diagnostics().diagnostics());
+    (output, problems)
+}
+
+/// Lines which differ between the texts, `-` for expected and `+` for
+/// actual ones, with a line of context around them.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    // Longest common subsequence of lines, from the end of both texts
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((' ', expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', expected[i]));
+            i += 1;
+        } else {
+            lines.push(('+', actual[j]));
+            j += 1;
+        }
+    }
+    let changed = |idx: usize| lines.get(idx).is_some_and(|(kind, _)| *kind != ' ');
+    let mut result = String::new();
+    for (idx, (kind, line)) in lines.iter().enumerate() {
+        if *kind != ' ' || changed(idx + 1) || (idx > 0 && changed(idx - 1)) {
+            result.push_str(&format!("    {} {}\n", kind, line));
+        }
+    }
+    result
 }
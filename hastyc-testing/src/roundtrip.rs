@@ -0,0 +1,490 @@
+use std::panic::AssertUnwindSafe;
+
+use hastyc_ast_fmt::SourceFormatter;
+use hastyc_common::{error::CommonErrorContext, identifiers::{ASTNodeID, IDCounter, Ident, PkgID, SourceFileID, SymbolStorage}, path::{Path, PathSegment}, session::{Options, Session}, source::SourceFile, span::{MakeSpanned, Span}};
+use hastyc_parser::{lexer::Lexer, parser::{Attribute, AttributeKind, Attributes, BinOpKind, Block, DataVariant, DelimSpan, EnumDef, EnumVariant, Expr, ExprKind, Extern, FieldDef, FieldLitExpr, FnInput, FnRetTy, FnSignature, Function, Generics, ImportKind, ImportTree, Item, ItemKind, ItemStream, LetBinding, LetBindingKind, Lit, LitKind, ModuleDecl, Package, Parser, Pat, PatKind, RestExpr, Stmt, StmtKind, StmtStream, StructLiteral, Ty, TyKind, UnOpKind, Visibility}};
+use serde_json::Value;
+
+use crate::diff;
+
+const NAMES: &[&str] = &["a", "b", "c", "x", "y", "foo", "bar", "value", "count", "next_one", "io", "math"];
+const TYPE_NAMES: &[&str] = &["i32", "u8", "f64", "str", "Point", "Shape", "List"];
+const ATTR_NAMES: &[&str] = &["inline", "test", "allow", "cfg", "derive"];
+const WORDS: &[&str] = &["hello", "world", "lorem ipsum", "", "a b c"];
+const CHARS: &[char] = &['a', 'Z', '0', ' ', '"', 'ł'];
+const INT_SUFFIXES: &[&str] = &["u8", "i32", "usize"];
+/// Operators which the parser knows, others can't come from the source.
+const BIN_OPS: &[BinOpKind] = &[
+    BinOpKind::Or, BinOpKind::And, BinOpKind::Eq, BinOpKind::Ne,
+    BinOpKind::Lt, BinOpKind::Le, BinOpKind::Gt, BinOpKind::Ge,
+    BinOpKind::Add, BinOpKind::Sub, BinOpKind::Mul, BinOpKind::Div
+];
+
+/// Check that the random package of the seed is printed by the source
+/// formatter into code which parses back into the same package. Returns
+/// what to print about it if it isn't.
+pub fn check_seed(seed: u64) -> Result<(), String> {
+    let package = Generator::new(seed).package();
+    let text = match std::panic::catch_unwind(AssertUnwindSafe(|| SourceFormatter::format(&package, &empty_source(), &[]))) {
+        Ok(text) => text,
+        Err(_) => return Err("    formatter panicked".to_string())
+    };
+    let reparsed = match std::panic::catch_unwind(AssertUnwindSafe(|| reparse(&text))) {
+        Ok(reparsed) => reparsed,
+        Err(_) => Err("compiler panicked".to_string())
+    };
+    let message = match reparsed {
+        Ok(reparsed) if shape(&reparsed) == shape(&package) => return Ok(()),
+        Ok(reparsed) => diff(&pretty(&package), &pretty(&reparsed)),
+        Err(errors) => indent(&errors)
+    };
+    Err(format!("    formatted code:\n{}\n{}", indent(&text), message))
+}
+
+/// The formatter only reads comments and blank lines from the source,
+/// which a generated package doesn't have.
+fn empty_source() -> SourceFile {
+    SourceFile::new_virtual("<roundtrip>", String::new(), PkgID(0), SourceFileID(0))
+}
+
+/// Parse the formatted text, or return its diagnostics.
+fn reparse(text: &str) -> Result<Package, String> {
+    let sess = Session::new(Options::default());
+    let source = SourceFile::new_virtual("<roundtrip>", text.to_string(), PkgID(0), SourceFileID(0));
+    let package = Lexer::lex(&sess, &source).and_then(|tokens| Parser::parse_from_root(&sess, &source, &tokens));
+    package.ok_or_else(|| sess.diagnostics().diagnostics().iter()
+        .map(|diag| diag.fmt_plain(&CommonErrorContext::for_diagnostic(diag.as_ref(), &sess.source_map, &source)))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Syntax tree of the package without ids and positions, which are the
+/// only numbers in it, as symbols are serialized as their text.
+fn shape(package: &Package) -> Value {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Number(_) => *value = Value::Null,
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            Value::Object(fields) => fields.values_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value((&package.attrs, &package.items)).unwrap();
+    strip(&mut value);
+    value
+}
+
+fn pretty(package: &Package) -> String {
+    serde_json::to_string_pretty(&shape(package)).unwrap()
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("    {}\n", line)).collect()
+}
+
+/// Xorshift generator, random enough for picking syntax.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// Builds random packages out of syntax which the parser can produce.
+/// Spans are all dummies, so nothing but the tree itself is printed.
+struct Generator {
+    rng: Rng,
+    idgen: IDCounter,
+    symbols: SymbolStorage,
+    module_decls: Vec<ModuleDecl>,
+    /// Modules which the generated items are in.
+    module_path: Vec<Ident>
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            idgen: IDCounter::create(),
+            symbols: SymbolStorage::new(),
+            module_decls: Vec::new(),
+            module_path: Vec::new()
+        }
+    }
+
+    fn package(mut self) -> Package {
+        let attrs = self.attributes();
+        let items = self.items(3, false);
+        Package {
+            attrs,
+            items,
+            id: self.id(),
+            pkg: PkgID(0),
+            idgen: self.idgen,
+            symbol_storage: self.symbols,
+            span_table: None,
+            module_decls: self.module_decls
+        }
+    }
+
+    fn id(&self) -> ASTNodeID {
+        (&self.idgen).into()
+    }
+
+    fn ident_of(&mut self, text: &str) -> Ident {
+        Ident::new(self.symbols.get_or_register(text), Span::dummy())
+    }
+
+    fn ident(&mut self, names: &[&str]) -> Ident {
+        let name = *self.rng.pick(names);
+        self.ident_of(name)
+    }
+
+    fn path(&mut self, names: &[&str], max_len: usize) -> Path {
+        let len = 1 + self.rng.below(max_len);
+        Path {
+            segments: (0..len).map(|_| PathSegment::new(self.ident(names))).collect(),
+            span: Span::dummy()
+        }
+    }
+
+    fn vis(&mut self) -> Visibility {
+        if self.rng.chance(30) { Visibility::Public } else { Visibility::Inherited }
+    }
+
+    fn attributes(&mut self) -> Attributes {
+        let count = if self.rng.chance(20) { 1 + self.rng.below(2) } else { 0 };
+        let attributes = (0..count).map(|_| {
+            let ident = self.ident(ATTR_NAMES);
+            let kind = if self.rng.chance(50) {
+                AttributeKind::ListAttribute((0..self.rng.below(3)).map(|_| self.ident(NAMES)).collect())
+            } else { AttributeKind::FlagAttribute };
+            Attribute { ident, kind }
+        }).collect();
+        Attributes { attributes }
+    }
+
+    fn ty(&mut self) -> Ty {
+        let kind = match self.rng.below(6) {
+            0 => TyKind::Void,
+            1 => TyKind::Never,
+            _ => TyKind::Path(self.path(TYPE_NAMES, 2))
+        };
+        Ty { id: self.id(), kind, span: Span::dummy() }
+    }
+
+    fn pat(&mut self) -> Pat {
+        let ident = self.ident(NAMES);
+        Pat { id: self.id(), kind: PatKind::Ident(ident), span: Span::dummy() }
+    }
+
+    fn items(&mut self, depth: u32, in_block: bool) -> ItemStream {
+        let count = 1 + self.rng.below(4);
+        ItemStream::from_items((0..count).map(|_| self.item(depth, in_block)).collect())
+    }
+
+    /// Item of a module, or of a block, where attributes of items are
+    /// dropped by the parser and modules can't be in files of their own.
+    fn item(&mut self, depth: u32, in_block: bool) -> Item {
+        let attrs = if in_block { Attributes::empty() } else { self.attributes() };
+        let visibility = self.vis();
+        let mut ident = self.ident(NAMES);
+        let kind = match self.rng.below(if depth == 0 { 4 } else { 6 }) {
+            0 => ItemKind::Struct(self.data_variant(true)),
+            1 => ItemKind::Enum(EnumDef {
+                variants: (0..self.rng.below(4)).map(|_| EnumVariant {
+                    attrs: self.attributes(),
+                    id: self.id(),
+                    span: Span::dummy(),
+                    vis: self.vis(),
+                    ident: self.ident(TYPE_NAMES),
+                    data: self.data_variant(true)
+                }).collect()
+            }),
+            2 => {
+                let kind = *self.rng.pick(&[ImportKind::Relative, ImportKind::Super, ImportKind::Package]);
+                ident = Ident::dummy();
+                ItemKind::Import(kind, self.import_tree(2, false))
+            },
+            3 if !in_block && self.rng.chance(30) => {
+                let id = self.id();
+                self.module_decls.push(ModuleDecl {
+                    id,
+                    ident: ident.clone(),
+                    parents: self.module_path.clone(),
+                    file: None
+                });
+                return Item { attrs, id, visibility, kind: ItemKind::Module(ItemStream::empty()), ident, span: Span::dummy() }
+            },
+            3 => ItemKind::Fn(self.function(0)),
+            4 => ItemKind::Fn(self.function(depth - 1)),
+            _ => {
+                self.module_path.push(ident.clone());
+                let items = if self.rng.chance(20) { ItemStream::empty() } else { self.items(depth - 1, in_block) };
+                self.module_path.pop();
+                ItemKind::Module(items)
+            }
+        };
+        Item { attrs, id: self.id(), visibility, kind, ident, span: Span::dummy() }
+    }
+
+    fn data_variant(&mut self, allow_unit: bool) -> DataVariant {
+        let named = match self.rng.below(3) {
+            0 if allow_unit => return DataVariant::Unit,
+            0 | 1 => false,
+            _ => true
+        };
+        let fields = (0..self.rng.below(4)).map(|_| FieldDef {
+            attrs: self.attributes(),
+            id: self.id(),
+            span: Span::dummy(),
+            vis: self.vis(),
+            ident: named.then(|| self.ident(NAMES)),
+            ty: self.ty()
+        }).collect();
+        if named { DataVariant::Struct { fields } } else { DataVariant::Tuple { fields } }
+    }
+
+    /// Import tree, `nested` if it's inside of `{ ... }`.
+    fn import_tree(&mut self, depth: u32, nested: bool) -> ImportTree {
+        let prefix_len = self.rng.below(3);
+        let prefix = Path {
+            segments: (0..prefix_len).map(|_| PathSegment::new(self.ident(NAMES))).collect(),
+            span: Span::dummy()
+        };
+        let rename = |this: &mut Self| this.rng.chance(30).then(|| this.ident(NAMES));
+        match self.rng.below(if depth == 0 { 3 } else { 4 }) {
+            0 => ImportTree::glob(prefix, Span::dummy()),
+            // `self` without a prefix only makes sense in a group
+            1 if nested || prefix_len > 0 => {
+                let rename = rename(self);
+                ImportTree::self_import(prefix, rename, Span::dummy())
+            },
+            3 if prefix_len > 0 => {
+                let trees = (0..1 + self.rng.below(3)).map(|_| (self.import_tree(depth - 1, true), self.id())).collect();
+                ImportTree::nested(prefix, trees, Span::dummy())
+            },
+            _ => {
+                let mut path = prefix;
+                path.segments.push(PathSegment::new(self.ident(NAMES)));
+                let rename = rename(self);
+                ImportTree::simple(path, rename, Span::dummy())
+            }
+        }
+    }
+
+    fn function(&mut self, depth: u32) -> Function {
+        let ext = match self.rng.below(6) {
+            0 => Extern::Implicit(Span::dummy()),
+            1 => Extern::Explicit(self.symbols.get_or_register("C"), Span::dummy()),
+            _ => Extern::None
+        };
+        let mut inputs = Vec::new();
+        for idx in 0..self.rng.below(4) {
+            let attributes = self.attributes();
+            let (pat, ty) = if idx == 0 && self.rng.chance(30) {
+                (Pat { id: self.id(), kind: PatKind::SelfPat, span: Span::dummy() },
+                 Ty { id: self.id(), kind: TyKind::SelfTy, span: Span::dummy() })
+            } else { (self.pat(), self.ty()) };
+            inputs.push(FnInput { attributes, id: self.id(), span: Span::dummy(), pat, ty });
+        }
+        let output = if self.rng.chance(50) { FnRetTy::Ty(self.ty()) } else { FnRetTy::Default };
+        let body = match ext.is_extern() {
+            true => None,
+            false => Some(Box::new(self.block(depth)))
+        };
+        Function {
+            generics: Generics {},
+            signature: FnSignature { is_const: false, is_async: false, ext, inputs, output, span: Span::dummy() },
+            body
+        }
+    }
+
+    fn block(&mut self, depth: u32) -> Block {
+        let count = self.rng.below(4);
+        let stmts = (0..count).map(|idx| {
+            let kind = self.stmt_kind(depth, idx + 1 == count);
+            Stmt { id: self.id(), kind, span: Span::dummy() }
+        }).collect();
+        Block { stmts: StmtStream::from_vec(stmts), id: self.id(), span: Span::dummy() }
+    }
+
+    /// Expressions without semicolons can only be blocks and alike, or at the
+    /// end of the block.
+    fn stmt_kind(&mut self, depth: u32, last: bool) -> StmtKind {
+        match self.rng.below(10) {
+            0 | 1 => {
+                let attribs = self.attributes();
+                let pat = self.pat();
+                let ty = if self.rng.chance(40) { self.ty() } else {
+                    Ty { id: self.id(), kind: TyKind::Infer, span: Span::dummy() }
+                };
+                let kind = if self.rng.chance(80) {
+                    LetBindingKind::Init(Box::new(self.expr(depth)))
+                } else { LetBindingKind::Decl };
+                StmtKind::LetBinding(Box::new(LetBinding {
+                    id: self.id(),
+                    pat,
+                    ty: Some(ty),
+                    kind,
+                    span: Span::dummy(),
+                    attribs
+                }))
+            },
+            2 if depth > 0 => StmtKind::Item(Box::new(self.item(depth - 1, true))),
+            // Without a value `break` has to be followed by a semicolon
+            3 => StmtKind::Expr(Box::new(self.node(ExprKind::Break(None)))),
+            4..=6 => StmtKind::Expr(Box::new(self.expr(depth))),
+            _ => {
+                let expr = self.expr(depth);
+                if last || expr.kind.is_block_like() {
+                    StmtKind::ExprNS(Box::new(expr))
+                } else { StmtKind::Expr(Box::new(expr)) }
+            }
+        }
+    }
+
+    fn node(&self, kind: ExprKind) -> Expr {
+        Expr { id: self.id(), kind, span: Span::dummy(), attrs: Attributes::empty() }
+    }
+
+    fn boxed(&mut self, depth: u32) -> Box<Expr> {
+        Box::new(self.expr(depth))
+    }
+
+    fn literal(&mut self) -> Expr {
+        let sign = if self.rng.chance(20) { "-" } else { "" };
+        let (kind, text, suffix) = match self.rng.below(5) {
+            0 => (LitKind::String, format!("\"{}\"", self.rng.pick(WORDS)), None),
+            1 => (LitKind::Float, format!("{}{}.{}", sign, self.rng.below(100), self.rng.below(100)), None),
+            2 => (LitKind::Char, format!("'{}'", self.rng.pick(CHARS)), None),
+            _ => {
+                let suffix = self.rng.chance(20).then(|| *self.rng.pick(INT_SUFFIXES));
+                (LitKind::Integer, format!("{}{}", sign, self.rng.below(1000)), suffix)
+            }
+        };
+        let lit = Lit {
+            id: self.id(),
+            kind,
+            symbol: self.symbols.get_or_register(&text),
+            suffix: suffix.map(|suffix| self.symbols.get_or_register(suffix))
+        };
+        self.node(ExprKind::Literal(lit))
+    }
+
+    fn leaf(&mut self) -> Expr {
+        match self.rng.below(6) {
+            0 => self.node(ExprKind::Continue),
+            1 | 2 => self.literal(),
+            _ => {
+                let path = self.path(NAMES, 2);
+                self.node(ExprKind::Path(path))
+            }
+        }
+    }
+
+    /// Condition of `if` or `while`, or what `for` iterates over. A path
+    /// right before the block would start a struct literal, even in
+    /// parentheses, so the parser can't produce such heads.
+    fn head(&mut self, depth: u32) -> Box<Expr> {
+        for _ in 0..8 {
+            let expr = self.expr(depth);
+            if !ends_with_path(&expr) {
+                return Box::new(expr)
+            }
+        }
+        Box::new(self.literal())
+    }
+
+    fn if_expr(&mut self, depth: u32) -> Expr {
+        let condition = self.head(depth);
+        let block = Box::new(self.block(depth));
+        let else_expr = match self.rng.below(3) {
+            0 => None,
+            1 if depth > 0 => Some(Box::new(self.if_expr(depth - 1))),
+            _ => {
+                let block = self.block(depth);
+                Some(Box::new(self.node(ExprKind::Block(Box::new(block)))))
+            }
+        };
+        self.node(ExprKind::If(condition, block, else_expr))
+    }
+
+    fn expr(&mut self, depth: u32) -> Expr {
+        if depth == 0 {
+            return self.leaf()
+        }
+        let depth = depth - 1;
+        let kind = match self.rng.below(16) {
+            0 | 1 => return self.leaf(),
+            2 => ExprKind::Field(self.boxed(depth), self.ident(NAMES), Span::dummy()),
+            3 => ExprKind::Assign(self.boxed(depth), self.boxed(depth), Span::dummy()),
+            4 => {
+                let op = if self.rng.chance(50) { UnOpKind::Not } else { UnOpKind::Neg };
+                ExprKind::Unary(op.spanned(Span::dummy()), self.boxed(depth))
+            },
+            5..=7 => {
+                let op = self.rng.pick(BIN_OPS).clone();
+                ExprKind::Binary(op.spanned(Span::dummy()), self.boxed(depth), self.boxed(depth))
+            },
+            8 => {
+                let args = (0..self.rng.below(3)).map(|_| self.boxed(depth)).collect();
+                ExprKind::Call(self.boxed(depth), args, DelimSpan::new(Span::dummy(), Span::dummy()))
+            },
+            9 => return self.if_expr(depth),
+            10 => ExprKind::Block(Box::new(self.block(depth))),
+            11 => ExprKind::Loop(Box::new(self.block(depth))),
+            12 => ExprKind::While(self.head(depth), Box::new(self.block(depth))),
+            13 => ExprKind::For(self.pat(), self.head(depth), Box::new(self.block(depth))),
+            14 => ExprKind::Break(Some(self.boxed(depth))),
+            _ => {
+                let path = self.path(TYPE_NAMES, 2);
+                let fields = (0..self.rng.below(3)).map(|_| FieldLitExpr {
+                    attrs: self.attributes(),
+                    id: self.id(),
+                    span: Span::dummy(),
+                    ident: self.ident(NAMES),
+                    expr: self.boxed(depth)
+                }).collect();
+                let rest = match self.rng.below(4) {
+                    0 => RestExpr::Rest(Span::dummy()),
+                    1 => RestExpr::Valued(self.boxed(depth)),
+                    _ => RestExpr::None
+                };
+                ExprKind::StructLit(Box::new(StructLiteral { path, fields, rest }))
+            }
+        };
+        self.node(kind)
+    }
+}
+
+/// Whether the printed expression may end with a path. This doesn't know
+/// where the formatter adds parentheses, so it can be true when it doesn't.
+fn ends_with_path(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Path(..) => true,
+        ExprKind::Unary(_, ref last) | ExprKind::Binary(_, _, ref last)
+        | ExprKind::Assign(_, ref last, _) | ExprKind::Break(Some(ref last)) => ends_with_path(last),
+        _ => false
+    }
+}
@@ -0,0 +1,105 @@
+use std::{fmt::Write, path::Path, process::Command};
+
+use hastyc_codegen::{backend::{codegen, Artifact, CodegenInput}, link::Linker};
+use hastyc_common::session::BackendKind;
+use hastyc_passes::{hir::mono::main_fn, mir::ConstKind, passes::QueryContext};
+use hastyc_vm::{bytecode::Value, vm::{Vm, VmError}};
+
+/// Status native programs exit with when they panic, and which runs
+/// on the VM that fail are given.
+const PANIC_STATUS: i32 = 101;
+
+/// What running `main` of the program did.
+#[derive(Debug, PartialEq)]
+struct Execution {
+    status: i32,
+    stdout: String,
+    /// Why the program stopped, like `panicked at <location>: <message>`.
+    error: Option<String>
+}
+
+/// Compile the package with every backend there is and run it, printing
+/// what it did if every backend agrees, otherwise what each one did.
+/// Native programs are built in the directory.
+pub fn run_backends(cx: &QueryContext, dir: &Path) -> String {
+    let mut runs = vec![("vm", run_vm(cx))];
+    runs.push(("c", run_native(cx, BackendKind::C, dir)));
+    #[cfg(feature = "llvm")]
+    runs.push(("llvm", run_native(cx, BackendKind::Llvm, dir)));
+
+    let mut result = String::new();
+    if runs.iter().all(|(_, run)| *run == runs[0].1) {
+        print_run(&mut result, &runs[0].1);
+        return result
+    }
+    for (backend, run) in runs.iter() {
+        writeln!(result, "{}:", backend).unwrap();
+        print_run(&mut result, run);
+    }
+    result
+}
+
+fn print_run(out: &mut String, run: &Result<Execution, String>) {
+    let execution = match run {
+        Ok(execution) => execution,
+        Err(err) => return writeln!(out, "failed to run: {}", err).unwrap()
+    };
+    writeln!(out, "status: {}", execution.status).unwrap();
+    for line in execution.stdout.lines() {
+        writeln!(out, "stdout: {}", line).unwrap();
+    }
+    if let Some(ref error) = execution.error {
+        writeln!(out, "error: {}", error).unwrap();
+    }
+}
+
+fn input<'a>(cx: &'a QueryContext) -> Result<CodegenInput<'a>, String> {
+    let (Some(mir), Some(hir)) = (&cx.mir, &cx.hir) else { return Err("package has no MIR".to_string()) };
+    Ok(CodegenInput { mir, hir, symbols: &cx.package.symbol_storage, sources: &cx.sess.source_map, mono_items: &cx.mono_items })
+}
+
+/// Run on the VM, errors which aren't panics are located like native
+/// panics are, at the instruction the program stopped at.
+fn run_vm(cx: &QueryContext) -> Result<Execution, String> {
+    let input = input(cx)?;
+    let Artifact::Bytecode(program) = codegen(BackendKind::Vm, input).map_err(|err| err.to_string())? else {
+        return Err("VM backend made something other than bytecode".to_string())
+    };
+    let main = main_fn(input.hir).and_then(|main| program.fn_index(main)).ok_or("no `main` function to run")?;
+    let mut vm = Vm::new(&program, input.symbols, Vec::new());
+    let result = vm.call(main, Vec::new());
+    let location = vm.backtrace().first().and_then(|frame| frame.location);
+    let stdout = String::from_utf8_lossy(&vm.into_output()).into_owned();
+    let (status, error) = match result {
+        Ok(Value::Prim(constant)) => match constant.kind {
+            ConstKind::Int(value) => (value as u8 as i32, None),
+            _ => (0, None)
+        },
+        Ok(_) => (0, None),
+        Err(err @ VmError::Panic { .. }) => (PANIC_STATUS, Some(err.to_string())),
+        Err(err) => match location {
+            Some(location) => (PANIC_STATUS, Some(format!("panicked at {}: {}", location, err))),
+            None => (PANIC_STATUS, Some(err.to_string()))
+        }
+    };
+    Ok(Execution { status, stdout, error })
+}
+
+/// Build an executable with the native backend and run it.
+fn run_native(cx: &QueryContext, backend: BackendKind, dir: &Path) -> Result<Execution, String> {
+    let artifact = codegen(backend, input(cx)?).map_err(|err| err.to_string())?;
+    let linker = Linker::find().map_err(|err| err.to_string())?;
+    let exe = dir.join(backend.name());
+    match artifact {
+        Artifact::Source(source) => linker.compile_source(&source, &exe),
+        Artifact::Object(object) => linker.link(&object, &exe),
+        Artifact::Bytecode(_) => return Err("native backend made bytecode".to_string())
+    }.map_err(|err| err.to_string())?;
+    let output = Command::new(&exe).output().map_err(|err| format!("can't run `{}`: {}", exe.display(), err))?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim_end().to_string();
+    Ok(Execution {
+        status: output.status.code().ok_or_else(|| format!("killed by {}", output.status))?,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        error: (!stderr.is_empty()).then_some(stderr)
+    })
+}
@@ -0,0 +1,253 @@
+use std::fmt::Write;
+
+use hastyc_ast_fmt::{format_file, PackageASTPrettyPrinter};
+use hastyc_common::{error::CommonErrorContext, identifiers::SourceFileID, session::{EmitKind, Session}, source::SourceFile};
+use hastyc_parser::{lexer::{Lexer, TokenStream}, loader, parser::ParserOptions};
+
+use crate::run;
+use hastyc_passes::{hir::{captures::CapturePass, dead_code::DeadCodePass, lower::LowerToHir, mono::MonoCollectPass, passing::PassingModePass, typeck::TypeCheckPass}, mir::{build::BuildMir, pretty::MirDisplay, transform::OptimizeMir}, passes::{desugar::DesugarLoops, dump::{ResolvedNamesDisplay, ScopesDisplay, TypesDisplay}, manager::PassManager, name_resolve::NameResolvePass, prelude::InjectPrelude, transform::ASTMutPipeline, QueryContext}};
+
+/// Stage of the compiler, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Lex,
+    Parse,
+    Resolve,
+    /// Lowering to HIR and type checking.
+    Typecheck,
+    /// Building and optimizing MIR.
+    Mir,
+    /// Building MIR, compiling it with every backend and running `main`.
+    Run
+}
+
+impl Stage {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "lex" => Some(Self::Lex),
+            "parse" => Some(Self::Parse),
+            "resolve" => Some(Self::Resolve),
+            "typecheck" => Some(Self::Typecheck),
+            "mir" => Some(Self::Mir),
+            "run" => Some(Self::Run),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lex => "lex",
+            Self::Parse => "parse",
+            Self::Resolve => "resolve",
+            Self::Typecheck => "typecheck",
+            Self::Mir => "mir",
+            Self::Run => "run"
+        }
+    }
+
+    /// What is printed when nothing else is asked for.
+    pub fn default_output(self) -> Output {
+        match self {
+            Self::Lex => Output::Tokens,
+            Self::Parse => Output::Ast,
+            Self::Resolve => Output::Names,
+            Self::Typecheck => Output::Types,
+            Self::Mir => Output::Mir,
+            Self::Run => Output::Run
+        }
+    }
+}
+
+/// Something printed about the compiled package, each one is printed
+/// in a section with a `--- <name>` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// Tokens of the root file with their text and positions.
+    Tokens,
+    Ast,
+    /// Root file formatted like with `hastyc fmt`, or why it can't be.
+    Formatted,
+    /// What every path resolved to.
+    Names,
+    Scopes,
+    /// Types of all nodes found by type checking.
+    Types,
+    /// Optimized MIR of every function.
+    Mir,
+    /// Exit status, output and error of `main`, which every backend
+    /// has to agree on.
+    Run,
+    Diagnostics
+}
+
+impl Output {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tokens" => Some(Self::Tokens),
+            "ast" => Some(Self::Ast),
+            "formatted" => Some(Self::Formatted),
+            "names" => Some(Self::Names),
+            "scopes" => Some(Self::Scopes),
+            "types" => Some(Self::Types),
+            "mir" => Some(Self::Mir),
+            "run" => Some(Self::Run),
+            "diagnostics" => Some(Self::Diagnostics),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tokens => "tokens",
+            Self::Ast => "ast",
+            Self::Formatted => "formatted",
+            Self::Names => "names",
+            Self::Scopes => "scopes",
+            Self::Types => "types",
+            Self::Mir => "mir",
+            Self::Run => "run",
+            Self::Diagnostics => "diagnostics"
+        }
+    }
+
+    /// Stage which has to run for the output to exist.
+    pub fn stage(self) -> Stage {
+        match self {
+            Self::Tokens | Self::Diagnostics => Stage::Lex,
+            Self::Ast | Self::Formatted => Stage::Parse,
+            Self::Names | Self::Scopes => Stage::Resolve,
+            Self::Types => Stage::Typecheck,
+            Self::Mir => Stage::Mir,
+            Self::Run => Stage::Run
+        }
+    }
+}
+
+/// Compile the package with the root file loaded in the session up to
+/// the stage, and print the outputs in the order they are given. Parser
+/// recovers from errors when it can, and names are resolved even when
+/// there were errors before, so that as much as possible is printed.
+/// Outputs of stages which didn't get to run are left out.
+pub fn compile(sess: &mut Session, root: SourceFileID, stop_after: Stage, outputs: &[Output]) -> String {
+    let wants = |output| outputs.contains(&output);
+    let mut sections = Vec::new();
+    if let Some(file) = sess.source_map.get(root).filter(|_| wants(Output::Tokens)) {
+        // Errors are reported once the file is lexed for real
+        if let Some(tokens) = Lexer::lex(&Session::default(), file) {
+            sections.push((Output::Tokens, print_tokens(&tokens, file)));
+        }
+    }
+    if let Some(file) = sess.source_map.get(root).filter(|_| wants(Output::Formatted)) {
+        let text = match format_file(&Session::default(), file) {
+            Ok(text) => text,
+            Err(err) => format!("can't format: {}\n", err)
+        };
+        sections.push((Output::Formatted, text));
+    }
+
+    if stop_after == Stage::Lex {
+        if let Some(file) = sess.source_map.get(root) {
+            Lexer::lex(sess, file);
+        }
+    } else {
+        let options = ParserOptions { recover: true, ..ParserOptions::default() };
+        if let Some(mut package) = loader::load_package(sess, root, &options) {
+            if wants(Output::Ast) {
+                sections.push((Output::Ast, PackageASTPrettyPrinter::pretty_print(&package)));
+            }
+            if stop_after >= Stage::Resolve {
+                // Ribs are only recorded when they are asked for
+                if wants(Output::Scopes) && !sess.options.emit.contains(&EmitKind::Scopes) {
+                    sess.options.emit.push(EmitKind::Scopes);
+                }
+                // Pipeline stops early when there are errors, which are expected
+                // here, so names are resolved no matter how far it got
+                ASTMutPipeline::new()
+                    .with_pass(InjectPrelude)
+                    .with_pass(DesugarLoops)
+                    .run(&mut package, sess);
+                let mut ctx = QueryContext::for_package(sess, &package);
+                let mut passes = PassManager::new().with_pass(NameResolvePass::new());
+                if stop_after >= Stage::Typecheck {
+                    passes.add_pass(LowerToHir);
+                    passes.add_pass(TypeCheckPass);
+                }
+                if stop_after >= Stage::Mir {
+                    passes.add_pass(CapturePass);
+                    passes.add_pass(PassingModePass);
+                    passes.add_pass(DeadCodePass);
+                    passes.add_pass(MonoCollectPass);
+                    passes.add_pass(BuildMir);
+                    passes.add_pass(OptimizeMir::new());
+                }
+                passes.run(&mut ctx);
+                if wants(Output::Names) {
+                    sections.push((Output::Names, ResolvedNamesDisplay::new(&ctx).to_string()));
+                }
+                if wants(Output::Scopes) {
+                    sections.push((Output::Scopes, ScopesDisplay::new(&ctx).to_string()));
+                }
+                if wants(Output::Types) {
+                    sections.push((Output::Types, TypesDisplay::new(&ctx).to_string()));
+                }
+                if wants(Output::Mir) {
+                    if let (Some(mir), Some(hir)) = (&ctx.mir, &ctx.hir) {
+                        sections.push((Output::Mir, MirDisplay::new(mir, hir, &ctx.package.symbol_storage).to_string()));
+                    }
+                }
+                // Programs with errors have no MIR to run
+                if wants(Output::Run) && !ctx.has_errors() {
+                    let dir = std::env::temp_dir().join(format!("hastyc-testing-{}", std::process::id()));
+                    let output = match std::fs::create_dir_all(&dir) {
+                        Ok(()) => run::run_backends(&ctx, &dir),
+                        Err(err) => format!("can't create `{}`: {}\n", dir.display(), err)
+                    };
+                    let _ = std::fs::remove_dir_all(&dir);
+                    sections.push((Output::Run, output));
+                }
+            }
+        }
+    }
+    sections.push((Output::Diagnostics, print_diagnostics(sess)));
+
+    let mut result = String::new();
+    for output in outputs.iter() {
+        if let Some((_, text)) = sections.iter().find(|(section, _)| section == output) {
+            writeln!(result, "--- {}", output.name()).unwrap();
+            result.push_str(text);
+        }
+    }
+    result
+}
+
+fn print_tokens(tokens: &TokenStream, source: &SourceFile) -> String {
+    let mut result = String::new();
+    for token in tokens.iter() {
+        let (start_line, start_col) = source.lookup_line(token.span.start);
+        let (end_line, end_col) = source.lookup_line(token.span.end);
+        writeln!(
+            result,
+            "{:?} {:?} {}:{}-{}:{}",
+            token.kind,
+            source.get_span(&token.span),
+            start_line,
+            start_col,
+            end_line,
+            end_col
+        ).unwrap();
+    }
+    result
+}
+
+/// Diagnostics in plain text, with an empty line after each one.
+fn print_diagnostics(sess: &Session) -> String {
+    let mut result = String::new();
+    // Root file is loaded first
+    let Some(fallback) = sess.source_map.files().first() else { return result };
+    for diag in sess.diagnostics().diagnostics() {
+        let ctx = CommonErrorContext::for_diagnostic(diag.as_ref(), &sess.source_map, fallback);
+        result.push_str(&diag.fmt_plain(&ctx));
+        result.push('\n');
+    }
+    result
+}
@@ -0,0 +1,55 @@
+//! Recursive `fib` run on the VM, run with `cargo bench -p hastyc-vm --bench fib`.
+
+use std::time::{Duration, Instant};
+
+use hastyc_common::{identifiers::{PkgID, SourceFileID}, session::Session, source::SourceFile};
+use hastyc_parser::{lexer::Lexer, parser::Parser};
+use hastyc_passes::{hir::{lower::LowerToHir, mono::main_fn, typeck::TypeCheckPass}, mir::build::BuildMir, passes::{manager::PassManager, name_resolve::NameResolvePass, QueryContext}};
+use hastyc_vm::{compile::compile, vm::Vm};
+
+const N: i32 = 25;
+const RUNS: usize = 10;
+
+fn fib(n: i32) -> i32 {
+    if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+}
+
+fn main() {
+    let code = format!("
+        fn fib(n: i32) -> i32 {{
+            if n < 2 {{ n }} else {{ fib(n - 1) + fib(n - 2) }}
+        }}
+
+        pub fn main() -> i32 {{ fib({}) }}
+    ", N);
+    let source = SourceFile::new_raw(code, PkgID::new_unique(), SourceFileID::new_unique());
+    let sess = Session::default();
+    let tokens = Lexer::lex(&sess, &source).expect("benchmark should lex");
+    let package = Parser::parse_from_root(&sess, &source, &tokens).expect("benchmark should parse");
+    let mut ctx = QueryContext::for_package(&sess, &package);
+    PassManager::new()
+        .with_pass(NameResolvePass::new())
+        .with_pass(LowerToHir)
+        .with_pass(TypeCheckPass)
+        .with_pass(BuildMir)
+        .run(&mut ctx);
+    assert!(!sess.has_errors(), "benchmark should compile");
+    let (Some(mir), Some(hir)) = (&ctx.mir, &ctx.hir) else { unreachable!() };
+
+    let start = Instant::now();
+    let program = compile(mir, hir, &sess.source_map);
+    let compile_time = start.elapsed();
+    let main = program.fn_index(main_fn(hir).unwrap()).unwrap();
+
+    let mut best: Option<Duration> = None;
+    for _ in 0..RUNS {
+        let mut vm = Vm::new(&program, &package.symbol_storage, std::io::sink());
+        let start = Instant::now();
+        let result = vm.call(main, Vec::new()).expect("benchmark should run");
+        let elapsed = start.elapsed();
+        assert_eq!(result.to_string(), fib(N).to_string());
+        best = Some(best.map_or(elapsed, |best| best.min(elapsed)));
+    }
+    println!("compile to bytecode: {:?}", compile_time);
+    println!("vm, fib({}): {:?}", N, best.unwrap());
+}
@@ -0,0 +1,358 @@
+use std::{collections::HashMap, fmt::{Display, Formatter}, rc::Rc};
+
+use hastyc_common::identifiers::{Symbol, SymbolStorage};
+use hastyc_parser::parser::{BinOpKind, UnOpKind};
+use hastyc_passes::{hir::{ty::Ty, DefId}, mir::{ConstKind, Constant}, passes::{builtin::PrimTy, intrinsic::Intrinsic}};
+
+/// Index of a function in `Program::functions`.
+pub type FnIndex = u32;
+
+/// Package compiled to bytecode, with everything the VM needs to run it.
+#[derive(Debug, Default)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    /// Constant pool shared by all functions. Strings are kept as their
+    /// symbols, so their text lives in the symbol storage of the package.
+    pub constants: Vec<Value>,
+    /// Structs and variants which are constructed, indexed by `Value::Adt`.
+    pub adts: Vec<DefId>,
+    pub(crate) by_def: HashMap<DefId, FnIndex>
+}
+
+impl Program {
+    /// Function compiled from the definition.
+    pub fn fn_index(&self, def: DefId) -> Option<FnIndex> {
+        self.by_def.get(&def).copied()
+    }
+
+    pub fn function(&self, index: FnIndex) -> &Function {
+        &self.functions[index as usize]
+    }
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub owner: DefId,
+    pub name: Symbol,
+    pub arg_count: u32,
+    /// Number of locals, including the return place and arguments.
+    pub local_count: u32,
+    pub code: FnCode
+}
+
+#[derive(Debug)]
+pub enum FnCode {
+    Bytecode(Chunk),
+    /// Function of the prelude, which the VM implements natively.
+    Intrinsic(Intrinsic),
+    /// Function declared without a body, which the embedder of the VM
+    /// can bind to a host function, see `Vm::register`.
+    Extern(ExternSig)
+}
+
+/// Types of an extern function, which arguments and results of host
+/// functions are converted between, see `crate::ffi`.
+#[derive(Debug)]
+pub struct ExternSig {
+    pub inputs: Vec<Ty>,
+    pub output: Ty
+}
+
+/// Encoded instructions of a single function, see `Instr::encode`.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    /// Fields walked by `Instr::StoreField`, from the outermost one.
+    pub projections: Vec<Vec<u32>>,
+    /// Offsets where code of a statement starts, with its location in
+    /// the source, ordered by offset.
+    pub lines: Vec<(u32, SourceLoc)>
+}
+
+impl Chunk {
+    /// Location of the code that the instruction at the offset was
+    /// compiled from.
+    pub fn location(&self, offset: usize) -> Option<SourceLoc> {
+        let idx = self.lines.partition_point(|&(at, _)| at as usize <= offset);
+        idx.checked_sub(1).map(|idx| self.lines[idx].1)
+    }
+}
+
+/// Line and column in a source file, both starting at 1, the name of
+/// the file is interned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLoc {
+    pub file: Symbol,
+    pub line: u32,
+    pub col: u32
+}
+
+impl Display for SourceLoc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.as_str(), self.line, self.col)
+    }
+}
+
+/// Value that the VM works with. Primitive values are kept as MIR
+/// constants, so that arithmetic follows the same rules as constant
+/// evaluation, see `hastyc_passes::mir::const_eval`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Primitive value or `()`, never a function.
+    Prim(Constant),
+    Fn(FnIndex),
+    /// Struct or variant, shared until one of its fields is assigned.
+    Adt(Rc<AdtValue>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdtValue {
+    /// Index in `Program::adts`.
+    pub adt: u32,
+    pub fields: Vec<Value>
+}
+
+impl Value {
+    pub const VOID: Value = Value::Prim(Constant { kind: ConstKind::Void, ty: Ty::Void });
+
+    pub fn int(value: i128, prim: PrimTy) -> Self {
+        Self::Prim(Constant { kind: ConstKind::Int(value as u128), ty: Ty::Prim(prim) })
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Prim(constant) => match constant.kind {
+                ConstKind::Int(value) => match constant.ty {
+                    Ty::Prim(PrimTy::Int(_)) => write!(f, "{}", value as i128),
+                    _ => write!(f, "{}", value)
+                },
+                ConstKind::Float(value) => write!(f, "{:?}", value),
+                ConstKind::Bool(value) => write!(f, "{}", value),
+                ConstKind::Char(value) => write!(f, "{:?}", value),
+                ConstKind::Str(symbol) => write!(f, "{:?}", symbol.as_str()),
+                ConstKind::Fn(_) => f.write_str("fn"),
+                ConstKind::Void => f.write_str("()")
+            },
+            Self::Fn(index) => write!(f, "fn#{}", index),
+            Self::Adt(adt) => {
+                write!(f, "adt#{} {{", adt.adt)?;
+                for (idx, field) in adt.fields.iter().enumerate() {
+                    let sep = if idx == 0 { " " } else { ", " };
+                    write!(f, "{}{}", sep, field)?;
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+/// Single instruction. Instructions work on a stack of values, locals
+/// of the running function are at its bottom, with the return place
+/// first, like in MIR. Operands are encoded as little endian `u32`,
+/// except for operators which are a single byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    /// Push constant from the pool.
+    Const(u32),
+    /// Push value of the local.
+    Load(u32),
+    /// Pop value into the local.
+    Store(u32),
+    /// Pop struct or variant, pushing the field with the index.
+    Field(u32),
+    /// Pop value into the field of the local, walking fields of
+    /// `Chunk::projections` with the index.
+    StoreField { local: u32, projection: u32 },
+    /// Pop values of all fields, the last field on the top, and push
+    /// struct or variant of `Program::adts` with the index.
+    Aggregate { adt: u32, fields: u32 },
+    /// Pop rhs and lhs, pushing the result, index in `BIN_OPS`.
+    Binary(u8),
+    /// Replace the top with the result, index in `UN_OPS`.
+    Unary(u8),
+    /// Continue at the offset in the chunk.
+    Jump(u32),
+    /// Pop bool, continuing at the offset if it is false.
+    JumpIfFalse(u32),
+    /// Call function below the arguments, which are on the top. The
+    /// function is replaced by its result after it returns.
+    Call(u32),
+    /// Return value of the return place to the caller.
+    Return,
+    /// Code that can't be reached was reached.
+    Unreachable
+}
+
+/// Binary operators, by their encoded index.
+pub const BIN_OPS: [BinOpKind; 18] = [
+    BinOpKind::Add, BinOpKind::Sub, BinOpKind::Mul,
+    BinOpKind::Div, BinOpKind::Rem, BinOpKind::And,
+    BinOpKind::Or, BinOpKind::BitAnd, BinOpKind::BitXor,
+    BinOpKind::BitOr, BinOpKind::Shl, BinOpKind::Shr,
+    BinOpKind::Eq, BinOpKind::Lt, BinOpKind::Le,
+    BinOpKind::Ne, BinOpKind::Ge, BinOpKind::Gt
+];
+
+/// Unary operators, by their encoded index.
+pub const UN_OPS: [UnOpKind; 2] = [UnOpKind::Neg, UnOpKind::Not];
+
+pub fn bin_op_index(op: &BinOpKind) -> u8 {
+    match op {
+        BinOpKind::Add => 0, BinOpKind::Sub => 1, BinOpKind::Mul => 2,
+        BinOpKind::Div => 3, BinOpKind::Rem => 4, BinOpKind::And => 5,
+        BinOpKind::Or => 6, BinOpKind::BitAnd => 7, BinOpKind::BitXor => 8,
+        BinOpKind::BitOr => 9, BinOpKind::Shl => 10, BinOpKind::Shr => 11,
+        BinOpKind::Eq => 12, BinOpKind::Lt => 13, BinOpKind::Le => 14,
+        BinOpKind::Ne => 15, BinOpKind::Ge => 16, BinOpKind::Gt => 17
+    }
+}
+
+pub fn un_op_index(op: &UnOpKind) -> u8 {
+    match op {
+        UnOpKind::Neg => 0,
+        UnOpKind::Not => 1
+    }
+}
+
+mod opcode {
+    pub const CONST: u8 = 0;
+    pub const LOAD: u8 = 1;
+    pub const STORE: u8 = 2;
+    pub const FIELD: u8 = 3;
+    pub const STORE_FIELD: u8 = 4;
+    pub const AGGREGATE: u8 = 5;
+    pub const BINARY: u8 = 6;
+    pub const UNARY: u8 = 7;
+    pub const JUMP: u8 = 8;
+    pub const JUMP_IF_FALSE: u8 = 9;
+    pub const CALL: u8 = 10;
+    pub const RETURN: u8 = 11;
+    pub const UNREACHABLE: u8 = 12;
+}
+
+impl Instr {
+    /// Append the instruction to the code.
+    pub fn encode(self, code: &mut Vec<u8>) {
+        let (opcode, operands): (u8, &[u32]) = match self {
+            Self::Const(idx) => (opcode::CONST, &[idx]),
+            Self::Load(local) => (opcode::LOAD, &[local]),
+            Self::Store(local) => (opcode::STORE, &[local]),
+            Self::Field(idx) => (opcode::FIELD, &[idx]),
+            Self::StoreField { local, projection } => (opcode::STORE_FIELD, &[local, projection]),
+            Self::Aggregate { adt, fields } => (opcode::AGGREGATE, &[adt, fields]),
+            Self::Binary(op) => return code.extend([opcode::BINARY, op]),
+            Self::Unary(op) => return code.extend([opcode::UNARY, op]),
+            Self::Jump(target) => (opcode::JUMP, &[target]),
+            Self::JumpIfFalse(target) => (opcode::JUMP_IF_FALSE, &[target]),
+            Self::Call(args) => (opcode::CALL, &[args]),
+            Self::Return => (opcode::RETURN, &[]),
+            Self::Unreachable => (opcode::UNREACHABLE, &[])
+        };
+        code.push(opcode);
+        for operand in operands {
+            code.extend(operand.to_le_bytes());
+        }
+    }
+
+    /// Instruction at the offset, together with offset of the next one.
+    /// Code must come from `encode`, anything else panics.
+    pub fn decode(code: &[u8], at: usize) -> (Self, usize) {
+        Self::try_decode(code, at).unwrap_or_else(|| panic!("invalid instruction at {}", at))
+    }
+
+    /// Like `decode`, but None if there is no valid instruction at the
+    /// offset, for code which doesn't come straight from `encode`.
+    pub fn try_decode(code: &[u8], at: usize) -> Option<(Self, usize)> {
+        let operand = |idx: usize| {
+            let start = at + 1 + idx * 4;
+            code.get(start..start + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        Some(match *code.get(at)? {
+            opcode::CONST => (Self::Const(operand(0)?), at + 5),
+            opcode::LOAD => (Self::Load(operand(0)?), at + 5),
+            opcode::STORE => (Self::Store(operand(0)?), at + 5),
+            opcode::FIELD => (Self::Field(operand(0)?), at + 5),
+            opcode::STORE_FIELD => (Self::StoreField { local: operand(0)?, projection: operand(1)? }, at + 9),
+            opcode::AGGREGATE => (Self::Aggregate { adt: operand(0)?, fields: operand(1)? }, at + 9),
+            opcode::BINARY => (Self::Binary(*code.get(at + 1)?), at + 2),
+            opcode::UNARY => (Self::Unary(*code.get(at + 1)?), at + 2),
+            opcode::JUMP => (Self::Jump(operand(0)?), at + 5),
+            opcode::JUMP_IF_FALSE => (Self::JumpIfFalse(operand(0)?), at + 5),
+            opcode::CALL => (Self::Call(operand(0)?), at + 5),
+            opcode::RETURN => (Self::Return, at + 1),
+            opcode::UNREACHABLE => (Self::Unreachable, at + 1),
+            _ => return None
+        })
+    }
+}
+
+/// Renders bytecode of the whole program, used for debugging, like:
+///
+/// ```text
+/// fn add_one(1 args, 2 locals)
+///     0000  load _1
+///     0005  const #0 (1)
+///     0010  binary Add
+///     0012  store _0
+///     0017  return
+/// ```
+pub struct Disassembly<'a> {
+    program: &'a Program,
+    symbols: &'a SymbolStorage
+}
+
+impl<'a> Disassembly<'a> {
+    pub fn new(program: &'a Program, symbols: &'a SymbolStorage) -> Self {
+        Self { program, symbols }
+    }
+}
+
+impl Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for function in self.program.functions.iter() {
+            let name = self.symbols.text_of(function.name).unwrap_or("?");
+            let chunk = match function.code {
+                FnCode::Bytecode(ref chunk) => chunk,
+                FnCode::Intrinsic(intrinsic) => {
+                    writeln!(f, "fn {}({} args) = intrinsic {:?}", name, function.arg_count, intrinsic)?;
+                    continue
+                },
+                FnCode::Extern(_) => {
+                    writeln!(f, "fn {}({} args) = extern", name, function.arg_count)?;
+                    continue
+                }
+            };
+            writeln!(f, "fn {}({} args, {} locals)", name, function.arg_count, function.local_count)?;
+            let mut at = 0;
+            while at < chunk.code.len() {
+                let (instr, next) = Instr::decode(&chunk.code, at);
+                write!(f, "    {:04}  ", at)?;
+                match instr {
+                    Instr::Const(idx) => writeln!(f, "const #{} ({})", idx, self.program.constants[idx as usize])?,
+                    Instr::Load(local) => writeln!(f, "load _{}", local)?,
+                    Instr::Store(local) => writeln!(f, "store _{}", local)?,
+                    Instr::Field(idx) => writeln!(f, "field {}", idx)?,
+                    Instr::StoreField { local, projection } => {
+                        write!(f, "store _{}", local)?;
+                        for field in chunk.projections[projection as usize].iter() {
+                            write!(f, ".{}", field)?;
+                        }
+                        writeln!(f)?;
+                    },
+                    Instr::Aggregate { adt, fields } => writeln!(f, "aggregate adt#{} {}", adt, fields)?,
+                    Instr::Binary(op) => writeln!(f, "binary {:?}", BIN_OPS[op as usize])?,
+                    Instr::Unary(op) => writeln!(f, "unary {:?}", UN_OPS[op as usize])?,
+                    Instr::Jump(target) => writeln!(f, "jump {:04}", target)?,
+                    Instr::JumpIfFalse(target) => writeln!(f, "jump_if_false {:04}", target)?,
+                    Instr::Call(args) => writeln!(f, "call {}", args)?,
+                    Instr::Return => writeln!(f, "return")?,
+                    Instr::Unreachable => writeln!(f, "unreachable")?
+                }
+                at = next;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use hastyc_common::{identifiers::Symbol, source::SourceMap, span::Span};
+use hastyc_passes::{hir::{ty::Ty, typeck::declared_ty, DefId, DefKind, Hir}, mir::{BasicBlock, ConstKind, Constant, Mir, MirBody, Operand, Place, PlaceElem, Rvalue, StatementKind, TerminatorKind}, passes::{builtin::PrimTy, intrinsic::{location, Intrinsic}}};
+
+use crate::bytecode::{bin_op_index, un_op_index, Chunk, ExternSig, FnCode, FnIndex, Function, Instr, Program, SourceLoc, Value};
+
+/// Compile MIR of the package to bytecode. Every body becomes a function
+/// of its own, and so does every function without a body which is used,
+/// these are either intrinsics or externs. Blocks are laid out in order,
+/// so jumps to the next block are left out.
+pub fn compile(mir: &Mir, hir: &Hir, sources: &SourceMap) -> Program {
+    let mut compiler = Compiler::new(hir, sources);
+    for body in mir.bodies.values() {
+        compiler.declare_body(body);
+    }
+    for body in mir.bodies.values() {
+        compiler.define_body(body);
+    }
+    compiler.finish()
+}
+
+/// Constants by their value, floats by their bits, so that the pool
+/// doesn't keep the same constant twice.
+#[derive(PartialEq, Eq, Hash)]
+enum ConstKey {
+    Int(u128, Ty),
+    Float(u64, Ty),
+    Bool(bool),
+    Char(char),
+    Str(Symbol),
+    Fn(FnIndex),
+    Void
+}
+
+/// Compiler of single functions, for backends which choose the functions
+/// themselves. Functions have to be declared before any body is defined,
+/// so that calls can refer to functions which are compiled later.
+pub struct Compiler<'a> {
+    hir: &'a Hir,
+    /// Sources of the package, for locations passed to intrinsics.
+    sources: &'a SourceMap,
+    program: Program,
+    constants: HashMap<ConstKey, u32>,
+    adts: HashMap<DefId, u32>
+}
+
+/// Chunk that is being compiled, with jumps waiting for their targets.
+struct ChunkBuilder {
+    chunk: Chunk,
+    /// Offsets of operands of jumps, together with blocks they jump to.
+    fixups: Vec<(usize, BasicBlock)>
+}
+
+impl ChunkBuilder {
+    fn emit(&mut self, instr: Instr) {
+        instr.encode(&mut self.chunk.code);
+    }
+
+    /// Mark code which follows as compiled from the location, unless
+    /// it is where the previous code is from.
+    fn locate(&mut self, location: Option<SourceLoc>) {
+        let Some(location) = location else { return };
+        let at = self.chunk.code.len() as u32;
+        match self.chunk.lines.last_mut() {
+            Some(&mut (_, last)) if last == location => {},
+            // Previous statement compiled to no code
+            Some(last) if last.0 == at => last.1 = location,
+            _ => self.chunk.lines.push((at, location))
+        }
+    }
+
+    fn jump(&mut self, target: BasicBlock, if_false: bool) {
+        self.fixups.push((self.chunk.code.len() + 1, target));
+        self.emit(if if_false { Instr::JumpIfFalse(0) } else { Instr::Jump(0) });
+    }
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(hir: &'a Hir, sources: &'a SourceMap) -> Self {
+        Self { hir, sources, program: Program::default(), constants: HashMap::new(), adts: HashMap::new() }
+    }
+
+    pub fn declare_body(&mut self, body: &MirBody) {
+        self.add_function(body.owner, body.arg_count as u32, body.locals.len() as u32, FnCode::Bytecode(Chunk::default()));
+    }
+
+    /// Declare function without a body, calls to it are run by the VM
+    /// if it is an intrinsic and by the host function bound to it otherwise.
+    pub fn declare_extern(&mut self, def: DefId) {
+        self.function(def);
+    }
+
+    pub fn define_body(&mut self, body: &MirBody) {
+        let chunk = self.body(body);
+        let index = self.program.by_def[&body.owner];
+        self.program.functions[index as usize].code = FnCode::Bytecode(chunk);
+    }
+
+    pub fn finish(self) -> Program {
+        self.program
+    }
+
+    fn add_function(&mut self, owner: DefId, arg_count: u32, local_count: u32, code: FnCode) -> FnIndex {
+        let index = self.program.functions.len() as FnIndex;
+        let name = self.hir.def(owner).ident.symbol;
+        self.program.functions.push(Function { owner, name, arg_count, local_count, code });
+        self.program.by_def.insert(owner, index);
+        index
+    }
+
+    /// Function of the definition, adding functions without a body
+    /// the first time they are used.
+    fn function(&mut self, def: DefId) -> FnIndex {
+        if let Some(index) = self.program.fn_index(def) {
+            return index
+        }
+        let DefKind::Fn { ref sig, .. } = self.hir.def(def).kind else { unreachable!("called definition is not a function") };
+        let mut arg_count = sig.inputs.len() as u32;
+        let code = match Intrinsic::of_def(self.hir, def) {
+            Some(intrinsic) => {
+                arg_count += intrinsic.takes_location() as u32;
+                FnCode::Intrinsic(intrinsic)
+            },
+            None => FnCode::Extern(ExternSig {
+                inputs: sig.inputs.iter().map(|ty| declared_ty(self.hir, ty).unwrap_or(Ty::Err)).collect(),
+                output: sig.output.as_ref().map_or(Ty::Void, |ty| declared_ty(self.hir, ty).unwrap_or(Ty::Err))
+            })
+        };
+        self.add_function(def, arg_count, arg_count + 1, code)
+    }
+
+    fn constant(&mut self, constant: &Constant) -> u32 {
+        let (key, value) = match constant.kind {
+            ConstKind::Int(value) => (ConstKey::Int(value, constant.ty), Value::Prim(constant.clone())),
+            ConstKind::Float(value) => (ConstKey::Float(value.to_bits(), constant.ty), Value::Prim(constant.clone())),
+            ConstKind::Bool(value) => (ConstKey::Bool(value), Value::Prim(constant.clone())),
+            ConstKind::Char(value) => (ConstKey::Char(value), Value::Prim(constant.clone())),
+            ConstKind::Str(symbol) => (ConstKey::Str(symbol), Value::Prim(constant.clone())),
+            ConstKind::Fn(def) => {
+                let index = self.function(def);
+                (ConstKey::Fn(index), Value::Fn(index))
+            },
+            ConstKind::Void => (ConstKey::Void, Value::VOID)
+        };
+        let pool = &mut self.program.constants;
+        *self.constants.entry(key).or_insert_with(|| {
+            pool.push(value);
+            pool.len() as u32 - 1
+        })
+    }
+
+    fn adt(&mut self, def: DefId) -> u32 {
+        let adts = &mut self.program.adts;
+        *self.adts.entry(def).or_insert_with(|| {
+            adts.push(def);
+            adts.len() as u32 - 1
+        })
+    }
+
+    fn body(&mut self, body: &MirBody) -> Chunk {
+        let mut builder = ChunkBuilder { chunk: Chunk::default(), fixups: Vec::new() };
+        let mut offsets = Vec::with_capacity(body.blocks.len());
+        for (id, block) in body.basic_blocks() {
+            offsets.push(builder.chunk.code.len() as u32);
+            let next = BasicBlock(id.0 + 1);
+            for stmt in block.stmts.iter() {
+                builder.locate(self.source_loc(stmt.span));
+                match stmt.kind {
+                    StatementKind::Assign(ref place, ref rvalue) => {
+                        self.rvalue(&mut builder, rvalue);
+                        self.store(&mut builder, place);
+                    },
+                    StatementKind::Nop => {}
+                }
+            }
+            builder.locate(self.source_loc(block.terminator.span));
+            match block.terminator.kind {
+                TerminatorKind::Goto(target) => {
+                    if target != next {
+                        builder.jump(target, false);
+                    }
+                },
+                TerminatorKind::If { ref cond, then, otherwise } => {
+                    self.operand(&mut builder, cond);
+                    builder.jump(otherwise, true);
+                    if then != next {
+                        builder.jump(then, false);
+                    }
+                },
+                TerminatorKind::Call { ref func, ref args, ref dest, target } => {
+                    self.operand(&mut builder, func);
+                    for arg in args.iter() {
+                        self.operand(&mut builder, arg);
+                    }
+                    let mut arg_count = args.len() as u32;
+                    let callee = match func {
+                        Operand::Constant(constant) => constant.ty,
+                        Operand::Copy(place) => body.local(place.local).ty
+                    };
+                    let intrinsic = match callee {
+                        Ty::FnDef(def) => Intrinsic::of_def(self.hir, def),
+                        _ => None
+                    };
+                    if intrinsic.is_some_and(Intrinsic::takes_location) {
+                        let location = location(self.sources, block.terminator.span);
+                        let idx = self.constant(&Constant { kind: ConstKind::Str(Symbol::intern(&location)), ty: Ty::Prim(PrimTy::Str) });
+                        builder.emit(Instr::Const(idx));
+                        arg_count += 1;
+                    }
+                    builder.emit(Instr::Call(arg_count));
+                    self.store(&mut builder, dest);
+                    match target {
+                        Some(target) if target != next => builder.jump(target, false),
+                        Some(_) => {},
+                        None => builder.emit(Instr::Unreachable)
+                    }
+                },
+                TerminatorKind::Return => builder.emit(Instr::Return),
+                TerminatorKind::Unreachable => builder.emit(Instr::Unreachable)
+            }
+        }
+        let ChunkBuilder { mut chunk, fixups } = builder;
+        for (at, target) in fixups {
+            chunk.code[at..at + 4].copy_from_slice(&offsets[target.index()].to_le_bytes());
+        }
+        chunk
+    }
+
+    fn source_loc(&self, span: Span) -> Option<SourceLoc> {
+        let file = self.sources.get(span.source)?;
+        let (line, col) = file.lookup_line(span.start);
+        Some(SourceLoc { file: Symbol::intern(&file.name.to_string()), line, col })
+    }
+
+    fn rvalue(&mut self, builder: &mut ChunkBuilder, rvalue: &Rvalue) {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(builder, operand),
+            Rvalue::BinaryOp(op, lhs, rhs) => {
+                self.operand(builder, lhs);
+                self.operand(builder, rhs);
+                builder.emit(Instr::Binary(bin_op_index(op)));
+            },
+            Rvalue::UnaryOp(op, operand) => {
+                self.operand(builder, operand);
+                builder.emit(Instr::Unary(un_op_index(op)));
+            },
+            Rvalue::Aggregate(def, fields) => {
+                for field in fields.iter() {
+                    self.operand(builder, field);
+                }
+                let adt = self.adt(*def);
+                builder.emit(Instr::Aggregate { adt, fields: fields.len() as u32 });
+            }
+        }
+    }
+
+    fn operand(&mut self, builder: &mut ChunkBuilder, operand: &Operand) {
+        match operand {
+            Operand::Copy(place) => {
+                builder.emit(Instr::Load(place.local.0));
+                for elem in place.projection.iter() {
+                    let PlaceElem::Field(idx) = *elem;
+                    builder.emit(Instr::Field(idx));
+                }
+            },
+            Operand::Constant(constant) => {
+                let idx = self.constant(constant);
+                builder.emit(Instr::Const(idx));
+            }
+        }
+    }
+
+    fn store(&mut self, builder: &mut ChunkBuilder, place: &Place) {
+        if place.projection.is_empty() {
+            return builder.emit(Instr::Store(place.local.0))
+        }
+        let fields = place.projection.iter().map(|elem| {
+            let PlaceElem::Field(idx) = *elem;
+            idx
+        });
+        builder.chunk.projections.push(fields.collect());
+        let projection = builder.chunk.projections.len() as u32 - 1;
+        builder.emit(Instr::StoreField { local: place.local.0, projection });
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::Display;
+
+use hastyc_common::identifiers::{Symbol, SymbolStorage};
+use hastyc_passes::{hir::ty::Ty, mir::{ConstKind, Constant}, passes::builtin::{FloatTy, PrimTy}};
+
+use crate::bytecode::Value;
+
+/// Host function bound to an extern, see `Vm::register`. It gets the
+/// arguments in order and fails with a message which stops the program.
+pub type HostFn<'a> = Box<dyn FnMut(&[HostValue]) -> Result<HostValue, String> + 'a>;
+
+/// Value passed between the program and host functions. Only primitive
+/// values can be passed, structs and enums stay inside the VM.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostValue {
+    /// Value of any signed integer type.
+    Int(i64),
+    /// Value of any unsigned integer type.
+    Uint(u64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    /// `()`, returned by functions without a return type.
+    Void
+}
+
+impl Display for HostValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Uint(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{:?}", value),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Str(value) => write!(f, "{:?}", value),
+            Self::Void => f.write_str("()")
+        }
+    }
+}
+
+impl From<i32> for HostValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<i64> for HostValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u64> for HostValue {
+    fn from(value: u64) -> Self {
+        Self::Uint(value)
+    }
+}
+
+impl From<f64> for HostValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<bool> for HostValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<String> for HostValue {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<&str> for HostValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_string())
+    }
+}
+
+impl From<()> for HostValue {
+    fn from(_: ()) -> Self {
+        Self::Void
+    }
+}
+
+/// Argument of the program as passed to host functions.
+pub(crate) fn to_host(value: &Value, symbols: &SymbolStorage) -> Result<HostValue, String> {
+    let Value::Prim(constant) = value else {
+        return Err(format!("can't pass `{}` to the host, only primitive values can be passed", value))
+    };
+    Ok(match constant.kind {
+        ConstKind::Int(value) if matches!(constant.ty, Ty::Prim(PrimTy::Int(_))) => HostValue::Int(value as i128 as i64),
+        ConstKind::Int(value) => HostValue::Uint(value as u64),
+        ConstKind::Float(value) => HostValue::Float(value),
+        ConstKind::Bool(value) => HostValue::Bool(value),
+        ConstKind::Str(symbol) => HostValue::Str(symbols.text_of(symbol).unwrap_or("").to_string()),
+        ConstKind::Void => HostValue::Void,
+        ConstKind::Char(_) | ConstKind::Fn(_) => return Err(format!("can't pass `{}` to the host", value))
+    })
+}
+
+/// Result of a host function as a value of the declared return type.
+/// Integers are accepted whether they are signed or not, as long as they
+/// fit in the type.
+pub(crate) fn from_host(value: HostValue, ty: Ty) -> Result<Value, String> {
+    let kind = match (value, ty) {
+        (HostValue::Void, Ty::Void) => return Ok(Value::VOID),
+        (HostValue::Int(value), Ty::Prim(prim)) if prim.int_range().is_some() => int_kind(value as i128, prim)?,
+        (HostValue::Uint(value), Ty::Prim(prim)) if prim.int_range().is_some() => int_kind(value as i128, prim)?,
+        (HostValue::Float(value), Ty::Prim(PrimTy::Float(FloatTy::F32))) => ConstKind::Float(value as f32 as f64),
+        (HostValue::Float(value), Ty::Prim(PrimTy::Float(_))) => ConstKind::Float(value),
+        (HostValue::Bool(value), Ty::Prim(PrimTy::Bool)) => ConstKind::Bool(value),
+        (HostValue::Str(value), Ty::Prim(PrimTy::Str)) => ConstKind::Str(Symbol::intern(&value)),
+        (value, Ty::Never) => return Err(format!("returned `{}` from a function which never returns", value)),
+        (value, Ty::Prim(prim)) => return Err(format!("returned `{}` where `{}` was expected", value, prim)),
+        (value, _) => return Err(format!("returned `{}` from a function which can't return values of the host", value))
+    };
+    Ok(Value::Prim(Constant { kind, ty }))
+}
+
+/// Integer constant of the type, stored like `ConstKind::Int` expects.
+fn int_kind(value: i128, prim: PrimTy) -> Result<ConstKind, String> {
+    let (min, max) = prim.int_range().expect("type is not an integer");
+    if !(min..=max).contains(&value) {
+        return Err(format!("returned {} which doesn't fit in `{}`", value, prim))
+    }
+    Ok(ConstKind::Int(value as u128))
+}
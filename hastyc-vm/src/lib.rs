@@ -0,0 +1,5 @@
+pub mod bytecode;
+pub mod compile;
+pub mod ffi;
+pub mod package;
+pub mod vm;
@@ -0,0 +1,643 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use hastyc_common::{identifiers::{PkgID, Symbol, SymbolStorage}, stable_hash::StableHasher};
+use hastyc_parser::parser::Visibility;
+use hastyc_passes::{hir::{self, mono::main_fn, ty::Ty, DefId, Hir}, mir::{ConstKind, Constant}, passes::{builtin::{FloatTy, IntTy, PrimTy, UintTy}, defs::{DefIndex, DefKind}, intrinsic::INTRINSICS}};
+
+use crate::bytecode::{Chunk, ExternSig, FnCode, FnIndex, Function, Instr, Program, SourceLoc, Value, BIN_OPS, UN_OPS};
+
+/// First bytes of every compiled package.
+const MAGIC: &[u8; 8] = b"HASTYPKG";
+
+/// Version of the format, packages of other versions are not loaded.
+/// Bump it whenever bytecode or anything below changes.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Extension of files that compiled packages are saved to.
+pub const EXTENSION: &str = "hpkg";
+
+/// Package compiled to bytecode together with what is needed to use it
+/// without its sources, so it can be saved once and loaded by the VM or
+/// by packages which depend on it:
+///
+/// ```text
+/// magic, version
+/// checksum:  fingerprint of everything below
+/// symbols:   text of every symbol used below
+/// defs:      index, kind, visibility and path of every definition
+/// functions: owner, name, counts and bytecode, intrinsic or extern types
+/// constants, adts, main
+/// ```
+///
+/// Integers are little endian `u32`, except for integer constants which
+/// are `u128`. Symbols are stored as indices in the symbol table and
+/// definitions as their index in the package. Packages get a new `PkgID`
+/// when they are loaded, since ids are only unique within a compilation.
+#[derive(Debug)]
+pub struct CompiledPackage {
+    pub program: Program,
+    pub defs: Vec<DefEntry>,
+    /// Function which runs the package, if it has one.
+    pub main: Option<FnIndex>,
+    /// Symbols used by a loaded package, their text is interned again
+    /// when it is loaded. Packages which were just compiled use storage of
+    /// their sources instead, so this is empty for them.
+    pub symbols: SymbolStorage
+}
+
+/// Definition of the package, as other packages can see it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefEntry {
+    pub def: DefId,
+    pub kind: DefKind,
+    /// Whether the definition and all modules that contain it are public.
+    pub exported: bool,
+    /// Names from the root module, which is left out.
+    pub path: Vec<Symbol>
+}
+
+impl DefEntry {
+    /// Path like `math::add`.
+    pub fn path_str(&self) -> String {
+        self.path.iter().map(|symbol| symbol.as_str()).collect::<Vec<_>>().join("::")
+    }
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+    Io(std::io::Error),
+    /// Data doesn't start with `MAGIC`, so it isn't a compiled package.
+    NotAPackage,
+    Version { found: u32 },
+    /// Data doesn't match the checksum in the header, so it was damaged.
+    Checksum,
+    /// Data ends before everything was read.
+    Truncated,
+    /// Data was read, but it doesn't make sense, eg. an index is out of bounds.
+    Corrupt(String),
+    /// Program has a value that can't be saved.
+    Unsupported(&'static str)
+}
+
+impl Display for PackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::NotAPackage => f.write_str("file is not a compiled package"),
+            Self::Version { found } => write!(f, "package has format version {}, but this compiler reads version {}", found, FORMAT_VERSION),
+            Self::Truncated => f.write_str("package ends unexpectedly"),
+            Self::Checksum => f.write_str("package doesn't match its checksum"),
+            Self::Corrupt(what) => write!(f, "package is corrupt: {}", what),
+            Self::Unsupported(what) => write!(f, "{} can't be saved in a package", what)
+        }
+    }
+}
+
+impl std::error::Error for PackageError {}
+
+impl From<std::io::Error> for PackageError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl CompiledPackage {
+    /// Package of the program compiled from the HIR.
+    pub fn new(program: Program, hir: &Hir) -> Self {
+        let defs = hir.defs.iter()
+            .filter(|(id, _)| **id != hir.root)
+            .map(|(id, def)| {
+                let mut path = vec![def.ident.symbol];
+                let mut exported = matches!(def.visibility, Visibility::Public);
+                let mut parent = def.parent;
+                while let Some(id) = parent.filter(|id| *id != hir.root) {
+                    let def = hir.def(id);
+                    path.push(def.ident.symbol);
+                    exported &= matches!(def.visibility, Visibility::Public);
+                    parent = def.parent;
+                }
+                path.reverse();
+                DefEntry { def: *id, kind: def_kind(&def.kind), exported, path }
+            })
+            .collect();
+        let main = main_fn(hir).and_then(|main| program.fn_index(main));
+        Self { program, defs, main, symbols: SymbolStorage::new() }
+    }
+
+    /// Exported definition with the path, like `math::add`.
+    pub fn export(&self, path: &str) -> Option<&DefEntry> {
+        self.defs.iter().find(|entry| entry.exported && entry.path_str() == path)
+    }
+
+    /// Function of the exported definition with the path.
+    pub fn exported_fn(&self, path: &str) -> Option<FnIndex> {
+        self.export(path).and_then(|entry| self.program.fn_index(entry.def))
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, PackageError> {
+        let mut encoder = Encoder::default();
+        encoder.len(self.defs.len());
+        for entry in self.defs.iter() {
+            encoder.def(entry.def);
+            encoder.u8(def_kind_tag(entry.kind));
+            encoder.u8(entry.exported as u8);
+            encoder.len(entry.path.len());
+            for symbol in entry.path.iter() {
+                encoder.symbol(*symbol);
+            }
+        }
+        let program = &self.program;
+        encoder.len(program.functions.len());
+        for function in program.functions.iter() {
+            encoder.function(function)?;
+        }
+        encoder.len(program.constants.len());
+        for value in program.constants.iter() {
+            encoder.value(value)?;
+        }
+        encoder.len(program.adts.len());
+        for adt in program.adts.iter() {
+            encoder.def(*adt);
+        }
+        match self.main {
+            Some(main) => {
+                encoder.u8(1);
+                encoder.u32(main);
+            },
+            None => encoder.u8(0)
+        }
+
+        let mut body = Vec::with_capacity(encoder.out.len() + 64);
+        body.extend((encoder.symbols.len() as u32).to_le_bytes());
+        for symbol in encoder.symbols.iter() {
+            let text = symbol.as_str();
+            body.extend((text.len() as u32).to_le_bytes());
+            body.extend(text.as_bytes());
+        }
+        body.extend(encoder.out);
+
+        let mut out = Vec::with_capacity(body.len() + 28);
+        out.extend(MAGIC);
+        out.extend(FORMAT_VERSION.to_le_bytes());
+        out.extend(checksum(&body).to_le_bytes());
+        out.extend(body);
+        Ok(out)
+    }
+
+    /// Read the package, checking that it can be run safely.
+    pub fn decode(data: &[u8]) -> Result<Self, PackageError> {
+        if !data.starts_with(MAGIC) {
+            return Err(PackageError::NotAPackage)
+        }
+        let mut decoder = Decoder { data, at: MAGIC.len(), symbols: Vec::new(), pkg: PkgID::new_unique() };
+        let version = decoder.u32()?;
+        if version != FORMAT_VERSION {
+            return Err(PackageError::Version { found: version })
+        }
+        let expected = u128::from_le_bytes(decoder.bytes(16)?.try_into().unwrap());
+        if checksum(&data[decoder.at..]) != expected {
+            return Err(PackageError::Checksum)
+        }
+        let mut symbols = SymbolStorage::new();
+        for _ in 0..decoder.len()? {
+            let len = decoder.len()?;
+            let text = std::str::from_utf8(decoder.bytes(len)?)
+                .map_err(|_| PackageError::Corrupt("symbol is not UTF-8".to_string()))?;
+            decoder.symbols.push(symbols.get_or_register(text));
+        }
+
+        let mut defs = Vec::new();
+        for _ in 0..decoder.len()? {
+            let def = decoder.def()?;
+            let kind = decoder.def_kind()?;
+            let exported = decoder.bool()?;
+            let path = (0..decoder.len()?).map(|_| decoder.symbol()).collect::<Result<_, _>>()?;
+            defs.push(DefEntry { def, kind, exported, path });
+        }
+        let mut program = Program::default();
+        for _ in 0..decoder.len()? {
+            let function = decoder.function()?;
+            program.by_def.insert(function.owner, program.functions.len() as FnIndex);
+            program.functions.push(function);
+        }
+        for _ in 0..decoder.len()? {
+            program.constants.push(decoder.value()?);
+        }
+        for _ in 0..decoder.len()? {
+            program.adts.push(decoder.def()?);
+        }
+        let main = match decoder.u8()? {
+            0 => None,
+            _ => Some(decoder.u32()?)
+        };
+        if decoder.at != data.len() {
+            return Err(PackageError::Corrupt("data after the end of the package".to_string()))
+        }
+
+        verify(&program, main)?;
+        Ok(Self { program, defs, main, symbols })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PackageError> {
+        std::fs::write(path, self.encode()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PackageError> {
+        Self::decode(&std::fs::read(path)?)
+    }
+}
+
+fn def_kind(kind: &hir::DefKind) -> DefKind {
+    match kind {
+        hir::DefKind::Module { .. } => DefKind::Module,
+        hir::DefKind::Fn { .. } => DefKind::Fn,
+        hir::DefKind::Struct { .. } => DefKind::Struct,
+        hir::DefKind::Enum { .. } => DefKind::Enum,
+        hir::DefKind::Variant { .. } => DefKind::Variant,
+        hir::DefKind::Field { .. } => DefKind::Field
+    }
+}
+
+const DEF_KINDS: [DefKind; 7] = [
+    DefKind::Module, DefKind::Fn, DefKind::Struct, DefKind::Enum,
+    DefKind::Variant, DefKind::Field, DefKind::Local
+];
+
+fn def_kind_tag(kind: DefKind) -> u8 {
+    DEF_KINDS.iter().position(|other| *other == kind).unwrap() as u8
+}
+
+const PRIM_TYS: [PrimTy; 15] = [
+    PrimTy::Int(IntTy::I8), PrimTy::Int(IntTy::I16), PrimTy::Int(IntTy::I32),
+    PrimTy::Int(IntTy::I64), PrimTy::Int(IntTy::Isize),
+    PrimTy::Uint(UintTy::U8), PrimTy::Uint(UintTy::U16), PrimTy::Uint(UintTy::U32),
+    PrimTy::Uint(UintTy::U64), PrimTy::Uint(UintTy::Usize),
+    PrimTy::Float(FloatTy::F32), PrimTy::Float(FloatTy::F64),
+    PrimTy::Bool, PrimTy::Char, PrimTy::Str
+];
+
+mod tag {
+    pub const TY_PRIM: u8 = 0;
+    pub const TY_ADT: u8 = 1;
+    pub const TY_FN_DEF: u8 = 2;
+    pub const TY_VOID: u8 = 3;
+    pub const TY_NEVER: u8 = 4;
+    pub const TY_ERR: u8 = 5;
+
+    pub const CONST_INT: u8 = 0;
+    pub const CONST_FLOAT: u8 = 1;
+    pub const CONST_BOOL: u8 = 2;
+    pub const CONST_CHAR: u8 = 3;
+    pub const CONST_STR: u8 = 4;
+    pub const CONST_FN: u8 = 5;
+    pub const CONST_VOID: u8 = 6;
+
+    pub const VALUE_PRIM: u8 = 0;
+    pub const VALUE_FN: u8 = 1;
+
+    pub const CODE_BYTECODE: u8 = 0;
+    pub const CODE_INTRINSIC: u8 = 1;
+    pub const CODE_EXTERN: u8 = 2;
+}
+
+/// Writer of everything after the symbol table, which collects the
+/// symbols as they are written.
+#[derive(Default)]
+struct Encoder {
+    out: Vec<u8>,
+    symbols: Vec<Symbol>,
+    symbol_idx: HashMap<Symbol, u32>
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.out.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.out.extend(value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.out.extend(bytes);
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        let symbols = &mut self.symbols;
+        let idx = *self.symbol_idx.entry(symbol).or_insert_with(|| {
+            symbols.push(symbol);
+            symbols.len() as u32 - 1
+        });
+        self.u32(idx);
+    }
+
+    fn def(&mut self, def: DefId) {
+        self.u32(def.index.0);
+    }
+
+    fn ty(&mut self, ty: Ty) -> Result<(), PackageError> {
+        match ty {
+            Ty::Prim(prim) => {
+                self.u8(tag::TY_PRIM);
+                self.u8(PRIM_TYS.iter().position(|other| *other == prim).unwrap() as u8);
+            },
+            Ty::Adt(def) => {
+                self.u8(tag::TY_ADT);
+                self.def(def);
+            },
+            Ty::FnDef(def) => {
+                self.u8(tag::TY_FN_DEF);
+                self.def(def);
+            },
+            Ty::Void => self.u8(tag::TY_VOID),
+            Ty::Never => self.u8(tag::TY_NEVER),
+            Ty::Err => self.u8(tag::TY_ERR),
+            Ty::Var(_) => return Err(PackageError::Unsupported("type inference variable"))
+        }
+        Ok(())
+    }
+
+    fn constant(&mut self, constant: &Constant) -> Result<(), PackageError> {
+        match constant.kind {
+            ConstKind::Int(value) => {
+                self.u8(tag::CONST_INT);
+                self.out.extend(value.to_le_bytes());
+            },
+            ConstKind::Float(value) => {
+                self.u8(tag::CONST_FLOAT);
+                self.out.extend(value.to_bits().to_le_bytes());
+            },
+            ConstKind::Bool(value) => {
+                self.u8(tag::CONST_BOOL);
+                self.u8(value as u8);
+            },
+            ConstKind::Char(value) => {
+                self.u8(tag::CONST_CHAR);
+                self.u32(value as u32);
+            },
+            ConstKind::Str(symbol) => {
+                self.u8(tag::CONST_STR);
+                self.symbol(symbol);
+            },
+            ConstKind::Fn(def) => {
+                self.u8(tag::CONST_FN);
+                self.def(def);
+            },
+            ConstKind::Void => self.u8(tag::CONST_VOID)
+        }
+        self.ty(constant.ty)
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), PackageError> {
+        match value {
+            Value::Prim(constant) => {
+                self.u8(tag::VALUE_PRIM);
+                self.constant(constant)
+            },
+            Value::Fn(index) => {
+                self.u8(tag::VALUE_FN);
+                self.u32(*index);
+                Ok(())
+            },
+            Value::Adt(_) => Err(PackageError::Unsupported("struct constant"))
+        }
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), PackageError> {
+        self.def(function.owner);
+        self.symbol(function.name);
+        self.u32(function.arg_count);
+        self.u32(function.local_count);
+        match function.code {
+            FnCode::Bytecode(ref chunk) => {
+                self.u8(tag::CODE_BYTECODE);
+                self.bytes(&chunk.code);
+                self.len(chunk.projections.len());
+                for projection in chunk.projections.iter() {
+                    self.len(projection.len());
+                    for field in projection.iter() {
+                        self.u32(*field);
+                    }
+                }
+                self.len(chunk.lines.len());
+                for (offset, location) in chunk.lines.iter() {
+                    self.u32(*offset);
+                    self.symbol(location.file);
+                    self.u32(location.line);
+                    self.u32(location.col);
+                }
+            },
+            FnCode::Intrinsic(intrinsic) => {
+                self.u8(tag::CODE_INTRINSIC);
+                self.u8(INTRINSICS.iter().position(|other| *other == intrinsic).unwrap() as u8);
+            },
+            FnCode::Extern(ref sig) => {
+                self.u8(tag::CODE_EXTERN);
+                self.len(sig.inputs.len());
+                for ty in sig.inputs.iter() {
+                    self.ty(*ty)?;
+                }
+                self.ty(sig.output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    at: usize,
+    symbols: Vec<Symbol>,
+    /// Package which definitions are given to.
+    pkg: PkgID
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PackageError> {
+        let end = self.at.checked_add(len).filter(|end| *end <= self.data.len()).ok_or(PackageError::Truncated)?;
+        let bytes = &self.data[self.at..end];
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, PackageError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, PackageError> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> Result<u32, PackageError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, PackageError> {
+        self.u32().map(|len| len as usize)
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, PackageError> {
+        let idx = self.len()?;
+        self.symbols.get(idx).copied().ok_or_else(|| corrupt("symbol", idx))
+    }
+
+    fn def(&mut self) -> Result<DefId, PackageError> {
+        Ok(DefId { pkg: self.pkg, index: DefIndex(self.u32()?) })
+    }
+
+    fn def_kind(&mut self) -> Result<DefKind, PackageError> {
+        let idx = self.u8()? as usize;
+        DEF_KINDS.get(idx).copied().ok_or_else(|| corrupt("definition kind", idx))
+    }
+
+    fn ty(&mut self) -> Result<Ty, PackageError> {
+        Ok(match self.u8()? {
+            tag::TY_PRIM => {
+                let idx = self.u8()? as usize;
+                Ty::Prim(PRIM_TYS.get(idx).copied().ok_or_else(|| corrupt("primitive type", idx))?)
+            },
+            tag::TY_ADT => Ty::Adt(self.def()?),
+            tag::TY_FN_DEF => Ty::FnDef(self.def()?),
+            tag::TY_VOID => Ty::Void,
+            tag::TY_NEVER => Ty::Never,
+            tag::TY_ERR => Ty::Err,
+            other => return Err(corrupt("type", other as usize))
+        })
+    }
+
+    fn constant(&mut self) -> Result<Constant, PackageError> {
+        let kind = match self.u8()? {
+            tag::CONST_INT => ConstKind::Int(u128::from_le_bytes(self.bytes(16)?.try_into().unwrap())),
+            tag::CONST_FLOAT => ConstKind::Float(f64::from_bits(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))),
+            tag::CONST_BOOL => ConstKind::Bool(self.bool()?),
+            tag::CONST_CHAR => {
+                let value = self.u32()?;
+                ConstKind::Char(char::from_u32(value).ok_or_else(|| corrupt("char", value as usize))?)
+            },
+            tag::CONST_STR => ConstKind::Str(self.symbol()?),
+            tag::CONST_FN => ConstKind::Fn(self.def()?),
+            tag::CONST_VOID => ConstKind::Void,
+            other => return Err(corrupt("constant", other as usize))
+        };
+        Ok(Constant { kind, ty: self.ty()? })
+    }
+
+    fn value(&mut self) -> Result<Value, PackageError> {
+        match self.u8()? {
+            tag::VALUE_PRIM => Ok(Value::Prim(self.constant()?)),
+            tag::VALUE_FN => Ok(Value::Fn(self.u32()?)),
+            other => Err(corrupt("value", other as usize))
+        }
+    }
+
+    fn function(&mut self) -> Result<Function, PackageError> {
+        let owner = self.def()?;
+        let name = self.symbol()?;
+        let arg_count = self.u32()?;
+        let local_count = self.u32()?;
+        let code = match self.u8()? {
+            tag::CODE_BYTECODE => {
+                let len = self.len()?;
+                let code = self.bytes(len)?.to_vec();
+                let mut projections = Vec::new();
+                for _ in 0..self.len()? {
+                    projections.push((0..self.len()?).map(|_| self.u32()).collect::<Result<_, _>>()?);
+                }
+                let mut lines = Vec::new();
+                for _ in 0..self.len()? {
+                    let offset = self.u32()?;
+                    let location = SourceLoc { file: self.symbol()?, line: self.u32()?, col: self.u32()? };
+                    lines.push((offset, location));
+                }
+                FnCode::Bytecode(Chunk { code, projections, lines })
+            },
+            tag::CODE_INTRINSIC => {
+                let idx = self.u8()? as usize;
+                FnCode::Intrinsic(INTRINSICS.get(idx).copied().ok_or_else(|| corrupt("intrinsic", idx))?)
+            },
+            tag::CODE_EXTERN => {
+                let inputs = (0..self.len()?).map(|_| self.ty()).collect::<Result<_, _>>()?;
+                FnCode::Extern(ExternSig { inputs, output: self.ty()? })
+            },
+            other => return Err(corrupt("function", other as usize))
+        };
+        Ok(Function { owner, name, arg_count, local_count, code })
+    }
+}
+
+/// Checksum of the package after its header.
+fn checksum(body: &[u8]) -> u128 {
+    let mut hasher = StableHasher::new();
+    hasher.write(body);
+    hasher.finish().0
+}
+
+fn corrupt(what: &str, idx: usize) -> PackageError {
+    PackageError::Corrupt(format!("invalid {} {}", what, idx))
+}
+
+/// Most locals a function of a loaded package can have.
+const MAX_LOCALS: u32 = 1 << 16;
+
+/// Check that every index in the program is in bounds, so that a package
+/// which matches its checksum but wasn't written by this compiler is
+/// rejected here rather than making the VM read outside of the program.
+/// Bytecode is not type checked, the VM reports operands of wrong types
+/// or missing ones as errors when it runs.
+fn verify(program: &Program, main: Option<FnIndex>) -> Result<(), PackageError> {
+    let functions = program.functions.len();
+    if main.is_some_and(|main| main as usize >= functions) {
+        return Err(corrupt("main function", main.unwrap() as usize))
+    }
+    for value in program.constants.iter() {
+        if let Value::Fn(index) = *value {
+            if index as usize >= functions {
+                return Err(corrupt("function", index as usize))
+            }
+        }
+    }
+    for function in program.functions.iter() {
+        if function.local_count > MAX_LOCALS {
+            return Err(corrupt("local count", function.local_count as usize))
+        }
+        // Return place is a local as well
+        if function.arg_count >= function.local_count {
+            return Err(corrupt("argument count", function.arg_count as usize))
+        }
+        let FnCode::Bytecode(ref chunk) = function.code else { continue };
+        let mut starts = Vec::new();
+        let mut jumps = Vec::new();
+        let mut at = 0;
+        while at < chunk.code.len() {
+            starts.push(at);
+            let (instr, next) = Instr::try_decode(&chunk.code, at).ok_or_else(|| corrupt("instruction at", at))?;
+            let in_bounds = match instr {
+                Instr::Const(idx) => (idx as usize) < program.constants.len(),
+                Instr::Load(local) | Instr::Store(local) => local < function.local_count,
+                Instr::StoreField { local, projection } => local < function.local_count && (projection as usize) < chunk.projections.len(),
+                Instr::Aggregate { adt, .. } => (adt as usize) < program.adts.len(),
+                Instr::Binary(op) => (op as usize) < BIN_OPS.len(),
+                Instr::Unary(op) => (op as usize) < UN_OPS.len(),
+                Instr::Jump(target) | Instr::JumpIfFalse(target) => {
+                    jumps.push(target as usize);
+                    true
+                },
+                Instr::Field(_) | Instr::Call(_) | Instr::Return | Instr::Unreachable => true
+            };
+            if !in_bounds {
+                return Err(corrupt("operand of instruction at", at))
+            }
+            at = next;
+        }
+        if let Some(target) = jumps.into_iter().find(|target| starts.binary_search(target).is_err()) {
+            return Err(corrupt("jump target", target))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,351 @@
+use std::{collections::HashMap, fmt::Display, io::Write, rc::Rc};
+
+use hastyc_common::identifiers::{Symbol, SymbolStorage};
+use hastyc_parser::parser::BinOpKind;
+use hastyc_passes::{hir::ty::Ty, mir::{const_eval::{eval_binary, eval_unary}, ConstKind, Constant}, passes::{builtin::PrimTy, intrinsic::{ArithmeticError, Intrinsic}}};
+
+use crate::{bytecode::{AdtValue, ExternSig, FnCode, FnIndex, Instr, Program, SourceLoc, Value, BIN_OPS, UN_OPS}, ffi::{from_host, to_host, HostFn, HostValue}};
+
+/// Deepest nesting of calls, deeper calls fail instead of taking
+/// all memory there is.
+pub const MAX_FRAMES: usize = 4096;
+
+/// Why the program stopped before returning.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    /// Arithmetic overflowed, or a shift was larger than its type.
+    Overflow,
+    DivisionByZero,
+    /// `panic` was called with the message at the location.
+    Panic { message: String, location: String },
+    /// Code which can't be reached was reached, like after a call to
+    /// a function which never returns.
+    Unreachable,
+    StackOverflow,
+    /// Called function without a body which the VM doesn't provide,
+    /// and which no host function is bound to.
+    Extern(Symbol),
+    /// Host function bound to the extern failed, or its arguments or
+    /// result could not be converted.
+    Host { name: Symbol, message: String },
+    /// Output of an intrinsic could not be written.
+    Io(String),
+    /// Bytecode did something the compiler never generates, like adding
+    /// a struct or popping from an empty stack. Loaded packages are only
+    /// checked for indices out of bounds, so this is found when it runs.
+    InvalidBytecode(&'static str)
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overflow => f.write_str(ArithmeticError::Overflow.message()),
+            Self::DivisionByZero => f.write_str(ArithmeticError::DivisionByZero.message()),
+            Self::Panic { message, location } => write!(f, "panicked at {}: {}", location, message),
+            Self::Unreachable => f.write_str("entered unreachable code"),
+            Self::StackOverflow => write!(f, "stack overflow, more than {} nested calls", MAX_FRAMES),
+            Self::Extern(name) => write!(f, "can't call extern function `{}`", name),
+            Self::Host { name, message } => write!(f, "extern function `{}` failed: {}", name, message),
+            Self::Io(err) => write!(f, "can't write output: {}", err),
+            Self::InvalidBytecode(what) => write!(f, "invalid bytecode: {}", what)
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// Call which was running when the program stopped, see `Vm::backtrace`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktraceFrame {
+    pub function: Symbol,
+    /// Where the call was running, None if the bytecode has no location.
+    pub location: Option<SourceLoc>
+}
+
+impl Display for BacktraceFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some(location) => write!(f, "{} at {}", self.function.as_str(), location),
+            None => write!(f, "{}", self.function.as_str())
+        }
+    }
+}
+
+/// Function which is running.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    function: FnIndex,
+    /// Offset of the next instruction.
+    ip: usize,
+    /// Position of the return place on the stack, locals follow it.
+    base: usize
+}
+
+/// Stack based virtual machine which runs compiled programs. Intrinsics
+/// write their output into `out`, text of strings is taken from the
+/// symbol storage of the package. Externs are called through host
+/// functions that the embedder registers.
+pub struct Vm<'a, W: Write> {
+    program: &'a Program,
+    symbols: &'a SymbolStorage,
+    out: W,
+    host_fns: HashMap<Symbol, HostFn<'a>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>
+}
+
+impl<'a, W: Write> Vm<'a, W> {
+    pub fn new(program: &'a Program, symbols: &'a SymbolStorage, out: W) -> Self {
+        Self { program, symbols, out, host_fns: HashMap::new(), stack: Vec::new(), frames: Vec::new() }
+    }
+
+    /// Bind the host function to externs with the name, replacing the
+    /// function bound before. Arguments are converted from the types
+    /// the extern declares, and so is the result, like:
+    ///
+    /// ```text
+    /// vm.register("add", |args| match args {
+    ///     [HostValue::Int(a), HostValue::Int(b)] => Ok(HostValue::Int(a + b)),
+    ///     _ => Err("expected two integers".to_string())
+    /// });
+    /// ```
+    pub fn register(&mut self, name: &str, function: impl FnMut(&[HostValue]) -> Result<HostValue, String> + 'a) {
+        self.host_fns.insert(Symbol::intern(name), Box::new(function));
+    }
+
+    /// Call the function with the arguments, running until it returns.
+    pub fn call(&mut self, function: FnIndex, args: Vec<Value>) -> Result<Value, VmError> {
+        self.stack.clear();
+        self.frames.clear();
+        let arg_count = args.len() as u32;
+        self.stack.push(Value::Fn(function));
+        self.stack.extend(args);
+        self.enter(arg_count)?;
+        self.run()?;
+        self.pop()
+    }
+
+    /// Calls which were running when the last call stopped with an error,
+    /// innermost first. Empty if it returned.
+    pub fn backtrace(&self) -> Vec<BacktraceFrame> {
+        self.frames.iter().rev().map(|frame| {
+            let function = self.program.function(frame.function);
+            let FnCode::Bytecode(ref chunk) = function.code else { unreachable!("frame of a function without bytecode") };
+            // Instruction pointer is already past the instruction which failed
+            BacktraceFrame { function: function.name, location: chunk.location(frame.ip.saturating_sub(1)) }
+        }).collect()
+    }
+
+    /// Output that intrinsics wrote to.
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    fn pop(&mut self) -> Result<Value, VmError> {
+        self.stack.pop().ok_or(VmError::InvalidBytecode("value stack is empty"))
+    }
+
+    /// Values on top of the stack, removed from it.
+    fn pop_n(&mut self, count: usize) -> Result<Vec<Value>, VmError> {
+        let at = self.stack.len().checked_sub(count).ok_or(VmError::InvalidBytecode("value stack is empty"))?;
+        Ok(self.stack.split_off(at))
+    }
+
+    fn local(&mut self, base: usize, local: u32) -> Result<&mut Value, VmError> {
+        self.stack.get_mut(base + local as usize).ok_or(VmError::InvalidBytecode("local was popped from the stack"))
+    }
+
+    /// Start the call of the function below the arguments. Intrinsics
+    /// run right away, leaving their result in place of the function.
+    fn enter(&mut self, arg_count: u32) -> Result<(), VmError> {
+        let base = self.stack.len().checked_sub(arg_count as usize + 1)
+            .ok_or(VmError::InvalidBytecode("call has more arguments than values on the stack"))?;
+        let Value::Fn(index) = self.stack[base] else { return Err(VmError::InvalidBytecode("called value is not a function")) };
+        let function = self.program.function(index);
+        match function.code {
+            FnCode::Bytecode(_) => {
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(VmError::StackOverflow)
+                }
+                self.stack[base] = Value::VOID;
+                self.stack.resize(base + function.local_count as usize, Value::VOID);
+                self.frames.push(Frame { function: index, ip: 0, base });
+                Ok(())
+            },
+            FnCode::Intrinsic(intrinsic) => {
+                let args = self.stack.split_off(base + 1);
+                let result = self.intrinsic(intrinsic, &args)?;
+                self.stack[base] = result;
+                Ok(())
+            },
+            FnCode::Extern(ref sig) => {
+                let args = self.stack.split_off(base + 1);
+                self.stack[base] = self.host(function.name, sig, &args)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Call host function bound to the extern with the name.
+    fn host(&mut self, name: Symbol, sig: &ExternSig, args: &[Value]) -> Result<Value, VmError> {
+        let Some(function) = self.host_fns.get_mut(&name) else { return Err(VmError::Extern(name)) };
+        let fail = |message| VmError::Host { name, message };
+        let args = args.iter()
+            .map(|arg| to_host(arg, self.symbols))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(fail)?;
+        let result = function(&args).map_err(fail)?;
+        from_host(result, sig.output).map_err(fail)
+    }
+
+    /// Run the intrinsic natively, strings it makes are interned like
+    /// literals are, so they are used like any other string.
+    fn intrinsic(&mut self, intrinsic: Intrinsic, args: &[Value]) -> Result<Value, VmError> {
+        let arg = |idx: usize| args.get(idx).ok_or(VmError::InvalidBytecode("intrinsic called with too few arguments"));
+        let written = match intrinsic {
+            Intrinsic::Print => write!(self.out, "{}", self.text(arg(0)?)?),
+            Intrinsic::Println => writeln!(self.out, "{}", self.text(arg(0)?)?),
+            Intrinsic::Panic => {
+                let message = self.text(arg(0)?)?.to_string();
+                let location = self.text(arg(1)?)?.to_string();
+                return Err(VmError::Panic { message, location })
+            },
+            Intrinsic::Concat => {
+                let text = format!("{}{}", self.text(arg(0)?)?, self.text(arg(1)?)?);
+                return Ok(string(&text))
+            },
+            Intrinsic::FormatI32 | Intrinsic::FormatI64 => return Ok(string(&(int(arg(0)?)? as i128).to_string())),
+            Intrinsic::FormatU64 => return Ok(string(&(int(arg(0)?)? as u64).to_string())),
+            Intrinsic::FormatF64 => {
+                let ConstKind::Float(value) = prim(arg(0)?)?.kind else {
+                    return Err(VmError::InvalidBytecode("intrinsic called with a value that is not a float"))
+                };
+                return Ok(string(&value.to_string()))
+            },
+            Intrinsic::FormatBool => {
+                let ConstKind::Bool(value) = prim(arg(0)?)?.kind else {
+                    return Err(VmError::InvalidBytecode("intrinsic called with a value that is not a bool"))
+                };
+                return Ok(string(&value.to_string()))
+            }
+        };
+        written.map_err(|err| VmError::Io(err.to_string()))?;
+        Ok(Value::VOID)
+    }
+
+    fn text(&self, value: &Value) -> Result<&'a str, VmError> {
+        match prim(value)?.kind {
+            ConstKind::Str(symbol) => Ok(self.symbols.text_of(symbol).unwrap_or("")),
+            _ => Err(VmError::InvalidBytecode("intrinsic called with a value that is not a string"))
+        }
+    }
+
+    fn run(&mut self) -> Result<(), VmError> {
+        let program = self.program;
+        while let Some(frame) = self.frames.last_mut() {
+            let FnCode::Bytecode(ref chunk) = program.function(frame.function).code else {
+                unreachable!("frame of a function without bytecode")
+            };
+            let (instr, next) = Instr::decode(&chunk.code, frame.ip);
+            frame.ip = next;
+            let base = frame.base;
+            match instr {
+                Instr::Const(idx) => self.stack.push(program.constants[idx as usize].clone()),
+                Instr::Load(local) => {
+                    let value = self.local(base, local)?.clone();
+                    self.stack.push(value);
+                },
+                Instr::Store(local) => {
+                    let value = self.pop()?;
+                    *self.local(base, local)? = value;
+                },
+                Instr::Field(idx) => {
+                    let Value::Adt(adt) = self.pop()? else { return Err(VmError::InvalidBytecode("field of a value that is not a struct")) };
+                    let field = adt.fields.get(idx as usize).ok_or(VmError::InvalidBytecode("struct has no such field"))?;
+                    self.stack.push(field.clone());
+                },
+                Instr::StoreField { local, projection } => {
+                    let value = self.pop()?;
+                    let mut place = self.local(base, local)?;
+                    for field in chunk.projections[projection as usize].iter() {
+                        let Value::Adt(adt) = place else { return Err(VmError::InvalidBytecode("field of a value that is not a struct")) };
+                        // Other copies of the value keep the old fields
+                        place = Rc::make_mut(adt).fields.get_mut(*field as usize)
+                            .ok_or(VmError::InvalidBytecode("struct has no such field"))?;
+                    }
+                    *place = value;
+                },
+                Instr::Aggregate { adt, fields } => {
+                    let fields = self.pop_n(fields as usize)?;
+                    self.stack.push(Value::Adt(Rc::new(AdtValue { adt, fields })));
+                },
+                Instr::Binary(op) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    let result = binary(&BIN_OPS[op as usize], &lhs, &rhs)?;
+                    self.stack.push(result);
+                },
+                Instr::Unary(op) => {
+                    let Value::Prim(operand) = self.pop()? else { return Err(VmError::InvalidBytecode("operator applied to a value that is not primitive")) };
+                    let result = eval_unary(&UN_OPS[op as usize], &operand).ok_or(VmError::Overflow)?;
+                    self.stack.push(Value::Prim(result));
+                },
+                Instr::Jump(target) => self.jump(target),
+                Instr::JumpIfFalse(target) => {
+                    let Value::Prim(Constant { kind: ConstKind::Bool(cond), .. }) = self.pop()? else {
+                        return Err(VmError::InvalidBytecode("condition is not a bool"))
+                    };
+                    if !cond {
+                        self.jump(target);
+                    }
+                },
+                Instr::Call(arg_count) => self.enter(arg_count)?,
+                Instr::Return => {
+                    let result = std::mem::replace(self.local(base, 0)?, Value::VOID);
+                    self.stack.truncate(base);
+                    self.frames.pop();
+                    self.stack.push(result);
+                },
+                Instr::Unreachable => return Err(VmError::Unreachable)
+            }
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, target: u32) {
+        self.frames.last_mut().expect("jump outside of a function").ip = target as usize;
+    }
+}
+
+fn prim(value: &Value) -> Result<&Constant, VmError> {
+    match value {
+        Value::Prim(constant) => Ok(constant),
+        _ => Err(VmError::InvalidBytecode("intrinsic called with a value that is not primitive"))
+    }
+}
+
+/// Integers are sign extended, see `ConstKind::Int`.
+fn int(value: &Value) -> Result<u128, VmError> {
+    match prim(value)?.kind {
+        ConstKind::Int(value) => Ok(value),
+        _ => Err(VmError::InvalidBytecode("intrinsic called with a value that is not an integer"))
+    }
+}
+
+fn string(text: &str) -> Value {
+    Value::Prim(Constant { kind: ConstKind::Str(Symbol::intern(text)), ty: Ty::Prim(PrimTy::Str) })
+}
+
+/// Binary operation on primitive values. Constant evaluation gives
+/// nothing for operations which would fail at runtime, and the only
+/// ones which aren't overflows are divisions by zero.
+fn binary(op: &BinOpKind, lhs: &Value, rhs: &Value) -> Result<Value, VmError> {
+    let (Value::Prim(lhs), Value::Prim(rhs)) = (lhs, rhs) else {
+        return Err(VmError::InvalidBytecode("operator applied to a value that is not primitive"))
+    };
+    match eval_binary(op, lhs, rhs) {
+        Some(result) => Ok(Value::Prim(result)),
+        None if matches!(op, BinOpKind::Div | BinOpKind::Rem) && rhs.kind == ConstKind::Int(0) => Err(VmError::DivisionByZero),
+        None => Err(VmError::Overflow)
+    }
+}
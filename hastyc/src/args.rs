@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use hastyc_common::{error::{ColorChoice, RenderConfig}, session::{BackendKind, DiagnosticFormat, EmitKind, OptLevel, Options, ShadowingPolicy}};
+use hastyc_parser::lexer;
+
+pub const USAGE: &str = "\
+Usage: hastyc <command> <path> [options]
+
+Path is the root file of the package, or its directory with the root
+in `src/main.hty` or `src/lib.hty`. Modules declared like `module foo;`
+are loaded from `foo.hty` or `foo/mod.hty` next to the root.
+
+Commands:
+    build     Compile the package with the backend
+    check     Report diagnostics without generating code
+    run       Compile and run `main` on the VM, or run a saved package
+    ast       Print the syntax tree of the package
+    tokens    Print tokens of the root file
+    fmt       Format source files of the package in place
+
+Options:
+    --backend=<vm|c|llvm>     Backend used by `build`, vm by default
+    --out=<path>              Where `build` writes its output
+    -O<0|1|2>                 Optimize MIR before generating code
+    --emit=<kind>,...         Also emit tokens, ast, ast-json, resolved-names,
+                              resolved, scopes, types, item-graph,
+                              expr-graph, module-graph, object or package
+    --graph-fn=<path>         Function drawn by `--emit=expr-graph`, like
+                              `math::add`
+    --error-format=<format>   human, json or plain
+    --color=<auto|always|never>
+    --error-limit=<n>         Stop after the number of errors
+    --feature=<name>          Enable unstable language feature
+    --shadowing=<allow|warn|deny>
+                              What to do with variables that shadow others
+                              in scope, allow by default
+    --max-expr-depth=<n>      How deep expressions can be nested, 1024 by
+                              default
+    --dump-after=<pass>       Print what the pass produced, can be repeated
+    --time-passes             Print how long every pass took
+    --check                   Make `fmt` list files that aren't formatted
+                              instead of changing them
+    -h, --help                Print this message";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Build,
+    Check,
+    Run,
+    Ast,
+    Tokens,
+    Fmt
+}
+
+impl Command {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "build" => Some(Self::Build),
+            "check" => Some(Self::Check),
+            "run" => Some(Self::Run),
+            "ast" => Some(Self::Ast),
+            "tokens" => Some(Self::Tokens),
+            "fmt" => Some(Self::Fmt),
+            _ => None
+        }
+    }
+}
+
+/// Parsed command line.
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    pub path: PathBuf,
+    pub options: Options,
+    pub out: Option<PathBuf>,
+    pub dump_after: Vec<String>,
+    pub time_passes: bool,
+    pub check: bool,
+    pub graph_fn: Option<String>
+}
+
+impl Args {
+    /// Parse arguments, without the name of the program, None if they
+    /// ask for the usage. Errors are messages which are printed together
+    /// with the usage.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut command = None;
+        let mut path = None;
+        let mut options = Options::default();
+        let mut color = ColorChoice::default();
+        let mut backend = None;
+        let mut out = None;
+        let mut dump_after = Vec::new();
+        let mut time_passes = false;
+        let mut check = false;
+        let mut graph_fn = None;
+        for arg in args {
+            if arg == "-h" || arg == "--help" {
+                return Ok(None)
+            } else if arg == "--time-passes" {
+                time_passes = true;
+            } else if arg == "--check" {
+                check = true;
+            } else if let Some(value) = arg.strip_prefix("--backend=") {
+                backend = Some(BackendKind::parse(value).ok_or_else(|| invalid("backend", value))?);
+            } else if let Some(value) = arg.strip_prefix("--out=") {
+                out = Some(PathBuf::from(value));
+            } else if let Some(value) = arg.strip_prefix("-O") {
+                options.opt_level = OptLevel::parse(value).ok_or_else(|| invalid("optimization level", value))?;
+            } else if let Some(value) = arg.strip_prefix("--emit=") {
+                for kind in value.split(',') {
+                    options.emit.push(EmitKind::parse(kind).ok_or_else(|| invalid("emit kind", kind))?);
+                }
+            } else if let Some(value) = arg.strip_prefix("--error-format=") {
+                options.diagnostics.format = DiagnosticFormat::parse(value).ok_or_else(|| invalid("error format", value))?;
+            } else if let Some(value) = arg.strip_prefix("--color=") {
+                color = ColorChoice::parse(value).ok_or_else(|| invalid("color choice", value))?;
+            } else if let Some(value) = arg.strip_prefix("--error-limit=") {
+                options.diagnostics.error_limit = Some(value.parse().map_err(|_| invalid("error limit", value))?);
+            } else if let Some(value) = arg.strip_prefix("--feature=") {
+                options.features.insert(value.to_string());
+            } else if let Some(value) = arg.strip_prefix("--shadowing=") {
+                options.shadowing = ShadowingPolicy::parse(value).ok_or_else(|| invalid("shadowing policy", value))?;
+            } else if let Some(value) = arg.strip_prefix("--max-expr-depth=") {
+                options.max_expr_depth = value.parse().map_err(|_| invalid("expression depth", value))?;
+            } else if let Some(value) = arg.strip_prefix("--dump-after=") {
+                dump_after.push(value.to_string());
+            } else if let Some(value) = arg.strip_prefix("--graph-fn=") {
+                graph_fn = Some(value.to_string());
+            } else if arg.starts_with('-') {
+                return Err(format!("unknown option `{}`", arg));
+            } else if command.is_none() {
+                command = Some(Command::parse(&arg).ok_or_else(|| format!("unknown command `{}`", arg))?);
+            } else if path.is_none() {
+                path = Some(PathBuf::from(arg));
+            } else {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+        }
+        let command = command.ok_or("no command given")?;
+        let path = path.ok_or("no path given")?;
+        if command == Command::Run && backend.is_some_and(|backend| backend != BackendKind::Vm) {
+            return Err("`run` only runs bytecode, use `build` for other backends".to_string());
+        }
+        if check && command != Command::Fmt {
+            return Err("`--check` can only be used with `fmt`".to_string());
+        }
+        if options.emit.contains(&EmitKind::ExprGraph) && graph_fn.is_none() {
+            return Err("`--emit=expr-graph` needs the function given with `--graph-fn`".to_string());
+        }
+        options.diagnostics.render = RenderConfig {
+            highlighter: Some(lexer::highlight),
+            ..RenderConfig::from_env(color)
+        };
+        options.backend = backend.unwrap_or_default();
+        Ok(Some(Self { command, path, options, out, dump_after, time_passes, check, graph_fn }))
+    }
+}
+
+fn invalid(what: &str, value: &str) -> String {
+    format!("invalid {} `{}`", what, value)
+}
@@ -0,0 +1,400 @@
+use std::{path::{Path, PathBuf}, process::ExitCode};
+
+use hastyc_ast_fmt::{find_fn, format_file, DotPrinter, FormatError, PackageASTPrettyPrinter};
+use hastyc_common::{error::CommonErrorContext, identifiers::{PkgID, SourceFileID, SymbolStorage}, session::{DiagnosticFormat, EmitKind, Session}, source::{SourceFile, SourceMap}};
+use hastyc_parser::{lexer::{Lexer, TokenStream}, loader, parser::{Package, ParserOptions}};
+use hastyc_passes::{hir::{captures::CapturePass, dead_code::DeadCodePass, lower::LowerToHir, mono::{main_fn, MonoCollectPass}, passing::PassingModePass, typeck::TypeCheckPass}, mir::{build::BuildMir, pretty::MirDisplay, transform::{MirPipeline, OptimizeMir}, ConstKind, Mir}, passes::{desugar::DesugarLoops, dump::{ModuleGraphDisplay, ResolvedNamesDisplay, ResolvedSourceDisplay, ScopesDisplay, TypesDisplay}, manager::PassManager, name_resolve::NameResolvePass, prelude::InjectPrelude, transform::ASTMutPipeline, QueryContext}};
+use hastyc_codegen::{backend::{codegen, Artifact, CodegenInput}, link::{LinkError, Linker}};
+use hastyc_vm::{bytecode::{FnIndex, Program, Value}, package::{self, CompiledPackage}, vm::{BacktraceFrame, Vm}};
+
+use crate::args::{Args, Command, USAGE};
+
+mod args;
+
+/// Exit code of programs which stopped with an error, the same as
+/// native programs exit with when they panic.
+const RUNTIME_ERROR: u8 = 101;
+
+/// Number of distinct calls printed in a backtrace, the rest is counted.
+const MAX_BACKTRACE_RUNS: usize = 32;
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS
+        },
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2)
+        }
+    };
+    if args.command == Command::Run && args.path.extension().is_some_and(|ext| ext == package::EXTENSION) {
+        return run_package(&args.path);
+    }
+    // Paths in diagnostics are relative to the package directory
+    let (root_path, source_map) = if args.path.is_dir() {
+        match loader::find_root(&args.path) {
+            Some(root) => (root, SourceMap::with_root(&args.path)),
+            None => {
+                eprintln!("error: `{}` has no `src/main.hty` or `src/lib.hty`", args.path.display());
+                return ExitCode::FAILURE
+            }
+        }
+    } else {
+        (args.path.clone(), SourceMap::new())
+    };
+    let mut sess = Session::with_source_map(args.options.clone(), source_map);
+    let root = match sess.source_map.load_file(&root_path, PkgID::new_unique()) {
+        Ok(id) => id,
+        Err(err) => {
+            eprintln!("error: can't read `{}`: {}", root_path.display(), err);
+            return ExitCode::FAILURE
+        }
+    };
+    if args.command == Command::Fmt {
+        return format_package(&mut sess, root, args.check)
+    }
+    match compile(&mut sess, root, &args) {
+        Ok(code) => code,
+        Err(()) => {
+            print_diagnostics(&sess);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the command on the package with the root file, Err if it stopped
+/// because of errors, which are left in the session.
+fn compile(sess: &mut Session, root: SourceFileID, args: &Args) -> Result<ExitCode, ()> {
+    if args.command == Command::Tokens || sess.options.emit.contains(&EmitKind::Tokens) {
+        let source = sess.source_map.get(root).unwrap();
+        let tokens = Lexer::lex(sess, source).ok_or(())?;
+        print_tokens(&tokens, source);
+    }
+    if args.command == Command::Tokens {
+        return finish(sess)
+    }
+
+    let mut package = loader::load_package(sess, root, &ParserOptions::default()).ok_or(())?;
+    let sess: &Session = sess;
+    let emits = |kind| sess.options.emit.contains(&kind);
+    if args.command == Command::Ast || emits(EmitKind::Ast) {
+        print!("{}", PackageASTPrettyPrinter::pretty_print(&package));
+    }
+    if emits(EmitKind::AstJson) {
+        print_ast_json(sess, &package);
+    }
+    if emits(EmitKind::ItemGraph) {
+        print!("{}", DotPrinter::item_graph(&package));
+    }
+    if let Some(path) = args.graph_fn.as_ref().filter(|_| emits(EmitKind::ExprGraph)) {
+        match find_fn(&package.items, path, &package) {
+            Some((item, function)) => print!("{}", DotPrinter::expr_graph(&package, item, function)),
+            None => {
+                eprintln!("error: no function `{}` to draw", path);
+                return Ok(ExitCode::FAILURE)
+            }
+        }
+    }
+    if args.command == Command::Ast {
+        return finish(sess)
+    }
+    let mut pipeline = ASTMutPipeline::new()
+        .with_pass(InjectPrelude)
+        .with_pass(DesugarLoops);
+    if !pipeline.run(&mut package, sess) {
+        return Err(())
+    }
+
+    let mut ctx = QueryContext::for_package(sess, &package);
+    let mut optimize = OptimizeMir::new();
+    for name in MirPipeline::for_level(sess.options.opt_level).pass_names() {
+        if args.dump_after.iter().any(|dump| dump == name) {
+            optimize.add_dump(name, move |mir, cx| dump_mir(name, mir, cx));
+        }
+    }
+    let mut passes = PassManager::new()
+        .with_pass(NameResolvePass::new())
+        .with_pass(LowerToHir)
+        .with_pass(TypeCheckPass)
+        .with_pass(CapturePass)
+        .with_pass(PassingModePass)
+        .with_pass(DeadCodePass)
+        .with_pass(MonoCollectPass)
+        .with_pass(BuildMir)
+        .with_pass(optimize);
+    for name in passes.pass_names() {
+        if args.dump_after.iter().any(|dump| dump == name) {
+            passes.add_dump(name, move |cx| dump_pass(name, cx));
+        }
+    }
+    passes.run(&mut ctx);
+    if args.time_passes {
+        for timing in passes.timings() {
+            eprintln!("time: {:>10.3?} {}", timing.duration, timing.name);
+        }
+    }
+    for kind in sess.options.emit.iter() {
+        match kind {
+            EmitKind::ResolvedNames => print!("{}", ResolvedNamesDisplay::new(&ctx)),
+            EmitKind::ResolvedSource => print!("{}", ResolvedSourceDisplay::new(&ctx)),
+            EmitKind::Scopes => print!("{}", ScopesDisplay::new(&ctx)),
+            EmitKind::Types => print!("{}", TypesDisplay::new(&ctx)),
+            EmitKind::ModuleGraph => print!("{}", ModuleGraphDisplay::new(&ctx)),
+            _ => {}
+        }
+    }
+    if sess.has_errors() || args.command == Command::Check {
+        return finish(sess)
+    }
+    print_diagnostics(sess);
+    Ok(generate_code(&ctx, args))
+}
+
+/// Format all files of the package in place, or with `--check` only
+/// list the files which aren't formatted, failing if there are any.
+fn format_package(sess: &mut Session, root: SourceFileID, check: bool) -> ExitCode {
+    // Files of modules are found by loading the package
+    if loader::load_package(sess, root, &ParserOptions::default()).is_none() {
+        print_diagnostics(sess);
+        return ExitCode::FAILURE
+    }
+    let mut failed = false;
+    for file in sess.source_map.files() {
+        let text = match format_file(sess, file) {
+            Ok(text) => text,
+            Err(FormatError::Syntax) => {
+                failed = true;
+                continue
+            },
+            Err(err) => {
+                eprintln!("error: can't format `{}`: {}", file.name, err);
+                failed = true;
+                continue
+            }
+        };
+        if file.src.as_ref().is_some_and(|src| src.as_str() == text) {
+            continue
+        }
+        if check {
+            println!("{}", file.name);
+            failed = true;
+            continue
+        }
+        let written = match sess.source_map.full_path(&file.name) {
+            Some(path) => std::fs::write(&path, text).map_err(|err| format!("can't write `{}`: {}", path.display(), err)),
+            None => Err(format!("`{}` is not a file on disk", file.name))
+        };
+        if let Err(message) = written {
+            eprintln!("error: {}", message);
+            failed = true;
+        }
+    }
+    print_diagnostics(sess);
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+/// Print warnings, Err if there were errors as well.
+fn finish(sess: &Session) -> Result<ExitCode, ()> {
+    if sess.has_errors() {
+        return Err(())
+    }
+    print_diagnostics(sess);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print tokens one per line with their kind, text and where they are
+/// in the file, like `Ident "main" 1:4-1:8`.
+fn print_tokens(tokens: &TokenStream, source: &SourceFile) {
+    for token in tokens.iter() {
+        let (start_line, start_col) = source.lookup_line(token.span.start);
+        let (end_line, end_col) = source.lookup_line(token.span.end);
+        println!(
+            "{:?} {:?} {}:{}-{}:{}",
+            token.kind,
+            source.get_span(&token.span),
+            start_line,
+            start_col,
+            end_line,
+            end_col
+        );
+    }
+}
+
+/// Print the syntax tree as JSON, together with names of the files
+/// that `source` of spans refers to.
+fn print_ast_json(sess: &Session, package: &Package) {
+    let files = sess.source_map.files().iter()
+        .map(|file| serde_json::json!({ "id": file.id, "name": file.name.to_string() }))
+        .collect::<Vec<_>>();
+    let ast = serde_json::json!({ "files": files, "package": package });
+    println!("{}", ast);
+}
+
+fn dump_pass(name: &str, cx: &QueryContext) {
+    println!("After {}:", name);
+    match name {
+        "name-resolve" => println!("{}", ResolvedNamesDisplay::new(cx)),
+        "type-check" => println!("{}", TypesDisplay::new(cx)),
+        "closure-captures" => println!("{:#?}", cx.closure_envs),
+        "passing-modes" => println!("{:#?}", cx.passing_modes),
+        "mono-collect" => println!("{:#?}", cx.mono_items),
+        "build-mir" | "optimize-mir" => match (&cx.mir, &cx.hir) {
+            (Some(mir), Some(hir)) => println!("{}", MirDisplay::new(mir, hir, &cx.package.symbol_storage)),
+            _ => println!("No MIR, the package has errors.")
+        },
+        _ => println!("Nothing to dump.")
+    }
+}
+
+/// Print MIR that the pass of the optimization pipeline left.
+fn dump_mir(name: &str, mir: &Mir, cx: &QueryContext) {
+    println!("After {}:", name);
+    if let Some(ref hir) = cx.hir {
+        println!("{}", MirDisplay::new(mir, hir, &cx.package.symbol_storage));
+    }
+}
+
+/// Generate code with the backend from options of the session. `run`
+/// runs the bytecode right away, `build` writes the output next to the
+/// source, or into the package directory named after it, unless `--out`
+/// says where.
+fn generate_code(cx: &QueryContext, args: &Args) -> ExitCode {
+    let (Some(mir), Some(hir)) = (&cx.mir, &cx.hir) else { return ExitCode::FAILURE };
+    let symbols = &cx.package.symbol_storage;
+    let input = CodegenInput { mir, hir, symbols, sources: &cx.sess.source_map, mono_items: &cx.mono_items };
+    let artifact = match codegen(cx.sess.options.backend, input) {
+        Ok(artifact) => artifact,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::FAILURE
+        }
+    };
+    let emit_object = cx.sess.options.emit.contains(&EmitKind::Object);
+    let out = |extension: Option<&str>| args.out.clone().unwrap_or_else(|| {
+        let path = output_stem(&args.path);
+        match extension {
+            Some(extension) => path.with_extension(extension),
+            None => path
+        }
+    });
+    let written = match artifact {
+        Artifact::Bytecode(program) if args.command == Command::Run => {
+            let main = main_fn(hir).and_then(|main| program.fn_index(main));
+            return run_main(&program, main, symbols)
+        },
+        Artifact::Bytecode(program) => {
+            let path = out(Some(package::EXTENSION));
+            CompiledPackage::new(program, hir).save(&path)
+                .map_err(|err| format!("can't save package `{}`: {}", path.display(), err))
+        },
+        Artifact::Source(source) => {
+            let path = out(Some("c"));
+            std::fs::write(&path, source).map_err(|err| format!("can't write `{}`: {}", path.display(), err))
+        },
+        Artifact::Object(bytes) if emit_object => {
+            let path = out(Some("o"));
+            std::fs::write(&path, bytes).map_err(|err| format!("can't write `{}`: {}", path.display(), err))
+        },
+        Artifact::Object(bytes) => link_executable(&bytes, &out(None))
+    };
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Path of the output without extension, `pkg/pkg` for package directories.
+fn output_stem(path: &Path) -> PathBuf {
+    if !path.is_dir() {
+        return path.with_extension("")
+    }
+    let name = path.canonicalize().ok()
+        .and_then(|path| path.file_name().map(ToOwned::to_owned))
+        .unwrap_or_else(|| "main".into());
+    path.join(name)
+}
+
+/// Link the object file with the runtime into an executable at the path.
+fn link_executable(object: &[u8], path: &Path) -> Result<(), String> {
+    let linked = Linker::find().and_then(|linker| linker.link(object, path));
+    linked.map_err(|err| {
+        let mut message = format!("can't link `{}`: {}", path.display(), err);
+        if matches!(err, LinkError::NotFound { .. }) {
+            message.push_str("\nnote: pass `--emit=object` to write the object file without linking it");
+        }
+        message
+    })
+}
+
+/// Run `main` function of the saved package.
+fn run_package(path: &Path) -> ExitCode {
+    match CompiledPackage::load(path) {
+        Ok(package) => run_main(&package.program, package.main, &package.symbols),
+        Err(err) => {
+            eprintln!("error: can't load package `{}`: {}", path.display(), err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run `main` function of the package, exiting with what it returned
+/// if it returned an integer.
+fn run_main(program: &Program, main: Option<FnIndex>, symbols: &SymbolStorage) -> ExitCode {
+    let Some(main) = main else {
+        eprintln!("error: no `main` function to run");
+        return ExitCode::FAILURE
+    };
+    let mut vm = Vm::new(program, symbols, std::io::stdout());
+    match vm.call(main, Vec::new()) {
+        Ok(Value::Prim(constant)) => match constant.kind {
+            ConstKind::Int(value) => ExitCode::from(value as u8),
+            _ => ExitCode::SUCCESS
+        },
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            print_backtrace(&vm.backtrace());
+            ExitCode::from(RUNTIME_ERROR)
+        }
+    }
+}
+
+/// Print the innermost frames, a run of the same call repeated like in
+/// a recursion that overflowed the stack is printed once.
+fn print_backtrace(frames: &[BacktraceFrame]) {
+    eprintln!("backtrace:");
+    let mut idx = 0;
+    for (printed, run) in frames.chunk_by(|a, b| a == b).enumerate() {
+        if printed == MAX_BACKTRACE_RUNS {
+            eprintln!("      ... {} more frames", frames.len() - idx);
+            return
+        }
+        eprintln!("{:>4}: {}", idx, run[0]);
+        if run.len() > 1 {
+            eprintln!("      ... {} more frames of `{}`", run.len() - 1, run[0].function.as_str());
+        }
+        idx += run.len();
+    }
+}
+
+fn print_diagnostics(sess: &Session) {
+    // Root file is loaded first
+    let Some(root) = sess.source_map.files().first() else { return };
+    let options = &sess.options.diagnostics;
+    for diag in sess.diagnostics().diagnostics() {
+        let ctx = CommonErrorContext::for_diagnostic(diag.as_ref(), &sess.source_map, root);
+        let text = match options.format {
+            DiagnosticFormat::Human => diag.fmt_error_with(&ctx, &options.render),
+            DiagnosticFormat::Json => diag.fmt_json(&ctx),
+            DiagnosticFormat::Plain => diag.fmt_plain(&ctx)
+        };
+        eprintln!("{}", text);
+    }
+}